@@ -0,0 +1,49 @@
+use std::path::Path;
+use std::process::Command;
+
+// Recompiles the GLSL sources in `shaders/` to SPIR-V when `glslc` (shipped
+// with the Vulkan SDK) is available, otherwise keeps the prebuilt `.spv` files.
+fn main() {
+    println!("cargo:rerun-if-changed=shaders");
+
+    let entries = match std::fs::read_dir("shaders") {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_shader = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("vert" | "tesc" | "tese" | "frag" | "comp")
+        );
+        if !is_shader {
+            continue;
+        }
+
+        println!("cargo:rerun-if-changed={}", path.display());
+        compile(&path);
+    }
+}
+
+fn compile(path: &Path) {
+    let output = format!("{}.spv", path.display());
+
+    // Ray queries need SPIR-V 1.4, which the default Vulkan 1.0 target lacks.
+    let mut command = Command::new("glslc");
+    let source = std::fs::read_to_string(path).unwrap_or_default();
+    if source.contains("GL_EXT_ray_query") {
+        command.arg("--target-env=vulkan1.2");
+    }
+
+    match command
+        .arg(path)
+        .arg("-o")
+        .arg(&output)
+        .status()
+    {
+        Ok(status) if status.success() => {}
+        Ok(_) => panic!("Failed to compile shader {}", path.display()),
+        Err(_) => println!("cargo:warning=glslc not found, using prebuilt {}", output),
+    }
+}
@@ -0,0 +1,8 @@
+#version 450
+
+// Occlusion probes only count samples; color writes are masked off.
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(1.0);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(push_constant) uniform constants {
+    mat4 model;
+} pcs;
+
+// Corners of the unit cube, indexed by bits x, y, z.
+const uint FACES[36] = uint[](
+    0, 2, 1, 1, 2, 3,
+    4, 5, 6, 5, 7, 6,
+    0, 1, 4, 1, 5, 4,
+    2, 6, 3, 3, 6, 7,
+    0, 4, 2, 2, 4, 6,
+    1, 3, 5, 3, 7, 5
+);
+
+void main() {
+    uint corner = FACES[gl_VertexIndex];
+    vec3 position = vec3(corner & 1u, (corner >> 1) & 1u, (corner >> 2) & 1u);
+    gl_Position = ubo.proj * ubo.view * pcs.model * vec4(position, 1.0);
+}
@@ -0,0 +1,58 @@
+#version 450
+
+// shader.vert for meshes of CompactVertex: positions in the mesh's bounds,
+// mapped back by the model transform, and octahedral normals.
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    vec4 lightPosition;
+    vec4 lightColor;
+    uint shadingMode;
+} ubo;
+
+// Set by the toon outline pipelines: pushes vertices out along their normal
+// to draw the inverted hull.
+layout(constant_id = 0) const bool OUTLINE = false;
+const float OUTLINE_WIDTH = 0.004;
+
+layout(push_constant) uniform constants {
+    mat4 model;
+} pcs;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec2 inNormal;
+layout(location = 2) in vec2 inUv;
+layout(location = 5) in float inOcclusion;
+layout(location = 6) in vec2 inLightmapUv;
+
+layout(location = 0) out vec3 fragPosition;
+layout(location = 1) out vec3 fragNormal;
+layout(location = 2) out vec2 fragUv;
+layout(location = 3) out float fragOcclusion;
+layout(location = 4) out vec2 fragLightmapUv;
+
+// Unfolds a direction stored on the unit square by its octahedral mapping.
+vec3 octahedralDecode(vec2 e) {
+    vec3 n = vec3(e, 1.0 - abs(e.x) - abs(e.y));
+    float t = max(-n.z, 0.0);
+    n.x += n.x >= 0.0 ? -t : t;
+    n.y += n.y >= 0.0 ? -t : t;
+    return n;
+}
+
+void main() {
+    mat4 modelView = ubo.view * pcs.model;
+    // Normalized once transformed, the stored normals being scaled by the
+    // mesh's bounds.
+    fragNormal = normalize(mat3(transpose(inverse(modelView))) * octahedralDecode(inNormal));
+    fragPosition = vec3(modelView * vec4(inPosition, 1.0));
+    if (OUTLINE) {
+        // Scaled by depth so the outline keeps roughly the same screen width.
+        fragPosition += fragNormal * OUTLINE_WIDTH * -fragPosition.z;
+    }
+    gl_Position = ubo.proj * vec4(fragPosition, 1.0);
+    fragUv = inUv;
+    fragOcclusion = inOcclusion;
+    fragLightmapUv = inLightmapUv;
+}
@@ -0,0 +1,68 @@
+#version 450
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    vec4 lightPosition;
+    vec4 lightColor;
+    uint shadingMode;
+} ubo;
+
+layout(push_constant) uniform constants {
+    vec2 screenSize;
+    // Line width, in pixels.
+    float width;
+} pcs;
+
+// One segment per instance, expanded into a screen-space quad: wide lines
+// are an optional feature, and often capped at a single pixel.
+layout(location = 0) in vec3 inStart;
+layout(location = 1) in vec3 inStartColor;
+layout(location = 2) in vec3 inEnd;
+layout(location = 3) in vec3 inEndColor;
+
+layout(location = 0) out vec3 fragColor;
+
+// Segment end and side of each quad corner, as two triangles.
+const vec2 CORNERS[6] = vec2[](
+    vec2(0.0, -1.0), vec2(0.0, 1.0), vec2(1.0, 1.0),
+    vec2(0.0, -1.0), vec2(1.0, 1.0), vec2(1.0, -1.0)
+);
+
+void main() {
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec3 start = vec3(ubo.view * vec4(inStart, 1.0));
+    vec3 end = vec3(ubo.view * vec4(inEnd, 1.0));
+
+    // Clipped to the near plane first, where the projection would flip the
+    // segment's far side around. Orthographic projections map it to depth 0.
+    float near = ubo.proj[2][3] == 0.0
+        ? ubo.proj[3][2] / ubo.proj[2][2]
+        : ubo.proj[3][2] / (ubo.proj[2][2] - 1.0);
+    if (start.z > -near && end.z > -near) {
+        gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+        fragColor = vec3(0.0);
+        return;
+    }
+    if (start.z > -near) {
+        start = mix(start, end, (start.z + near) / (start.z - end.z));
+    } else if (end.z > -near) {
+        end = mix(end, start, (end.z + near) / (end.z - start.z));
+    }
+
+    vec4 clipStart = ubo.proj * vec4(start, 1.0);
+    vec4 clipEnd = ubo.proj * vec4(end, 1.0);
+    vec2 screenStart = clipStart.xy / clipStart.w * pcs.screenSize;
+    vec2 screenEnd = clipEnd.xy / clipEnd.w * pcs.screenSize;
+    vec2 delta = screenEnd - screenStart;
+    vec2 direction = length(delta) > 1e-6 ? normalize(delta) : vec2(1.0, 0.0);
+
+    // Half the width on each side, and past each end so segments join.
+    vec2 offset = vec2(-direction.y, direction.x) * corner.y
+        + direction * (corner.x * 2.0 - 1.0);
+    offset *= pcs.width / pcs.screenSize;
+
+    vec4 clip = corner.x == 0.0 ? clipStart : clipEnd;
+    gl_Position = vec4(clip.xy + offset * clip.w, clip.zw);
+    fragColor = corner.x == 0.0 ? inStartColor : inEndColor;
+}
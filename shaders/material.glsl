@@ -0,0 +1,397 @@
+// Material shading shared by the fragment shader variants.
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    vec4 lightPosition;
+    vec4 lightColor;
+    uint shadingMode;
+    uint matcap;
+    float displacement;
+    // Display adjustments, applied once the surface is shaded.
+    float exposure;
+    float gamma;
+    float brightness;
+    float contrast;
+    // Ranges of the analysis shading modes.
+    float curvatureRadius;
+    vec4 analysisAxis;
+    float deviationAngle;
+    // Angle the light's disk spans, in radians, and the taps filtering
+    // the shadow map cascades: 1 for a fixed 3x3 grid, more for soft
+    // shadows.
+    float lightSize;
+    uint shadowSamples;
+    // Shadow map cascades, none when 0: each one's world to clip transform
+    // and the view depth it ends at.
+    uint cascadeCount;
+    mat4 cascadeViewProj[4];
+    vec4 cascadeSplits;
+} ubo;
+
+layout(set = 1, binding = 0) uniform sampler2D tex[512];
+layout(set = 0, binding = 3) uniform samplerCube envMap;
+// The cascades side by side, left to right.
+layout(set = 0, binding = 16) uniform sampler2DShadow cascadeMap;
+// The same, read as depth for the occluder search of soft shadows.
+layout(set = 0, binding = 17) uniform sampler2D cascadeDepth;
+
+#ifdef RAY_QUERY_SHADOWS
+layout(set = 0, binding = 10) uniform accelerationStructureEXT sceneStructure;
+#endif
+
+layout(location = 0) in vec3 fragPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec2 fragUv;
+// Baked ambient visibility, 1 unless the mesh was baked.
+layout(location = 3) in float fragOcclusion;
+layout(location = 4) in vec2 fragLightmapUv;
+
+layout(push_constant) uniform GpuMaterial {
+    layout(offset = 64)
+    vec3 ambient;
+    float dissolve;
+    vec3 diffuse;
+    float shininess;
+    vec3 specular;
+    uint tex_lightmap;
+    int illum;
+    // Diffuse texture in the low 16 bits, cutout mask in the high ones.
+    uint tex_diffuse;
+    // Ambient texture in the low 16 bits, height map in the high ones.
+    uint tex_ambient;
+    // Specular texture in the low 16 bits, flags in the high ones.
+    uint tex_specular;
+} mat;
+
+layout(location = 0) out vec4 outColor;
+
+const uint SHADING_LIT = 0;
+const uint SHADING_FLAT = 1;
+const uint SHADING_UNLIT = 2;
+const uint SHADING_TOON = 3;
+const uint SHADING_MATCAP = 4;
+const uint SHADING_UV_CHECKER = 5;
+const uint SHADING_NORMALS = 6;
+const uint SHADING_DEPTH = 7;
+const uint SHADING_FACE_ORIENTATION = 8;
+const uint SHADING_BAKED = 10;
+const uint SHADING_CURVATURE = 11;
+const uint SHADING_NORMAL_DEVIATION = 12;
+const uint SHADING_CASCADES = 13;
+
+const float CHECKER_TILES = 8.0;
+
+const float TOON_BANDS = 3.0;
+
+// Lit on both sides: back faces are shaded with their normal flipped.
+const uint MATERIAL_TWO_SIDED = 1u << 16;
+
+// Mask value under which `map_d` cutouts discard the fragment.
+const float ALPHA_CUTOFF = 0.5;
+
+// Offset of shadow rays off the surface, per unit of distance to the camera.
+const float SHADOW_BIAS = 1e-3;
+
+// Angle between successive shadow map taps on the filter's disk, spreading
+// any number of them evenly.
+const float GOLDEN_ANGLE = 2.39996323;
+
+// Offset of shadow map lookups along the normal, in texels of the cascade.
+const float CASCADE_NORMAL_BIAS = 1.5;
+
+// Widest radius soft shadows search for occluders and filter over, in texels
+// of the cascade.
+const float PENUMBRA_MAX_RADIUS = 24.0;
+
+// Tint of each cascade in the cascades view.
+const vec3 CASCADE_COLORS[4] = vec3[](
+    vec3(0.9, 0.2, 0.2),
+    vec3(0.2, 0.8, 0.2),
+    vec3(0.2, 0.4, 0.9),
+    vec3(0.9, 0.8, 0.2)
+);
+
+// Blue, cyan, green, yellow, red as t goes from 0 to 1.
+vec3 heat(float t) {
+    return clamp(vec3(4.0 * t - 2.0, 2.0 - abs(4.0 * t - 2.0), 2.0 - 4.0 * t), 0.0, 1.0);
+}
+
+#ifdef RAY_QUERY_SHADOWS
+// 1 when nothing lies on the segment from `origin` to `origin + toLight`.
+float traceShadow(vec3 origin, vec3 toLight) {
+    float distance = length(toLight);
+    rayQueryEXT query;
+    rayQueryInitializeEXT(query, sceneStructure,
+        gl_RayFlagsTerminateOnFirstHitEXT | gl_RayFlagsOpaqueEXT, 0xFF,
+        origin, 0.0, toLight / distance, distance);
+    while (rayQueryProceedEXT(query)) {}
+    return rayQueryGetIntersectionTypeEXT(query, true) == gl_RayQueryCommittedIntersectionNoneEXT
+        ? 1.0 : 0.0;
+}
+#endif
+
+// Cascade covering the fragment, the first one ending past its view depth,
+// or cascadeCount beyond the last.
+uint cascadeIndex() {
+    float depth = -fragPosition.z;
+    uint cascade = 0u;
+    while (cascade < ubo.cascadeCount && depth > ubo.cascadeSplits[cascade]) {
+        cascade++;
+    }
+    return cascade;
+}
+
+// Point `index` of `count` on a spiral filling the unit disk, turned by
+// `turn` radians.
+vec2 diskTap(uint index, uint count, float turn) {
+    float r = sqrt((float(index) + 0.5) / float(count));
+    float angle = float(index) * GOLDEN_ANGLE + turn;
+    return vec2(cos(angle), sin(angle)) * r;
+}
+
+// Share of the shadow map taps around the world `position` that see the
+// light, in cascade `cascade`. Hard shadows take a 3x3 grid. Soft ones are
+// percentage-closer soft shadows: the occluders found around the fragment
+// give their average depth, and the taps then spread over a disk as wide as
+// the penumbra the light's disk casts from that far, so shadows stay sharp
+// where the occluder touches the surface and widen away from it.
+float cascadeVisibility(uint cascade, vec3 position, vec3 normal) {
+    mat4 viewProj = ubo.cascadeViewProj[cascade];
+    vec2 size = vec2(textureSize(cascadeMap, 0));
+    // World size of a texel, from the projection's horizontal scale.
+    float texel = 2.0 / (length(vec3(viewProj[0][0], viewProj[1][0], viewProj[2][0])) * size.y);
+    vec4 clip = viewProj * vec4(position + normal * texel * CASCADE_NORMAL_BIAS, 1.0);
+    vec3 ndc = clip.xyz / clip.w;
+    if (any(greaterThan(abs(ndc.xy), vec2(1.0)))) {
+        return 1.0;
+    }
+    vec2 uv = vec2((float(cascade) + ndc.x * 0.5 + 0.5) / float(ubo.cascadeCount), ndc.y * 0.5 + 0.5);
+    if (ubo.shadowSamples <= 1u) {
+        float lit = 0.0;
+        for (int x = -1; x <= 1; x++) {
+            for (int y = -1; y <= 1; y++) {
+                lit += texture(cascadeMap, vec3(uv + vec2(x, y) / size, ndc.z));
+            }
+        }
+        return lit / 9.0;
+    }
+
+    // Wide taps stay in the cascade's slice of the atlas.
+    float sliceWidth = 1.0 / float(ubo.cascadeCount);
+    vec2 low = vec2(float(cascade) * sliceWidth, 0.0) + 0.5 / size;
+    vec2 high = vec2(float(cascade + 1u) * sliceWidth, 1.0) - 0.5 / size;
+    // Penumbra radius in texels per unit of depth between occluder and
+    // receiver, the projection mapping its depth range to 0..1.
+    float depthRange = 1.0 / length(vec3(viewProj[0][2], viewProj[1][2], viewProj[2][2]));
+    float spread = tan(ubo.lightSize * 0.5) * depthRange / texel;
+    // Taps turned by a per-pixel angle, trading the banding of few taps for
+    // noise.
+    float noise = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    float turn = 6.28318531 * noise;
+
+    float searchRadius = clamp(ndc.z * spread, 1.0, PENUMBRA_MAX_RADIUS);
+    float occluders = 0.0;
+    float occluderDepth = 0.0;
+    for (uint i = 0u; i < ubo.shadowSamples; i++) {
+        vec2 tap = clamp(uv + diskTap(i, ubo.shadowSamples, turn) * searchRadius / size, low, high);
+        float depth = texture(cascadeDepth, tap).r;
+        if (depth < ndc.z) {
+            occluders += 1.0;
+            occluderDepth += depth;
+        }
+    }
+    if (occluders == 0.0) {
+        return 1.0;
+    }
+
+    float radius = clamp((ndc.z - occluderDepth / occluders) * spread, 1.0, PENUMBRA_MAX_RADIUS);
+    float lit = 0.0;
+    for (uint i = 0u; i < ubo.shadowSamples; i++) {
+        vec2 tap = clamp(uv + diskTap(i, ubo.shadowSamples, turn) * radius / size, low, high);
+        lit += texture(cascadeMap, vec3(tap, ndc.z));
+    }
+    return lit / float(ubo.shadowSamples);
+}
+
+// Share of the light seen from the fragment. With cascades, the share of the
+// filtered shadow map taps lit, fragments past the last one all lit.
+// Otherwise 1 when no shadow ray hits anything between them and 0 otherwise;
+// without ray queries every fragment is lit.
+float lightVisibility(vec3 norm) {
+    mat3 toWorld = transpose(mat3(ubo.view));
+    vec3 position = toWorld * (fragPosition - ubo.view[3].xyz);
+    vec3 normal = toWorld * norm;
+    if (ubo.cascadeCount > 0u) {
+        uint cascade = cascadeIndex();
+        return cascade < ubo.cascadeCount ? cascadeVisibility(cascade, position, normal) : 1.0;
+    }
+
+#ifdef RAY_QUERY_SHADOWS
+    vec3 origin = position + normal * SHADOW_BIAS * max(length(fragPosition), 1.0);
+    return traceShadow(origin, ubo.lightPosition.xyz - origin);
+#else
+    return 1.0;
+#endif
+}
+
+void shade() {
+    // Alpha testing: no sorting needed, at the cost of hard cutout edges.
+    uint tex_mask = mat.tex_diffuse >> 16;
+    if (tex_mask != 0 && texture(tex[tex_mask], fragUv).a < ALPHA_CUTOFF) {
+        discard;
+    }
+
+    vec3 lightPos = vec3(ubo.view * vec4(ubo.lightPosition.xyz, 1.0));
+    vec3 lightColor = ubo.lightColor.rgb;
+    vec3 norm = normalize(fragNormal);
+    if ((mat.tex_specular & MATERIAL_TWO_SIDED) != 0 && !gl_FrontFacing) {
+        norm = -norm;
+    }
+    if (ubo.shadingMode == SHADING_FLAT) {
+        norm = normalize(cross(dFdx(fragPosition), dFdy(fragPosition)));
+        if (dot(norm, -fragPosition) < 0.0) {
+            norm = -norm;
+        }
+    }
+    vec3 lightDir = normalize(lightPos - fragPosition);
+
+    vec3 ambient_color  = texture(tex[mat.tex_ambient & 0xFFFF], fragUv).rgb * mat.ambient * fragOcclusion;
+    vec3 diffuse_color  = texture(tex[mat.tex_diffuse & 0xFFFF], fragUv).rgb * mat.diffuse;
+    vec3 specular_color = texture(tex[mat.tex_specular & 0xFFFF], fragUv).rgb * mat.specular;
+
+    if (ubo.shadingMode == SHADING_UNLIT) {
+        outColor = vec4(diffuse_color, mat.dissolve);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_BAKED) {
+        vec3 light = texture(tex[mat.tex_lightmap], fragLightmapUv).rgb;
+        outColor = vec4(diffuse_color * light, mat.dissolve);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_UV_CHECKER) {
+        // Tinted by the UV itself so flipped or rotated islands stand out.
+        vec2 cell = floor(fragUv * CHECKER_TILES);
+        float check = mod(cell.x + cell.y, 2.0);
+        vec3 tint = vec3(0.5 + 0.5 * fract(fragUv), 0.75);
+        outColor = vec4(mix(vec3(0.2), vec3(1.0), check) * tint, 1.0);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_NORMALS) {
+        vec3 worldNormal = transpose(mat3(ubo.view)) * norm;
+        outColor = vec4(worldNormal * 0.5 + 0.5, 1.0);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_DEPTH) {
+        // Logarithmic between the clip planes, recovered from the projection.
+        float near = ubo.proj[3][2] / (ubo.proj[2][2] - 1.0);
+        float far = ubo.proj[3][2] / (ubo.proj[2][2] + 1.0);
+        float depth = log(max(-fragPosition.z, near) / near) / log(far / near);
+        outColor = vec4(vec3(1.0 - clamp(depth, 0.0, 1.0)), 1.0);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_FACE_ORIENTATION) {
+        outColor = gl_FrontFacing ? vec4(0.1, 0.3, 0.9, 1.0) : vec4(0.9, 0.15, 0.1, 1.0);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_CURVATURE) {
+        // Mean curvature of the interpolated normals, from how fast they turn
+        // along the screen axes: positive where they spread apart.
+        vec3 dx = dFdx(fragPosition);
+        vec3 dy = dFdy(fragPosition);
+        float curvature = 0.5 * (dot(dFdx(norm), dx) / max(dot(dx, dx), 1e-12)
+            + dot(dFdy(norm), dy) / max(dot(dy, dy), 1e-12));
+        float t = clamp(curvature * ubo.curvatureRadius, -1.0, 1.0);
+        vec3 color = t > 0.0
+            ? mix(vec3(1.0), vec3(0.9, 0.15, 0.1), t)
+            : mix(vec3(1.0), vec3(0.1, 0.3, 0.9), -t);
+        // Lit from the camera so the shape still reads.
+        float facing = abs(dot(norm, normalize(-fragPosition)));
+        outColor = vec4(color * (0.4 + 0.6 * facing), 1.0);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_NORMAL_DEVIATION) {
+        // Faces pointing down the axis count as aligned, like the bottom of
+        // a plate.
+        vec3 worldNormal = transpose(mat3(ubo.view)) * norm;
+        float cosine = abs(dot(worldNormal, ubo.analysisAxis.xyz));
+        float angle = acos(clamp(cosine, 0.0, 1.0));
+        outColor = vec4(heat(clamp(angle / ubo.deviationAngle, 0.0, 1.0)), 1.0);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_CASCADES) {
+        // Lit diffuse tinted by cascade, gray beyond the last one or with
+        // the cascades off.
+        uint cascade = cascadeIndex();
+        vec3 tint = cascade < ubo.cascadeCount ? CASCADE_COLORS[cascade] : vec3(0.5);
+        float diff = max(dot(norm, lightDir), 0.0);
+        float visibility = diff > 0.0 ? lightVisibility(norm) : 0.0;
+        outColor = vec4(tint * (0.3 + 0.7 * diff * visibility), 1.0);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_MATCAP) {
+        // Build the lookup basis around the view direction so the matcap
+        // doesn't slide towards the edges of a perspective view.
+        vec3 viewDir = normalize(-fragPosition);
+        vec3 x = normalize(vec3(viewDir.z, 0.0, -viewDir.x));
+        vec3 y = cross(viewDir, x);
+        vec2 uv = vec2(dot(x, norm), -dot(y, norm)) * 0.495 + 0.5;
+        outColor = vec4(texture(tex[ubo.matcap], uv).rgb * fragOcclusion, mat.dissolve);
+        return;
+    }
+
+    if (ubo.shadingMode == SHADING_TOON) {
+        // Diffuse snapped to a few flat bands, with a hard-edged highlight.
+        float diff = max(dot(norm, lightDir), 0.0);
+        float visibility = diff > 0.0 ? lightVisibility(norm) : 0.0;
+        float band = ceil(diff * TOON_BANDS) / TOON_BANDS * visibility;
+        vec3 halfDir = normalize(lightDir + normalize(-fragPosition));
+        float spec = pow(max(dot(norm, halfDir), 0.0), max(mat.shininess, 1.0)) * visibility;
+        vec3 toon = ambient_color + band * diffuse_color * lightColor;
+        toon += step(0.5, spec) * specular_color * lightColor;
+        outColor = vec4(toon, mat.dissolve);
+        return;
+    }
+
+    vec3 color = ambient_color;
+
+    float diff = max(dot(norm, lightDir), 0.0);
+    float visibility = diff > 0.0 && mat.illum >= 1 ? lightVisibility(norm) : 0.0;
+
+    if (mat.illum >= 1) {
+        color += diff * visibility * diffuse_color * lightColor * mat.dissolve;
+    }
+
+    if (mat.illum >= 2) {
+        vec3 viewDir = normalize(-fragPosition);
+        vec3 reflectDir = reflect(-lightDir, norm);
+        float spec = pow(max(dot(viewDir, reflectDir), 0.0), max(mat.shininess, 1.0));
+        color += spec * visibility * specular_color * lightColor;
+
+        // Shiny materials mirror the environment; lower exponents read a
+        // blurrier mip and reflect less.
+        float roughness = sqrt(2.0 / (max(mat.shininess, 1.0) + 2.0));
+        vec3 worldDir = transpose(mat3(ubo.view)) * reflect(-viewDir, norm);
+        float lod = roughness * float(textureQueryLevels(envMap) - 1);
+        vec3 env = textureLod(envMap, worldDir, lod).rgb * fragOcclusion;
+        color += env * specular_color * (1.0 - roughness) * (1.0 - roughness);
+    }
+
+    outColor = vec4(color, mat.dissolve);
+}
+void main() {
+    shade();
+
+    vec3 rgb = outColor.rgb * exp2(ubo.exposure);
+    rgb = (rgb - 0.5) * ubo.contrast + 0.5 + ubo.brightness;
+    outColor.rgb = pow(clamp(rgb, 0.0, 1.0), vec3(1.0 / ubo.gamma));
+}
@@ -0,0 +1,16 @@
+#version 450
+
+// Same layout as the material block of shader.frag; only the solid color is
+// read.
+layout(push_constant) uniform GpuMaterial {
+    layout(offset = 64)
+    vec3 ambient;
+    float dissolve;
+    vec3 diffuse;
+} mat;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(mat.diffuse, mat.dissolve);
+}
@@ -0,0 +1,24 @@
+#version 450
+
+layout(set = 0, binding = 4, r32ui) uniform uimage2D overdraw;
+
+layout(location = 0) out vec4 outColor;
+
+const float MAX_COUNT = 65536.0;
+
+// Blue, cyan, green, yellow, red as t goes from 0 to 1.
+vec3 heat(float t) {
+    return clamp(vec3(4.0 * t - 2.0, 2.0 - abs(4.0 * t - 2.0), 2.0 - 4.0 * t), 0.0, 1.0);
+}
+
+void main() {
+    uint count = imageAtomicAdd(overdraw, ivec2(gl_FragCoord.xy), 1u) + 1u;
+
+    // The fragment holding the highest count wins the depth test whatever
+    // order the fragments ran in, so each pixel ends up showing its total.
+    gl_FragDepth = 1.0 - min(float(count), MAX_COUNT - 1.0) / MAX_COUNT;
+
+    // One layer is blue, 16 or more are red.
+    float t = clamp(log2(float(count)) / 4.0, 0.0, 1.0);
+    outColor = vec4(heat(t), 1.0);
+}
@@ -3,8 +3,16 @@
 layout(binding = 0) uniform UniformBufferObject {
     mat4 view;
     mat4 proj;
+    vec4 lightPosition;
+    vec4 lightColor;
+    uint shadingMode;
 } ubo;
 
+// Set by the toon outline pipelines: pushes vertices out along their normal
+// to draw the inverted hull.
+layout(constant_id = 0) const bool OUTLINE = false;
+const float OUTLINE_WIDTH = 0.004;
+
 layout(push_constant) uniform constants {
     mat4 model;
 } pcs;
@@ -12,14 +20,25 @@ layout(push_constant) uniform constants {
 layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec3 inNormal;
 layout(location = 2) in vec2 inUv;
+layout(location = 5) in float inOcclusion;
+layout(location = 6) in vec2 inLightmapUv;
 
 layout(location = 0) out vec3 fragPosition;
 layout(location = 1) out vec3 fragNormal;
 layout(location = 2) out vec2 fragUv;
+layout(location = 3) out float fragOcclusion;
+layout(location = 4) out vec2 fragLightmapUv;
 
 void main() {
-    gl_Position = ubo.proj * ubo.view * pcs.model * vec4(inPosition, 1.0);
-    fragNormal = mat3(transpose(inverse(ubo.view * pcs.model))) * inNormal;
-    fragPosition = vec3(ubo.view * pcs.model * vec4(inPosition, 1.0));
+    mat4 modelView = ubo.view * pcs.model;
+    fragNormal = mat3(transpose(inverse(modelView))) * inNormal;
+    fragPosition = vec3(modelView * vec4(inPosition, 1.0));
+    if (OUTLINE) {
+        // Scaled by depth so the outline keeps roughly the same screen width.
+        fragPosition += normalize(fragNormal) * OUTLINE_WIDTH * -fragPosition.z;
+    }
+    gl_Position = ubo.proj * vec4(fragPosition, 1.0);
     fragUv = inUv;
+    fragOcclusion = inOcclusion;
+    fragLightmapUv = inLightmapUv;
 }
\ No newline at end of file
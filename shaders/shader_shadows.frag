@@ -0,0 +1,8 @@
+#version 460
+#extension GL_EXT_ray_query : require
+#extension GL_GOOGLE_include_directive : require
+
+// Material shading with shadows traced against the scene, for devices
+// supporting ray queries.
+#define RAY_QUERY_SHADOWS
+#include "material.glsl"
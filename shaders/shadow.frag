@@ -0,0 +1,6 @@
+#version 450
+
+// Shadow map cascades only keep the depth; there is no color attachment.
+
+void main() {
+}
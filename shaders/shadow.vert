@@ -0,0 +1,18 @@
+#version 450
+
+// Places vertices in the shadow map cascade picked by the draw's first
+// instance, in its slice of the atlas set by the viewport.
+
+layout(binding = 0) uniform UniformBufferObject {
+    layout(offset = 224) mat4 cascadeViewProj[4];
+} ubo;
+
+layout(push_constant) uniform constants {
+    mat4 model;
+} pcs;
+
+layout(location = 0) in vec3 inPosition;
+
+void main() {
+    gl_Position = ubo.cascadeViewProj[gl_InstanceIndex] * pcs.model * vec4(inPosition, 1.0);
+}
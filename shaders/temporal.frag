@@ -0,0 +1,9 @@
+#version 450
+
+layout(set = 0, binding = 14, rgba16f) uniform readonly image2D resolved;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(imageLoad(resolved, ivec2(gl_FragCoord.xy)).rgb, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(set = 1, binding = 0) uniform sampler2D tex[512];
+
+layout(push_constant) uniform constants {
+    layout(offset = 64)
+    uint atlas;
+} pcs;
+
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) in vec4 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(fragColor.rgb, fragColor.a * texture(tex[pcs.atlas], fragUv).a);
+}
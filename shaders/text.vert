@@ -0,0 +1,18 @@
+#version 450
+
+layout(push_constant) uniform constants {
+    vec2 screenSize;
+} pcs;
+
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inUv;
+layout(location = 2) in vec4 inColor;
+
+layout(location = 0) out vec2 fragUv;
+layout(location = 1) out vec4 fragColor;
+
+void main() {
+    gl_Position = vec4(inPosition / pcs.screenSize * 2.0 - 1.0, 0.0, 1.0);
+    fragUv = inUv;
+    fragColor = inColor;
+}
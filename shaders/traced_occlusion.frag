@@ -0,0 +1,12 @@
+#version 450
+
+layout(set = 0, binding = 6, r32f) uniform readonly image2D occlusion;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    float visibility = imageLoad(occlusion, ivec2(gl_FragCoord.xy)).r;
+
+    // Alpha blending turns this into color * visibility.
+    outColor = vec4(0.0, 0.0, 0.0, 1.0 - visibility);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+// Motion of the surface since the previous frame, in normalized device
+// coordinates, with coverage in blue.
+
+layout(push_constant) uniform constants {
+    layout(offset = 64) mat4 previousModelViewProj;
+} pcs;
+
+layout(location = 0) in vec3 fragLocalPosition;
+layout(location = 1) in vec4 fragClip;
+
+layout(location = 0) out vec4 outVelocity;
+
+void main() {
+    vec4 previous = pcs.previousModelViewProj * vec4(fragLocalPosition, 1.0);
+    vec2 velocity = fragClip.xy / fragClip.w - previous.xy / previous.w;
+    outVelocity = vec4(velocity, 1.0, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+// Places vertices exactly like shader.vert, so the fragments pass the depth
+// test against the depth it left.
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(push_constant) uniform constants {
+    mat4 model;
+} pcs;
+
+layout(location = 0) in vec3 inPosition;
+
+layout(location = 0) out vec3 fragLocalPosition;
+layout(location = 1) out vec4 fragClip;
+
+void main() {
+    mat4 modelView = ubo.view * pcs.model;
+    vec3 position = vec3(modelView * vec4(inPosition, 1.0));
+    gl_Position = ubo.proj * vec4(position, 1.0);
+    fragLocalPosition = inPosition;
+    fragClip = gl_Position;
+}
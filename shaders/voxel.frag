@@ -0,0 +1,24 @@
+#version 450
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    vec4 lightPosition;
+    vec4 lightColor;
+} ubo;
+
+layout(location = 0) in vec3 fragPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec3 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+// Share of the color kept on faces turned away from the light.
+const float AMBIENT = 0.25;
+
+void main() {
+    vec3 toLight = normalize(ubo.lightPosition.xyz - fragPosition);
+    float diffuse = max(dot(normalize(fragNormal), toLight), 0.0);
+    vec3 light = ubo.lightColor.rgb * diffuse;
+    outColor = vec4(fragColor * (AMBIENT + (1.0 - AMBIENT) * light), 1.0);
+}
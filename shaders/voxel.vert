@@ -0,0 +1,45 @@
+#version 450
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+// Maps cell coordinates to world space.
+layout(push_constant) uniform constants {
+    mat4 model;
+} pcs;
+
+// One cube per instance, generated without vertex buffers.
+layout(location = 0) in vec3 inCell;
+layout(location = 1) in vec3 inColor;
+
+layout(location = 0) out vec3 fragPosition;
+layout(location = 1) out vec3 fragNormal;
+layout(location = 2) out vec3 fragColor;
+
+// Corners of the unit cube, indexed by bits x, y, z, as in bounds.vert.
+const uint FACES[36] = uint[](
+    0, 2, 1, 1, 2, 3,
+    4, 5, 6, 5, 7, 6,
+    0, 1, 4, 1, 5, 4,
+    2, 6, 3, 3, 6, 7,
+    0, 4, 2, 2, 4, 6,
+    1, 3, 5, 3, 7, 5
+);
+const vec3 NORMALS[6] = vec3[](
+    vec3(0.0, 0.0, -1.0), vec3(0.0, 0.0, 1.0),
+    vec3(0.0, -1.0, 0.0), vec3(0.0, 1.0, 0.0),
+    vec3(-1.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0)
+);
+
+void main() {
+    uint corner = FACES[gl_VertexIndex];
+    vec3 position = inCell + vec3(corner & 1u, (corner >> 1) & 1u, (corner >> 2) & 1u);
+    vec4 world = pcs.model * vec4(position, 1.0);
+
+    fragPosition = world.xyz;
+    fragNormal = normalize(transpose(inverse(mat3(pcs.model))) * NORMALS[gl_VertexIndex / 6]);
+    fragColor = inColor;
+    gl_Position = ubo.proj * ubo.view * world;
+}
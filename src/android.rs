@@ -0,0 +1,49 @@
+use std::ffi::{c_char, c_int, CStr};
+
+use crate::app::App;
+use crate::cli::Options;
+use crate::crash;
+use crate::scene::Object;
+
+/// Entry point SDL's Android activity calls once the app's library is
+/// loaded, with the arguments of the activity's `getArguments`. The library
+/// is built as a `cdylib` for Android alone, see the README.
+#[export_name = "SDL_main"]
+pub extern "C" fn sdl_main(argc: c_int, argv: *mut *mut c_char) -> c_int {
+    let args = (0..argc.max(0) as usize)
+        .map(|index| unsafe { CStr::from_ptr(*argv.add(index)) })
+        .map(|arg| arg.to_string_lossy().into_owned());
+
+    match run(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Shows the model of the command line, read from the APK's assets, until
+/// the activity finishes. Rendering options are those of the desktop
+/// viewer, minus the offscreen modes.
+fn run(args: impl Iterator<Item = String>) -> Result<(), String> {
+    crash::install();
+    let options = Options::parse(args)?;
+    let mut app = App::new(None, options.validation, options.gpu, false)?;
+
+    let mesh = app.load_mesh_with_splash(&options.model, |_| {})?;
+    app.add_object(Object::new(mesh));
+    app.set_auto_rotation(options.auto_rotation);
+    app.set_line_width(options.line_width);
+    if let Some(scale) = options.temporal {
+        app.set_temporal(Some(scale));
+    }
+
+    while app.handle_events()? {
+        app.update();
+        app.draw();
+    }
+
+    app.shutdown();
+    Ok(())
+}
@@ -1,16 +1,101 @@
 use crate::camera::Camera;
-use crate::math::Vec3;
-use crate::renderer::{Engine, MeshHandle};
-use crate::scene::{Object, ObjectHandle, Scene};
+use crate::crash;
+use crate::gallery::Gallery;
+use crate::gizmo::{Gizmo, GizmoMode, GizmoView};
+use crate::math::{Aabb, Mat4, Vec2, Vec3, Vec4};
+use crate::parser::ObjFileParser;
+use crate::pose::ViewPose;
+use crate::profile::LoadProfile;
+use crate::renderer::{
+    decode_rgba8, BitmapFont, DisplayAdjustments, Engine, EngineEvent, FaceHit, FrameStats,
+    GpuSelector, Matcap, MeshHandle, RenderPlugin, ShadingMode, ShadowQuality, Stereo, SyncTimings,
+    ValidationFeatures, Viewport, VoxelInstance,
+};
+use crate::scene::{
+    Animation, AnimationPlayer, AutoRotation, GroundReflection, Hud, Light, Mesh, Object,
+    ObjectHandle, Scene, Skin, SkinHandle, StatsHistory, StatsSample, TopologyReport, VoxelFill,
+};
+use crate::session::{Session, Toggles};
+use std::collections::HashMap;
 
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use image::RgbaImage;
 use sdl3::{
+    clipboard::ClipboardUtil,
     event::{Event, WindowEvent},
-    keyboard::Keycode,
+    keyboard::{Keycode, Mod},
     mouse::MouseButton,
+    pixels::PixelFormat,
+    surface::Surface,
     video::Window,
     Sdl,
 };
 
+/// Identifies the application to the desktop: Wayland app id and X11 window
+/// class, matching a `.desktop` file of the same name.
+const APP_ID: &str = "io.github.pradene.scop";
+
+/// Window icon, shown in taskbars and docks.
+const ICON_PNG: &[u8] = include_bytes!("../assets/icon.png");
+
+/// Windowing system SDL talks to on Linux, auto-selected when unset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoBackend {
+    Wayland,
+    X11,
+}
+
+impl VideoBackend {
+    /// Environment variable read when `--backend` is not given.
+    pub const ENV: &'static str = "SCOP_BACKEND";
+
+    /// Backend from `SCOP_BACKEND`, none when it is unset.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var(Self::ENV) {
+            Ok(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|e| format!("Invalid {}: {}", Self::ENV, e)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Name of the SDL video driver.
+    fn driver(&self) -> &'static str {
+        match self {
+            VideoBackend::Wayland => "wayland",
+            VideoBackend::X11 => "x11",
+        }
+    }
+}
+
+impl FromStr for VideoBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wayland" => Ok(VideoBackend::Wayland),
+            "x11" => Ok(VideoBackend::X11),
+            _ => Err(format!("Unknown backend '{}', expected wayland or x11", s)),
+        }
+    }
+}
+
+impl fmt::Display for VideoBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.driver())
+    }
+}
+
 pub struct App {
     sdl_context: Sdl,
     engine: Engine,
@@ -23,6 +108,13 @@ pub struct App {
     mouse_pressed: bool,
     last_mouse: Option<(f32, f32)>,
 
+    /// Fingers on the touch screen, at their positions normalized to the
+    /// window.
+    touches: HashMap<u64, Vec2>,
+    /// Whether the window surface is released, with the app in the
+    /// background or the window minimized.
+    suspended: bool,
+
     // Keys currently held
     key_forward: bool,
     key_backward: bool,
@@ -30,30 +122,184 @@ pub struct App {
     key_right: bool,
     key_up: bool,
     key_down: bool,
+    key_ctrl: bool,
+
+    // Light placement and its debug visualization
+    light_motion: Vec3,
+    show_light_gizmo: bool,
+    show_light_frustum: bool,
+
+    // Gizmo editing the selected object
+    gizmo: Gizmo,
+
+    animation_player: AnimationPlayer,
+    auto_rotation: AutoRotation,
+
+    // Triangle under the cursor, shown while inspecting faces
+    inspect_faces: bool,
+    hovered_face: Option<FaceHit>,
+
+    // Voxelized objects drawn in place of their meshes, the visible cubes of
+    // each mesh kept with their transform until the settings change
+    show_voxels: bool,
+    voxel_resolution: u32,
+    voxel_fill: VoxelFill,
+    voxel_grids: HashMap<MeshHandle, (Mat4, Vec<VoxelInstance>)>,
+
+    // Convex hulls and their oriented boxes drawn around the objects
+    show_hulls: bool,
+
+    // Open and non-manifold edges drawn in red, analyzed once per mesh
+    show_topology: bool,
+    topology_reports: HashMap<MeshHandle, TopologyReport>,
+
+    /// Scale of the scene against the window under temporal anti-aliasing,
+    /// kept while it is off.
+    render_scale: f32,
+    /// Percentage of the triangles the meshes are simplified to.
+    decimation: u32,
+    /// Loop subdivision steps applied to the meshes once simplified.
+    subdivision: u32,
+    /// Geometry of the meshes before they were first simplified or
+    /// subdivided, which every later run starts over from.
+    mesh_sources: HashMap<MeshHandle, Arc<Mesh>>,
+    /// Run on a worker thread, with its decimation and subdivision.
+    remesh_worker: Option<((u32, u32), RemeshWorker)>,
+    /// Triangles of the meshes before and after the last run.
+    remeshed_triangles: Option<(usize, usize)>,
+
+    /// Folder of models cycled through with `PageUp` and `PageDown`,
+    /// shown by the object.
+    gallery: Option<(Gallery, ObjectHandle)>,
+
+    // Clip planes fitted around the scene every update, instead of fixed
+    auto_clip: bool,
+
+    // Perspective view next to top, front and side orthographic views
+    quad_view: bool,
+    // Side-by-side views for each eye, kept while off for the next toggle
+    stereo_view: bool,
+    stereo: Stereo,
+
+    // On-screen statistics overlay
+    show_hud: bool,
+    /// Smoothed frame duration, in seconds.
+    frame_time: f32,
+    /// Smoothed waits on the GPU and the swapchain.
+    sync_timings: SyncTimings,
+
+    // Second window plotting the recent statistics, dropped after the engine
+    stats_window: Option<Window>,
+    stats_history: StatsHistory,
 
     last_update: std::time::Instant,
+    /// Simulated seconds since startup, driving animations and motion.
+    time: f32,
+    /// Advance the simulation by `FIXED_TIMESTEP` every update instead of the
+    /// wall-clock time, for reproducible frames.
+    deterministic: bool,
+    /// Print the time spent in each stage of `load_mesh_with_splash`.
+    profile_loads: bool,
 }
 
+/// Thread simplifying and subdividing meshes, returning them with the handles
+/// to replace.
+type RemeshWorker = JoinHandle<Vec<(MeshHandle, Mesh)>>;
+
 impl App {
-    pub fn new() -> Result<App, String> {
+    /// Simulated duration of an update in deterministic mode, in seconds.
+    pub const FIXED_TIMESTEP: f32 = 1. / 60.;
+    /// Factor applied to the displacement strength by `]`, undone by `[`.
+    const DISPLACEMENT_STEP: f32 = 1.25;
+    /// Factor sharpening the analysis shading ranges by `F2`, undone by `F1`.
+    const ANALYSIS_STEP: f32 = 2.;
+    /// Stops of exposure added by `F6`, removed by `F5`.
+    const EXPOSURE_STEP: f32 = 0.5;
+    /// Factor applied to the display gamma by `F8`, undone by `F7`.
+    const GAMMA_STEP: f32 = 1.1;
+    /// Brightness added by `F10`, removed by `F9`.
+    const BRIGHTNESS_STEP: f32 = 0.05;
+    /// Factor applied to the display contrast by `F12`, undone by `F11`.
+    const CONTRAST_STEP: f32 = 1.1;
+    /// Factor applied to a clip plane distance by `0` and `=`, undone by `9`
+    /// and `-`.
+    const CLIP_STEP: f32 = 1.25;
+    /// Percentage points added to the decimation target by `Ctrl`+`]`,
+    /// removed by `Ctrl`+`[`.
+    const DECIMATION_STEP: u32 = 10;
+    /// Loop subdivision steps reached by `Ctrl`+`S` before it wraps around,
+    /// each multiplying the triangles by four.
+    pub const MAX_SUBDIVISION: u32 = 3;
+    /// Voxels along the longest side of each mesh in the voxel preview.
+    pub const DEFAULT_VOXEL_RESOLUTION: u32 = 64;
+    /// Field of view at startup and after `Ctrl`+`0`, in degrees.
+    const DEFAULT_FOV: f32 = 45.;
+    /// Range of the field of view reached with `Ctrl`+scroll, in degrees.
+    const FOV_RANGE: (f32, f32) = (5., 120.);
+    /// Factor applied to the field of view per scroll step.
+    const FOV_STEP: f32 = 1.1;
+    /// Factor applied to the auto-rotation speed by `6`, undone by `5`.
+    const ROTATION_STEP: f32 = 1.25;
+
+    /// Opens the window, on `backend` when given, and its engine, on `gpu`
+    /// when given and also rendering to a VR headset when `xr`.
+    pub fn new(
+        backend: Option<VideoBackend>,
+        validation: ValidationFeatures,
+        gpu: Option<GpuSelector>,
+        xr: bool,
+    ) -> Result<App, String> {
+        Self::open(backend, validation, gpu, xr, false)
+    }
+
+    /// Opens the app like `new` with its window hidden, for offscreen
+    /// captures only, such as thumbnails.
+    pub fn hidden(
+        backend: Option<VideoBackend>,
+        validation: ValidationFeatures,
+        gpu: Option<GpuSelector>,
+    ) -> Result<App, String> {
+        Self::open(backend, validation, gpu, false, true)
+    }
+
+    fn open(
+        backend: Option<VideoBackend>,
+        validation: ValidationFeatures,
+        gpu: Option<GpuSelector>,
+        xr: bool,
+        hidden: bool,
+    ) -> Result<App, String> {
         let width: u32 = 800;
         let height: u32 = 600;
 
+        if let Some(backend) = backend {
+            // Read by the video subsystem, it must be set before SDL starts.
+            if !sdl3::hint::set("SDL_VIDEO_DRIVER", backend.driver()) {
+                eprintln!("Failed to select the {} backend", backend);
+            }
+        }
+
+        sdl3::hint::set("SDL_APP_ID", APP_ID);
+        sdl3::hint::set("SDL_APP_NAME", "Scop");
+        // Touches orbit the camera instead of moving the mouse.
+        sdl3::hint::set("SDL_TOUCH_MOUSE_EVENTS", "0");
         let sdl_context = sdl3::init().map_err(|e| format!("Failed to init SDL3: {}", e))?;
 
         let video_subsystem = sdl_context
             .video()
             .map_err(|e| format!("Failed to get video subsystem: {}", e))?;
 
-        let window = video_subsystem
-            .window("Scop", width, height)
-            .position_centered()
-            .vulkan()
-            .resizable()
+        let mut builder = video_subsystem.window("Scop", width, height);
+        builder.position_centered().vulkan().resizable();
+        if hidden {
+            builder.hidden();
+        }
+        let mut window = builder
             .build()
             .map_err(|e| format!("Failed to create window: {}", e))?;
+        Self::set_icon(&mut window);
 
-        let engine = Engine::new(&window)?;
+        let engine = Engine::new(&window, validation, gpu, xr)?;
 
         let event_pump = sdl_context
             .event_pump()
@@ -64,7 +310,7 @@ impl App {
         let camera = Camera::new(
             Vec3::new(0., 0., -200.),
             Vec3::ZERO,
-            45f32.to_radians(),
+            Self::DEFAULT_FOV.to_radians(),
             width as f32 / height as f32,
             0.1,
             500.,
@@ -79,13 +325,50 @@ impl App {
             event_pump,
             mouse_pressed: false,
             last_mouse: None,
+            touches: HashMap::new(),
+            suspended: false,
             key_forward: false,
             key_backward: false,
             key_left: false,
             key_right: false,
             key_up: false,
             key_down: false,
+            key_ctrl: false,
+            light_motion: Vec3::ZERO,
+            show_light_gizmo: true,
+            show_light_frustum: false,
+            gizmo: Gizmo::default(),
+            animation_player: AnimationPlayer::default(),
+            auto_rotation: AutoRotation::default(),
+            inspect_faces: false,
+            hovered_face: None,
+            show_voxels: false,
+            voxel_resolution: Self::DEFAULT_VOXEL_RESOLUTION,
+            voxel_fill: VoxelFill::default(),
+            voxel_grids: HashMap::new(),
+            show_hulls: false,
+            show_topology: false,
+            topology_reports: HashMap::new(),
+            render_scale: 1.,
+            decimation: 100,
+            subdivision: 0,
+            mesh_sources: HashMap::new(),
+            remesh_worker: None,
+            remeshed_triangles: None,
+            gallery: None,
+            auto_clip: true,
+            quad_view: false,
+            stereo_view: false,
+            stereo: Stereo::default(),
+            show_hud: true,
+            frame_time: 0.,
+            sync_timings: SyncTimings::default(),
+            stats_window: None,
+            stats_history: StatsHistory::default(),
             last_update: std::time::Instant::now(),
+            time: 0.,
+            deterministic: false,
+            profile_loads: false,
         })
     }
 
@@ -103,22 +386,72 @@ impl App {
                 }
 
                 Event::Window {
-                    win_event: WindowEvent::Resized(w, h),
+                    window_id,
+                    win_event,
                     ..
-                } => {
-                    if w > 0 && h > 0 {
-                        self.camera.resize(w as u32, h as u32);
-                        if let Err(e) = self.engine.resize(w as u32, h as u32) {
-                            eprintln!("Failed to resize swapchain: {:?}", e);
+                } if self.is_stats_window(window_id) => match win_event {
+                    WindowEvent::Resized(w, h) if w > 0 && h > 0 => {
+                        if let Err(e) = self.engine.resize_stats_window(w as u32, h as u32) {
+                            eprintln!("Failed to resize stats window: {:?}", e);
                         }
                     }
+                    WindowEvent::CloseRequested => {
+                        self.set_stats_window(false)?;
+                    }
+                    _ => {}
+                },
+                Event::Window {
+                    win_event: WindowEvent::CloseRequested,
+                    ..
+                } => {
+                    return Ok(false);
+                }
+                Event::Window {
+                    win_event: WindowEvent::Resized(w, h),
+                    ..
+                } => self.on_resize(w, h),
+
+                // Android destroys the native window of apps sent to the
+                // background and gives them a new one when they come back.
+                // Hidden windows let go of theirs too, instead of blocking
+                // on presents some compositors never complete.
+                Event::AppWillEnterBackground { .. }
+                | Event::Window {
+                    win_event: WindowEvent::Minimized | WindowEvent::Hidden,
+                    ..
+                } => self.suspend(),
+                Event::AppDidEnterForeground { .. }
+                | Event::Window {
+                    win_event: WindowEvent::Restored | WindowEvent::Maximized | WindowEvent::Shown,
+                    ..
+                } => self.resume()?,
+
+                Event::FingerDown {
+                    finger_id, x, y, ..
+                } => {
+                    self.touches.insert(finger_id, Vec2::new(x, y));
+                }
+                Event::FingerUp { finger_id, .. } => {
+                    self.touches.remove(&finger_id);
                 }
+                Event::FingerMotion {
+                    finger_id, x, y, ..
+                } => self.on_touch_motion(finger_id, Vec2::new(x, y)),
+
+                // The stats window only shows plots.
+                Event::MouseButtonDown { window_id, .. }
+                | Event::MouseMotion { window_id, .. }
+                | Event::MouseWheel { window_id, .. }
+                    if self.is_stats_window(window_id) => {}
 
                 Event::MouseButtonDown {
                     mouse_btn: MouseButton::Left,
+                    x,
+                    y,
                     ..
                 } => {
                     self.mouse_pressed = true;
+                    self.on_click(Vec2::new(x, y));
                 }
                 Event::MouseButtonUp {
                     mouse_btn: MouseButton::Left,
@@ -126,21 +459,32 @@ impl App {
                 } => {
                     self.mouse_pressed = false;
                     self.last_mouse = None;
+                    self.gizmo.end_drag();
                 }
 
                 Event::MouseMotion { x, y, .. } => {
                     let current = (x as f32, y as f32);
                     if self.mouse_pressed {
                         if let Some(last) = self.last_mouse {
-                            let (w, h) = self.window.size();
-                            let dx = (current.0 - last.0) / w as f32;
-                            let dy = (current.1 - last.1) / h as f32;
-                            self.camera.look(dx, -dy);
+                            if self.gizmo.is_dragging() {
+                                self.drag_gizmo(
+                                    Vec2::new(last.0, last.1),
+                                    Vec2::new(current.0, current.1),
+                                );
+                            } else {
+                                let (w, h) = self.window.size();
+                                let dx = (current.0 - last.0) / w as f32;
+                                let dy = (current.1 - last.1) / h as f32;
+                                self.camera.look(dx, -dy);
+                            }
                         }
                     }
                     self.last_mouse = Some(current);
                 }
 
+                Event::MouseWheel { y, .. } if self.key_ctrl => {
+                    self.zoom(y);
+                }
                 Event::MouseWheel { y, .. } => {
                     let amount = y * 10.0;
                     self.camera
@@ -149,10 +493,20 @@ impl App {
 
                 Event::KeyDown {
                     keycode: Some(key),
+                    keymod,
                     repeat: false,
                     ..
                 } => {
-                    self.set_key(key, true);
+                    if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                        self.on_shortcut(key);
+                    } else {
+                        self.on_key_pressed(key);
+                    }
+                    // The keys of shortcuts, like the S of Ctrl+S, don't move
+                    // the camera or the light.
+                    if !self.key_ctrl {
+                        self.set_key(key, true);
+                    }
                 }
                 Event::KeyUp {
                     keycode: Some(key), ..
@@ -167,10 +521,139 @@ impl App {
         Ok(true)
     }
 
+    /// Gives `window` the application icon. Windows keep the default one when
+    /// it can't be set.
+    fn set_icon(window: &mut Window) {
+        let mut icon = match decode_rgba8(ICON_PNG) {
+            Ok(icon) => icon,
+            Err(e) => {
+                eprintln!("Failed to load the window icon: {}", e);
+                return;
+            }
+        };
+        let (width, height) = icon.dimensions();
+        match Surface::from_data(&mut icon, width, height, width * 4, PixelFormat::RGBA32) {
+            Ok(surface) => {
+                window.set_icon(surface);
+            }
+            Err(e) => eprintln!("Failed to create the window icon: {}", e),
+        }
+    }
+
+    fn is_stats_window(&self, window_id: u32) -> bool {
+        self.stats_window
+            .as_ref()
+            .is_some_and(|window| window.id() == window_id)
+    }
+
+    /// Opens or closes the window plotting frame time, draw calls, triangles
+    /// and GPU memory over the last frames.
+    pub fn set_stats_window(&mut self, open: bool) -> Result<(), String> {
+        if open == self.stats_window.is_some() {
+            return Ok(());
+        }
+        if !open {
+            self.engine.close_stats_window();
+            self.stats_window = None;
+            return Ok(());
+        }
+
+        let mut window = self
+            .sdl_context
+            .video()
+            .map_err(|e| format!("Failed to get video subsystem: {}", e))?
+            .window("Scop statistics", 480, 480)
+            .vulkan()
+            .resizable()
+            .build()
+            .map_err(|e| format!("Failed to create stats window: {}", e))?;
+        Self::set_icon(&mut window);
+        self.engine.open_stats_window(&window)?;
+        self.stats_window = Some(window);
+        Ok(())
+    }
+
+    fn on_resize(&mut self, w: i32, h: i32) {
+        if w > 0 && h > 0 {
+            self.camera.resize(w as u32, h as u32);
+            if let Err(e) = self.engine.resize(w as u32, h as u32) {
+                eprintln!("Failed to resize swapchain: {:?}", e);
+            }
+        }
+    }
+
+    /// Releases the window surface until `resume`, skipping frames.
+    fn suspend(&mut self) {
+        if !self.suspended {
+            self.engine.suspend();
+            self.suspended = true;
+        }
+    }
+
+    /// Presents to the window again after `suspend`, restarting the frame
+    /// timer so the time spent in the background isn't simulated.
+    fn resume(&mut self) -> Result<(), String> {
+        if self.suspended {
+            self.engine.resume(&self.window)?;
+            let (w, h) = self.window.size();
+            self.camera.resize(w, h);
+            self.last_update = Instant::now();
+            self.suspended = false;
+        }
+        Ok(())
+    }
+
+    /// Orbits around the scene while one finger moves, and moves towards it
+    /// while two pinch.
+    fn on_touch_motion(&mut self, finger: u64, position: Vec2) {
+        let Some(previous) = self.touches.insert(finger, position) else {
+            return;
+        };
+        let bounds = self.scene_bounds();
+        let target = if bounds.is_empty() {
+            Vec3::ZERO
+        } else {
+            bounds.center()
+        };
+
+        match self.touches.len() {
+            1 => {
+                let delta = position - previous;
+                self.camera.orbit(target, -delta.x, delta.y);
+            }
+            2 => {
+                let Some(&other) = self
+                    .touches
+                    .iter()
+                    .find_map(|(&id, position)| (id != finger).then_some(position))
+                else {
+                    return;
+                };
+                let (before, after) = ((previous - other).length(), (position - other).length());
+                if after > f32::EPSILON {
+                    let distance = (target - self.camera.position).length();
+                    self.camera.move_forward(distance * (1. - before / after));
+                }
+            }
+            _ => {}
+        }
+    }
+
     pub fn update(&mut self) {
         let now = std::time::Instant::now();
-        let dt = now.duration_since(self.last_update).as_secs_f32();
+        let frame_dt = now.duration_since(self.last_update).as_secs_f32();
         self.last_update = now;
+        self.frame_time = if self.frame_time > 0. {
+            self.frame_time + (frame_dt - self.frame_time) * 0.05
+        } else {
+            frame_dt
+        };
+        let dt = if self.deterministic {
+            Self::FIXED_TIMESTEP
+        } else {
+            frame_dt
+        };
+        self.time += dt;
 
         let speed = self.camera.move_speed * dt;
 
@@ -192,12 +675,1245 @@ impl App {
         if self.key_down {
             self.camera.move_up(-speed);
         }
+
+        let light = &mut self.scene.light;
+        light.position += self.light_motion * speed;
+
+        if self.auto_clip {
+            let points = self.clip_points();
+            self.camera.fit_clip_planes_to_points(&points);
+        }
+
+        self.animation_player.update(dt, self.animation_duration());
+        self.auto_rotation.update(dt);
+        self.apply_animations();
+
+        self.sync_timings = self.sync_timings.smoothed(self.engine.sync_timings(), 0.05);
+
+        let stats = self.engine.stats();
+        self.stats_history.push(StatsSample {
+            frame_ms: frame_dt * 1000.,
+            draw_calls: stats.draw_calls,
+            triangles: stats.triangles,
+            gpu_memory: self.engine.gpu_memory_usage(),
+        });
+
+        self.update_remesh();
+        self.update_gallery();
+        self.update_hovered_face();
+        self.update_voxels();
+        self.update_debug_lines();
+        self.update_hud();
+    }
+
+    fn update_hovered_face(&mut self) {
+        self.hovered_face = None;
+        if !self.inspect_faces || self.gizmo.is_dragging() {
+            return;
+        }
+        let Some((x, y)) = self.last_mouse else {
+            return;
+        };
+
+        let size = self.view_size();
+        let ray = self.camera.ray(Vec2::new(x, y), size.x, size.y);
+        self.hovered_face = self.engine.pick_face(&self.scene, &ray);
+    }
+
+    /// Simulated seconds since startup: the wall-clock time, or a multiple of
+    /// `FIXED_TIMESTEP` in deterministic mode.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Makes every update advance animations, motion and `time` by
+    /// `FIXED_TIMESTEP`, so the same run always renders the same frames.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Prints, after each model loaded with a splash, how long reading,
+    /// parsing, preparing and uploading it took, stage by stage. Needs the
+    /// `profiling` feature.
+    pub fn set_load_profiling(&mut self, enabled: bool) -> Result<(), String> {
+        if enabled && !cfg!(feature = "profiling") {
+            return Err(
+                "Built without load profiling, rebuild with --features profiling".to_string(),
+            );
+        }
+        self.profile_loads = enabled;
+        Ok(())
+    }
+
+    /// Stores the vertices of the meshes added from now on quantized, in
+    /// about half the GPU memory, at the cost of some precision.
+    pub fn set_compact_vertices(&mut self, compact: bool) {
+        self.engine.set_compact_vertices(compact);
+    }
+
+    /// Mirrors the objects on the ground under their current bounds, or
+    /// removes the mirror.
+    pub fn set_reflection(&mut self, enabled: bool) {
+        if enabled && !self.engine.reflection_supported() {
+            eprintln!("Ground reflection unavailable: no stencil in the depth buffer");
+        }
+        self.scene.reflection = (enabled && !self.scene.objects.is_empty())
+            .then(|| GroundReflection::under(&self.scene_bounds()));
+    }
+
+    /// How the shadow map cascades are filtered, see `ShadowQuality`.
+    pub fn set_shadow_quality(&mut self, shadows: ShadowQuality) {
+        if shadows != ShadowQuality::Hard && self.engine.shadow_cascades() == 0 {
+            eprintln!("Soft shadows need shadow map cascades, see --shadow-cascades");
+        }
+        self.engine.set_shadow_quality(shadows);
+    }
+
+    /// Angle the light spans seen from the model, in degrees, which soft
+    /// shadows' penumbras widen with.
+    pub fn set_light_size(&mut self, degrees: f32) {
+        self.engine.set_light_size(degrees);
+    }
+
+    /// Shadows the scene from `count` shadow map cascades, 0 going back to
+    /// the traced shadows, see `Engine::set_shadow_cascades`.
+    pub fn set_shadow_cascades(&mut self, count: usize) {
+        if let Err(e) = self.engine.set_shadow_cascades(count) {
+            eprintln!("Failed to set up shadow cascades: {}", e);
+        }
+    }
+
+    /// Spin of the loaded models, see `set_auto_rotation`.
+    pub fn auto_rotation(&self) -> AutoRotation {
+        self.auto_rotation
+    }
+
+    /// Replaces the spin applied to the loaded models, keeping the angle
+    /// turned so far.
+    pub fn set_auto_rotation(&mut self, rotation: AutoRotation) {
+        self.auto_rotation = AutoRotation {
+            angle: self.auto_rotation.angle,
+            ..rotation
+        };
+    }
+
+    /// Draws triangles sorted back to front without depth test, see
+    /// `Engine::set_face_sorted`.
+    pub fn set_face_sorted(&mut self, sorted: bool) {
+        self.engine.set_face_sorted(sorted);
+    }
+
+    /// Turns temporal anti-aliasing on, drawing the scene at `scale` times the
+    /// window's resolution, or off with `None`, see `Engine::set_temporal`.
+    pub fn set_temporal(&mut self, scale: Option<f32>) {
+        if let Some(scale) = scale {
+            self.render_scale = scale;
+        }
+        if let Err(e) = self.engine.set_temporal(scale) {
+            eprintln!("Failed to set up temporal anti-aliasing: {}", e);
+        }
+    }
+
+    /// Draws every object as cubes filling a grid over its mesh instead of
+    /// the mesh itself.
+    pub fn set_voxel_preview(&mut self, show: bool) {
+        self.show_voxels = show;
+    }
+
+    /// Voxelizes the meshes with `resolution` cubes along their longest side
+    /// from now on.
+    pub fn set_voxel_grid(&mut self, resolution: u32, fill: VoxelFill) {
+        self.voxel_resolution = resolution.max(1);
+        self.voxel_fill = fill;
+        self.voxel_grids.clear();
+    }
+
+    /// Fills the scene's voxel preview with the cubes of every object,
+    /// voxelizing their meshes on first use.
+    fn update_voxels(&mut self) {
+        self.scene.voxels.clear();
+        if !self.show_voxels {
+            return;
+        }
+        for object in &self.scene.objects {
+            let mesh = object.id();
+            let (resolution, fill) = (self.voxel_resolution, self.voxel_fill);
+            let engine = &self.engine;
+            let (transform, instances) = self.voxel_grids.entry(mesh).or_insert_with(|| {
+                let grid = engine.mesh(mesh).voxelize(resolution, fill);
+                let instances = grid.visible_instances();
+                println!(
+                    "Voxelized mesh {}: {}x{}x{} grid, {} {} voxels, {} visible",
+                    mesh,
+                    grid.dims[0],
+                    grid.dims[1],
+                    grid.dims[2],
+                    grid.count(),
+                    fill,
+                    instances.len()
+                );
+                (grid.transform(), instances)
+            });
+            self.scene
+                .voxels
+                .add(instances, object.transform() * *transform);
+        }
+    }
+
+    /// Draws the convex hull of every object in cyan and its oriented box in
+    /// magenta, printing their sizes.
+    pub fn set_show_hulls(&mut self, show: bool) {
+        self.show_hulls = show;
+        if !show {
+            return;
+        }
+        let mut meshes: Vec<MeshHandle> = self.scene.objects.iter().map(Object::id).collect();
+        meshes.sort_unstable();
+        meshes.dedup();
+        for mesh in meshes {
+            let hull = self.engine.mesh_hull(mesh);
+            let obb = self.engine.mesh_obb(mesh);
+            let size = obb.half_extents * 2.;
+            let bounds = self.engine.mesh_bounds(mesh).size();
+            println!(
+                "Hull of mesh {}: {} points, {} triangles | box {:.3} x {:.3} x {:.3}, {:.0}% of the bounds volume",
+                mesh,
+                hull.points.len(),
+                hull.triangles.len(),
+                size.x,
+                size.y,
+                size.z,
+                obb.volume() / (bounds.x * bounds.y * bounds.z).max(f32::MIN_POSITIVE) * 100.
+            );
+        }
+    }
+
+    /// Draws the open and non-manifold edges of every object as red lines,
+    /// printing the analysis of each mesh the first time.
+    pub fn set_show_topology(&mut self, show: bool) {
+        self.show_topology = show;
+        if show {
+            for object in 0..self.scene.objects.len() {
+                self.topology_report(object);
+            }
+        }
+    }
+
+    /// Topology of the mesh of `object`, analyzed on first use.
+    fn topology_report(&mut self, object: ObjectHandle) -> &TopologyReport {
+        let mesh = self.scene.objects[object].id();
+        let engine = &self.engine;
+        self.topology_reports.entry(mesh).or_insert_with(|| {
+            let report = engine.mesh_topology(mesh);
+            println!("Topology of mesh {}: {}", mesh, report);
+            report
+        })
+    }
+
+    /// Simplifies the meshes of the scene to `percent` of their original
+    /// triangles in the background, swapping them in once done.
+    pub fn set_decimation(&mut self, percent: u32) {
+        self.decimation = percent.clamp(1, 100);
+        if self.remesh_worker.is_none() {
+            self.start_remesh();
+        }
+    }
+
+    /// Smooths the meshes of the scene by `levels` steps of Loop subdivision,
+    /// up to `MAX_SUBDIVISION`, in the background like `set_decimation`.
+    pub fn set_subdivision(&mut self, levels: u32) {
+        self.subdivision = levels.min(Self::MAX_SUBDIVISION);
+        if self.remesh_worker.is_none() {
+            self.start_remesh();
+        }
+    }
+
+    fn start_remesh(&mut self) {
+        let mut meshes: Vec<MeshHandle> = self.scene.objects.iter().map(Object::id).collect();
+        meshes.sort_unstable();
+        meshes.dedup();
+        let sources: Vec<(MeshHandle, Arc<Mesh>)> = meshes
+            .into_iter()
+            .map(|mesh| {
+                let engine = &self.engine;
+                let source = self
+                    .mesh_sources
+                    .entry(mesh)
+                    .or_insert_with(|| Arc::new(engine.mesh(mesh).clone()));
+                (mesh, Arc::clone(source))
+            })
+            .collect();
+
+        let (percent, levels) = (self.decimation, self.subdivision);
+        let worker = std::thread::spawn(move || {
+            sources
+                .into_iter()
+                .map(|(mesh, source)| {
+                    let simplified = if percent < 100 {
+                        source.simplify(percent as f32 / 100.)
+                    } else {
+                        (*source).clone()
+                    };
+                    (mesh, simplified.subdivide(levels))
+                })
+                .collect()
+        });
+        self.remesh_worker = Some(((percent, levels), worker));
+    }
+
+    /// Swaps in the meshes of a finished run, starting another one if the
+    /// settings changed meanwhile.
+    fn update_remesh(&mut self) {
+        let Some((settings, worker)) = self.remesh_worker.take_if(|(_, w)| w.is_finished()) else {
+            return;
+        };
+        let start = Instant::now();
+        let meshes = match worker.join() {
+            Ok(meshes) => meshes,
+            Err(_) => {
+                eprintln!("Mesh simplification or subdivision panicked");
+                return;
+            }
+        };
+
+        let (mut before, mut after) = (0, 0);
+        for (handle, mesh) in meshes {
+            if let Err(e) = self.engine.replace_mesh(handle, &mesh) {
+                eprintln!("{}", e);
+                continue;
+            }
+            before += self.mesh_sources[&handle].triangle_count();
+            after += mesh.triangle_count();
+            // Derived from the previous geometry.
+            self.topology_reports.remove(&handle);
+            self.voxel_grids.remove(&handle);
+        }
+        self.remeshed_triangles = Some((before, after));
+        println!(
+            "Decimation {}%, subdivision {}: {} -> {} triangles, swapped in {:.2} ms",
+            settings.0,
+            settings.1,
+            before,
+            after,
+            start.elapsed().as_secs_f64() * 1000.
+        );
+        if self.show_topology {
+            self.set_show_topology(true);
+        }
+
+        if settings != (self.decimation, self.subdivision) {
+            self.start_remesh();
+        }
+    }
+
+    /// Cycles `object` through the models of `gallery`, starting from the
+    /// one it shows, and preloads the next one.
+    pub fn set_gallery(&mut self, mut gallery: Gallery, object: ObjectHandle) {
+        let next = gallery.neighbour(1).to_string();
+        if self.engine.cached_mesh(&next).is_none() {
+            gallery.preload(&next);
+        }
+        self.gallery = Some((gallery, object));
+    }
+
+    /// Shows the model `step` positions away in the gallery, framed from
+    /// the current direction, then preloads the one after it.
+    fn step_gallery(&mut self, step: isize) {
+        let Some((gallery, object)) = &mut self.gallery else {
+            return;
+        };
+        let object = *object;
+        let path = gallery.step(step).to_string();
+        let mesh = match self.engine.cached_mesh(&path) {
+            Some(mesh) => Ok(mesh),
+            None => gallery.load(&path).and_then(|mesh| {
+                self.engine
+                    .add_loaded_mesh(&path, &mesh, &mut LoadProfile::default())
+            }),
+        };
+        let mesh = match mesh {
+            Ok(mesh) => mesh,
+            Err(e) => {
+                eprintln!("Failed to load '{}': {}", path, e);
+                return;
+            }
+        };
+        let (index, count) = gallery.position();
+        println!("Gallery {}/{}: {}", index, count, path);
+        let next = gallery.neighbour(step.signum()).to_string();
+        if self.engine.cached_mesh(&next).is_none() {
+            gallery.preload(&next);
+        }
+
+        self.scene.get_object(object).set_mesh(mesh);
+        let direction = self.camera.forward();
+        self.frame_scene(direction, self.camera.ratio());
+        if self.decimation < 100 || self.subdivision > 0 {
+            self.start_remesh();
+        }
+    }
+
+    /// Uploads the model preloaded by the gallery once parsed, for `PageUp`
+    /// and `PageDown` to swap it in at once.
+    fn update_gallery(&mut self) {
+        let Some((gallery, _)) = &mut self.gallery else {
+            return;
+        };
+        let Some((path, mesh)) = gallery.take_preloaded() else {
+            return;
+        };
+        let uploaded = mesh.and_then(|mesh| match self.engine.cached_mesh(&path) {
+            Some(_) => Ok(()),
+            None => self
+                .engine
+                .add_loaded_mesh(&path, &mesh, &mut LoadProfile::default())
+                .map(|_| ()),
+        });
+        if let Err(e) = uploaded {
+            eprintln!("Failed to preload '{}': {}", path, e);
+        }
+    }
+
+    /// Fixes the camera's clip planes, turning off their fitting around the
+    /// scene.
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.auto_clip = false;
+        self.camera.set_clip_planes(near, far);
+    }
+
+    /// Fits the camera's clip planes around the scene every update, as done
+    /// by default.
+    pub fn set_auto_clip(&mut self, enabled: bool) {
+        self.auto_clip = enabled;
+    }
+
+    /// Splits the window into the perspective view and top, front and side
+    /// orthographic views of the scene, or goes back to the perspective view
+    /// alone.
+    pub fn set_quad_view(&mut self, enabled: bool) {
+        self.quad_view = enabled;
+        self.stereo_view &= !enabled;
+    }
+
+    /// Draws the camera twice side by side as seen by each eye, or once.
+    pub fn set_stereo(&mut self, stereo: Option<Stereo>) {
+        self.stereo_view = stereo.is_some();
+        self.stereo = stereo.unwrap_or(self.stereo);
+        self.quad_view &= !self.stereo_view;
+    }
+
+    /// Views drawn side by side, none when the camera fills the window.
+    fn viewports(&self) -> Vec<Viewport> {
+        if self.quad_view {
+            Viewport::quad(&self.camera, &self.scene_bounds())
+        } else if self.stereo_view {
+            Viewport::stereo(&self.camera, self.stereo)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Size of the camera's view in pixels, picking and gizmos only working
+    /// in the perspective view, or the left eye's.
+    fn view_size(&self) -> Vec2 {
+        let (w, h) = self.window.size();
+        let size = Vec2::new(w as f32, h as f32);
+        if self.quad_view {
+            size * 0.5
+        } else if self.stereo_view {
+            Vec2::new(size.x * 0.5, size.y)
+        } else {
+            size
+        }
+    }
+
+    /// Width of the gizmo and other debug lines, in pixels.
+    pub fn set_line_width(&mut self, width: f32) {
+        self.scene.debug_lines.width = width;
+    }
+
+    pub fn add_skin(&mut self, skin: Skin) -> SkinHandle {
+        self.scene.add_skin(skin)
+    }
+
+    pub fn add_animation(&mut self, animation: Animation) {
+        self.scene.animations.push(animation);
+    }
+
+    fn animation_duration(&self) -> f32 {
+        self.scene
+            .animations
+            .iter()
+            .map(Animation::duration)
+            .fold(0., f32::max)
+    }
+
+    fn apply_animations(&mut self) {
+        let time = self.animation_player.time;
+        let animations = std::mem::take(&mut self.scene.animations);
+        for animation in &animations {
+            animation.apply(&mut self.scene, time);
+        }
+        self.scene.animations = animations;
+    }
+
+    /// World-space bounds of every object in the scene.
+    pub fn scene_bounds(&self) -> Aabb {
+        (0..self.scene.objects.len()).fold(Aabb::EMPTY, |bounds, object| {
+            bounds.union(self.object_bounds(object))
+        })
+    }
+
+    /// Corners of the oriented boxes around the objects, of their mirror
+    /// images and of the mirror itself, for fitting the clip planes.
+    fn clip_points(&self) -> Vec<Vec3> {
+        let mut points: Vec<Vec3> = self
+            .scene
+            .objects
+            .iter()
+            .flat_map(|object| {
+                self.engine
+                    .mesh_obb(object.id())
+                    .transformed_corners(&object.transform())
+            })
+            .collect();
+        if let Some(reflection) = &self.scene.reflection {
+            let mirror = reflection.mirror();
+            let mirrored: Vec<Vec3> = points
+                .iter()
+                .map(|&p| (mirror * Vec4::from_vec3(p, 1.)).xyz())
+                .collect();
+            points.extend(mirrored);
+            let Vec3 { x, y, z } = reflection.center;
+            let extent = reflection.extent;
+            for (dx, dz) in [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)] {
+                points.push(Vec3::new(x + dx * extent, y, z + dz * extent));
+            }
+        }
+        points
+    }
+
+    /// World-space bounds of a single object.
+    pub fn object_bounds(&self, object: ObjectHandle) -> Aabb {
+        let object = &self.scene.objects[object];
+        self.engine
+            .mesh_bounds(object.id())
+            .transform(&object.transform())
+    }
+
+    pub fn selected(&self) -> Option<ObjectHandle> {
+        self.scene.selected
+    }
+
+    /// Grabs a gizmo handle of the selected object under the cursor, otherwise
+    /// selects the closest object hit by the cursor ray.
+    fn on_click(&mut self, mouse: Vec2) {
+        let viewport = self.view_size();
+
+        if let Some(selected) = self.scene.selected {
+            let view = GizmoView {
+                camera: &self.camera,
+                viewport,
+                center: self.scene.objects[selected].position(),
+                size: self.gizmo_size(selected),
+            };
+            if self.gizmo.begin_drag(&view, mouse) {
+                return;
+            }
+        }
+
+        let ray = self.camera.ray(mouse, viewport.x, viewport.y);
+        self.scene.selected = self
+            .engine
+            .pick_face(&self.scene, &ray)
+            .map(|hit| hit.object);
+    }
+
+    fn drag_gizmo(&mut self, from: Vec2, to: Vec2) {
+        let Some(selected) = self.scene.selected else {
+            return;
+        };
+        let view = GizmoView {
+            camera: &self.camera,
+            viewport: self.view_size(),
+            center: self.scene.objects[selected].position(),
+            size: self.gizmo_size(selected),
+        };
+        self.gizmo
+            .drag(&view, &mut self.scene.objects[selected], from, to);
+    }
+
+    fn gizmo_size(&self, object: ObjectHandle) -> f32 {
+        (self.object_bounds(object).radius() * 0.75).max(1.)
+    }
+
+    fn update_debug_lines(&mut self) {
+        let bounds = self.scene_bounds();
+        let light = &self.scene.light;
+        let lines = &mut self.scene.debug_lines;
+        lines.clear();
+
+        if self.show_light_gizmo {
+            let radius = (bounds.radius() * 0.05).max(1.);
+            lines.sphere(light.position, radius, light.color);
+        }
+        if self.show_light_frustum && !bounds.is_empty() {
+            let camera = light.frustum_camera(&bounds);
+            lines.hexahedron(&camera.frustum_corners(), Vec3::new(1., 0.8, 0.));
+            lines.line(light.position, bounds.center(), light.color);
+        }
+
+        if let Some(face) = &self.hovered_face {
+            let transform = self.scene.objects[face.object].transform();
+            let [a, b, c] = face
+                .positions
+                .map(|p| (transform * Vec4::from_vec3(p, 1.)).xyz());
+            let color = Vec3::new(1., 1., 0.);
+            lines.line(a, b, color);
+            lines.line(b, c, color);
+            lines.line(c, a, color);
+        }
+
+        if let Some(selected) = self.scene.selected {
+            let size = self.gizmo_size(selected);
+            let bounds = self.object_bounds(selected);
+            let lines = &mut self.scene.debug_lines;
+            lines.aabb(&bounds, Vec3::splat(0.8));
+            self.gizmo
+                .draw(lines, self.scene.objects[selected].position(), size);
+        }
+
+        if self.show_hulls {
+            let lines = &mut self.scene.debug_lines;
+            for object in &self.scene.objects {
+                let transform = object.transform();
+                let to_world = |p: Vec3| (transform * Vec4::from_vec3(p, 1.)).xyz();
+                let hull = self.engine.mesh_hull(object.id());
+                for [a, b] in hull.edges() {
+                    let [a, b] = [a, b].map(|i| to_world(hull.points[i as usize]));
+                    lines.line(a, b, Vec3::new(0., 1., 1.));
+                }
+                let corners = self
+                    .engine
+                    .mesh_obb(object.id())
+                    .transformed_corners(&transform);
+                lines.hexahedron(&corners, Vec3::new(1., 0., 1.));
+            }
+        }
+
+        if self.show_topology {
+            let red = Vec3::new(1., 0., 0.);
+            for object in 0..self.scene.objects.len() {
+                let transform = self.scene.objects[object].transform();
+                let mesh = self.scene.objects[object].id();
+                self.topology_report(object);
+                let report = &self.topology_reports[&mesh];
+                let edges = report
+                    .boundary_edges
+                    .iter()
+                    .chain(&report.non_manifold_edges);
+                for edge in edges {
+                    let [a, b] = edge.map(|p| (transform * Vec4::from_vec3(p, 1.)).xyz());
+                    self.scene.debug_lines.line(a, b, red);
+                }
+            }
+        }
+    }
+
+    fn update_hud(&mut self) {
+        self.scene.hud.clear();
+        if let Some(face) = &self.hovered_face {
+            let (x, y) = self.last_mouse.unwrap_or_default();
+            let text = Self::describe_face(face);
+            self.scene.hud.text(
+                Vec2::new(x + 16., y + 16.),
+                2.,
+                Vec4::new(1., 1., 0.5, 1.),
+                &text,
+            );
+        }
+        let (w, h) = self.window.size();
+        for viewport in self.viewports() {
+            let corner = Vec2::new(
+                viewport.origin.x * w as f32 + 10.,
+                (viewport.origin.y + viewport.size.y) * h as f32 - 26.,
+            );
+            self.scene
+                .hud
+                .text(corner, 2., Vec4::new(0.7, 0.9, 1., 1.), viewport.name);
+        }
+        if !self.show_hud {
+            return;
+        }
+
+        let hud = &mut self.scene.hud;
+        let stats = self.engine.stats();
+        let fps = if self.frame_time > 0. {
+            1. / self.frame_time
+        } else {
+            0.
+        };
+        let shading = match self.engine.shading_mode() {
+            ShadingMode::Matcap => format!("matcap ({})", self.engine.matcap()),
+            ShadingMode::Curvature => format!(
+                "curvature (radius {:.3})",
+                self.engine.surface_analysis().curvature_radius
+            ),
+            ShadingMode::NormalDeviation => {
+                let analysis = self.engine.surface_analysis();
+                format!(
+                    "normal-deviation ({}, {:.1} deg)",
+                    analysis.axis_name(),
+                    analysis.deviation_angle
+                )
+            }
+            mode => mode.to_string(),
+        };
+        let mut text = format!(
+            "{:.1} fps ({:.2} ms)\nsync: {}\n{} objects | {} draw calls | {} triangles | {} occluded\nshading: {} | gizmo: {}{}",
+            fps,
+            self.frame_time * 1000.,
+            self.sync_timings,
+            self.scene.objects.len(),
+            stats.draw_calls,
+            stats.triangles,
+            stats.occluded_groups,
+            shading,
+            self.gizmo.mode,
+            if self.engine.occlusion_traced() {
+                " | traced ao"
+            } else {
+                ""
+            },
+        );
+        if self.engine.face_sorted() {
+            text += " | face sorted";
+        }
+        if let Some(scale) = self.engine.temporal_scale() {
+            text += &format!(" | taa at {:.0}%", scale * 100.);
+        }
+        let cascades = self.engine.shadow_cascades();
+        if cascades > 0 {
+            text += &format!(" | {} cascades", cascades);
+        }
+        if self.show_voxels {
+            text += &format!(
+                " | voxels: {} {}, {} cubes",
+                self.voxel_resolution,
+                self.voxel_fill,
+                self.scene.voxels.instances.len()
+            );
+        }
+        text += &format!(
+            "\nfov: {:.1} | clip: {:.3} to {:.3}{}",
+            self.camera.fov().to_degrees(),
+            self.camera.near(),
+            self.camera.far(),
+            if self.auto_clip { " (fitted)" } else { "" }
+        );
+        if let Some(displacement) = self.engine.displacement() {
+            text += &format!("\ndisplacement: {:.3}", displacement);
+        }
+        let display = self.engine.display_adjustments();
+        if !display.is_neutral() {
+            text += &format!("\ndisplay: {}", display);
+        }
+        text += &format!("\nrotation: {}", self.auto_rotation);
+        if self.decimation < 100 || self.subdivision > 0 || self.remesh_worker.is_some() {
+            text += &format!(
+                "\ndecimation: {}% | subdivision: {}",
+                self.decimation, self.subdivision
+            );
+            if let Some((before, after)) = self.remeshed_triangles {
+                text += &format!(" | {} -> {} triangles", before, after);
+            }
+            if self.remesh_worker.is_some() {
+                text += " (working...)";
+            }
+        }
+        if self.show_topology {
+            let object = self.scene.selected.unwrap_or(0);
+            if let Some(report) = self
+                .scene
+                .objects
+                .get(object)
+                .and_then(|object| self.topology_reports.get(&object.id()))
+            {
+                text += &format!("\ntopology (object {}): {}", object, report);
+            }
+        }
+        if let Some((gallery, _)) = &self.gallery {
+            let (index, count) = gallery.position();
+            text += &format!(
+                "\nmodel {}/{}: {}",
+                index,
+                count,
+                Gallery::label(gallery.current())
+            );
+        }
+        if !self.scene.animations.is_empty() {
+            let player = &self.animation_player;
+            text += &format!(
+                "\nanimation: {:.2}s {}",
+                player.time,
+                if player.playing { "playing" } else { "paused" }
+            );
+        }
+
+        hud.text(Vec2::new(10., 10.), 2., Vec4::new(1., 1., 1., 1.), &text);
+    }
+
+    fn describe_face(face: &FaceHit) -> String {
+        let vec = |v: Vec3| format!("({:.3}, {:.3}, {:.3})", v.x, v.y, v.z);
+        let [a, b, c] = face.indices;
+        let mut text = format!(
+            "object {} group {} triangle {}\nindices: {} {} {}",
+            face.object, face.group, face.triangle, a, b, c
+        );
+        for (i, position) in face.positions.iter().enumerate() {
+            text += &format!("\nv{}: {}", i, vec(*position));
+        }
+        text += &format!("\nnormal: {}", vec(face.normal));
+        text += &format!(
+            "\nmaterial: {}",
+            if face.material.is_empty() {
+                "(default)"
+            } else {
+                &face.material
+            }
+        );
+        text
     }
 
     pub fn draw(&mut self) {
+        if self.suspended {
+            return;
+        }
+        self.engine.set_viewports(self.viewports());
         if let Err(e) = self.engine.draw(&self.window, &self.camera, &self.scene) {
             eprintln!("Failed to draw: {:?}", e);
         }
+
+        let Some(window) = &self.stats_window else {
+            return;
+        };
+        if !self.engine.has_stats_window() {
+            // Lost with the device.
+            self.stats_window = None;
+            return;
+        }
+        let (width, height) = window.size();
+        let mut hud = Hud::default();
+        self.stats_history
+            .layout(&mut hud, Vec2::new(width as f32, height as f32));
+        if let Err(e) = self.engine.draw_stats_window(&hud) {
+            eprintln!("Failed to draw stats window: {:?}", e);
+        }
+    }
+
+    fn on_key_pressed(&mut self, key: Keycode) {
+        if key == Keycode::M {
+            let mode = self.engine.shading_mode().next();
+            self.engine.set_shading_mode(mode);
+            println!("Shading mode: {}", mode);
+        }
+        if key == Keycode::V {
+            let mode = self.engine.shading_mode().next_debug();
+            self.engine.set_shading_mode(mode);
+            println!("Shading mode: {}", mode);
+        }
+        if key == Keycode::N {
+            let matcap = self.engine.matcap().next();
+            self.engine.set_matcap(matcap);
+            println!("Matcap: {}", matcap);
+        }
+        let gizmo_mode = match key {
+            Keycode::_1 => Some(GizmoMode::Translate),
+            Keycode::_2 => Some(GizmoMode::Rotate),
+            Keycode::_3 => Some(GizmoMode::Scale),
+            _ => None,
+        };
+        if let Some(mode) = gizmo_mode {
+            self.gizmo.mode = mode;
+            println!("Gizmo: {}", mode);
+        }
+        match key {
+            Keycode::P => {
+                self.animation_player.toggle();
+                println!(
+                    "Animation {}",
+                    if self.animation_player.playing {
+                        "playing"
+                    } else {
+                        "paused"
+                    }
+                );
+            }
+            Keycode::Period => {
+                let duration = self.animation_duration();
+                self.animation_player.step(1, duration);
+                self.apply_animations();
+            }
+            Keycode::Comma => {
+                let duration = self.animation_duration();
+                self.animation_player.step(-1, duration);
+                self.apply_animations();
+            }
+            Keycode::PageDown => self.step_gallery(1),
+            Keycode::PageUp => self.step_gallery(-1),
+            _ => {}
+        }
+        let rotation = &mut self.auto_rotation;
+        let rotation_changed = match key {
+            Keycode::_4 => {
+                rotation.toggle();
+                true
+            }
+            Keycode::_5 => {
+                rotation.speed /= Self::ROTATION_STEP;
+                true
+            }
+            Keycode::_6 => {
+                rotation.speed *= Self::ROTATION_STEP;
+                true
+            }
+            Keycode::_7 => {
+                rotation.next_axis();
+                true
+            }
+            _ => false,
+        };
+        if rotation_changed {
+            println!("Auto-rotation: {}", rotation);
+        }
+        if key == Keycode::G {
+            self.show_light_gizmo = !self.show_light_gizmo;
+        }
+        if key == Keycode::F {
+            self.show_light_frustum = !self.show_light_frustum;
+        }
+        if key == Keycode::H {
+            self.show_hud = !self.show_hud;
+        }
+        if key == Keycode::C {
+            let open = self.stats_window.is_none();
+            if let Err(e) = self.set_stats_window(open) {
+                eprintln!("Failed to open stats window: {}", e);
+            }
+        }
+        if key == Keycode::T {
+            self.inspect_faces = !self.inspect_faces;
+            println!(
+                "Face inspection {}",
+                if self.inspect_faces { "on" } else { "off" }
+            );
+        }
+        if key == Keycode::R {
+            let traced = !self.engine.occlusion_traced();
+            self.engine.set_occlusion_traced(traced);
+            println!(
+                "Traced ambient occlusion {}",
+                if traced { "on" } else { "off" }
+            );
+        }
+        if let Some(displacement) = self.engine.displacement() {
+            let scale = match key {
+                Keycode::LeftBracket => Some(1. / Self::DISPLACEMENT_STEP),
+                Keycode::RightBracket => Some(Self::DISPLACEMENT_STEP),
+                _ => None,
+            };
+            if let Some(scale) = scale {
+                self.engine.set_displacement(displacement * scale);
+                println!("Displacement: {:.3}", displacement * scale);
+            }
+        }
+        let previous = self.engine.surface_analysis();
+        let mut analysis = previous;
+        match key {
+            Keycode::F1 => analysis.sharpen(1. / Self::ANALYSIS_STEP),
+            Keycode::F2 => analysis.sharpen(Self::ANALYSIS_STEP),
+            Keycode::F3 => analysis.next_axis(),
+            _ => {}
+        }
+        if analysis != previous {
+            self.engine.set_surface_analysis(analysis);
+            println!(
+                "Surface analysis: curvature radius {:.3} | deviation {:.1}° from {}",
+                analysis.curvature_radius,
+                analysis.deviation_angle,
+                analysis.axis_name()
+            );
+        }
+        let previous = self.engine.display_adjustments();
+        let mut display = previous;
+        match key {
+            Keycode::F4 => display = DisplayAdjustments::default(),
+            Keycode::F5 => display.exposure -= Self::EXPOSURE_STEP,
+            Keycode::F6 => display.exposure += Self::EXPOSURE_STEP,
+            Keycode::F7 => display.gamma /= Self::GAMMA_STEP,
+            Keycode::F8 => display.gamma *= Self::GAMMA_STEP,
+            Keycode::F9 => display.brightness -= Self::BRIGHTNESS_STEP,
+            Keycode::F10 => display.brightness += Self::BRIGHTNESS_STEP,
+            Keycode::F11 => display.contrast /= Self::CONTRAST_STEP,
+            Keycode::F12 => display.contrast *= Self::CONTRAST_STEP,
+            _ => {}
+        }
+        if display != previous {
+            self.engine.set_display_adjustments(display);
+            println!("Display: {}", display);
+        }
+        if key == Keycode::X {
+            let enabled = self.scene.reflection.is_none();
+            self.set_reflection(enabled);
+            println!("Ground reflection {}", if enabled { "on" } else { "off" });
+        }
+        if key == Keycode::Y {
+            self.set_quad_view(!self.quad_view);
+            println!("Quad view {}", if self.quad_view { "on" } else { "off" });
+        }
+        let (near, far) = (self.camera.near(), self.camera.far());
+        let clip_planes = match key {
+            Keycode::_9 => Some((near / Self::CLIP_STEP, far)),
+            Keycode::_0 => Some(((near * Self::CLIP_STEP).min(far * 0.5), far)),
+            Keycode::Minus => Some((near, (far / Self::CLIP_STEP).max(near * 2.))),
+            Keycode::Equals => Some((near, far * Self::CLIP_STEP)),
+            _ => None,
+        };
+        if let Some((near, far)) = clip_planes {
+            self.set_clip_planes(near, far);
+            println!("Clip planes: {:.3} to {:.3}", near, far);
+        }
+        if key == Keycode::_8 {
+            self.set_auto_clip(!self.auto_clip);
+            println!(
+                "Clip plane fitting {}",
+                if self.auto_clip { "on" } else { "off" }
+            );
+        }
+        if key == Keycode::Tab {
+            self.set_voxel_preview(!self.show_voxels);
+            println!(
+                "Voxel preview {}",
+                if self.show_voxels { "on" } else { "off" }
+            );
+        }
+        if key == Keycode::Grave {
+            let fill = match self.voxel_fill {
+                VoxelFill::Surface => VoxelFill::Solid,
+                VoxelFill::Solid => VoxelFill::Surface,
+            };
+            self.set_voxel_grid(self.voxel_resolution, fill);
+            println!("Voxel fill: {}", fill);
+        }
+        if key == Keycode::Apostrophe {
+            self.set_show_hulls(!self.show_hulls);
+            println!("Hulls {}", if self.show_hulls { "on" } else { "off" });
+        }
+        if key == Keycode::Semicolon {
+            if let Some(selected) = self.scene.selected {
+                match self.split_object(selected) {
+                    Ok(parts) => println!("Split object {} into {} parts", selected, parts.len()),
+                    Err(e) => eprintln!("Failed to split object: {}", e),
+                }
+            }
+        }
+        if key == Keycode::Slash {
+            self.set_show_topology(!self.show_topology);
+            println!(
+                "Topology edges {}",
+                if self.show_topology { "on" } else { "off" }
+            );
+        }
+        if key == Keycode::Backslash {
+            let sorted = !self.engine.face_sorted();
+            self.engine.set_face_sorted(sorted);
+            println!("Face sorting {}", if sorted { "on" } else { "off" });
+        }
+        if key == Keycode::Z {
+            let stereo = (!self.stereo_view).then_some(self.stereo);
+            self.set_stereo(stereo);
+            println!(
+                "Stereo view {}",
+                if self.stereo_view { "on" } else { "off" }
+            );
+        }
+        if key == Keycode::B {
+            if let Err(e) = self.bake_lightmaps() {
+                eprintln!("Failed to bake lightmaps: {}", e);
+            }
+        }
+    }
+
+    /// Narrows the field of view by `steps` scroll steps, widening it when
+    /// negative, within `FOV_RANGE`.
+    fn zoom(&mut self, steps: f32) {
+        let (min, max) = Self::FOV_RANGE;
+        let fov = (self.camera.fov().to_degrees() / Self::FOV_STEP.powf(steps)).clamp(min, max);
+        self.camera.set_fov(fov.to_radians());
+    }
+
+    fn on_shortcut(&mut self, key: Keycode) {
+        let result = match key {
+            Keycode::C => self.copy_pose(),
+            Keycode::V => self.paste_pose(),
+            Keycode::_0 => {
+                self.camera.set_fov(Self::DEFAULT_FOV.to_radians());
+                println!("Field of view: {:.1}°", Self::DEFAULT_FOV);
+                Ok(())
+            }
+            Keycode::LeftBracket | Keycode::RightBracket => {
+                let percent = if key == Keycode::RightBracket {
+                    self.decimation + Self::DECIMATION_STEP
+                } else {
+                    self.decimation.saturating_sub(Self::DECIMATION_STEP)
+                };
+                self.set_decimation(percent);
+                println!("Decimation target: {}%", self.decimation);
+                Ok(())
+            }
+            Keycode::Semicolon => match self.scene.selected {
+                Some(selected) => self.split_named_objects(selected).map(|parts| {
+                    println!("Split object {} into {} objects", selected, parts.len())
+                }),
+                None => Ok(()),
+            },
+            Keycode::H => {
+                self.set_shadow_quality(self.engine.shadow_quality().next());
+                println!("Shadows: {}", self.engine.shadow_quality());
+                Ok(())
+            }
+            Keycode::S => {
+                self.set_subdivision((self.subdivision + 1) % (Self::MAX_SUBDIVISION + 1));
+                println!("Subdivision: {} levels", self.subdivision);
+                Ok(())
+            }
+            Keycode::T => {
+                let enabled = self.engine.temporal_scale().is_none();
+                self.set_temporal(enabled.then_some(self.render_scale));
+                println!(
+                    "Temporal anti-aliasing {}",
+                    if enabled { "on" } else { "off" }
+                );
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+        }
+    }
+
+    /// Object whose transform is shared with the view: the selected one, or
+    /// the first.
+    fn posed_object(&self) -> Option<ObjectHandle> {
+        self.scene
+            .selected
+            .or((!self.scene.objects.is_empty()).then_some(0))
+    }
+
+    /// Copies the camera and model placement to the clipboard as JSON, see
+    /// `ViewPose`.
+    pub fn copy_pose(&self) -> Result<(), String> {
+        let object = self.posed_object().ok_or("No model to copy the pose of")?;
+        let pose = ViewPose::capture(&self.camera, &self.scene.objects[object]);
+        self.clipboard()?
+            .set_clipboard_text(&pose.to_json())
+            .map_err(|e| format!("Failed to copy the view: {}", e))?;
+        println!("View copied to the clipboard");
+        Ok(())
+    }
+
+    /// Applies a pose copied by `copy_pose`, from this or another session.
+    pub fn paste_pose(&mut self) -> Result<(), String> {
+        let text = self
+            .clipboard()?
+            .clipboard_text()
+            .map_err(|e| format!("Failed to read the clipboard: {}", e))?;
+        let pose =
+            ViewPose::from_json(&text).map_err(|e| format!("Failed to paste the view: {}", e))?;
+        let object = self.posed_object().ok_or("No model to paste the pose on")?;
+        pose.apply(&mut self.camera, &mut self.scene.objects[object]);
+        println!("View pasted from the clipboard");
+        Ok(())
+    }
+
+    /// The viewer state to resume later, with `model` as the model shown.
+    /// None without any model to take the placement of.
+    pub fn session(&self, model: Option<String>) -> Option<Session> {
+        let object = self.posed_object()?;
+        Some(Session {
+            model,
+            pose: ViewPose::capture(&self.camera, &self.scene.objects[object]),
+            shading: self.engine.shading_mode(),
+            matcap: self.engine.matcap(),
+            light: self.scene.light.clone(),
+            auto_rotation: self.auto_rotation,
+            toggles: Toggles {
+                hud: self.show_hud,
+                light_gizmo: self.show_light_gizmo,
+                light_frustum: self.show_light_frustum,
+                inspect_faces: self.inspect_faces,
+                reflection: self.scene.reflection.is_some(),
+                quad_view: self.quad_view,
+                stereo: self.stereo_view,
+                voxels: self.show_voxels,
+                hulls: self.show_hulls,
+                topology: self.show_topology,
+                face_sorting: self.engine.face_sorted(),
+            },
+        })
+    }
+
+    /// Puts back the state of a `session`, its placement going to the first
+    /// model.
+    pub fn restore_session(&mut self, session: &Session) {
+        if let Some(object) = self.posed_object() {
+            session
+                .pose
+                .apply(&mut self.camera, &mut self.scene.objects[object]);
+        }
+        self.set_shading_mode(session.shading);
+        self.engine.set_matcap(session.matcap);
+        self.scene.light = session.light.clone();
+        self.auto_rotation = session.auto_rotation;
+
+        let toggles = &session.toggles;
+        self.show_hud = toggles.hud;
+        self.show_light_gizmo = toggles.light_gizmo;
+        self.show_light_frustum = toggles.light_frustum;
+        self.inspect_faces = toggles.inspect_faces;
+        self.set_reflection(toggles.reflection);
+        self.set_quad_view(toggles.quad_view);
+        self.set_stereo(toggles.stereo.then_some(self.stereo));
+        self.set_voxel_preview(toggles.voxels);
+        self.set_show_hulls(toggles.hulls);
+        self.set_show_topology(toggles.topology);
+        self.engine.set_face_sorted(toggles.face_sorting);
+    }
+
+    fn clipboard(&self) -> Result<ClipboardUtil, String> {
+        self.sdl_context
+            .video()
+            .map(|video| video.clipboard())
+            .map_err(|e| format!("Failed to get video subsystem: {}", e))
+    }
+
+    /// Bakes the current light into the lightmapped meshes and switches to
+    /// baked shading.
+    pub fn bake_lightmaps(&mut self) -> Result<(), String> {
+        let start = Instant::now();
+        let baked = self.engine.bake_lightmaps(&self.scene)?;
+        if baked == 0 {
+            println!("No mesh has lightmap UVs, load with --lightmap");
+            return Ok(());
+        }
+        println!(
+            "Baked {} lightmap(s) in {:.2} s",
+            baked,
+            start.elapsed().as_secs_f32()
+        );
+        self.engine.set_shading_mode(ShadingMode::Baked);
+        Ok(())
     }
 
     fn set_key(&mut self, key: Keycode, pressed: bool) {
@@ -208,6 +1924,13 @@ impl App {
             Keycode::D | Keycode::Right => self.key_right = pressed,
             Keycode::E | Keycode::Space => self.key_up = pressed,
             Keycode::Q | Keycode::LShift => self.key_down = pressed,
+            Keycode::LCtrl | Keycode::RCtrl => self.key_ctrl = pressed,
+            Keycode::L => self.light_motion.x = if pressed { 1. } else { 0. },
+            Keycode::J => self.light_motion.x = if pressed { -1. } else { 0. },
+            Keycode::O => self.light_motion.y = if pressed { 1. } else { 0. },
+            Keycode::U => self.light_motion.y = if pressed { -1. } else { 0. },
+            Keycode::I => self.light_motion.z = if pressed { 1. } else { 0. },
+            Keycode::K => self.light_motion.z = if pressed { -1. } else { 0. },
             _ => {}
         }
     }
@@ -220,13 +1943,344 @@ impl App {
         self.scene.get_object(object_id)
     }
 
+    /// Replaces `object` with one object per connected component of its
+    /// mesh, each centered on its own part and placed where the part was.
+    /// The first part keeps the handle of `object`, the others are added at
+    /// the end of the scene. Returns the handles of all parts.
+    pub fn split_object(&mut self, object: ObjectHandle) -> Result<Vec<ObjectHandle>, String> {
+        self.split_with(object, Mesh::split_components)
+    }
+
+    /// Same as `split_object`, with one part per named object of the OBJ
+    /// file, see `Mesh::split_objects`.
+    pub fn split_named_objects(
+        &mut self,
+        object: ObjectHandle,
+    ) -> Result<Vec<ObjectHandle>, String> {
+        self.split_with(object, Mesh::split_objects)
+    }
+
+    fn split_with(
+        &mut self,
+        object: ObjectHandle,
+        split: impl FnOnce(&Mesh) -> Vec<Mesh>,
+    ) -> Result<Vec<ObjectHandle>, String> {
+        let source = &self.scene.objects[object];
+        if source.skin().is_some() {
+            return Err(format!("Can't split skinned object {}", object));
+        }
+        let parts = split(self.engine.mesh(source.id()));
+        if parts.len() < 2 {
+            return Ok(vec![object]);
+        }
+
+        let transform = source.transform();
+        let (rotation, scale, layer) = (source.rotation(), source.scale(), source.layer());
+        let mut handles = Vec::new();
+        for (i, mut part) in parts.into_iter().enumerate() {
+            let center = part.recenter();
+            let mut part_object = Object::new(self.engine.add_mesh(&part)?);
+            part_object
+                .set_position((transform * Vec4::from_vec3(center, 1.)).xyz())
+                .set_rotation(rotation.x, rotation.y, rotation.z)
+                .set_scale(scale)
+                .set_layer(layer);
+            if i == 0 {
+                self.scene.objects[object] = part_object;
+                handles.push(object);
+            } else {
+                handles.push(self.scene.add_object(part_object));
+            }
+        }
+        Ok(handles)
+    }
+
     pub fn load_mesh(&mut self, path: &str) -> Result<MeshHandle, String> {
         self.engine.load_mesh(path)
     }
+
+    /// Parses an OBJ file and runs `prepare` on it on a worker thread while
+    /// drawing a progress splash, then uploads it. Fails if the window is
+    /// closed before it is done.
+    pub fn load_mesh_with_splash(
+        &mut self,
+        path: &str,
+        prepare: impl FnOnce(&mut Mesh) + Send + 'static,
+    ) -> Result<MeshHandle, String> {
+        crash::set_model(path);
+        let progress = Arc::new(AtomicU32::new(0));
+        let worker = {
+            let progress = Arc::clone(&progress);
+            let path = path.to_string();
+            std::thread::spawn(move || {
+                let mut profile = LoadProfile::default();
+                let mut mesh = profile.stage("parse", |profile| {
+                    ObjFileParser::parse_profiled(
+                        &path,
+                        |fraction| progress.store(fraction.to_bits(), Ordering::Relaxed),
+                        profile,
+                    )
+                })?;
+                profile.stage("prepare", |_| prepare(&mut mesh));
+                Ok::<_, String>((mesh, profile))
+            })
+        };
+
+        let start = Instant::now();
+        while !worker.is_finished() {
+            if !self.handle_loading_events() {
+                return Err(format!("Loading of '{}' cancelled", path));
+            }
+            let fraction = f32::from_bits(progress.load(Ordering::Relaxed));
+            self.draw_splash(path, fraction, start.elapsed());
+            self.draw();
+            std::thread::sleep(Duration::from_millis(16));
+        }
+        self.scene.hud.clear();
+
+        let (mesh, mut profile) = worker
+            .join()
+            .map_err(|_| format!("Mesh loader for '{}' panicked", path))?
+            .map_err(|e| format!("Failed to parse mesh '{}': {}", path, e))?;
+        let handle = profile.stage("upload", |profile| {
+            self.engine.add_loaded_mesh(path, &mesh, profile)
+        })?;
+        if self.profile_loads {
+            println!(
+                "Loaded '{}' in {:.2} ms:\n{}",
+                path,
+                profile.total().as_secs_f64() * 1000.,
+                profile
+            );
+        }
+        Ok(handle)
+    }
+
+    /// Only honours quitting, resizing, minimizing and going to the
+    /// background, the scene is not interactive yet.
+    fn handle_loading_events(&mut self) -> bool {
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return false,
+                Event::Window {
+                    win_event: WindowEvent::Resized(w, h),
+                    ..
+                } => self.on_resize(w, h),
+                Event::AppWillEnterBackground { .. }
+                | Event::Window {
+                    win_event: WindowEvent::Minimized | WindowEvent::Hidden,
+                    ..
+                } => self.suspend(),
+                Event::AppDidEnterForeground { .. }
+                | Event::Window {
+                    win_event: WindowEvent::Restored | WindowEvent::Maximized | WindowEvent::Shown,
+                    ..
+                } => {
+                    if let Err(e) = self.resume() {
+                        eprintln!("Failed to resume: {}", e);
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    fn draw_splash(&mut self, path: &str, fraction: f32, elapsed: Duration) {
+        const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+        const SCALE: f32 = 2.;
+        const BAR_SIZE: Vec2 = Vec2 { x: 320., y: 12. };
+
+        let (w, h) = self.window.size();
+        let center = Vec2::new(w as f32, h as f32) * 0.5;
+        let name = Path::new(path)
+            .file_name()
+            .map_or(path.into(), |n| n.to_string_lossy());
+        let spinner = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+        let label = format!("{} Loading {} {:>3.0}%", spinner, name, fraction * 100.);
+
+        let text_size = BitmapFont::measure(&label, SCALE);
+        let bar = Vec2::new(center.x - BAR_SIZE.x * 0.5, center.y + 8.);
+        let white = Vec4::new(1., 1., 1., 1.);
+
+        let hud = &mut self.scene.hud;
+        hud.clear();
+        hud.text(
+            Vec2::new(center.x - text_size.x * 0.5, center.y - text_size.y - 8.),
+            SCALE,
+            white,
+            &label,
+        );
+        hud.rect(bar, BAR_SIZE, Vec4::new(1., 1., 1., 0.2));
+        hud.rect(
+            bar,
+            Vec2::new(BAR_SIZE.x * fraction.clamp(0., 1.), BAR_SIZE.y),
+            white,
+        );
+    }
+
+    /// Uploads CPU-side geometry, e.g. one of the `Mesh` primitives.
+    pub fn add_mesh(&mut self, mesh: &Mesh) -> Result<MeshHandle, String> {
+        self.engine.add_mesh(mesh)
+    }
+
+    pub fn mesh_bounds(&self, mesh: MeshHandle) -> Aabb {
+        self.engine.mesh_bounds(mesh)
+    }
+
+    pub fn camera(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Smoothed waits on the GPU and the swapchain, telling what bounds the
+    /// frame rate.
+    pub fn sync_timings(&self) -> SyncTimings {
+        self.sync_timings
+    }
+
+    /// Events of the engine from now on, see `EngineEvent`.
+    pub fn subscribe(&self) -> Receiver<EngineEvent> {
+        self.engine.subscribe()
+    }
+
+    /// Draws `plugin` over the following frames, see `RenderPlugin`.
+    pub fn add_plugin(&mut self, plugin: Box<dyn RenderPlugin>) -> Result<(), String> {
+        self.engine.add_plugin(plugin)
+    }
+
+    pub fn frame_stats(&self) -> FrameStats {
+        self.engine.stats()
+    }
+
+    pub fn set_shading_mode(&mut self, mode: ShadingMode) {
+        self.engine.set_shading_mode(mode);
+    }
+
+    pub fn set_matcap(&mut self, matcap: Matcap) {
+        self.engine.set_matcap(matcap);
+    }
+
+    pub fn light(&mut self) -> &mut Light {
+        &mut self.scene.light
+    }
+
+    /// Gives the materials named `name` of the shown meshes the diffuse
+    /// color `kd`, every material when `name` is empty, and uploads the
+    /// meshes again. Returns how many materials changed.
+    pub fn set_material_color(&mut self, name: &str, kd: Vec3) -> Result<usize, String> {
+        let recolor = |mesh: &mut Mesh| {
+            let mut changed = 0;
+            for material in &mut mesh.materials {
+                if name.is_empty() || material.name == name {
+                    material.kd = kd;
+                    changed += 1;
+                }
+            }
+            changed
+        };
+
+        let mut meshes: Vec<MeshHandle> = self.scene.objects.iter().map(Object::id).collect();
+        meshes.sort_unstable();
+        meshes.dedup();
+        let mut changed = 0;
+        for handle in meshes {
+            let mut mesh = self.engine.mesh(handle).clone();
+            let count = recolor(&mut mesh);
+            if count == 0 {
+                continue;
+            }
+            self.engine.replace_mesh(handle, &mesh)?;
+            if let Some(source) = self.mesh_sources.get_mut(&handle) {
+                recolor(Arc::make_mut(source));
+            }
+            changed += count;
+        }
+        Ok(changed)
+    }
+
+    /// Lets the GPU finish before anything is destroyed; call on exit.
+    pub fn shutdown(&mut self) {
+        self.engine.shutdown();
+    }
+
+    /// The camera as it would draw into an image of the given size.
+    fn capture_camera(&self, width: u32, height: u32) -> Camera {
+        let mut camera = self.camera.clone();
+        camera.resize(width, height);
+        if self.auto_clip {
+            camera.fit_clip_planes_to_points(&self.clip_points());
+        }
+        camera
+    }
+
+    /// Aims the camera at the center of the scene along `direction`, from
+    /// far enough for the whole scene to fit a view of aspect `ratio`, such
+    /// as a capture's.
+    pub fn frame_scene(&mut self, direction: Vec3, ratio: f32) {
+        let bounds = self.scene_bounds();
+        if bounds.is_empty() {
+            return;
+        }
+        let radius = bounds.size().length() * 0.5;
+        let half_fov = self.camera.fov() * 0.5;
+        let half_angle = half_fov.min((half_fov.tan() * ratio).atan());
+        let distance = radius / half_angle.sin();
+
+        self.camera.position = bounds.center() - direction.normalize() * distance;
+        self.camera.look_towards(direction);
+    }
+
+    /// Renders the current scene offscreen at the given size, independently of
+    /// the window resolution.
+    pub fn capture(&mut self, width: u32, height: u32) -> Result<RgbaImage, String> {
+        let camera = self.capture_camera(width, height);
+
+        // Captures show the scene only, without the on-screen statistics.
+        let hud = std::mem::take(&mut self.scene.hud);
+        let pixels = self.engine.capture(&camera, &self.scene, width, height);
+        self.scene.hud = hud;
+
+        let pixels = pixels?;
+        RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| "Captured image has an unexpected size".to_string())
+    }
+
+    /// Path traces what `capture` would show with `samples` paths per pixel,
+    /// printing the progress.
+    pub fn render_reference(
+        &mut self,
+        width: u32,
+        height: u32,
+        samples: u32,
+    ) -> Result<RgbaImage, String> {
+        let camera = self.capture_camera(width, height);
+
+        let start = Instant::now();
+        let pixels =
+            self.engine
+                .render_reference(&camera, &self.scene, (width, height), samples, |done| {
+                    print!(
+                        "\rReference: {}/{} samples ({:.1} s)",
+                        done,
+                        samples,
+                        start.elapsed().as_secs_f32()
+                    );
+                    let _ = std::io::stdout().flush();
+                });
+        println!();
+
+        RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| "Reference image has an unexpected size".to_string())
+    }
 }
 
 impl Drop for App {
     fn drop(&mut self) {
-        self.engine.wait_idle();
+        self.shutdown();
     }
 }
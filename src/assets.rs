@@ -0,0 +1,284 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Environment variable naming an asset root, like `--asset-root`.
+pub const ENV: &str = "SCOP_ASSET_DIR";
+
+/// Root given on the command line, see `set_root`.
+static CLI_ROOT: OnceLock<PathBuf> = OnceLock::new();
+static ROOTS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+/// Searches `root` before any other directory. Must be called before the
+/// first `resolve`, which fixes the roots for the rest of the process.
+pub fn set_root(root: PathBuf) -> Result<(), String> {
+    if !root.is_dir() {
+        return Err(format!(
+            "Asset root '{}' is not a directory",
+            root.display()
+        ));
+    }
+    if ROOTS.get().is_some() {
+        return Err("Asset root set after the first asset was loaded".to_string());
+    }
+    CLI_ROOT
+        .set(root)
+        .map_err(|_| "Asset root set twice".to_string())
+}
+
+/// Directories relative paths are looked up in, in order: the `--asset-root`
+/// option, `SCOP_ASSET_DIR`, the working directory, then the executable's
+/// directory and its parents, each also with a `share/scop` subdirectory for
+/// installed copies.
+pub fn roots() -> &'static [PathBuf] {
+    ROOTS.get_or_init(|| {
+        let mut roots: Vec<PathBuf> = CLI_ROOT.get().cloned().into_iter().collect();
+        roots.extend(std::env::var_os(ENV).map(PathBuf::from));
+        roots.push(PathBuf::new());
+        if let Some(dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        {
+            for ancestor in dir.ancestors() {
+                roots.push(ancestor.to_path_buf());
+                roots.push(ancestor.join("share/scop"));
+            }
+        }
+        roots
+    })
+}
+
+/// The first existing `path` under one of the `roots`, or `path` unchanged
+/// when it is absolute or found nowhere, for the caller to report.
+pub fn resolve<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    roots()
+        .iter()
+        .map(|root| root.join(path))
+        .find(|candidate| candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Reader of the asset at `path`, see `resolve`, with its size in bytes. On
+/// Android, paths found in no root are read from the APK's assets.
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<(Box<dyn Read>, u64)> {
+    let resolved = resolve(&path);
+    #[cfg(target_os = "android")]
+    if !resolved.exists() {
+        return apk::open(path.as_ref());
+    }
+    let file = File::open(resolved)?;
+    let size = file.metadata()?.len();
+    Ok((Box::new(file), size))
+}
+
+/// Contents of the asset at `path`, see `open`.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let (mut reader, size) = open(path)?;
+    let mut bytes = Vec::with_capacity(size as usize);
+    reader.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Bytes of an asset, see `map`.
+pub enum AssetBytes {
+    Read(Vec<u8>),
+    #[cfg(unix)]
+    Mapped(mapped::MappedFile),
+}
+
+impl Deref for AssetBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            AssetBytes::Read(bytes) => bytes,
+            #[cfg(unix)]
+            AssetBytes::Mapped(file) => file,
+        }
+    }
+}
+
+/// Size from which `map` maps files rather than reading them: smaller ones
+/// are read about as fast, and spared the risks of mappings.
+#[cfg(unix)]
+const MIN_MAPPED_SIZE: u64 = 16 << 20;
+
+/// Contents of the asset at `path` like `read`, but mapped into memory
+/// rather than copied when it is a local file of `MIN_MAPPED_SIZE` or more,
+/// so large models are paged in as they are parsed.
+///
+/// A mapped file must not be truncated or rewritten while its bytes are
+/// alive: reading pages past a truncation raises SIGBUS, and rewrites show
+/// through what is handed out as an immutable slice. Files another process
+/// may still be writing, such as models a tool exports for the viewer to
+/// reload, go through `read` instead.
+pub fn map<P: AsRef<Path>>(path: P) -> io::Result<AssetBytes> {
+    #[cfg(unix)]
+    {
+        let resolved = resolve(&path);
+        if resolved
+            .metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.len() >= MIN_MAPPED_SIZE)
+        {
+            return mapped::MappedFile::open(&resolved).map(AssetBytes::Mapped);
+        }
+    }
+    read(path).map(AssetBytes::Read)
+}
+
+/// Whether `open` finds an asset at `path`.
+pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+    #[cfg(target_os = "android")]
+    if !resolve(&path).exists() {
+        return apk::open(path.as_ref()).is_ok();
+    }
+    resolve(path).exists()
+}
+
+/// Files of the asset directory `dir` with the given extension, in path
+/// order.
+pub fn list<P: AsRef<Path>>(dir: P, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let resolved = resolve(&dir);
+    #[cfg(target_os = "android")]
+    if !resolved.exists() {
+        return apk::list(dir.as_ref(), extension);
+    }
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(resolved)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Read-only file mappings.
+#[cfg(unix)]
+mod mapped {
+    use std::fs::File;
+    use std::io;
+    use std::ops::Deref;
+    use std::os::fd::AsRawFd;
+    use std::path::Path;
+
+    /// A whole file mapped privately, unmapped when dropped. Changes made to
+    /// the file meanwhile by other processes may show through, and reading
+    /// past a truncation raises SIGBUS, so the file must be left alone while
+    /// the mapping lives, see `map`.
+    pub struct MappedFile {
+        ptr: *mut libc::c_void,
+        len: usize,
+    }
+
+    // The mapping is read-only and owned by this value alone.
+    unsafe impl Send for MappedFile {}
+    unsafe impl Sync for MappedFile {}
+
+    impl MappedFile {
+        pub fn open(path: &Path) -> io::Result<Self> {
+            let file = File::open(path)?;
+            let len = usize::try_from(file.metadata()?.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::OutOfMemory, "File too large to map"))?;
+            // Empty mappings are invalid, and nothing needs mapping anyway.
+            if len == 0 {
+                return Ok(Self {
+                    ptr: std::ptr::null_mut(),
+                    len,
+                });
+            }
+            let ptr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            // Only a hint for the kernel's read-ahead, fine to ignore.
+            unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+            Ok(Self { ptr, len })
+        }
+    }
+
+    impl Deref for MappedFile {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            if self.len == 0 {
+                return &[];
+            }
+            unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for MappedFile {
+        fn drop(&mut self) {
+            if self.len != 0 {
+                unsafe { libc::munmap(self.ptr, self.len) };
+            }
+        }
+    }
+}
+
+/// Assets packaged in the APK, which SDL reads for relative paths.
+#[cfg(target_os = "android")]
+mod apk {
+    use std::ffi::{c_int, c_void, CStr, CString};
+    use std::io::{self, Read, Seek, SeekFrom};
+    use std::path::{Path, PathBuf};
+
+    use sdl3::iostream::IOStream;
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Path holds a nul byte"))
+    }
+
+    pub fn open(path: &Path) -> io::Result<(Box<dyn Read>, u64)> {
+        let mut stream = IOStream::from_file(path, "rb")
+            .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))?;
+        let size = stream.seek(SeekFrom::End(0))?;
+        stream.rewind()?;
+        Ok((Box::new(stream), size))
+    }
+
+    pub fn list(dir: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+        let c_dir = c_path(dir)?;
+        let pattern = c_path(Path::new(&format!("*.{}", extension)))?;
+        let mut count: c_int = 0;
+        let mut paths = unsafe {
+            let entries = sdl3::sys::filesystem::SDL_GlobDirectory(
+                c_dir.as_ptr(),
+                pattern.as_ptr(),
+                Default::default(),
+                &mut count,
+            );
+            if entries.is_null() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    sdl3::get_error().to_string(),
+                ));
+            }
+            let paths: Vec<PathBuf> = (0..count.max(0) as usize)
+                .map(|index| {
+                    let name = CStr::from_ptr(*entries.add(index));
+                    dir.join(name.to_string_lossy().as_ref())
+                })
+                .collect();
+            sdl3::sys::stdinc::SDL_free(entries as *mut c_void);
+            paths
+        };
+        paths.sort();
+        Ok(paths)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::math::{Mat4, Vec3};
+use crate::math::{Aabb, Mat4, Ray, Vec2, Vec3, Vec4};
 
 #[derive(Debug, Clone)]
 pub struct Camera {
@@ -13,11 +13,22 @@ pub struct Camera {
     far: f32,
     near: f32,
     fov: f32,
+    /// Half the view height of an orthographic camera, `None` in perspective.
+    ortho: Option<f32>,
+    /// View and projection of a headset's eye, replacing the ones derived
+    /// from the fields above, see `set_eye`.
+    eye: Option<(Mat4, Mat4)>,
 }
 
 impl Camera {
     const MIN_PITCH: f32 = -std::f32::consts::FRAC_PI_2 + 0.01;
     const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+    /// Largest far to near ratio `fit_clip_planes` gives, keeping enough
+    /// depth precision against z-fighting.
+    const MAX_DEPTH_RATIO: f32 = 1000.;
+    /// Margin around the bounds fitted by `fit_clip_planes` and
+    /// `fit_clip_planes_to_points`.
+    const CLIP_MARGIN: f32 = 1.05;
 
     pub fn new(position: Vec3, target: Vec3, fov: f32, ratio: f32, near: f32, far: f32) -> Self {
         let dir = (target - position).normalize();
@@ -35,6 +46,24 @@ impl Camera {
             near,
             far,
             ratio,
+            ortho: None,
+            eye: None,
+        }
+    }
+
+    /// Camera projecting parallel to its direction, seeing `half_height`
+    /// world units above and below its axis.
+    pub fn orthographic(
+        position: Vec3,
+        target: Vec3,
+        half_height: f32,
+        ratio: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        Self {
+            ortho: Some(half_height),
+            ..Self::new(position, target, 0., ratio, near, far)
         }
     }
 
@@ -50,12 +79,77 @@ impl Camera {
         self.forward().cross(Vec3::Y).normalize()
     }
 
+    pub fn up(&self) -> Vec3 {
+        self.right().cross(self.forward())
+    }
+
+    /// World-space corners of the view frustum, near plane first, each plane
+    /// going around from bottom-left.
+    pub fn frustum_corners(&self) -> [Vec3; 8] {
+        let (forward, right, up) = (self.forward(), self.right(), self.up());
+        let tan = (self.fov * 0.5).tan();
+
+        let mut corners = [Vec3::ZERO; 8];
+        for (plane, distance) in [self.near, self.far].into_iter().enumerate() {
+            let center = self.position + forward * distance;
+            let half = self.ortho.unwrap_or(distance * tan);
+            let half_height = up * half;
+            let half_width = right * (half * self.ratio);
+            let offsets = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)];
+            for (i, (x, y)) in offsets.into_iter().enumerate() {
+                corners[plane * 4 + i] = center + half_width * x + half_height * y;
+            }
+        }
+        corners
+    }
+
+    /// Ray through the given pixel of a `width` x `height` viewport.
+    pub fn ray(&self, pixel: Vec2, width: f32, height: f32) -> Ray {
+        let ndc_x = pixel.x / width * 2. - 1.;
+        let ndc_y = pixel.y / height * 2. - 1.;
+        if let Some(half_height) = self.ortho {
+            let origin = self.position + self.right() * (ndc_x * half_height * self.ratio)
+                - self.up() * (ndc_y * half_height);
+            return Ray::new(origin, self.forward());
+        }
+        let tan = (self.fov * 0.5).tan();
+
+        let direction =
+            self.forward() + self.right() * (ndc_x * tan * self.ratio) - self.up() * (ndc_y * tan);
+        Ray::new(self.position, direction.normalize())
+    }
+
+    /// Pixel coordinates of a world-space point, or `None` when it is behind
+    /// the camera.
+    pub fn project(&self, point: Vec3, width: f32, height: f32) -> Option<Vec2> {
+        let view_proj = self.get_projection_matrix() * self.get_view_matrix();
+        let clip = view_proj.mul_vec4(Vec4::new(point.x, point.y, point.z, 1.));
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+        Some(Vec2::new(
+            (clip.x / clip.w + 1.) * 0.5 * width,
+            (clip.y / clip.w + 1.) * 0.5 * height,
+        ))
+    }
+
     pub fn get_view_matrix(&self) -> Mat4 {
+        if let Some((view, _)) = self.eye {
+            return view;
+        }
         Mat4::look_at(self.position, self.position + self.forward(), Vec3::Y)
     }
 
     pub fn get_projection_matrix(&self) -> Mat4 {
-        Mat4::projection(self.fov, self.ratio, self.near, self.far)
+        if let Some((_, projection)) = self.eye {
+            return projection;
+        }
+        match self.ortho {
+            Some(half_height) => {
+                Mat4::orthographic(half_height * self.ratio, half_height, self.near, self.far)
+            }
+            None => Mat4::projection(self.fov, self.ratio, self.near, self.far),
+        }
     }
 
     pub fn look(&mut self, delta_x: f32, delta_y: f32) {
@@ -64,6 +158,49 @@ impl Camera {
             (self.pitch + delta_y * self.look_speed).clamp(Self::MIN_PITCH, Self::MAX_PITCH);
     }
 
+    /// Makes the camera a headset's eye at `position` looking towards
+    /// `direction`, with the exact `view` and `projection` the headset gives,
+    /// which may roll and be off-axis. The angles only approximate them.
+    pub fn set_eye(&mut self, position: Vec3, direction: Vec3, view: Mat4, projection: Mat4) {
+        self.position = position;
+        self.look_towards(direction);
+        self.eye = Some((view, projection));
+    }
+
+    /// Turns the camera around `target` like `look`, keeping its distance
+    /// and facing it.
+    pub fn orbit(&mut self, target: Vec3, delta_x: f32, delta_y: f32) {
+        let distance = (target - self.position).length();
+        if distance <= f32::EPSILON {
+            self.look(delta_x, delta_y);
+            return;
+        }
+        self.look_towards(target - self.position);
+        self.look(delta_x, delta_y);
+        self.position = target - self.forward() * distance;
+    }
+
+    /// Turns the camera to face `direction`, which need not be normalized.
+    pub fn look_towards(&mut self, direction: Vec3) {
+        let dir = direction.normalize();
+        self.yaw = dir.x.atan2(dir.z);
+        self.pitch = dir.y.asin().clamp(Self::MIN_PITCH, Self::MAX_PITCH);
+    }
+
+    /// Vertical field of view, in radians.
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    /// Width over height of the view.
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(1f32.to_radians(), 179f32.to_radians());
+    }
+
     pub fn move_forward(&mut self, amount: f32) {
         self.position = self.position + self.forward() * amount;
     }
@@ -76,7 +213,58 @@ impl Camera {
         self.position = self.position + Vec3::Y * amount;
     }
 
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far;
+    }
+
+    /// Moves the clip planes as close as possible around the bounding sphere
+    /// of `bounds`, the near plane no closer than `MAX_DEPTH_RATIO` times
+    /// less than the far plane, for when the camera is inside it.
+    pub fn fit_clip_planes(&mut self, bounds: &Aabb) {
+        if bounds.is_empty() {
+            return;
+        }
+        let radius = bounds.radius() * Self::CLIP_MARGIN;
+        let distance = (bounds.center() - self.position).length();
+        let far = (distance + radius).max(0.01);
+        let near = (distance - radius).max(far / Self::MAX_DEPTH_RATIO);
+        self.set_clip_planes(near, far);
+    }
+
+    /// Moves the clip planes to the nearest and farthest of `points` along
+    /// the view direction, limited like `fit_clip_planes`. Tighter than
+    /// fitting a bounding sphere, given the corners of boxes around the
+    /// objects.
+    pub fn fit_clip_planes_to_points(&mut self, points: &[Vec3]) {
+        if points.is_empty() {
+            return;
+        }
+        let forward = self.forward();
+        let (nearest, farthest) = points
+            .iter()
+            .map(|&p| (p - self.position).dot(forward))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), depth| {
+                (lo.min(depth), hi.max(depth))
+            });
+        let far = (farthest * Self::CLIP_MARGIN).max(0.01);
+        let near = (nearest / Self::CLIP_MARGIN).max(far / Self::MAX_DEPTH_RATIO);
+        self.set_clip_planes(near, far);
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.ratio = width as f32 / height as f32;
     }
+
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio;
+    }
 }
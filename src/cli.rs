@@ -0,0 +1,708 @@
+use crate::app::{App, VideoBackend};
+use crate::math::Vec3;
+use crate::remote::RemoteEndpoint;
+use crate::renderer::{GpuSelector, ShadingMode, ShadowQuality, Stereo, ValidationFeatures};
+use crate::scene::{AutoRotation, DebugLines, MaterialOverrides, VoxelFill};
+
+pub const USAGE: &str = "Usage: scop [model.obj|folder] [options]
+       scop convert <model.obj> <output.glb|ply|obj> [options]
+       scop thumbnail <model.obj> [-o out.png] [--size 512] [options]
+       scop script <script.rhai> [options]
+
+Given a folder, the viewer cycles through its OBJ files with PageUp and
+PageDown.
+
+Commands:
+  convert             Write the model, after the load options below such as
+                      --fix-orientation, --subdivide and --decimate, to a
+                      glTF binary, PLY or OBJ file without opening a window
+  thumbnail           Render the model framed from the front-left, under
+                      the default light, to a PNG with a hidden window, for
+                      file manager thumbnailers
+  script              Run a Rhai script loading models, placing the camera,
+                      setting materials and taking screenshots (needs the
+                      scripting feature)
+
+Options:
+  --primitive <name>  Show a built-in mesh instead of a model: cube, sphere,
+                      icosphere, plane, torus or cone
+  --compare <a>,<b>   Render the model with shading modes a and b offscreen and
+                      write a side-by-side and a difference image
+  --reference <samples>
+                      Path trace the model on the CPU with <samples> paths
+                      per pixel and compare it against the lit rendering
+  -o, --output <path> Output image path (default: compare.png, or
+                      thumbnail.png for thumbnail)
+  --size <w>x<h>      Offscreen render size, or <n> for a square one
+                      (default: 800x600, or 256 for thumbnail)
+  --fix-orientation   Make triangle windings consistent and turn inward-facing
+                      parts of the model inside out at load
+  --unwrap            Replace the model's texture coordinates with a planar
+                      or box projection of each connected part, packed into
+                      one atlas, e.g. to convert scans to OBJ for painting
+  --force-color <r>,<g>,<b>
+                      Give every material of the model this diffuse color,
+                      components in 0..=1, instead of its MTL color and
+                      texture
+  --force-material <name>=<r>,<g>,<b>
+                      Same for the material called <name> only, taking
+                      precedence over --force-color; may be repeated
+  --ao <samples>      Bake ambient occlusion into the vertices at load,
+                      casting <samples> rays per vertex
+  --lightmap <size>   Give the model a <size>x<size> lightmap and bake the
+                      light into it at startup (experimental)
+  --diff <other.obj>  Overlay a second version of the model, each in its own
+                      translucent color
+  --heatmap           With --diff, color the model by its distance to the
+                      second version instead
+  --stress <x>x<y>x<z>
+                      Instance the model in a 3D grid and print frame stats
+  --profile-load      Print how long each stage of loading the model took,
+                      from reading the file to uploading it to the GPU
+  --deterministic     Advance animations by a fixed 1/60 s per frame instead
+                      of the wall clock, for reproducible frames
+  --spin-speed <deg/s>
+                      Speed of the model's rotation, 0 to keep it still
+                      (default: 115)
+  --spin-axis <axis>  Axis the model rotates around: x, y, z or a vector
+                      such as 1,1,0 (default: y)
+  --line-width <px>   Width of the gizmo and debug lines, in pixels
+                      (default: 2)
+  --clip <near>,<far> Fixed clip plane distances, instead of fitting them
+                      around the scene every frame
+  --sort-faces        Sort triangles back to front on the CPU instead of
+                      testing depth, to debug depth artifacts
+  --compact-vertices  Store the model's vertices as half floats and
+                      octahedral normals, in about half the GPU memory
+  --voxels <resolution>
+                      Draw the model as cubes, <resolution> along its longest
+                      side
+  --voxel-fill <fill> Voxels to draw: surface or solid (default: surface)
+  --hull              Draw the convex hull and the oriented bounding box of
+                      the model
+  --topology          Draw the open and non-manifold edges of the model in
+                      red and print its connected components
+  --decimate <percent>
+                      Simplify the model to <percent> of its triangles
+  --subdivide <levels>
+                      Smooth the model at load with 1 to 3 levels of Loop
+                      subdivision
+  --taa               Smooth edges with temporal anti-aliasing
+  --render-scale <scale>
+                      With temporal anti-aliasing, draw the scene at <scale>
+                      times the window's resolution, 0.25 to 1, and upscale
+                      it (implies --taa)
+  --shadows <quality> Filtering of the shadow map cascades: hard, or soft or
+                      high for penumbras from 16 or 32 taps (default: hard)
+  --light-size <deg>  Angle the light spans seen from the model, widening soft
+                      shadows' penumbras (default: 4)
+  --shadow-cascades <count>
+                      Shadow the scene from 2 to 4 shadow maps, each covering
+                      a range of depth, instead of tracing rays; keeps shadows
+                      sharp up close in large scenes and works without ray
+                      queries
+  --reflection        Mirror the model on the ground under it
+  --quad-view         Split the window into perspective, top, front and side
+                      views
+  --stereo            Draw the model side by side for each eye, for 3D TVs
+  --ipd <units>       Eye distance of --stereo, in world units (default: 6.5)
+  --cross-eye         With --stereo, swap the eyes for cross-eyed viewing
+  --xr                Also render the model to a VR headset through OpenXR
+                      (needs the openxr feature)
+  --stats-window      Open a second window plotting frame statistics
+  --remote <endpoint> Take JSON-RPC commands, one per line, from stdin or from
+                      connections to a local TCP port, such as 7878: load a
+                      model, set the camera, take a screenshot
+  --remote-token <token>
+                      Token --remote requests over TCP must carry (default: a
+                      random one, printed at startup)
+  --remote-dir <dir>  Directory --remote screenshots are saved in (default:
+                      the current directory)
+  --session <file>    Resume the viewer state saved in <file> and save it
+                      there on exit (default: SCOP_SESSION, or
+                      scop/session.json in the user's state directory)
+  --validation <list> Enable the Vulkan validation layers with extra checks,
+                      comma-separated: core, gpu (GPU-assisted),
+                      best-practices, sync or all; also read from
+                      SCOP_VALIDATION
+  --gpu <gpu>         GPU to render on, by index in the device list or part of
+                      its name, such as nvidia (default: the most capable
+                      one, or SCOP_GPU)
+  --backend <name>    Windowing system on Linux: wayland or x11 (default:
+                      auto, or SCOP_BACKEND)
+  --asset-root <dir>  Directory holding shaders/ and the models, searched
+                      before SCOP_ASSET_DIR, the working directory and the
+                      executable's location
+  -h, --help          Print this help";
+
+/// What to do with the model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Open it in the viewer.
+    View,
+    /// Write it to `output`, in the format its extension names.
+    Convert { output: String },
+    /// Render it to the output image.
+    Thumbnail,
+    /// Run the Rhai script at `path`, which loads the models itself.
+    Script { path: String },
+}
+
+pub struct Options {
+    pub command: Command,
+    pub model: String,
+    /// Whether the model was named on the command line, else the one of the
+    /// session is shown.
+    pub model_given: bool,
+    pub primitive: Option<String>,
+    pub compare: Option<(ShadingMode, ShadingMode)>,
+    /// Paths per pixel of the reference render, none to skip it.
+    pub reference_samples: Option<u32>,
+    pub output: String,
+    /// Second version of the model to compare against.
+    pub diff: Option<String>,
+    pub diff_heatmap: bool,
+    pub size: (u32, u32),
+    pub stress: Option<(u32, u32, u32)>,
+    pub fix_orientation: bool,
+    /// Whether the model's texture coordinates are replaced by
+    /// `Mesh::unwrap_uvs`.
+    pub unwrap: bool,
+    pub material_overrides: MaterialOverrides,
+    /// Rays per vertex of the ambient occlusion bake, none to skip it.
+    pub occlusion_samples: Option<u32>,
+    /// Edge of the baked lightmap, none to keep real-time lighting only.
+    pub lightmap_size: Option<u32>,
+    pub stats_window: bool,
+    /// Source of JSON-RPC commands driving the viewer, none to take none.
+    pub remote: Option<RemoteEndpoint>,
+    /// Token of remote requests over TCP, none for a random one.
+    pub remote_token: Option<String>,
+    /// Directory of remote screenshots, none for the current one.
+    pub remote_dir: Option<String>,
+    /// Session file to resume and save, none for the default one.
+    pub session: Option<String>,
+    pub deterministic: bool,
+    pub profile_load: bool,
+    pub auto_rotation: AutoRotation,
+    /// Width of the debug lines, in pixels.
+    pub line_width: f32,
+    /// Fixed near and far plane distances, none to fit them to the scene.
+    pub clip: Option<(f32, f32)>,
+    pub sort_faces: bool,
+    pub compact_vertices: bool,
+    /// Cubes along the model's longest side, none to draw the model itself.
+    pub voxels: Option<u32>,
+    pub voxel_fill: VoxelFill,
+    pub hull: bool,
+    pub topology: bool,
+    /// Percentage of the triangles the model is simplified to.
+    pub decimate: Option<u32>,
+    /// Loop subdivision levels applied at load.
+    pub subdivide: Option<u32>,
+    /// Scale of the scene under temporal anti-aliasing, none to draw it
+    /// directly.
+    pub temporal: Option<f32>,
+    pub shadows: ShadowQuality,
+    /// Angle the light spans, in degrees.
+    pub light_size: f32,
+    /// Shadow map cascades, none when 0.
+    pub shadow_cascades: usize,
+    pub reflection: bool,
+    pub quad_view: bool,
+    pub stereo: bool,
+    /// Eye distance of the stereo view, in world units.
+    pub ipd: f32,
+    pub cross_eye: bool,
+    /// Whether to render to a VR headset as well.
+    pub xr: bool,
+    pub validation: ValidationFeatures,
+    /// GPU to render on, none to pick the most capable one.
+    pub gpu: Option<GpuSelector>,
+    pub backend: Option<VideoBackend>,
+    pub asset_root: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Command::View,
+            model: "assets/low_poly_fox.obj".to_string(),
+            model_given: false,
+            primitive: None,
+            compare: None,
+            reference_samples: None,
+            output: "compare.png".to_string(),
+            diff: None,
+            diff_heatmap: false,
+            size: (800, 600),
+            stress: None,
+            fix_orientation: false,
+            unwrap: false,
+            material_overrides: MaterialOverrides::default(),
+            occlusion_samples: None,
+            lightmap_size: None,
+            stats_window: false,
+            remote: None,
+            remote_token: None,
+            remote_dir: None,
+            session: None,
+            deterministic: false,
+            profile_load: false,
+            auto_rotation: AutoRotation::default(),
+            line_width: DebugLines::DEFAULT_WIDTH,
+            clip: None,
+            sort_faces: false,
+            compact_vertices: false,
+            voxels: None,
+            voxel_fill: VoxelFill::Surface,
+            hull: false,
+            topology: false,
+            decimate: None,
+            subdivide: None,
+            temporal: None,
+            shadows: ShadowQuality::default(),
+            light_size: ShadowQuality::DEFAULT_LIGHT_SIZE,
+            shadow_cascades: 0,
+            reflection: false,
+            quad_view: false,
+            stereo: false,
+            ipd: Stereo::DEFAULT_IPD,
+            cross_eye: false,
+            xr: false,
+            validation: ValidationFeatures::default(),
+            gpu: None,
+            backend: None,
+            asset_root: None,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the process arguments, the first one being the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter().skip(1).peekable();
+        let command =
+            args.next_if(|arg| matches!(arg.as_str(), "convert" | "thumbnail" | "script"));
+        let (mut positional, mut output, mut size) = (Vec::new(), None, None);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--primitive" => {
+                    options.primitive = Some(Self::value(&mut args, &arg)?);
+                }
+                "--compare" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.compare = Some(Self::parse_pair(&value)?);
+                }
+                "--reference" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.reference_samples = Some(Self::parse_samples(&value)?);
+                }
+                "-o" | "--output" => {
+                    output = Some(Self::value(&mut args, &arg)?);
+                }
+                "--diff" => {
+                    options.diff = Some(Self::value(&mut args, &arg)?);
+                }
+                "--heatmap" => {
+                    options.diff_heatmap = true;
+                }
+                "--size" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    size = Some(Self::parse_size(&value)?);
+                }
+                "--stress" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.stress = Some(Self::parse_grid(&value)?);
+                }
+                "--fix-orientation" => {
+                    options.fix_orientation = true;
+                }
+                "--unwrap" => {
+                    options.unwrap = true;
+                }
+                "--force-color" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.material_overrides.color = Some(Self::parse_color(&value)?);
+                }
+                "--force-material" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    let (name, color) = value.split_once('=').ok_or_else(|| {
+                        format!("Expected a material as <name>=<r>,<g>,<b>, got '{}'", value)
+                    })?;
+                    let color = Self::parse_color(color)?;
+                    options
+                        .material_overrides
+                        .named
+                        .push((name.to_string(), color));
+                }
+                "--ao" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.occlusion_samples = Some(Self::parse_samples(&value)?);
+                }
+                "--lightmap" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    let size = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|size| (16..=8192).contains(size))
+                        .ok_or_else(|| {
+                            format!("Expected a lightmap size in 16..=8192, got '{}'", value)
+                        })?;
+                    options.lightmap_size = Some(size);
+                }
+                "--deterministic" => {
+                    options.deterministic = true;
+                }
+                "--profile-load" => {
+                    options.profile_load = true;
+                }
+                "--clip" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.clip = Some(Self::parse_clip(&value)?);
+                }
+                "--spin-speed" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    let speed = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|speed| speed.is_finite())
+                        .ok_or_else(|| {
+                            format!("Expected a speed in degrees per second, got '{}'", value)
+                        })?;
+                    options.auto_rotation.speed = speed.to_radians();
+                }
+                "--spin-axis" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.auto_rotation.axis = Self::parse_axis(&value)?;
+                }
+                "--sort-faces" => {
+                    options.sort_faces = true;
+                }
+                "--compact-vertices" => {
+                    options.compact_vertices = true;
+                }
+                "--voxels" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    let resolution = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|resolution| (1..=512).contains(resolution))
+                        .ok_or_else(|| {
+                            format!("Expected a voxel resolution in 1..=512, got '{}'", value)
+                        })?;
+                    options.voxels = Some(resolution);
+                }
+                "--voxel-fill" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.voxel_fill = match value.as_str() {
+                        "surface" => VoxelFill::Surface,
+                        "solid" => VoxelFill::Solid,
+                        _ => {
+                            return Err(format!(
+                                "Expected a voxel fill, surface or solid, got '{}'",
+                                value
+                            ))
+                        }
+                    };
+                }
+                "--hull" => {
+                    options.hull = true;
+                }
+                "--topology" => {
+                    options.topology = true;
+                }
+                "--decimate" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    let percent = value
+                        .trim_end_matches('%')
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|percent| (1..=100).contains(percent))
+                        .ok_or_else(|| {
+                            format!("Expected a percentage in 1..=100, got '{}'", value)
+                        })?;
+                    options.decimate = Some(percent);
+                }
+                "--subdivide" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    let levels = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|levels| (1..=App::MAX_SUBDIVISION).contains(levels))
+                        .ok_or_else(|| {
+                            format!(
+                                "Expected 1 to {} subdivision levels, got '{}'",
+                                App::MAX_SUBDIVISION,
+                                value
+                            )
+                        })?;
+                    options.subdivide = Some(levels);
+                }
+                "--taa" => {
+                    options.temporal = options.temporal.or(Some(1.));
+                }
+                "--render-scale" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    let scale = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|scale| (0.25..=1.).contains(scale))
+                        .ok_or_else(|| {
+                            format!("Expected a render scale in 0.25..=1, got '{}'", value)
+                        })?;
+                    options.temporal = Some(scale);
+                }
+                "--shadows" => {
+                    options.shadows = Self::value(&mut args, &arg)?.parse()?;
+                }
+                "--light-size" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.light_size = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|size| (0. ..90.).contains(size))
+                        .ok_or_else(|| {
+                            format!("Expected a light size in 0..90 degrees, got '{}'", value)
+                        })?;
+                }
+                "--shadow-cascades" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.shadow_cascades = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|count| (2..=4).contains(count))
+                        .ok_or_else(|| {
+                            format!("Expected a cascade count in 2..=4, got '{}'", value)
+                        })?;
+                }
+                "--reflection" => {
+                    options.reflection = true;
+                }
+                "--quad-view" => {
+                    options.quad_view = true;
+                }
+                "--stereo" => {
+                    options.stereo = true;
+                }
+                "--ipd" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.ipd = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|ipd| *ipd >= 0. && ipd.is_finite())
+                        .ok_or_else(|| format!("Expected an eye distance, got '{}'", value))?;
+                }
+                "--cross-eye" => {
+                    options.cross_eye = true;
+                }
+                "--xr" => {
+                    options.xr = true;
+                }
+                "--line-width" => {
+                    let value = Self::value(&mut args, &arg)?;
+                    options.line_width = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|width| *width > 0. && width.is_finite())
+                        .ok_or_else(|| {
+                            format!("Expected a line width in pixels, got '{}'", value)
+                        })?;
+                }
+                "--stats-window" => {
+                    options.stats_window = true;
+                }
+                "--remote" => {
+                    options.remote = Some(Self::value(&mut args, &arg)?.parse()?);
+                }
+                "--remote-token" => {
+                    let token = Self::value(&mut args, &arg)?;
+                    if token.is_empty() {
+                        return Err("Expected a non-empty remote token".to_string());
+                    }
+                    options.remote_token = Some(token);
+                }
+                "--remote-dir" => {
+                    options.remote_dir = Some(Self::value(&mut args, &arg)?);
+                }
+                "--session" => {
+                    options.session = Some(Self::value(&mut args, &arg)?);
+                }
+                "--validation" => {
+                    options.validation = Self::value(&mut args, &arg)?.parse()?;
+                }
+                "--gpu" => {
+                    options.gpu = Some(Self::value(&mut args, &arg)?.parse()?);
+                }
+                "--backend" => {
+                    options.backend = Some(Self::value(&mut args, &arg)?.parse()?);
+                }
+                "--asset-root" => {
+                    options.asset_root = Some(Self::value(&mut args, &arg)?);
+                }
+                "-h" | "--help" => {
+                    return Err(USAGE.to_string());
+                }
+                flag if flag.starts_with('-') => {
+                    return Err(format!("Unknown option '{}'\n\n{}", flag, USAGE));
+                }
+                _ => {
+                    positional.push(arg);
+                }
+            }
+        }
+
+        match (command.as_deref(), positional.as_slice()) {
+            (None, [.., model]) => options.model = model.clone(),
+            (None, []) => {}
+            (Some("convert"), [model, output]) => {
+                options.model = model.clone();
+                options.command = Command::Convert {
+                    output: output.clone(),
+                };
+            }
+            (Some("convert"), _) => {
+                return Err(format!(
+                    "convert takes a model and an output path\n\n{}",
+                    USAGE
+                ))
+            }
+            (Some("script"), [path]) => {
+                options.command = Command::Script { path: path.clone() };
+            }
+            (Some("script"), _) => return Err(format!("script takes a script path\n\n{}", USAGE)),
+            (_, [model]) => {
+                options.model = model.clone();
+                options.command = Command::Thumbnail;
+                output = output.or_else(|| Some("thumbnail.png".to_string()));
+                size = size.or(Some((256, 256)));
+            }
+            (_, _) => return Err(format!("thumbnail takes a model\n\n{}", USAGE)),
+        }
+        options.model_given = !positional.is_empty();
+        if let Some(output) = output {
+            options.output = output;
+        }
+        if let Some(size) = size {
+            options.size = size;
+        }
+
+        if options.diff_heatmap && options.diff.is_none() {
+            return Err("--heatmap needs a model to compare against with --diff".to_string());
+        }
+        if options.quad_view && options.stereo {
+            return Err("--quad-view and --stereo can't be combined".to_string());
+        }
+
+        Ok(options)
+    }
+
+    fn value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+        args.next()
+            .ok_or_else(|| format!("Missing value for '{}'", flag))
+    }
+
+    fn parse_axis(value: &str) -> Result<Vec3, String> {
+        match value.trim().to_lowercase().as_str() {
+            "x" => return Ok(Vec3::X),
+            "y" => return Ok(Vec3::Y),
+            "z" => return Ok(Vec3::Z),
+            _ => {}
+        }
+        let error = || {
+            format!(
+                "Expected x, y, z or a non-zero x,y,z vector, got '{}'",
+                value
+            )
+        };
+        let components = value
+            .split(',')
+            .map(|c| c.trim().parse::<f32>().map_err(|_| error()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match components[..] {
+            [x, y, z] => Vec3::new(x, y, z).try_normalize().ok_or_else(error),
+            _ => Err(error()),
+        }
+    }
+
+    fn parse_color(value: &str) -> Result<Vec3, String> {
+        let error = || {
+            format!(
+                "Expected an r,g,b color with components in 0..=1, got '{}'",
+                value
+            )
+        };
+        let components = value
+            .split(',')
+            .map(|c| {
+                c.trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|c| (0. ..=1.).contains(c))
+                    .ok_or_else(error)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match components[..] {
+            [r, g, b] => Ok(Vec3::new(r, g, b)),
+            _ => Err(error()),
+        }
+    }
+
+    fn parse_clip(value: &str) -> Result<(f32, f32), String> {
+        let error = || {
+            format!(
+                "Expected comma-separated near and far distances with 0 < near < far, got '{}'",
+                value
+            )
+        };
+        let (near, far) = value.split_once(',').ok_or_else(error)?;
+        let near: f32 = near.trim().parse().map_err(|_| error())?;
+        let far: f32 = far.trim().parse().map_err(|_| error())?;
+        if near > 0. && near < far && far.is_finite() {
+            Ok((near, far))
+        } else {
+            Err(error())
+        }
+    }
+
+    fn parse_pair(value: &str) -> Result<(ShadingMode, ShadingMode), String> {
+        let (a, b) = value
+            .split_once(',')
+            .ok_or_else(|| format!("Expected two comma-separated modes, got '{}'", value))?;
+        Ok((a.trim().parse()?, b.trim().parse()?))
+    }
+
+    fn parse_size(value: &str) -> Result<(u32, u32), String> {
+        let (w, h) = value.split_once('x').unwrap_or((value, value));
+        let w = w
+            .parse::<u32>()
+            .map_err(|e| format!("Invalid width: {}", e))?;
+        let h = h
+            .parse::<u32>()
+            .map_err(|e| format!("Invalid height: {}", e))?;
+        if w == 0 || h == 0 {
+            return Err("Size must be non-zero".to_string());
+        }
+        Ok((w, h))
+    }
+
+    fn parse_samples(value: &str) -> Result<u32, String> {
+        value
+            .parse::<u32>()
+            .ok()
+            .filter(|&samples| samples > 0)
+            .ok_or_else(|| format!("Expected a sample count, got '{}'", value))
+    }
+
+    fn parse_grid(value: &str) -> Result<(u32, u32, u32), String> {
+        let dims = value
+            .split('x')
+            .map(|d| d.parse::<u32>().ok().filter(|&d| d > 0))
+            .collect::<Option<Vec<_>>>()
+            .filter(|dims| dims.len() == 3)
+            .ok_or_else(|| format!("Expected a grid like 10x10x10, got '{}'", value))?;
+        Ok((dims[0], dims[1], dims[2]))
+    }
+}
@@ -0,0 +1,60 @@
+use image::{Rgba, RgbaImage};
+
+pub struct DiffStats {
+    pub max: u8,
+    pub mean: f32,
+    pub differing_pixels: usize,
+}
+
+/// Places `left` and `right` next to each other in a single image.
+pub fn side_by_side(left: &RgbaImage, right: &RgbaImage) -> RgbaImage {
+    let width = left.width() + right.width();
+    let height = left.height().max(right.height());
+
+    let mut output = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    image::imageops::replace(&mut output, left, 0, 0);
+    image::imageops::replace(&mut output, right, left.width() as i64, 0);
+    output
+}
+
+/// Per-channel absolute difference of two images of the same size, along
+/// with a summary of how much they differ.
+pub fn difference(a: &RgbaImage, b: &RgbaImage) -> Result<(RgbaImage, DiffStats), String> {
+    if a.dimensions() != b.dimensions() {
+        return Err(format!(
+            "Cannot diff images of different sizes: {:?} and {:?}",
+            a.dimensions(),
+            b.dimensions()
+        ));
+    }
+
+    let mut output = RgbaImage::new(a.width(), a.height());
+    let mut max = 0u8;
+    let mut total = 0u64;
+    let mut differing_pixels = 0;
+
+    for ((pa, pb), out) in a.pixels().zip(b.pixels()).zip(output.pixels_mut()) {
+        let mut diff = [0u8; 3];
+        for channel in 0..3 {
+            diff[channel] = pa[channel].abs_diff(pb[channel]);
+            total += diff[channel] as u64;
+        }
+
+        let pixel_max = diff[0].max(diff[1]).max(diff[2]);
+        if pixel_max > 0 {
+            differing_pixels += 1;
+        }
+        max = max.max(pixel_max);
+
+        *out = Rgba([diff[0], diff[1], diff[2], 255]);
+    }
+
+    let channels = (a.width() * a.height() * 3).max(1) as f32;
+    let stats = DiffStats {
+        max,
+        mean: total as f32 / channels,
+        differing_pixels,
+    };
+
+    Ok((output, stats))
+}
@@ -0,0 +1,131 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use std::sync::{Mutex, TryLockError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::session;
+
+/// Call site of a Vulkan call that succeeded, left by `breadcrumb!`.
+#[derive(Debug)]
+pub struct Breadcrumb {
+    pub call: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+}
+
+static LAST_CALL: AtomicPtr<Breadcrumb> = AtomicPtr::new(std::ptr::null_mut());
+static FRAMES: AtomicU64 = AtomicU64::new(0);
+static SWAPCHAIN: Mutex<String> = Mutex::new(String::new());
+static MODEL: Mutex<String> = Mutex::new(String::new());
+
+/// Records that the Vulkan call named by the argument just succeeded here,
+/// for the crash report to tell how far the renderer got. Costs a single
+/// atomic store.
+#[macro_export]
+macro_rules! breadcrumb {
+    ($call:expr) => {{
+        static BREADCRUMB: $crate::crash::Breadcrumb = $crate::crash::Breadcrumb {
+            call: $call,
+            file: file!(),
+            line: line!(),
+        };
+        $crate::crash::leave(&BREADCRUMB);
+    }};
+}
+
+/// See `breadcrumb!`.
+pub fn leave(breadcrumb: &'static Breadcrumb) {
+    LAST_CALL.store(
+        (breadcrumb as *const Breadcrumb).cast_mut(),
+        Ordering::Relaxed,
+    );
+}
+
+/// Counts a frame started.
+pub fn next_frame() {
+    FRAMES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Describes the swapchain in use, or its absence, for the report.
+pub fn set_swapchain(state: String) {
+    *SWAPCHAIN.lock().unwrap_or_else(|e| e.into_inner()) = state;
+}
+
+/// Names the model being loaded or last loaded, for the report.
+pub fn set_model(path: &str) {
+    *MODEL.lock().unwrap_or_else(|e| e.into_inner()) = path.to_string();
+}
+
+/// Makes panics write a crash report holding the panic message, the frame
+/// count, the last successful Vulkan call, the swapchain, the model and a
+/// backtrace, after the usual message. Reports go to `scop/crashes` in the
+/// user's state directory, or the working directory without one.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown");
+        let location = info
+            .location()
+            .map_or("unknown".to_string(), ToString::to_string);
+        let report = report(message, &location);
+        match write(&report) {
+            Ok(path) => eprintln!("Crash report written to '{}'", path.display()),
+            Err(e) => eprintln!("Failed to write crash report: {}\n{}", e, report),
+        }
+    }));
+}
+
+fn report(message: &str, location: &str) -> String {
+    let thread = std::thread::current();
+    // SAFETY: only ever set from a `&'static Breadcrumb`.
+    let last_call = match unsafe { LAST_CALL.load(Ordering::Relaxed).as_ref() } {
+        Some(crumb) => format!("{} at {}:{}", crumb.call, crumb.file, crumb.line),
+        None => "none".to_string(),
+    };
+
+    let mut report = String::new();
+    let _ = writeln!(report, "scop {} crashed", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "panic: {} at {}", message, location);
+    let _ = writeln!(report, "thread: {}", thread.name().unwrap_or("unnamed"));
+    let _ = writeln!(report, "frames started: {}", FRAMES.load(Ordering::Relaxed));
+    let _ = writeln!(report, "last Vulkan call: {}", last_call);
+    let _ = writeln!(report, "swapchain: {}", read(&SWAPCHAIN, "none"));
+    let _ = writeln!(report, "model: {}", read(&MODEL, "none"));
+    let _ = writeln!(report, "\n{}", Backtrace::force_capture());
+    report
+}
+
+/// Contents of `state`, without waiting on a lock the panicking thread may
+/// hold.
+fn read(state: &Mutex<String>, empty: &str) -> String {
+    let value = match state.try_lock() {
+        Ok(value) => value.clone(),
+        Err(TryLockError::Poisoned(e)) => e.into_inner().clone(),
+        Err(TryLockError::WouldBlock) => return "unavailable".to_string(),
+    };
+    if value.is_empty() {
+        empty.to_string()
+    } else {
+        value
+    }
+}
+
+fn write(report: &str) -> std::io::Result<PathBuf> {
+    let dir = session::state_dir().map_or(PathBuf::new(), |dir| dir.join("crashes"));
+    std::fs::create_dir_all(&dir)?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let path = dir.join(format!("crash-{}.txt", time));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
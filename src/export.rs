@@ -0,0 +1,166 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::math::Vec3;
+use crate::scene::{Material, Mesh};
+
+/// Writes `mesh` to `path` in the format its extension names: `obj` (with a
+/// `.mtl` next to it for the materials), `ply` or `glb`.
+pub fn export_mesh(mesh: &Mesh, path: &Path) -> Result<(), String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    let result = match extension.as_str() {
+        "obj" => write_obj(mesh, path),
+        "ply" => std::fs::write(path, ply(mesh)),
+        #[cfg(feature = "gltf")]
+        "glb" => std::fs::write(path, crate::gltf::glb(mesh)),
+        #[cfg(not(feature = "gltf"))]
+        "glb" => return Err("Built without glTF support, rebuild with --features gltf".to_string()),
+        _ => {
+            return Err(format!(
+                "Unknown output format '{}', expected obj, ply or glb",
+                path.display()
+            ))
+        }
+    };
+    result.map_err(|e| format!("Failed to write '{}': {}", path.display(), e))
+}
+
+/// Name of material `index` in exported files, which need one.
+pub fn material_name(material: &Material, index: usize) -> String {
+    if material.name.is_empty() {
+        format!("material_{}", index)
+    } else {
+        material.name.clone()
+    }
+}
+
+fn write_obj(mesh: &Mesh, path: &Path) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "# Written by scop")?;
+
+    let library = path.with_extension("mtl");
+    if !mesh.materials.is_empty() {
+        let name = library.file_name().unwrap_or_default().to_string_lossy();
+        writeln!(out, "mtllib {}", name)?;
+    }
+
+    // Indices are global and 1-based, each group's vertices following the
+    // previous group's.
+    let mut first = 1;
+    for (index, group) in mesh.groups.iter().enumerate() {
+        match group.object.and_then(|object| mesh.objects.get(object)) {
+            Some(name) => writeln!(out, "o {}", name)?,
+            None => writeln!(out, "o group_{}", index)?,
+        }
+        for v in &group.vertices {
+            writeln!(out, "v {} {} {}", v.position.x, v.position.y, v.position.z)?;
+        }
+        for v in &group.vertices {
+            writeln!(out, "vt {} {}", v.uv.x, v.uv.y)?;
+        }
+        for v in &group.vertices {
+            writeln!(out, "vn {} {} {}", v.normal.x, v.normal.y, v.normal.z)?;
+        }
+        if let Some(index) = group.material.filter(|&m| m < mesh.materials.len()) {
+            writeln!(
+                out,
+                "usemtl {}",
+                material_name(&mesh.materials[index], index)
+            )?;
+        }
+        for triangle in group.indices.chunks_exact(3) {
+            write!(out, "f")?;
+            for &i in triangle {
+                let i = first + i as usize;
+                write!(out, " {}/{}/{}", i, i, i)?;
+            }
+            writeln!(out)?;
+        }
+        first += group.vertices.len();
+    }
+    // Each segment gets its own two vertices, following the groups'.
+    for line in &mesh.lines {
+        for p in line {
+            writeln!(out, "v {} {} {}", p.x, p.y, p.z)?;
+        }
+        writeln!(out, "l {} {}", first, first + 1)?;
+        first += 2;
+    }
+    out.flush()?;
+
+    if !mesh.materials.is_empty() {
+        std::fs::write(library, mtl(&mesh.materials))?;
+    }
+    Ok(())
+}
+
+fn mtl(materials: &[Material]) -> String {
+    let mut out = String::from("# Written by scop\n");
+    let vec3 = |v: Vec3| format!("{} {} {}", v.x, v.y, v.z);
+    for (index, material) in materials.iter().enumerate() {
+        let _ = writeln!(out, "\nnewmtl {}", material_name(material, index));
+        let _ = writeln!(out, "Ka {}", vec3(material.ka));
+        let _ = writeln!(out, "Kd {}", vec3(material.kd));
+        let _ = writeln!(out, "Ks {}", vec3(material.ks));
+        let _ = writeln!(out, "Ns {}", material.ns);
+        let _ = writeln!(out, "Ni {}", material.ni);
+        let _ = writeln!(out, "d {}", material.dissolve);
+        let _ = writeln!(out, "illum {}", material.illum);
+        for (key, map) in [
+            ("map_Ka", &material.map_ka),
+            ("map_Kd", &material.map_kd),
+            ("map_Ks", &material.map_ks),
+            ("map_d", &material.map_d),
+            ("disp", &material.disp),
+        ] {
+            if !map.is_empty() {
+                let _ = writeln!(out, "{} {}", key, map);
+            }
+        }
+        if material.two_sided {
+            let _ = writeln!(out, "two_sided 1");
+        }
+    }
+    out
+}
+
+/// Binary little-endian PLY of every group merged, with normals and
+/// texture coordinates.
+fn ply(mesh: &Mesh) -> Vec<u8> {
+    let vertex_count: usize = mesh.groups.iter().map(|g| g.vertices.len()).sum();
+    let mut out = format!(
+        "ply\nformat binary_little_endian 1.0\ncomment Written by scop\n\
+         element vertex {}\n\
+         property float x\nproperty float y\nproperty float z\n\
+         property float nx\nproperty float ny\nproperty float nz\n\
+         property float s\nproperty float t\n\
+         element face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+        vertex_count,
+        mesh.triangle_count()
+    )
+    .into_bytes();
+
+    for v in mesh.groups.iter().flat_map(|g| &g.vertices) {
+        let (p, n) = (v.position, v.normal);
+        for value in [p.x, p.y, p.z, n.x, n.y, n.z, v.uv.x, v.uv.y] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    let mut first = 0;
+    for group in &mesh.groups {
+        for triangle in group.indices.chunks_exact(3) {
+            out.push(3);
+            for &i in triangle {
+                out.extend_from_slice(&(first + i).to_le_bytes());
+            }
+        }
+        first += group.vertices.len() as u32;
+    }
+    out
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::parser::ObjFileParser;
+use crate::scene::Mesh;
+
+/// Run on every model of a gallery once parsed, like the viewer's
+/// orientation repair or subdivision.
+pub type Prepare = Arc<dyn Fn(&mut Mesh) + Send + Sync>;
+
+/// Thread parsing and preparing a model.
+type Preloader = JoinHandle<Result<Mesh, String>>;
+
+/// The OBJ files of a folder, cycled through one at a time. The folder is
+/// scanned again on every step, picking up files added or removed since.
+/// Models are copied before being parsed rather than mapped, as an exporter
+/// may be rewriting them meanwhile.
+pub struct Gallery {
+    folder: String,
+    files: Vec<String>,
+    index: usize,
+    prepare: Prepare,
+    /// Model parsed ahead on a worker thread, with its path.
+    preloader: Option<(String, Preloader)>,
+}
+
+impl Gallery {
+    /// Scans `folder`, starting on its first model in name order. Fails if
+    /// it holds none.
+    pub fn open(folder: &str, prepare: Prepare) -> Result<Self, String> {
+        let files = Self::scan(folder)?;
+        if files.is_empty() {
+            return Err(format!("No OBJ files in '{}'", folder));
+        }
+        Ok(Self {
+            folder: folder.to_string(),
+            files,
+            index: 0,
+            prepare,
+            preloader: None,
+        })
+    }
+
+    /// Paths of the `.obj` files directly in `folder`, sorted.
+    fn scan(folder: &str) -> Result<Vec<String>, String> {
+        let entries = std::fs::read_dir(folder)
+            .map_err(|e| format!("Failed to read folder '{}': {}", folder, e))?;
+        let mut files: Vec<String> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("obj"))
+            })
+            .filter_map(|path| path.to_str().map(str::to_string))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Path of the model shown.
+    pub fn current(&self) -> &str {
+        &self.files[self.index]
+    }
+
+    /// Position of the model shown, from 1, and the number of models.
+    pub fn position(&self) -> (usize, usize) {
+        (self.index + 1, self.files.len())
+    }
+
+    /// The model `step` positions away from the one shown, wrapping around.
+    pub fn neighbour(&self, step: isize) -> &str {
+        let index = (self.index as isize + step).rem_euclid(self.files.len() as isize);
+        &self.files[index as usize]
+    }
+
+    /// Rescans the folder and moves `step` models away from the one shown,
+    /// or from where it was if it is gone. Keeps the model shown when the
+    /// folder cannot be read or is now empty.
+    pub fn step(&mut self, step: isize) -> &str {
+        let mut from = self.index as isize;
+        match Self::scan(&self.folder) {
+            Ok(files) if !files.is_empty() => {
+                let current = self.current();
+                from = match files.binary_search_by(|file| file.as_str().cmp(current)) {
+                    Ok(index) => index as isize,
+                    // Gone, the step counts from between its neighbours.
+                    Err(index) if step > 0 => index as isize - 1,
+                    Err(index) => index as isize,
+                };
+                self.files = files;
+            }
+            Ok(_) => eprintln!("No OBJ files left in '{}'", self.folder),
+            Err(e) => eprintln!("{}", e),
+        }
+        self.index = (from + step).rem_euclid(self.files.len() as isize) as usize;
+        self.current()
+    }
+
+    /// Parses and prepares `path` on a worker thread, unless another model
+    /// is still being preloaded.
+    pub fn preload(&mut self, path: &str) {
+        if self.preloader.is_some() {
+            return;
+        }
+        let prepare = Arc::clone(&self.prepare);
+        let worker = {
+            let path = path.to_string();
+            std::thread::spawn(move || {
+                let mut mesh = ObjFileParser::parse_copied(&path)?;
+                prepare(&mut mesh);
+                Ok(mesh)
+            })
+        };
+        self.preloader = Some((path.to_string(), worker));
+    }
+
+    /// The model preloaded, with its path, once the worker is done.
+    pub fn take_preloaded(&mut self) -> Option<(String, Result<Mesh, String>)> {
+        let (path, worker) = self.preloader.take_if(|(_, w)| w.is_finished())?;
+        Some((path, Self::join(worker)))
+    }
+
+    /// `path` parsed and prepared, waiting for the worker if it is
+    /// preloading it, else on this thread.
+    pub fn load(&mut self, path: &str) -> Result<Mesh, String> {
+        if let Some((_, worker)) = self.preloader.take_if(|(preloaded, _)| preloaded == path) {
+            return Self::join(worker);
+        }
+        let mut mesh = ObjFileParser::parse_copied(path)?;
+        (self.prepare)(&mut mesh);
+        Ok(mesh)
+    }
+
+    fn join(worker: Preloader) -> Result<Mesh, String> {
+        worker
+            .join()
+            .map_err(|_| "Model preloader panicked".to_string())?
+    }
+
+    /// Name of `path` shown in the window, without its folder.
+    pub fn label(path: &str) -> String {
+        Path::new(path)
+            .file_name()
+            .map_or(path.into(), |name| name.to_string_lossy().into_owned())
+    }
+}
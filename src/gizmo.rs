@@ -0,0 +1,210 @@
+use std::f32::consts::TAU;
+use std::fmt;
+
+use crate::camera::Camera;
+use crate::math::{Vec2, Vec3};
+use crate::scene::{DebugLines, Object};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl fmt::Display for GizmoMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            GizmoMode::Translate => "translate",
+            GizmoMode::Rotate => "rotate",
+            GizmoMode::Scale => "scale",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl GizmoAxis {
+    pub const ALL: [GizmoAxis; 3] = [GizmoAxis::X, GizmoAxis::Y, GizmoAxis::Z];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn direction(self) -> Vec3 {
+        match self {
+            GizmoAxis::X => Vec3::X,
+            GizmoAxis::Y => Vec3::Y,
+            GizmoAxis::Z => Vec3::Z,
+        }
+    }
+
+    fn color(self, highlighted: bool) -> Vec3 {
+        if highlighted {
+            return Vec3::new(1., 1., 0.2);
+        }
+        match self {
+            GizmoAxis::X => Vec3::new(0.9, 0.2, 0.2),
+            GizmoAxis::Y => Vec3::new(0.2, 0.9, 0.2),
+            GizmoAxis::Z => Vec3::new(0.2, 0.4, 1.),
+        }
+    }
+}
+
+/// Screen-space view of the gizmo for one frame: everything needed to hit
+/// test handles and turn mouse motion into world-space edits.
+pub struct GizmoView<'a> {
+    pub camera: &'a Camera,
+    pub viewport: Vec2,
+    pub center: Vec3,
+    pub size: f32,
+}
+
+impl GizmoView<'_> {
+    fn project(&self, point: Vec3) -> Option<Vec2> {
+        self.camera.project(point, self.viewport.x, self.viewport.y)
+    }
+}
+
+/// Translate/rotate/scale handles along the world axes, drawn around the
+/// selected object and dragged with the mouse.
+#[derive(Default)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    active: Option<GizmoAxis>,
+}
+
+impl Gizmo {
+    /// Handle distance, in pixels, under which a click grabs an axis.
+    const PICK_RADIUS: f32 = 8.;
+    const CIRCLE_SEGMENTS: usize = 48;
+
+    pub fn is_dragging(&self) -> bool {
+        self.active.is_some()
+    }
+
+    pub fn end_drag(&mut self) {
+        self.active = None;
+    }
+
+    /// Starts dragging the handle under `mouse`, if any.
+    pub fn begin_drag(&mut self, view: &GizmoView, mouse: Vec2) -> bool {
+        self.active = GizmoAxis::ALL
+            .into_iter()
+            .filter_map(|axis| Some((axis, self.distance_to_handle(view, axis, mouse)?)))
+            .filter(|&(_, distance)| distance <= Self::PICK_RADIUS)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(axis, _)| axis);
+        self.active.is_some()
+    }
+
+    /// Applies the mouse motion from `from` to `to` to the object along the
+    /// grabbed axis.
+    pub fn drag(&self, view: &GizmoView, object: &mut Object, from: Vec2, to: Vec2) {
+        let Some(axis) = self.active else {
+            return;
+        };
+        let direction = axis.direction();
+        let (Some(center), Some(tip)) = (
+            view.project(view.center),
+            view.project(view.center + direction * view.size),
+        ) else {
+            return;
+        };
+
+        let screen_axis = tip - center;
+        let length_squared = screen_axis.length_squared();
+        if length_squared < 1. {
+            return;
+        }
+        let along = (to - from).dot(screen_axis) / length_squared;
+
+        match self.mode {
+            GizmoMode::Translate => {
+                object.translate(direction * (along * view.size));
+            }
+            GizmoMode::Scale => {
+                let mut scale = object.scale();
+                let i = axis.index();
+                scale[i] = (scale[i] * (1. + along)).max(0.01);
+                object.set_scale(scale);
+            }
+            GizmoMode::Rotate => {
+                let (a, b) = (from - center, to - center);
+                let mut angle = a.perp_dot(b).atan2(a.dot(b));
+                // Screen Y points down, so the counter-clockwise drag matching a
+                // positive rotation around an axis facing the camera measures
+                // as a negative screen angle.
+                if direction.dot(view.camera.forward()) < 0. {
+                    angle = -angle;
+                }
+                object.rotate(
+                    direction.x * angle,
+                    direction.y * angle,
+                    direction.z * angle,
+                );
+            }
+        }
+    }
+
+    pub fn draw(&self, lines: &mut DebugLines, center: Vec3, size: f32) {
+        for axis in GizmoAxis::ALL {
+            let color = axis.color(self.active == Some(axis));
+            let handle = self.handle(axis, center, size);
+            for segment in handle.windows(2) {
+                lines.line(segment[0], segment[1], color);
+            }
+            if self.mode == GizmoMode::Scale {
+                lines.sphere(center + axis.direction() * size, size * 0.06, color);
+            }
+        }
+    }
+
+    /// Polyline of the handle for `axis`: a line for translate and scale, a
+    /// circle around the axis for rotate.
+    fn handle(&self, axis: GizmoAxis, center: Vec3, size: f32) -> Vec<Vec3> {
+        let direction = axis.direction();
+        match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => vec![center, center + direction * size],
+            GizmoMode::Rotate => {
+                let u = GizmoAxis::ALL[(axis.index() + 1) % 3].direction();
+                let v = direction.cross(u);
+                (0..=Self::CIRCLE_SEGMENTS)
+                    .map(|i| {
+                        let angle = TAU * i as f32 / Self::CIRCLE_SEGMENTS as f32;
+                        center + (u * angle.cos() + v * angle.sin()) * (size * 0.8)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn distance_to_handle(&self, view: &GizmoView, axis: GizmoAxis, mouse: Vec2) -> Option<f32> {
+        let points = self
+            .handle(axis, view.center, view.size)
+            .into_iter()
+            .map(|p| view.project(p))
+            .collect::<Option<Vec<_>>>()?;
+
+        points
+            .windows(2)
+            .map(|segment| distance_to_segment(mouse, segment[0], segment[1]))
+            .min_by(f32::total_cmp)
+    }
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = if ab.length_squared() > 0. {
+        ((p - a).dot(ab) / ab.length_squared()).clamp(0., 1.)
+    } else {
+        0.
+    };
+    (p - (a + ab * t)).length()
+}
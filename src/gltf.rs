@@ -0,0 +1,206 @@
+use std::fmt::Write as _;
+
+use crate::export::material_name;
+use crate::scene::Mesh;
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a;
+const GLB_CHUNK_BIN: u32 = 0x004e_4942;
+
+const GL_FLOAT: u32 = 5126;
+const GL_UNSIGNED_INT: u32 = 5125;
+const GL_ARRAY_BUFFER: u32 = 34962;
+const GL_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Buffer views and accessors of a glTF file being assembled, over one
+/// binary buffer.
+#[derive(Default)]
+struct GltfBuffers {
+    bin: Vec<u8>,
+    views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+impl GltfBuffers {
+    /// Appends `components`, `size` per element, as a new view and an
+    /// accessor of type `kind`, returning the accessor's index.
+    fn push_floats(&mut self, components: &[f32], size: usize, kind: &str, bounds: bool) -> usize {
+        let count = components.len() / size;
+        let mut extra = String::new();
+        if bounds && count > 0 {
+            let (mut min, mut max) = (vec![f32::MAX; size], vec![f32::MIN; size]);
+            for element in components.chunks_exact(size) {
+                for (axis, &value) in element.iter().enumerate() {
+                    min[axis] = min[axis].min(value);
+                    max[axis] = max[axis].max(value);
+                }
+            }
+            let _ = write!(extra, r#","min":{:?},"max":{:?}"#, min, max);
+        }
+        let bytes: Vec<u8> = components.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.push(&bytes, GL_ARRAY_BUFFER, GL_FLOAT, count, kind, &extra)
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes: Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let target = GL_ELEMENT_ARRAY_BUFFER;
+        self.push(&bytes, target, GL_UNSIGNED_INT, indices.len(), "SCALAR", "")
+    }
+
+    fn push(
+        &mut self,
+        bytes: &[u8],
+        target: u32,
+        component_type: u32,
+        count: usize,
+        kind: &str,
+        extra: &str,
+    ) -> usize {
+        self.views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+            self.bin.len(),
+            bytes.len(),
+            target
+        ));
+        self.bin.extend_from_slice(bytes);
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"{}"{}}}"#,
+            self.views.len() - 1,
+            component_type,
+            count,
+            kind,
+            extra
+        ));
+        self.accessors.len() - 1
+    }
+}
+
+/// Binary glTF with a primitive per group and the materials as
+/// metallic-roughness ones. Texture paths are referenced as given.
+pub fn glb(mesh: &Mesh) -> Vec<u8> {
+    let mut buffers = GltfBuffers::default();
+    let mut primitives = Vec::new();
+    for group in &mesh.groups {
+        let positions: Vec<f32> = group
+            .vertices
+            .iter()
+            .flat_map(|v| [v.position.x, v.position.y, v.position.z])
+            .collect();
+        let normals: Vec<f32> = group
+            .vertices
+            .iter()
+            .flat_map(|v| [v.normal.x, v.normal.y, v.normal.z])
+            .collect();
+        // glTF puts the origin of texture coordinates at the top left.
+        let uvs: Vec<f32> = group
+            .vertices
+            .iter()
+            .flat_map(|v| [v.uv.x, 1. - v.uv.y])
+            .collect();
+
+        let position = buffers.push_floats(&positions, 3, "VEC3", true);
+        let normal = buffers.push_floats(&normals, 3, "VEC3", false);
+        let uv = buffers.push_floats(&uvs, 2, "VEC2", false);
+        let indices = buffers.push_indices(&group.indices);
+        let material = group
+            .material
+            .filter(|&m| m < mesh.materials.len())
+            .map(|m| format!(r#","material":{}"#, m))
+            .unwrap_or_default();
+        primitives.push(format!(
+            r#"{{"attributes":{{"POSITION":{},"NORMAL":{},"TEXCOORD_0":{}}},"indices":{}{}}}"#,
+            position, normal, uv, indices, material
+        ));
+    }
+
+    let mut images = Vec::new();
+    let materials: Vec<String> = mesh
+        .materials
+        .iter()
+        .enumerate()
+        .map(|(index, material)| {
+            let kd = material.kd;
+            // The usual mapping from a Phong exponent to a roughness.
+            let roughness = (2. / (material.ns.max(0.) + 2.)).sqrt();
+            let mut texture = String::new();
+            if !material.map_kd.is_empty() {
+                images.push(format!(r#"{{"uri":{}}}"#, json_string(&material.map_kd)));
+                let _ = write!(texture, r#","baseColorTexture":{{"index":{}}}"#, images.len() - 1);
+            }
+            format!(
+                r#"{{"name":{},"pbrMetallicRoughness":{{"baseColorFactor":[{},{},{},{}],"metallicFactor":0,"roughnessFactor":{}{}}},"alphaMode":"{}","doubleSided":{}}}"#,
+                json_string(&material_name(material, index)),
+                kd.x,
+                kd.y,
+                kd.z,
+                material.dissolve,
+                roughness,
+                texture,
+                if material.dissolve < 1. { "BLEND" } else { "OPAQUE" },
+                material.two_sided
+            )
+        })
+        .collect();
+
+    let mut json = format!(
+        r#"{{"asset":{{"version":"2.0","generator":"scop"}},"scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"meshes":[{{"primitives":[{}]}}],"buffers":[{{"byteLength":{}}}],"bufferViews":[{}],"accessors":[{}]"#,
+        primitives.join(","),
+        buffers.bin.len(),
+        buffers.views.join(","),
+        buffers.accessors.join(",")
+    );
+    if !materials.is_empty() {
+        let _ = write!(json, r#","materials":[{}]"#, materials.join(","));
+    }
+    if !images.is_empty() {
+        // Each image is sampled by the texture of the same index.
+        let textures: Vec<String> = (0..images.len())
+            .map(|i| format!(r#"{{"source":{}}}"#, i))
+            .collect();
+        let _ = write!(
+            json,
+            r#","images":[{}],"textures":[{}]"#,
+            images.join(","),
+            textures.join(",")
+        );
+    }
+    json.push('}');
+
+    let mut json = json.into_bytes();
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    let mut bin = buffers.bin;
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+    let mut out = Vec::with_capacity(length);
+    for word in [GLB_MAGIC, 2, length as u32] {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+    for (kind, chunk) in [(GLB_CHUNK_JSON, &json), (GLB_CHUNK_BIN, &bin)] {
+        out.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+/// `value` as a quoted JSON string.
+fn json_string(value: &str) -> String {
+    let mut out = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1,6 +1,23 @@
+#[cfg(target_os = "android")]
+mod android;
 pub mod app;
+pub mod assets;
 pub mod camera;
+pub mod cli;
+pub mod compare;
+pub mod crash;
+pub mod export;
+pub mod gallery;
+pub mod gizmo;
+#[cfg(feature = "gltf")]
+pub mod gltf;
 pub mod math;
 pub mod parser;
+pub mod pose;
+pub mod profile;
+pub mod remote;
 pub mod renderer;
 pub mod scene;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod session;
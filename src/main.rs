@@ -1,15 +1,170 @@
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use scop::{app::App, math::Vec3, scene::Object};
+use image::RgbaImage;
+use scop::{
+    app::{App, VideoBackend},
+    assets,
+    cli::{Command, Options},
+    compare, crash,
+    export::export_mesh,
+    gallery::{Gallery, Prepare},
+    math::Vec3,
+    parser::ObjFileParser,
+    remote::{RemoteControl, RemoteEndpoint},
+    renderer::{GpuSelector, MeshHandle, ShadingMode, Stereo, ValidationFeatures},
+    scene::{Mesh, Object, ObjectHandle, DIFF_COLORS, DIFF_DISSOLVE},
+    session::Session,
+};
+
+/// An object spinning with the app's `AutoRotation`, starting from `rotation`
+/// at `speed` times its angular speed.
+struct Spinner {
+    object: ObjectHandle,
+    rotation: Vec3,
+    speed: f32,
+}
 
 fn main() -> Result<(), String> {
-    let mut app: App = App::new()?;
+    crash::install();
+    let mut options = Options::parse(std::env::args())?;
+    if options.remote == Some(RemoteEndpoint::Stdin) {
+        RemoteControl::claim_stdout()?;
+    }
+    if let Some(root) = &options.asset_root {
+        assets::set_root(PathBuf::from(root))?;
+    }
+
+    let (occlusion_samples, lightmap_size) = (options.occlusion_samples, options.lightmap_size);
+    let (fix_orientation, subdivision) = (options.fix_orientation, options.subdivide);
+    let unwrap = options.unwrap;
+    let overrides = options.material_overrides.clone();
+    let prepare = move |mesh: &mut Mesh| {
+        if !overrides.is_empty() {
+            for name in overrides.unmatched(mesh) {
+                eprintln!("No material named '{}' to override", name);
+            }
+            overrides.apply(mesh);
+        }
+        if fix_orientation {
+            let fix = mesh.fix_orientation();
+            println!(
+                "Orientation: flipped {} triangles ({} whole components), {} normals",
+                fix.flipped_triangles, fix.flipped_components, fix.flipped_normals
+            );
+        }
+        if let Some(levels) = subdivision {
+            let before = mesh.triangle_count();
+            *mesh = mesh.subdivide(levels);
+            println!(
+                "Subdivision: {} levels, {} -> {} triangles",
+                levels,
+                before,
+                mesh.triangle_count()
+            );
+        }
+        if unwrap {
+            let atlas = mesh.unwrap_uvs();
+            println!(
+                "Unwrap: {} charts from {} components ({} planar)",
+                atlas.charts, atlas.components, atlas.planar_components
+            );
+        }
+        if let Some(samples) = occlusion_samples {
+            mesh.bake_occlusion(samples);
+        }
+        if let Some(size) = lightmap_size {
+            mesh.generate_lightmap_uvs(size);
+        }
+    };
+
+    if let Command::Convert { output } = &options.command {
+        return run_convert(&options, output, prepare);
+    }
+
+    let validation = options.validation.union(ValidationFeatures::from_env()?);
+    let backend = match options.backend {
+        Some(backend) => Some(backend),
+        None => VideoBackend::from_env()?,
+    };
+    let gpu = match options.gpu.clone() {
+        Some(gpu) => Some(gpu),
+        None => GpuSelector::from_env()?,
+    };
+    if options.command == Command::Thumbnail {
+        let mut app = App::hidden(backend, validation, gpu)?;
+        return run_thumbnail(&mut app, &options, prepare);
+    }
+    if let Command::Script { path } = &options.command {
+        return run_script(backend, validation, gpu, path, Arc::new(prepare));
+    }
+
+    // Stress tests place the camera themselves.
+    let session_path = match &options.session {
+        _ if options.stress.is_some() => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => Session::default_path(),
+    };
+    let session = session_path.as_deref().and_then(|path| {
+        Session::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            None
+        })
+    });
+    if let Some(model) = session.as_ref().and_then(|session| session.model.clone()) {
+        if !options.model_given && options.primitive.is_none() && options.diff.is_none() {
+            options.model = model;
+        }
+    }
+    // What the session records as shown, restored only onto the same model.
+    let shown_model =
+        (options.primitive.is_none() && options.diff.is_none()).then(|| options.model.clone());
 
-    let mesh_id = app.load_mesh("assets/low_poly_fox.obj")?;
+    let mut app: App = App::new(backend, validation, gpu, options.xr)?;
+    app.set_compact_vertices(options.compact_vertices);
+    app.set_load_profiling(options.profile_load)?;
+
+    let gallery = if options.primitive.is_none()
+        && options.diff.is_none()
+        && Path::new(&options.model).is_dir()
+    {
+        Some(Gallery::open(&options.model, Arc::new(prepare))?)
+    } else {
+        None
+    };
+
+    let mut mesh_ids = match (&options.primitive, &options.diff) {
+        (Some(name), _) => {
+            let mut mesh = Mesh::primitive(name, 100.)?;
+            prepare(&mut mesh);
+            vec![app.add_mesh(&mesh)?]
+        }
+        (None, Some(other)) => load_diff(
+            &mut app,
+            &options.model,
+            other,
+            options.diff_heatmap,
+            prepare,
+        )?,
+        (None, None) => {
+            let model = gallery.as_ref().map_or(&*options.model, Gallery::current);
+            vec![app.load_mesh_with_splash(model, prepare)?]
+        }
+    };
+    let mesh_id = mesh_ids.remove(0);
 
     let obj1 = Object::new(mesh_id);
     // let obj2 = Object::new(mesh_id);
     let obj1_id = app.add_object(obj1);
+    if let Some(gallery) = gallery {
+        app.set_gallery(gallery, obj1_id);
+    }
+    // Second version of a diff, spinning with the first one.
+    let overlays: Vec<ObjectHandle> = mesh_ids
+        .into_iter()
+        .map(|mesh| app.add_object(Object::new(mesh)))
+        .collect();
     // let obj2_id = app.add_object(obj2);
 
     // app.get_object(obj1_id)
@@ -18,23 +173,406 @@ fn main() -> Result<(), String> {
     //     .translate(Vec3::new(100., 0., 0.));
     // app.get_object(obj2_id).translate(Vec3::new(-100., 0., 0.));
 
-    let start = Instant::now();
+    if options.lightmap_size.is_some() {
+        app.bake_lightmaps()?;
+    }
+
+    if let Some((a, b)) = options.compare {
+        return run_compare(&mut app, a, b, &options);
+    }
+
+    if let Some(samples) = options.reference_samples {
+        return run_reference(&mut app, samples, &options);
+    }
+
+    let mut spinners: Vec<Spinner> = std::iter::once(obj1_id)
+        .chain(overlays)
+        .map(|object| Spinner {
+            object,
+            rotation: Vec3::ZERO,
+            speed: 1.,
+        })
+        .collect();
+
+    if let Some(grid) = options.stress {
+        spinners = build_stress_grid(&mut app, mesh_id, obj1_id, grid);
+    }
+
+    if options.deterministic {
+        app.set_deterministic(true);
+    }
+
+    // Under the options below, except the spin ones.
+    match (session, &session_path) {
+        (Some(session), Some(path)) if session.model == shown_model => {
+            app.restore_session(&session);
+            println!("Resumed the session in {}", path.display());
+        }
+        _ => app.set_auto_rotation(options.auto_rotation),
+    }
+
+    app.set_line_width(options.line_width);
+
+    if let Some((near, far)) = options.clip {
+        app.set_clip_planes(near, far);
+    }
+
+    if options.sort_faces {
+        app.set_face_sorted(true);
+    }
+
+    app.set_voxel_grid(
+        options.voxels.unwrap_or(App::DEFAULT_VOXEL_RESOLUTION),
+        options.voxel_fill,
+    );
+    if options.voxels.is_some() {
+        app.set_voxel_preview(true);
+    }
+
+    if options.hull {
+        app.set_show_hulls(true);
+    }
+
+    if options.topology {
+        app.set_show_topology(true);
+    }
+
+    if let Some(percent) = options.decimate {
+        app.set_decimation(percent);
+    }
+
+    if let Some(scale) = options.temporal {
+        app.set_temporal(Some(scale));
+    }
+
+    if options.shadow_cascades > 0 {
+        app.set_shadow_cascades(options.shadow_cascades);
+    }
+    app.set_shadow_quality(options.shadows);
+    app.set_light_size(options.light_size);
+
+    if options.reflection {
+        app.set_reflection(true);
+    }
+
+    if options.quad_view {
+        app.set_quad_view(true);
+    }
+
+    if options.stereo {
+        app.set_stereo(Some(Stereo {
+            ipd: options.ipd,
+            cross_eye: options.cross_eye,
+        }));
+    }
+
+    if options.stats_window {
+        app.set_stats_window(true)?;
+    }
+
+    let remote = options
+        .remote
+        .map(|endpoint| {
+            RemoteControl::open(
+                endpoint,
+                options.remote_token.clone(),
+                options.remote_dir.clone(),
+            )
+        })
+        .transpose()?;
+
+    let mut last_report = Instant::now();
+    let mut frames = 0;
 
     loop {
         if !app.handle_events()? {
             break;
         }
+        if let Some(remote) = &remote {
+            if !remote.poll(&mut app, obj1_id, &prepare) {
+                break;
+            }
+        }
 
         let now = Instant::now();
-        let elapsed = now.duration_since(start).as_millis() as f32 / 1000.;
-        let speed = 2.;
-        let angle = speed * elapsed;
+        let auto_rotation = app.auto_rotation();
 
-        app.get_object(obj1_id).set_rotation(0., angle, 0.);
+        for spinner in &spinners {
+            let r = auto_rotation.orient(spinner.rotation, spinner.speed);
+            app.get_object(spinner.object).set_rotation(r.x, r.y, r.z);
+        }
 
         app.update();
         app.draw();
+
+        frames += 1;
+        let since_report = now.duration_since(last_report);
+        if options.stress.is_some() && since_report >= Duration::from_secs(1) {
+            let stats = app.frame_stats();
+            let seconds = since_report.as_secs_f32();
+            println!(
+                "{:.1} fps ({:.2} ms) | {} objects | {} draw calls | {} triangles | {} occluded | {}",
+                frames as f32 / seconds,
+                seconds * 1000. / frames as f32,
+                spinners.len(),
+                stats.draw_calls,
+                stats.triangles,
+                stats.occluded_groups,
+                app.sync_timings().bound()
+            );
+            last_report = now;
+            frames = 0;
+        }
+    }
+
+    if let Some(path) = &session_path {
+        if let Some(session) = app.session(shown_model) {
+            if let Err(e) = session.save(path) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    app.shutdown();
+    Ok(())
+}
+
+/// Loads `model` and its other version `other`, aligned, either overlaid in
+/// two translucent colors or as a heatmap of `model`'s distance to `other`.
+fn load_diff(
+    app: &mut App,
+    model: &str,
+    other: &str,
+    heatmap: bool,
+    prepare: impl Fn(&mut Mesh),
+) -> Result<Vec<MeshHandle>, String> {
+    let (a, b) = ObjFileParser::parse_pair(model, other)?;
+    if heatmap {
+        let (mut mesh, distance) = a.distance_heatmap(&b);
+        prepare(&mut mesh);
+        println!(
+            "Distance from {} to {}: max {:.4}, mean {:.4}",
+            model, other, distance.max, distance.mean
+        );
+        return Ok(vec![app.add_mesh(&mesh)?]);
+    }
+
+    [(a, DIFF_COLORS[0]), (b, DIFF_COLORS[1])]
+        .into_iter()
+        .map(|(mesh, color)| {
+            let mut mesh = mesh.tinted(color, DIFF_DISSOLVE);
+            prepare(&mut mesh);
+            app.add_mesh(&mesh)
+        })
+        .collect()
+}
+
+/// Lays out `x * y * z` copies of the mesh on a grid, reusing `first` for the
+/// first cell, with pseudo-random orientations, scales and spin speeds.
+fn build_stress_grid(
+    app: &mut App,
+    mesh: MeshHandle,
+    first: ObjectHandle,
+    (x, y, z): (u32, u32, u32),
+) -> Vec<Spinner> {
+    let radius = app.mesh_bounds(mesh).radius().max(1.);
+    let spacing = radius * 2.5;
+    let offset = Vec3::new(x as f32 - 1., y as f32 - 1., z as f32 - 1.) * 0.5;
+
+    let mut seed: u32 = 0x9e37_79b9;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32
+    };
+
+    let mut spinners = Vec::new();
+    for k in 0..z {
+        for j in 0..y {
+            for i in 0..x {
+                let object = if spinners.is_empty() {
+                    first
+                } else {
+                    app.add_object(Object::new(mesh))
+                };
+
+                let cell = Vec3::new(i as f32, j as f32, k as f32) - offset;
+                let rotation = Vec3::new(random(), random(), random()) * std::f32::consts::TAU;
+                app.get_object(object)
+                    .set_position(cell * spacing)
+                    .scale_uniform(0.5 + random() * 0.5);
+
+                spinners.push(Spinner {
+                    object,
+                    rotation,
+                    speed: 0.25 + random() * 1.5,
+                });
+            }
+        }
+    }
+
+    let extent = offset.length() * spacing + radius;
+    let camera = app.camera();
+    camera.position = Vec3::new(0., 0., -extent * 2.5);
+    camera.move_speed = extent;
+
+    println!("Stress test: {} objects", spinners.len());
+    spinners
+}
+
+/// The model or primitive of `options`, prepared and decimated as the viewer
+/// would show it.
+fn load_model(options: &Options, prepare: impl FnOnce(&mut Mesh)) -> Result<Mesh, String> {
+    let mut mesh = match &options.primitive {
+        Some(name) => Mesh::primitive(name, 100.)?,
+        None => ObjFileParser::parse(&options.model)?,
+    };
+    prepare(&mut mesh);
+    if let Some(percent) = options.decimate {
+        let before = mesh.triangle_count();
+        mesh = mesh.simplify(percent as f32 / 100.);
+        println!(
+            "Decimation: {}%, {} -> {} triangles",
+            percent,
+            before,
+            mesh.triangle_count()
+        );
     }
+    Ok(mesh)
+}
+
+#[cfg(feature = "scripting")]
+fn run_script(
+    backend: Option<VideoBackend>,
+    validation: ValidationFeatures,
+    gpu: Option<GpuSelector>,
+    path: &str,
+    prepare: Prepare,
+) -> Result<(), String> {
+    let app = App::new(backend, validation, gpu, false)?;
+    scop::script::run_script(app, path, prepare)
+}
+
+#[cfg(not(feature = "scripting"))]
+fn run_script(
+    _: Option<VideoBackend>,
+    _: ValidationFeatures,
+    _: Option<GpuSelector>,
+    _: &str,
+    _: Prepare,
+) -> Result<(), String> {
+    Err("Built without scripting support, rebuild with --features scripting".to_string())
+}
 
+/// Loads the model like the viewer does, without a window or a GPU, and
+/// writes it to `output`.
+fn run_convert(
+    options: &Options,
+    output: &str,
+    prepare: impl FnOnce(&mut Mesh),
+) -> Result<(), String> {
+    let mesh = load_model(options, prepare)?;
+    export_mesh(&mesh, Path::new(output))?;
+    println!("Wrote {} ({} triangles)", output, mesh.triangle_count());
     Ok(())
 }
+
+/// Renders the model framed from the front-left and slightly above to the
+/// output image.
+fn run_thumbnail(
+    app: &mut App,
+    options: &Options,
+    prepare: impl FnOnce(&mut Mesh),
+) -> Result<(), String> {
+    let mesh = load_model(options, prepare)?;
+    let mesh = app.add_mesh(&mesh)?;
+    app.add_object(Object::new(mesh));
+
+    let (width, height) = options.size;
+    app.frame_scene(Vec3::new(0.5, -0.4, 1.), width as f32 / height as f32);
+    let image = app.capture(width, height)?;
+    image
+        .save(&options.output)
+        .map_err(|e| format!("Failed to save '{}': {}", options.output, e))?;
+    println!("Wrote {}", options.output);
+    Ok(())
+}
+
+fn run_compare(
+    app: &mut App,
+    a: ShadingMode,
+    b: ShadingMode,
+    options: &Options,
+) -> Result<(), String> {
+    let (width, height) = options.size;
+
+    app.set_shading_mode(a);
+    let image_a = app.capture(width, height)?;
+    app.set_shading_mode(b);
+    let image_b = app.capture(width, height)?;
+
+    write_comparison(&image_a, &image_b, &a.to_string(), &b.to_string(), options)
+}
+
+/// Compares the lit rendering against a path-traced reference, also written
+/// on its own as `<output>_reference`.
+fn run_reference(app: &mut App, samples: u32, options: &Options) -> Result<(), String> {
+    let (width, height) = options.size;
+
+    app.set_shading_mode(ShadingMode::Lit);
+    let lit = app.capture(width, height)?;
+    let reference = app.render_reference(width, height, samples)?;
+
+    let reference_output = output_variant(&options.output, "reference");
+    reference
+        .save(&reference_output)
+        .map_err(|e| format!("Failed to save '{}': {}", reference_output.display(), e))?;
+    println!("Wrote {}", reference_output.display());
+
+    write_comparison(&lit, &reference, "lit", "reference", options)
+}
+
+/// Writes `a` and `b` side by side to the output path and their difference
+/// next to it, and prints how much they differ.
+fn write_comparison(
+    a: &RgbaImage,
+    b: &RgbaImage,
+    a_name: &str,
+    b_name: &str,
+    options: &Options,
+) -> Result<(), String> {
+    let (diff, stats) = compare::difference(a, b)?;
+    let combined = compare::side_by_side(a, b);
+
+    let output = Path::new(&options.output);
+    let diff_output = output_variant(&options.output, "diff");
+
+    combined
+        .save(output)
+        .map_err(|e| format!("Failed to save '{}': {}", output.display(), e))?;
+    diff.save(&diff_output)
+        .map_err(|e| format!("Failed to save '{}': {}", diff_output.display(), e))?;
+
+    println!(
+        "{} vs {}: {} differing pixels, max channel diff {}, mean {:.3}",
+        a_name, b_name, stats.differing_pixels, stats.max, stats.mean
+    );
+    println!("Wrote {} and {}", output.display(), diff_output.display());
+
+    Ok(())
+}
+
+/// `output` with `_<suffix>` appended to its file stem.
+fn output_variant(output: &str, suffix: &str) -> PathBuf {
+    let output = Path::new(output);
+    output.with_file_name(format!(
+        "{}_{}.{}",
+        output
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("compare"),
+        suffix,
+        output.extension().and_then(|s| s.to_str()).unwrap_or("png"),
+    ))
+}
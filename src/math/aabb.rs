@@ -0,0 +1,126 @@
+use crate::math::{Mat4, Vec3, Vec4};
+
+/// Axis-aligned bounding box. An empty box has `min > max`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::EMPTY
+    }
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3 {
+            x: f32::INFINITY,
+            y: f32::INFINITY,
+            z: f32::INFINITY,
+        },
+        max: Vec3 {
+            x: f32::NEG_INFINITY,
+            y: f32::NEG_INFINITY,
+            z: f32::NEG_INFINITY,
+        },
+    };
+
+    #[inline]
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Self {
+        points.into_iter().fold(Self::EMPTY, |acc, p| acc.extend(p))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// Returns the box grown to contain `point`.
+    #[inline]
+    pub fn extend(self, point: Vec3) -> Self {
+        Self::new(self.min.min(point), self.max.max(point))
+    }
+
+    #[inline]
+    pub fn union(self, other: Aabb) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Returns the box grown by `margin` on every side.
+    #[inline]
+    pub fn grow(self, margin: f32) -> Self {
+        Self::new(
+            self.min - Vec3::splat(margin),
+            self.max + Vec3::splat(margin),
+        )
+    }
+
+    #[inline]
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.x >= self.min.x
+            && point.y >= self.min.y
+            && point.z >= self.min.z
+            && point.x <= self.max.x
+            && point.y <= self.max.y
+            && point.z <= self.max.z
+    }
+
+    #[inline]
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    #[inline]
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z),
+            Vec3::new(b.x, a.y, a.z),
+            Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z),
+            Vec3::new(b.x, a.y, b.z),
+            Vec3::new(b.x, b.y, b.z),
+            Vec3::new(a.x, b.y, b.z),
+        ]
+    }
+
+    /// Bounds of the box once transformed by `m`.
+    pub fn transform(&self, m: &Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points(
+            self.corners()
+                .into_iter()
+                .map(|p| m.mul_vec4(Vec4::new(p.x, p.y, p.z, 1.)).xyz()),
+        )
+    }
+
+    /// Squared distance from `point` to the box, 0 inside it.
+    #[inline]
+    pub fn distance_squared(&self, point: Vec3) -> f32 {
+        let outside = (self.min - point).max(point - self.max).max(Vec3::ZERO);
+        outside.length_squared()
+    }
+
+    /// Radius of the sphere centered on the box that encloses it.
+    #[inline]
+    pub fn radius(&self) -> f32 {
+        if self.is_empty() {
+            0.
+        } else {
+            self.size().length() * 0.5
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::math::{Aabb, Obb, Vec2, Vec3};
+
+/// Distance below which points count as on a hull face, relative to the size
+/// of the point set.
+const HULL_EPSILON: f32 = 1e-6;
+
+/// Smallest convex shape containing a point set, as triangles wound counter-
+/// clockwise seen from outside. Flat point sets give a single-sided polygon
+/// facing an arbitrary side, collinear ones no triangles at all.
+#[derive(Debug, Clone, Default)]
+pub struct ConvexHull {
+    /// Points of the set on the hull's corners.
+    pub points: Vec<Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl ConvexHull {
+    /// Quickhull: starts from a tetrahedron of extreme points, then
+    /// repeatedly adds the point farthest outside a face, replacing the faces
+    /// it sees.
+    pub fn build(points: &[Vec3]) -> Self {
+        let bounds = Aabb::from_points(points.iter().copied());
+        if bounds.is_empty() {
+            return Self::default();
+        }
+        let epsilon = bounds.radius().max(f32::MIN_POSITIVE) * HULL_EPSILON;
+
+        let farthest = |distance: &dyn Fn(Vec3) -> f32| {
+            (0..points.len()).map(|i| (i, distance(points[i]))).fold(
+                (0, f32::NEG_INFINITY),
+                |best, candidate| {
+                    if candidate.1 > best.1 {
+                        candidate
+                    } else {
+                        best
+                    }
+                },
+            )
+        };
+
+        // Two extreme points along an axis, the farthest from their line, and
+        // the farthest from the plane of the three.
+        let extremes: Vec<usize> = [Vec3::X, Vec3::Y, Vec3::Z]
+            .into_iter()
+            .flat_map(|axis| [farthest(&|p| p.dot(axis)).0, farthest(&|p| -p.dot(axis)).0])
+            .collect();
+        let (a, b) = extremes
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .max_by(|x, y| {
+                let length = |(i, j): (usize, usize)| (points[i] - points[j]).length_squared();
+                length(*x).total_cmp(&length(*y))
+            })
+            .unwrap_or((0, 0));
+        let line = points[b] - points[a];
+        let (c, line_distance) = farthest(&|p| {
+            (p - points[a]).cross(line).length() / line.length().max(f32::MIN_POSITIVE)
+        });
+        if line_distance <= epsilon {
+            return Self::from_corners(
+                points,
+                if a == b { vec![a] } else { vec![a, b] },
+                Vec::new(),
+            );
+        }
+        let normal = line.cross(points[c] - points[a]).normalize();
+        let (d, plane_distance) = farthest(&|p| (p - points[a]).dot(normal).abs());
+        if plane_distance <= epsilon {
+            return Self::planar(points, points[a], line.normalize(), normal, epsilon);
+        }
+
+        let mut faces: Vec<HullFace> = Vec::new();
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for (corners, opposite) in [
+            ([a, b, c], d),
+            ([a, b, d], c),
+            ([b, c, d], a),
+            ([c, a, d], b),
+        ] {
+            let mut face = HullFace::new(points, corners);
+            if face.distance(points[opposite]) > 0. {
+                face = HullFace::new(points, [corners[0], corners[2], corners[1]]);
+            }
+            face.register(&mut edges, faces.len());
+            faces.push(face);
+        }
+        let seeds: Vec<usize> = (0..points.len())
+            .filter(|i| ![a, b, c, d].contains(i))
+            .collect();
+        Self::assign(points, &mut faces, 0, seeds, epsilon);
+
+        // Faces only gain outside points when created, so one pass over the
+        // growing list handles them all.
+        let mut current = 0;
+        while current < faces.len() {
+            if !faces[current].alive || faces[current].outside.is_empty() {
+                current += 1;
+                continue;
+            }
+            let face = &faces[current];
+            let apex = *face
+                .outside
+                .iter()
+                .max_by(|&&i, &&j| {
+                    face.distance(points[i])
+                        .total_cmp(&face.distance(points[j]))
+                })
+                .unwrap_or(&face.outside[0]);
+            let apex_point = points[apex];
+
+            // Faces seeing the apex, found across edges from the current one.
+            let mut visible = vec![current];
+            let mut seen: HashSet<usize> = HashSet::from([current]);
+            let mut horizon: Vec<(usize, usize)> = Vec::new();
+            let mut next = 0;
+            while next < visible.len() {
+                let [p, q, r] = faces[visible[next]].corners;
+                for (from, to) in [(p, q), (q, r), (r, p)] {
+                    let Some(&neighbor) = edges.get(&(to, from)) else {
+                        horizon.push((from, to));
+                        continue;
+                    };
+                    if seen.contains(&neighbor) {
+                        continue;
+                    }
+                    if faces[neighbor].distance(apex_point) > epsilon {
+                        seen.insert(neighbor);
+                        visible.push(neighbor);
+                    } else {
+                        horizon.push((from, to));
+                    }
+                }
+                next += 1;
+            }
+
+            let mut orphans: Vec<usize> = Vec::new();
+            for &index in &visible {
+                faces[index].alive = false;
+                orphans.append(&mut faces[index].outside);
+            }
+            orphans.retain(|&i| i != apex);
+
+            let first = faces.len();
+            for (from, to) in horizon {
+                let face = HullFace::new(points, [from, to, apex]);
+                face.register(&mut edges, faces.len());
+                faces.push(face);
+            }
+            Self::assign(points, &mut faces, first, orphans, epsilon);
+        }
+
+        let triangles: Vec<[usize; 3]> = faces
+            .iter()
+            .filter(|face| face.alive)
+            .map(|face| face.corners)
+            .collect();
+        let corners: Vec<usize> = triangles.iter().flatten().copied().collect();
+        Self::from_corners(points, corners, triangles)
+    }
+
+    /// Hull of points lying in the plane through `origin` spanned by `u` and
+    /// `normal.cross(u)`, as a fan facing `normal`.
+    fn planar(points: &[Vec3], origin: Vec3, u: Vec3, normal: Vec3, epsilon: f32) -> Self {
+        let v = normal.cross(u);
+        let projected: Vec<Vec2> = points
+            .iter()
+            .map(|&p| Vec2::new((p - origin).dot(u), (p - origin).dot(v)))
+            .collect();
+        let outline = convex_hull_2d(&projected, epsilon);
+        let triangles = (1..outline.len().saturating_sub(1))
+            .map(|i| [outline[0], outline[i], outline[i + 1]])
+            .collect();
+        Self::from_corners(points, outline, triangles)
+    }
+
+    /// Keeps only the points at `corners`, renumbering `triangles`.
+    fn from_corners(points: &[Vec3], corners: Vec<usize>, triangles: Vec<[usize; 3]>) -> Self {
+        let mut hull = Self::default();
+        let mut remap: HashMap<usize, u32> = HashMap::new();
+        for corner in corners {
+            remap.entry(corner).or_insert_with(|| {
+                hull.points.push(points[corner]);
+                hull.points.len() as u32 - 1
+            });
+        }
+        hull.triangles = triangles
+            .into_iter()
+            .map(|triangle| triangle.map(|corner| remap[&corner]))
+            .collect();
+        hull
+    }
+
+    /// Gives each point to the first face from `first` on it lies outside
+    /// of, dropping the points inside them all.
+    fn assign(
+        points: &[Vec3],
+        faces: &mut [HullFace],
+        first: usize,
+        candidates: Vec<usize>,
+        epsilon: f32,
+    ) {
+        for point in candidates {
+            if let Some(face) = faces[first..]
+                .iter_mut()
+                .find(|face| face.distance(points[point]) > epsilon)
+            {
+                face.outside.push(point);
+            }
+        }
+    }
+
+    /// Every edge of the hull once.
+    pub fn edges(&self) -> Vec<[u32; 2]> {
+        let mut edges: HashSet<[u32; 2]> = HashSet::new();
+        for &[a, b, c] in &self.triangles {
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                edges.insert([from.min(to), from.max(to)]);
+            }
+        }
+        edges.into_iter().collect()
+    }
+
+    /// Smallest box found with a face against one of the hull's faces, which
+    /// is the minimal box in most cases but not all. Its other axes are those
+    /// of the smallest rectangle around the hull projected on that face.
+    pub fn oriented_box(&self) -> Obb {
+        let fallback = Obb::from_aabb(&Aabb::from_points(self.points.iter().copied()));
+        let epsilon = fallback.half_extents.length().max(f32::MIN_POSITIVE) * HULL_EPSILON;
+
+        let mut normals: Vec<Vec3> = Vec::new();
+        let mut seen: HashSet<[i32; 3]> = HashSet::new();
+        for &[a, b, c] in &self.triangles {
+            let [a, b, c] = [a, b, c].map(|i| self.points[i as usize]);
+            let Some(normal) = (b - a).cross(c - a).try_normalize() else {
+                continue;
+            };
+            // Opposite faces give the same boxes.
+            let dominant = [normal.x, normal.y, normal.z]
+                .into_iter()
+                .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap_or(0.);
+            let normal = if dominant < 0. { -normal } else { normal };
+            if seen.insert([normal.x, normal.y, normal.z].map(|x| (x * 1e4).round() as i32)) {
+                normals.push(normal);
+            }
+        }
+
+        let mut best: Option<(f32, f32, Obb)> = None;
+        for w in normals {
+            let helper = if w.x.abs() < 0.9 { Vec3::X } else { Vec3::Y };
+            let u = w.cross(helper).normalize();
+            let v = w.cross(u);
+            let projected: Vec<Vec2> = self
+                .points
+                .iter()
+                .map(|&p| Vec2::new(p.dot(u), p.dot(v)))
+                .collect();
+            let (depth_min, depth_max) = self
+                .points
+                .iter()
+                .map(|&p| p.dot(w))
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), d| {
+                    (lo.min(d), hi.max(d))
+                });
+
+            let outline: Vec<Vec2> = convex_hull_2d(&projected, epsilon)
+                .into_iter()
+                .map(|i| projected[i])
+                .collect();
+            for i in 0..outline.len() {
+                let Some(x) = (outline[(i + 1) % outline.len()] - outline[i]).try_normalize()
+                else {
+                    continue;
+                };
+                let y = Vec2::new(-x.y, x.x);
+                let range = |axis: Vec2| {
+                    outline
+                        .iter()
+                        .map(|p| p.dot(axis))
+                        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), d| {
+                            (lo.min(d), hi.max(d))
+                        })
+                };
+                let ((x_min, x_max), (y_min, y_max)) = (range(x), range(y));
+                let size = Vec3::new(x_max - x_min, y_max - y_min, depth_max - depth_min);
+                let (volume, area) = (size.x * size.y * size.z, size.x * size.y);
+                if best
+                    .as_ref()
+                    .is_some_and(|&(v, a, _)| (v, a) <= (volume, area))
+                {
+                    continue;
+                }
+
+                let axis_x = u * x.x + v * x.y;
+                let axis_y = u * y.x + v * y.y;
+                let center = axis_x * ((x_min + x_max) * 0.5)
+                    + axis_y * ((y_min + y_max) * 0.5)
+                    + w * ((depth_min + depth_max) * 0.5);
+                let obb = Obb {
+                    center,
+                    axes: [axis_x, axis_y, w],
+                    half_extents: size * 0.5,
+                };
+                best = Some((volume, area, obb));
+            }
+        }
+        best.map_or(fallback, |(_, _, obb)| obb)
+    }
+}
+
+struct HullFace {
+    corners: [usize; 3],
+    normal: Vec3,
+    offset: f32,
+    /// Points outside the face not yet on the hull.
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+impl HullFace {
+    fn new(points: &[Vec3], corners: [usize; 3]) -> Self {
+        let [a, b, c] = corners.map(|i| points[i]);
+        let normal = (b - a).cross(c - a).try_normalize().unwrap_or(Vec3::ZERO);
+        Self {
+            corners,
+            normal,
+            offset: normal.dot(a),
+            outside: Vec::new(),
+            alive: true,
+        }
+    }
+
+    /// Signed distance of `point` to the face's plane, positive outside.
+    fn distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) - self.offset
+    }
+
+    /// Records the face as the owner of its directed edges.
+    fn register(&self, edges: &mut HashMap<(usize, usize), usize>, index: usize) {
+        let [a, b, c] = self.corners;
+        for edge in [(a, b), (b, c), (c, a)] {
+            edges.insert(edge, index);
+        }
+    }
+}
+
+/// Indices of the points on the convex hull of `points`, counter-clockwise,
+/// dropping points within `epsilon` of an edge.
+fn convex_hull_2d(points: &[Vec2], epsilon: f32) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by(|&i, &j| {
+        points[i]
+            .x
+            .total_cmp(&points[j].x)
+            .then(points[i].y.total_cmp(&points[j].y))
+    });
+    if order.len() < 3 {
+        return order;
+    }
+
+    // Andrew's monotone chain, lower then upper half.
+    let turns_left = |hull: &[usize], next: usize| {
+        let [a, b] = [hull[hull.len() - 2], hull[hull.len() - 1]].map(|i| points[i]);
+        let (ab, an) = (b - a, points[next] - a);
+        ab.perp_dot(an) > epsilon * ab.length()
+    };
+    let mut hull: Vec<usize> = Vec::new();
+    for pass in [order.clone(), order.into_iter().rev().collect()] {
+        let start = hull.len();
+        for next in pass {
+            while hull.len() >= start + 2 && !turns_left(&hull, next) {
+                hull.pop();
+            }
+            hull.push(next);
+        }
+        hull.pop();
+    }
+    hull
+}
@@ -125,6 +125,87 @@ impl Mat4 {
     pub fn mul_vec4(&self, v: Vec4) -> Vec4 {
         self.x_axis * v.x + self.y_axis * v.y + self.z_axis * v.z + self.w_axis * v.w
     }
+
+    /// Inverse by cofactor expansion, or `None` for a singular matrix.
+    pub fn inverse(&self) -> Option<Self> {
+        let m: [f32; 16] = std::array::from_fn(|i| self.get(i / 4, i % 4));
+        let mut inv = [0f32; 16];
+
+        inv[0] = m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15]
+            + m[9] * m[7] * m[14]
+            + m[13] * m[6] * m[11]
+            - m[13] * m[7] * m[10];
+        inv[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15]
+            - m[8] * m[7] * m[14]
+            - m[12] * m[6] * m[11]
+            + m[12] * m[7] * m[10];
+        inv[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15]
+            + m[8] * m[7] * m[13]
+            + m[12] * m[5] * m[11]
+            - m[12] * m[7] * m[9];
+        inv[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14]
+            - m[8] * m[6] * m[13]
+            - m[12] * m[5] * m[10]
+            + m[12] * m[6] * m[9];
+        inv[1] = -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15]
+            - m[9] * m[3] * m[14]
+            - m[13] * m[2] * m[11]
+            + m[13] * m[3] * m[10];
+        inv[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15]
+            + m[8] * m[3] * m[14]
+            + m[12] * m[2] * m[11]
+            - m[12] * m[3] * m[10];
+        inv[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15]
+            - m[8] * m[3] * m[13]
+            - m[12] * m[1] * m[11]
+            + m[12] * m[3] * m[9];
+        inv[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14]
+            + m[8] * m[2] * m[13]
+            + m[12] * m[1] * m[10]
+            - m[12] * m[2] * m[9];
+        inv[2] = m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15]
+            + m[5] * m[3] * m[14]
+            + m[13] * m[2] * m[7]
+            - m[13] * m[3] * m[6];
+        inv[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15]
+            - m[4] * m[3] * m[14]
+            - m[12] * m[2] * m[7]
+            + m[12] * m[3] * m[6];
+        inv[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15]
+            + m[4] * m[3] * m[13]
+            + m[12] * m[1] * m[7]
+            - m[12] * m[3] * m[5];
+        inv[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14]
+            - m[4] * m[2] * m[13]
+            - m[12] * m[1] * m[6]
+            + m[12] * m[2] * m[5];
+        inv[3] = -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11]
+            - m[5] * m[3] * m[10]
+            - m[9] * m[2] * m[7]
+            + m[9] * m[3] * m[6];
+        inv[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11]
+            + m[4] * m[3] * m[10]
+            + m[8] * m[2] * m[7]
+            - m[8] * m[3] * m[6];
+        inv[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11]
+            - m[4] * m[3] * m[9]
+            - m[8] * m[1] * m[7]
+            + m[8] * m[3] * m[5];
+        inv[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10]
+            + m[4] * m[2] * m[9]
+            + m[8] * m[1] * m[6]
+            - m[8] * m[2] * m[5];
+
+        let det = m[0] * inv[0] + m[1] * inv[4] + m[2] * inv[8] + m[3] * inv[12];
+        if det.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let inv_det = 1. / det;
+        Some(Self::from_cols_array(std::array::from_fn(|col| {
+            std::array::from_fn(|row| inv[col * 4 + row] * inv_det)
+        })))
+    }
 }
 
 impl Mat4 {
@@ -155,6 +236,43 @@ impl Mat4 {
             Vec4::new(0., 0., two_near_far / range, 0.),
         )
     }
+
+    /// Perspective projection like `projection` through a window off the view
+    /// axis, given by the tangents of its left, right, top and bottom angles
+    /// (left and bottom negative), as VR headsets have for each eye.
+    pub fn off_axis_projection(
+        (left, right, up, down): (f32, f32, f32, f32),
+        near: f32,
+        far: f32,
+    ) -> Self {
+        let range = near - far;
+        let two_near_far = 2. * near * far;
+
+        Self::from_cols(
+            Vec4::new(2. / (right - left), 0., 0., 0.),
+            Vec4::new(0., -2. / (up - down), 0., 0.),
+            Vec4::new(
+                (right + left) / (right - left),
+                -(up + down) / (up - down),
+                (far + near) / range,
+                -1.,
+            ),
+            Vec4::new(0., 0., two_near_far / range, 0.),
+        )
+    }
+
+    /// Orthographic projection of a box `half_width` by `half_height` around
+    /// the view axis (right-handed, depth 0..1 from `near` to `far`).
+    pub fn orthographic(half_width: f32, half_height: f32, near: f32, far: f32) -> Self {
+        let range = far - near;
+
+        Self::from_cols(
+            Vec4::new(1. / half_width, 0., 0., 0.),
+            Vec4::new(0., -1. / half_height, 0., 0.),
+            Vec4::new(0., 0., -1. / range, 0.),
+            Vec4::new(0., 0., -near / range, 1.),
+        )
+    }
 }
 
 impl Mat4 {
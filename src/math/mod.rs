@@ -1,5 +1,15 @@
+mod aabb;
+mod hull;
 mod mat;
+mod obb;
+mod quat;
+mod ray;
 mod vec;
 
+pub use aabb::*;
+pub use hull::*;
 pub use mat::*;
+pub use obb::*;
+pub use quat::*;
+pub use ray::*;
 pub use vec::*;
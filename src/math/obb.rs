@@ -0,0 +1,49 @@
+use crate::math::{Aabb, Mat4, Vec3, Vec4};
+
+/// Oriented bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Obb {
+    pub center: Vec3,
+    /// Orthonormal directions of the box edges.
+    pub axes: [Vec3; 3],
+    /// Half the size of the box along each of `axes`.
+    pub half_extents: Vec3,
+}
+
+impl Obb {
+    pub fn from_aabb(bounds: &Aabb) -> Self {
+        Self {
+            center: bounds.center(),
+            axes: [Vec3::X, Vec3::Y, Vec3::Z],
+            half_extents: bounds.size() * 0.5,
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        let Vec3 { x, y, z } = self.half_extents * 2.;
+        x * y * z
+    }
+
+    /// Corners in the order of `Aabb::corners`, along the box's own axes.
+    pub fn corners(&self) -> [Vec3; 8] {
+        let [u, v, w] = self.axes;
+        let Vec3 { x, y, z } = self.half_extents;
+        [
+            (-1., -1., -1.),
+            (1., -1., -1.),
+            (1., 1., -1.),
+            (-1., 1., -1.),
+            (-1., -1., 1.),
+            (1., -1., 1.),
+            (1., 1., 1.),
+            (-1., 1., 1.),
+        ]
+        .map(|(a, b, c)| self.center + u * (a * x) + v * (b * y) + w * (c * z))
+    }
+
+    /// Corners once transformed by `m`, which may shear the box.
+    pub fn transformed_corners(&self, m: &Mat4) -> [Vec3; 8] {
+        self.corners()
+            .map(|p| m.mul_vec4(Vec4::from_vec3(p, 1.)).xyz())
+    }
+}
@@ -0,0 +1,178 @@
+use std::ops::{Add, Mul, Neg};
+
+use crate::math::{Mat4, Vec3, Vec4};
+
+/// Unit quaternion rotation, stored as `(x, y, z, w)` like glTF.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    #[inline]
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (s, c) = (angle * 0.5).sin_cos();
+        Self::new(axis.x * s, axis.y * s, axis.z * s, c)
+    }
+
+    /// Rotation equal to the `Object` convention `Rx(pitch) * Ry(yaw) * Rz(roll)`.
+    pub fn from_euler(euler: Vec3) -> Self {
+        Self::from_axis_angle(Vec3::X, euler.x)
+            * Self::from_axis_angle(Vec3::Y, euler.y)
+            * Self::from_axis_angle(Vec3::Z, euler.z)
+    }
+
+    /// Inverse of `from_euler`, with yaw in [-π/2, π/2].
+    pub fn to_euler(self) -> Vec3 {
+        let Quat { x, y, z, w } = self;
+        let r00 = 1. - 2. * (y * y + z * z);
+        let r01 = 2. * (x * y - w * z);
+        let r02 = 2. * (x * z + w * y);
+        let r12 = 2. * (y * z - w * x);
+        let r22 = 1. - 2. * (x * x + y * y);
+
+        Vec3::new(
+            (-r12).atan2(r22),
+            r02.clamp(-1., 1.).asin(),
+            (-r01).atan2(r00),
+        )
+    }
+
+    #[inline]
+    pub fn dot(self, rhs: Self) -> f32 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    #[inline]
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len <= f32::EPSILON {
+            Self::IDENTITY
+        } else {
+            self * (1. / len)
+        }
+    }
+
+    /// Normalized linear interpolation along the shortest arc.
+    pub fn nlerp(self, rhs: Self, t: f32) -> Self {
+        let rhs = if self.dot(rhs) < 0. { -rhs } else { rhs };
+        (self * (1. - t) + rhs * t).normalize()
+    }
+
+    /// Spherical interpolation along the shortest arc.
+    pub fn slerp(self, rhs: Self, t: f32) -> Self {
+        let mut cos = self.dot(rhs);
+        let rhs = if cos < 0. {
+            cos = -cos;
+            -rhs
+        } else {
+            rhs
+        };
+
+        if cos > 0.9995 {
+            return self.nlerp(rhs, t);
+        }
+
+        let angle = cos.acos();
+        let sin = angle.sin();
+        let a = ((1. - t) * angle).sin() / sin;
+        let b = (t * angle).sin() / sin;
+        self * a + rhs * b
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        let Quat { x, y, z, w } = self;
+        Mat4::from_cols(
+            Vec4::new(
+                1. - 2. * (y * y + z * z),
+                2. * (x * y + w * z),
+                2. * (x * z - w * y),
+                0.,
+            ),
+            Vec4::new(
+                2. * (x * y - w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z + w * x),
+                0.,
+            ),
+            Vec4::new(
+                2. * (x * z + w * y),
+                2. * (y * z - w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ),
+            Vec4::new(0., 0., 0., 1.),
+        )
+    }
+}
+
+impl From<[f32; 4]> for Quat {
+    fn from(v: [f32; 4]) -> Self {
+        Self::new(v[0], v[1], v[2], v[3])
+    }
+}
+
+impl Neg for Quat {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+impl Add for Quat {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(
+            self.x + rhs.x,
+            self.y + rhs.y,
+            self.z + rhs.z,
+            self.w + rhs.w,
+        )
+    }
+}
+
+impl Mul<f32> for Quat {
+    type Output = Self;
+    fn mul(self, rhs: f32) -> Self {
+        Self::new(self.x * rhs, self.y * rhs, self.z * rhs, self.w * rhs)
+    }
+}
+
+/// Hamilton product: `a * b` applies `b` first, then `a`.
+impl Mul for Quat {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
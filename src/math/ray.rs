@@ -0,0 +1,81 @@
+use crate::math::{Aabb, Mat4, Vec3, Vec4};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    #[inline]
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    #[inline]
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Distance along the ray to the first hit with `bounds` (slab test), or 0
+    /// when the origin is inside.
+    pub fn intersect_aabb(&self, bounds: &Aabb) -> Option<f32> {
+        if bounds.is_empty() {
+            return None;
+        }
+
+        let mut t_min = 0f32;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            let inv = 1. / self.direction[axis];
+            let mut t0 = (bounds.min[axis] - self.origin[axis]) * inv;
+            let mut t1 = (bounds.max[axis] - self.origin[axis]) * inv;
+            if inv < 0. {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+
+    /// Distance along the ray to triangle `abc` (Möller–Trumbore), hitting
+    /// both sides.
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let det = ab.dot(p);
+        if det.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let inv_det = 1. / det;
+        let ao = self.origin - a;
+        let u = ao.dot(p) * inv_det;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+
+        let q = ao.cross(ab);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+
+        let t = ac.dot(q) * inv_det;
+        (t >= 0.).then_some(t)
+    }
+
+    /// The ray with origin and direction transformed by `m`. Distances stay
+    /// comparable since the direction isn't renormalized.
+    pub fn transform(&self, m: &Mat4) -> Self {
+        Self::new(
+            (*m * Vec4::from_vec3(self.origin, 1.)).xyz(),
+            (*m * Vec4::from_vec3(self.direction, 0.)).xyz(),
+        )
+    }
+}
@@ -31,6 +31,59 @@ impl Vec2 {
     pub fn splat(v: f32) -> Self {
         Self::new(v, v)
     }
+
+    #[inline]
+    pub fn dot(self, rhs: Self) -> f32 {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    /// Z component of the 3D cross product, i.e. the signed parallelogram area
+    #[inline]
+    pub fn perp_dot(self, rhs: Self) -> f32 {
+        self.x * rhs.y - self.y * rhs.x
+    }
+
+    #[inline]
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    #[inline]
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+
+    /// Returns a unit vector, or `None` if the vector is near-zero.
+    #[inline]
+    pub fn try_normalize(self) -> Option<Self> {
+        let len = self.length();
+        if len > f32::EPSILON {
+            Some(self * (1. / len))
+        } else {
+            None
+        }
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Self;
+    fn mul(self, rhs: f32) -> Self {
+        Self::new(self.x * rhs, self.y * rhs)
+    }
 }
 
 #[repr(C)]
@@ -153,6 +206,18 @@ impl Vec3 {
             None
         }
     }
+
+    /// Component-wise minimum
+    #[inline]
+    pub fn min(self, rhs: Self) -> Self {
+        Self::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+
+    /// Component-wise maximum
+    #[inline]
+    pub fn max(self, rhs: Self) -> Self {
+        Self::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
+    }
 }
 
 // ── From / Into ──────────────────────────────
@@ -240,6 +305,14 @@ impl MulAssign<f32> for Vec3 {
     }
 }
 
+/// Component-wise, for modulating colors.
+impl Mul for Vec3 {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(self.x * rhs.x, self.y * rhs.y, self.z * rhs.z)
+    }
+}
+
 impl Div<f32> for Vec3 {
     type Output = Self;
     fn div(self, rhs: f32) -> Self {
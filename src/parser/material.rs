@@ -1,8 +1,8 @@
 use std::collections::HashMap;
-use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 
+use crate::assets;
 use crate::math::Vec3;
 use crate::scene::Material;
 
@@ -10,7 +10,7 @@ pub struct MtlFileParser;
 
 impl MtlFileParser {
     pub fn parse<P: AsRef<Path>>(path: P) -> Result<HashMap<String, Material>, String> {
-        let file = File::open(path).map_err(|e| format!("Failed to open MTL: {}", e))?;
+        let (file, _) = assets::open(path).map_err(|e| format!("Failed to open MTL: {}", e))?;
         let reader = BufReader::new(file);
 
         let mut materials = HashMap::new();
@@ -54,6 +54,7 @@ impl MtlFileParser {
                 }
                 *current = Material::default();
                 *name = remainder.join(" ");
+                current.name = name.clone();
             }
             "Ka" => {
                 current.ka = Self::to_vec3(remainder)
@@ -103,6 +104,21 @@ impl MtlFileParser {
             "map_Ks" => {
                 current.map_ks = remainder.join(" ");
             }
+            "map_d" => {
+                current.map_d = remainder.join(" ");
+            }
+            "disp" => {
+                current.disp = remainder.join(" ");
+            }
+
+            // Not part of the MTL format: `two_sided 1` or `two_sided on`.
+            "two_sided" => {
+                current.two_sided = match remainder.get(0).copied() {
+                    Some("1" | "on") => true,
+                    Some("0" | "off") => false,
+                    _ => return Err("Invalid Two Sided Flag".to_string()),
+                };
+            }
             _ => {
                 println!("{}", parts[0]);
             }
@@ -1,9 +1,12 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::num::NonZeroUsize;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use crate::assets;
 use crate::math::{Vec2, Vec3};
+use crate::profile::LoadProfile;
 use crate::renderer::Vertex;
 use crate::scene::{Group, Material, Mesh};
 
@@ -13,228 +16,841 @@ pub struct ObjFileParser;
 
 impl ObjFileParser {
     pub fn parse<P: AsRef<Path>>(path: P) -> Result<Mesh, String> {
-        let path_ref = path.as_ref();
-        let base_dir = path_ref.parent().unwrap_or(Path::new(""));
+        Self::parse_with_progress(path, |_| {})
+    }
+
+    /// Same as `parse`, reporting the fraction of the file read so far to
+    /// `progress` every few thousand lines.
+    pub fn parse_with_progress<P: AsRef<Path>>(
+        path: P,
+        progress: impl FnMut(f32),
+    ) -> Result<Mesh, String> {
+        Self::parse_profiled(path, progress, &mut LoadProfile::default())
+    }
+
+    /// Same as `parse_with_progress`, timing each step into `profile`.
+    pub fn parse_profiled<P: AsRef<Path>>(
+        path: P,
+        progress: impl FnMut(f32),
+        profile: &mut LoadProfile,
+    ) -> Result<Mesh, String> {
+        let mut mesh = Self::read(path, false, progress, profile)?;
+        profile.stage("center", |_| {
+            let center = Self::centroid(&mesh);
+            Self::translate(&mut mesh, -center);
+        });
+        Ok(mesh)
+    }
+
+    /// Same as `parse`, copying the file before parsing it even when it is
+    /// large enough to be mapped, for files another process may rewrite
+    /// meanwhile, such as models a tool exports for the viewer to reload.
+    pub fn parse_copied<P: AsRef<Path>>(path: P) -> Result<Mesh, String> {
+        let mut mesh = Self::read(path, true, |_| {}, &mut LoadProfile::default())?;
+        let center = Self::centroid(&mesh);
+        Self::translate(&mut mesh, -center);
+        Ok(mesh)
+    }
+
+    /// Parses two versions of a model, both centered on the first one's
+    /// vertex centroid so they stay aligned.
+    pub fn parse_pair<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> Result<(Mesh, Mesh), String> {
+        let mut a = Self::read(a, false, |_| {}, &mut LoadProfile::default())?;
+        let mut b = Self::read(b, false, |_| {}, &mut LoadProfile::default())?;
+        let center = Self::centroid(&a);
+        Self::translate(&mut a, -center);
+        Self::translate(&mut b, -center);
+        Ok((a, b))
+    }
 
-        let file = File::open(path_ref).map_err(|e| format!("Failed to open OBJ: {}", e))?;
-        let reader = BufReader::new(file);
+    /// Average vertex position, the origin for an empty mesh.
+    fn centroid(mesh: &Mesh) -> Vec3 {
+        let count: usize = mesh.groups.iter().map(|g| g.vertices.len()).sum();
+        if count == 0 {
+            return Vec3::ZERO;
+        }
+        mesh.groups
+            .iter()
+            .flat_map(|g| g.vertices.iter().map(|v| v.position))
+            .fold(Vec3::ZERO, |acc, p| acc + p)
+            / count as f32
+    }
+
+    fn translate(mesh: &mut Mesh, offset: Vec3) {
+        for group in &mut mesh.groups {
+            for vertex in &mut group.vertices {
+                vertex.position += offset;
+            }
+        }
+        for point in mesh.lines.iter_mut().flatten() {
+            *point += offset;
+        }
+    }
+
+    /// Reads the model at `path` where the file places it, mapped unless
+    /// `copy`, see `assets::map`. Files of a few megabytes and more are split
+    /// at line boundaries and their parts parsed on as many threads, before
+    /// the faces are assembled in order and reordered for the vertex cache.
+    fn read<P: AsRef<Path>>(
+        path: P,
+        copy: bool,
+        progress: impl FnMut(f32),
+        profile: &mut LoadProfile,
+    ) -> Result<Mesh, String> {
+        let path = assets::resolve(path);
+        let bytes = profile
+            .stage("read", |_| {
+                if copy {
+                    assets::read(&path).map(assets::AssetBytes::Read)
+                } else {
+                    assets::map(&path)
+                }
+            })
+            .map_err(|e| format!("Failed to open OBJ: {}", e))?;
 
+        let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let parts = (bytes.len() / Self::MIN_PART_SIZE).clamp(1, threads);
+        Self::parse_parts(&bytes, parts, &path, progress, profile)
+    }
+
+    /// Parses the contents `bytes` of the file at `path` in `parts` parts.
+    fn parse_parts(
+        bytes: &[u8],
+        parts: usize,
+        path: &Path,
+        mut progress: impl FnMut(f32),
+        profile: &mut LoadProfile,
+    ) -> Result<Mesh, String> {
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let size = bytes.len().max(1);
+        let parts = Self::split_lines(bytes, parts);
+        let parsed = AtomicUsize::new(0);
+        let mut chunks = profile.stage("tokenize", |_| {
+            if parts.len() == 1 {
+                return Ok(vec![Self::parse_chunk(parts[0], &parsed)?]);
+            }
+            std::thread::scope(|scope| {
+                let parsed = &parsed;
+                let workers: Vec<_> = parts
+                    .iter()
+                    .map(|&part| scope.spawn(move || Self::parse_chunk(part, parsed)))
+                    .collect();
+                while !workers.iter().all(|worker| worker.is_finished()) {
+                    progress(0.5 * parsed.load(Ordering::Relaxed) as f32 / size as f32);
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                workers
+                    .into_iter()
+                    .map(|worker| {
+                        worker
+                            .join()
+                            .map_err(|_| "OBJ parser thread panicked".to_string())?
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+            })
+        })?;
+        let skipped: usize = chunks.iter().map(|chunk| chunk.skipped).sum();
+        if skipped > 0 {
+            eprintln!(
+                "Skipped {} lines of unsupported statements in '{}'",
+                skipped,
+                path.display()
+            );
+        }
+
+        // Indices count from the start of the file, so the parts' vertices
+        // are joined in order before any face is read, and the relative
+        // indices of each part resolved past the vertices of those before.
         let mut positions: Vec<Vec3> = Vec::new();
         let mut normals: Vec<Vec3> = Vec::new();
         let mut texcoords: Vec<Vec2> = Vec::new();
-        let mut materials_map: HashMap<String, usize> = HashMap::new();
-        let mut materials: Vec<Material> = Vec::new();
+        let mut corners: Vec<Vec<Corner>> = Vec::with_capacity(chunks.len());
+        let mut line_points: Vec<Vec<usize>> = Vec::with_capacity(chunks.len());
+        for chunk in &mut chunks {
+            corners.push(
+                chunk
+                    .corners
+                    .iter()
+                    .map(|&(v, t, n)| {
+                        Ok((
+                            v.resolve(positions.len(), "vertex")?,
+                            t.map(|t| t.resolve(texcoords.len(), "texture"))
+                                .transpose()?,
+                            n.map(|n| n.resolve(normals.len(), "normal")).transpose()?,
+                        ))
+                    })
+                    .collect::<Result<_, String>>()?,
+            );
+            line_points.push(
+                chunk
+                    .line_points
+                    .iter()
+                    .map(|v| v.resolve(positions.len(), "vertex"))
+                    .collect::<Result<_, String>>()?,
+            );
+            positions.append(&mut chunk.positions);
+            normals.append(&mut chunk.normals);
+            texcoords.append(&mut chunk.texcoords);
+        }
 
-        let mut groups: Vec<Group> = Vec::new();
-        let mut cur_verts: Vec<Vertex> = Vec::new();
-        let mut cur_indices: Vec<u32> = Vec::new();
-        let mut cur_index_map: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
-        let mut cur_material: Option<usize> = None;
+        // Faces are triangulated and their corners merged into shared
+        // vertices, within their smoothing group for those without a normal;
+        // material files are read as they are named. Groups end at every
+        // material and object change. Lines only keep their positions.
+        let builder = profile.stage("assemble", |profile| {
+            let mut builder = MeshBuilder::default();
+            let mut materials = Duration::ZERO;
+            let mut material_statement =
+                |builder: &mut MeshBuilder, statement: &MaterialStatement| {
+                    let start = Instant::now();
+                    let result = builder.material_statement(statement, base_dir);
+                    materials += start.elapsed();
+                    result
+                };
+            let mut assembled = 0;
+            let runs = chunks.iter().zip(&corners).zip(&line_points).zip(&parts);
+            for (((chunk, corners), line_points), part) in runs {
+                let mut statements = chunk.materials.iter().peekable();
+                let mut smoothing = chunk.smoothing.iter().peekable();
+                let mut objects = chunk.objects.iter().peekable();
+                let mut start = 0;
+                for (face, &end) in chunk.faces.iter().enumerate() {
+                    while let Some((_, name)) = objects.next_if(|(before, _)| *before == face) {
+                        builder.object(name);
+                    }
+                    while let Some((_, statement)) =
+                        statements.next_if(|(before, _)| *before == face)
+                    {
+                        material_statement(&mut builder, statement)?;
+                    }
+                    while let Some(&(_, group)) = smoothing.next_if(|(before, _)| *before == face) {
+                        builder.smoothing = group;
+                    }
+                    builder.face(&corners[start..end], &positions, &texcoords, &normals)?;
+                    start = end;
+                }
+                for (_, name) in objects {
+                    builder.object(name);
+                }
+                for (_, statement) in statements {
+                    material_statement(&mut builder, statement)?;
+                }
+                if let Some(&(_, group)) = smoothing.last() {
+                    builder.smoothing = group;
+                }
+                let mut start = 0;
+                for &end in &chunk.lines {
+                    builder.line(&line_points[start..end], &positions)?;
+                    start = end;
+                }
+                assembled += part.len();
+                progress(0.5 + 0.5 * assembled as f32 / size as f32);
+            }
+            profile.record("materials", materials);
+            Ok::<_, String>(builder)
+        })?;
+
+        let mut mesh = builder.finish();
+        profile.stage("vertex cache", |_| mesh.optimize_vertex_cache());
+        progress(1.);
+        Ok(mesh)
+    }
 
-        for line_result in reader.lines() {
-            let line = line_result.map_err(|e| e.to_string())?;
-            let trimmed = line.trim();
+    /// `bytes` cut into `parts` runs of whole lines of about the same size.
+    fn split_lines(bytes: &[u8], parts: usize) -> Vec<&[u8]> {
+        let mut runs = Vec::with_capacity(parts);
+        let mut start = 0;
+        for part in 1..parts {
+            let cut = (bytes.len() * part / parts).max(start);
+            let end = bytes[cut..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |newline| cut + newline + 1);
+            runs.push(&bytes[start..end]);
+            start = end;
+        }
+        runs.push(&bytes[start..]);
+        runs
+    }
 
-            if trimmed.is_empty() || trimmed.starts_with('#') {
-                continue;
+    /// Parses the lines of `bytes`, adding how many of them are read to
+    /// `parsed` every few thousand lines.
+    fn parse_chunk(bytes: &[u8], parsed: &AtomicUsize) -> Result<Chunk, String> {
+        let mut chunk = Chunk::default();
+        let mut reported = 0;
+        for (line_number, line) in bytes.split(|&b| b == b'\n').enumerate() {
+            if line_number % Self::PROGRESS_INTERVAL == 0 {
+                let read = line.as_ptr() as usize - bytes.as_ptr() as usize;
+                parsed.fetch_add(read - reported, Ordering::Relaxed);
+                reported = read;
             }
 
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            if parts.is_empty() {
+            let mut tokens = line
+                .split(u8::is_ascii_whitespace)
+                .filter(|token| !token.is_empty());
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            if keyword.starts_with(b"#") {
                 continue;
             }
 
-            let remainder = &parts[1..];
-
-            match parts[0] {
-                "v" => {
-                    positions.push(Self::to_vec3(remainder).ok_or("Invalid vertex coordinates")?)
-                }
-                "vn" => normals.push(Self::to_vec3(remainder).ok_or("Invalid normal coordinates")?),
-                "vt" => {
-                    texcoords.push(Self::to_vec2(remainder).ok_or("Invalid texture coordinates")?)
-                }
-                "usemtl" => {
-                    if !cur_indices.is_empty() {
-                        groups.push(Group {
-                            vertices: std::mem::take(&mut cur_verts),
-                            indices: std::mem::take(&mut cur_indices),
-                            material: cur_material.clone(),
-                        });
-                        cur_index_map.clear();
+            match keyword {
+                b"v" => chunk
+                    .positions
+                    .push(Self::to_vec3(tokens).ok_or("Invalid vertex coordinates")?),
+                b"vn" => chunk
+                    .normals
+                    .push(Self::to_vec3(tokens).ok_or("Invalid normal coordinates")?),
+                b"vt" => chunk
+                    .texcoords
+                    .push(Self::to_texcoord(tokens).ok_or("Invalid texture coordinates")?),
+                b"f" => {
+                    let start = chunk.corners.len();
+                    let counts = [
+                        chunk.positions.len(),
+                        chunk.texcoords.len(),
+                        chunk.normals.len(),
+                    ];
+                    for token in tokens {
+                        let corner = Self::to_corner(token, counts)
+                            .ok_or("Missing or invalid vertex index")?;
+                        chunk.corners.push(corner);
                     }
-                    cur_material = if remainder.is_empty() {
-                        None
-                    } else {
-                        let name = remainder.join(" ");
-                        materials_map.get(&name).copied()
-                    };
-                }
-                "f" => {
-                    if remainder.len() < 3 {
+                    if chunk.corners.len() - start < 3 {
                         return Err("Face needs at least 3 vertices".to_string());
                     }
-
-                    let parse_fv =
-                        |s: &str| -> Result<(usize, Option<usize>, Option<usize>), String> {
-                            let idx: Vec<&str> = s.split('/').collect();
-
-                            let v = idx
-                                .get(0)
-                                .and_then(|s| Self::to_usize(s))
-                                .ok_or("Missing or invalid vertex index")?;
-                            if v >= positions.len() {
-                                return Err(format!("Vertex index {} out of bounds", v + 1));
-                            }
-
-                            let parse_sub = |i: usize| -> Option<usize> {
-                                idx.get(i)
-                                    .filter(|s| !s.is_empty())
-                                    .and_then(|s| Self::to_usize(s))
-                            };
-
-                            let t = parse_sub(1);
-                            if let Some(t) = t {
-                                if t >= texcoords.len() {
-                                    return Err(format!("Texture index {} out of bounds", t + 1));
-                                }
-                            }
-
-                            let n = parse_sub(2);
-                            if let Some(n) = n {
-                                if n >= normals.len() {
-                                    return Err(format!("Normal index {} out of bounds", n + 1));
-                                }
-                            }
-
-                            Ok((v, t, n))
-                        };
-
-                    let first = parse_fv(remainder[0])?;
-                    let mut prev = parse_fv(remainder[1])?;
-
-                    for token in &remainder[2..] {
-                        let current = parse_fv(token)?;
-
-                        for (vi, ti, ni) in [first, prev, current] {
-                            let idx = *cur_index_map.entry((vi, ti, ni)).or_insert_with(|| {
-                                let i = cur_verts.len() as u32;
-                                cur_verts.push(Vertex {
-                                    position: positions[vi],
-                                    normal: ni
-                                        .and_then(|n| normals.get(n))
-                                        .copied()
-                                        .unwrap_or(Vec3::ZERO),
-                                    uv: ti
-                                        .and_then(|t| texcoords.get(t))
-                                        .map(|v| Vec2::new(v.x, v.y))
-                                        .unwrap_or_default(),
-                                });
-                                i
-                            });
-                            cur_indices.push(idx);
-                        }
-
-                        prev = current;
-                    }
+                    chunk.faces.push(chunk.corners.len());
                 }
-                "mtllib" => {
-                    if !remainder.is_empty() {
-                        let parsed = MtlFileParser::parse(base_dir.join(remainder.join(" ")))?;
-                        for (name, material) in parsed {
-                            materials_map.insert(name, materials.len());
-                            materials.push(material);
-                        }
+                b"l" => {
+                    let start = chunk.line_points.len();
+                    let counts = [
+                        chunk.positions.len(),
+                        chunk.texcoords.len(),
+                        chunk.normals.len(),
+                    ];
+                    for token in tokens {
+                        let (v, _, _) = Self::to_corner(token, counts)
+                            .ok_or("Missing or invalid vertex index")?;
+                        chunk.line_points.push(v);
+                    }
+                    if chunk.line_points.len() - start < 2 {
+                        return Err("Line needs at least 2 vertices".to_string());
                     }
+                    chunk.lines.push(chunk.line_points.len());
+                }
+                b"usemtl" => {
+                    let statement = MaterialStatement::Use(Self::to_name(tokens)?);
+                    chunk.materials.push((chunk.faces.len(), statement));
+                }
+                b"mtllib" => {
+                    let statement = MaterialStatement::Library(Self::to_name(tokens)?);
+                    chunk.materials.push((chunk.faces.len(), statement));
                 }
-                "o" => {
-                    continue;
+                b"s" => {
+                    let group = Self::to_smoothing(tokens).ok_or("Invalid smoothing group")?;
+                    chunk.smoothing.push((chunk.faces.len(), group));
                 }
-                _ => {
-                    println!("{}", parts[0]);
+                b"o" => {
+                    let name = Self::to_name(tokens)?;
+                    chunk.objects.push((chunk.faces.len(), name));
                 }
+                _ => chunk.skipped += 1,
             }
         }
+        parsed.fetch_add(bytes.len() - reported, Ordering::Relaxed);
+        Ok(chunk)
+    }
 
-        if normals.is_empty() {
-            for triangle in cur_indices.chunks(3) {
-                let (a, b, c) = (
-                    triangle[0] as usize,
-                    triangle[1] as usize,
-                    triangle[2] as usize,
-                );
-                let n = compute_normal(
-                    cur_verts[a].position,
-                    cur_verts[b].position,
-                    cur_verts[c].position,
-                );
-                cur_verts[a].normal = n;
-                cur_verts[b].normal = n;
-                cur_verts[c].normal = n;
-            }
-            for v in &mut cur_verts {
-                v.normal = v.normal.normalize();
+    const PROGRESS_INTERVAL: usize = 4096;
+
+    /// Smallest part of a file given its own thread.
+    const MIN_PART_SIZE: usize = 1 << 20;
+
+    /// A face corner of `v`, `v/t`, `v//n` or `v/t/n` form, `counts` being
+    /// the positions, texture coordinates and normals of the run read so
+    /// far. Unreadable texture or normal indices are left out.
+    fn to_corner(token: &[u8], counts: [usize; 3]) -> Option<RawCorner> {
+        let mut indices = token.split(|&b| b == b'/');
+        let v = indices
+            .next()
+            .and_then(|index| Self::to_index(index, counts[0]))?;
+        let mut sub = |count| {
+            indices
+                .next()
+                .filter(|s| !s.is_empty())
+                .and_then(|index| Self::to_index(index, count))
+        };
+        let t = sub(counts[1]);
+        Some((v, t, sub(counts[2])))
+    }
+
+    /// A 1-based OBJ index, or a negative one counting back from the last
+    /// of the `count` elements of the run read so far.
+    fn to_index(token: &[u8], count: usize) -> Option<Index> {
+        match token.strip_prefix(b"-") {
+            Some(back) => {
+                let back = Self::to_usize(back)? + 1;
+                Some(Index::Relative(count as isize - back as isize))
             }
+            None => Self::to_usize(token).map(Index::Absolute),
         }
+    }
 
-        if !cur_indices.is_empty() {
-            groups.push(Group {
-                vertices: cur_verts,
-                indices: cur_indices,
-                material: cur_material,
-            });
+    /// 0-based index of a 1-based OBJ index.
+    fn to_usize(token: &[u8]) -> Option<usize> {
+        if token.is_empty() {
+            return None;
         }
+        token
+            .iter()
+            .try_fold(0usize, |index, &digit| {
+                let digit = (digit as char).to_digit(10)?;
+                index.checked_mul(10)?.checked_add(digit as usize)
+            })?
+            .checked_sub(1)
+    }
 
-        let vertices_count: usize = groups.iter().map(|s| s.vertices.len()).sum();
-        if vertices_count > 0 {
-            let center = groups
-                .iter()
-                .flat_map(|s| s.vertices.iter().map(|v| v.position))
-                .fold(Vec3::ZERO, |acc, p| acc + p)
-                / vertices_count as f32;
+    fn to_f32(token: &[u8]) -> Option<f32> {
+        std::str::from_utf8(token).ok()?.parse::<f32>().ok()
+    }
 
-            for sm in &mut groups {
-                for v in &mut sm.vertices {
-                    v.position -= center;
+    fn to_vec3<'a>(mut tokens: impl Iterator<Item = &'a [u8]>) -> Option<Vec3> {
+        Some(Vec3::new(
+            Self::to_f32(tokens.next()?)?,
+            Self::to_f32(tokens.next()?)?,
+            Self::to_f32(tokens.next()?)?,
+        ))
+    }
+
+    /// `u [v [w]]`: `v` defaults to 0 and the `w` of 3D textures is dropped.
+    fn to_texcoord<'a>(mut tokens: impl Iterator<Item = &'a [u8]>) -> Option<Vec2> {
+        let u = Self::to_f32(tokens.next()?)?;
+        let v = tokens.next().map_or(Some(0.), Self::to_f32)?;
+        if let Some(w) = tokens.next() {
+            Self::to_f32(w)?;
+        }
+        tokens.next().is_none().then_some(Vec2::new(u, v))
+    }
+
+    /// `off` or a group number, 0 meaning off too.
+    fn to_smoothing<'a>(mut tokens: impl Iterator<Item = &'a [u8]>) -> Option<Option<u32>> {
+        let group = match tokens.next()? {
+            b"off" => None,
+            token => Some(std::str::from_utf8(token).ok()?.parse::<u32>().ok()?),
+        };
+        tokens
+            .next()
+            .is_none()
+            .then_some(group.filter(|&group| group != 0))
+    }
+
+    /// The rest of a line as a name, its tokens joined by single spaces.
+    fn to_name<'a>(tokens: impl Iterator<Item = &'a [u8]>) -> Result<String, String> {
+        let tokens = tokens
+            .map(std::str::from_utf8)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "Invalid UTF-8 in name".to_string())?;
+        Ok(tokens.join(" "))
+    }
+}
+
+/// Face corner: 0-based position, texture and normal indices.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Face corner as read in a run of lines, before its relative indices are
+/// resolved.
+type RawCorner = (Index, Option<Index>, Option<Index>);
+
+#[derive(Debug, Clone, Copy)]
+enum Index {
+    /// 0-based from the start of the file.
+    Absolute(usize),
+    /// 0-based from the start of the run, negative for elements of the runs
+    /// before.
+    Relative(isize),
+}
+
+impl Index {
+    /// 0-based index in the file, for a run following `before` elements.
+    fn resolve(self, before: usize, kind: &str) -> Result<usize, String> {
+        match self {
+            Index::Absolute(index) => Ok(index),
+            Index::Relative(index) => usize::try_from(before as isize + index)
+                .map_err(|_| format!("Relative {} index out of bounds", kind)),
+        }
+    }
+}
+
+enum MaterialStatement {
+    /// `mtllib`, naming a material file.
+    Library(String),
+    /// `usemtl`, naming the material of the faces after it, none when empty.
+    Use(String),
+}
+
+/// The statements of a run of lines of an OBJ file, parsed apart from the
+/// rest of the file.
+#[derive(Default)]
+struct Chunk {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    texcoords: Vec<Vec2>,
+    corners: Vec<RawCorner>,
+    /// End of each face in `corners`.
+    faces: Vec<usize>,
+    /// Material statements, with the number of faces of the run before them.
+    materials: Vec<(usize, MaterialStatement)>,
+    /// Smoothing groups set by `s`, none when off, with the number of faces
+    /// of the run before them.
+    smoothing: Vec<(usize, Option<u32>)>,
+    /// Object names of `o` statements, empty for none, with the number of
+    /// faces of the run before them.
+    objects: Vec<(usize, String)>,
+    /// Points of the `l` statements, their texture indices left out.
+    line_points: Vec<Index>,
+    /// End of each line in `line_points`.
+    lines: Vec<usize>,
+    /// Lines of keywords the viewer has no use for, such as `g` or `vp`.
+    skipped: usize,
+}
+
+/// Faces whose corners without a normal index may share a vertex, and so
+/// get the normal averaged over them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Smoothing {
+    /// Smoothing group of an `s` statement.
+    Group(u32),
+    /// Face at that position in the file, smoothed with no other.
+    Face(usize),
+}
+
+/// Groups of a mesh being assembled face by face, a new one starting at
+/// every material or object change.
+struct MeshBuilder {
+    materials_map: HashMap<String, usize>,
+    materials: Vec<Material>,
+    objects: Vec<String>,
+    groups: Vec<Group>,
+    cur_verts: Vec<Vertex>,
+    /// Whether each vertex of `cur_verts` sums the normals of its faces,
+    /// having no normal in the file.
+    cur_generated: Vec<bool>,
+    cur_indices: Vec<u32>,
+    cur_index_map: HashMap<(Corner, Smoothing), u32>,
+    cur_material: Option<usize>,
+    cur_object: Option<usize>,
+    /// Smoothing group of the next face, none when smoothing is off, as it
+    /// is before any `s` statement and after `s 0` or `s off`.
+    smoothing: Option<u32>,
+    /// Faces added so far, numbering `Smoothing::Face`.
+    faces: usize,
+    lines: Vec<[Vec3; 2]>,
+}
+
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        Self {
+            materials_map: HashMap::new(),
+            materials: Vec::new(),
+            objects: Vec::new(),
+            groups: Vec::new(),
+            cur_verts: Vec::new(),
+            cur_generated: Vec::new(),
+            cur_indices: Vec::new(),
+            cur_index_map: HashMap::new(),
+            cur_material: None,
+            cur_object: None,
+            smoothing: None,
+            faces: 0,
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl MeshBuilder {
+    fn material_statement(
+        &mut self,
+        statement: &MaterialStatement,
+        base_dir: &Path,
+    ) -> Result<(), String> {
+        match statement {
+            MaterialStatement::Library(name) => {
+                if !name.is_empty() {
+                    let parsed = MtlFileParser::parse(base_dir.join(name))?;
+                    for (name, material) in parsed {
+                        self.materials_map.insert(name, self.materials.len());
+                        self.materials.push(material);
+                    }
                 }
             }
+            MaterialStatement::Use(name) => {
+                self.end_group();
+                self.cur_material = if name.is_empty() {
+                    None
+                } else {
+                    self.materials_map.get(name).copied()
+                };
+            }
         }
+        Ok(())
+    }
 
-        Ok(Mesh { groups, materials })
+    /// Puts the next faces in the object `name`, none when empty. Objects
+    /// named again are continued.
+    fn object(&mut self, name: &str) {
+        self.end_group();
+        self.cur_object = (!name.is_empty()).then(|| {
+            self.objects
+                .iter()
+                .position(|object| object == name)
+                .unwrap_or_else(|| {
+                    self.objects.push(name.to_string());
+                    self.objects.len() - 1
+                })
+        });
     }
 
-    fn to_usize(s: &str) -> Option<usize> {
-        s.parse::<usize>().ok()?.checked_sub(1)
+    /// Adds the face of `corners` as a fan of triangles. Its indices may
+    /// name vertices anywhere in the file.
+    fn face(
+        &mut self,
+        corners: &[Corner],
+        positions: &[Vec3],
+        texcoords: &[Vec2],
+        normals: &[Vec3],
+    ) -> Result<(), String> {
+        for &(v, t, n) in corners {
+            if v >= positions.len() {
+                return Err(format!("Vertex index {} out of bounds", v + 1));
+            }
+            if let Some(t) = t.filter(|&t| t >= texcoords.len()) {
+                return Err(format!("Texture index {} out of bounds", t + 1));
+            }
+            if let Some(n) = n.filter(|&n| n >= normals.len()) {
+                return Err(format!("Normal index {} out of bounds", n + 1));
+            }
+        }
+
+        let smoothing = match self.smoothing {
+            Some(group) => Smoothing::Group(group),
+            None => Smoothing::Face(self.faces),
+        };
+        self.faces += 1;
+
+        let first = corners[0];
+        for pair in corners[1..].windows(2) {
+            let triangle = [first, pair[0], pair[1]].map(|(vi, ti, ni)| {
+                // Corners with a normal in the file keep it whatever their
+                // smoothing group.
+                let key = ((vi, ti, ni), ni.map_or(smoothing, |_| Smoothing::Group(0)));
+                *self.cur_index_map.entry(key).or_insert_with(|| {
+                    let i = self.cur_verts.len() as u32;
+                    self.cur_generated.push(ni.is_none());
+                    self.cur_verts.push(Vertex {
+                        position: positions[vi],
+                        normal: ni
+                            .and_then(|n| normals.get(n))
+                            .copied()
+                            .unwrap_or(Vec3::ZERO),
+                        uv: ti
+                            .and_then(|t| texcoords.get(t))
+                            .map(|v| Vec2::new(v.x, v.y))
+                            .unwrap_or_default(),
+                        occlusion: 1.,
+                        lightmap_uv: Vec2::ZERO,
+                    });
+                    i
+                })
+            });
+
+            // Weighted by area, so slivers barely tilt the average.
+            let [a, b, c] = triangle.map(|i| self.cur_verts[i as usize].position);
+            let normal = (b - a).cross(c - a);
+            for i in triangle {
+                if self.cur_generated[i as usize] {
+                    self.cur_verts[i as usize].normal += normal;
+                }
+            }
+            self.cur_indices.extend(triangle);
+        }
+        Ok(())
     }
 
-    fn to_f32(s: &str) -> Option<f32> {
-        s.parse::<f32>().ok()
+    /// Adds the polyline through `points` as one segment per pair of
+    /// consecutive points.
+    fn line(&mut self, points: &[usize], positions: &[Vec3]) -> Result<(), String> {
+        if let Some(&v) = points.iter().find(|&&v| v >= positions.len()) {
+            return Err(format!("Vertex index {} out of bounds", v + 1));
+        }
+        self.lines.extend(
+            points
+                .windows(2)
+                .map(|pair| [positions[pair[0]], positions[pair[1]]]),
+        );
+        Ok(())
     }
 
-    fn to_vec3(tokens: &[&str]) -> Option<Vec3> {
-        if tokens.len() < 3 {
-            return None;
+    /// Closes the group being assembled, averaging the normals of its
+    /// vertices without one in the file.
+    fn end_group(&mut self) {
+        if self.cur_indices.is_empty() {
+            return;
         }
-        Some(Vec3::new(
-            Self::to_f32(tokens[0])?,
-            Self::to_f32(tokens[1])?,
-            Self::to_f32(tokens[2])?,
-        ))
+        for (vertex, &generated) in self.cur_verts.iter_mut().zip(&self.cur_generated) {
+            if generated {
+                // Vertices on degenerate faces only face up.
+                vertex.normal = vertex.normal.try_normalize().unwrap_or(Vec3::Y);
+            }
+        }
+        self.groups.push(Group {
+            vertices: std::mem::take(&mut self.cur_verts),
+            indices: std::mem::take(&mut self.cur_indices),
+            material: self.cur_material,
+            object: self.cur_object,
+            skinning: Vec::new(),
+        });
+        self.cur_generated.clear();
+        self.cur_index_map.clear();
     }
 
-    fn to_vec2(tokens: &[&str]) -> Option<Vec2> {
-        if tokens.len() != 2 {
-            return None;
+    fn finish(mut self) -> Mesh {
+        self.end_group();
+        Mesh {
+            groups: self.groups,
+            materials: self.materials,
+            lightmap: None,
+            objects: self.objects,
+            lines: self.lines,
         }
-        Some(Vec2::new(
-            Self::to_f32(tokens[0])?,
-            Self::to_f32(tokens[1])?,
-        ))
     }
 }
 
-fn compute_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
-    let edge1 = b - a;
-    let edge2 = c - a;
-    edge1.cross(edge2).normalize()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_text(text: &str, parts: usize) -> Result<Mesh, String> {
+        ObjFileParser::parse_parts(
+            text.as_bytes(),
+            parts,
+            Path::new("test.obj"),
+            |_| {},
+            &mut LoadProfile::default(),
+        )
+    }
+
+    fn vertices(mesh: &Mesh) -> Vec<Vertex> {
+        mesh.groups
+            .iter()
+            .flat_map(|g| g.vertices.iter().copied())
+            .collect()
+    }
+
+    fn triangles(mesh: &Mesh) -> Vec<[Vec3; 3]> {
+        let mut triangles = Vec::new();
+        for group in &mesh.groups {
+            for triangle in group.indices.chunks(3) {
+                triangles.push([0, 1, 2].map(|i| group.vertices[triangle[i] as usize].position));
+            }
+        }
+        triangles
+    }
+
+    fn assert_same(a: &Mesh, b: &Mesh) {
+        assert_eq!(a.groups.len(), b.groups.len());
+        for (a, b) in a.groups.iter().zip(&b.groups) {
+            assert_eq!(a.indices, b.indices);
+            assert_eq!(a.material, b.material);
+            assert_eq!(a.object, b.object);
+            assert_eq!(a.vertices.len(), b.vertices.len());
+            for (a, b) in a.vertices.iter().zip(&b.vertices) {
+                assert_eq!(a.position, b.position);
+                assert_eq!(a.normal, b.normal);
+                assert_eq!(a.uv, b.uv);
+            }
+        }
+        assert_eq!(a.objects, b.objects);
+        assert_eq!(a.lines, b.lines);
+    }
+
+    #[test]
+    fn resolves_relative_indices_across_parts() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nv 0 0 1\nf -4 -3 -1\n";
+        let whole = parse_text(text, 1).unwrap();
+        let second = [Vec3::ZERO, Vec3::X, Vec3::Z];
+        assert!(triangles(&whole)
+            .iter()
+            .any(|triangle| second.iter().all(|p| triangle.contains(p))));
+        // Two parts cut right before the last vertex, more down to a line each.
+        for parts in 2..=6 {
+            assert_same(&parse_text(text, parts).unwrap(), &whole);
+        }
+    }
+
+    #[test]
+    fn reads_texture_coordinates() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.25 0.5\nvt 0.75 0.5 0.9\nvt 1\n\
+                    f 1/1 2/2 3/3\n";
+        let mesh = parse_text(text, 1).unwrap();
+        let uv = |position| {
+            vertices(&mesh)
+                .iter()
+                .find(|v| v.position == position)
+                .unwrap()
+                .uv
+        };
+        assert_eq!(uv(Vec3::ZERO), Vec2::new(0.25, 0.5));
+        assert_eq!(uv(Vec3::X), Vec2::new(0.75, 0.5));
+        assert_eq!(uv(Vec3::Y), Vec2::new(1., 0.));
+        assert!(parse_text("vt 0 0 0 0\n", 1).is_err());
+    }
+
+    #[test]
+    fn smooths_within_groups() {
+        // Two triangles folded along the edge from vertex 1 to vertex 2.
+        let faces = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 -1\nf 1 2 3\nf 2 1 4\n";
+        for smoothing in ["", "s off\n", "s 0\n", "s 1\ns off\n"] {
+            let mesh = parse_text(&format!("{}{}", smoothing, faces), 1).unwrap();
+            let vertices = vertices(&mesh);
+            assert_eq!(vertices.len(), 6, "{:?}", smoothing);
+            assert!(vertices
+                .iter()
+                .all(|v| v.normal == Vec3::Z || v.normal == -Vec3::Y));
+        }
+        let mesh = parse_text(&format!("s 1\n{}", faces), 1).unwrap();
+        let vertices = vertices(&mesh);
+        assert_eq!(vertices.len(), 4);
+        let shared = vertices.iter().find(|v| v.position == Vec3::ZERO).unwrap();
+        assert!((shared.normal - Vec3::new(0., -1., 1.).normalize()).length() < 1e-6);
+    }
+
+    #[test]
+    fn reads_objects_and_lines() {
+        let text = "o first\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n\
+                    o second\nv 0 0 1\nf 1 2 4\nl 1 2 -1\n";
+        let mesh = parse_text(text, 1).unwrap();
+        assert_eq!(mesh.objects, ["first", "second"]);
+        let objects: Vec<_> = mesh.groups.iter().map(|g| g.object).collect();
+        assert_eq!(objects, [Some(0), Some(1)]);
+        assert_eq!(mesh.lines, [[Vec3::ZERO, Vec3::X], [Vec3::X, Vec3::Z]]);
+    }
+
+    #[test]
+    fn maps_and_copies_alike() {
+        // Large enough to be mapped, see `assets::map`.
+        let padding = format!("# {}\n", "-".repeat(1021));
+        let mut text = String::new();
+        for i in 0..(17 << 10) {
+            let x = i as f32;
+            text += &format!("v {} 0 0\nv {} 1 0\nv {} 0 1\nf -3 -2 -1\n", x, x, x);
+            text += &padding;
+        }
+        let path = std::env::temp_dir().join(format!("scop-maps-{}.obj", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let mapped = ObjFileParser::parse(&path);
+        let copied = ObjFileParser::parse_copied(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_same(&mapped.unwrap(), &copied.unwrap());
+    }
 }
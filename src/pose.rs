@@ -0,0 +1,320 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::camera::Camera;
+use crate::math::Vec3;
+use crate::scene::Object;
+
+/// Camera placement and model transform, exchanged as JSON through the
+/// clipboard so an exact view can be shared and restored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewPose {
+    pub camera_position: Vec3,
+    pub camera_direction: Vec3,
+    /// Vertical field of view, in degrees.
+    pub fov: f32,
+    pub model_position: Vec3,
+    /// Euler angles (pitch, yaw, roll), in radians.
+    pub model_rotation: Vec3,
+    pub model_scale: Vec3,
+}
+
+impl ViewPose {
+    pub fn capture(camera: &Camera, model: &Object) -> Self {
+        Self {
+            camera_position: camera.position,
+            camera_direction: camera.forward(),
+            fov: camera.fov().to_degrees(),
+            model_position: model.position(),
+            model_rotation: model.rotation(),
+            model_scale: model.scale(),
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera, model: &mut Object) {
+        camera.position = self.camera_position;
+        camera.look_towards(self.camera_direction);
+        camera.set_fov(self.fov.to_radians());
+
+        let rotation = self.model_rotation;
+        model
+            .set_position(self.model_position)
+            .set_rotation(rotation.x, rotation.y, rotation.z)
+            .set_scale(self.model_scale);
+    }
+
+    pub fn to_json(&self) -> String {
+        let vec = |v: Vec3| format!("[{}, {}, {}]", v.x, v.y, v.z);
+        format!(
+            "{{\n  \"camera\": {{\n    \"position\": {},\n    \"direction\": {},\n    \"fov\": {}\n  }},\n  \"model\": {{\n    \"position\": {},\n    \"rotation\": {},\n    \"scale\": {}\n  }}\n}}",
+            vec(self.camera_position),
+            vec(self.camera_direction),
+            self.fov,
+            vec(self.model_position),
+            vec(self.model_rotation),
+            vec(self.model_scale)
+        )
+    }
+
+    /// Reads what `to_json` writes. Unknown keys are ignored, and every known
+    /// one is required.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        Self::from_value(&Json::parse_document(text)?)
+    }
+
+    /// Reads the pose out of a parsed `to_json` object.
+    pub fn from_value(root: &Json) -> Result<Self, String> {
+        let camera = root.get("camera")?;
+        let model = root.get("model")?;
+        Ok(Self {
+            camera_position: camera.get("position")?.vec3()?,
+            camera_direction: camera
+                .get("direction")?
+                .vec3()?
+                .try_normalize()
+                .ok_or("Expected a non-zero camera direction")?,
+            fov: camera.get("fov")?.number()?,
+            model_position: model.get("position")?.vec3()?,
+            model_rotation: model.get("rotation")?.vec3()?,
+            model_scale: model.get("scale")?.vec3()?,
+        })
+    }
+}
+
+/// The subset of JSON values poses and sessions are made of; nulls are
+/// parsed but not kept.
+pub enum Json {
+    /// Double precision, so integers such as request ids up to 2^53 stay
+    /// exact.
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+    Other,
+}
+
+/// `text` as a JSON string literal.
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+impl Json {
+    /// Arrays and objects nested deeper are rejected, rather than overflowing
+    /// the stack on hostile input such as thousands of `[`.
+    const MAX_DEPTH: usize = 64;
+
+    /// Parses a whole document, holding a single value.
+    pub fn parse_document(text: &str) -> Result<Json, String> {
+        let mut chars = text.chars().peekable();
+        let root = Json::parse(&mut chars, 0)?;
+        skip_whitespace(&mut chars);
+        if chars.peek().is_some() {
+            return Err("Unexpected text after the JSON value".to_string());
+        }
+        Ok(root)
+    }
+
+    fn parse(chars: &mut Peekable<Chars>, depth: usize) -> Result<Json, String> {
+        skip_whitespace(chars);
+        if matches!(chars.peek(), Some('{' | '[')) && depth >= Self::MAX_DEPTH {
+            return Err(format!(
+                "JSON nested deeper than {} levels",
+                Self::MAX_DEPTH
+            ));
+        }
+        match chars.peek() {
+            Some('{') => {
+                chars.next();
+                let mut members = Vec::new();
+                skip_whitespace(chars);
+                if chars.peek() == Some(&'}') {
+                    chars.next();
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    skip_whitespace(chars);
+                    let key = Self::parse_string(chars)?;
+                    skip_whitespace(chars);
+                    if chars.next() != Some(':') {
+                        return Err(format!("Expected ':' after \"{}\"", key));
+                    }
+                    members.push((key, Self::parse(chars, depth + 1)?));
+                    skip_whitespace(chars);
+                    match chars.next() {
+                        Some(',') => continue,
+                        Some('}') => return Ok(Json::Object(members)),
+                        _ => return Err("Expected ',' or '}' in object".to_string()),
+                    }
+                }
+            }
+            Some('[') => {
+                chars.next();
+                let mut items = Vec::new();
+                skip_whitespace(chars);
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(Self::parse(chars, depth + 1)?);
+                    skip_whitespace(chars);
+                    match chars.next() {
+                        Some(',') => continue,
+                        Some(']') => return Ok(Json::Array(items)),
+                        _ => return Err("Expected ',' or ']' in array".to_string()),
+                    }
+                }
+            }
+            Some('"') => Self::parse_string(chars).map(Json::String),
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    "null" => Ok(Json::Other),
+                    _ => word
+                        .parse()
+                        .map(Json::Number)
+                        .map_err(|_| format!("Invalid JSON value '{}'", word)),
+                }
+            }
+            None => Err("Unexpected end of JSON".to_string()),
+        }
+    }
+
+    fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+        if chars.next() != Some('"') {
+            return Err("Expected a string".to_string());
+        }
+        let mut string = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match chars.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some(c @ ('"' | '\\' | '/')) => string.push(c),
+                    Some('u') => {
+                        let high = Self::parse_code_unit(chars)?;
+                        // Characters outside the Basic Multilingual Plane are
+                        // written as a surrogate pair of escapes.
+                        let code = if (0xd800..0xdc00).contains(&high) {
+                            let low = match (chars.next(), chars.next()) {
+                                (Some('\\'), Some('u')) => Self::parse_code_unit(chars)?,
+                                _ => return Err("Unpaired surrogate in string".to_string()),
+                            };
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err("Unpaired surrogate in string".to_string());
+                            }
+                            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                        } else {
+                            high
+                        };
+                        string.push(
+                            char::from_u32(code)
+                                .ok_or_else(|| "Unpaired surrogate in string".to_string())?,
+                        );
+                    }
+                    Some(c) => return Err(format!("Invalid escape '\\{}'", c)),
+                    None => return Err("Unterminated string".to_string()),
+                },
+                Some(c) => string.push(c),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+    }
+
+    /// The four hex digits of a `\u` escape.
+    fn parse_code_unit(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+        let code: String = chars.by_ref().take(4).collect();
+        if code.len() != 4 || !code.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid escape '\\u{}'", code));
+        }
+        u32::from_str_radix(&code, 16).map_err(|e| e.to_string())
+    }
+
+    pub fn get(&self, key: &str) -> Result<&Json, String> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("Missing \"{}\"", key)),
+            _ => Err(format!("Expected an object around \"{}\"", key)),
+        }
+    }
+
+    /// The member `key` of an object, none when it is missing or null.
+    pub fn find(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, value)| name == key && !matches!(value, Json::Other))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn number(&self) -> Result<f32, String> {
+        match self {
+            Json::Number(value) if (*value as f32).is_finite() => Ok(*value as f32),
+            _ => Err("Expected a number".to_string()),
+        }
+    }
+
+    pub fn string(&self) -> Result<&str, String> {
+        match self {
+            Json::String(value) => Ok(value),
+            _ => Err("Expected a string".to_string()),
+        }
+    }
+
+    pub fn boolean(&self) -> Result<bool, String> {
+        match self {
+            Json::Bool(value) => Ok(*value),
+            _ => Err("Expected true or false".to_string()),
+        }
+    }
+
+    pub fn vec3(&self) -> Result<Vec3, String> {
+        match self {
+            Json::Array(items) if items.len() == 3 => Ok(Vec3::new(
+                items[0].number()?,
+                items[1].number()?,
+                items[2].number()?,
+            )),
+            _ => Err("Expected an array of 3 numbers".to_string()),
+        }
+    }
+}
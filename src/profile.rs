@@ -0,0 +1,113 @@
+use std::fmt;
+use std::time::Duration;
+#[cfg(feature = "profiling")]
+use std::time::Instant;
+
+/// Wall-clock durations of the stages of a model load, each listed under the
+/// stage it is part of, to find where loading a large model spends its time.
+/// Empty, its stages run untimed, when built without the `profiling` feature.
+#[derive(Debug, Default)]
+pub struct LoadProfile {
+    #[cfg(feature = "profiling")]
+    stages: Vec<Stage>,
+    /// Nesting of the stage being run.
+    #[cfg(feature = "profiling")]
+    depth: usize,
+}
+
+#[cfg(feature = "profiling")]
+#[derive(Debug)]
+struct Stage {
+    name: &'static str,
+    depth: usize,
+    duration: Duration,
+}
+
+#[cfg(feature = "profiling")]
+impl LoadProfile {
+    /// Runs `f` as the stage `name`, the stages it runs on the profile it is
+    /// given nested under it.
+    pub fn stage<T>(&mut self, name: &'static str, f: impl FnOnce(&mut Self) -> T) -> T {
+        let index = self.stages.len();
+        self.stages.push(Stage {
+            name,
+            depth: self.depth,
+            duration: Duration::ZERO,
+        });
+        self.depth += 1;
+        let start = Instant::now();
+        let result = f(self);
+        self.stages[index].duration = start.elapsed();
+        self.depth -= 1;
+        result
+    }
+
+    /// Adds a stage timed by the caller, for work interleaved with other
+    /// stages and summed over its runs.
+    pub fn record(&mut self, name: &'static str, duration: Duration) {
+        self.stages.push(Stage {
+            name,
+            depth: self.depth,
+            duration,
+        });
+    }
+
+    /// Time spent in the outermost stages.
+    pub fn total(&self) -> Duration {
+        self.stages
+            .iter()
+            .filter(|stage| stage.depth == 0)
+            .map(|stage| stage.duration)
+            .sum()
+    }
+}
+
+/// The profile's methods when built without profiling.
+#[cfg(not(feature = "profiling"))]
+impl LoadProfile {
+    pub fn stage<T>(&mut self, _name: &'static str, f: impl FnOnce(&mut Self) -> T) -> T {
+        f(self)
+    }
+
+    pub fn record(&mut self, _name: &'static str, _duration: Duration) {}
+
+    pub fn total(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+#[cfg(feature = "profiling")]
+impl fmt::Display for LoadProfile {
+    /// One stage per line, indented by nesting, with its share of the total.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().as_secs_f64().max(f64::MIN_POSITIVE);
+        let width = self
+            .stages
+            .iter()
+            .map(|stage| stage.depth * 2 + stage.name.len())
+            .max()
+            .unwrap_or(0);
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let seconds = stage.duration.as_secs_f64();
+            write!(
+                f,
+                "  {:width$} {:>9.2} ms {:>5.1}%",
+                format!("{}{}", "  ".repeat(stage.depth), stage.name),
+                seconds * 1000.,
+                seconds / total * 100.,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+impl fmt::Display for LoadProfile {
+    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+use crate::app::App;
+use crate::math::Vec3;
+use crate::parser::ObjFileParser;
+use crate::pose::{json_string, Json};
+use crate::scene::{Mesh, ObjectHandle};
+
+/// Where remote commands come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteEndpoint {
+    /// Lines on standard input, answered on standard output.
+    Stdin,
+    /// TCP connections to this port on the loopback interface.
+    Port(u16),
+}
+
+impl FromStr for RemoteEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdin" => Ok(RemoteEndpoint::Stdin),
+            _ => s
+                .parse()
+                .map(RemoteEndpoint::Port)
+                .map_err(|_| format!("Expected stdin or a port number, got '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for RemoteEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemoteEndpoint::Stdin => f.write_str("stdin"),
+            RemoteEndpoint::Port(port) => write!(f, "127.0.0.1:{}", port),
+        }
+    }
+}
+
+/// Standard output as it was before `claim_stdout`, left to the responses.
+#[cfg(unix)]
+static RESPONSES: OnceLock<std::fs::File> = OnceLock::new();
+
+/// A request line, with where to send the response line, none for a
+/// notification.
+type Request = (String, Sender<Option<String>>);
+
+/// JSON-RPC 2.0 commands driving the viewer from another process, such as a
+/// DCC plugin previewing its scene: one request object per line, each
+/// answered with one response line but notifications, the requests without
+/// an `id`. Requests are read on background threads and run on the main
+/// thread by `poll`.
+///
+/// Any local process, and any web page through the browser, can reach a
+/// loopback port, so requests over TCP must carry the session's `token`, and
+/// connections opening with an HTTP request line are dropped. Screenshots
+/// are only written under `directory`.
+pub struct RemoteControl {
+    requests: Receiver<Request>,
+    /// Token requests must carry, none on standard input, which only the
+    /// process that started the viewer can write to.
+    token: Option<String>,
+    directory: PathBuf,
+}
+
+impl RemoteControl {
+    /// JSON-RPC error code of a request that failed to run.
+    const SERVER_ERROR: i32 = -32000;
+    const PARSE_ERROR: i32 = -32700;
+    const INVALID_REQUEST: i32 = -32600;
+    const METHOD_NOT_FOUND: i32 = -32601;
+    /// JSON-RPC error code of a request without the session's token.
+    const UNAUTHORIZED: i32 = -32001;
+
+    /// Starts reading requests from `endpoint`, over TCP with `token` or a
+    /// random one printed here, saving screenshots under `directory` or the
+    /// current one.
+    pub fn open(
+        endpoint: RemoteEndpoint,
+        token: Option<String>,
+        directory: Option<String>,
+    ) -> Result<Self, String> {
+        let directory = PathBuf::from(directory.unwrap_or_else(|| ".".to_string()));
+        let (sender, requests) = channel();
+        let token = match endpoint {
+            RemoteEndpoint::Stdin => {
+                let output = Self::claim_stdout()?;
+                std::thread::spawn(move || Self::serve(std::io::stdin().lock(), output, &sender));
+                println!("Remote control on {}", endpoint);
+                None
+            }
+            RemoteEndpoint::Port(port) => {
+                let listener = TcpListener::bind(("127.0.0.1", port))
+                    .map_err(|e| format!("Failed to listen on {}: {}", endpoint, e))?;
+                std::thread::spawn(move || {
+                    for stream in listener.incoming().flatten() {
+                        let sender = sender.clone();
+                        std::thread::spawn(move || Self::serve_stream(stream, &sender));
+                    }
+                });
+                let token = token.unwrap_or_else(Self::random_token);
+                println!("Remote control on {} with token {}", endpoint, token);
+                Some(token)
+            }
+        };
+        Ok(Self {
+            requests,
+            token,
+            directory,
+        })
+    }
+
+    /// Keeps standard output for the responses to requests on standard
+    /// input, sending whatever the viewer prints there from now on, through
+    /// `println!` or otherwise, to standard error instead. Call before
+    /// anything is printed; later calls return the same output.
+    #[cfg(unix)]
+    pub fn claim_stdout() -> Result<Box<dyn Write + Send>, String> {
+        if RESPONSES.get().is_none() {
+            use std::os::fd::FromRawFd;
+
+            let _ = std::io::stdout().flush();
+            let responses = unsafe { libc::dup(libc::STDOUT_FILENO) };
+            if responses < 0 || unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0
+            {
+                return Err(format!(
+                    "Failed to set standard output aside: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+            // SAFETY: `dup` just returned this descriptor, owned by nothing
+            // else.
+            let _ = RESPONSES.set(unsafe { std::fs::File::from_raw_fd(responses) });
+        }
+        match RESPONSES.get() {
+            Some(file) => Ok(Box::new(file)),
+            None => Err("Standard output was not set aside".to_string()),
+        }
+    }
+
+    /// Standard output as is, where descriptors can't be redirected.
+    #[cfg(not(unix))]
+    pub fn claim_stdout() -> Result<Box<dyn Write + Send>, String> {
+        Ok(Box::new(std::io::stdout()))
+    }
+
+    /// 128 bits from the standard library's randomly keyed hasher.
+    fn random_token() -> String {
+        let state = RandomState::new();
+        let mut token = String::new();
+        for part in 0..2u64 {
+            let mut hasher = state.build_hasher();
+            hasher.write_u64(part);
+            hasher.write_u32(std::process::id());
+            hasher.write_u128(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_nanos()),
+            );
+            token.push_str(&format!("{:016x}", hasher.finish()));
+        }
+        token
+    }
+
+    fn serve_stream(stream: TcpStream, sender: &Sender<Request>) {
+        if let Ok(reader) = stream.try_clone() {
+            let mut reader = BufReader::new(reader);
+            // A browser sending a request to the port starts with its request
+            // line; drop it before anything runs.
+            let mut first = String::new();
+            if reader.read_line(&mut first).is_err() || Self::is_http_request_line(&first) {
+                return;
+            }
+            Self::serve(std::io::Cursor::new(first).chain(reader), stream, sender);
+        }
+    }
+
+    /// Whether `line` looks like `METHOD target HTTP/version`.
+    fn is_http_request_line(line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        matches!(words.as_slice(), [method, _, version]
+            if version.starts_with("HTTP/")
+                && method.bytes().all(|byte| byte.is_ascii_uppercase()))
+    }
+
+    /// Forwards the lines of `input` to the main thread and writes back its
+    /// responses, until either side is gone.
+    fn serve(input: impl BufRead, mut output: impl Write, sender: &Sender<Request>) {
+        for line in input.lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (reply, response) = channel();
+            if sender.send((line, reply)).is_err() {
+                return;
+            }
+            let response = match response.recv() {
+                Ok(Some(response)) => response,
+                Ok(None) => continue,
+                Err(_) => return,
+            };
+            if writeln!(output, "{}", response)
+                .and_then(|_| output.flush())
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Runs the requests received since the last call on `app`, models
+    /// being loaded into `object` after `prepare`. Returns false once a
+    /// request asked to quit.
+    pub fn poll(&self, app: &mut App, object: ObjectHandle, prepare: &dyn Fn(&mut Mesh)) -> bool {
+        let mut running = true;
+        while let Ok((line, reply)) = self.requests.try_recv() {
+            let (id, outcome) = match Json::parse_document(&line) {
+                Ok(request) => {
+                    let id = request.find("id").map(Self::id);
+                    let authorized = self.token.as_deref().map_or(true, |token| {
+                        request.find("token").and_then(|t| t.string().ok()) == Some(token)
+                    });
+                    let outcome = match request.get("method").and_then(Json::string) {
+                        _ if !authorized => Err((
+                            Self::UNAUTHORIZED,
+                            "Missing or wrong session token".to_string(),
+                        )),
+                        Ok("quit") => {
+                            running = false;
+                            Ok("null".to_string())
+                        }
+                        Ok(method) => {
+                            let no_params = Json::Object(Vec::new());
+                            let params = request.find("params").unwrap_or(&no_params);
+                            self.run(app, object, prepare, method, params)
+                        }
+                        Err(e) => Err((Self::INVALID_REQUEST, e)),
+                    };
+                    (id, outcome)
+                }
+                Err(e) => (None, Err((Self::PARSE_ERROR, e))),
+            };
+            // Notifications get no response, unless they could not be read
+            // as requests at all.
+            let notification = id.is_none()
+                && !matches!(outcome, Err((Self::PARSE_ERROR | Self::INVALID_REQUEST, _)));
+            let id = id.unwrap_or_else(|| "null".to_string());
+            let response = match outcome {
+                Ok(result) => format!(
+                    "{{\"jsonrpc\": \"2.0\", \"id\": {}, \"result\": {}}}",
+                    id, result
+                ),
+                Err((code, message)) => format!(
+                    "{{\"jsonrpc\": \"2.0\", \"id\": {}, \"error\": {{\"code\": {}, \"message\": {}}}}}",
+                    id,
+                    code,
+                    json_string(&message)
+                ),
+            };
+            // A client gone meanwhile needs no response.
+            let _ = reply.send((!notification).then_some(response));
+        }
+        running
+    }
+
+    /// A request id echoed back in its response.
+    fn id(id: &Json) -> String {
+        match id {
+            Json::Number(number) => number.to_string(),
+            Json::String(string) => json_string(string),
+            _ => "null".to_string(),
+        }
+    }
+
+    /// Where a screenshot named `path` goes: under the screenshot directory,
+    /// which `path` must not leave.
+    fn screenshot_path(&self, path: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(path);
+        if relative.as_os_str().is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(format!(
+                "Expected a path relative to the screenshot directory, got '{}'",
+                path
+            ));
+        }
+        Ok(self.directory.join(relative))
+    }
+
+    /// Runs `method`, returning its JSON result.
+    fn run(
+        &self,
+        app: &mut App,
+        object: ObjectHandle,
+        prepare: &dyn Fn(&mut Mesh),
+        method: &str,
+        params: &Json,
+    ) -> Result<String, (i32, String)> {
+        let vec = |v: Vec3| format!("[{}, {}, {}]", v.x, v.y, v.z);
+        let failed = |e: String| (Self::SERVER_ERROR, e);
+        match method {
+            "load_model" => {
+                let path = params.get("path").and_then(Json::string).map_err(failed)?;
+                // The client may export the file again while it is parsed.
+                let mut mesh = ObjFileParser::parse_copied(path)
+                    .map_err(|e| failed(format!("Failed to parse mesh '{}': {}", path, e)))?;
+                prepare(&mut mesh);
+                let handle = app.add_mesh(&mesh).map_err(failed)?;
+                app.get_object(object).set_mesh(handle);
+                Ok(format!("{{\"triangles\": {}}}", mesh.triangle_count()))
+            }
+            "get_camera" => {
+                let camera = app.camera();
+                Ok(format!(
+                    "{{\"position\": {}, \"direction\": {}, \"fov\": {}}}",
+                    vec(camera.position),
+                    vec(camera.forward()),
+                    camera.fov().to_degrees()
+                ))
+            }
+            "set_camera" => {
+                let vec3 = |key| params.find(key).map(Json::vec3).transpose().map_err(failed);
+                let (position, target, direction) =
+                    (vec3("position")?, vec3("target")?, vec3("direction")?);
+                let fov = params
+                    .find("fov")
+                    .map(Json::number)
+                    .transpose()
+                    .map_err(failed)?;
+                let camera = app.camera();
+                if let Some(position) = position {
+                    camera.position = position;
+                }
+                let direction = target.map(|target| target - camera.position).or(direction);
+                if let Some(direction) = direction {
+                    let direction = direction
+                        .try_normalize()
+                        .ok_or_else(|| failed("The camera direction is zero".to_string()))?;
+                    camera.look_towards(direction);
+                }
+                if let Some(fov) = fov {
+                    if !(1. ..=179.).contains(&fov) {
+                        return Err(failed(format!(
+                            "Expected a field of view in 1..=179 degrees, got {}",
+                            fov
+                        )));
+                    }
+                    camera.set_fov(fov.to_radians());
+                }
+                Ok("null".to_string())
+            }
+            "frame" => {
+                let camera = app.camera();
+                let (direction, ratio) = (camera.forward(), camera.ratio());
+                app.frame_scene(direction, ratio);
+                Ok("null".to_string())
+            }
+            "set_shading" => {
+                let mode = params.get("mode").and_then(Json::string).map_err(failed)?;
+                app.set_shading_mode(mode.parse().map_err(failed)?);
+                Ok("null".to_string())
+            }
+            "screenshot" => {
+                let path = params.get("path").and_then(Json::string).map_err(failed)?;
+                let size = |key, default| match params.find(key) {
+                    Some(value) => value
+                        .number()
+                        .ok()
+                        .filter(|size| (1. ..=16384.).contains(size))
+                        .map(|size| size as u32)
+                        .ok_or_else(|| failed(format!("Expected a size for \"{}\"", key))),
+                    None => Ok(default),
+                };
+                let (width, height) = (size("width", 800)?, size("height", 600)?);
+                let target = self.screenshot_path(path).map_err(failed)?;
+                let image = app.capture(width, height).map_err(failed)?;
+                image
+                    .save(&target)
+                    .map_err(|e| failed(format!("Failed to save '{}': {}", target.display(), e)))?;
+                Ok(format!(
+                    "{{\"path\": {}}}",
+                    json_string(&target.to_string_lossy())
+                ))
+            }
+            _ => Err((
+                Self::METHOD_NOT_FOUND,
+                format!("Unknown method '{}'", method),
+            )),
+        }
+    }
+}
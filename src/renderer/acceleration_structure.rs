@@ -0,0 +1,333 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::{ResourcesManager, VkBuffer, VkCommandPool, VkContext, VkDevice, VkQueue};
+use crate::math::{Mat4, Vec3};
+use crate::scene::{Mesh, Scene};
+
+/// Objects casting ray-queried shadows; the rest cast none.
+pub const MAX_SHADOW_CASTERS: usize = 4096;
+
+/// A bottom or top level acceleration structure and the buffer backing it.
+/// Only created on devices with ray query support.
+pub struct VkAccelerationStructure {
+    device: Arc<VkDevice>,
+    pub handle: vk::AccelerationStructureKHR,
+    pub address: vk::DeviceAddress,
+    _buffer: VkBuffer<u8>,
+}
+
+impl VkAccelerationStructure {
+    /// Bottom level structure over the triangles of `mesh`, in mesh space.
+    /// Skinned meshes keep their bind pose.
+    pub fn bottom_level(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        mesh: &Mesh,
+    ) -> Result<Self, String> {
+        let positions: Vec<Vec3> = mesh
+            .groups
+            .iter()
+            .flat_map(|group| {
+                group
+                    .indices
+                    .iter()
+                    .map(|&index| group.vertices[index as usize].position)
+            })
+            .collect();
+        if positions.is_empty() {
+            return Err("Mesh has no triangles".to_string());
+        }
+
+        let vertices = VkBuffer::device_local(
+            context,
+            queue,
+            command_pool,
+            &positions,
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+                | vk::BufferUsageFlags::TRANSFER_DST,
+        )?;
+
+        let geometry = vk::AccelerationStructureGeometryKHR {
+            s_type: vk::StructureType::ACCELERATION_STRUCTURE_GEOMETRY_KHR,
+            geometry_type: vk::GeometryTypeKHR::TRIANGLES,
+            geometry: vk::AccelerationStructureGeometryDataKHR {
+                triangles: vk::AccelerationStructureGeometryTrianglesDataKHR {
+                    s_type: vk::StructureType::ACCELERATION_STRUCTURE_GEOMETRY_TRIANGLES_DATA_KHR,
+                    vertex_format: vk::Format::R32G32B32_SFLOAT,
+                    vertex_data: vk::DeviceOrHostAddressConstKHR {
+                        device_address: vertices.device_address(),
+                    },
+                    vertex_stride: std::mem::size_of::<Vec3>() as vk::DeviceSize,
+                    max_vertex: positions.len() as u32 - 1,
+                    index_type: vk::IndexType::NONE_KHR,
+                    ..Default::default()
+                },
+            },
+            flags: vk::GeometryFlagsKHR::OPAQUE,
+            ..Default::default()
+        };
+
+        let triangles = positions.len() as u32 / 3;
+        let (structure, scratch) = Self::create(
+            context,
+            vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+            &geometry,
+            triangles,
+        )?;
+
+        let cmd = command_pool.begin_single_cmd()?;
+        structure.record_build(cmd, &geometry, &scratch, triangles);
+        command_pool.end_single_cmd(queue, cmd)?;
+
+        Ok(structure)
+    }
+
+    /// Allocates a structure of type `ty` large enough for `primitives` of
+    /// `geometry`, with a scratch buffer for building it.
+    fn create(
+        context: &VkContext,
+        ty: vk::AccelerationStructureTypeKHR,
+        geometry: &vk::AccelerationStructureGeometryKHR,
+        primitives: u32,
+    ) -> Result<(Self, VkBuffer<u8>), String> {
+        let device = context.device();
+        let loader = device
+            .acceleration_structure
+            .as_ref()
+            .ok_or("Acceleration structures are not supported")?;
+
+        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR {
+            s_type: vk::StructureType::ACCELERATION_STRUCTURE_BUILD_GEOMETRY_INFO_KHR,
+            ty,
+            flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
+            mode: vk::BuildAccelerationStructureModeKHR::BUILD,
+            geometry_count: 1,
+            p_geometries: geometry,
+            ..Default::default()
+        };
+        let mut sizes = vk::AccelerationStructureBuildSizesInfoKHR::default();
+        unsafe {
+            loader.get_acceleration_structure_build_sizes(
+                vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                &build_info,
+                &[primitives],
+                &mut sizes,
+            );
+        }
+
+        let buffer = VkBuffer::uninitialized(
+            context,
+            sizes.acceleration_structure_size as usize,
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        )?;
+        let scratch = VkBuffer::uninitialized(
+            context,
+            sizes.build_scratch_size as usize,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        )?;
+
+        let create_info = vk::AccelerationStructureCreateInfoKHR {
+            s_type: vk::StructureType::ACCELERATION_STRUCTURE_CREATE_INFO_KHR,
+            buffer: buffer.handle,
+            size: sizes.acceleration_structure_size,
+            ty,
+            ..Default::default()
+        };
+        let handle = unsafe {
+            loader
+                .create_acceleration_structure(&create_info, None)
+                .map_err(|e| format!("Failed to create acceleration structure: {}", e))?
+        };
+        let address = unsafe {
+            loader.get_acceleration_structure_device_address(
+                &vk::AccelerationStructureDeviceAddressInfoKHR {
+                    s_type: vk::StructureType::ACCELERATION_STRUCTURE_DEVICE_ADDRESS_INFO_KHR,
+                    acceleration_structure: handle,
+                    ..Default::default()
+                },
+            )
+        };
+
+        Ok((
+            Self {
+                device,
+                handle,
+                address,
+                _buffer: buffer,
+            },
+            scratch,
+        ))
+    }
+
+    /// Records a full build from `primitives` of `geometry`.
+    fn record_build(
+        &self,
+        cmd: vk::CommandBuffer,
+        geometry: &vk::AccelerationStructureGeometryKHR,
+        scratch: &VkBuffer<u8>,
+        primitives: u32,
+    ) {
+        let Some(loader) = &self.device.acceleration_structure else {
+            return;
+        };
+
+        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR {
+            s_type: vk::StructureType::ACCELERATION_STRUCTURE_BUILD_GEOMETRY_INFO_KHR,
+            ty: if geometry.geometry_type == vk::GeometryTypeKHR::INSTANCES {
+                vk::AccelerationStructureTypeKHR::TOP_LEVEL
+            } else {
+                vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL
+            },
+            flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
+            mode: vk::BuildAccelerationStructureModeKHR::BUILD,
+            dst_acceleration_structure: self.handle,
+            geometry_count: 1,
+            p_geometries: geometry,
+            scratch_data: vk::DeviceOrHostAddressKHR {
+                device_address: scratch.device_address(),
+            },
+            ..Default::default()
+        };
+        let range = vk::AccelerationStructureBuildRangeInfoKHR {
+            primitive_count: primitives,
+            ..Default::default()
+        };
+
+        unsafe {
+            loader.cmd_build_acceleration_structures(cmd, &[build_info], &[&[range]]);
+        }
+    }
+}
+
+impl Drop for VkAccelerationStructure {
+    fn drop(&mut self) {
+        if let Some(loader) = &self.device.acceleration_structure {
+            unsafe {
+                loader.destroy_acceleration_structure(self.handle, None);
+            }
+        }
+    }
+}
+
+/// Top level structure over the scene's objects, rebuilt every frame so the
+/// shadows follow them. One per frame in flight.
+pub struct SceneAccelerationStructure {
+    pub structure: VkAccelerationStructure,
+    instances: VkBuffer<vk::AccelerationStructureInstanceKHR>,
+    scratch: VkBuffer<u8>,
+    instance_count: u32,
+}
+
+impl SceneAccelerationStructure {
+    pub fn new(context: &VkContext) -> Result<Self, String> {
+        let instances = VkBuffer::host_visible(
+            context,
+            MAX_SHADOW_CASTERS,
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        )?;
+        let (structure, scratch) = VkAccelerationStructure::create(
+            context,
+            vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+            &Self::geometry(&instances),
+            MAX_SHADOW_CASTERS as u32,
+        )?;
+
+        Ok(Self {
+            structure,
+            instances,
+            scratch,
+            instance_count: 0,
+        })
+    }
+
+    fn geometry(
+        instances: &VkBuffer<vk::AccelerationStructureInstanceKHR>,
+    ) -> vk::AccelerationStructureGeometryKHR<'static> {
+        vk::AccelerationStructureGeometryKHR {
+            s_type: vk::StructureType::ACCELERATION_STRUCTURE_GEOMETRY_KHR,
+            geometry_type: vk::GeometryTypeKHR::INSTANCES,
+            geometry: vk::AccelerationStructureGeometryDataKHR {
+                instances: vk::AccelerationStructureGeometryInstancesDataKHR {
+                    s_type: vk::StructureType::ACCELERATION_STRUCTURE_GEOMETRY_INSTANCES_DATA_KHR,
+                    array_of_pointers: vk::FALSE,
+                    data: vk::DeviceOrHostAddressConstKHR {
+                        device_address: instances.device_address(),
+                    },
+                    ..Default::default()
+                },
+            },
+            flags: vk::GeometryFlagsKHR::OPAQUE,
+            ..Default::default()
+        }
+    }
+
+    /// Places every object whose mesh has a bottom level structure. The frame
+    /// must not be in flight.
+    pub fn update(&mut self, scene: &Scene, resources: &ResourcesManager) {
+        let instances: Vec<_> = scene
+            .objects
+            .iter()
+            .filter_map(|object| {
+                let blas = resources.get_mesh(object.id()).blas.as_ref()?;
+                Some(vk::AccelerationStructureInstanceKHR {
+                    transform: Self::transform(&object.transform()),
+                    instance_custom_index_and_mask: vk::Packed24_8::new(0, 0xff),
+                    instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(
+                        0,
+                        vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE.as_raw() as u8,
+                    ),
+                    acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+                        device_handle: blas.address,
+                    },
+                })
+            })
+            .take(MAX_SHADOW_CASTERS)
+            .collect();
+
+        self.instances.write(&instances);
+        self.instance_count = instances.len() as u32;
+    }
+
+    /// Rows of the top 3x4 part of `m`.
+    fn transform(m: &Mat4) -> vk::TransformMatrixKHR {
+        let mut matrix = [0.; 12];
+        for row in 0..3 {
+            for col in 0..4 {
+                matrix[row * 4 + col] = m.get(col, row);
+            }
+        }
+        vk::TransformMatrixKHR { matrix }
+    }
+
+    /// Rebuilds the structure from the last `update` and makes it visible to
+    /// fragment shaders. Must be recorded outside a render pass.
+    pub fn record_build(&self, device: &VkDevice, cmd: vk::CommandBuffer) {
+        let geometry = Self::geometry(&self.instances);
+        self.structure
+            .record_build(cmd, &geometry, &self.scratch, self.instance_count);
+
+        let barrier = vk::MemoryBarrier {
+            s_type: vk::StructureType::MEMORY_BARRIER,
+            src_access_mask: vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR,
+            dst_access_mask: vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR,
+            ..Default::default()
+        };
+        unsafe {
+            device.handle.cmd_pipeline_barrier(
+                cmd,
+                vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[barrier],
+                &[],
+                &[],
+            );
+        }
+    }
+}
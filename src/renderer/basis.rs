@@ -0,0 +1,716 @@
+use ash::vk;
+use std::collections::HashMap;
+
+use super::{CompressedFormats, CompressedImage, EAC_MODIFIERS};
+
+/// Modifiers of ETC1S colors by intensity, from the darkest selector to the
+/// brightest.
+const INTENSITIES: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+
+/// ETC1 pixel index of each ETC1S selector.
+const ETC1_SELECTORS: [u8; 4] = [3, 2, 0, 1];
+
+/// Order the code lengths of the code length table are stored in.
+const CODE_LENGTH_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+/// Predictor symbol repeating the previous one.
+const ENDPOINT_PRED_REPEAT: u32 = 256;
+
+/// Run lengths of the selector history start at this count.
+const SELECTOR_RUN_MIN: u32 = 3;
+
+/// Run symbol followed by a longer, variable length count.
+const SELECTOR_RUN_LONG: u32 = 63;
+
+/// Image flag of video frames predicted from the previous one.
+const P_FRAME: u32 = 2;
+
+/// Reads BasisLZ streams, least significant bit first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Next `count` bits, zeros past the end as in the reference decoder.
+    fn bits(&mut self, count: u32) -> u32 {
+        while self.count < count {
+            let byte = self.bytes.get(self.position).copied().unwrap_or(0);
+            self.position += 1;
+            self.buffer |= (byte as u64) << self.count;
+            self.count += 8;
+        }
+        let value = (self.buffer & ((1 << count) - 1)) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        value
+    }
+
+    /// Variable length number stored in chunks of `chunk_bits`, each
+    /// followed by a bit telling whether another one comes.
+    fn vlc(&mut self, chunk_bits: u32) -> Result<u32, String> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let chunk = self.bits(chunk_bits + 1);
+            value |= (chunk & ((1 << chunk_bits) - 1)) << shift;
+            shift += chunk_bits;
+            if chunk & (1 << chunk_bits) == 0 {
+                return Ok(value);
+            }
+            if shift >= 32 {
+                return Err("BasisLZ number is too long".to_string());
+            }
+        }
+    }
+
+    fn symbol(&mut self, table: &Huffman) -> Result<u32, String> {
+        table.decode(self)
+    }
+
+    /// Huffman table stored as its code lengths, themselves Huffman coded
+    /// with runs of zeros and repeats.
+    fn huffman(&mut self) -> Result<Huffman, String> {
+        let symbol_count = self.bits(14) as usize;
+        if symbol_count == 0 {
+            return Huffman::new(&[]);
+        }
+
+        let code_length_count = self.bits(5) as usize;
+        if !(1..=CODE_LENGTH_ORDER.len()).contains(&code_length_count) {
+            return Err("Invalid BasisLZ Huffman table".to_string());
+        }
+        let mut code_length_lengths = [0; 21];
+        for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_length_lengths[symbol] = self.bits(3) as u8;
+        }
+        let code_lengths = Huffman::new(&code_length_lengths)?;
+
+        let mut lengths = vec![0; symbol_count];
+        let mut current = 0;
+        while current < symbol_count {
+            let (value, run) = match self.symbol(&code_lengths)? {
+                length @ 0..=16 => (length as u8, 1),
+                17 => (0, self.bits(3) + 3),
+                18 => (0, self.bits(7) + 11),
+                code => {
+                    let previous = match current {
+                        0 => 0,
+                        _ => lengths[current - 1],
+                    };
+                    if previous == 0 {
+                        return Err("BasisLZ Huffman table repeats nothing".to_string());
+                    }
+                    let run = match code {
+                        19 => self.bits(2) + 3,
+                        _ => self.bits(7) + 7,
+                    };
+                    (previous, run)
+                }
+            };
+            let end = current + run as usize;
+            if end > symbol_count {
+                return Err("BasisLZ Huffman table overflows".to_string());
+            }
+            lengths[current..end].fill(value);
+            current = end;
+        }
+        Huffman::new(&lengths)
+    }
+}
+
+/// Canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    /// Codes of each length, up to 16 bits.
+    counts: [u32; 17],
+    /// Symbols by increasing code length, then value.
+    symbols: Vec<u32>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let mut counts = [0; 17];
+        for &length in lengths {
+            if length > 16 {
+                return Err("BasisLZ Huffman code is too long".to_string());
+            }
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols = Vec::new();
+        for length in 1..=16 {
+            symbols.extend(
+                lengths
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &l)| l as usize == length)
+                    .map(|(symbol, _)| symbol as u32),
+            );
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u32, String> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1);
+            if code >= first && code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid BasisLZ Huffman code".to_string())
+    }
+}
+
+/// Base color of ETC1S blocks, in 5 bits per channel, and the row of
+/// `INTENSITIES` modifying it.
+#[derive(Debug, Clone, Copy)]
+struct Endpoint {
+    color: [u8; 3],
+    intensity: u8,
+}
+
+impl Endpoint {
+    /// Color of each selector.
+    fn colors(&self) -> [[u8; 3]; 4] {
+        INTENSITIES[self.intensity as usize].map(|modifier| {
+            self.color.map(|c| {
+                let c = (c << 3 | c >> 2) as i32;
+                (c + modifier).clamp(0, 255) as u8
+            })
+        })
+    }
+}
+
+/// Selector of each pixel of a block, row by row.
+type Selector = [u8; 16];
+
+/// Codebooks and Huffman tables shared by every slice of a BasisLZ file.
+struct Codebooks {
+    endpoints: Vec<Endpoint>,
+    selectors: Vec<Selector>,
+    endpoint_pred: Huffman,
+    delta_endpoint: Huffman,
+    selector: Huffman,
+    selector_run: Huffman,
+    history_size: usize,
+}
+
+impl Codebooks {
+    fn new(
+        (endpoint_count, endpoints): (usize, &[u8]),
+        (selector_count, selectors): (usize, &[u8]),
+        tables: &[u8],
+    ) -> Result<Self, String> {
+        let mut reader = BitReader::new(endpoints);
+        let color_models = [reader.huffman()?, reader.huffman()?, reader.huffman()?];
+        let intensity_model = reader.huffman()?;
+        let grayscale = reader.bits(1) == 1;
+        let mut color = [16; 3];
+        let mut intensity = 0;
+        let mut endpoint_list = Vec::with_capacity(endpoint_count);
+        for _ in 0..endpoint_count {
+            intensity = (intensity + reader.symbol(&intensity_model)?) & 7;
+            let channels = if grayscale { 1 } else { 3 };
+            for channel in &mut color[..channels] {
+                let model = match *channel {
+                    0..=9 => &color_models[0],
+                    10..=21 => &color_models[1],
+                    _ => &color_models[2],
+                };
+                *channel = (*channel + reader.symbol(model)?) & 31;
+            }
+            if grayscale {
+                color = [color[0]; 3];
+            }
+            endpoint_list.push(Endpoint {
+                color: color.map(|c| c as u8),
+                intensity: intensity as u8,
+            });
+        }
+
+        let mut reader = BitReader::new(selectors);
+        if reader.bits(1) == 1 || reader.bits(1) == 1 {
+            return Err("BasisLZ global selector codebooks are not supported".to_string());
+        }
+        let raw = reader.bits(1) == 1;
+        let delta_model = if raw { None } else { Some(reader.huffman()?) };
+        let mut previous = [0; 4];
+        let mut selector_list = Vec::with_capacity(selector_count);
+        for index in 0..selector_count {
+            let mut selector = [0; 16];
+            for (row, previous) in previous.iter_mut().enumerate() {
+                let byte = match &delta_model {
+                    Some(model) if index > 0 => reader.symbol(model)? ^ *previous,
+                    _ => reader.bits(8),
+                };
+                *previous = byte;
+                for x in 0..4 {
+                    selector[row * 4 + x] = ((byte >> (2 * x)) & 3) as u8;
+                }
+            }
+            selector_list.push(selector);
+        }
+
+        let mut reader = BitReader::new(tables);
+        Ok(Self {
+            endpoints: endpoint_list,
+            selectors: selector_list,
+            endpoint_pred: reader.huffman()?,
+            delta_endpoint: reader.huffman()?,
+            selector: reader.huffman()?,
+            selector_run: reader.huffman()?,
+            history_size: reader.bits(13) as usize,
+        })
+    }
+
+    /// Endpoint and selector of each block of a slice `blocks_x` blocks
+    /// wide, row by row.
+    fn decode_slice(
+        &self,
+        data: &[u8],
+        (blocks_x, blocks_y): (usize, usize),
+    ) -> Result<Vec<(usize, usize)>, String> {
+        let invalid = || "Invalid BasisLZ slice".to_string();
+        let mut reader = BitReader::new(data);
+        let selector_count = self.selectors.len();
+        let run_symbol = (selector_count + self.history_size) as u32;
+
+        // Recently used selectors, moved towards the front when reused.
+        let mut history = vec![0; self.history_size];
+        let mut history_rover = self.history_size / 2;
+        let mut run = 0;
+
+        // Per column: the endpoint of the latest block and the predictors
+        // of the next row, the row's parity picking the half.
+        let mut previous_row = vec![(0, 0); blocks_x];
+        let mut current_row = vec![(0, 0); blocks_x];
+        let mut pred_bits = 0;
+        let mut previous_pred = 0;
+        let mut pred_repeat = 0;
+        let mut previous_endpoint = 0;
+
+        let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+        for y in 0..blocks_y {
+            for x in 0..blocks_x {
+                // Predictors come in groups of 2 by 2 blocks.
+                if x % 2 == 0 {
+                    if y % 2 == 0 {
+                        if pred_repeat > 0 {
+                            pred_repeat -= 1;
+                            pred_bits = previous_pred;
+                        } else {
+                            pred_bits = reader.symbol(&self.endpoint_pred)?;
+                            if pred_bits == ENDPOINT_PRED_REPEAT {
+                                pred_repeat = reader.vlc(4)? + 3 - 1;
+                                pred_bits = previous_pred;
+                            } else {
+                                previous_pred = pred_bits;
+                            }
+                        }
+                        current_row[x].1 = pred_bits >> 4;
+                    } else {
+                        pred_bits = previous_row[x].1;
+                    }
+                }
+
+                let endpoint = match pred_bits & 3 {
+                    0 if x > 0 => previous_endpoint,
+                    1 if y > 0 => previous_row[x].0,
+                    2 if x > 0 && y > 0 => previous_row[x - 1].0,
+                    3 => {
+                        let delta = reader.symbol(&self.delta_endpoint)? as usize;
+                        (previous_endpoint + delta) % self.endpoints.len().max(1)
+                    }
+                    _ => return Err(invalid()),
+                };
+                pred_bits >>= 2;
+                current_row[x].0 = endpoint;
+                previous_endpoint = endpoint;
+
+                let symbol: u32 = if run > 0 {
+                    run -= 1;
+                    selector_count as u32
+                } else {
+                    match reader.symbol(&self.selector)? {
+                        symbol if symbol == run_symbol => {
+                            let count = match reader.symbol(&self.selector_run)? {
+                                SELECTOR_RUN_LONG => reader.vlc(7)? + SELECTOR_RUN_MIN,
+                                count => count + SELECTOR_RUN_MIN,
+                            };
+                            if count as usize > blocks_x * blocks_y {
+                                return Err(invalid());
+                            }
+                            run = count - 1;
+                            selector_count as u32
+                        }
+                        symbol => symbol,
+                    }
+                };
+                let symbol = symbol as usize;
+                let selector = if symbol >= selector_count {
+                    let index = symbol - selector_count;
+                    let selector = *history.get(index).ok_or_else(invalid)?;
+                    history.swap(index / 2, index);
+                    selector
+                } else {
+                    if !history.is_empty() {
+                        history[history_rover] = symbol;
+                        history_rover += 1;
+                        if history_rover == history.len() {
+                            history_rover = history.len() / 2;
+                        }
+                    }
+                    symbol
+                };
+
+                if endpoint >= self.endpoints.len() || selector >= selector_count {
+                    return Err(invalid());
+                }
+                blocks.push((endpoint, selector));
+            }
+            std::mem::swap(&mut previous_row, &mut current_row);
+        }
+        Ok(blocks)
+    }
+}
+
+/// Formats ETC1S slices are transcoded to, from the closest to their
+/// blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// ETC1S blocks are ETC1 blocks, themselves ETC2 RGB blocks.
+    Etc2,
+    Bc,
+    Rgba8,
+}
+
+impl Target {
+    fn format(self, alpha: bool, srgb: bool) -> vk::Format {
+        let (linear, nonlinear) = match (self, alpha) {
+            (Self::Etc2, false) => (
+                vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+                vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+            ),
+            (Self::Etc2, true) => (
+                vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+                vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+            ),
+            (Self::Bc, false) => (
+                vk::Format::BC1_RGB_UNORM_BLOCK,
+                vk::Format::BC1_RGB_SRGB_BLOCK,
+            ),
+            (Self::Bc, true) => (vk::Format::BC3_UNORM_BLOCK, vk::Format::BC3_SRGB_BLOCK),
+            (Self::Rgba8, _) => (vk::Format::R8G8B8A8_UNORM, vk::Format::R8G8B8A8_SRGB),
+        };
+        if srgb {
+            nonlinear
+        } else {
+            linear
+        }
+    }
+}
+
+/// Transcodes the ETC1S levels of a BasisLZ supercompressed KTX2 file to
+/// the closest format the device samples: ETC2 without loss, BC1 or BC3
+/// approximately, RGBA8 otherwise. `global` is the file's supercompression
+/// global data, and `levels` the compressed data of each level, the largest
+/// first.
+pub fn transcode_etc1s(
+    global: &[u8],
+    levels: &[&[u8]],
+    (width, height): (u32, u32),
+    srgb: bool,
+    formats: &CompressedFormats,
+) -> Result<CompressedImage, String> {
+    let truncated = || "BasisLZ global data is truncated".to_string();
+    let u16_at = |offset: usize| {
+        global
+            .get(offset..offset + 2)
+            .map(|field| u16::from_le_bytes([field[0], field[1]]) as usize)
+            .ok_or_else(truncated)
+    };
+    let u32_at = |offset: usize| {
+        global
+            .get(offset..offset + 4)
+            .map(|field| u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+            .ok_or_else(truncated)
+    };
+
+    let (endpoint_count, selector_count) = (u16_at(0)?, u16_at(2)?);
+    let lengths = [u32_at(4)?, u32_at(8)?, u32_at(12)?].map(|length| length as usize);
+    // Flags and the offset and length of the color and alpha slices, per
+    // level.
+    let image_descs = 20;
+    let mut sections = Vec::with_capacity(3);
+    let mut offset = image_descs + levels.len() * 20;
+    for length in lengths {
+        sections.push(global.get(offset..offset + length).ok_or_else(truncated)?);
+        offset += length;
+    }
+    let codebooks = Codebooks::new(
+        (endpoint_count, sections[0]),
+        (selector_count, sections[1]),
+        sections[2],
+    )?;
+
+    let alpha = u32_at(image_descs + 16)? > 0;
+    let target = if formats.etc2 {
+        Target::Etc2
+    } else if formats.bc {
+        Target::Bc
+    } else {
+        Target::Rgba8
+    };
+
+    let mut color_blocks = HashMap::new();
+    let mut alpha_blocks = HashMap::new();
+    let mut transcoded = Vec::with_capacity(levels.len());
+    for (level, data) in levels.iter().enumerate() {
+        let desc = image_descs + level * 20;
+        if u32_at(desc)? & P_FRAME != 0 {
+            return Err("BasisLZ video frames are not supported".to_string());
+        }
+        // Offset and length of a slice, within the level's data.
+        let slice = |field: usize| -> Result<std::ops::Range<usize>, String> {
+            let offset = u32_at(field)? as usize;
+            Ok(offset..offset + u32_at(field + 4)? as usize)
+        };
+        let truncated = || format!("BasisLZ slice of level {} is truncated", level);
+
+        let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+        let blocks = (
+            level_width.div_ceil(4) as usize,
+            level_height.div_ceil(4) as usize,
+        );
+        let colors = data.get(slice(desc + 4)?).ok_or_else(truncated)?;
+        let colors = codebooks.decode_slice(colors, blocks)?;
+        let alphas = match alpha {
+            true => {
+                let alphas = data.get(slice(desc + 12)?).ok_or_else(truncated)?;
+                Some(codebooks.decode_slice(alphas, blocks)?)
+            }
+            false => None,
+        };
+
+        let mut out = Vec::new();
+        if target == Target::Rgba8 {
+            out.resize(level_width as usize * level_height as usize * 4, 0);
+        }
+        for (index, &color) in colors.iter().enumerate() {
+            let alpha = alphas.as_ref().map(|alphas| alphas[index]);
+            match target {
+                Target::Etc2 | Target::Bc => {
+                    if let Some(alpha) = alpha {
+                        let block = alpha_blocks.entry(alpha).or_insert_with(|| match target {
+                            Target::Etc2 => eac_block(&block_alphas(&codebooks, alpha)),
+                            _ => bc3_alpha_block(&block_alphas(&codebooks, alpha)),
+                        });
+                        out.extend_from_slice(block);
+                    }
+                    let block = color_blocks.entry(color).or_insert_with(|| {
+                        let (endpoint, selector) =
+                            (&codebooks.endpoints[color.0], &codebooks.selectors[color.1]);
+                        match target {
+                            Target::Etc2 => etc1_block(endpoint, selector),
+                            _ => bc1_block(&endpoint.colors(), selector),
+                        }
+                    });
+                    out.extend_from_slice(block);
+                }
+                Target::Rgba8 => {
+                    let palette = codebooks.endpoints[color.0].colors();
+                    let selector = &codebooks.selectors[color.1];
+                    let alphas = alpha.map(|alpha| block_alphas(&codebooks, alpha));
+                    let (block_x, block_y) = (index % blocks.0, index / blocks.0);
+                    for (pixel, &s) in selector.iter().enumerate() {
+                        let x = block_x * 4 + pixel % 4;
+                        let y = block_y * 4 + pixel / 4;
+                        if x >= level_width as usize || y >= level_height as usize {
+                            continue;
+                        }
+                        let start = (y * level_width as usize + x) * 4;
+                        let [r, g, b] = palette[s as usize];
+                        let a = alphas.map_or(255, |alphas| alphas[pixel]);
+                        out[start..start + 4].copy_from_slice(&[r, g, b, a]);
+                    }
+                }
+            }
+        }
+        transcoded.push(out);
+    }
+
+    Ok(CompressedImage {
+        format: target.format(alpha, srgb),
+        width,
+        height,
+        levels: transcoded,
+    })
+}
+
+/// Alpha of each pixel of an alpha slice block, its green channel.
+fn block_alphas(codebooks: &Codebooks, (endpoint, selector): (usize, usize)) -> [u8; 16] {
+    let palette = codebooks.endpoints[endpoint].colors();
+    codebooks.selectors[selector].map(|s| palette[s as usize][1])
+}
+
+/// ETC1 block in differential mode with both halves alike, as ETC1S
+/// blocks are.
+fn etc1_block(endpoint: &Endpoint, selector: &Selector) -> [u8; 8] {
+    let [r, g, b] = endpoint.color;
+    let mut indices = 0u32;
+    for (pixel, &s) in selector.iter().enumerate() {
+        // Pixels are stored column by column.
+        let i = (pixel % 4) * 4 + pixel / 4;
+        let index = ETC1_SELECTORS[s as usize] as u32;
+        indices |= (index >> 1) << (16 + i) | (index & 1) << i;
+    }
+    let intensity = endpoint.intensity;
+    let indices = indices.to_be_bytes();
+    [
+        r << 3,
+        g << 3,
+        b << 3,
+        intensity << 5 | intensity << 2 | 2,
+        indices[0],
+        indices[1],
+        indices[2],
+        indices[3],
+    ]
+}
+
+fn to_rgb565(color: [u8; 3]) -> u16 {
+    (color[0] as u16 >> 3) << 11 | (color[1] as u16 >> 2) << 5 | color[2] as u16 >> 3
+}
+
+fn from_rgb565(color: u16) -> [i32; 3] {
+    let (r, g, b) = (
+        (color >> 11) as i32,
+        (color >> 5 & 63) as i32,
+        (color & 31) as i32,
+    );
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+/// BC1 block between the darkest and brightest colors of an ETC1S block,
+/// each pixel taking the BC1 color nearest its own.
+fn bc1_block(palette: &[[u8; 3]; 4], selector: &Selector) -> [u8; 8] {
+    let (mut high, mut low) = (to_rgb565(palette[3]), to_rgb565(palette[0]));
+    let mut indices = 0u32;
+    if high != low {
+        if high < low {
+            std::mem::swap(&mut high, &mut low);
+        }
+        let (e0, e1) = (from_rgb565(high), from_rgb565(low));
+        let bc1: [[i32; 3]; 4] = [
+            e0,
+            e1,
+            [0, 1, 2].map(|c| (2 * e0[c] + e1[c]) / 3),
+            [0, 1, 2].map(|c| (e0[c] + 2 * e1[c]) / 3),
+        ];
+        let nearest = palette.map(|color| {
+            (0..4)
+                .min_by_key(|&index| {
+                    (0..3)
+                        .map(|c| (bc1[index][c] - color[c] as i32).pow(2))
+                        .sum::<i32>()
+                })
+                .unwrap_or(0) as u32
+        });
+        for (pixel, &s) in selector.iter().enumerate() {
+            indices |= nearest[s as usize] << (2 * pixel);
+        }
+    }
+    let [h0, h1] = high.to_le_bytes();
+    let [l0, l1] = low.to_le_bytes();
+    let [i0, i1, i2, i3] = indices.to_le_bytes();
+    [h0, h1, l0, l1, i0, i1, i2, i3]
+}
+
+/// BC3 alpha block interpolating between the extremes of `alphas`.
+fn bc3_alpha_block(alphas: &[u8; 16]) -> [u8; 8] {
+    let high = alphas.iter().copied().max().unwrap_or(255) as i32;
+    let low = alphas.iter().copied().min().unwrap_or(255) as i32;
+    let mut block = [high as u8, low as u8, 0, 0, 0, 0, 0, 0];
+    if high == low {
+        return block;
+    }
+
+    let palette: Vec<i32> = (0..8)
+        .map(|i| match i {
+            0 => high,
+            1 => low,
+            i => ((8 - i) * high + (i - 1) * low) / 7,
+        })
+        .collect();
+    let mut indices = 0u64;
+    for (pixel, &alpha) in alphas.iter().enumerate() {
+        let index = (0..8)
+            .min_by_key(|&i| (palette[i] - alpha as i32).abs())
+            .unwrap_or(0) as u64;
+        indices |= index << (3 * pixel);
+    }
+    block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+/// EAC alpha block of ETC2 RGBA8 fitting `alphas`: each table spans their
+/// range with the closest multiplier, and the one erring least is kept.
+fn eac_block(alphas: &[u8; 16]) -> [u8; 8] {
+    let high = alphas.iter().copied().max().unwrap_or(255) as i32;
+    let low = alphas.iter().copied().min().unwrap_or(255) as i32;
+
+    let mut best = (i32::MAX, 0u64);
+    for (table, modifiers) in EAC_MODIFIERS.iter().enumerate() {
+        let span = modifiers[7] - modifiers[3];
+        let multiplier = ((high - low + span / 2) / span).clamp(1, 15);
+        let base =
+            ((high + low) / 2 - multiplier * (modifiers[7] + modifiers[3]) / 2).clamp(0, 255);
+
+        let mut error = 0;
+        let mut indices = 0u64;
+        for (pixel, &alpha) in alphas.iter().enumerate() {
+            let (index, value) = modifiers
+                .iter()
+                .map(|m| (base + m * multiplier).clamp(0, 255))
+                .enumerate()
+                .min_by_key(|&(_, value)| (value - alpha as i32).abs())
+                .unwrap_or((0, base));
+            error += (value - alpha as i32).pow(2);
+            // Pixels are stored column by column.
+            let i = (pixel % 4) * 4 + pixel / 4;
+            indices |= (index as u64) << (45 - 3 * i);
+        }
+        if error < best.0 {
+            let header = (base as u64) << 56 | (multiplier as u64) << 52 | (table as u64) << 48;
+            best = (error, header | indices);
+        }
+    }
+    best.1.to_be_bytes()
+}
@@ -20,9 +20,25 @@ impl<T: Copy> VkBuffer<T> {
         command_pool: &VkCommandPool,
         data: &[T],
         usage: vk::BufferUsageFlags,
+    ) -> Result<VkBuffer<T>, String> {
+        Self::device_local_with(context, queue, command_pool, data.len(), usage, |writer| {
+            writer.extend_from_slice(data)
+        })
+    }
+
+    /// Device-local buffer of `count` elements, which `fill` writes straight
+    /// into the mapped staging memory, so data generated on the fly needs no
+    /// copy of its own. `fill` must write exactly `count` elements.
+    pub fn device_local_with(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        count: usize,
+        usage: vk::BufferUsageFlags,
+        fill: impl FnOnce(&mut StagingWriter<T>),
     ) -> Result<VkBuffer<T>, String> {
         let device = context.device();
-        let size = (std::mem::size_of::<T>() * data.len()) as u64;
+        let size = (std::mem::size_of::<T>() * count) as u64;
 
         let staging_usage = vk::BufferUsageFlags::TRANSFER_SRC;
         let staging_properties =
@@ -30,50 +46,120 @@ impl<T: Copy> VkBuffer<T> {
 
         let (staging_buffer, staging_buffer_memory) =
             create_buffer(context, &size, &staging_usage, &staging_properties)?;
+        let destroy_staging = || unsafe {
+            device.handle.destroy_buffer(staging_buffer, None);
+            device.handle.free_memory(staging_buffer_memory, None);
+        };
 
-        let data_ptr = unsafe {
+        let data_ptr = match unsafe {
             device
                 .handle
                 .map_memory(staging_buffer_memory, 0, size, vk::MemoryMapFlags::empty())
-                .map_err(|e| format!("Failed to map staging buffer memory: {}", e))?
+        } {
+            Ok(ptr) => ptr,
+            Err(e) => {
+                destroy_staging();
+                return Err(format!("Failed to map staging buffer memory: {}", e));
+            }
         };
 
-        unsafe {
-            std::ptr::copy_nonoverlapping(data.as_ptr(), data_ptr as *mut T, data.len());
-            device.handle.unmap_memory(staging_buffer_memory);
+        let mut writer = StagingWriter {
+            ptr: data_ptr as *mut T,
+            len: 0,
+            capacity: count,
+            _memory: PhantomData,
+        };
+        fill(&mut writer);
+        let written = writer.len;
+        unsafe { device.handle.unmap_memory(staging_buffer_memory) };
+        if written != count {
+            destroy_staging();
+            return Err(format!(
+                "Staging buffer filled with {} of its {} elements",
+                written, count
+            ));
         }
 
         let target_properties = vk::MemoryPropertyFlags::DEVICE_LOCAL;
-        let (handle, memory) = create_buffer(context, &size, &usage, &target_properties)?;
-
-        let cmd = command_pool.begin_single_cmd()?;
-        unsafe {
-            device.handle.cmd_copy_buffer(
-                cmd,
-                staging_buffer,
-                handle,
-                &[vk::BufferCopy {
-                    src_offset: 0,
-                    dst_offset: 0,
-                    size,
-                }],
-            );
-        }
-        command_pool.end_single_cmd(queue, cmd)?;
+        let (handle, memory) = match create_buffer(context, &size, &usage, &target_properties) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                destroy_staging();
+                return Err(e);
+            }
+        };
 
-        unsafe {
-            device.handle.destroy_buffer(staging_buffer, None);
-            device.handle.free_memory(staging_buffer_memory, None);
-        }
+        let copied = command_pool.begin_single_cmd().and_then(|cmd| {
+            unsafe {
+                device.handle.cmd_copy_buffer(
+                    cmd,
+                    staging_buffer,
+                    handle,
+                    &[vk::BufferCopy {
+                        src_offset: 0,
+                        dst_offset: 0,
+                        size,
+                    }],
+                );
+            }
+            command_pool.end_single_cmd(queue, cmd)
+        });
+        destroy_staging();
 
-        Ok(VkBuffer {
+        let buffer = VkBuffer {
             device,
             handle,
             size,
             memory,
             mapped: None,
             _type: PhantomData,
-        })
+        };
+        copied.map(|_| buffer)
+    }
+}
+
+/// Appends elements to the mapped staging memory of a buffer being created
+/// by `VkBuffer::device_local_with`.
+pub struct StagingWriter<'a, T> {
+    ptr: *mut T,
+    len: usize,
+    capacity: usize,
+    _memory: PhantomData<&'a mut [T]>,
+}
+
+impl<T: Copy> StagingWriter<'_, T> {
+    /// Number of elements written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, value: T) {
+        assert!(self.len < self.capacity, "Staging buffer overflow!");
+        unsafe { self.ptr.add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        assert!(
+            values.len() <= self.capacity - self.len,
+            "Staging buffer overflow!"
+        );
+        unsafe {
+            std::ptr::copy_nonoverlapping(values.as_ptr(), self.ptr.add(self.len), values.len());
+        }
+        self.len += values.len();
+    }
+}
+
+impl<T: Copy> Extend<T> for StagingWriter<'_, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        for value in values {
+            self.push(value);
+        }
     }
 }
 
@@ -107,12 +193,61 @@ impl<T> VkBuffer<T> {
         })
     }
 
+    /// Device-local buffer of `count` elements, left for the GPU to fill.
+    pub fn uninitialized(
+        context: &VkContext,
+        count: usize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self, String> {
+        let device = context.device();
+        let size = (std::mem::size_of::<T>() * count) as u64;
+        let (handle, memory) = create_buffer(
+            context,
+            &size,
+            &usage,
+            &vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        Ok(Self {
+            device,
+            handle,
+            size,
+            memory,
+            mapped: None,
+            _type: PhantomData,
+        })
+    }
+
+    /// GPU address of the buffer, which must have been created with
+    /// `SHADER_DEVICE_ADDRESS` usage.
+    pub fn device_address(&self) -> vk::DeviceAddress {
+        let info = vk::BufferDeviceAddressInfo {
+            s_type: vk::StructureType::BUFFER_DEVICE_ADDRESS_INFO,
+            buffer: self.handle,
+            ..Default::default()
+        };
+        unsafe { self.device.handle.get_buffer_device_address(&info) }
+    }
+
+    /// Number of `T` elements the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.size as usize / std::mem::size_of::<T>()
+    }
+
     pub fn write(&self, data: &[T]) {
         let ptr = self.mapped.expect("Cannot write to a non-mapped buffer!");
         unsafe {
             std::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut T, data.len());
         }
     }
+
+    pub fn read(&self, count: usize) -> Vec<T>
+    where
+        T: Copy,
+    {
+        let ptr = self.mapped.expect("Cannot read from a non-mapped buffer!");
+        unsafe { std::slice::from_raw_parts(ptr as *const T, count).to_vec() }
+    }
 }
 
 impl<T> Drop for VkBuffer<T> {
@@ -155,8 +290,19 @@ pub fn create_buffer(
         .physical_device
         .find_memory_type(memory_requirements.memory_type_bits, *properties)?;
 
+    // Buffers read through their device address need memory allowing it.
+    let flags_info = vk::MemoryAllocateFlagsInfo {
+        s_type: vk::StructureType::MEMORY_ALLOCATE_FLAGS_INFO,
+        flags: vk::MemoryAllocateFlags::DEVICE_ADDRESS,
+        ..Default::default()
+    };
     let allocate_info = vk::MemoryAllocateInfo {
         s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+        p_next: if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            &flags_info as *const _ as *const c_void
+        } else {
+            std::ptr::null()
+        },
         allocation_size: memory_requirements.size,
         memory_type_index,
         ..Default::default()
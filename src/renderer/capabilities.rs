@@ -0,0 +1,140 @@
+use std::ffi::{CStr, FromBytesUntilNulError};
+use std::fmt::Write;
+
+use ash::{vk, Entry};
+
+use super::{VkInstance, VkSurface, DEVICE_EXTENSIONS};
+
+/// Where `report_capabilities` writes its report, in the working directory.
+pub const CAPABILITY_REPORT_PATH: &str = "scop-vulkan-report.txt";
+
+/// Logs what the Vulkan implementation offers after instance creation or
+/// device selection failed with `error`, and saves it to
+/// `CAPABILITY_REPORT_PATH` for bug reports. Devices are only listed when
+/// the instance and the window surface exist. Returns `error` pointing to the
+/// saved report.
+pub fn report_capabilities(
+    entry: &Entry,
+    instance: Option<(&VkInstance, &VkSurface)>,
+    error: String,
+) -> String {
+    let mut report = format!("Vulkan initialization failed: {}\n\n", error);
+    write_instance_capabilities(&mut report, entry);
+    if let Some((instance, surface)) = instance {
+        write_devices(&mut report, instance, surface);
+    }
+
+    eprint!("{}", report);
+    match std::fs::write(CAPABILITY_REPORT_PATH, &report) {
+        Ok(()) => format!(
+            "{} (capability report written to {})",
+            error, CAPABILITY_REPORT_PATH
+        ),
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", CAPABILITY_REPORT_PATH, e);
+            error
+        }
+    }
+}
+
+fn version(v: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(v),
+        vk::api_version_minor(v),
+        vk::api_version_patch(v)
+    )
+}
+
+fn name(name: Result<&CStr, FromBytesUntilNulError>) -> String {
+    name.map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "?".to_string())
+}
+
+fn write_instance_capabilities(report: &mut String, entry: &Entry) {
+    let api_version = unsafe { entry.try_enumerate_instance_version() }
+        .ok()
+        .flatten()
+        .unwrap_or(vk::API_VERSION_1_0);
+    let _ = writeln!(report, "Instance API version: {}", version(api_version));
+
+    let layers = unsafe { entry.enumerate_instance_layer_properties() }.unwrap_or_default();
+    let _ = writeln!(report, "Layers ({}):", layers.len());
+    for layer in &layers {
+        let _ = writeln!(
+            report,
+            "  {} (spec {})",
+            name(layer.layer_name_as_c_str()),
+            version(layer.spec_version)
+        );
+    }
+
+    let extensions =
+        unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap_or_default();
+    let _ = writeln!(report, "Instance extensions ({}):", extensions.len());
+    for extension in &extensions {
+        let _ = writeln!(report, "  {}", name(extension.extension_name_as_c_str()));
+    }
+}
+
+fn write_devices(report: &mut String, instance: &VkInstance, surface: &VkSurface) {
+    let devices = unsafe { instance.handle.enumerate_physical_devices() }.unwrap_or_default();
+    let _ = writeln!(report, "Physical devices ({}):", devices.len());
+
+    for (index, device) in devices.into_iter().enumerate() {
+        let properties = unsafe { instance.handle.get_physical_device_properties(device) };
+        let _ = writeln!(
+            report,
+            "  #{} {} ({:?}, vendor {:#06x}, driver {}, Vulkan {})",
+            index,
+            name(properties.device_name_as_c_str()),
+            properties.device_type,
+            properties.vendor_id,
+            version(properties.driver_version),
+            version(properties.api_version)
+        );
+
+        let families = unsafe {
+            instance
+                .handle
+                .get_physical_device_queue_family_properties(device)
+        };
+        for (index, family) in families.iter().enumerate() {
+            let present = unsafe {
+                surface
+                    .loader
+                    .get_physical_device_surface_support(device, index as u32, surface.handle)
+                    .unwrap_or(false)
+            };
+            let _ = writeln!(
+                report,
+                "    queue family {}: {:?} x{}{}",
+                index,
+                family.queue_flags,
+                family.queue_count,
+                if present { ", present" } else { "" }
+            );
+        }
+
+        let extensions = unsafe {
+            instance
+                .handle
+                .enumerate_device_extension_properties(device)
+        }
+        .unwrap_or_default();
+        let extensions: Vec<String> = extensions
+            .iter()
+            .map(|e| name(e.extension_name_as_c_str()))
+            .collect();
+        for required in DEVICE_EXTENSIONS {
+            let required = required.to_string_lossy();
+            if !extensions.iter().any(|name| *name == required) {
+                let _ = writeln!(report, "    missing required extension {}", required);
+            }
+        }
+        let _ = writeln!(report, "    extensions ({}):", extensions.len());
+        for extension in extensions {
+            let _ = writeln!(report, "      {}", extension);
+        }
+    }
+}
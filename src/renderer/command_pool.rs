@@ -2,6 +2,7 @@ use ash::vk;
 use std::sync::Arc;
 
 use super::{VkDevice, VkQueue};
+use crate::breadcrumb;
 
 pub struct VkCommandPool {
     device: Arc<VkDevice>,
@@ -104,11 +105,13 @@ impl VkCommandPool {
                     vk::Fence::null(),
                 )
                 .map_err(|e| format!("Failed to submit: {}", e))?;
+            breadcrumb!("vkQueueSubmit");
 
             self.device
                 .handle
                 .queue_wait_idle(queue.handle)
                 .map_err(|e| format!("Failed to wait idle: {}", e))?;
+            breadcrumb!("vkQueueWaitIdle");
 
             self.free_buffers(&[command_buffer]);
         }
@@ -123,6 +126,39 @@ impl VkCommandPool {
         image: vk::Image,
         width: u32,
         height: u32,
+    ) -> Result<(), String> {
+        self.copy_buffer_to_image_regions(
+            queue,
+            buffer,
+            image,
+            &[vk::BufferImageCopy {
+                buffer_offset: 0,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                image_extent: vk::Extent3D {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            }],
+        )
+    }
+
+    /// Copies several regions of `buffer` at once, e.g. every face and mip
+    /// level of a cubemap.
+    pub fn copy_buffer_to_image_regions(
+        &self,
+        queue: &VkQueue,
+        buffer: vk::Buffer,
+        image: vk::Image,
+        regions: &[vk::BufferImageCopy],
     ) -> Result<(), String> {
         let cmd = self.begin_single_cmd()?;
         unsafe {
@@ -131,23 +167,7 @@ impl VkCommandPool {
                 buffer,
                 image,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[vk::BufferImageCopy {
-                    buffer_offset: 0,
-                    buffer_row_length: 0,
-                    buffer_image_height: 0,
-                    image_subresource: vk::ImageSubresourceLayers {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        mip_level: 0,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    },
-                    image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
-                    image_extent: vk::Extent3D {
-                        width,
-                        height,
-                        depth: 1,
-                    },
-                }],
+                regions,
             );
         }
         self.end_single_cmd(queue, cmd)
@@ -159,6 +179,19 @@ impl VkCommandPool {
         image: vk::Image,
         old_layout: vk::ImageLayout,
         new_layout: vk::ImageLayout,
+    ) -> Result<(), String> {
+        self.transition_image_layers(queue, image, (1, 1), old_layout, new_layout)
+    }
+
+    /// Transitions the first `levels` mip levels of the first `layers` array
+    /// layers of `image`.
+    pub fn transition_image_layers(
+        &self,
+        queue: &VkQueue,
+        image: vk::Image,
+        (levels, layers): (u32, u32),
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
     ) -> Result<(), String> {
         let (src_access, dst_access, src_stage, dst_stage) = match (old_layout, new_layout) {
             (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL) => (
@@ -195,9 +228,9 @@ impl VkCommandPool {
                     subresource_range: vk::ImageSubresourceRange {
                         aspect_mask: vk::ImageAspectFlags::COLOR,
                         base_mip_level: 0,
-                        level_count: 1,
+                        level_count: levels,
                         base_array_layer: 0,
-                        layer_count: 1,
+                        layer_count: layers,
                     },
                     src_access_mask: src_access,
                     dst_access_mask: dst_access,
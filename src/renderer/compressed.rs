@@ -0,0 +1,440 @@
+use ash::vk;
+use image::RgbaImage;
+
+/// Block-compressed format families, in order of preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockCompression {
+    /// BC1 to BC7, on desktop GPUs.
+    Bc,
+    /// ASTC with low dynamic range, on most ARM and mobile GPUs.
+    Astc,
+    /// ETC2 and EAC, on every OpenGL ES 3 class GPU.
+    Etc2,
+}
+
+impl BlockCompression {
+    pub const ALL: [BlockCompression; 3] = [Self::Bc, Self::Astc, Self::Etc2];
+
+    /// Suffix of the texture variants compressed in the family, as in
+    /// `wood.astc.ktx2` for `wood.png`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Bc => "bc",
+            Self::Astc => "astc",
+            Self::Etc2 => "etc2",
+        }
+    }
+
+    /// Family of `format`, `None` for uncompressed formats.
+    pub fn of(format: vk::Format) -> Option<Self> {
+        match format.as_raw() {
+            131..=146 => Some(Self::Bc),
+            147..=156 => Some(Self::Etc2),
+            157..=184 => Some(Self::Astc),
+            _ => None,
+        }
+    }
+}
+
+/// Compressed format families a device samples, detected at device
+/// creation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompressedFormats {
+    pub bc: bool,
+    pub astc: bool,
+    pub etc2: bool,
+}
+
+impl CompressedFormats {
+    pub fn new(features: &vk::PhysicalDeviceFeatures) -> Self {
+        Self {
+            bc: features.texture_compression_bc == vk::TRUE,
+            astc: features.texture_compression_astc_ldr == vk::TRUE,
+            etc2: features.texture_compression_etc2 == vk::TRUE,
+        }
+    }
+
+    pub fn supports(&self, family: BlockCompression) -> bool {
+        match family {
+            BlockCompression::Bc => self.bc,
+            BlockCompression::Astc => self.astc,
+            BlockCompression::Etc2 => self.etc2,
+        }
+    }
+
+    /// Whether `format` can be sampled without decoding it first.
+    pub fn samples(&self, format: vk::Format) -> bool {
+        BlockCompression::of(format).is_none_or(|family| self.supports(family))
+    }
+}
+
+/// Width and height of the blocks of `format` and their size in bytes, one
+/// pixel per block for the uncompressed formats textures use.
+pub fn block_layout(format: vk::Format) -> Option<(u32, u32, usize)> {
+    const ASTC_BLOCKS: [(u32, u32); 14] = [
+        (4, 4),
+        (5, 4),
+        (5, 5),
+        (6, 5),
+        (6, 6),
+        (8, 5),
+        (8, 6),
+        (8, 8),
+        (10, 5),
+        (10, 6),
+        (10, 8),
+        (10, 10),
+        (12, 10),
+        (12, 12),
+    ];
+    let raw = format.as_raw();
+    match raw {
+        // R8G8B8A8, UNORM to SRGB.
+        37..=43 => Some((1, 1, 4)),
+        // BC1, BC4, ETC2 RGB8 and RGB8A1, EAC R11.
+        131..=134 | 139 | 140 | 147..=150 | 153 | 154 => Some((4, 4, 8)),
+        // BC2, BC3, BC5 to BC7, ETC2 RGBA8, EAC RG11.
+        135..=138 | 141..=146 | 151 | 152 | 155 | 156 => Some((4, 4, 16)),
+        // UNORM and SRGB of each block size.
+        157..=184 => {
+            let (width, height) = ASTC_BLOCKS[(raw - 157) as usize / 2];
+            Some((width, height, 16))
+        }
+        _ => None,
+    }
+}
+
+/// Bytes of a `width` by `height` image in `format`.
+pub fn image_size(format: vk::Format, width: u32, height: u32) -> Option<usize> {
+    let (block_width, block_height, block_size) = block_layout(format)?;
+    Some(width.div_ceil(block_width) as usize * height.div_ceil(block_height) as usize * block_size)
+}
+
+/// Texture data stored as the GPU samples it: its format and mip levels,
+/// the largest first.
+pub struct CompressedImage {
+    pub format: vk::Format,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    /// Decodes the largest level to RGBA8 for devices that can't sample the
+    /// format. BC1 to BC3 and ETC2 without punch-through alpha are handled.
+    pub fn decode_rgba8(&self) -> Result<RgbaImage, String> {
+        if matches!(
+            self.format,
+            vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB
+        ) {
+            let level = self.levels.first().cloned().unwrap_or_default();
+            return RgbaImage::from_raw(self.width, self.height, level)
+                .ok_or_else(|| "Texture level is truncated".to_string());
+        }
+        let decode: fn(&[u8], &mut [[u8; 4]; 16]) = match self.format {
+            vk::Format::BC1_RGB_UNORM_BLOCK | vk::Format::BC1_RGB_SRGB_BLOCK => {
+                |block, pixels| decode_bc_colors(block, pixels, true, false)
+            }
+            vk::Format::BC1_RGBA_UNORM_BLOCK | vk::Format::BC1_RGBA_SRGB_BLOCK => {
+                |block, pixels| decode_bc_colors(block, pixels, true, true)
+            }
+            vk::Format::BC2_UNORM_BLOCK | vk::Format::BC2_SRGB_BLOCK => decode_bc2,
+            vk::Format::BC3_UNORM_BLOCK | vk::Format::BC3_SRGB_BLOCK => decode_bc3,
+            vk::Format::ETC2_R8G8B8_UNORM_BLOCK | vk::Format::ETC2_R8G8B8_SRGB_BLOCK => {
+                |block, pixels| decode_etc2(block, pixels)
+            }
+            vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK => {
+                |block, pixels| {
+                    decode_etc2(&block[8..], pixels);
+                    decode_eac_alpha(&block[..8], pixels);
+                }
+            }
+            format => return Err(format!("Cannot decode {:?} textures", format)),
+        };
+        let Some((_, _, block_size)) = block_layout(self.format) else {
+            return Err(format!("Unknown texture format {:?}", self.format));
+        };
+        let level = self.levels.first().ok_or("Texture has no levels")?;
+        let blocks_wide = self.width.div_ceil(4);
+        if level.len() < image_size(self.format, self.width, self.height).unwrap_or(0) {
+            return Err("Texture level is truncated".to_string());
+        }
+
+        let mut image = RgbaImage::new(self.width, self.height);
+        let mut pixels = [[0; 4]; 16];
+        for (index, block) in level.chunks_exact(block_size).enumerate() {
+            let (block_x, block_y) = (index as u32 % blocks_wide, index as u32 / blocks_wide);
+            if block_y * 4 >= self.height {
+                break;
+            }
+            decode(block, &mut pixels);
+            for (pixel, color) in pixels.iter().enumerate() {
+                let x = block_x * 4 + pixel as u32 % 4;
+                let y = block_y * 4 + pixel as u32 / 4;
+                if x < self.width && y < self.height {
+                    image.put_pixel(x, y, image::Rgba(*color));
+                }
+            }
+        }
+        Ok(image)
+    }
+}
+
+fn rgb565(color: u16) -> [u8; 4] {
+    let extend = |value: u16, bits: u32| {
+        let value = value as u32;
+        ((value << (8 - bits)) | (value >> (2 * bits - 8))) as u8
+    };
+    [
+        extend(color >> 11, 5),
+        extend((color >> 5) & 0x3f, 6),
+        extend(color & 0x1f, 5),
+        255,
+    ]
+}
+
+/// Pixels of a BC1 color block, or the color half of BC2 and BC3 blocks, row
+/// by row. Only `bc1` blocks switch to three colors and black when their
+/// first endpoint isn't the greater, that black transparent with `alpha`.
+fn decode_bc_colors(block: &[u8], pixels: &mut [[u8; 4]; 16], bc1: bool, alpha: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let mix = |a: u8, b: u8, wa: u32, wb: u32| ((a as u32 * wa + b as u32 * wb) / (wa + wb)) as u8;
+    let mut palette = [e0, e1, [0; 4], [0; 4]];
+    if c0 > c1 || !bc1 {
+        for channel in 0..3 {
+            palette[2][channel] = mix(e0[channel], e1[channel], 2, 1);
+            palette[3][channel] = mix(e0[channel], e1[channel], 1, 2);
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for channel in 0..3 {
+            palette[2][channel] = mix(e0[channel], e1[channel], 1, 1);
+        }
+        palette[2][3] = 255;
+        palette[3] = [0, 0, 0, if alpha { 0 } else { 255 }];
+    }
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (pixel, color) in pixels.iter_mut().enumerate() {
+        *color = palette[(indices >> (2 * pixel) & 3) as usize];
+    }
+}
+
+/// Explicit 4-bit alpha, then a BC1 color block.
+fn decode_bc2(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    decode_bc_colors(&block[8..], pixels, false, false);
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap_or_default());
+    for (pixel, color) in pixels.iter_mut().enumerate() {
+        color[3] = ((alpha >> (4 * pixel)) & 0xf) as u8 * 17;
+    }
+}
+
+/// Interpolated alpha, then a BC1 color block.
+fn decode_bc3(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    decode_bc_colors(&block[8..], pixels, false, false);
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for (i, entry) in palette.iter_mut().enumerate().skip(2) {
+            *entry = ((8 - i as u32) * a0 + (i as u32 - 1) * a1) / 7;
+        }
+    } else {
+        for (i, entry) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *entry = ((6 - i as u32) * a0 + (i as u32 - 1) * a1) / 5;
+        }
+    }
+
+    let mut bytes = [0; 8];
+    bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+    for (pixel, color) in pixels.iter_mut().enumerate() {
+        color[3] = palette[(indices >> (3 * pixel) & 7) as usize] as u8;
+    }
+}
+
+const ETC_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+/// Pixels of an ETC2 RGB block, row by row, in any of its five modes.
+fn decode_etc2(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap_or_default());
+    let field = |high: u32, low: u32| ((bits >> low) & ((1 << (high - low + 1)) - 1)) as i32;
+    let extend4 = |value: i32| value * 17;
+    let extend5 = |value: i32| (value << 3) | (value >> 2);
+    let clamp = |value: i32| value.clamp(0, 255) as u8;
+    // Table index of the pixel at column `x` and row `y`.
+    let index = |x: usize, y: usize| {
+        let i = x * 4 + y;
+        (field(16 + i as u32, 16 + i as u32) << 1 | field(i as u32, i as u32)) as usize
+    };
+    let paint = |pixels: &mut [[u8; 4]; 16], colors: [[i32; 3]; 4]| {
+        for (pixel, color) in pixels.iter_mut().enumerate() {
+            let c = colors[index(pixel % 4, pixel / 4)];
+            *color = [clamp(c[0]), clamp(c[1]), clamp(c[2]), 255];
+        }
+    };
+
+    let differential = field(33, 33) == 1;
+    let (base0, base1) = if differential {
+        let delta = |high: u32| (field(high, high - 2) << 29) >> 29;
+        let r = (field(63, 59), field(63, 59) + delta(58));
+        let g = (field(55, 51), field(55, 51) + delta(50));
+        let b = (field(47, 43), field(47, 43) + delta(42));
+        if !(0..32).contains(&r.1) {
+            // T mode.
+            let c1 = [
+                extend4(field(60, 59) << 2 | field(57, 56)),
+                extend4(field(55, 52)),
+                extend4(field(51, 48)),
+            ];
+            let c2 = [
+                extend4(field(47, 44)),
+                extend4(field(43, 40)),
+                extend4(field(39, 36)),
+            ];
+            let d = ETC_DISTANCES[(field(35, 34) << 1 | field(32, 32)) as usize];
+            paint(pixels, [c1, c2.map(|c| c + d), c2, c2.map(|c| c - d)]);
+            return;
+        }
+        if !(0..32).contains(&g.1) {
+            // H mode.
+            let c1 = [
+                field(62, 59),
+                field(58, 56) << 1 | field(52, 52),
+                field(51, 51) << 3 | field(49, 47),
+            ];
+            let c2 = [field(46, 43), field(42, 39), field(38, 35)];
+            let order = |c: [i32; 3]| c[0] << 8 | c[1] << 4 | c[2];
+            let d = ETC_DISTANCES[(field(34, 34) << 2
+                | field(32, 32) << 1
+                | (order(c1) >= order(c2)) as i32) as usize];
+            let (c1, c2) = (c1.map(extend4), c2.map(extend4));
+            paint(
+                pixels,
+                [
+                    c1.map(|c| c + d),
+                    c1.map(|c| c - d),
+                    c2.map(|c| c + d),
+                    c2.map(|c| c - d),
+                ],
+            );
+            return;
+        }
+        if !(0..32).contains(&b.1) {
+            // Planar mode: a gradient from three colors.
+            let extend6 = |value: i32| (value << 2) | (value >> 4);
+            let extend7 = |value: i32| (value << 1) | (value >> 6);
+            let origin = [
+                extend6(field(62, 57)),
+                extend7(field(56, 56) << 6 | field(54, 49)),
+                extend6(field(48, 48) << 5 | field(44, 43) << 3 | field(41, 39)),
+            ];
+            let horizontal = [
+                extend6(field(38, 34) << 1 | field(32, 32)),
+                extend7(field(31, 25)),
+                extend6(field(24, 19)),
+            ];
+            let vertical = [
+                extend6(field(18, 13)),
+                extend7(field(12, 6)),
+                extend6(field(5, 0)),
+            ];
+            for (pixel, color) in pixels.iter_mut().enumerate() {
+                let (x, y) = ((pixel % 4) as i32, (pixel / 4) as i32);
+                let channel = |c: usize| {
+                    clamp(
+                        (x * (horizontal[c] - origin[c])
+                            + y * (vertical[c] - origin[c])
+                            + 4 * origin[c]
+                            + 2)
+                            >> 2,
+                    )
+                };
+                *color = [channel(0), channel(1), channel(2), 255];
+            }
+            return;
+        }
+        (
+            [extend5(r.0), extend5(g.0), extend5(b.0)],
+            [extend5(r.1), extend5(g.1), extend5(b.1)],
+        )
+    } else {
+        (
+            [
+                extend4(field(63, 60)),
+                extend4(field(55, 52)),
+                extend4(field(47, 44)),
+            ],
+            [
+                extend4(field(59, 56)),
+                extend4(field(51, 48)),
+                extend4(field(43, 40)),
+            ],
+        )
+    };
+
+    let tables = [field(39, 37) as usize, field(36, 34) as usize];
+    let flipped = field(32, 32) == 1;
+    for (pixel, color) in pixels.iter_mut().enumerate() {
+        let (x, y) = (pixel % 4, pixel / 4);
+        let second = if flipped { y >= 2 } else { x >= 2 };
+        let (base, [small, large]) = if second {
+            (base1, ETC_MODIFIERS[tables[1]])
+        } else {
+            (base0, ETC_MODIFIERS[tables[0]])
+        };
+        let modifier = [small, large, -small, -large][index(x, y)];
+        *color = [
+            clamp(base[0] + modifier),
+            clamp(base[1] + modifier),
+            clamp(base[2] + modifier),
+            255,
+        ];
+    }
+}
+
+/// Alpha modifiers of EAC blocks, by table.
+pub const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// Alpha of an ETC2 RGBA8 block, stored as EAC before its colors.
+fn decode_eac_alpha(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap_or_default());
+    let base = (bits >> 56) as i32;
+    let multiplier = ((bits >> 52) & 0xf) as i32;
+    let table = EAC_MODIFIERS[((bits >> 48) & 0xf) as usize];
+    for (pixel, color) in pixels.iter_mut().enumerate() {
+        let i = (pixel % 4) * 4 + pixel / 4;
+        let index = ((bits >> (45 - 3 * i)) & 7) as usize;
+        color[3] = (base + table[index] * multiplier).clamp(0, 255) as u8;
+    }
+}
@@ -1,31 +1,66 @@
-use ash::Entry;
-use sdl3::video::Window;
+use ash::{vk, Entry};
+use std::ffi::CString;
 use std::sync::Arc;
 
-use super::{VkDevice, VkInstance, VkPhysicalDevice, VkSurface};
+use super::{
+    report_capabilities, GpuSelector, SurfaceWindow, ValidationFeatures, VkDevice, VkInstance,
+    VkPhysicalDevice, VkSurface,
+};
+
+/// What the context must satisfy on top of the renderer's needs: the GPU the
+/// user picked, and the extensions a runtime sharing it needs, such as an
+/// OpenXR compositor.
+#[derive(Debug, Clone, Default)]
+pub struct ContextRequirements {
+    pub gpu: Option<GpuSelector>,
+    pub instance_extensions: Vec<CString>,
+    pub device_extensions: Vec<CString>,
+}
 
 pub struct VkContext {
     pub device: Arc<VkDevice>,
     pub physical_device: VkPhysicalDevice,
-    pub surface: VkSurface,
     pub instance: VkInstance,
     pub entry: Entry,
+    /// Validation the instance was created with, kept for device recovery.
+    pub validation: ValidationFeatures,
+    /// GPU choice and extensions for other runtimes, also kept for recovery.
+    pub requirements: ContextRequirements,
 }
 
 impl VkContext {
-    pub fn new(window: &Window) -> Result<VkContext, String> {
+    /// Picks a GPU able to present to `window`, the one the requirements
+    /// name if any. The surface used to check
+    /// it is dropped again, renderers create their own.
+    pub fn new(
+        window: &dyn SurfaceWindow,
+        validation: ValidationFeatures,
+        requirements: ContextRequirements,
+    ) -> Result<VkContext, String> {
         let entry = Entry::linked();
-        let instance = VkInstance::new(&entry, window)?;
+        let instance = VkInstance::new(
+            &entry,
+            window,
+            validation,
+            &requirements.instance_extensions,
+        )
+        .map_err(|e| report_capabilities(&entry, None, e))?;
         let surface = VkSurface::new(window, &entry, &instance)?;
-        let physical_device = VkPhysicalDevice::new(&instance, &surface)?;
-        let device = Arc::new(VkDevice::new(&instance, &physical_device)?);
+        let physical_device = VkPhysicalDevice::new(&instance, &surface, requirements.gpu.as_ref())
+            .map_err(|e| report_capabilities(&entry, Some((&instance, &surface)), e))?;
+        let device = Arc::new(VkDevice::new(
+            &instance,
+            &physical_device,
+            &requirements.device_extensions,
+        )?);
 
         Ok(Self {
             entry,
             instance,
-            surface,
             physical_device,
             device,
+            validation,
+            requirements,
         })
     }
 
@@ -40,6 +75,59 @@ impl VkContext {
     pub fn present_family(&self) -> u32 {
         self.physical_device.queue_families.present_family.unwrap()
     }
+
+    /// Bytes of device-local memory in use by the process, when the device
+    /// reports it.
+    pub fn gpu_memory_usage(&self) -> Option<u64> {
+        if !self.physical_device.memory_budget {
+            return None;
+        }
+
+        let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut properties = vk::PhysicalDeviceMemoryProperties2 {
+            p_next: &mut budget as *mut _ as *mut std::ffi::c_void,
+            ..Default::default()
+        };
+        unsafe {
+            self.instance.handle.get_physical_device_memory_properties2(
+                self.physical_device.handle,
+                &mut properties,
+            );
+        }
+
+        let memory = &properties.memory_properties;
+        let used = memory.memory_heaps[..memory.memory_heap_count as usize]
+            .iter()
+            .zip(budget.heap_usage)
+            .filter(|(heap, _)| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|(_, usage)| usage)
+            .sum();
+        Some(used)
+    }
+
+    /// GPU name with its driver and Vulkan versions, for diagnostics.
+    pub fn describe(&self) -> String {
+        let properties = &self.physical_device.properties;
+        let name = properties
+            .device_name_as_c_str()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "unknown device".to_string());
+        let version = |v: u32| {
+            format!(
+                "{}.{}.{}",
+                vk::api_version_major(v),
+                vk::api_version_minor(v),
+                vk::api_version_patch(v)
+            )
+        };
+        format!(
+            "{} (vendor {:#06x}, driver {}, Vulkan {})",
+            name,
+            properties.vendor_id,
+            version(properties.driver_version),
+            version(properties.api_version)
+        )
+    }
 }
 
 impl Drop for VkContext {
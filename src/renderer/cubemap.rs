@@ -0,0 +1,274 @@
+use ash::vk;
+use std::sync::Arc;
+
+use super::{linear_to_srgb8, SamplerKey, VkBuffer, VkCommandPool, VkContext, VkDevice, VkQueue};
+use crate::math::Vec3;
+
+/// Texels along the edge of the sky's largest mip level.
+const SKY_SIZE: u32 = 128;
+
+/// Environment cubemap with a full mip chain, so blurry reflections can read
+/// a pre-filtered level instead of averaging many samples.
+pub struct VkCubemap {
+    device: Arc<VkDevice>,
+    pub handle: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    /// Shared through the device's sampler cache, not owned.
+    pub sampler: vk::Sampler,
+}
+
+impl VkCubemap {
+    /// Procedural sky: a ground/horizon/zenith gradient with a sun.
+    pub fn sky(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+    ) -> Result<Self, String> {
+        let faces: Vec<Vec<Vec3>> = (0..6)
+            .map(|face| {
+                let mut texels = Vec::with_capacity((SKY_SIZE * SKY_SIZE) as usize);
+                for y in 0..SKY_SIZE {
+                    for x in 0..SKY_SIZE {
+                        let u = (x as f32 + 0.5) / SKY_SIZE as f32 * 2. - 1.;
+                        let v = (y as f32 + 0.5) / SKY_SIZE as f32 * 2. - 1.;
+                        texels.push(sky_color(face_direction(face, u, v)));
+                    }
+                }
+                texels
+            })
+            .collect();
+
+        Self::from_faces(context, queue, command_pool, faces, SKY_SIZE)
+    }
+
+    /// Uploads six square faces of linear colors, ordered +X, -X, +Y, -Y,
+    /// +Z, -Z, box-filtering them down to 1x1 for the mip chain.
+    pub fn from_faces(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        mut faces: Vec<Vec<Vec3>>,
+        size: u32,
+    ) -> Result<Self, String> {
+        let device = context.device();
+        let levels = size.ilog2() + 1;
+
+        // Level-major, so each level's six faces are contiguous and can be
+        // copied as one region.
+        let mut pixels = Vec::new();
+        let mut regions = Vec::with_capacity(levels as usize);
+        let mut extent = size;
+        for level in 0..levels {
+            regions.push(vk::BufferImageCopy {
+                buffer_offset: pixels.len() as vk::DeviceSize,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: level,
+                    base_array_layer: 0,
+                    layer_count: 6,
+                },
+                image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                image_extent: vk::Extent3D {
+                    width: extent,
+                    height: extent,
+                    depth: 1,
+                },
+            });
+
+            for face in &faces {
+                for texel in face {
+                    pixels.extend_from_slice(&[
+                        linear_to_srgb8(texel.x),
+                        linear_to_srgb8(texel.y),
+                        linear_to_srgb8(texel.z),
+                        255,
+                    ]);
+                }
+            }
+
+            if extent > 1 {
+                for face in faces.iter_mut() {
+                    *face = downsample(face, extent);
+                }
+                extent /= 2;
+            }
+        }
+
+        let staging = VkBuffer::<u8>::host_visible(
+            context,
+            pixels.len(),
+            vk::BufferUsageFlags::TRANSFER_SRC,
+        )?;
+
+        staging.write(&pixels);
+
+        let format = vk::Format::R8G8B8A8_SRGB;
+        let create_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::IMAGE_CREATE_INFO,
+            flags: vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            image_type: vk::ImageType::TYPE_2D,
+            extent: vk::Extent3D {
+                width: size,
+                height: size,
+                depth: 1,
+            },
+            mip_levels: levels,
+            array_layers: 6,
+            format,
+            tiling: vk::ImageTiling::OPTIMAL,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            samples: vk::SampleCountFlags::TYPE_1,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+
+        let handle = unsafe {
+            device
+                .handle
+                .create_image(&create_info, None)
+                .map_err(|e| format!("Failed to create cubemap image: {}", e))?
+        };
+
+        let memory_requirements = unsafe { device.handle.get_image_memory_requirements(handle) };
+        let memory_type = context.physical_device.find_memory_type(
+            memory_requirements.memory_type_bits,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+            allocation_size: memory_requirements.size,
+            memory_type_index: memory_type,
+            ..Default::default()
+        };
+
+        let memory = unsafe {
+            device
+                .handle
+                .allocate_memory(&allocate_info, None)
+                .map_err(|e| format!("Failed to allocate cubemap memory: {}", e))?
+        };
+
+        unsafe {
+            device
+                .handle
+                .bind_image_memory(handle, memory, 0)
+                .map_err(|e| format!("Failed to bind memory to cubemap: {}", e))?
+        };
+
+        let view_create_info = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+            image: handle,
+            view_type: vk::ImageViewType::CUBE,
+            format,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: levels,
+                base_array_layer: 0,
+                layer_count: 6,
+            },
+            ..Default::default()
+        };
+
+        let view = unsafe {
+            device
+                .handle
+                .create_image_view(&view_create_info, None)
+                .map_err(|e| format!("Failed to create cubemap view: {}", e))?
+        };
+
+        command_pool.transition_image_layers(
+            queue,
+            handle,
+            (levels, 6),
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        )?;
+
+        command_pool.copy_buffer_to_image_regions(queue, staging.handle, handle, &regions)?;
+
+        command_pool.transition_image_layers(
+            queue,
+            handle,
+            (levels, 6),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )?;
+
+        let sampler = device.sampler(SamplerKey {
+            filter: vk::Filter::LINEAR,
+            address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            anisotropy: 1,
+        })?;
+
+        Ok(Self {
+            device,
+            handle,
+            memory,
+            view,
+            sampler,
+        })
+    }
+}
+
+impl Drop for VkCubemap {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle.destroy_image_view(self.view, None);
+            self.device.handle.free_memory(self.memory, None);
+            self.device.handle.destroy_image(self.handle, None);
+        }
+    }
+}
+
+/// Direction through texel `(u, v)` in [-1, 1] of `face`, following the
+/// Vulkan cube face selection table.
+fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1., -v, -u),
+        1 => Vec3::new(-1., -v, u),
+        2 => Vec3::new(u, 1., v),
+        3 => Vec3::new(u, -1., -v),
+        4 => Vec3::new(u, -v, 1.),
+        _ => Vec3::new(-u, -v, -1.),
+    }
+}
+
+/// Linear radiance of the procedural sky seen along `dir`.
+pub fn sky_color(dir: Vec3) -> Vec3 {
+    let dir = dir.normalize();
+    let ground = Vec3::new(0.18, 0.16, 0.14);
+    let horizon = Vec3::new(0.75, 0.8, 0.85);
+    let zenith = Vec3::new(0.2, 0.4, 0.8);
+
+    let base = if dir.y >= 0. {
+        let t = dir.y.sqrt();
+        horizon * (1. - t) + zenith * t
+    } else {
+        let t = (-dir.y).powf(0.4);
+        horizon * (1. - t) + ground * t
+    };
+
+    let sun = Vec3::new(0.4, 0.8, -0.45).normalize();
+    let glow = dir.dot(sun).max(0.).powf(512.) * 4.;
+    base + Vec3::new(1., 0.95, 0.85) * glow
+}
+
+/// Averages 2x2 blocks of a square `size` face.
+fn downsample(face: &[Vec3], size: u32) -> Vec<Vec3> {
+    let half = (size / 2) as usize;
+    let size = size as usize;
+    let mut out = Vec::with_capacity(half * half);
+    for y in 0..half {
+        for x in 0..half {
+            let at = |dx: usize, dy: usize| face[(y * 2 + dy) * size + x * 2 + dx];
+            out.push((at(0, 0) + at(1, 0) + at(0, 1) + at(1, 1)) * 0.25);
+        }
+    }
+    out
+}
@@ -1,9 +1,18 @@
 use ash::vk;
 use std::sync::Arc;
 
-use crate::renderer::{TextureHandle, VkTexture};
+use crate::math::Mat4;
+use crate::renderer::{
+    SceneAccelerationStructure, ShadowCascades, TemporalResolve, TextureHandle, TracedOcclusion,
+    TransientImage, VkCubemap, VkTexture,
+};
 
-use super::{Uniforms, VkBuffer, VkDevice};
+use super::{DynamicBuffer, ShaderReflection, Uniforms, VkDevice};
+
+/// Set of the per-frame bindings: uniforms, joints and the frame's images.
+pub const FRAME_SET: u32 = 0;
+/// Set of the material textures, shared by every frame.
+pub const MATERIAL_SET: u32 = 1;
 
 pub struct VkDescriptorPool {
     device: Arc<VkDevice>,
@@ -11,17 +20,20 @@ pub struct VkDescriptorPool {
 }
 
 impl VkDescriptorPool {
-    pub fn new(device: Arc<VkDevice>, max_sets: u32) -> Result<Self, String> {
-        let pool_sizes = [
-            vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::UNIFORM_BUFFER,
-                descriptor_count: max_sets,
-            },
-            vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: max_sets * 512,
-            },
-        ];
+    /// Pool for `max_sets` sets of `layout`.
+    pub fn new(
+        device: Arc<VkDevice>,
+        layout: &VkDescriptorSetLayout,
+        max_sets: u32,
+    ) -> Result<Self, String> {
+        let pool_sizes: Vec<vk::DescriptorPoolSize> = layout
+            .descriptor_counts
+            .iter()
+            .map(|size| vk::DescriptorPoolSize {
+                ty: size.ty,
+                descriptor_count: size.descriptor_count * max_sets,
+            })
+            .collect();
 
         let create_info = vk::DescriptorPoolCreateInfo {
             s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
@@ -42,10 +54,59 @@ impl VkDescriptorPool {
         return Ok(VkDescriptorPool { device, handle });
     }
 
+    /// Allocates the set of frame `frame`, pointing at its regions of the
+    /// per-frame buffers.
     pub fn create_set(
         &self,
         layout: &VkDescriptorSetLayout,
-        uniform_buffer: &VkBuffer<Uniforms>,
+        uniforms: &DynamicBuffer<Uniforms>,
+        joints: &DynamicBuffer<Mat4>,
+        frame: usize,
+    ) -> Result<vk::DescriptorSet, String> {
+        let set = self.allocate_set(layout)?;
+
+        let buffer_info = vk::DescriptorBufferInfo {
+            buffer: uniforms.handle(),
+            offset: uniforms.offset(frame),
+            range: uniforms.range(),
+        };
+
+        let joint_info = vk::DescriptorBufferInfo {
+            buffer: joints.handle(),
+            offset: joints.offset(frame),
+            range: joints.range(),
+        };
+
+        let writes = [
+            vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: 0,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+                p_buffer_info: &buffer_info,
+                ..Default::default()
+            },
+            vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: 2,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+                p_buffer_info: &joint_info,
+                ..Default::default()
+            },
+        ];
+
+        unsafe { self.device.handle.update_descriptor_sets(&writes, &[]) };
+
+        Ok(set)
+    }
+
+    /// Allocates a set without writing any of its bindings.
+    pub fn allocate_set(
+        &self,
+        layout: &VkDescriptorSetLayout,
     ) -> Result<vk::DescriptorSet, String> {
         let allocate_info = vk::DescriptorSetAllocateInfo {
             s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
@@ -55,55 +116,288 @@ impl VkDescriptorPool {
             ..Default::default()
         };
 
-        let set = unsafe {
+        unsafe {
             self.device
                 .handle
                 .allocate_descriptor_sets(&allocate_info)
-                .map_err(|e| format!("Failed to allocate descriptor set: {}", e))?
-                .remove(0)
-        };
+                .map_err(|e| format!("Failed to allocate descriptor set: {}", e))
+                .map(|mut sets| sets.remove(0))
+        }
+    }
 
-        let buffer_info = vk::DescriptorBufferInfo {
-            buffer: uniform_buffer.handle,
-            offset: 0,
-            range: std::mem::size_of::<Uniforms>() as u64,
+    pub fn update_texture(
+        &self,
+        set: vk::DescriptorSet,
+        handle: TextureHandle,
+        texture: &VkTexture,
+    ) {
+        let image_info = vk::DescriptorImageInfo {
+            sampler: texture.sampler,
+            image_view: texture.view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         };
 
-        let write = vk::WriteDescriptorSet {
+        let writes = vk::WriteDescriptorSet {
             s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
             dst_set: set,
             dst_binding: 0,
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+            dst_array_element: handle as u32,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
             descriptor_count: 1,
-            p_buffer_info: &buffer_info,
+            p_image_info: &image_info,
             ..Default::default()
         };
 
-        unsafe { self.device.handle.update_descriptor_sets(&[write], &[]) };
+        unsafe {
+            self.device.handle.update_descriptor_sets(&[writes], &[]);
+        }
+    }
 
-        Ok(set)
+    pub fn update_environment(&self, set: vk::DescriptorSet, environment: &VkCubemap) {
+        let image_info = vk::DescriptorImageInfo {
+            sampler: environment.sampler,
+            image_view: environment.view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+
+        let writes = vk::WriteDescriptorSet {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            dst_set: set,
+            dst_binding: 3,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+            p_image_info: &image_info,
+            ..Default::default()
+        };
+
+        unsafe {
+            self.device.handle.update_descriptor_sets(&[writes], &[]);
+        }
     }
 
-    pub fn update_texture(
+    pub fn update_overdraw(&self, set: vk::DescriptorSet, overdraw: &TransientImage) {
+        let image_info = vk::DescriptorImageInfo {
+            sampler: vk::Sampler::null(),
+            image_view: overdraw.view,
+            image_layout: vk::ImageLayout::GENERAL,
+        };
+
+        let writes = vk::WriteDescriptorSet {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            dst_set: set,
+            dst_binding: 4,
+            descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: 1,
+            p_image_info: &image_info,
+            ..Default::default()
+        };
+
+        unsafe {
+            self.device.handle.update_descriptor_sets(&[writes], &[]);
+        }
+    }
+
+    /// Points the shadow map bindings at the cascades' atlas, compared and
+    /// read as depth, which the material shaders read even while the
+    /// cascades are off.
+    pub fn update_shadow_cascades(&self, set: vk::DescriptorSet, cascades: &ShadowCascades) {
+        let image_infos =
+            [cascades.sampler, cascades.depth_sampler].map(|sampler| vk::DescriptorImageInfo {
+                sampler,
+                image_view: cascades.atlas.view,
+                image_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            });
+
+        let writes: Vec<vk::WriteDescriptorSet> = (16..)
+            .zip(&image_infos)
+            .map(|(binding, info)| vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: binding,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+                p_image_info: info,
+                ..Default::default()
+            })
+            .collect();
+
+        unsafe {
+            self.device.handle.update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    /// Points the traced occlusion bindings at `depth`, the pass's output
+    /// image, its scene geometry and frame `frame`'s instances. Does nothing
+    /// until the geometry is built.
+    pub fn update_traced_occlusion(
         &self,
         set: vk::DescriptorSet,
-        handle: TextureHandle,
-        texture: &VkTexture,
+        depth_view: vk::ImageView,
+        occlusion: &TracedOcclusion,
+        frame: usize,
     ) {
+        let Some(geometry) = &occlusion.geometry else {
+            return;
+        };
+
+        let depth_info = vk::DescriptorImageInfo {
+            sampler: occlusion.depth_sampler,
+            image_view: depth_view,
+            image_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+        };
         let image_info = vk::DescriptorImageInfo {
-            sampler: texture.sampler,
-            image_view: texture.view,
-            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            sampler: vk::Sampler::null(),
+            image_view: occlusion.image.view,
+            image_layout: vk::ImageLayout::GENERAL,
         };
+        let buffer_infos = [
+            vk::DescriptorBufferInfo {
+                buffer: geometry.nodes.handle,
+                offset: 0,
+                range: vk::WHOLE_SIZE,
+            },
+            vk::DescriptorBufferInfo {
+                buffer: geometry.triangles.handle,
+                offset: 0,
+                range: vk::WHOLE_SIZE,
+            },
+            vk::DescriptorBufferInfo {
+                buffer: occlusion.instances.handle(),
+                offset: occlusion.instances.offset(frame),
+                range: occlusion.instances.range(),
+            },
+        ];
 
-        let writes = vk::WriteDescriptorSet {
+        let mut writes = vec![
+            vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: 5,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+                p_image_info: &depth_info,
+                ..Default::default()
+            },
+            vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: 6,
+                descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 1,
+                p_image_info: &image_info,
+                ..Default::default()
+            },
+        ];
+        for (binding, info) in (7..).zip(&buffer_infos) {
+            writes.push(vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: binding,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+                p_buffer_info: info,
+                ..Default::default()
+            });
+        }
+
+        unsafe {
+            self.device.handle.update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    /// Points the temporal resolve's bindings at its scene target, with its
+    /// motion vectors, and at the history images read and written this frame.
+    pub fn update_temporal(&self, set: vk::DescriptorSet, temporal: &TemporalResolve) {
+        let sampled = |sampler, image_view, image_layout| vk::DescriptorImageInfo {
+            sampler,
+            image_view,
+            image_layout,
+        };
+        let sampled_infos = [
+            sampled(
+                temporal.linear_sampler,
+                temporal.target.color.view,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            sampled(
+                temporal.nearest_sampler,
+                temporal.target.depth_view,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ),
+            sampled(
+                temporal.linear_sampler,
+                temporal.history[1 - temporal.current].view,
+                vk::ImageLayout::GENERAL,
+            ),
+        ];
+        let velocity_info = sampled(
+            temporal.nearest_sampler,
+            temporal.target.velocity.view,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        let resolved_info = sampled(
+            vk::Sampler::null(),
+            temporal.history[temporal.current].view,
+            vk::ImageLayout::GENERAL,
+        );
+
+        let mut writes: Vec<vk::WriteDescriptorSet> = (11..)
+            .zip(&sampled_infos)
+            .map(|(binding, info)| vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                dst_set: set,
+                dst_binding: binding,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+                p_image_info: info,
+                ..Default::default()
+            })
+            .collect();
+        writes.push(vk::WriteDescriptorSet {
             s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
             dst_set: set,
-            dst_binding: 1,
-            dst_array_element: handle as u32,
+            dst_binding: 14,
+            descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: 1,
+            p_image_info: &resolved_info,
+            ..Default::default()
+        });
+        writes.push(vk::WriteDescriptorSet {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            dst_set: set,
+            dst_binding: 15,
             descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
             descriptor_count: 1,
-            p_image_info: &image_info,
+            p_image_info: &velocity_info,
+            ..Default::default()
+        });
+
+        unsafe {
+            self.device.handle.update_descriptor_sets(&writes, &[]);
+        }
+    }
+
+    /// Points the shadow binding at `structure`, which keeps its handle when
+    /// rebuilt so this is only needed once per set.
+    pub fn update_scene_structure(
+        &self,
+        set: vk::DescriptorSet,
+        structure: &SceneAccelerationStructure,
+    ) {
+        let mut structure_info = vk::WriteDescriptorSetAccelerationStructureKHR {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET_ACCELERATION_STRUCTURE_KHR,
+            acceleration_structure_count: 1,
+            p_acceleration_structures: &structure.structure.handle,
+            ..Default::default()
+        };
+
+        let writes = vk::WriteDescriptorSet {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            p_next: &mut structure_info as *mut _ as *mut std::ffi::c_void,
+            dst_set: set,
+            dst_binding: 10,
+            descriptor_type: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            descriptor_count: 1,
             ..Default::default()
         };
 
@@ -126,15 +420,96 @@ impl Drop for VkDescriptorPool {
 pub struct VkDescriptorSetLayout {
     device: Arc<VkDevice>,
     pub handle: vk::DescriptorSetLayout,
+    /// Descriptors of each type in a set of this layout.
+    pub descriptor_counts: Vec<vk::DescriptorPoolSize>,
 }
 
 impl VkDescriptorSetLayout {
-    pub fn new(device: Arc<VkDevice>) -> Result<VkDescriptorSetLayout, String> {
-        let binding_flags = [
-            vk::DescriptorBindingFlags::empty(),
-            vk::DescriptorBindingFlags::PARTIALLY_BOUND
-                | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND,
-        ];
+    /// Layout of set `set` of all the pipelines, with every binding the
+    /// compiled shaders declare in it, visible to the stages declaring it.
+    /// The acceleration structure is left out without ray queries, as are
+    /// the tessellation stages without tessellation shaders.
+    pub fn new(device: Arc<VkDevice>, set: u32) -> Result<VkDescriptorSetLayout, String> {
+        let mut supported_stages = vk::ShaderStageFlags::VERTEX
+            | vk::ShaderStageFlags::FRAGMENT
+            | vk::ShaderStageFlags::COMPUTE;
+        if device.tessellation {
+            supported_stages |= vk::ShaderStageFlags::TESSELLATION_CONTROL
+                | vk::ShaderStageFlags::TESSELLATION_EVALUATION;
+        }
+
+        let mut bindings: Vec<vk::DescriptorSetLayoutBinding> = Vec::new();
+        for (path, reflection) in ShaderReflection::load_all()? {
+            let stage = reflection.stage & supported_stages;
+            for declared in &reflection.bindings {
+                if declared.set > MATERIAL_SET {
+                    return Err(format!(
+                        "{} uses descriptor set {}, only sets {} and {} are bound",
+                        path, declared.set, FRAME_SET, MATERIAL_SET
+                    ));
+                }
+                if declared.set != set
+                    || stage.is_empty()
+                    || (declared.descriptor_type == vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
+                        && !device.ray_query())
+                {
+                    continue;
+                }
+                match bindings.iter_mut().find(|b| b.binding == declared.binding) {
+                    Some(binding)
+                        if binding.descriptor_type != declared.descriptor_type
+                            || binding.descriptor_count != declared.count =>
+                    {
+                        return Err(format!(
+                            "{} declares binding {} as {} {:?}, other shaders as {} {:?}",
+                            path,
+                            declared.binding,
+                            declared.count,
+                            declared.descriptor_type,
+                            binding.descriptor_count,
+                            binding.descriptor_type
+                        ));
+                    }
+                    Some(binding) => binding.stage_flags |= stage,
+                    None => bindings.push(vk::DescriptorSetLayoutBinding {
+                        binding: declared.binding,
+                        descriptor_type: declared.descriptor_type,
+                        descriptor_count: declared.count,
+                        stage_flags: stage,
+                        p_immutable_samplers: std::ptr::null(),
+                        ..Default::default()
+                    }),
+                }
+            }
+        }
+        bindings.sort_by_key(|b| b.binding);
+
+        // Arrays are indexed by texture handle and filled as textures load.
+        let binding_flags: Vec<vk::DescriptorBindingFlags> = bindings
+            .iter()
+            .map(|b| {
+                if b.descriptor_count > 1 {
+                    vk::DescriptorBindingFlags::PARTIALLY_BOUND
+                        | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+                } else {
+                    vk::DescriptorBindingFlags::empty()
+                }
+            })
+            .collect();
+
+        let mut descriptor_counts: Vec<vk::DescriptorPoolSize> = Vec::new();
+        for binding in &bindings {
+            match descriptor_counts
+                .iter_mut()
+                .find(|size| size.ty == binding.descriptor_type)
+            {
+                Some(size) => size.descriptor_count += binding.descriptor_count,
+                None => descriptor_counts.push(vk::DescriptorPoolSize {
+                    ty: binding.descriptor_type,
+                    descriptor_count: binding.descriptor_count,
+                }),
+            }
+        }
 
         let mut binding_flags_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo {
             s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_BINDING_FLAGS_CREATE_INFO,
@@ -143,27 +518,6 @@ impl VkDescriptorSetLayout {
             ..Default::default()
         };
 
-        let bindings = [
-            // binding 0: UBO (view/proj matrices)
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::VERTEX,
-                p_immutable_samplers: std::ptr::null(),
-                ..Default::default()
-            },
-            // binding 1: texture sampler
-            vk::DescriptorSetLayoutBinding {
-                binding: 1,
-                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: 512,
-                stage_flags: vk::ShaderStageFlags::FRAGMENT,
-                p_immutable_samplers: std::ptr::null(),
-                ..Default::default()
-            },
-        ];
-
         let create_info = vk::DescriptorSetLayoutCreateInfo {
             s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
             p_next: &mut binding_flags_info as *mut _ as *mut std::ffi::c_void,
@@ -180,7 +534,11 @@ impl VkDescriptorSetLayout {
                 .map_err(|e| format!("Failed to create descriptor set layout: {}", e))?
         };
 
-        return Ok(VkDescriptorSetLayout { device, handle });
+        return Ok(VkDescriptorSetLayout {
+            device,
+            handle,
+            descriptor_counts,
+        });
     }
 }
 
@@ -193,3 +551,59 @@ impl Drop for VkDescriptorSetLayout {
         }
     }
 }
+
+/// Layouts of the sets every pipeline is laid out with.
+pub struct DescriptorLayouts {
+    pub frame: VkDescriptorSetLayout,
+    pub material: VkDescriptorSetLayout,
+}
+
+impl DescriptorLayouts {
+    pub fn new(device: Arc<VkDevice>) -> Result<Self, String> {
+        Ok(Self {
+            frame: VkDescriptorSetLayout::new(device.clone(), FRAME_SET)?,
+            material: VkDescriptorSetLayout::new(device, MATERIAL_SET)?,
+        })
+    }
+
+    /// Handles in set order, for pipeline layouts.
+    pub fn handles(&self) -> [vk::DescriptorSetLayout; 2] {
+        [self.frame.handle, self.material.handle]
+    }
+}
+
+/// The material set, allocated once for all the frames. Textures are
+/// written when added or replaced rather than into every frame's set each
+/// frame; the array binding updates after bind, so frames in flight keep
+/// drawing meanwhile.
+pub struct MaterialSet {
+    pool: VkDescriptorPool,
+    pub handle: vk::DescriptorSet,
+    /// View last written to each element of the texture array.
+    written: Vec<vk::ImageView>,
+}
+
+impl MaterialSet {
+    pub fn new(device: Arc<VkDevice>, layouts: &DescriptorLayouts) -> Result<Self, String> {
+        let pool = VkDescriptorPool::new(device, &layouts.material, 1)?;
+        let handle = pool.allocate_set(&layouts.material)?;
+        Ok(Self {
+            pool,
+            handle,
+            written: Vec::new(),
+        })
+    }
+
+    /// Writes the textures whose view changed since the last update.
+    pub fn update(&mut self, textures: &[VkTexture]) {
+        self.written.truncate(textures.len());
+        for (handle, texture) in textures.iter().enumerate() {
+            match self.written.get_mut(handle) {
+                Some(view) if *view == texture.view => continue,
+                Some(view) => *view = texture.view,
+                None => self.written.push(texture.view),
+            }
+            self.pool.update_texture(self.handle, handle, texture);
+        }
+    }
+}
@@ -1,16 +1,37 @@
-use crate::renderer::DEVICE_EXTENSIONS;
-use crate::renderer::{VkInstance, VkPhysicalDevice};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::sync::Mutex;
 
-use ash::{vk, Device};
+use crate::breadcrumb;
+use crate::renderer::{CompressedFormats, SamplerKey, VkInstance, VkPhysicalDevice};
+use crate::renderer::{DEVICE_EXTENSIONS, RAY_QUERY_EXTENSIONS};
+
+use ash::{khr, vk, Device};
 
 pub struct VkDevice {
     pub handle: Device,
+    /// Highest sampler anisotropy, 1 when the feature is unavailable.
+    max_anisotropy: f32,
+    /// Whether fragment shaders may write storage images, needed by the
+    /// overdraw view.
+    pub fragment_stores: bool,
+    /// Whether tessellation shaders are available, needed by displacement
+    /// maps.
+    pub tessellation: bool,
+    /// Block-compressed texture formats sampled; textures in the others are
+    /// decoded on load.
+    pub compressed_formats: CompressedFormats,
+    /// Acceleration structure commands, present when the device supports
+    /// ray queries; shadows are then traced in the fragment shader.
+    pub acceleration_structure: Option<khr::acceleration_structure::Device>,
+    samplers: Mutex<HashMap<SamplerKey, vk::Sampler>>,
 }
 
 impl VkDevice {
     pub fn new(
         instance: &VkInstance,
         physical_device: &VkPhysicalDevice,
+        extra_extensions: &[CString],
     ) -> Result<VkDevice, String> {
         let graphics_family = physical_device.queue_families.graphics_family.unwrap();
         let present_family = physical_device.queue_families.present_family.unwrap();
@@ -32,16 +53,73 @@ impl VkDevice {
             })
             .collect();
 
-        let device_features = vk::PhysicalDeviceFeatures::default();
+        let supported = unsafe {
+            instance
+                .handle
+                .get_physical_device_features(physical_device.handle)
+        };
+        let device_features = vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: supported.sampler_anisotropy,
+            fragment_stores_and_atomics: supported.fragment_stores_and_atomics,
+            tessellation_shader: supported.tessellation_shader,
+            texture_compression_bc: supported.texture_compression_bc,
+            texture_compression_etc2: supported.texture_compression_etc2,
+            texture_compression_astc_ldr: supported.texture_compression_astc_ldr,
+            ..Default::default()
+        };
+        let max_anisotropy = if supported.sampler_anisotropy == vk::TRUE {
+            physical_device.properties.limits.max_sampler_anisotropy
+        } else {
+            1.
+        };
 
-        let device_extensions: Vec<_> = DEVICE_EXTENSIONS
+        let ray_query = Self::supports_ray_query(instance, physical_device);
+        let mut device_extensions: Vec<_> = DEVICE_EXTENSIONS
             .iter()
             .map(|extension| extension.as_ptr())
             .collect();
 
+        for extension in extra_extensions {
+            if !device_extensions
+                .iter()
+                .any(|&enabled| unsafe { CStr::from_ptr(enabled) } == extension.as_c_str())
+            {
+                device_extensions.push(extension.as_ptr());
+            }
+        }
+
+        // Required on portability implementations, which only expose a subset
+        // of Vulkan.
+        if Self::has_extension(instance, physical_device, vk::KHR_PORTABILITY_SUBSET_NAME) {
+            device_extensions.push(vk::KHR_PORTABILITY_SUBSET_NAME.as_ptr());
+        }
+
+        let mut ray_query_features = vk::PhysicalDeviceRayQueryFeaturesKHR {
+            ray_query: vk::TRUE,
+            ..Default::default()
+        };
+        let mut acceleration_structure_features =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR {
+                acceleration_structure: vk::TRUE,
+                ..Default::default()
+            };
+        let mut vulkan12_features = vk::PhysicalDeviceVulkan12Features {
+            buffer_device_address: vk::TRUE,
+            ..Default::default()
+        };
+        let mut p_next = std::ptr::null();
+        if ray_query {
+            device_extensions.extend(RAY_QUERY_EXTENSIONS.iter().map(|e| e.as_ptr()));
+            acceleration_structure_features.p_next =
+                &mut ray_query_features as *mut _ as *mut std::ffi::c_void;
+            vulkan12_features.p_next =
+                &mut acceleration_structure_features as *mut _ as *mut std::ffi::c_void;
+            p_next = &vulkan12_features as *const _ as *const std::ffi::c_void;
+        }
+
         let create_info = vk::DeviceCreateInfo {
             s_type: vk::StructureType::DEVICE_CREATE_INFO,
-            p_next: std::ptr::null(),
+            p_next,
             flags: vk::DeviceCreateFlags::empty(),
             queue_create_info_count: queue_create_infos.len() as u32,
             p_queue_create_infos: queue_create_infos.as_ptr(),
@@ -57,8 +135,86 @@ impl VkDevice {
                 .create_device(physical_device.handle, &create_info, None)
                 .map_err(|e| format!("Failed to create logical device: {}", e))?
         };
+        breadcrumb!("vkCreateDevice");
+
+        let acceleration_structure = if ray_query {
+            Some(khr::acceleration_structure::Device::new(
+                &instance.handle,
+                &handle,
+            ))
+        } else {
+            eprintln!("Ray-queried shadows unavailable: no ray query support on this device");
+            None
+        };
+
+        return Ok(VkDevice {
+            handle,
+            max_anisotropy,
+            fragment_stores: supported.fragment_stores_and_atomics == vk::TRUE,
+            tessellation: supported.tessellation_shader == vk::TRUE,
+            compressed_formats: CompressedFormats::new(&supported),
+            acceleration_structure,
+            samplers: Mutex::new(HashMap::new()),
+        });
+    }
+
+    fn has_extension(
+        instance: &VkInstance,
+        physical_device: &VkPhysicalDevice,
+        extension: &CStr,
+    ) -> bool {
+        let extensions = unsafe {
+            instance
+                .handle
+                .enumerate_device_extension_properties(physical_device.handle)
+                .unwrap_or_default()
+        };
+        extensions.iter().any(|e| {
+            e.extension_name_as_c_str()
+                .is_ok_and(|name| name == extension)
+        })
+    }
+
+    /// Whether the device has the ray query extensions and the features they
+    /// need, including buffer device addresses.
+    fn supports_ray_query(instance: &VkInstance, physical_device: &VkPhysicalDevice) -> bool {
+        if physical_device.properties.api_version < vk::API_VERSION_1_2 {
+            return false;
+        }
+
+        let has_extensions = RAY_QUERY_EXTENSIONS
+            .iter()
+            .all(|&required| Self::has_extension(instance, physical_device, required));
+        if !has_extensions {
+            return false;
+        }
 
-        return Ok(VkDevice { handle });
+        let mut ray_query = vk::PhysicalDeviceRayQueryFeaturesKHR::default();
+        let mut acceleration_structure = vk::PhysicalDeviceAccelerationStructureFeaturesKHR {
+            p_next: &mut ray_query as *mut _ as *mut std::ffi::c_void,
+            ..Default::default()
+        };
+        let mut vulkan12 = vk::PhysicalDeviceVulkan12Features {
+            p_next: &mut acceleration_structure as *mut _ as *mut std::ffi::c_void,
+            ..Default::default()
+        };
+        let mut features = vk::PhysicalDeviceFeatures2 {
+            p_next: &mut vulkan12 as *mut _ as *mut std::ffi::c_void,
+            ..Default::default()
+        };
+        unsafe {
+            instance
+                .handle
+                .get_physical_device_features2(physical_device.handle, &mut features);
+        }
+
+        vulkan12.buffer_device_address == vk::TRUE
+            && acceleration_structure.acceleration_structure == vk::TRUE
+            && ray_query.ray_query == vk::TRUE
+    }
+
+    pub fn ray_query(&self) -> bool {
+        self.acceleration_structure.is_some()
     }
 
     pub fn wait_idle(&self) {
@@ -66,11 +222,39 @@ impl VkDevice {
             let _ = self.handle.device_wait_idle();
         }
     }
+
+    /// Returns the sampler for `key`, creating it on first use. Samplers live
+    /// as long as the device.
+    pub fn sampler(&self, key: SamplerKey) -> Result<vk::Sampler, String> {
+        let mut samplers = self.samplers.lock().unwrap();
+        if let Some(&sampler) = samplers.get(&key) {
+            return Ok(sampler);
+        }
+
+        let sampler = unsafe {
+            self.handle
+                .create_sampler(&key.create_info(self.max_anisotropy), None)
+                .map_err(|e| format!("Failed to create sampler: {}", e))?
+        };
+        samplers.insert(key, sampler);
+        Ok(sampler)
+    }
+
+    /// Whether the driver reports the device as lost (hang, reset, removal).
+    pub fn is_lost(&self) -> bool {
+        matches!(
+            unsafe { self.handle.device_wait_idle() },
+            Err(vk::Result::ERROR_DEVICE_LOST)
+        )
+    }
 }
 
 impl Drop for VkDevice {
     fn drop(&mut self) {
         unsafe {
+            for (_, sampler) in self.samplers.get_mut().unwrap().drain() {
+                self.handle.destroy_sampler(sampler, None);
+            }
             self.handle.destroy_device(None);
         }
     }
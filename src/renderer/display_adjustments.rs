@@ -0,0 +1,45 @@
+use std::fmt;
+
+/// Corrections applied to the shaded surfaces before they reach the screen,
+/// to make out detail in very dark or overbright textures on an uncalibrated
+/// monitor. Overlays and the HUD are drawn unadjusted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayAdjustments {
+    /// In stops: each one doubles the color.
+    pub exposure: f32,
+    /// Power applied on top of the display's own encoding; above 1
+    /// brightens the midtones, leaving black and white in place.
+    pub gamma: f32,
+    /// Added to every channel.
+    pub brightness: f32,
+    /// Scale of each channel's distance to mid gray.
+    pub contrast: f32,
+}
+
+impl Default for DisplayAdjustments {
+    fn default() -> Self {
+        Self {
+            exposure: 0.,
+            gamma: 1.,
+            brightness: 0.,
+            contrast: 1.,
+        }
+    }
+}
+
+impl DisplayAdjustments {
+    /// Whether the colors are shown as shaded.
+    pub fn is_neutral(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for DisplayAdjustments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "exposure {:+.1} | gamma {:.2} | brightness {:+.2} | contrast {:.2}",
+            self.exposure, self.gamma, self.brightness, self.contrast
+        )
+    }
+}
@@ -0,0 +1,79 @@
+use std::marker::PhantomData;
+
+use ash::vk;
+
+use super::{VkBuffer, VkContext, MAX_FRAMES_IN_FLIGHT};
+
+/// Host-visible buffer split into one region per frame in flight, so the CPU
+/// can fill the current frame's data while the GPU still reads the previous
+/// one.
+pub struct DynamicBuffer<T> {
+    buffer: VkBuffer<u8>,
+    /// Number of `T` in each region.
+    count: usize,
+    /// Distance in bytes between two regions, aligned for descriptor offsets.
+    stride: vk::DeviceSize,
+    _type: PhantomData<T>,
+}
+
+impl<T> DynamicBuffer<T> {
+    pub fn new(
+        context: &VkContext,
+        count: usize,
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self, String> {
+        let limits = &context.physical_device.properties.limits;
+        let mut alignment = limits.non_coherent_atom_size.max(1);
+        if usage.contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+            alignment = alignment.max(limits.min_uniform_buffer_offset_alignment);
+        }
+        if usage.contains(vk::BufferUsageFlags::STORAGE_BUFFER) {
+            alignment = alignment.max(limits.min_storage_buffer_offset_alignment);
+        }
+
+        let size = (std::mem::size_of::<T>() * count) as vk::DeviceSize;
+        let stride = size.div_ceil(alignment) * alignment;
+        let buffer = VkBuffer::host_visible(
+            context,
+            (stride * MAX_FRAMES_IN_FLIGHT as vk::DeviceSize) as usize,
+            usage,
+        )?;
+
+        Ok(Self {
+            buffer,
+            count,
+            stride,
+            _type: PhantomData,
+        })
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer.handle
+    }
+
+    /// Byte offset of `frame`'s region.
+    pub fn offset(&self, frame: usize) -> vk::DeviceSize {
+        self.stride * frame as vk::DeviceSize
+    }
+
+    /// Size in bytes of a region, as bound by a descriptor.
+    pub fn range(&self) -> vk::DeviceSize {
+        (std::mem::size_of::<T>() * self.count) as vk::DeviceSize
+    }
+
+    /// Copies `data` at the start of `frame`'s region. The frame must not be
+    /// in flight.
+    pub fn write(&self, frame: usize, data: &[T]) {
+        assert!(frame < MAX_FRAMES_IN_FLIGHT as usize);
+        assert!(data.len() <= self.count, "Dynamic buffer region overflow");
+
+        let ptr = self
+            .buffer
+            .mapped
+            .expect("Cannot write to a non-mapped buffer!");
+        unsafe {
+            let region = (ptr as *mut u8).add(self.offset(frame) as usize);
+            std::ptr::copy_nonoverlapping(data.as_ptr(), region as *mut T, data.len());
+        }
+    }
+}
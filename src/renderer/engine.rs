@@ -1,29 +1,94 @@
+use std::mem::ManuallyDrop;
+use std::sync::mpsc::Receiver;
 use std::sync::Arc;
 
-use super::{MeshHandle, Renderer, ResourcesManager, VkContext};
+#[cfg(feature = "stats-window")]
+use super::StatsWindow;
+use super::{
+    ContextRequirements, DisplayAdjustments, EngineEvent, EngineEvents, FaceHit, FrameStats,
+    GpuSelector, Matcap, MeshHandle, PathTracer, Picker, RenderPlugin, Renderer, ResourcesManager,
+    ShadingMode, ShadowQuality, SurfaceAnalysis, SurfaceWindow, SyncTimings, ValidationFeatures,
+    Viewport, VkContext,
+};
+#[cfg(feature = "openxr")]
+use super::{XrRuntime, XrSession};
 use crate::camera::Camera;
-use crate::scene::Scene;
-
-use sdl3::video::Window;
+use crate::crash;
+use crate::math::{Aabb, ConvexHull, Obb, Ray};
+#[cfg(feature = "openxr")]
+use crate::math::{Mat4, Vec3};
+use crate::profile::LoadProfile;
+use crate::scene::{Hud, Mesh, Scene, TopologyReport};
 
 pub struct Engine {
-    context: Arc<VkContext>,
+    #[cfg(feature = "openxr")]
+    xr: Option<XrSession>,
+    #[cfg(feature = "stats-window")]
+    stats_window: Option<StatsWindow>,
+    /// Dropped by hand in `drop`, after the device is idle and before the
+    /// context they were created from.
+    renderer: ManuallyDrop<Renderer>,
+    manager: ManuallyDrop<ResourcesManager>,
+    events: EngineEvents,
 
-    manager: ResourcesManager,
-    renderer: Renderer,
+    context: Arc<VkContext>,
 }
 
 impl Engine {
-    pub fn new(window: &Window) -> Result<Engine, String> {
-        let context = Arc::new(VkContext::new(window)?);
+    /// Creates the engine rendering to `window` on `gpu`, or the most capable
+    /// GPU, and to a VR headset through OpenXR as well when `xr`, which needs
+    /// the `openxr` feature.
+    pub fn new(
+        window: &dyn SurfaceWindow,
+        validation: ValidationFeatures,
+        gpu: Option<GpuSelector>,
+        xr: bool,
+    ) -> Result<Engine, String> {
+        #[cfg(not(feature = "openxr"))]
+        if xr {
+            return Err("Built without OpenXR support, rebuild with --features openxr".to_string());
+        }
+        #[cfg(feature = "openxr")]
+        let runtime = xr.then(XrRuntime::new).transpose()?;
+        #[cfg(feature = "openxr")]
+        let requirements = match &runtime {
+            Some(runtime) => ContextRequirements {
+                gpu,
+                ..runtime.requirements()?
+            },
+            None => ContextRequirements {
+                gpu,
+                ..Default::default()
+            },
+        };
+        #[cfg(not(feature = "openxr"))]
+        let requirements = ContextRequirements {
+            gpu,
+            ..Default::default()
+        };
 
-        let renderer = Renderer::new(window, context.clone())?;
+        let context = Arc::new(VkContext::new(window, validation, requirements)?);
+
+        let events = EngineEvents::default();
+        let renderer = Renderer::new(window, context.clone(), events.clone())?;
         let manager = ResourcesManager::new(context.clone())?;
 
         Ok(Self {
+            #[cfg(feature = "openxr")]
+            xr: match runtime {
+                Some(runtime) => Some(XrSession::new(
+                    runtime,
+                    context.clone(),
+                    renderer.color_format(),
+                )?),
+                None => None,
+            },
+            #[cfg(feature = "stats-window")]
+            stats_window: None,
             context,
-            renderer,
-            manager,
+            renderer: ManuallyDrop::new(renderer),
+            manager: ManuallyDrop::new(manager),
+            events,
         })
     }
 
@@ -31,21 +96,509 @@ impl Engine {
         self.renderer.resize(width, height)
     }
 
+    /// Releases the window surface, see `Renderer::suspend`.
+    pub fn suspend(&mut self) {
+        self.renderer.suspend();
+    }
+
+    /// Presents to `window` again after `suspend`.
+    pub fn resume(&mut self, window: &dyn SurfaceWindow) -> Result<(), String> {
+        self.renderer.resume(window)
+    }
+
     pub fn load_mesh(&mut self, path: &str) -> Result<MeshHandle, String> {
-        self.manager.load_mesh(&*self.context, path)
+        crash::set_model(path);
+        let cached = self.manager.mesh_cache.contains_key(path);
+        let handle = self.manager.load_mesh(&*self.context, path)?;
+        if !cached {
+            self.model_loaded(Some(path), handle);
+        }
+        Ok(handle)
+    }
+
+    /// The mesh already uploaded for `path`, if any.
+    pub fn cached_mesh(&self, path: &str) -> Option<MeshHandle> {
+        self.manager.mesh_cache.get(path).copied()
+    }
+
+    /// Uploads a mesh the caller parsed from `path`, timing each step into
+    /// `profile`.
+    pub fn add_loaded_mesh(
+        &mut self,
+        path: &str,
+        mesh: &Mesh,
+        profile: &mut LoadProfile,
+    ) -> Result<MeshHandle, String> {
+        crash::set_model(path);
+        let handle = self
+            .manager
+            .insert_mesh(&self.context, path, mesh, profile)?;
+        self.model_loaded(Some(path), handle);
+        Ok(handle)
+    }
+
+    pub fn add_mesh(&mut self, mesh: &Mesh) -> Result<MeshHandle, String> {
+        let handle = self.manager.save_mesh(&self.context, mesh)?;
+        self.model_loaded(None, handle);
+        Ok(handle)
+    }
+
+    fn model_loaded(&self, path: Option<&str>, mesh: MeshHandle) {
+        self.events.emit(|| EngineEvent::ModelLoaded {
+            path: path.map(str::to_string),
+            mesh,
+            triangles: self.manager.get_mesh(mesh).geometry.triangle_count(),
+        });
+    }
+
+    /// Events of the engine from now on, see `EngineEvent`.
+    pub fn subscribe(&self) -> Receiver<EngineEvent> {
+        self.events.subscribe()
+    }
+
+    /// Bakes the scene light into every mesh that has lightmap UVs, placed
+    /// like the first object using it, and uploads the results. Returns how
+    /// many meshes were baked.
+    pub fn bake_lightmaps(&mut self, scene: &Scene) -> Result<usize, String> {
+        let mut baked: Vec<MeshHandle> = Vec::new();
+        for object in &scene.objects {
+            let handle = object.id();
+            let mesh = &self.manager.get_mesh(handle).geometry;
+            if baked.contains(&handle) || mesh.lightmap.is_none() {
+                continue;
+            }
+            let Some(to_local) = object.transform().inverse() else {
+                continue;
+            };
+
+            let mut mesh = mesh.clone();
+            mesh.bake_lightmap(&scene.light, &to_local)?;
+
+            self.manager.replace_mesh(&self.context, handle, &mesh)?;
+            // The previous buffers may still be read by frames in flight.
+            self.shutdown();
+            baked.push(handle);
+        }
+
+        Ok(baked.len())
+    }
+
+    /// Uploads `mesh` in place of `handle`, once the frames in flight are
+    /// done with the previous buffers.
+    pub fn replace_mesh(&mut self, handle: MeshHandle, mesh: &Mesh) -> Result<(), String> {
+        self.manager.replace_mesh(&self.context, handle, mesh)?;
+        self.shutdown();
+        Ok(())
+    }
+
+    pub fn mesh_bounds(&self, mesh: MeshHandle) -> Aabb {
+        self.manager.get_mesh(mesh).bounds
+    }
+
+    /// Oriented box around the mesh, from its convex hull.
+    pub fn mesh_obb(&self, mesh: MeshHandle) -> Obb {
+        self.manager.get_mesh(mesh).obb
+    }
+
+    pub fn mesh_hull(&self, mesh: MeshHandle) -> &ConvexHull {
+        &self.manager.get_mesh(mesh).hull
+    }
+
+    /// CPU-side geometry of an uploaded mesh.
+    pub fn mesh(&self, mesh: MeshHandle) -> &Mesh {
+        &self.manager.get_mesh(mesh).geometry
+    }
+
+    pub fn mesh_topology(&self, mesh: MeshHandle) -> TopologyReport {
+        self.manager.get_mesh(mesh).geometry.analyze_topology()
     }
 
-    pub fn draw(&mut self, window: &Window, camera: &Camera, scene: &Scene) -> Result<(), String> {
-        self.renderer.draw(window, camera, scene, &self.manager)
+    /// Closest triangle under `ray`, for face inspection.
+    pub fn pick_face(&self, scene: &Scene, ray: &Ray) -> Option<FaceHit> {
+        Picker::face(scene, &self.manager, ray)
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.renderer.stats()
+    }
+
+    /// Where the CPU waited on the GPU and the swapchain in the last frame.
+    pub fn sync_timings(&self) -> SyncTimings {
+        self.renderer.sync_timings()
+    }
+
+    /// Device-local memory in use, in bytes, when the GPU reports it.
+    pub fn gpu_memory_usage(&self) -> Option<u64> {
+        self.context.gpu_memory_usage()
+    }
+
+    pub fn draw(
+        &mut self,
+        window: &dyn SurfaceWindow,
+        camera: &Camera,
+        scene: &Scene,
+    ) -> Result<(), String> {
+        match self.renderer.draw(window, camera, scene, &self.manager) {
+            Err(e) if self.context.device.is_lost() => return self.recover(window, &e),
+            result => result?,
+        }
+        #[cfg(feature = "openxr")]
+        self.draw_xr(camera, scene)?;
+        Ok(())
+    }
+
+    /// Renders the scene to the headset, if any, from where `camera` stands
+    /// and facing the way it does, scaled so the scene is about a meter
+    /// across. The session is dropped once the headset ends it.
+    #[cfg(feature = "openxr")]
+    fn draw_xr(&mut self, camera: &Camera, scene: &Scene) -> Result<(), String> {
+        let Some(xr) = &mut self.xr else {
+            return Ok(());
+        };
+
+        let bounds = scene.objects.iter().fold(Aabb::EMPTY, |bounds, object| {
+            bounds.union(
+                self.manager
+                    .get_mesh(object.id())
+                    .bounds
+                    .transform(&object.transform()),
+            )
+        });
+        let scale = if bounds.is_empty() {
+            1.
+        } else {
+            bounds.size().length().max(f32::EPSILON)
+        };
+        let anchor = Mat4::identity()
+            .translate(camera.position)
+            .rotate(camera.yaw + std::f32::consts::PI, Vec3::Y)
+            .scale(Vec3::splat(scale));
+
+        let (renderer, manager) = (&mut self.renderer, &self.manager);
+        let running = xr.frame(anchor, camera, |eye, target, image| {
+            renderer.render_to_image(eye, scene, manager, target, image)
+        })?;
+        if !running {
+            eprintln!("Headset session ended");
+            self.xr = None;
+        }
+        Ok(())
+    }
+
+    /// Draws `plugin` over the window's following frames, see `RenderPlugin`.
+    pub fn add_plugin(&mut self, plugin: Box<dyn RenderPlugin>) -> Result<(), String> {
+        self.renderer.add_plugin(plugin)
+    }
+
+    /// Rebuilds the Vulkan context, renderer and resources after the device
+    /// was lost. Mesh handles stay valid since meshes are uploaded again, from
+    /// their CPU copies, in the same order, and plugins are set up again.
+    fn recover(&mut self, window: &dyn SurfaceWindow, error: &str) -> Result<(), String> {
+        let stats = self.renderer.stats();
+        self.events
+            .emit(|| EngineEvent::DeviceLost(error.to_string()));
+        eprintln!("GPU device lost: {}", error);
+        eprintln!("  device: {}", self.context.describe());
+        eprintln!(
+            "  last frame: {} draw calls, {} triangles",
+            stats.draw_calls, stats.triangles
+        );
+        eprintln!(
+            "  resources: {} meshes, {} textures, {} materials",
+            self.manager.meshes.len(),
+            self.manager.textures.len(),
+            self.manager.materials.len()
+        );
+
+        #[cfg(feature = "stats-window")]
+        if self.stats_window.take().is_some() {
+            eprintln!("  stats window closed");
+        }
+        #[cfg(feature = "openxr")]
+        if self.xr.take().is_some() {
+            eprintln!("  headset session closed");
+        }
+
+        let shading_mode = self.renderer.shading_mode();
+        let matcap = self.renderer.matcap();
+        let occlusion_traced = self.renderer.occlusion_traced();
+        let displacement = self.renderer.displacement();
+        let display = self.renderer.display_adjustments();
+        let analysis = self.renderer.surface_analysis();
+        let (shadows, light_size) = (self.renderer.shadow_quality(), self.renderer.light_size());
+        let cascades = self.renderer.shadow_cascades();
+        let temporal = self.renderer.temporal_scale();
+        let face_sorted = self.renderer.face_sorted();
+        let plugins = self.renderer.take_plugins();
+        self.renderer.suspend();
+
+        let context = Arc::new(VkContext::new(
+            window,
+            self.context.validation,
+            self.context.requirements.clone(),
+        )?);
+        let mut manager = ResourcesManager::new(context.clone())?;
+        manager.compact_vertices = self.manager.compact_vertices;
+        for mesh in &self.manager.meshes {
+            manager.save_mesh(&context, &mesh.geometry)?;
+        }
+        manager.mesh_cache = std::mem::take(&mut self.manager.mesh_cache);
+        let mut renderer = Renderer::new(window, context.clone(), self.events.clone())?;
+        renderer.set_shading_mode(shading_mode);
+        renderer.set_matcap(matcap);
+        renderer.set_occlusion_traced(occlusion_traced);
+        renderer.set_display_adjustments(display);
+        renderer.set_surface_analysis(analysis);
+        renderer.set_shadow_quality(shadows);
+        renderer.set_light_size(light_size);
+        renderer.set_shadow_cascades(cascades)?;
+        renderer.set_temporal(temporal)?;
+        renderer.set_face_sorted(face_sorted);
+        if let Some(displacement) = displacement {
+            renderer.set_displacement(displacement);
+        }
+        for plugin in plugins {
+            renderer.add_plugin(plugin)?;
+        }
+
+        *self.renderer = renderer;
+        *self.manager = manager;
+        self.context = context;
+
+        eprintln!("Recovered on a new device: {}", self.context.describe());
+        Ok(())
+    }
+
+    /// Renders `scene` from `camera` offscreen at `width` by `height`,
+    /// independently of the window, as RGBA8 pixels, see `Renderer::capture`.
+    pub fn capture(
+        &mut self,
+        camera: &Camera,
+        scene: &Scene,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, String> {
+        self.renderer
+            .capture(camera, scene, &self.manager, width, height)
+    }
+
+    /// Path traces the scene on the CPU, see `PathTracer`.
+    pub fn render_reference(
+        &self,
+        camera: &Camera,
+        scene: &Scene,
+        size: (u32, u32),
+        samples: u32,
+        progress: impl FnMut(u32),
+    ) -> Vec<u8> {
+        PathTracer::new(scene, &self.manager).render(camera, size, samples, progress)
+    }
+
+    pub fn shading_mode(&self) -> ShadingMode {
+        self.renderer.shading_mode()
+    }
+
+    pub fn set_shading_mode(&mut self, mode: ShadingMode) {
+        self.renderer.set_shading_mode(mode);
+    }
+
+    pub fn matcap(&self) -> Matcap {
+        self.renderer.matcap()
+    }
+
+    pub fn set_matcap(&mut self, matcap: Matcap) {
+        self.renderer.set_matcap(matcap);
+    }
+
+    /// Height map scale of displaced materials, in world units; `None` when
+    /// the device can't tessellate them.
+    pub fn displacement(&self) -> Option<f32> {
+        self.renderer.displacement()
+    }
+
+    pub fn set_displacement(&mut self, displacement: f32) {
+        self.renderer.set_displacement(displacement);
+    }
+
+    pub fn display_adjustments(&self) -> DisplayAdjustments {
+        self.renderer.display_adjustments()
+    }
+
+    pub fn set_display_adjustments(&mut self, display: DisplayAdjustments) {
+        self.renderer.set_display_adjustments(display);
+    }
+
+    pub fn surface_analysis(&self) -> SurfaceAnalysis {
+        self.renderer.surface_analysis()
+    }
+
+    pub fn set_surface_analysis(&mut self, analysis: SurfaceAnalysis) {
+        self.renderer.set_surface_analysis(analysis);
+    }
+
+    pub fn shadow_quality(&self) -> ShadowQuality {
+        self.renderer.shadow_quality()
+    }
+
+    pub fn set_shadow_quality(&mut self, shadows: ShadowQuality) {
+        self.renderer.set_shadow_quality(shadows);
+    }
+
+    pub fn light_size(&self) -> f32 {
+        self.renderer.light_size()
+    }
+
+    /// Angle the light's disk spans seen from the lit surface, in degrees,
+    /// setting how wide soft shadows' penumbras get.
+    pub fn set_light_size(&mut self, degrees: f32) {
+        self.renderer.set_light_size(degrees);
+    }
+
+    pub fn shadow_cascades(&self) -> usize {
+        self.renderer.shadow_cascades()
+    }
+
+    /// Shadows the scene from `count` shadow map cascades instead of tracing
+    /// rays, keeping them sharp up close in large scenes; 0 turns them off.
+    pub fn set_shadow_cascades(&mut self, count: usize) -> Result<(), String> {
+        self.renderer.set_shadow_cascades(count)
+    }
+
+    /// Whether meshes uploaded from now on store their vertices in half the
+    /// memory, see `CompactVertex`.
+    pub fn set_compact_vertices(&mut self, compact: bool) {
+        self.manager.compact_vertices = compact;
+    }
+
+    /// Whether the device can draw `Scene::reflection`.
+    pub fn reflection_supported(&self) -> bool {
+        self.renderer.reflection_supported()
+    }
+
+    /// Whether the regular shading modes get ambient occlusion traced on the
+    /// GPU, see `TracedOcclusion`.
+    pub fn occlusion_traced(&self) -> bool {
+        self.renderer.occlusion_traced()
+    }
+
+    pub fn set_occlusion_traced(&mut self, traced: bool) {
+        self.renderer.set_occlusion_traced(traced);
+    }
+
+    /// Scale of the scene against the window under temporal anti-aliasing,
+    /// `None` when it is off, see `TemporalResolve`.
+    pub fn temporal_scale(&self) -> Option<f32> {
+        self.renderer.temporal_scale()
+    }
+
+    pub fn set_temporal(&mut self, scale: Option<f32>) -> Result<(), String> {
+        self.renderer.set_temporal(scale)
+    }
+
+    pub fn face_sorted(&self) -> bool {
+        self.renderer.face_sorted()
+    }
+
+    /// Draws triangles sorted back to front on the CPU instead of depth
+    /// tested, to tell depth precision artifacts from geometry ones.
+    pub fn set_face_sorted(&mut self, sorted: bool) {
+        self.renderer.set_face_sorted(sorted);
+    }
+
+    /// Cameras drawn side by side instead of the one given to `draw`, none to
+    /// go back to a single view.
+    pub fn set_viewports(&mut self, viewports: Vec<Viewport>) {
+        self.renderer.set_viewports(viewports);
     }
 
     pub fn wait_idle(&self) {
         self.context.device.wait_idle();
     }
+
+    /// Waits for the frames in flight and then for the whole device, and
+    /// destroys the resources retired meanwhile, so no GPU object is still
+    /// in use once the engine is dropped. Safe to call more than once.
+    pub fn shutdown(&mut self) {
+        #[cfg(feature = "stats-window")]
+        if let Some(stats_window) = &self.stats_window {
+            stats_window.wait_for_frames();
+        }
+        self.renderer.wait_for_frames();
+        self.wait_idle();
+        self.manager.destroy_retired();
+    }
+}
+
+#[cfg(feature = "stats-window")]
+impl Engine {
+    /// Presents the stats window into `window`, replacing any previous one.
+    pub fn open_stats_window(&mut self, window: &dyn SurfaceWindow) -> Result<(), String> {
+        self.close_stats_window();
+        self.stats_window = Some(StatsWindow::new(
+            window,
+            self.context.clone(),
+            &self.manager,
+        )?);
+        Ok(())
+    }
+
+    pub fn close_stats_window(&mut self) {
+        self.stats_window = None;
+    }
+
+    pub fn has_stats_window(&self) -> bool {
+        self.stats_window.is_some()
+    }
+
+    pub fn resize_stats_window(&mut self, width: u32, height: u32) -> Result<(), String> {
+        match &mut self.stats_window {
+            Some(stats_window) => stats_window.resize(width, height),
+            None => Ok(()),
+        }
+    }
+
+    pub fn draw_stats_window(&mut self, hud: &Hud) -> Result<(), String> {
+        match &mut self.stats_window {
+            Some(stats_window) => stats_window.draw(hud),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The stats window's methods when built without it.
+#[cfg(not(feature = "stats-window"))]
+impl Engine {
+    pub fn open_stats_window(&mut self, _window: &dyn SurfaceWindow) -> Result<(), String> {
+        Err("Built without the stats window, rebuild with --features stats-window".to_string())
+    }
+
+    pub fn close_stats_window(&mut self) {}
+
+    pub fn has_stats_window(&self) -> bool {
+        false
+    }
+
+    pub fn resize_stats_window(&mut self, _width: u32, _height: u32) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn draw_stats_window(&mut self, _hud: &Hud) -> Result<(), String> {
+        Ok(())
+    }
 }
 
 impl Drop for Engine {
     fn drop(&mut self) {
-        self.wait_idle();
+        self.shutdown();
+        #[cfg(feature = "openxr")]
+        drop(self.xr.take());
+        #[cfg(feature = "stats-window")]
+        drop(self.stats_window.take());
+        // SAFETY: neither is used again, and the context they hold on to
+        // only drops with the remaining fields, after them.
+        unsafe {
+            ManuallyDrop::drop(&mut self.renderer);
+            ManuallyDrop::drop(&mut self.manager);
+        }
     }
 }
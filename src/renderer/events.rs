@@ -0,0 +1,54 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use super::{FrameStats, MeshHandle};
+
+/// Something the engine did that a host application may react to.
+#[derive(Debug, Clone)]
+pub enum EngineEvent {
+    /// A mesh was uploaded, from the OBJ file at `path` if it came from one.
+    ModelLoaded {
+        path: Option<String>,
+        mesh: MeshHandle,
+        triangles: usize,
+    },
+    /// A frame was submitted and presented, with its draw counters.
+    FrameRendered(FrameStats),
+    /// The GPU device was lost, with the error that revealed it. The engine
+    /// then carries on on a new device.
+    DeviceLost(String),
+    /// The window's swapchain was created again, at this size.
+    SwapchainRecreated { width: u32, height: u32 },
+}
+
+/// Subscribers to the engine's events, shared by the engine and its
+/// renderer.
+#[derive(Clone, Default)]
+pub struct EngineEvents {
+    subscribers: Arc<Mutex<Vec<Sender<EngineEvent>>>>,
+}
+
+impl EngineEvents {
+    /// A channel receiving every event from now on. Dropping the receiver
+    /// unsubscribes.
+    pub fn subscribe(&self) -> Receiver<EngineEvent> {
+        let (sender, receiver) = channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Sends `event` to the subscribers still listening, without building
+    /// it when there are none.
+    pub fn emit(&self, event: impl FnOnce() -> EngineEvent) {
+        let Ok(mut subscribers) = self.subscribers.lock() else {
+            return;
+        };
+        if subscribers.is_empty() {
+            return;
+        }
+        let event = event();
+        subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
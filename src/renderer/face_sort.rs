@@ -0,0 +1,85 @@
+use crate::camera::Camera;
+use crate::math::{Vec3, Vec4};
+use crate::scene::{ObjectHandle, Scene};
+
+use super::ResourcesManager;
+
+/// Consecutive triangles of the sorted order coming from the same group.
+#[derive(Debug, Clone, Copy)]
+pub struct SortedRun {
+    pub object: ObjectHandle,
+    /// Index in the object's `GpuMesh::groups`.
+    pub group: usize,
+    pub first_index: u32,
+    pub index_count: u32,
+}
+
+/// Every triangle of a scene ordered back to front from a camera, for
+/// drawing without a depth test. Skinned meshes are sorted in their bind
+/// pose.
+#[derive(Debug, Default)]
+pub struct FaceSort {
+    /// Group-local indices of all triangles, in drawing order.
+    pub indices: Vec<u32>,
+    pub runs: Vec<SortedRun>,
+}
+
+impl FaceSort {
+    /// Sorts by render layer, then by view depth of the triangle centroids.
+    pub fn build(camera: &Camera, scene: &Scene, resources: &ResourcesManager) -> Self {
+        let view = camera.get_view_matrix();
+
+        let mut triangles: Vec<SortedTriangle> = Vec::new();
+        for (handle, object) in scene.objects.iter().enumerate() {
+            let mesh = resources.get_mesh(object.id());
+            let to_view = view * object.transform();
+            // Empty groups aren't uploaded, like in `GpuMesh::groups`.
+            let groups = mesh
+                .geometry
+                .groups
+                .iter()
+                .filter(|group| !group.indices.is_empty());
+            for (index, group) in groups.enumerate() {
+                for corners in group.indices.chunks_exact(3) {
+                    let centroid = corners.iter().fold(Vec3::ZERO, |sum, &i| {
+                        sum + group.vertices[i as usize].position
+                    }) / 3.;
+                    triangles.push(SortedTriangle {
+                        layer: object.layer().order,
+                        depth: (to_view * Vec4::from_vec3(centroid, 1.)).z,
+                        object: handle,
+                        group: index,
+                        indices: [corners[0], corners[1], corners[2]],
+                    });
+                }
+            }
+        }
+        // Views look down -z: the farthest triangles have the lowest depth.
+        triangles.sort_by(|a, b| a.layer.cmp(&b.layer).then(a.depth.total_cmp(&b.depth)));
+
+        let mut sort = Self::default();
+        for triangle in triangles {
+            match sort.runs.last_mut() {
+                Some(run) if run.object == triangle.object && run.group == triangle.group => {
+                    run.index_count += 3
+                }
+                _ => sort.runs.push(SortedRun {
+                    object: triangle.object,
+                    group: triangle.group,
+                    first_index: sort.indices.len() as u32,
+                    index_count: 3,
+                }),
+            }
+            sort.indices.extend_from_slice(&triangle.indices);
+        }
+        sort
+    }
+}
+
+struct SortedTriangle {
+    layer: i32,
+    depth: f32,
+    object: ObjectHandle,
+    group: usize,
+    indices: [u32; 3],
+}
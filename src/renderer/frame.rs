@@ -1,53 +1,304 @@
+use std::collections::HashSet;
+
 use super::{
-    Uniforms, VkBuffer, VkCommandPool, VkContext, VkDescriptorPool, VkDescriptorSetLayout, VkFence,
-    VkSemaphore,
+    BitmapFont, DynamicBuffer, FaceSort, GroupKey, LineVertex, SceneAccelerationStructure,
+    SortedRun, TemporalPushConstants, TextVertex, TracePushConstants, Uniforms, VkBuffer,
+    VkCommandPool, VkContext, VkDescriptorPool, VkDescriptorSetLayout, VkDevice, VkFence,
+    VkQueryPool, VkSemaphore, VoxelInstance,
 };
-use crate::camera::Camera;
+use super::{MAX_JOINTS, MAX_OCCLUSION_QUERIES};
+use crate::math::Mat4;
+use crate::scene::{Hud, Skin};
 use ash::vk;
 
 pub struct FrameData {
+    /// Region of this frame in the renderer's `DynamicBuffer`s.
+    pub index: usize,
     pub image_available: VkSemaphore,
     pub render_finished: VkSemaphore,
     pub in_flight: VkFence,
     pub command_buffer: vk::CommandBuffer,
-    pub uniform_buffer: VkBuffer<Uniforms>,
     pub descriptor_set: vk::DescriptorSet,
+    pub line_buffer: VkBuffer<LineVertex>,
+    /// Cubes of the voxel preview.
+    pub voxel_buffer: VkBuffer<VoxelInstance>,
+    pub text_buffer: VkBuffer<TextVertex>,
+    pub text_vertex_count: u32,
+    /// Triangles of `sorted_runs`, in drawing order.
+    pub sorted_indices: VkBuffer<u32>,
+    /// Draws of the face-sorted view, empty when it is off.
+    pub sorted_runs: Vec<SortedRun>,
+    /// Index of each scene skin's first matrix in the joint buffer.
+    pub joint_offsets: Vec<u32>,
+    /// Dispatch of the traced occlusion, `None` when this frame skips it.
+    pub occlusion: Option<TracePushConstants>,
+    /// Resolve of the temporal anti-aliasing, `None` when it is off.
+    pub temporal: Option<TemporalPushConstants>,
+    /// Model-view-projection of each object in the previous frame, for the
+    /// motion vectors of the temporal resolve.
+    pub previous_transforms: Vec<Mat4>,
+    /// Scene the fragment shader traces shadows against, on devices
+    /// supporting ray queries.
+    pub scene_structure: Option<SceneAccelerationStructure>,
+    /// One occlusion query per entry of `occlusion_probes`.
+    pub occlusion_queries: VkQueryPool,
+    /// Groups whose bounding box this frame draws in a query.
+    pub occlusion_probes: Vec<GroupKey>,
+    /// Groups this frame skips, hidden when their box was last probed.
+    pub culled_groups: HashSet<GroupKey>,
 }
 
 impl FrameData {
     pub fn new(
         context: &VkContext,
+        index: usize,
         command_pool: &VkCommandPool,
         descriptor_pool: &VkDescriptorPool,
         descriptor_set_layout: &VkDescriptorSetLayout,
+        (uniforms, joints): (&DynamicBuffer<Uniforms>, &DynamicBuffer<Mat4>),
     ) -> Result<Self, String> {
-        let uniform_buffer =
-            VkBuffer::host_visible(context, 1, vk::BufferUsageFlags::UNIFORM_BUFFER)?;
+        let descriptor_set =
+            descriptor_pool.create_set(descriptor_set_layout, uniforms, joints, index)?;
+
+        let line_buffer = VkBuffer::host_visible(
+            context,
+            Self::INITIAL_LINE_CAPACITY,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+
+        let voxel_buffer = VkBuffer::host_visible(
+            context,
+            Self::INITIAL_VOXEL_CAPACITY,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+
+        let text_buffer = VkBuffer::host_visible(
+            context,
+            Self::INITIAL_TEXT_CAPACITY,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
 
-        let descriptor_set = descriptor_pool.create_set(descriptor_set_layout, &uniform_buffer)?;
+        let sorted_indices = VkBuffer::host_visible(
+            context,
+            Self::INITIAL_SORTED_CAPACITY,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+        )?;
 
         let command_buffer = command_pool
             .allocate_buffers(vk::CommandBufferLevel::PRIMARY, 1)?
             .remove(0);
 
+        let scene_structure = if context.device().ray_query() {
+            let structure = SceneAccelerationStructure::new(context)?;
+            descriptor_pool.update_scene_structure(descriptor_set, &structure);
+            Some(structure)
+        } else {
+            None
+        };
+
+        let occlusion_queries = VkQueryPool::new(
+            context.device(),
+            vk::QueryType::OCCLUSION,
+            MAX_OCCLUSION_QUERIES,
+        )?;
+
         let image_available = VkSemaphore::new(context.device())?;
         let render_finished = VkSemaphore::new(context.device())?;
         let in_flight = VkFence::new(context.device())?;
 
         Ok(Self {
+            index,
             image_available,
             render_finished,
             in_flight,
             command_buffer,
-            uniform_buffer,
             descriptor_set,
+            line_buffer,
+            voxel_buffer,
+            text_buffer,
+            text_vertex_count: 0,
+            sorted_indices,
+            sorted_runs: Vec::new(),
+            joint_offsets: Vec::new(),
+            occlusion: None,
+            temporal: None,
+            previous_transforms: Vec::new(),
+            scene_structure,
+            occlusion_queries,
+            occlusion_probes: Vec::new(),
+            culled_groups: HashSet::new(),
         })
     }
 
-    pub fn update_uniforms(&self, camera: &Camera) {
-        self.uniform_buffer.write(&[Uniforms {
-            view: camera.get_view_matrix(),
-            proj: camera.get_projection_matrix(),
-        }]);
+    const INITIAL_LINE_CAPACITY: usize = 4096;
+    const INITIAL_VOXEL_CAPACITY: usize = 1024;
+    const INITIAL_TEXT_CAPACITY: usize = 6 * 1024;
+    const INITIAL_SORTED_CAPACITY: usize = 3 * 4096;
+
+    /// Packs the pose of every skin into this frame's joint region.
+    pub fn upload_joints(
+        &mut self,
+        joints: &DynamicBuffer<Mat4>,
+        skins: &[Skin],
+    ) -> Result<(), String> {
+        let mut matrices = Vec::new();
+        self.joint_offsets.clear();
+        for skin in skins {
+            self.joint_offsets.push(matrices.len() as u32);
+            matrices.extend(skin.joint_matrices());
+        }
+
+        if matrices.len() > MAX_JOINTS {
+            return Err(format!(
+                "Too many joints: {} (max {})",
+                matrices.len(),
+                MAX_JOINTS
+            ));
+        }
+        joints.write(self.index, &matrices);
+        Ok(())
+    }
+
+    pub fn update_uniforms(&self, uniforms: &DynamicBuffer<Uniforms>, data: Uniforms) {
+        uniforms.write(self.index, &[data]);
+    }
+
+    /// Records a copy of `data` over this frame's uniforms, between render
+    /// passes, for the draws recorded after it only.
+    pub fn record_uniforms(
+        &self,
+        device: &VkDevice,
+        cmd: vk::CommandBuffer,
+        uniforms: &DynamicBuffer<Uniforms>,
+        data: &Uniforms,
+    ) {
+        let barrier = |src_access_mask, dst_access_mask| vk::BufferMemoryBarrier {
+            s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+            src_access_mask,
+            dst_access_mask,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer: uniforms.handle(),
+            offset: uniforms.offset(self.index),
+            size: uniforms.range(),
+            ..Default::default()
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                data as *const Uniforms as *const u8,
+                std::mem::size_of::<Uniforms>(),
+            )
+        };
+
+        unsafe {
+            device.handle.cmd_pipeline_barrier(
+                cmd,
+                vk::PipelineStageFlags::ALL_GRAPHICS,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[barrier(
+                    vk::AccessFlags::UNIFORM_READ,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                )],
+                &[],
+            );
+            device.handle.cmd_update_buffer(
+                cmd,
+                uniforms.handle(),
+                uniforms.offset(self.index),
+                bytes,
+            );
+            device.handle.cmd_pipeline_barrier(
+                cmd,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::ALL_GRAPHICS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[barrier(
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::UNIFORM_READ,
+                )],
+                &[],
+            );
+        }
+    }
+
+    /// Copies the debug lines into this frame's vertex buffer, growing it when
+    /// needed. The frame must not be in flight.
+    pub fn upload_lines(
+        &mut self,
+        context: &VkContext,
+        vertices: &[LineVertex],
+    ) -> Result<(), String> {
+        if vertices.len() > self.line_buffer.capacity() {
+            self.line_buffer = VkBuffer::host_visible(
+                context,
+                vertices.len().next_power_of_two(),
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+            )?;
+        }
+        self.line_buffer.write(vertices);
+        Ok(())
+    }
+
+    /// Copies the voxel preview's cubes into this frame's instance buffer,
+    /// growing it when needed. The frame must not be in flight.
+    pub fn upload_voxels(
+        &mut self,
+        context: &VkContext,
+        instances: &[VoxelInstance],
+    ) -> Result<(), String> {
+        if instances.len() > self.voxel_buffer.capacity() {
+            self.voxel_buffer = VkBuffer::host_visible(
+                context,
+                instances.len().next_power_of_two(),
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+            )?;
+        }
+        self.voxel_buffer.write(instances);
+        Ok(())
+    }
+
+    /// Copies the triangle order of the face-sorted view into this frame's
+    /// index buffer, growing it when needed, or clears it with `None`. The
+    /// frame must not be in flight.
+    pub fn upload_face_sort(
+        &mut self,
+        context: &VkContext,
+        sort: Option<FaceSort>,
+    ) -> Result<(), String> {
+        let Some(sort) = sort else {
+            self.sorted_runs.clear();
+            return Ok(());
+        };
+        if sort.indices.len() > self.sorted_indices.capacity() {
+            self.sorted_indices = VkBuffer::host_visible(
+                context,
+                sort.indices.len().next_power_of_two(),
+                vk::BufferUsageFlags::INDEX_BUFFER,
+            )?;
+        }
+        self.sorted_indices.write(&sort.indices);
+        self.sorted_runs = sort.runs;
+        Ok(())
+    }
+
+    /// Lays out the HUD text into this frame's vertex buffer, growing it when
+    /// needed. The frame must not be in flight.
+    pub fn upload_text(&mut self, context: &VkContext, hud: &Hud) -> Result<(), String> {
+        let mut vertices = Vec::new();
+        BitmapFont::layout(hud, &mut vertices);
+
+        if vertices.len() > self.text_buffer.capacity() {
+            self.text_buffer = VkBuffer::host_visible(
+                context,
+                vertices.len().next_power_of_two(),
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+            )?;
+        }
+        self.text_buffer.write(&vertices);
+        self.text_vertex_count = vertices.len() as u32;
+        Ok(())
     }
 }
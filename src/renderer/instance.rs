@@ -1,17 +1,22 @@
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 
 use ash::{vk, Entry, Instance};
-use sdl3::video::Window;
 
-use super::{VALIDATION_LAYERS, VALIDATION_LAYERS_ENABLED};
+use super::{SurfaceWindow, ValidationFeatures, VALIDATION_LAYERS};
+use crate::breadcrumb;
 
 pub struct VkInstance {
     pub handle: Instance,
 }
 
 impl VkInstance {
-    pub fn new(entry: &Entry, window: &Window) -> Result<VkInstance, String> {
-        let handle = VkInstance::create_instance(&entry, window)?;
+    pub fn new(
+        entry: &Entry,
+        window: &dyn SurfaceWindow,
+        validation: ValidationFeatures,
+        extra_extensions: &[CString],
+    ) -> Result<VkInstance, String> {
+        let handle = VkInstance::create_instance(&entry, window, validation, extra_extensions)?;
 
         return Ok(VkInstance { handle });
     }
@@ -50,25 +55,63 @@ impl VkInstance {
         return true;
     }
 
-    fn create_instance(entry: &Entry, window: &Window) -> Result<Instance, String> {
-        if VALIDATION_LAYERS_ENABLED && !Self::check_validation_layer_support(entry) {
+    /// Whether `extension` is available, from the implementation or, when
+    /// given, from `layer`.
+    fn check_extension_support(entry: &Entry, layer: Option<&CStr>, extension: &CStr) -> bool {
+        let extensions = unsafe {
+            entry
+                .enumerate_instance_extension_properties(layer)
+                .unwrap_or_default()
+        };
+
+        extensions.iter().any(|properties| {
+            properties
+                .extension_name_as_c_str()
+                .is_ok_and(|name| name == extension)
+        })
+    }
+
+    fn create_instance(
+        entry: &Entry,
+        window: &dyn SurfaceWindow,
+        validation: ValidationFeatures,
+        extra_extensions: &[CString],
+    ) -> Result<Instance, String> {
+        let layers_enabled = validation.layers_enabled();
+        if layers_enabled && !Self::check_validation_layer_support(entry) {
             return Err("Validation layers not supported".to_string());
         }
 
+        let enables = validation.enables();
+        let layer = CString::new(VALIDATION_LAYERS[0]).unwrap_or_default();
+        if !enables.is_empty()
+            && !Self::check_extension_support(entry, Some(&layer), vk::EXT_VALIDATION_FEATURES_NAME)
+        {
+            return Err("Validation features not supported by the validation layers".to_string());
+        }
+
         let application_info = vk::ApplicationInfo {
             api_version: vk::API_VERSION_1_3,
             ..Default::default()
         };
 
-        let extension_names = window.vulkan_instance_extensions().unwrap();
+        let extension_names = window.instance_extensions()?;
 
         let extension_cstrings: Vec<CString> = extension_names
             .iter()
             .map(|s| CString::new(s.as_str()).unwrap())
             .collect();
 
-        let extension_names_raw: Vec<*const i8> =
+        let mut extension_names_raw: Vec<*const i8> =
             extension_cstrings.iter().map(|s| s.as_ptr()).collect();
+        for extension in extra_extensions {
+            if !extension_cstrings.contains(extension) {
+                extension_names_raw.push(extension.as_ptr());
+            }
+        }
+        if !enables.is_empty() {
+            extension_names_raw.push(vk::EXT_VALIDATION_FEATURES_NAME.as_ptr());
+        }
 
         let validation_layers_cstring: Vec<CString> = VALIDATION_LAYERS
             .iter()
@@ -87,16 +130,43 @@ impl VkInstance {
             ..Default::default()
         };
 
-        if VALIDATION_LAYERS_ENABLED {
+        if layers_enabled {
             create_info.pp_enabled_layer_names = validation_layers.as_ptr();
             create_info.enabled_layer_count = validation_layers.len() as u32;
+            eprintln!("Validation layers enabled: {}", validation);
         }
 
-        let instance = unsafe {
-            entry
-                .create_instance(&create_info, None)
-                .map_err(|e| format!("Failed to create Vulkan instance: {:?}", e))?
+        let validation_features = vk::ValidationFeaturesEXT {
+            s_type: vk::StructureType::VALIDATION_FEATURES_EXT,
+            enabled_validation_feature_count: enables.len() as u32,
+            p_enabled_validation_features: enables.as_ptr(),
+            ..Default::default()
         };
+        if !enables.is_empty() {
+            create_info.p_next = &validation_features as *const _ as *const std::ffi::c_void;
+        }
+
+        let instance = match unsafe { entry.create_instance(&create_info, None) } {
+            // Portability implementations such as MoltenVK are only listed on
+            // request.
+            Err(vk::Result::ERROR_INCOMPATIBLE_DRIVER)
+                if Self::check_extension_support(
+                    entry,
+                    None,
+                    vk::KHR_PORTABILITY_ENUMERATION_NAME,
+                ) =>
+            {
+                eprintln!("No conformant Vulkan driver, retrying with portability drivers");
+                extension_names_raw.push(vk::KHR_PORTABILITY_ENUMERATION_NAME.as_ptr());
+                create_info.pp_enabled_extension_names = extension_names_raw.as_ptr();
+                create_info.enabled_extension_count = extension_names_raw.len() as u32;
+                create_info.flags |= vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
+                unsafe { entry.create_instance(&create_info, None) }
+            }
+            result => result,
+        }
+        .map_err(|e| format!("Failed to create Vulkan instance: {:?}", e))?;
+        breadcrumb!("vkCreateInstance");
 
         Ok(instance)
     }
@@ -0,0 +1,153 @@
+use ash::vk;
+use std::path::Path;
+
+use crate::assets;
+
+use super::{image_size, transcode_etc1s, BlockCompression, CompressedFormats, CompressedImage};
+
+const IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+/// Bytes before the level index: the identifier, the header and the index
+/// of the other sections.
+const LEVEL_INDEX_OFFSET: usize = 80;
+
+/// BasisLZ supercompression, of ETC1S blocks.
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+
+/// Transfer function of sRGB data in the data format descriptor.
+const TRANSFER_SRGB: u8 = 2;
+
+/// Parses a KTX2 file holding a single 2D image, with its mip levels.
+/// BasisLZ files are transcoded to a format of `formats`, or RGBA8 without
+/// one, see `transcode_etc1s`.
+pub fn parse_ktx2(bytes: &[u8], formats: &CompressedFormats) -> Result<CompressedImage, String> {
+    if bytes.len() < LEVEL_INDEX_OFFSET || bytes[..12] != IDENTIFIER {
+        return Err("Not a KTX2 file".to_string());
+    }
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| {
+        bytes
+            .get(offset..offset + 8)
+            .map(|field| u64::from_le_bytes(field.try_into().unwrap()))
+            .ok_or_else(|| "KTX2 level index is truncated".to_string())
+    };
+
+    let format = vk::Format::from_raw(u32_at(12) as i32);
+    let (width, height, depth) = (u32_at(20), u32_at(24), u32_at(28));
+    let (layers, faces, level_count) = (u32_at(32), u32_at(36), u32_at(40));
+    let supercompression = u32_at(44);
+    if depth > 1 || layers > 1 || faces != 1 || height == 0 {
+        return Err("Only single 2D KTX2 images are supported".to_string());
+    }
+
+    let mut levels = Vec::new();
+    for level in 0..level_count.max(1) {
+        let entry = LEVEL_INDEX_OFFSET + level as usize * 24;
+        let offset = u64_at(entry)? as usize;
+        let length = u64_at(entry + 8)? as usize;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or_else(|| format!("KTX2 level {} is truncated", level))?;
+        levels.push(data);
+    }
+
+    match supercompression {
+        0 if format == vk::Format::UNDEFINED => {
+            return Err("UASTC KTX2 files are not supported".to_string())
+        }
+        0 => {}
+        SUPERCOMPRESSION_BASIS_LZ => {
+            let dfd = u32_at(48) as usize;
+            let srgb = bytes.get(dfd + 14) == Some(&TRANSFER_SRGB);
+            let (global_offset, global_length) = (u64_at(64)? as usize, u64_at(72)? as usize);
+            let global = bytes
+                .get(global_offset..global_offset + global_length)
+                .ok_or("KTX2 supercompression data is truncated")?;
+            return transcode_etc1s(global, &levels, (width, height), srgb, formats);
+        }
+        scheme => {
+            return Err(format!(
+                "KTX2 supercompression scheme {} is not supported",
+                scheme
+            ))
+        }
+    }
+
+    for (level, data) in levels.iter().enumerate() {
+        let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+        let expected = image_size(format, level_width, level_height)
+            .ok_or_else(|| format!("KTX2 format {:?} is not supported", format))?;
+        if data.len() != expected {
+            return Err(format!(
+                "KTX2 level {} holds {} bytes instead of {}",
+                level,
+                data.len(),
+                expected
+            ));
+        }
+    }
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        levels: levels.iter().map(|level| level.to_vec()).collect(),
+    })
+}
+
+/// Reads and parses the KTX2 file at `path`, see `parse_ktx2`.
+pub fn load_ktx2(path: &str, formats: &CompressedFormats) -> Result<CompressedImage, String> {
+    let bytes = assets::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    parse_ktx2(&bytes, formats)
+}
+
+/// Compressed texture to upload for `path` on a device sampling `formats`:
+/// the first variant `name.<family>.ktx2` next to it in a family the device
+/// supports, else a supercompressed `name.ktx2` transcoded for the device,
+/// or `path` itself when it is a KTX2 file the device samples or one
+/// transcoded. `None` when the source image has to be decoded instead.
+pub fn load_compressed(
+    path: &str,
+    formats: &CompressedFormats,
+) -> Result<Option<CompressedImage>, String> {
+    let source = Path::new(path);
+    if source.extension().is_some_and(|ext| ext == "ktx2") {
+        let image = load_ktx2(path, formats)?;
+        return Ok(formats.samples(image.format).then_some(image));
+    }
+
+    for family in BlockCompression::ALL {
+        if !formats.supports(family) {
+            continue;
+        }
+        let variant = source.with_extension(format!("{}.ktx2", family.suffix()));
+        let Some(variant) = variant.to_str() else {
+            continue;
+        };
+        if !assets::exists(variant) {
+            continue;
+        }
+        let image = load_ktx2(variant, formats)
+            .map_err(|e| format!("Invalid variant '{}': {}", variant, e))?;
+        if BlockCompression::of(image.format) != Some(family) {
+            return Err(format!(
+                "Variant '{}' holds {:?}, not {} blocks",
+                variant,
+                image.format,
+                family.suffix()
+            ));
+        }
+        return Ok(Some(image));
+    }
+
+    let universal = source.with_extension("ktx2");
+    match universal.to_str() {
+        Some(universal) if assets::exists(universal) => load_ktx2(universal, formats)
+            .map(Some)
+            .map_err(|e| format!("Invalid variant '{}': {}", universal, e)),
+        _ => Ok(None),
+    }
+}
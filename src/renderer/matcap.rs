@@ -0,0 +1,123 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::linear_to_srgb8;
+use crate::math::Vec3;
+
+/// Texels along the edge of a built-in matcap.
+pub const MATCAP_SIZE: u32 = 256;
+
+/// Built-in material captures: spheres lit once, looked up by view-space
+/// normal in matcap shading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Matcap {
+    #[default]
+    Clay,
+    RedWax,
+    Chrome,
+    Jade,
+}
+
+impl Matcap {
+    pub const ALL: [Matcap; 4] = [Matcap::Clay, Matcap::RedWax, Matcap::Chrome, Matcap::Jade];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&m| m == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Matcap::Clay => "clay",
+            Matcap::RedWax => "red wax",
+            Matcap::Chrome => "chrome",
+            Matcap::Jade => "jade",
+        }
+    }
+
+    /// Renders the sphere as `size` x `size` sRGB RGBA8 pixels. Texels
+    /// outside the disk repeat its rim so filtering doesn't pull in black.
+    pub fn rgba8(self, size: u32) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let u = (x as f32 + 0.5) / size as f32 * 2. - 1.;
+                let v = (y as f32 + 0.5) / size as f32 * 2. - 1.;
+                let d2 = u * u + v * v;
+                let normal = if d2 < 1. {
+                    Vec3::new(u, -v, (1. - d2).sqrt())
+                } else {
+                    Vec3::new(u, -v, 0.).normalize()
+                };
+
+                let color = self.shade(normal);
+                pixels.extend_from_slice(&[
+                    linear_to_srgb8(color.x),
+                    linear_to_srgb8(color.y),
+                    linear_to_srgb8(color.z),
+                    255,
+                ]);
+            }
+        }
+        pixels
+    }
+
+    /// Linear color of the sphere at `normal`, seen along -Z.
+    fn shade(self, normal: Vec3) -> Vec3 {
+        let light = Vec3::new(-0.5, 0.6, 0.6).normalize();
+        let view = Vec3::new(0., 0., 1.);
+        let half = (light + view).normalize();
+
+        let diffuse = normal.dot(light).max(0.);
+        let wrapped = (normal.dot(light) + 0.5) / 1.5;
+        let specular = |exponent: f32| normal.dot(half).max(0.).powf(exponent);
+        let rim = 1. - normal.z.max(0.);
+
+        match self {
+            Matcap::Clay => {
+                let base = Vec3::new(0.62, 0.52, 0.44);
+                base * (0.25 + 0.75 * wrapped.max(0.)) * (1. - 0.35 * rim * rim)
+                    + Vec3::splat(0.08 * specular(12.))
+            }
+            Matcap::RedWax => {
+                let base = Vec3::new(0.55, 0.05, 0.04);
+                base * (0.15 + 0.85 * diffuse)
+                    + Vec3::new(0.6, 0.2, 0.15) * rim.powf(3.)
+                    + Vec3::splat(0.7 * specular(80.))
+            }
+            Matcap::Chrome => {
+                // Mirror the view around the normal into a sharp sky/ground
+                // horizon.
+                let reflected = normal * (2. * normal.z) - view;
+                let sky = Vec3::new(0.85, 0.9, 1.) * (0.5 + 0.5 * reflected.y);
+                let ground = Vec3::new(0.12, 0.1, 0.09) * (1. + reflected.y);
+                let env = if reflected.y > 0. { sky } else { ground };
+                env + Vec3::splat(2. * specular(200.))
+            }
+            Matcap::Jade => {
+                let base = Vec3::new(0.2, 0.55, 0.35);
+                base * (0.3 + 0.7 * wrapped.max(0.))
+                    + Vec3::new(0.3, 0.7, 0.45) * rim.powf(2.) * 0.5
+                    + Vec3::splat(0.5 * specular(60.))
+            }
+        }
+    }
+}
+
+impl FromStr for Matcap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|m| m.name() == s)
+            .ok_or_else(|| format!("Unknown matcap '{}'", s))
+    }
+}
+
+impl fmt::Display for Matcap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -1,7 +1,12 @@
 use crate::{
-    math::{Mat4, Vec3},
-    renderer::{MaterialHandle, ResourcesManager, TextureHandle, Vertex, VkBuffer},
+    math::{Aabb, ConvexHull, Mat4, Obb, Vec3, Vec4},
+    renderer::{
+        CompactVertex, MaterialHandle, ResourcesManager, TextureHandle, Vertex, VertexSkin,
+        VkAccelerationStructure, VkBuffer,
+    },
+    scene::{Bvh, Mesh},
 };
+use ash::vk;
 
 #[derive(Debug, Clone)]
 pub struct GpuMaterial {
@@ -15,6 +20,13 @@ pub struct GpuMaterial {
     pub map_ka: TextureHandle,
     pub map_kd: TextureHandle,
     pub map_ks: TextureHandle,
+    /// Cutout mask, white for opaque materials.
+    pub map_d: TextureHandle,
+    /// Height map, drawn through the tessellated pipelines when the device
+    /// has them.
+    pub map_disp: Option<TextureHandle>,
+    /// Drawn without culling, back faces lit through their flipped normal.
+    pub two_sided: bool,
 }
 
 impl Default for GpuMaterial {
@@ -32,6 +44,9 @@ impl Default for GpuMaterial {
             map_ka: white,
             map_kd: white,
             map_ks: white,
+            map_d: white,
+            map_disp: None,
+            two_sided: false,
         }
     }
 }
@@ -43,13 +58,47 @@ pub struct MaterialPushConstants {
     pub diffuse: Vec3,
     pub shininess: f32,
     pub specular: Vec3,
-    pub optical_density: f32,
+    /// Lightmap of the drawn mesh, white when it has none. Takes the slot of
+    /// the optical density, which no shader reads.
+    pub tex_lightmap: u32,
     pub illum: i32,
+    /// Diffuse texture in the low 16 bits, cutout mask in the high ones; the
+    /// push constants are already at the guaranteed 128 bytes.
     pub tex_diffuse: u32,
+    /// Ambient texture in the low 16 bits, height map in the high ones, 0
+    /// without one.
     pub tex_ambient: u32,
+    /// Specular texture in the low 16 bits, `MATERIAL_TWO_SIDED` and any other
+    /// flags in the high ones.
     pub tex_specular: u32,
 }
 
+impl MaterialPushConstants {
+    pub const MATERIAL_TWO_SIDED: u32 = 1 << 16;
+
+    /// Flat `color`, as read by the outline shader.
+    pub fn solid(color: Vec4) -> Self {
+        Self {
+            ambient: Vec3::ZERO,
+            diffuse: color.xyz(),
+            specular: Vec3::ZERO,
+            shininess: 1.,
+            tex_lightmap: ResourcesManager::white_texture() as u32,
+            dissolve: color.w,
+            illum: 0,
+            tex_diffuse: ResourcesManager::white_texture() as u32,
+            tex_specular: ResourcesManager::white_texture() as u32,
+            tex_ambient: ResourcesManager::white_texture() as u32,
+        }
+    }
+
+    /// Blends `amount` of `color` into the diffuse and ambient terms.
+    pub fn tint(&mut self, color: Vec3, amount: f32) {
+        self.diffuse = self.diffuse * (1. - amount) + color * amount;
+        self.ambient = self.ambient * (1. - amount) + color * amount;
+    }
+}
+
 impl From<&GpuMaterial> for MaterialPushConstants {
     fn from(mat: &GpuMaterial) -> Self {
         Self {
@@ -57,27 +106,82 @@ impl From<&GpuMaterial> for MaterialPushConstants {
             diffuse: mat.kd,
             specular: mat.ks,
             shininess: mat.ns,
-            optical_density: mat.ni,
+            tex_lightmap: ResourcesManager::white_texture() as u32,
             dissolve: mat.dissolve,
             illum: mat.illum,
-            tex_diffuse: mat.map_kd as u32,
-            tex_specular: mat.map_ks as u32,
-            tex_ambient: mat.map_ka as u32,
+            tex_diffuse: mat.map_kd as u32 | (mat.map_d as u32) << 16,
+            tex_specular: mat.map_ks as u32
+                | if mat.two_sided {
+                    Self::MATERIAL_TWO_SIDED
+                } else {
+                    0
+                },
+            tex_ambient: mat.map_ka as u32 | (mat.map_disp.unwrap_or(0) as u32) << 16,
         }
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct GpuGroup {
     pub index_offset: u32,
     pub index_count: u32,
     pub vertex_offset: i32,
     pub material: MaterialHandle,
+    /// Bounds of the group's vertices, in mesh space.
+    pub bounds: Aabb,
+}
+
+/// Vertices of a mesh on the GPU, in the layout its pipeline variant reads.
+pub enum MeshVertices {
+    Full(VkBuffer<Vertex>),
+    /// Quantized into the mesh's bounds, which `decode` maps them back from.
+    Compact {
+        buffer: VkBuffer<CompactVertex>,
+        decode: Mat4,
+    },
+}
+
+impl MeshVertices {
+    pub fn handle(&self) -> vk::Buffer {
+        match self {
+            MeshVertices::Full(buffer) => buffer.handle,
+            MeshVertices::Compact { buffer, .. } => buffer.handle,
+        }
+    }
+
+    /// Transform applied before the model's to draw the vertices in place.
+    pub fn decode(&self) -> Mat4 {
+        match self {
+            MeshVertices::Full(_) => Mat4::identity(),
+            MeshVertices::Compact { decode, .. } => *decode,
+        }
+    }
+
+    pub fn is_compact(&self) -> bool {
+        matches!(self, MeshVertices::Compact { .. })
+    }
 }
 
 pub struct GpuMesh {
-    pub vertex_buffer: VkBuffer<Vertex>,
+    pub vertices: MeshVertices,
     pub index_buffer: VkBuffer<u32>,
+    /// Joint influences parallel to `vertices`, for skinned meshes.
+    pub skin_buffer: Option<VkBuffer<VertexSkin>>,
+    /// Both ends of each of `geometry.lines`, for meshes with any.
+    pub line_buffer: Option<VkBuffer<Vertex>>,
     pub groups: Vec<GpuGroup>,
+    pub bounds: Aabb,
+    /// Tighter bounds along the mesh's own axes, from `hull`.
+    pub obb: Obb,
+    pub hull: ConvexHull,
+    /// CPU copy of the uploaded mesh, for picking queries.
+    pub geometry: Mesh,
+    /// Hierarchy over `geometry`'s triangles.
+    pub bvh: Bvh,
+    /// Uploaded `geometry.lightmap`.
+    pub lightmap: Option<TextureHandle>,
+    /// Ray query structure over `geometry`, on devices supporting it.
+    pub blas: Option<VkAccelerationStructure>,
 }
 
 #[repr(C)]
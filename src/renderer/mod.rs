@@ -1,60 +1,144 @@
+mod acceleration_structure;
+mod basis;
 mod buffer;
+mod capabilities;
 mod command_pool;
+mod compressed;
 mod context;
+mod cubemap;
 mod descriptor;
 mod device;
+mod display_adjustments;
+mod dynamic_buffer;
 mod engine;
+mod events;
+mod face_sort;
 mod fence;
 mod frame;
 mod image;
 mod instance;
+mod ktx2;
+mod matcap;
 mod mesh;
+mod occlusion_culling;
+mod offscreen;
+#[cfg(feature = "openxr")]
+mod openxr;
+mod path_tracer;
 mod physical_device;
+mod picking;
 mod pipeline;
+mod pipeline_manager;
+mod plugin;
+mod query_pool;
 mod queue;
+mod reflection;
+mod render_graph;
 mod render_pass;
 mod renderer;
 mod resources;
+mod sampler;
 mod semaphore;
 mod shaders;
+mod shading;
+mod shadow_cascades;
+mod shadow_quality;
+#[cfg(feature = "stats-window")]
+mod stats_window;
 mod surface;
+mod surface_analysis;
 mod swapchain;
+mod sync_timings;
+mod temporal;
+mod text;
 mod texture;
+mod traced_occlusion;
+mod transient;
 mod utils;
+mod validation;
 mod vertex;
+mod viewport;
+mod window;
 
+pub use acceleration_structure::*;
+pub use basis::*;
 pub use buffer::*;
+pub use capabilities::*;
 pub use command_pool::*;
+pub use compressed::*;
 pub use context::*;
+pub use cubemap::*;
 pub use descriptor::*;
 pub use device::*;
+pub use display_adjustments::*;
+pub use dynamic_buffer::*;
 pub use engine::*;
+pub use events::*;
+pub use face_sort::*;
 pub use fence::*;
 pub use frame::*;
 pub use image::*;
 pub use instance::*;
+pub use ktx2::*;
+pub use matcap::*;
 pub use mesh::*;
+pub use occlusion_culling::*;
+pub use offscreen::*;
+#[cfg(feature = "openxr")]
+pub use openxr::*;
+pub use path_tracer::*;
 pub use physical_device::*;
+pub use picking::*;
 pub use pipeline::*;
+pub use pipeline_manager::*;
+pub use plugin::*;
+pub use query_pool::*;
 pub use queue::*;
+pub use reflection::*;
+pub use render_graph::*;
 pub use render_pass::*;
 pub use renderer::*;
 pub use resources::*;
+pub use sampler::*;
 pub use semaphore::*;
 pub use shaders::*;
+pub use shading::*;
+pub use shadow_cascades::*;
+pub use shadow_quality::*;
+#[cfg(feature = "stats-window")]
+pub use stats_window::*;
 pub use surface::*;
+pub use surface_analysis::*;
 pub use swapchain::*;
+pub use sync_timings::*;
+pub use temporal::*;
+pub use text::*;
 pub use texture::*;
+pub use traced_occlusion::*;
+pub use transient::*;
 pub use utils::*;
+pub use validation::*;
 pub use vertex::*;
+pub use viewport::*;
+pub use window::*;
 
 use ash::vk;
 use std::ffi::CStr;
 
 pub const MAX_FRAMES_IN_FLIGHT: u32 = 2;
 
+/// Joint matrices available to all the skins of a frame.
+pub const MAX_JOINTS: usize = 1024;
+
 // pub const VALIDATION_LAYERS_ENABLED: bool = cfg!(debug_assertions);
 pub const VALIDATION_LAYERS_ENABLED: bool = false;
 pub const VALIDATION_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
 
 pub const DEVICE_EXTENSIONS: [&CStr; 1] = [vk::KHR_SWAPCHAIN_NAME];
+
+/// Optional extensions enabling ray-queried shadows, all or none.
+pub const RAY_QUERY_EXTENSIONS: [&CStr; 3] = [
+    vk::KHR_ACCELERATION_STRUCTURE_NAME,
+    vk::KHR_RAY_QUERY_NAME,
+    vk::KHR_DEFERRED_HOST_OPERATIONS_NAME,
+];
@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::{
+    DescriptorLayouts, FrameData, MeshPushConstants, ResourcesManager, VkDevice, VkPipeline,
+    VkRenderPass,
+};
+use crate::camera::Camera;
+use crate::math::Vec3;
+use crate::scene::Scene;
+
+/// Groups with fewer triangles are always drawn, since probing them would
+/// cost about as much as drawing them.
+pub const MIN_OCCLUSION_TRIANGLES: u32 = 1024;
+
+/// Groups probed per frame; the rest are always drawn.
+pub const MAX_OCCLUSION_QUERIES: u32 = 4096;
+
+/// Growth of the probed boxes, as a fraction of their size, so faces lying on
+/// a box don't hide their own group.
+const OCCLUSION_BOX_MARGIN: f32 = 0.01;
+
+/// Vertices of the probed box, as drawn by the shader.
+const BOX_VERTICES: u32 = 36;
+
+/// A group of a mesh, by object index in the scene and group index in the
+/// mesh.
+pub type GroupKey = (usize, usize);
+
+/// Skips large groups that were hidden behind the rest of the scene the last
+/// time their frame was drawn. Each frame draws the bounding box of those
+/// groups inside an occlusion query once the objects are in the depth buffer,
+/// and the results decide which of them the frame after next skips.
+/// Skinned meshes are always drawn, as their bounds don't follow the pose.
+pub struct OcclusionCulling {
+    pipeline: VkPipeline,
+    /// Groups whose last probe passed no sample.
+    occluded: HashSet<GroupKey>,
+}
+
+impl OcclusionCulling {
+    pub fn new(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            pipeline: VkPipeline::occlusion_probe(device, render_pass, descriptor_layouts)?,
+            occluded: HashSet::new(),
+        })
+    }
+
+    /// Reads the probes `frame` recorded when last drawn, then picks the
+    /// groups it probes and skips this time. The frame must not be in flight.
+    pub fn update(
+        &mut self,
+        frame: &mut FrameData,
+        camera: &Camera,
+        scene: &Scene,
+        resources: &ResourcesManager,
+    ) {
+        let probes = frame.occlusion_probes.len() as u32;
+        if let Some(results) = frame.occlusion_queries.results(probes) {
+            for (key, samples) in frame.occlusion_probes.iter().zip(results) {
+                if samples == 0 {
+                    self.occluded.insert(*key);
+                } else {
+                    self.occluded.remove(key);
+                }
+            }
+        }
+
+        frame.occlusion_probes.clear();
+        frame.culled_groups.clear();
+
+        // Boxes reaching the near plane are clipped and could be missed.
+        let near = (camera.frustum_corners()[0] - camera.position).length();
+        for (index, object) in scene.objects.iter().enumerate() {
+            let mesh = resources.get_mesh(object.id());
+            if mesh.skin_buffer.is_some() {
+                continue;
+            }
+
+            let transform = object.transform();
+            for (group_index, group) in mesh.groups.iter().enumerate() {
+                if group.index_count / 3 < MIN_OCCLUSION_TRIANGLES
+                    || frame.occlusion_probes.len() >= MAX_OCCLUSION_QUERIES as usize
+                {
+                    continue;
+                }
+                let bounds = group.bounds.transform(&transform).grow(near);
+                if bounds.contains(camera.position) {
+                    continue;
+                }
+
+                let key = (index, group_index);
+                frame.occlusion_probes.push(key);
+                if self.occluded.contains(&key) {
+                    frame.culled_groups.insert(key);
+                }
+            }
+        }
+    }
+
+    /// Draws the box of every group `frame` probes inside its query, tested
+    /// against the depth drawn so far without writing anything. The queries
+    /// must have been reset before the render pass.
+    pub fn record(
+        &self,
+        device: &VkDevice,
+        cmd: vk::CommandBuffer,
+        frame: &FrameData,
+        scene: &Scene,
+        resources: &ResourcesManager,
+    ) {
+        if frame.occlusion_probes.is_empty() {
+            return;
+        }
+
+        unsafe {
+            device.handle.cmd_bind_pipeline(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.handle,
+            );
+            device.handle.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.layout,
+                0,
+                &[frame.descriptor_set],
+                &[],
+            );
+            device
+                .handle
+                .cmd_set_cull_mode(cmd, vk::CullModeFlags::NONE);
+        }
+
+        for (query, &(index, group_index)) in frame.occlusion_probes.iter().enumerate() {
+            let object = &scene.objects[index];
+            let bounds = resources.get_mesh(object.id()).groups[group_index].bounds;
+            let margin = Vec3::splat(bounds.radius() * OCCLUSION_BOX_MARGIN);
+            let vpc = MeshPushConstants {
+                transform: object
+                    .transform()
+                    .translate(bounds.min - margin)
+                    .scale(bounds.size() + margin * 2.),
+            };
+
+            unsafe {
+                device.handle.cmd_push_constants(
+                    cmd,
+                    self.pipeline.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    std::slice::from_raw_parts(
+                        &vpc as *const _ as *const u8,
+                        std::mem::size_of::<MeshPushConstants>(),
+                    ),
+                );
+                device.handle.cmd_begin_query(
+                    cmd,
+                    frame.occlusion_queries.handle,
+                    query as u32,
+                    vk::QueryControlFlags::empty(),
+                );
+                device.handle.cmd_draw(cmd, BOX_VERTICES, 1, 0, 0);
+                device
+                    .handle
+                    .cmd_end_query(cmd, frame.occlusion_queries.handle, query as u32);
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use ash::vk;
+use std::sync::Arc;
+
+use super::{depth_aspects, find_depth_format};
+use super::{VkBuffer, VkContext, VkDevice, VkImage, VkRenderPass};
+
+/// A color + depth render target living outside the swapchain, whose color
+/// image can be read back to the host after rendering.
+pub struct VkOffscreenTarget {
+    device: Arc<VkDevice>,
+    pub render_pass: VkRenderPass,
+    pub color_image: VkImage,
+    pub depth_image: VkImage,
+    pub framebuffer: vk::Framebuffer,
+    pub extent: vk::Extent2D,
+    pub readback: VkBuffer<u8>,
+}
+
+impl VkOffscreenTarget {
+    pub fn new(
+        context: &VkContext,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, String> {
+        let device = context.device();
+        let extent = vk::Extent2D { width, height };
+
+        let render_pass = VkRenderPass::offscreen(context, format)?;
+
+        let color_image = VkImage::new(
+            context,
+            width,
+            height,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let depth_format = find_depth_format(&context.instance, &context.physical_device)?;
+        let depth_image = VkImage::new(
+            context,
+            width,
+            height,
+            depth_format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            depth_aspects(depth_format),
+        )?;
+
+        let attachments = [color_image.view, depth_image.view];
+        let create_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+            render_pass: render_pass.handle,
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            width,
+            height,
+            layers: 1,
+            ..Default::default()
+        };
+
+        let framebuffer = unsafe {
+            device
+                .handle
+                .create_framebuffer(&create_info, None)
+                .map_err(|e| format!("Failed to create offscreen framebuffer: {}", e))?
+        };
+
+        let readback = VkBuffer::host_visible(
+            context,
+            (width * height * 4) as usize,
+            vk::BufferUsageFlags::TRANSFER_DST,
+        )?;
+
+        Ok(Self {
+            device,
+            render_pass,
+            color_image,
+            depth_image,
+            framebuffer,
+            extent,
+            readback,
+        })
+    }
+
+    /// Records the copy of the rendered color image into the readback buffer.
+    /// Must be recorded once the color image is ready for transfer reads.
+    pub fn record_readback(&self, cmd: vk::CommandBuffer) {
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            },
+        };
+
+        unsafe {
+            self.device.handle.cmd_copy_image_to_buffer(
+                cmd,
+                self.color_image.handle,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.readback.handle,
+                &[region],
+            );
+        }
+    }
+
+    /// Returns the pixels as tightly packed RGBA8, converting from the
+    /// target's format when needed.
+    pub fn read_rgba8(&self) -> Result<Vec<u8>, String> {
+        let mut pixels = self
+            .readback
+            .read((self.extent.width * self.extent.height * 4) as usize);
+
+        match self.color_image.format {
+            vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => {}
+            vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            format => return Err(format!("Unsupported capture format: {:?}", format)),
+        }
+
+        Ok(pixels)
+    }
+}
+
+impl Drop for VkOffscreenTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .handle
+                .destroy_framebuffer(self.framebuffer, None);
+        }
+    }
+}
@@ -0,0 +1,1077 @@
+use ash::vk;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr;
+use std::sync::Arc;
+
+use super::{ContextRequirements, VkContext, VkOffscreenTarget};
+use crate::camera::Camera;
+use crate::math::{Mat4, Quat, Vec3, Vec4};
+
+// Minimal bindings to the OpenXR loader, for the calls the viewer makes.
+// Handles are 64-bit on every platform, results are negative on failure.
+
+type XrResult = i32;
+
+const XR_EVENT_UNAVAILABLE: XrResult = 4;
+const XR_INFINITE_DURATION: i64 = i64::MAX;
+
+const TYPE_INSTANCE_CREATE_INFO: i32 = 3;
+const TYPE_SYSTEM_GET_INFO: i32 = 4;
+const TYPE_VIEW_LOCATE_INFO: i32 = 6;
+const TYPE_VIEW: i32 = 7;
+const TYPE_SESSION_CREATE_INFO: i32 = 8;
+const TYPE_SWAPCHAIN_CREATE_INFO: i32 = 9;
+const TYPE_SESSION_BEGIN_INFO: i32 = 10;
+const TYPE_VIEW_STATE: i32 = 11;
+const TYPE_FRAME_END_INFO: i32 = 12;
+const TYPE_EVENT_DATA_BUFFER: i32 = 16;
+const TYPE_EVENT_DATA_SESSION_STATE_CHANGED: i32 = 18;
+const TYPE_FRAME_WAIT_INFO: i32 = 33;
+const TYPE_COMPOSITION_LAYER_PROJECTION: i32 = 35;
+const TYPE_REFERENCE_SPACE_CREATE_INFO: i32 = 37;
+const TYPE_VIEW_CONFIGURATION_VIEW: i32 = 41;
+const TYPE_FRAME_STATE: i32 = 44;
+const TYPE_FRAME_BEGIN_INFO: i32 = 46;
+const TYPE_COMPOSITION_LAYER_PROJECTION_VIEW: i32 = 48;
+const TYPE_SWAPCHAIN_IMAGE_ACQUIRE_INFO: i32 = 55;
+const TYPE_SWAPCHAIN_IMAGE_WAIT_INFO: i32 = 56;
+const TYPE_SWAPCHAIN_IMAGE_RELEASE_INFO: i32 = 57;
+const TYPE_GRAPHICS_BINDING_VULKAN: i32 = 1000025000;
+const TYPE_SWAPCHAIN_IMAGE_VULKAN: i32 = 1000025001;
+const TYPE_GRAPHICS_REQUIREMENTS_VULKAN: i32 = 1000025002;
+
+const FORM_FACTOR_HEAD_MOUNTED_DISPLAY: i32 = 1;
+const VIEW_CONFIGURATION_PRIMARY_STEREO: i32 = 2;
+const REFERENCE_SPACE_LOCAL: i32 = 2;
+const ENVIRONMENT_BLEND_MODE_OPAQUE: i32 = 1;
+
+const SWAPCHAIN_USAGE_COLOR_ATTACHMENT: u64 = 0x1;
+const SWAPCHAIN_USAGE_TRANSFER_DST: u64 = 0x10;
+
+const VIEW_STATE_POSE_VALID: u64 = 0x1 | 0x2;
+
+const SESSION_STATE_READY: i32 = 2;
+const SESSION_STATE_STOPPING: i32 = 6;
+const SESSION_STATE_LOSS_PENDING: i32 = 7;
+const SESSION_STATE_EXITING: i32 = 8;
+
+const fn make_version(major: u64, minor: u64, patch: u64) -> u64 {
+    (major << 48) | (minor << 32) | patch
+}
+
+const VULKAN_ENABLE_EXTENSION: &CStr = c"XR_KHR_vulkan_enable";
+
+#[repr(C)]
+struct ApplicationInfo {
+    application_name: [c_char; 128],
+    application_version: u32,
+    engine_name: [c_char; 128],
+    engine_version: u32,
+    api_version: u64,
+}
+
+#[repr(C)]
+struct InstanceCreateInfo {
+    ty: i32,
+    next: *const c_void,
+    create_flags: u64,
+    application_info: ApplicationInfo,
+    enabled_api_layer_count: u32,
+    enabled_api_layer_names: *const *const c_char,
+    enabled_extension_count: u32,
+    enabled_extension_names: *const *const c_char,
+}
+
+#[repr(C)]
+struct SystemGetInfo {
+    ty: i32,
+    next: *const c_void,
+    form_factor: i32,
+}
+
+#[repr(C)]
+struct GraphicsRequirementsVulkan {
+    ty: i32,
+    next: *mut c_void,
+    min_api_version_supported: u64,
+    max_api_version_supported: u64,
+}
+
+#[repr(C)]
+struct GraphicsBindingVulkan {
+    ty: i32,
+    next: *const c_void,
+    instance: vk::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: vk::Device,
+    queue_family_index: u32,
+    queue_index: u32,
+}
+
+#[repr(C)]
+struct SessionCreateInfo {
+    ty: i32,
+    next: *const c_void,
+    create_flags: u64,
+    system_id: u64,
+}
+
+#[repr(C)]
+struct SessionBeginInfo {
+    ty: i32,
+    next: *const c_void,
+    primary_view_configuration_type: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Quaternionf {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Vector3f {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Posef {
+    orientation: Quaternionf,
+    position: Vector3f,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Fovf {
+    angle_left: f32,
+    angle_right: f32,
+    angle_up: f32,
+    angle_down: f32,
+}
+
+#[repr(C)]
+struct ReferenceSpaceCreateInfo {
+    ty: i32,
+    next: *const c_void,
+    reference_space_type: i32,
+    pose_in_reference_space: Posef,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ViewConfigurationView {
+    ty: i32,
+    next: *mut c_void,
+    recommended_image_rect_width: u32,
+    max_image_rect_width: u32,
+    recommended_image_rect_height: u32,
+    max_image_rect_height: u32,
+    recommended_swapchain_sample_count: u32,
+    max_swapchain_sample_count: u32,
+}
+
+#[repr(C)]
+struct SwapchainCreateInfo {
+    ty: i32,
+    next: *const c_void,
+    create_flags: u64,
+    usage_flags: u64,
+    format: i64,
+    sample_count: u32,
+    width: u32,
+    height: u32,
+    face_count: u32,
+    array_size: u32,
+    mip_count: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SwapchainImageVulkan {
+    ty: i32,
+    next: *mut c_void,
+    image: vk::Image,
+}
+
+#[repr(C)]
+struct SwapchainImageInfo {
+    ty: i32,
+    next: *const c_void,
+}
+
+#[repr(C)]
+struct SwapchainImageWaitInfo {
+    ty: i32,
+    next: *const c_void,
+    timeout: i64,
+}
+
+#[repr(C)]
+struct EventDataBuffer {
+    ty: i32,
+    next: *const c_void,
+    varying: [u8; 4000],
+}
+
+#[repr(C)]
+struct EventDataSessionStateChanged {
+    ty: i32,
+    next: *const c_void,
+    session: u64,
+    state: i32,
+    time: i64,
+}
+
+#[repr(C)]
+struct FrameInfo {
+    ty: i32,
+    next: *const c_void,
+}
+
+#[repr(C)]
+struct FrameState {
+    ty: i32,
+    next: *mut c_void,
+    predicted_display_time: i64,
+    predicted_display_period: i64,
+    should_render: u32,
+}
+
+#[repr(C)]
+struct ViewLocateInfo {
+    ty: i32,
+    next: *const c_void,
+    view_configuration_type: i32,
+    display_time: i64,
+    space: u64,
+}
+
+#[repr(C)]
+struct ViewState {
+    ty: i32,
+    next: *mut c_void,
+    view_state_flags: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct View {
+    ty: i32,
+    next: *mut c_void,
+    pose: Posef,
+    fov: Fovf,
+}
+
+#[repr(C)]
+struct Rect2Di {
+    offset: [i32; 2],
+    extent: [i32; 2],
+}
+
+#[repr(C)]
+struct SwapchainSubImage {
+    swapchain: u64,
+    image_rect: Rect2Di,
+    image_array_index: u32,
+}
+
+#[repr(C)]
+struct CompositionLayerProjectionView {
+    ty: i32,
+    next: *const c_void,
+    pose: Posef,
+    fov: Fovf,
+    sub_image: SwapchainSubImage,
+}
+
+#[repr(C)]
+struct CompositionLayerProjection {
+    ty: i32,
+    next: *const c_void,
+    layer_flags: u64,
+    space: u64,
+    view_count: u32,
+    views: *const CompositionLayerProjectionView,
+}
+
+#[repr(C)]
+struct FrameEndInfo {
+    ty: i32,
+    next: *const c_void,
+    display_time: i64,
+    environment_blend_mode: i32,
+    layer_count: u32,
+    layers: *const *const c_void,
+}
+
+#[link(name = "openxr_loader")]
+extern "system" {
+    fn xrCreateInstance(info: *const InstanceCreateInfo, instance: *mut u64) -> XrResult;
+    fn xrDestroyInstance(instance: u64) -> XrResult;
+    fn xrResultToString(instance: u64, value: XrResult, buffer: *mut c_char) -> XrResult;
+    fn xrGetInstanceProcAddr(
+        instance: u64,
+        name: *const c_char,
+        function: *mut Option<unsafe extern "system" fn()>,
+    ) -> XrResult;
+    fn xrGetSystem(instance: u64, info: *const SystemGetInfo, system: *mut u64) -> XrResult;
+    fn xrEnumerateViewConfigurationViews(
+        instance: u64,
+        system: u64,
+        view_configuration_type: i32,
+        capacity: u32,
+        count: *mut u32,
+        views: *mut ViewConfigurationView,
+    ) -> XrResult;
+    fn xrCreateSession(
+        instance: u64,
+        info: *const SessionCreateInfo,
+        session: *mut u64,
+    ) -> XrResult;
+    fn xrDestroySession(session: u64) -> XrResult;
+    fn xrBeginSession(session: u64, info: *const SessionBeginInfo) -> XrResult;
+    fn xrEndSession(session: u64) -> XrResult;
+    fn xrCreateReferenceSpace(
+        session: u64,
+        info: *const ReferenceSpaceCreateInfo,
+        space: *mut u64,
+    ) -> XrResult;
+    fn xrDestroySpace(space: u64) -> XrResult;
+    fn xrEnumerateSwapchainFormats(
+        session: u64,
+        capacity: u32,
+        count: *mut u32,
+        formats: *mut i64,
+    ) -> XrResult;
+    fn xrCreateSwapchain(
+        session: u64,
+        info: *const SwapchainCreateInfo,
+        swapchain: *mut u64,
+    ) -> XrResult;
+    fn xrDestroySwapchain(swapchain: u64) -> XrResult;
+    fn xrEnumerateSwapchainImages(
+        swapchain: u64,
+        capacity: u32,
+        count: *mut u32,
+        images: *mut SwapchainImageVulkan,
+    ) -> XrResult;
+    fn xrAcquireSwapchainImage(
+        swapchain: u64,
+        info: *const SwapchainImageInfo,
+        index: *mut u32,
+    ) -> XrResult;
+    fn xrWaitSwapchainImage(swapchain: u64, info: *const SwapchainImageWaitInfo) -> XrResult;
+    fn xrReleaseSwapchainImage(swapchain: u64, info: *const SwapchainImageInfo) -> XrResult;
+    fn xrPollEvent(instance: u64, event: *mut EventDataBuffer) -> XrResult;
+    fn xrWaitFrame(session: u64, info: *const FrameInfo, state: *mut FrameState) -> XrResult;
+    fn xrBeginFrame(session: u64, info: *const FrameInfo) -> XrResult;
+    fn xrEndFrame(session: u64, info: *const FrameEndInfo) -> XrResult;
+    fn xrLocateViews(
+        session: u64,
+        info: *const ViewLocateInfo,
+        state: *mut ViewState,
+        capacity: u32,
+        count: *mut u32,
+        views: *mut View,
+    ) -> XrResult;
+}
+
+type ExtensionsFn = unsafe extern "system" fn(u64, u64, u32, *mut u32, *mut c_char) -> XrResult;
+type GraphicsDeviceFn =
+    unsafe extern "system" fn(u64, u64, vk::Instance, *mut vk::PhysicalDevice) -> XrResult;
+type GraphicsRequirementsFn =
+    unsafe extern "system" fn(u64, u64, *mut GraphicsRequirementsVulkan) -> XrResult;
+
+/// Functions of `XR_KHR_vulkan_enable`, which the loader only hands out by
+/// name.
+struct VulkanFunctions {
+    instance_extensions: ExtensionsFn,
+    device_extensions: ExtensionsFn,
+    graphics_device: GraphicsDeviceFn,
+    graphics_requirements: GraphicsRequirementsFn,
+}
+
+impl VulkanFunctions {
+    fn load(instance: u64) -> Result<Self, String> {
+        Ok(Self {
+            instance_extensions: runtime_function(instance, c"xrGetVulkanInstanceExtensionsKHR")?,
+            device_extensions: runtime_function(instance, c"xrGetVulkanDeviceExtensionsKHR")?,
+            graphics_device: runtime_function(instance, c"xrGetVulkanGraphicsDeviceKHR")?,
+            graphics_requirements: runtime_function(
+                instance,
+                c"xrGetVulkanGraphicsRequirementsKHR",
+            )?,
+        })
+    }
+}
+
+/// An OpenXR instance and the headset it drives. It comes before the Vulkan
+/// context, which must enable the extensions the runtime asks for.
+pub struct XrRuntime {
+    instance: u64,
+    system: u64,
+    vulkan: VulkanFunctions,
+}
+
+impl XrRuntime {
+    /// Connects to the active OpenXR runtime and finds its headset.
+    pub fn new() -> Result<Self, String> {
+        let mut application_info = ApplicationInfo {
+            application_name: [0; 128],
+            application_version: 1,
+            engine_name: [0; 128],
+            engine_version: 1,
+            api_version: make_version(1, 0, 0),
+        };
+        for (dst, src) in application_info.application_name.iter_mut().zip(b"scop") {
+            *dst = *src as c_char;
+        }
+        application_info.engine_name = application_info.application_name;
+
+        let extensions = [VULKAN_ENABLE_EXTENSION.as_ptr()];
+        let create_info = InstanceCreateInfo {
+            ty: TYPE_INSTANCE_CREATE_INFO,
+            next: ptr::null(),
+            create_flags: 0,
+            application_info,
+            enabled_api_layer_count: 0,
+            enabled_api_layer_names: ptr::null(),
+            enabled_extension_count: extensions.len() as u32,
+            enabled_extension_names: extensions.as_ptr(),
+        };
+        let mut instance = 0;
+        check(
+            0,
+            unsafe { xrCreateInstance(&create_info, &mut instance) },
+            "create the OpenXR instance",
+        )?;
+
+        let vulkan = VulkanFunctions::load(instance).inspect_err(|_| unsafe {
+            xrDestroyInstance(instance);
+        })?;
+        // Destroys the instance should the headset be missing.
+        let mut runtime = Self {
+            instance,
+            system: 0,
+            vulkan,
+        };
+
+        let system_info = SystemGetInfo {
+            ty: TYPE_SYSTEM_GET_INFO,
+            next: ptr::null(),
+            form_factor: FORM_FACTOR_HEAD_MOUNTED_DISPLAY,
+        };
+        let mut system = 0;
+        runtime.check(
+            unsafe { xrGetSystem(instance, &system_info, &mut system) },
+            "find a headset",
+        )?;
+        runtime.system = system;
+        Ok(runtime)
+    }
+
+    /// Extensions the Vulkan context needs for the runtime to use its
+    /// device. Fails when the runtime needs a newer Vulkan than the
+    /// renderer's.
+    pub fn requirements(&self) -> Result<ContextRequirements, String> {
+        let mut graphics = GraphicsRequirementsVulkan {
+            ty: TYPE_GRAPHICS_REQUIREMENTS_VULKAN,
+            next: ptr::null_mut(),
+            min_api_version_supported: 0,
+            max_api_version_supported: 0,
+        };
+        self.check(
+            unsafe {
+                (self.vulkan.graphics_requirements)(self.instance, self.system, &mut graphics)
+            },
+            "get the runtime's Vulkan requirements",
+        )?;
+        // Patch versions don't matter.
+        if graphics.min_api_version_supported >> 32 > make_version(1, 3, 0) >> 32 {
+            return Err(format!(
+                "The OpenXR runtime needs Vulkan {}.{}",
+                graphics.min_api_version_supported >> 48,
+                (graphics.min_api_version_supported >> 32) & 0xffff
+            ));
+        }
+
+        Ok(ContextRequirements {
+            gpu: None,
+            instance_extensions: self.extensions(self.vulkan.instance_extensions)?,
+            device_extensions: self.extensions(self.vulkan.device_extensions)?,
+        })
+    }
+
+    /// Names of a space-separated extension list the runtime writes.
+    fn extensions(&self, list: ExtensionsFn) -> Result<Vec<CString>, String> {
+        let mut count = 0;
+        self.check(
+            unsafe { list(self.instance, self.system, 0, &mut count, ptr::null_mut()) },
+            "list the Vulkan extensions",
+        )?;
+        let mut buffer = vec![0u8; count as usize];
+        self.check(
+            unsafe {
+                list(
+                    self.instance,
+                    self.system,
+                    count,
+                    &mut count,
+                    buffer.as_mut_ptr() as *mut c_char,
+                )
+            },
+            "list the Vulkan extensions",
+        )?;
+
+        let names = CStr::from_bytes_until_nul(&buffer)
+            .map_err(|e| format!("Invalid Vulkan extension list: {}", e))?;
+        names
+            .to_string_lossy()
+            .split(' ')
+            .filter(|name| !name.is_empty())
+            .map(|name| CString::new(name).map_err(|e| e.to_string()))
+            .collect()
+    }
+
+    fn check(&self, result: XrResult, action: &str) -> Result<(), String> {
+        check(self.instance, result, action)
+    }
+}
+
+impl Drop for XrRuntime {
+    fn drop(&mut self) {
+        unsafe {
+            xrDestroyInstance(self.instance);
+        }
+    }
+}
+
+/// Fails with the name of `result` when it is an error.
+fn check(instance: u64, result: XrResult, action: &str) -> Result<(), String> {
+    if result >= 0 {
+        return Ok(());
+    }
+    let mut buffer = [0 as c_char; 64];
+    let name = if instance != 0
+        && unsafe { xrResultToString(instance, result, buffer.as_mut_ptr()) } >= 0
+    {
+        unsafe { CStr::from_ptr(buffer.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        result.to_string()
+    };
+    Err(format!("Failed to {}: {}", action, name))
+}
+
+/// Loads the runtime function `name`, of type `F`.
+fn runtime_function<F>(instance: u64, name: &CStr) -> Result<F, String> {
+    let mut function = None;
+    check(
+        instance,
+        unsafe { xrGetInstanceProcAddr(instance, name.as_ptr(), &mut function) },
+        &format!("load {}", name.to_string_lossy()),
+    )?;
+    let function = function.ok_or_else(|| format!("Missing {}", name.to_string_lossy()))?;
+    // SAFETY: `F` is the function pointer type of `name` in the spec.
+    Ok(unsafe { std::mem::transmute_copy::<unsafe extern "system" fn(), F>(&function) })
+}
+
+/// Swapchain of one eye, and the target its images are blitted from.
+struct XrEye {
+    swapchain: u64,
+    images: Vec<vk::Image>,
+    target: VkOffscreenTarget,
+}
+
+/// A running headset session, rendering the scene to each eye from the pose
+/// the runtime predicts for the frame.
+// Fields drop in declaration order: the eye targets go before the runtime
+// and the context; the session and its swapchains are destroyed in `drop`.
+pub struct XrSession {
+    eyes: Vec<XrEye>,
+    space: u64,
+    session: u64,
+    /// Whether the session was begun, between the runtime's ready and
+    /// stopping states.
+    running: bool,
+    runtime: XrRuntime,
+    context: Arc<VkContext>,
+}
+
+impl XrSession {
+    /// Starts a session on the device of `context`, created with the
+    /// runtime's `requirements`. Eye images are of `format` when the runtime
+    /// offers it, like the offscreen targets rendered into.
+    pub fn new(
+        runtime: XrRuntime,
+        context: Arc<VkContext>,
+        format: vk::Format,
+    ) -> Result<Self, String> {
+        let mut physical_device = vk::PhysicalDevice::null();
+        runtime.check(
+            unsafe {
+                (runtime.vulkan.graphics_device)(
+                    runtime.instance,
+                    runtime.system,
+                    context.instance.handle.handle(),
+                    &mut physical_device,
+                )
+            },
+            "get the headset's GPU",
+        )?;
+        if physical_device != context.physical_device.handle {
+            return Err("The headset is driven by another GPU than the window's".to_string());
+        }
+
+        let binding = GraphicsBindingVulkan {
+            ty: TYPE_GRAPHICS_BINDING_VULKAN,
+            next: ptr::null(),
+            instance: context.instance.handle.handle(),
+            physical_device,
+            device: context.device.handle.handle(),
+            queue_family_index: context.graphics_family(),
+            queue_index: 0,
+        };
+        let session_info = SessionCreateInfo {
+            ty: TYPE_SESSION_CREATE_INFO,
+            next: &binding as *const _ as *const c_void,
+            create_flags: 0,
+            system_id: runtime.system,
+        };
+        let mut session = 0;
+        runtime.check(
+            unsafe { xrCreateSession(runtime.instance, &session_info, &mut session) },
+            "create the headset session",
+        )?;
+
+        let mut xr = Self {
+            eyes: Vec::new(),
+            space: 0,
+            session,
+            running: false,
+            runtime,
+            context,
+        };
+
+        let space_info = ReferenceSpaceCreateInfo {
+            ty: TYPE_REFERENCE_SPACE_CREATE_INFO,
+            next: ptr::null(),
+            reference_space_type: REFERENCE_SPACE_LOCAL,
+            pose_in_reference_space: Posef {
+                orientation: Quaternionf {
+                    w: 1.,
+                    ..Default::default()
+                },
+                position: Vector3f::default(),
+            },
+        };
+        let mut space = 0;
+        xr.check(
+            unsafe { xrCreateReferenceSpace(session, &space_info, &mut space) },
+            "create the headset space",
+        )?;
+        xr.space = space;
+
+        let format = xr.swapchain_format(format)?;
+        for view in xr.view_configuration()? {
+            let (width, height) = (
+                view.recommended_image_rect_width,
+                view.recommended_image_rect_height,
+            );
+            let swapchain_info = SwapchainCreateInfo {
+                ty: TYPE_SWAPCHAIN_CREATE_INFO,
+                next: ptr::null(),
+                create_flags: 0,
+                usage_flags: SWAPCHAIN_USAGE_COLOR_ATTACHMENT | SWAPCHAIN_USAGE_TRANSFER_DST,
+                format: format.as_raw() as i64,
+                sample_count: 1,
+                width,
+                height,
+                face_count: 1,
+                array_size: 1,
+                mip_count: 1,
+            };
+            let mut swapchain = 0;
+            xr.check(
+                unsafe { xrCreateSwapchain(session, &swapchain_info, &mut swapchain) },
+                "create an eye swapchain",
+            )?;
+            // Destroyed with the session should what follows fail.
+            xr.eyes.push(XrEye {
+                swapchain,
+                images: Vec::new(),
+                target: VkOffscreenTarget::new(&xr.context, format, width, height)?,
+            });
+
+            let mut count = 0;
+            xr.check(
+                unsafe { xrEnumerateSwapchainImages(swapchain, 0, &mut count, ptr::null_mut()) },
+                "list the eye images",
+            )?;
+            let empty = SwapchainImageVulkan {
+                ty: TYPE_SWAPCHAIN_IMAGE_VULKAN,
+                next: ptr::null_mut(),
+                image: vk::Image::null(),
+            };
+            let mut images = vec![empty; count as usize];
+            xr.check(
+                unsafe {
+                    xrEnumerateSwapchainImages(swapchain, count, &mut count, images.as_mut_ptr())
+                },
+                "list the eye images",
+            )?;
+            if let Some(eye) = xr.eyes.last_mut() {
+                eye.images = images.iter().map(|image| image.image).collect();
+            }
+        }
+        Ok(xr)
+    }
+
+    /// `preferred` when the runtime takes it, else its first sRGB format.
+    fn swapchain_format(&self, preferred: vk::Format) -> Result<vk::Format, String> {
+        let mut count = 0;
+        self.check(
+            unsafe { xrEnumerateSwapchainFormats(self.session, 0, &mut count, ptr::null_mut()) },
+            "list the headset formats",
+        )?;
+        let mut formats = vec![0; count as usize];
+        self.check(
+            unsafe {
+                xrEnumerateSwapchainFormats(self.session, count, &mut count, formats.as_mut_ptr())
+            },
+            "list the headset formats",
+        )?;
+
+        let formats: Vec<vk::Format> = formats
+            .iter()
+            .map(|&format| vk::Format::from_raw(format as i32))
+            .collect();
+        if formats.contains(&preferred) {
+            return Ok(preferred);
+        }
+        [vk::Format::R8G8B8A8_SRGB, vk::Format::B8G8R8A8_SRGB]
+            .into_iter()
+            .find(|format| formats.contains(format))
+            .ok_or_else(|| "The headset takes none of the renderer's formats".to_string())
+    }
+
+    /// Views of the headset, one per eye.
+    fn view_configuration(&self) -> Result<Vec<ViewConfigurationView>, String> {
+        let (instance, system) = (self.runtime.instance, self.runtime.system);
+        let mut count = 0;
+        self.check(
+            unsafe {
+                xrEnumerateViewConfigurationViews(
+                    instance,
+                    system,
+                    VIEW_CONFIGURATION_PRIMARY_STEREO,
+                    0,
+                    &mut count,
+                    ptr::null_mut(),
+                )
+            },
+            "list the headset views",
+        )?;
+        let empty = ViewConfigurationView {
+            ty: TYPE_VIEW_CONFIGURATION_VIEW,
+            next: ptr::null_mut(),
+            recommended_image_rect_width: 0,
+            max_image_rect_width: 0,
+            recommended_image_rect_height: 0,
+            max_image_rect_height: 0,
+            recommended_swapchain_sample_count: 0,
+            max_swapchain_sample_count: 0,
+        };
+        let mut views = vec![empty; count as usize];
+        self.check(
+            unsafe {
+                xrEnumerateViewConfigurationViews(
+                    instance,
+                    system,
+                    VIEW_CONFIGURATION_PRIMARY_STEREO,
+                    count,
+                    &mut count,
+                    views.as_mut_ptr(),
+                )
+            },
+            "list the headset views",
+        )?;
+        Ok(views)
+    }
+
+    /// Handles the runtime's events, beginning and ending the session when
+    /// the headset asks. False once the session is over.
+    fn poll_events(&mut self) -> Result<bool, String> {
+        loop {
+            let mut event = EventDataBuffer {
+                ty: TYPE_EVENT_DATA_BUFFER,
+                next: ptr::null(),
+                varying: [0; 4000],
+            };
+            let result = unsafe { xrPollEvent(self.runtime.instance, &mut event) };
+            if result == XR_EVENT_UNAVAILABLE {
+                return Ok(true);
+            }
+            self.check(result, "poll the headset events")?;
+            if event.ty != TYPE_EVENT_DATA_SESSION_STATE_CHANGED {
+                continue;
+            }
+
+            // SAFETY: the runtime wrote an event of that type in the buffer.
+            let changed = unsafe {
+                &*(&event as *const EventDataBuffer as *const EventDataSessionStateChanged)
+            };
+            match changed.state {
+                SESSION_STATE_READY => {
+                    let begin_info = SessionBeginInfo {
+                        ty: TYPE_SESSION_BEGIN_INFO,
+                        next: ptr::null(),
+                        primary_view_configuration_type: VIEW_CONFIGURATION_PRIMARY_STEREO,
+                    };
+                    self.check(
+                        unsafe { xrBeginSession(self.session, &begin_info) },
+                        "begin the headset session",
+                    )?;
+                    self.running = true;
+                }
+                SESSION_STATE_STOPPING => {
+                    self.running = false;
+                    self.check(
+                        unsafe { xrEndSession(self.session) },
+                        "end the headset session",
+                    )?;
+                }
+                SESSION_STATE_LOSS_PENDING | SESSION_STATE_EXITING => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+
+    /// Renders a frame to the headset when the runtime wants one. Eyes look
+    /// from `anchor`, the transform placing the headset's space in the
+    /// world, and see `camera`'s clip planes. `render` draws an eye's camera
+    /// into its target and blits it to a swapchain image, see
+    /// `Renderer::render_to_image`. False once the session is over.
+    pub fn frame(
+        &mut self,
+        anchor: Mat4,
+        camera: &Camera,
+        mut render: impl FnMut(&Camera, &VkOffscreenTarget, vk::Image) -> Result<(), String>,
+    ) -> Result<bool, String> {
+        if !self.poll_events()? {
+            return Ok(false);
+        }
+        if !self.running {
+            return Ok(true);
+        }
+
+        let mut state = FrameState {
+            ty: TYPE_FRAME_STATE,
+            next: ptr::null_mut(),
+            predicted_display_time: 0,
+            predicted_display_period: 0,
+            should_render: 0,
+        };
+        let frame_info = |ty| FrameInfo {
+            ty,
+            next: ptr::null(),
+        };
+        self.check(
+            unsafe { xrWaitFrame(self.session, &frame_info(TYPE_FRAME_WAIT_INFO), &mut state) },
+            "wait for a headset frame",
+        )?;
+        self.check(
+            unsafe { xrBeginFrame(self.session, &frame_info(TYPE_FRAME_BEGIN_INFO)) },
+            "begin a headset frame",
+        )?;
+
+        // The frame ends even when rendering fails, with no layer then.
+        let views = if state.should_render != 0 {
+            self.render_views(anchor, camera, state.predicted_display_time, &mut render)
+        } else {
+            Ok(Vec::new())
+        };
+        let projection = CompositionLayerProjection {
+            ty: TYPE_COMPOSITION_LAYER_PROJECTION,
+            next: ptr::null(),
+            layer_flags: 0,
+            space: self.space,
+            view_count: views.as_ref().map_or(0, |views| views.len() as u32),
+            views: views.as_ref().map_or(ptr::null(), |views| views.as_ptr()),
+        };
+        let layers = [&projection as *const CompositionLayerProjection as *const c_void];
+        let end_info = FrameEndInfo {
+            ty: TYPE_FRAME_END_INFO,
+            next: ptr::null(),
+            display_time: state.predicted_display_time,
+            environment_blend_mode: ENVIRONMENT_BLEND_MODE_OPAQUE,
+            layer_count: (projection.view_count > 0) as u32,
+            layers: layers.as_ptr(),
+        };
+        self.check(
+            unsafe { xrEndFrame(self.session, &end_info) },
+            "end a headset frame",
+        )?;
+        views.map(|_| true)
+    }
+
+    /// Renders each eye at `display_time`, returning the views to compose,
+    /// none when the headset isn't tracked.
+    fn render_views(
+        &self,
+        anchor: Mat4,
+        camera: &Camera,
+        display_time: i64,
+        render: &mut impl FnMut(&Camera, &VkOffscreenTarget, vk::Image) -> Result<(), String>,
+    ) -> Result<Vec<CompositionLayerProjectionView>, String> {
+        let locate_info = ViewLocateInfo {
+            ty: TYPE_VIEW_LOCATE_INFO,
+            next: ptr::null(),
+            view_configuration_type: VIEW_CONFIGURATION_PRIMARY_STEREO,
+            display_time,
+            space: self.space,
+        };
+        let mut view_state = ViewState {
+            ty: TYPE_VIEW_STATE,
+            next: ptr::null_mut(),
+            view_state_flags: 0,
+        };
+        let empty = View {
+            ty: TYPE_VIEW,
+            next: ptr::null_mut(),
+            pose: Posef::default(),
+            fov: Fovf::default(),
+        };
+        let mut views = vec![empty; self.eyes.len()];
+        let mut count = 0;
+        self.check(
+            unsafe {
+                xrLocateViews(
+                    self.session,
+                    &locate_info,
+                    &mut view_state,
+                    views.len() as u32,
+                    &mut count,
+                    views.as_mut_ptr(),
+                )
+            },
+            "locate the headset views",
+        )?;
+        if view_state.view_state_flags & VIEW_STATE_POSE_VALID != VIEW_STATE_POSE_VALID {
+            return Ok(Vec::new());
+        }
+
+        let mut layers = Vec::with_capacity(self.eyes.len());
+        for (eye, view) in self.eyes.iter().zip(&views) {
+            let mut index = 0;
+            let acquire_info = SwapchainImageInfo {
+                ty: TYPE_SWAPCHAIN_IMAGE_ACQUIRE_INFO,
+                next: ptr::null(),
+            };
+            self.check(
+                unsafe { xrAcquireSwapchainImage(eye.swapchain, &acquire_info, &mut index) },
+                "acquire an eye image",
+            )?;
+            let wait_info = SwapchainImageWaitInfo {
+                ty: TYPE_SWAPCHAIN_IMAGE_WAIT_INFO,
+                next: ptr::null(),
+                timeout: XR_INFINITE_DURATION,
+            };
+            self.check(
+                unsafe { xrWaitSwapchainImage(eye.swapchain, &wait_info) },
+                "wait for an eye image",
+            )?;
+
+            let image = eye.images.get(index as usize).copied();
+            let rendered = image
+                .ok_or_else(|| "The headset acquired an unknown image".to_string())
+                .and_then(|image| render(&eye_camera(camera, anchor, view), &eye.target, image));
+            let release_info = SwapchainImageInfo {
+                ty: TYPE_SWAPCHAIN_IMAGE_RELEASE_INFO,
+                next: ptr::null(),
+            };
+            self.check(
+                unsafe { xrReleaseSwapchainImage(eye.swapchain, &release_info) },
+                "release an eye image",
+            )?;
+            rendered?;
+
+            layers.push(CompositionLayerProjectionView {
+                ty: TYPE_COMPOSITION_LAYER_PROJECTION_VIEW,
+                next: ptr::null(),
+                pose: view.pose,
+                fov: view.fov,
+                sub_image: SwapchainSubImage {
+                    swapchain: eye.swapchain,
+                    image_rect: Rect2Di {
+                        offset: [0, 0],
+                        extent: [
+                            eye.target.extent.width as i32,
+                            eye.target.extent.height as i32,
+                        ],
+                    },
+                    image_array_index: 0,
+                },
+            });
+        }
+        Ok(layers)
+    }
+
+    fn check(&self, result: XrResult, action: &str) -> Result<(), String> {
+        self.runtime.check(result, action)
+    }
+}
+
+impl Drop for XrSession {
+    fn drop(&mut self) {
+        self.context.device.wait_idle();
+        unsafe {
+            for eye in &self.eyes {
+                xrDestroySwapchain(eye.swapchain);
+            }
+            if self.space != 0 {
+                xrDestroySpace(self.space);
+            }
+            xrDestroySession(self.session);
+        }
+    }
+}
+
+/// Camera of an eye at the pose of `view`, in the headset space placed by
+/// `anchor`. Its view matrix keeps world units, so the clip planes of
+/// `camera` apply.
+fn eye_camera(camera: &Camera, anchor: Mat4, view: &View) -> Camera {
+    let (p, q) = (view.pose.position, view.pose.orientation);
+    let world_from_eye =
+        anchor.translate(Vec3::new(p.x, p.y, p.z)) * Quat::new(q.x, q.y, q.z, q.w).to_mat4();
+    // The anchor's scale, as the length of any of its axes.
+    let scale = anchor.x_axis.xyz().length();
+    let eye_from_world = world_from_eye.inverse().unwrap_or_else(Mat4::identity);
+    let view_matrix = Mat4::identity().scale(Vec3::splat(scale)) * eye_from_world;
+
+    let fov = view.fov;
+    let projection = Mat4::off_axis_projection(
+        (
+            fov.angle_left.tan(),
+            fov.angle_right.tan(),
+            fov.angle_up.tan(),
+            fov.angle_down.tan(),
+        ),
+        camera.near(),
+        camera.far(),
+    );
+
+    let position = world_from_eye * Vec4::new(0., 0., 0., 1.);
+    let forward = world_from_eye * Vec4::new(0., 0., -1., 0.);
+    let mut eye = camera.clone();
+    eye.set_eye(position.xyz(), forward.xyz(), view_matrix, projection);
+    eye
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::thread;
+
+use image::RgbaImage;
+
+use super::{linear_to_srgb8, load_rgba8, sky_color, srgb8_to_linear, ResourcesManager};
+use crate::camera::Camera;
+use crate::math::{Mat4, Ray, Vec2, Vec3, Vec4};
+use crate::scene::{Bvh, Material, Mesh, Scene, TriangleRef};
+
+/// Bounces before a path is cut short.
+const MAX_BOUNCES: u32 = 8;
+
+/// Bounces after which paths are randomly terminated by their throughput.
+const ROULETTE_BOUNCES: u32 = 3;
+
+/// Offset along the normal for rays leaving a surface, in world units.
+const RAY_BIAS: f32 = 1e-3;
+
+/// CPU path tracer over the uploaded meshes' CPU copies, as a reference for
+/// the real-time shading. Lighting follows the raster conventions: the point
+/// light has no falloff and diffuse surfaces reflect `kd` of it. The sky is
+/// the one reflected by shiny materials, and only `map_kd` textures are read.
+/// Skinned meshes are traced in their bind pose.
+pub struct PathTracer<'a> {
+    scene: &'a Scene,
+    instances: Vec<Instance<'a>>,
+    default_material: Material,
+    textures: HashMap<String, RgbaImage>,
+}
+
+struct Instance<'a> {
+    geometry: &'a Mesh,
+    bvh: &'a Bvh,
+    to_world: Mat4,
+    to_local: Mat4,
+    /// Inverse transpose of `to_world`, for normals.
+    normal_matrix: Mat4,
+}
+
+struct Hit<'a> {
+    position: Vec3,
+    /// Shading normal, facing the incoming ray.
+    normal: Vec3,
+    uv: Vec2,
+    material: &'a Material,
+}
+
+impl<'a> PathTracer<'a> {
+    pub fn new(scene: &'a Scene, resources: &'a ResourcesManager) -> Self {
+        let mut instances = Vec::new();
+        let mut textures = HashMap::new();
+        for object in &scene.objects {
+            let mesh = resources.get_mesh(object.id());
+            let to_world = object.transform();
+            let Some(to_local) = to_world.inverse() else {
+                continue;
+            };
+
+            for material in &mesh.geometry.materials {
+                let path = &material.map_kd;
+                if path.is_empty() || textures.contains_key(path) {
+                    continue;
+                }
+                match load_rgba8(path) {
+                    Ok(image) => {
+                        textures.insert(path.clone(), image);
+                    }
+                    Err(e) => eprintln!("Failed to load texture '{}': {}", path, e),
+                }
+            }
+
+            instances.push(Instance {
+                geometry: &mesh.geometry,
+                bvh: &mesh.bvh,
+                to_world,
+                to_local,
+                normal_matrix: to_local.transpose(),
+            });
+        }
+
+        Self {
+            scene,
+            instances,
+            default_material: Material::default(),
+            textures,
+        }
+    }
+
+    /// Traces `samples` paths per pixel of a `width` x `height` image seen
+    /// from `camera`, one sample per pixel at a time, calling `progress` with
+    /// the samples done after each pass. Returns sRGB RGBA8 pixels.
+    pub fn render(
+        &self,
+        camera: &Camera,
+        (width, height): (u32, u32),
+        samples: u32,
+        mut progress: impl FnMut(u32),
+    ) -> Vec<u8> {
+        let samples = samples.max(1);
+        let threads = thread::available_parallelism().map_or(1, |n| n.get()) as u32;
+        let rows = height.div_ceil(threads).max(1);
+        let mut accumulated = vec![Vec3::ZERO; (width * height) as usize];
+
+        for pass in 0..samples {
+            thread::scope(|scope| {
+                for (band, pixels) in accumulated.chunks_mut((rows * width) as usize).enumerate() {
+                    scope.spawn(move || {
+                        for (i, pixel) in pixels.iter_mut().enumerate() {
+                            let index = band * (rows * width) as usize + i;
+                            let (x, y) = (index as u32 % width, index as u32 / width);
+                            let mut rng = Rng::new(index as u32, pass);
+                            let jitter = Vec2::new(x as f32 + rng.next(), y as f32 + rng.next());
+                            let ray = camera.ray(jitter, width as f32, height as f32);
+                            *pixel += self.radiance(ray, &mut rng);
+                        }
+                    });
+                }
+            });
+            progress(pass + 1);
+        }
+
+        let scale = 1. / samples as f32;
+        accumulated
+            .iter()
+            .flat_map(|&color| {
+                let color = color * scale;
+                [
+                    linear_to_srgb8(color.x),
+                    linear_to_srgb8(color.y),
+                    linear_to_srgb8(color.z),
+                    255,
+                ]
+            })
+            .collect()
+    }
+
+    /// Light arriving along `ray`.
+    fn radiance(&self, mut ray: Ray, rng: &mut Rng) -> Vec3 {
+        let light = &self.scene.light;
+        let light_color = light.color * light.intensity;
+        let mut throughput = Vec3::ONE;
+        let mut radiance = Vec3::ZERO;
+
+        for bounce in 0..MAX_BOUNCES {
+            let Some(hit) = self.intersect(&ray) else {
+                radiance += throughput * sky_color(ray.direction);
+                break;
+            };
+            let material = hit.material;
+
+            // Partially transparent surfaces let a share of the paths through.
+            if material.dissolve < 1. && rng.next() >= material.dissolve {
+                ray = Ray::new(hit.position + ray.direction * RAY_BIAS, ray.direction);
+                continue;
+            }
+
+            let view = -ray.direction;
+            let normal = hit.normal;
+            let albedo = material.kd * self.diffuse_texture(material, hit.uv);
+            let specular = if material.illum >= 2 {
+                material.ks
+            } else {
+                Vec3::ZERO
+            };
+            let origin = hit.position + normal * RAY_BIAS;
+
+            let to_light = light.position - hit.position;
+            let distance = to_light.length();
+            let light_dir = to_light / distance.max(f32::EPSILON);
+            let cosine = normal.dot(light_dir);
+            if cosine > 0. && !self.occluded(&Ray::new(origin, light_dir), distance) {
+                let mirror = reflect(-light_dir, normal);
+                let highlight = view.dot(mirror).max(0.).powf(material.ns.max(1.));
+                radiance += throughput * light_color * (albedo * cosine + specular * highlight);
+            }
+
+            // Pick the diffuse or the glossy lobe in proportion to their
+            // reflectance.
+            let diffuse_weight = luminance(albedo);
+            let specular_weight = luminance(specular);
+            let total = diffuse_weight + specular_weight;
+            if total <= 0. {
+                break;
+            }
+
+            let direction = if rng.next() * total < diffuse_weight {
+                throughput = throughput * albedo * (total / diffuse_weight);
+                cosine_sample(normal, rng.next(), rng.next())
+            } else {
+                throughput = throughput * specular * (total / specular_weight);
+                let lobe = phong_sample(reflect(ray.direction, normal), material.ns, rng);
+                if lobe.dot(normal) <= 0. {
+                    break;
+                }
+                lobe
+            };
+            ray = Ray::new(origin, direction);
+
+            if bounce >= ROULETTE_BOUNCES {
+                let survival = luminance(throughput).clamp(0.05, 1.);
+                if rng.next() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+
+        radiance
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let mut closest: Option<(&Instance, TriangleRef, f32)> = None;
+        for instance in &self.instances {
+            let local = ray.transform(&instance.to_local);
+            let Some((triangle, distance)) = instance.bvh.intersect(instance.geometry, &local)
+            else {
+                continue;
+            };
+            if closest.is_none_or(|(_, _, best)| distance < best) {
+                closest = Some((instance, triangle, distance));
+            }
+        }
+
+        let (instance, triangle, distance) = closest?;
+        let geometry = instance.geometry;
+        let group = &geometry.groups[triangle.group as usize];
+        let first = triangle.triangle as usize * 3;
+        let [a, b, c] = [0, 1, 2].map(|i| group.vertices[group.indices[first + i] as usize]);
+
+        // Barycentrics of the hit point, from the local-space position.
+        let local = ray.transform(&instance.to_local);
+        let point = local.origin + local.direction * distance;
+        let (v0, v1, v2) = (
+            b.position - a.position,
+            c.position - a.position,
+            point - a.position,
+        );
+        let face = v0.cross(v1);
+        let area = face.length_squared().max(f32::EPSILON);
+        let w1 = v2.cross(v1).dot(face) / area;
+        let w2 = v0.cross(v2).dot(face) / area;
+        let w0 = 1. - w1 - w2;
+
+        let to_world = |n: Vec3| (instance.normal_matrix * Vec4::from_vec3(n, 0.)).xyz();
+        let face_normal = to_world(face).normalize();
+        let mut normal = to_world(a.normal * w0 + b.normal * w1 + c.normal * w2)
+            .try_normalize()
+            .unwrap_or(face_normal);
+        if face_normal.dot(ray.direction) > 0. {
+            normal = -normal;
+        }
+
+        let uv = a.uv * w0 + b.uv * w1 + c.uv * w2;
+        let material = group
+            .material
+            .map_or(&self.default_material, |m| &geometry.materials[m]);
+
+        Some(Hit {
+            position: (instance.to_world * Vec4::from_vec3(point, 1.)).xyz(),
+            normal,
+            uv,
+            material,
+        })
+    }
+
+    fn occluded(&self, ray: &Ray, max_distance: f32) -> bool {
+        self.instances.iter().any(|instance| {
+            let local = ray.transform(&instance.to_local);
+            instance
+                .bvh
+                .occluded(instance.geometry, &local, max_distance)
+        })
+    }
+
+    /// Linear `map_kd` color at `uv`, repeating, white without a texture.
+    fn diffuse_texture(&self, material: &Material, uv: Vec2) -> Vec3 {
+        let Some(texture) = self.textures.get(&material.map_kd) else {
+            return Vec3::ONE;
+        };
+        let (width, height) = texture.dimensions();
+        let x = (uv.x.rem_euclid(1.) * width as f32) as u32;
+        let y = (uv.y.rem_euclid(1.) * height as f32) as u32;
+        let texel = texture.get_pixel(x.min(width - 1), y.min(height - 1));
+        Vec3::new(
+            srgb8_to_linear(texel[0]),
+            srgb8_to_linear(texel[1]),
+            srgb8_to_linear(texel[2]),
+        )
+    }
+}
+
+/// Xorshift generator seeded per pixel and pass.
+struct Rng(u32);
+
+impl Rng {
+    fn new(pixel: u32, pass: u32) -> Self {
+        Self((pixel.wrapping_mul(0x9e37_79b9) ^ pass.wrapping_mul(0x85eb_ca6b)) | 1)
+    }
+
+    /// Uniform in [0, 1).
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+fn reflect(direction: Vec3, normal: Vec3) -> Vec3 {
+    direction - normal * (2. * direction.dot(normal))
+}
+
+/// Orthonormal tangents around `normal`.
+fn basis(normal: Vec3) -> (Vec3, Vec3) {
+    let helper = if normal.x.abs() < 0.9 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    let tangent = helper.cross(normal).normalize();
+    (tangent, normal.cross(tangent))
+}
+
+/// Direction around `normal` with a cosine-weighted density.
+fn cosine_sample(normal: Vec3, u: f32, v: f32) -> Vec3 {
+    let (tangent, bitangent) = basis(normal);
+    let (r, phi) = (u.sqrt(), TAU * v);
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1. - u).sqrt()
+}
+
+/// Direction around `axis` with a density proportional to the Phong lobe of
+/// `exponent`.
+fn phong_sample(axis: Vec3, exponent: f32, rng: &mut Rng) -> Vec3 {
+    let (tangent, bitangent) = basis(axis);
+    let cos_theta = rng.next().powf(1. / (exponent.max(1.) + 1.));
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let phi = TAU * rng.next();
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta
+}
@@ -1,5 +1,6 @@
 use std::collections::{BTreeMap, HashSet};
 use std::ffi::CStr;
+use std::str::FromStr;
 
 use ash::{khr, vk, Instance};
 
@@ -7,32 +8,117 @@ use super::query_swapchain_support;
 use super::DEVICE_EXTENSIONS;
 use super::{QueueFamiliesIndices, SwapChainSupportDetails, VkInstance, VkSurface};
 
+/// GPU the user picked, by its position in the Vulkan device list or by
+/// part of its name, case-insensitively.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GpuSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl GpuSelector {
+    pub const ENV: &'static str = "SCOP_GPU";
+
+    /// GPU named in `SCOP_GPU`, none when it is unset or empty.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var(Self::ENV) {
+            Ok(value) if !value.trim().is_empty() => value
+                .parse()
+                .map(Some)
+                .map_err(|e| format!("Invalid {}: {}", Self::ENV, e)),
+            _ => Ok(None),
+        }
+    }
+
+    fn matches(&self, index: usize, name: &str) -> bool {
+        match self {
+            GpuSelector::Index(wanted) => *wanted == index,
+            GpuSelector::Name(part) => name.to_lowercase().contains(part),
+        }
+    }
+}
+
+impl FromStr for GpuSelector {
+    type Err = String;
+
+    /// Parses an index such as `1`, or part of a name such as `nvidia`.
+    fn from_str(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("Empty GPU name".to_string());
+        }
+        Ok(match value.parse() {
+            Ok(index) => GpuSelector::Index(index),
+            Err(_) => GpuSelector::Name(value.to_lowercase()),
+        })
+    }
+}
+
+impl std::fmt::Display for GpuSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GpuSelector::Index(index) => write!(f, "#{}", index),
+            GpuSelector::Name(name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
 pub struct VkPhysicalDevice {
     pub handle: vk::PhysicalDevice,
     pub queue_families: QueueFamiliesIndices,
     pub swapchain_support: SwapChainSupportDetails,
     pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub properties: vk::PhysicalDeviceProperties,
+    /// Whether `VK_EXT_memory_budget` reports the memory used per heap.
+    pub memory_budget: bool,
 }
 
 impl VkPhysicalDevice {
-    pub fn new(instance: &VkInstance, surface: &VkSurface) -> Result<VkPhysicalDevice, String> {
-        let (handle, queue_families, swapchain_support) = VkPhysicalDevice::choose_physical_device(
-            &instance.handle,
-            &surface.loader,
-            &surface.handle,
-        )?;
+    /// Picks the GPU `gpu` selects, else the most capable one able to
+    /// present to `surface`, warning then when the display is likely driven
+    /// by another GPU.
+    pub fn new(
+        instance: &VkInstance,
+        surface: &VkSurface,
+        gpu: Option<&GpuSelector>,
+    ) -> Result<VkPhysicalDevice, String> {
+        let (handle, queue_families, swapchain_support) = match gpu {
+            Some(gpu) => Self::select_physical_device(instance, surface, gpu)?,
+            None => VkPhysicalDevice::choose_physical_device(
+                &instance.handle,
+                &surface.loader,
+                &surface.handle,
+            )?,
+        };
+        if gpu.is_none() {
+            Self::warn_cross_gpu(instance, surface, handle);
+        }
 
         let memory_properties = unsafe {
             instance
                 .handle
                 .get_physical_device_memory_properties(handle)
         };
+        let properties = unsafe { instance.handle.get_physical_device_properties(handle) };
+        let memory_budget = unsafe {
+            instance
+                .handle
+                .enumerate_device_extension_properties(handle)
+                .unwrap_or_default()
+                .iter()
+                .any(|e| {
+                    e.extension_name_as_c_str()
+                        .is_ok_and(|name| name == vk::EXT_MEMORY_BUDGET_NAME)
+                })
+        };
 
         return Ok(VkPhysicalDevice {
             handle,
             queue_families,
             swapchain_support,
             memory_properties,
+            properties,
+            memory_budget,
         });
     }
 
@@ -88,6 +174,121 @@ impl VkPhysicalDevice {
             .ok_or("Failed to find a suitable GPU.".to_string());
     }
 
+    /// The device `gpu` selects, which must be able to render to `surface`.
+    fn select_physical_device(
+        instance: &VkInstance,
+        surface: &VkSurface,
+        gpu: &GpuSelector,
+    ) -> Result<
+        (
+            vk::PhysicalDevice,
+            QueueFamiliesIndices,
+            SwapChainSupportDetails,
+        ),
+        String,
+    > {
+        let physical_devices = unsafe {
+            instance
+                .handle
+                .enumerate_physical_devices()
+                .map_err(|e| format!("Failed to enumerate physical devices: {:?}", e))?
+        };
+        let names: Vec<String> = physical_devices
+            .iter()
+            .map(|&handle| device_name(&instance.handle, handle))
+            .collect();
+        let listed = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| format!("#{} {}", index, name))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let Some(index) = (0..physical_devices.len()).find(|&i| gpu.matches(i, &names[i])) else {
+            return Err(format!("No GPU {}, the GPUs are: {}", gpu, listed));
+        };
+        let handle = physical_devices[index];
+        let queue_families =
+            Self::find_queue_families(&instance.handle, &handle, &surface.loader, &surface.handle);
+        let swapchain_support = query_swapchain_support(&handle, &surface.loader, &surface.handle)
+            .map_err(|e| format!("Swapchain not supported: {}", e))?;
+
+        if queue_families.present_family.is_none() {
+            return Err(format!(
+                "{} can't present to the window, the display is wired to another GPU; \
+                 pick one that can among: {}",
+                names[index], listed
+            ));
+        }
+        if !Self::is_device_suitable(
+            &instance.handle,
+            &handle,
+            &queue_families,
+            &swapchain_support,
+        ) {
+            return Err(format!(
+                "{} lacks features the renderer needs",
+                names[index]
+            ));
+        }
+        Ok((handle, queue_families, swapchain_support))
+    }
+
+    /// Warns when frames rendered on `handle` likely reach the display
+    /// through another GPU: on hybrid laptops (PRIME on Linux) a discrete GPU
+    /// presents by copying each frame to the integrated one driving the
+    /// screen. Also reports device groups, whose other GPUs stay unused.
+    fn warn_cross_gpu(instance: &VkInstance, surface: &VkSurface, handle: vk::PhysicalDevice) {
+        let instance_handle = &instance.handle;
+        let properties = unsafe { instance_handle.get_physical_device_properties(handle) };
+
+        let group_size = unsafe {
+            let count = instance_handle
+                .enumerate_physical_device_groups_len()
+                .unwrap_or(0);
+            let mut groups = vec![vk::PhysicalDeviceGroupProperties::default(); count];
+            let _ = instance_handle.enumerate_physical_device_groups(&mut groups);
+            groups
+                .iter()
+                .find(|group| {
+                    group.physical_devices[..group.physical_device_count as usize].contains(&handle)
+                })
+                .map_or(1, |group| group.physical_device_count)
+        };
+        if group_size > 1 {
+            eprintln!(
+                "{} is linked with {} other GPUs in a device group, rendering on it alone",
+                device_name(instance_handle, handle),
+                group_size - 1
+            );
+        }
+
+        if properties.device_type != vk::PhysicalDeviceType::DISCRETE_GPU {
+            return;
+        }
+        let devices = unsafe { instance_handle.enumerate_physical_devices() }.unwrap_or_default();
+        let integrated = devices.into_iter().find(|&other| {
+            let other_properties = unsafe { instance_handle.get_physical_device_properties(other) };
+            other_properties.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU
+                && Self::find_queue_families(
+                    instance_handle,
+                    &other,
+                    &surface.loader,
+                    &surface.handle,
+                )
+                .present_family
+                .is_some()
+        });
+        if let Some(integrated) = integrated {
+            eprintln!(
+                "Rendering on {} while {} can drive the display: on hybrid graphics \
+                 (PRIME) every frame is copied between them, pass --gpu to pick one",
+                device_name(instance_handle, handle),
+                device_name(instance_handle, integrated)
+            );
+        }
+    }
+
     fn rate_device(
         instance: &Instance,
         surface_loader: &khr::surface::Instance,
@@ -200,3 +401,11 @@ impl VkPhysicalDevice {
         Err("Failed to find suitable memory type for requirements".to_string())
     }
 }
+
+fn device_name(instance: &Instance, handle: vk::PhysicalDevice) -> String {
+    let properties = unsafe { instance.get_physical_device_properties(handle) };
+    properties
+        .device_name_as_c_str()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown device".to_string())
+}
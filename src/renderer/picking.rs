@@ -0,0 +1,94 @@
+use crate::math::{Ray, Vec3};
+use crate::scene::{LayerDepth, ObjectHandle, RenderLayer, Scene};
+
+use super::ResourcesManager;
+
+/// Triangle under a ray, described in the object's own space so it can be
+/// matched against the source file.
+#[derive(Debug, Clone)]
+pub struct FaceHit {
+    pub object: ObjectHandle,
+    pub group: usize,
+    /// Triangle index within the group.
+    pub triangle: usize,
+    /// Vertex indices within the group.
+    pub indices: [u32; 3],
+    pub positions: [Vec3; 3],
+    /// Geometric normal from the winding order.
+    pub normal: Vec3,
+    /// Material name, empty when the group uses the default material.
+    pub material: String,
+    pub distance: f32,
+}
+
+pub struct Picker;
+
+impl Picker {
+    /// Closest triangle hit by `ray` over every object of `scene`, hits in
+    /// layers drawn over a cleared depth buffer coming first. Skinned meshes
+    /// are tested in their bind pose.
+    pub fn face(scene: &Scene, resources: &ResourcesManager, ray: &Ray) -> Option<FaceHit> {
+        let mut closest: Option<FaceHit> = None;
+        let mut closest_clears = 0;
+
+        for (object, instance) in scene.objects.iter().enumerate() {
+            let mesh = resources.get_mesh(instance.id());
+            let Some(to_local) = instance.transform().inverse() else {
+                continue;
+            };
+            if ray
+                .intersect_aabb(&mesh.bounds.transform(&instance.transform()))
+                .is_none()
+            {
+                continue;
+            }
+
+            let local = ray.transform(&to_local);
+            let Some((hit, distance)) = mesh.bvh.intersect(&mesh.geometry, &local) else {
+                continue;
+            };
+            let clears = Self::depth_clears(scene, instance.layer());
+            if closest
+                .as_ref()
+                .is_some_and(|c| (closest_clears, -c.distance) >= (clears, -distance))
+            {
+                continue;
+            }
+            closest_clears = clears;
+
+            let group = &mesh.geometry.groups[hit.group as usize];
+            let first = hit.triangle as usize * 3;
+            let indices = [0, 1, 2].map(|i| group.indices[first + i]);
+            let [a, b, c] = indices.map(|i| group.vertices[i as usize].position);
+            closest = Some(FaceHit {
+                object,
+                group: hit.group as usize,
+                triangle: hit.triangle as usize,
+                indices,
+                positions: [a, b, c],
+                normal: (b - a).cross(c - a).try_normalize().unwrap_or(Vec3::ZERO),
+                material: group
+                    .material
+                    .map(|m| mesh.geometry.materials[m].name.clone())
+                    .unwrap_or_default(),
+                distance,
+            });
+        }
+
+        closest
+    }
+
+    /// Number of depth clears before `layer` is drawn, each putting the
+    /// layers after it in front of the ones before.
+    fn depth_clears(scene: &Scene, layer: RenderLayer) -> usize {
+        let mut cleared: Vec<RenderLayer> = scene
+            .objects
+            .iter()
+            .map(|object| object.layer())
+            .filter(|l| l.depth == LayerDepth::Cleared && *l <= layer)
+            .collect();
+        cleared.sort();
+        cleared.dedup();
+        cleared.len()
+    }
+}
@@ -1,25 +1,586 @@
+use crate::breadcrumb;
 use crate::renderer::{MaterialPushConstants, MeshPushConstants};
 
-use super::{Vertex, VkDescriptorSetLayout, VkDevice, VkRenderPass, VkShaderModule};
+use super::{
+    CompactVertex, DescriptorLayouts, LineVertex, TextVertex, Vertex, VertexSkin, VkDevice,
+    VkRenderPass, VkShaderModule, VoxelInstance,
+};
 
 use ash::vk;
 use std::ffi::CString;
 use std::sync::Arc;
 
+/// Fixed-function state that differs between the pipeline variants.
+struct PipelineDesc<'a> {
+    shaders: (&'a str, &'a str),
+    bindings: &'a [vk::VertexInputBindingDescription],
+    attributes: &'a [vk::VertexInputAttributeDescription],
+    topology: vk::PrimitiveTopology,
+    depth_test: bool,
+    /// Sets the vertex shader's `OUTLINE` specialization constant.
+    outline: bool,
+    /// Only tests depth, passing equal depths, and writes neither color nor
+    /// depth, for occlusion queries.
+    probe: bool,
+    /// Tests depth like `probe` but writes color, to draw over the depth of
+    /// an earlier pass.
+    motion: bool,
+    /// Draws triangle patches through the displacement tessellation stages.
+    tessellated: bool,
+    /// Writes depth alone, biased against the surfaces shadowing themselves,
+    /// for render passes without a color attachment.
+    depth_only: bool,
+    stencil: Stencil,
+    state: RenderState,
+}
+
+/// Fixed-function state pipeline variants are keyed by besides their shaders
+/// and vertex layout. The cull mode is dynamic, set per pass, so it needs no
+/// variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderState {
+    pub polygon_mode: vk::PolygonMode,
+    pub blend: Blend,
+    pub samples: vk::SampleCountFlags,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        Self {
+            polygon_mode: vk::PolygonMode::FILL,
+            blend: Blend::Alpha,
+            samples: vk::SampleCountFlags::TYPE_1,
+        }
+    }
+}
+
+/// How a pipeline's color output combines with the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Blend {
+    /// Replaces the target's color.
+    Opaque,
+    /// Blends over it by the fragment's alpha.
+    Alpha,
+}
+
+/// Use of the stencil buffer, which masks the ground reflection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stencil {
+    Off,
+    /// Marks the covered pixels.
+    Mark,
+    /// Only draws over marked pixels, with the winding of mirrored meshes.
+    Inside,
+}
+
+/// Fragment stage of a mesh pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshShading {
+    /// Material lighting in the current shading mode.
+    Material,
+    /// Inverted hull for the toon outline: the mesh pushed out along its
+    /// normals in a flat ink color. Drawn with front faces culled.
+    Outline,
+    /// Counts fragments into the overdraw image and shows them as a heatmap.
+    Overdraw,
+    /// Material lighting over triangles tessellated and displaced by the
+    /// material's height map. Needs the tessellation shader feature.
+    Displaced,
+    /// Material lighting of meshes mirrored by the ground reflection, only
+    /// within its mask.
+    Reflected,
+    /// Material lighting without depth test or writes, for triangles drawn
+    /// in `FaceSort` order.
+    Sorted,
+    /// The mesh's line segments as a line list, in the flat color of the
+    /// outline shader. Only the rigid variant reads the line buffer's full
+    /// vertices.
+    Lines,
+}
+
+/// Vertex input of a mesh pipeline, following how the drawn mesh stores its
+/// vertices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshVariant {
+    Rigid,
+    /// Full vertices deformed by the joint influences of a second vertex
+    /// buffer.
+    Skinned,
+    /// `CompactVertex`es of a rigid mesh.
+    Compact,
+}
+
+impl MeshVariant {
+    pub const ALL: [MeshVariant; 3] = [
+        MeshVariant::Rigid,
+        MeshVariant::Skinned,
+        MeshVariant::Compact,
+    ];
+}
+
 pub struct VkPipeline {
     device: Arc<VkDevice>,
     pub handle: vk::Pipeline,
     pub layout: vk::PipelineLayout,
+    /// Whether indexed triangles are drawn as tessellated patches.
+    pub tessellated: bool,
 }
 
 impl VkPipeline {
-    pub fn new(
+    /// Stages to push `MaterialPushConstants` to, at offset 64.
+    pub fn material_stages(&self) -> vk::ShaderStageFlags {
+        material_stages(self.tessellated)
+    }
+
+    /// Mesh pipeline for `shading`. The skinned variant reads joint
+    /// influences from a second vertex buffer and deforms vertices with the
+    /// frame's joint matrices; the first instance index selects the skin's
+    /// first matrix. The compact variant decodes `CompactVertex`es.
+    pub fn mesh(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        variant: MeshVariant,
+        shading: MeshShading,
+        state: RenderState,
+    ) -> Result<VkPipeline, String> {
+        let vert = match variant {
+            MeshVariant::Rigid => "shaders/shader.vert.spv",
+            MeshVariant::Skinned => "shaders/skinned.vert.spv",
+            MeshVariant::Compact => "shaders/compact.vert.spv",
+        };
+        let frag = match shading {
+            MeshShading::Material
+            | MeshShading::Displaced
+            | MeshShading::Reflected
+            | MeshShading::Sorted
+                if device.ray_query() =>
+            {
+                "shaders/shader_shadows.frag.spv"
+            }
+            MeshShading::Material
+            | MeshShading::Displaced
+            | MeshShading::Reflected
+            | MeshShading::Sorted => "shaders/shader.frag.spv",
+            MeshShading::Outline | MeshShading::Lines => "shaders/outline.frag.spv",
+            MeshShading::Overdraw => "shaders/overdraw.frag.spv",
+        };
+
+        let (mut bindings, mut attributes) = match variant {
+            MeshVariant::Compact => (
+                vec![CompactVertex::get_binding_description()],
+                CompactVertex::get_attribute_description().to_vec(),
+            ),
+            _ => (
+                vec![Vertex::get_binding_description()],
+                Vertex::get_attribute_description().to_vec(),
+            ),
+        };
+        if variant == MeshVariant::Skinned {
+            bindings.push(VertexSkin::get_binding_description());
+            attributes.extend_from_slice(&VertexSkin::get_attribute_description());
+        }
+
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: (vert, frag),
+                bindings: &bindings,
+                attributes: &attributes,
+                topology: match shading {
+                    MeshShading::Displaced => vk::PrimitiveTopology::PATCH_LIST,
+                    MeshShading::Lines => vk::PrimitiveTopology::LINE_LIST,
+                    _ => vk::PrimitiveTopology::TRIANGLE_LIST,
+                },
+                depth_test: shading != MeshShading::Sorted,
+                outline: matches!(shading, MeshShading::Outline),
+                probe: false,
+                motion: false,
+                tessellated: shading == MeshShading::Displaced,
+                depth_only: false,
+                stencil: if shading == MeshShading::Reflected {
+                    Stencil::Inside
+                } else {
+                    Stencil::Off
+                },
+                state,
+            },
+        )
+    }
+
+    /// Pipeline drawing `LineVertex` segments as screen-space quads of six
+    /// vertices, one instance each, sharing the mesh pipeline layout so the
+    /// same descriptor set applies.
+    pub fn lines(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+    ) -> Result<VkPipeline, String> {
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: ("shaders/line.vert.spv", "shaders/line.frag.spv"),
+                bindings: &[LineVertex::get_binding_description()],
+                attributes: &LineVertex::get_attribute_description(),
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: true,
+                outline: false,
+                probe: false,
+                motion: false,
+                tessellated: false,
+                depth_only: false,
+                stencil: Stencil::Off,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// Lit unit cubes of 36 vertices, one `VoxelInstance` each, placed by
+    /// the mesh transform push constant.
+    pub fn voxels(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+    ) -> Result<VkPipeline, String> {
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: ("shaders/voxel.vert.spv", "shaders/voxel.frag.spv"),
+                bindings: &[VoxelInstance::get_binding_description()],
+                attributes: &VoxelInstance::get_attribute_description(),
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: true,
+                outline: false,
+                probe: false,
+                motion: false,
+                tessellated: false,
+                depth_only: false,
+                stencil: Stencil::Off,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// Alpha-blended screen-space pipeline for `TextVertex` triangles, drawn
+    /// over everything else.
+    pub fn text(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+    ) -> Result<VkPipeline, String> {
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: ("shaders/text.vert.spv", "shaders/text.frag.spv"),
+                bindings: &[TextVertex::get_binding_description()],
+                attributes: &TextVertex::get_attribute_description(),
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: false,
+                outline: false,
+                probe: false,
+                motion: false,
+                tessellated: false,
+                depth_only: false,
+                stencil: Stencil::Off,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// Screen-covering triangle darkening the scene by the traced occlusion,
+    /// drawn without vertex buffers.
+    pub fn occlusion_composite(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+    ) -> Result<VkPipeline, String> {
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: (
+                    "shaders/fullscreen.vert.spv",
+                    "shaders/traced_occlusion.frag.spv",
+                ),
+                bindings: &[],
+                attributes: &[],
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: false,
+                outline: false,
+                probe: false,
+                motion: false,
+                tessellated: false,
+                depth_only: false,
+                stencil: Stencil::Off,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// Screen-covering triangle copying the temporal resolve to the
+    /// swapchain, drawn without vertex buffers.
+    pub fn temporal_composite(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+    ) -> Result<VkPipeline, String> {
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: ("shaders/fullscreen.vert.spv", "shaders/temporal.frag.spv"),
+                bindings: &[],
+                attributes: &[],
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: false,
+                outline: false,
+                probe: false,
+                motion: false,
+                tessellated: false,
+                depth_only: false,
+                stencil: Stencil::Off,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// Rigid meshes drawn over the scene's depth, writing how far each
+    /// fragment moved since the previous frame, see `velocity.frag`. Only
+    /// positions are read, from `CompactVertex`es when `compact`.
+    pub fn velocity(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        compact: bool,
+    ) -> Result<VkPipeline, String> {
+        let (binding, position) = if compact {
+            (
+                CompactVertex::get_binding_description(),
+                CompactVertex::get_attribute_description()[0],
+            )
+        } else {
+            (
+                Vertex::get_binding_description(),
+                Vertex::get_attribute_description()[0],
+            )
+        };
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: ("shaders/velocity.vert.spv", "shaders/velocity.frag.spv"),
+                bindings: &[binding],
+                attributes: &[position],
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: true,
+                outline: false,
+                probe: false,
+                motion: true,
+                tessellated: false,
+                depth_only: false,
+                stencil: Stencil::Off,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// Rigid meshes drawn into the shadow map cascades, their depth alone,
+    /// in the cascade the draw's first instance picks, see `shadow.vert`.
+    /// Only positions are read, from `CompactVertex`es when `compact`.
+    pub fn shadow_cascades(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        compact: bool,
+    ) -> Result<VkPipeline, String> {
+        let (binding, position) = if compact {
+            (
+                CompactVertex::get_binding_description(),
+                CompactVertex::get_attribute_description()[0],
+            )
+        } else {
+            (
+                Vertex::get_binding_description(),
+                Vertex::get_attribute_description()[0],
+            )
+        };
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: ("shaders/shadow.vert.spv", "shaders/shadow.frag.spv"),
+                bindings: &[binding],
+                attributes: &[position],
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: true,
+                outline: false,
+                probe: false,
+                motion: false,
+                tessellated: false,
+                depth_only: true,
+                stencil: Stencil::Off,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// Unit cube generated without vertex buffers, scaled to a bounding box
+    /// by the mesh transform, for occlusion queries.
+    pub fn occlusion_probe(
         device: Arc<VkDevice>,
         render_pass: &VkRenderPass,
-        descriptor_set_layout: &VkDescriptorSetLayout,
+        descriptor_layouts: &DescriptorLayouts,
     ) -> Result<VkPipeline, String> {
-        let frag_shader_module = VkShaderModule::new(device.clone(), "shaders/shader.frag.spv")?;
-        let vert_shader_module = VkShaderModule::new(device.clone(), "shaders/shader.vert.spv")?;
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: ("shaders/bounds.vert.spv", "shaders/bounds.frag.spv"),
+                bindings: &[],
+                attributes: &[],
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: true,
+                outline: false,
+                probe: true,
+                motion: false,
+                tessellated: false,
+                depth_only: false,
+                stencil: Stencil::Off,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// The occlusion probe's unit cube marking the stencil where it passes
+    /// the depth test, for the ground reflection's mask.
+    pub fn reflection_mask(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+    ) -> Result<VkPipeline, String> {
+        Self::create(
+            device,
+            render_pass,
+            descriptor_layouts,
+            &PipelineDesc {
+                shaders: ("shaders/bounds.vert.spv", "shaders/bounds.frag.spv"),
+                bindings: &[],
+                attributes: &[],
+                topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+                depth_test: true,
+                outline: false,
+                probe: true,
+                motion: false,
+                tessellated: false,
+                depth_only: false,
+                stencil: Stencil::Mark,
+                state: RenderState::default(),
+            },
+        )
+    }
+
+    /// Compute pipeline running `shader` over the mesh descriptor set, with
+    /// the push constant range its block declares.
+    pub fn compute(
+        device: Arc<VkDevice>,
+        descriptor_layouts: &DescriptorLayouts,
+        shader: &str,
+    ) -> Result<VkPipeline, String> {
+        let shader_module = VkShaderModule::new(device.clone(), shader)?;
+
+        let push_constant_ranges: Vec<vk::PushConstantRange> = shader_module
+            .reflection
+            .push_constants
+            .iter()
+            .map(|range| vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                offset: range.start,
+                size: range.end - range.start,
+            })
+            .collect();
+        let set_layouts = descriptor_layouts.handles();
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            set_layout_count: set_layouts.len() as u32,
+            p_set_layouts: set_layouts.as_ptr(),
+            push_constant_range_count: push_constant_ranges.len() as u32,
+            p_push_constant_ranges: push_constant_ranges.as_ptr(),
+            ..Default::default()
+        };
+
+        let layout = unsafe {
+            device
+                .handle
+                .create_pipeline_layout(&pipeline_layout_create_info, None)
+                .map_err(|e| format!("Failed to create pipeline layout: {}", e))?
+        };
+
+        let entrypoint = CString::new("main").unwrap();
+        let pipeline_create_info = vk::ComputePipelineCreateInfo {
+            s_type: vk::StructureType::COMPUTE_PIPELINE_CREATE_INFO,
+            stage: vk::PipelineShaderStageCreateInfo {
+                s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+                stage: vk::ShaderStageFlags::COMPUTE,
+                module: shader_module.handle,
+                p_name: entrypoint.as_ptr(),
+                ..Default::default()
+            },
+            layout,
+            ..Default::default()
+        };
+
+        let handle = unsafe {
+            device
+                .handle
+                .create_compute_pipelines(vk::PipelineCache::null(), &[pipeline_create_info], None)
+                .map_err(|_| "Failed to create compute pipeline".to_string())?
+                .remove(0)
+        };
+
+        return Ok(VkPipeline {
+            device,
+            handle,
+            layout,
+            tessellated: false,
+        });
+    }
+
+    fn create(
+        device: Arc<VkDevice>,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        desc: &PipelineDesc,
+    ) -> Result<VkPipeline, String> {
+        let (vert_path, frag_path) = desc.shaders;
+        let frag_shader_module = VkShaderModule::new(device.clone(), frag_path)?;
+        let vert_shader_module = VkShaderModule::new(device.clone(), vert_path)?;
+
+        let outline = vk::Bool32::from(desc.outline);
+        let specialization_entry = vk::SpecializationMapEntry {
+            constant_id: 0,
+            offset: 0,
+            size: std::mem::size_of::<vk::Bool32>(),
+        };
+        let specialization = vk::SpecializationInfo {
+            map_entry_count: 1,
+            p_map_entries: &specialization_entry,
+            data_size: std::mem::size_of::<vk::Bool32>(),
+            p_data: &outline as *const _ as *const std::ffi::c_void,
+            ..Default::default()
+        };
 
         let entrypoint = CString::new("main").unwrap();
         let vert_shader_create_info = vk::PipelineShaderStageCreateInfo {
@@ -27,6 +588,11 @@ impl VkPipeline {
             stage: vk::ShaderStageFlags::VERTEX,
             module: vert_shader_module.handle,
             p_name: entrypoint.as_ptr(),
+            p_specialization_info: if desc.outline {
+                &specialization
+            } else {
+                std::ptr::null()
+            },
             ..Default::default()
         };
 
@@ -38,22 +604,47 @@ impl VkPipeline {
             ..Default::default()
         };
 
-        let shader_stages = [vert_shader_create_info, frag_shader_create_info];
+        let mut shader_stages = vec![vert_shader_create_info, frag_shader_create_info];
+        let tessellation_modules = if desc.tessellated {
+            Some((
+                VkShaderModule::new(device.clone(), "shaders/displaced.tesc.spv")?,
+                VkShaderModule::new(device.clone(), "shaders/displaced.tese.spv")?,
+            ))
+        } else {
+            None
+        };
+        if let Some((control, evaluation)) = &tessellation_modules {
+            for (stage, module) in [
+                (vk::ShaderStageFlags::TESSELLATION_CONTROL, control),
+                (vk::ShaderStageFlags::TESSELLATION_EVALUATION, evaluation),
+            ] {
+                shader_stages.push(vk::PipelineShaderStageCreateInfo {
+                    s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+                    stage,
+                    module: module.handle,
+                    p_name: entrypoint.as_ptr(),
+                    ..Default::default()
+                });
+            }
+        }
+        let tessellation_state = vk::PipelineTessellationStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_TESSELLATION_STATE_CREATE_INFO,
+            patch_control_points: 3,
+            ..Default::default()
+        };
 
-        let binding_description = Vertex::get_binding_description();
-        let attribute_descriptions = Vertex::get_attribute_description();
         let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
-            vertex_binding_description_count: 1,
-            p_vertex_binding_descriptions: &binding_description,
-            vertex_attribute_description_count: attribute_descriptions.len() as u32,
-            p_vertex_attribute_descriptions: attribute_descriptions.as_ptr(),
+            vertex_binding_description_count: desc.bindings.len() as u32,
+            p_vertex_binding_descriptions: desc.bindings.as_ptr(),
+            vertex_attribute_description_count: desc.attributes.len() as u32,
+            p_vertex_attribute_descriptions: desc.attributes.as_ptr(),
             ..Default::default()
         };
 
         let input_assembly = vk::PipelineInputAssemblyStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            topology: desc.topology,
             primitive_restart_enable: vk::FALSE,
             ..Default::default()
         };
@@ -69,20 +660,25 @@ impl VkPipeline {
             s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
             depth_clamp_enable: vk::FALSE,
             rasterizer_discard_enable: vk::FALSE,
-            polygon_mode: vk::PolygonMode::FILL,
+            polygon_mode: desc.state.polygon_mode,
             line_width: 1.,
-            front_face: vk::FrontFace::CLOCKWISE,
-            depth_bias_enable: vk::FALSE,
-            depth_bias_constant_factor: 0.,
+            // Mirroring flips the winding.
+            front_face: if desc.stencil == Stencil::Inside {
+                vk::FrontFace::COUNTER_CLOCKWISE
+            } else {
+                vk::FrontFace::CLOCKWISE
+            },
+            depth_bias_enable: desc.depth_only.into(),
+            depth_bias_constant_factor: if desc.depth_only { 1.25 } else { 0. },
             depth_bias_clamp: 0.,
-            depth_bias_slope_factor: 0.,
+            depth_bias_slope_factor: if desc.depth_only { 1.75 } else { 0. },
             ..Default::default()
         };
 
         let multisampling = vk::PipelineMultisampleStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
             sample_shading_enable: vk::FALSE,
-            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            rasterization_samples: desc.state.samples,
             min_sample_shading: 1.,
             p_sample_mask: std::ptr::null(),
             alpha_to_coverage_enable: vk::FALSE,
@@ -90,22 +686,46 @@ impl VkPipeline {
             ..Default::default()
         };
 
+        let stencil_op = vk::StencilOpState {
+            fail_op: vk::StencilOp::KEEP,
+            pass_op: if desc.stencil == Stencil::Mark {
+                vk::StencilOp::REPLACE
+            } else {
+                vk::StencilOp::KEEP
+            },
+            depth_fail_op: vk::StencilOp::KEEP,
+            compare_op: if desc.stencil == Stencil::Mark {
+                vk::CompareOp::ALWAYS
+            } else {
+                vk::CompareOp::EQUAL
+            },
+            compare_mask: 0xff,
+            write_mask: 0xff,
+            reference: 1,
+        };
         let depth_stencil = vk::PipelineDepthStencilStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
-            depth_test_enable: vk::TRUE,
-            depth_write_enable: vk::TRUE,
-            depth_compare_op: vk::CompareOp::LESS,
+            depth_test_enable: desc.depth_test.into(),
+            depth_write_enable: (desc.depth_test && !desc.probe && !desc.motion).into(),
+            depth_compare_op: if desc.probe || desc.motion {
+                vk::CompareOp::LESS_OR_EQUAL
+            } else {
+                vk::CompareOp::LESS
+            },
             depth_bounds_test_enable: vk::FALSE,
-            stencil_test_enable: vk::FALSE,
+            stencil_test_enable: (desc.stencil != Stencil::Off).into(),
+            front: stencil_op,
+            back: stencil_op,
             ..Default::default()
         };
 
         let color_blend_attachment = vk::PipelineColorBlendAttachmentState {
-            color_write_mask: vk::ColorComponentFlags::R
-                | vk::ColorComponentFlags::G
-                | vk::ColorComponentFlags::B
-                | vk::ColorComponentFlags::A,
-            blend_enable: vk::TRUE,
+            color_write_mask: if desc.probe {
+                vk::ColorComponentFlags::empty()
+            } else {
+                vk::ColorComponentFlags::RGBA
+            },
+            blend_enable: (desc.state.blend == Blend::Alpha).into(),
             src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
             dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
             color_blend_op: vk::BlendOp::ADD,
@@ -118,7 +738,7 @@ impl VkPipeline {
             s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
             logic_op_enable: vk::FALSE,
             logic_op: vk::LogicOp::COPY,
-            attachment_count: 1,
+            attachment_count: if desc.depth_only { 0 } else { 1 },
             p_attachments: &color_blend_attachment,
             blend_constants: [0.; 4],
             ..Default::default()
@@ -143,16 +763,29 @@ impl VkPipeline {
                 size: std::mem::size_of::<MeshPushConstants>() as u32,
             },
             vk::PushConstantRange {
-                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                stage_flags: material_stages(desc.tessellated),
                 offset: std::mem::size_of::<MeshPushConstants>() as u32,
                 size: std::mem::size_of::<MaterialPushConstants>() as u32,
             },
         ];
 
+        let mut modules = vec![
+            (vert_path, &vert_shader_module),
+            (frag_path, &frag_shader_module),
+        ];
+        if let Some((control, evaluation)) = &tessellation_modules {
+            modules.push(("shaders/displaced.tesc.spv", control));
+            modules.push(("shaders/displaced.tese.spv", evaluation));
+        }
+        for (path, module) in modules {
+            check_interface(path, module, desc.attributes, &push_constant_ranges)?;
+        }
+
+        let set_layouts = descriptor_layouts.handles();
         let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
             s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
-            set_layout_count: 1,
-            p_set_layouts: &descriptor_set_layout.handle,
+            set_layout_count: set_layouts.len() as u32,
+            p_set_layouts: set_layouts.as_ptr(),
             push_constant_range_count: push_constant_ranges.len() as u32,
             p_push_constant_ranges: push_constant_ranges.as_ptr(),
             ..Default::default()
@@ -171,6 +804,11 @@ impl VkPipeline {
             p_stages: shader_stages.as_ptr(),
             p_vertex_input_state: &vertex_input_info,
             p_input_assembly_state: &input_assembly,
+            p_tessellation_state: if desc.tessellated {
+                &tessellation_state
+            } else {
+                std::ptr::null()
+            },
             p_viewport_state: &viewport_state,
             p_rasterization_state: &rasterizer,
             p_multisample_state: &multisampling,
@@ -192,15 +830,65 @@ impl VkPipeline {
                 .map_err(|_| format!("Failed to create graphics pipeline"))?
                 .remove(0)
         };
+        breadcrumb!("vkCreateGraphicsPipelines");
 
         return Ok(VkPipeline {
             device,
             handle,
             layout,
+            tessellated: desc.tessellated,
         });
     }
 }
 
+/// Stages reading the material push constants: the height map is sampled
+/// after tessellation.
+fn material_stages(tessellated: bool) -> vk::ShaderStageFlags {
+    if tessellated {
+        vk::ShaderStageFlags::FRAGMENT | vk::ShaderStageFlags::TESSELLATION_EVALUATION
+    } else {
+        vk::ShaderStageFlags::FRAGMENT
+    }
+}
+
+/// Errors when the shader at `path` reads a vertex attribute missing from
+/// `attributes`, or push constants outside the `ranges` of its stage, which
+/// the renderer pushes at fixed offsets.
+fn check_interface(
+    path: &str,
+    module: &VkShaderModule,
+    attributes: &[vk::VertexInputAttributeDescription],
+    ranges: &[vk::PushConstantRange],
+) -> Result<(), String> {
+    let reflection = &module.reflection;
+    if reflection.stage == vk::ShaderStageFlags::VERTEX {
+        if let Some(location) = reflection
+            .inputs
+            .iter()
+            .find(|&&location| !attributes.iter().any(|a| a.location == location))
+        {
+            return Err(format!(
+                "{} reads vertex input {}, which the pipeline doesn't provide",
+                path, location
+            ));
+        }
+    }
+    if let Some(block) = &reflection.push_constants {
+        let covered = ranges.iter().any(|range| {
+            range.stage_flags.contains(reflection.stage)
+                && range.offset <= block.start
+                && block.end <= range.offset + range.size
+        });
+        if !covered {
+            return Err(format!(
+                "{} declares push constants at bytes {}..{}, outside the {:?} range",
+                path, block.start, block.end, reflection.stage
+            ));
+        }
+    }
+    Ok(())
+}
+
 impl Drop for VkPipeline {
     fn drop(&mut self) {
         unsafe {
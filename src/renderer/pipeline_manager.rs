@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::{
+    DescriptorLayouts, MeshShading, MeshVariant, RenderState, VkDevice, VkPipeline, VkRenderPass,
+};
+
+/// Everything a mesh pipeline is built from: its shaders, the vertex layout
+/// they read and the fixed-function state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshPipelineKey {
+    pub shading: MeshShading,
+    pub variant: MeshVariant,
+    pub state: RenderState,
+}
+
+impl MeshPipelineKey {
+    /// Key of `shading` over `variant` in the default render state.
+    pub fn new(shading: MeshShading, variant: MeshVariant) -> Self {
+        Self {
+            shading,
+            variant,
+            state: RenderState::default(),
+        }
+    }
+}
+
+/// Mesh pipelines created the first time a key is drawn and kept for the
+/// renderer's lifetime, instead of building every combination up front.
+pub struct PipelineManager {
+    device: Arc<VkDevice>,
+    /// `None` for keys whose pipeline failed to build, so the error is only
+    /// reported once.
+    pipelines: Mutex<HashMap<MeshPipelineKey, Option<Arc<VkPipeline>>>>,
+}
+
+impl PipelineManager {
+    pub fn new(device: Arc<VkDevice>) -> Self {
+        Self {
+            device,
+            pipelines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Builds the pipeline of `key` now unless cached, failing with its
+    /// error, for the pipelines the renderer can't draw without.
+    pub fn preload(
+        &self,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        key: MeshPipelineKey,
+    ) -> Result<(), String> {
+        let mut pipelines = self.pipelines.lock().unwrap();
+        if !pipelines.contains_key(&key) {
+            let pipeline = self.create(render_pass, descriptor_layouts, key)?;
+            pipelines.insert(key, Some(Arc::new(pipeline)));
+        }
+        Ok(())
+    }
+
+    /// Pipeline of `key`, built on first use. A pipeline failing to build is
+    /// reported and `None` returned from then on.
+    pub fn get(
+        &self,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        key: MeshPipelineKey,
+    ) -> Option<Arc<VkPipeline>> {
+        let mut pipelines = self.pipelines.lock().unwrap();
+        pipelines
+            .entry(key)
+            .or_insert_with(|| {
+                self.create(render_pass, descriptor_layouts, key)
+                    .map_err(|e| eprintln!("Failed to create {:?} pipeline: {}", key, e))
+                    .ok()
+                    .map(Arc::new)
+            })
+            .clone()
+    }
+
+    fn create(
+        &self,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        key: MeshPipelineKey,
+    ) -> Result<VkPipeline, String> {
+        VkPipeline::mesh(
+            self.device.clone(),
+            render_pass,
+            descriptor_layouts,
+            key.variant,
+            key.shading,
+            key.state,
+        )
+    }
+}
@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::{FrameStats, VkContext};
+use crate::math::Mat4;
+use crate::scene::Scene;
+
+/// What a plugin creates its resources against.
+pub struct PluginSetup<'a> {
+    pub context: &'a Arc<VkContext>,
+    /// Render pass the plugin records in, loading the window's color and
+    /// depth. Pipelines created for it can draw over the scene.
+    pub render_pass: vk::RenderPass,
+    pub color_format: vk::Format,
+    /// Slots of the per-frame resources a plugin needs, see
+    /// `PluginFrame::index`.
+    pub frames_in_flight: usize,
+}
+
+/// The frame a plugin records into.
+pub struct PluginFrame<'a> {
+    /// Slot of the frame among those in flight, for resources updated every
+    /// frame while the GPU may still read the previous slots' ones.
+    pub index: usize,
+    pub extent: vk::Extent2D,
+    pub view: Mat4,
+    pub proj: Mat4,
+    pub scene: &'a Scene,
+    /// Counters of the frame, which the plugin's draws can be added to.
+    pub stats: &'a mut FrameStats,
+}
+
+/// Custom drawing added to the window's frames from outside the renderer,
+/// like overlays or effects, after the scene and the HUD.
+///
+/// The plugin owns its Vulkan objects and destroys them when dropped, which
+/// happens before the renderer's device once the GPU is idle.
+pub trait RenderPlugin {
+    /// Creates the plugin's pipelines and buffers. Runs when the plugin is
+    /// added, and again on a new device after the previous one was lost.
+    fn setup(&mut self, setup: &PluginSetup) -> Result<(), String>;
+
+    /// Records the plugin's commands, inside the render pass given to
+    /// `setup` with the viewport and scissor covering the whole window.
+    fn record(&mut self, cmd: vk::CommandBuffer, frame: &mut PluginFrame);
+}
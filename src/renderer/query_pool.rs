@@ -0,0 +1,67 @@
+use ash::vk;
+use std::sync::Arc;
+
+use super::VkDevice;
+
+pub struct VkQueryPool {
+    device: Arc<VkDevice>,
+    pub handle: vk::QueryPool,
+    pub capacity: u32,
+}
+
+impl VkQueryPool {
+    pub fn new(device: Arc<VkDevice>, ty: vk::QueryType, capacity: u32) -> Result<Self, String> {
+        let create_info = vk::QueryPoolCreateInfo {
+            s_type: vk::StructureType::QUERY_POOL_CREATE_INFO,
+            query_type: ty,
+            query_count: capacity,
+            ..Default::default()
+        };
+
+        let handle = unsafe {
+            device
+                .handle
+                .create_query_pool(&create_info, None)
+                .map_err(|e| format!("Failed to create query pool: {}", e))?
+        };
+
+        Ok(Self {
+            device,
+            handle,
+            capacity,
+        })
+    }
+
+    /// Results of the first `count` queries, or `None` when any of them isn't
+    /// available, e.g. because it was never recorded.
+    pub fn results(&self, count: u32) -> Option<Vec<u64>> {
+        let mut results = vec![0; count.min(self.capacity) as usize];
+        if results.is_empty() {
+            return Some(results);
+        }
+        unsafe {
+            self.device
+                .handle
+                .get_query_pool_results(self.handle, 0, &mut results, vk::QueryResultFlags::TYPE_64)
+                .ok()?;
+        }
+        Some(results)
+    }
+
+    /// Resets every query; must be recorded outside a render pass.
+    pub fn record_reset(&self, cmd: vk::CommandBuffer) {
+        unsafe {
+            self.device
+                .handle
+                .cmd_reset_query_pool(cmd, self.handle, 0, self.capacity);
+        }
+    }
+}
+
+impl Drop for VkQueryPool {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle.destroy_query_pool(self.handle, None);
+        }
+    }
+}
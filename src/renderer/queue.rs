@@ -2,6 +2,7 @@ use ash::vk;
 use std::sync::Arc;
 
 use super::VkDevice;
+use crate::breadcrumb;
 
 #[derive(Clone)]
 pub struct QueueFamiliesIndices {
@@ -45,7 +46,9 @@ impl VkQueue {
             self.device
                 .handle
                 .queue_submit(self.handle, &[submit_info], *fence)
-                .map_err(|e| format!("Failed to submit queue: {}", e))
+                .map_err(|e| format!("Failed to submit queue: {}", e))?;
         }
+        breadcrumb!("vkQueueSubmit");
+        Ok(())
     }
 }
@@ -0,0 +1,344 @@
+use crate::assets;
+use ash::vk;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::ops::Range;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+// Opcodes, decorations and storage classes of the SPIR-V specification read
+// below.
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const IMAGE_DIM_BUFFER: u32 = 5;
+
+/// A resource a shader declares in a descriptor set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// Elements of an arrayed binding, 1 otherwise.
+    pub count: u32,
+}
+
+/// What a SPIR-V module expects from the pipeline using it.
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+    pub stage: vk::ShaderStageFlags,
+    pub bindings: Vec<ReflectedBinding>,
+    /// Bytes of the push constant block its members cover, `None` without
+    /// one.
+    pub push_constants: Option<Range<u32>>,
+    /// Locations of the stage's inputs, vertex attributes for vertex shaders.
+    pub inputs: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Scalar(u32),
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array(u32, Option<u32>),
+    Struct(Vec<u32>),
+    Pointer(u32),
+    AccelerationStructure,
+}
+
+impl ShaderReflection {
+    /// Reflections of every compiled shader in the `shaders` asset
+    /// directory, with their paths, in path order.
+    pub fn load_all() -> Result<Vec<(String, ShaderReflection)>, String> {
+        let paths =
+            assets::list("shaders", "spv").map_err(|e| format!("Failed to list shaders: {}", e))?;
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let name = path.display().to_string();
+                let bytes = assets::read(&path)
+                    .map_err(|e| format!("Failed to open file {}: {}", name, e))?;
+                let code = ash::util::read_spv(&mut Cursor::new(bytes))
+                    .map_err(|e| format!("Failed to decode SPIR-V file {}: {}", name, e))?;
+                let reflection = ShaderReflection::parse(&code)
+                    .map_err(|e| format!("Failed to reflect {}: {}", name, e))?;
+                Ok((name, reflection))
+            })
+            .collect()
+    }
+
+    /// Reads the entry point's stage, descriptor bindings, push constant
+    /// block and inputs from SPIR-V words.
+    pub fn parse(code: &[u32]) -> Result<ShaderReflection, String> {
+        if code.len() < 5 || code[0] != SPIRV_MAGIC {
+            return Err("Not a SPIR-V module".to_string());
+        }
+
+        let mut stage = None;
+        let mut types: HashMap<u32, Type> = HashMap::new();
+        let mut constants: HashMap<u32, u32> = HashMap::new();
+        let mut decorations: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut member_decorations: HashMap<(u32, u32, u32), u32> = HashMap::new();
+        let mut variables: Vec<(u32, u32, u32)> = Vec::new();
+
+        let mut words = &code[5..];
+        while let Some(&first) = words.first() {
+            let (count, opcode) = ((first >> 16) as usize, first & 0xffff);
+            if count == 0 || count > words.len() {
+                return Err("Truncated SPIR-V instruction".to_string());
+            }
+            let operands = &words[1..count];
+            let operand = |i: usize| operands.get(i).copied().unwrap_or(0);
+            match opcode {
+                OP_ENTRY_POINT if stage.is_none() => {
+                    stage = Some(match operand(0) {
+                        0 => vk::ShaderStageFlags::VERTEX,
+                        1 => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+                        2 => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+                        3 => vk::ShaderStageFlags::GEOMETRY,
+                        4 => vk::ShaderStageFlags::FRAGMENT,
+                        5 => vk::ShaderStageFlags::COMPUTE,
+                        model => return Err(format!("Unsupported execution model {}", model)),
+                    });
+                }
+                OP_TYPE_BOOL => {
+                    types.insert(operand(0), Type::Scalar(4));
+                }
+                OP_TYPE_INT | OP_TYPE_FLOAT => {
+                    types.insert(operand(0), Type::Scalar(operand(1) / 8));
+                }
+                OP_TYPE_VECTOR => {
+                    types.insert(operand(0), Type::Vector(operand(1), operand(2)));
+                }
+                OP_TYPE_MATRIX => {
+                    types.insert(operand(0), Type::Matrix(operand(1), operand(2)));
+                }
+                OP_TYPE_IMAGE => {
+                    types.insert(
+                        operand(0),
+                        Type::Image {
+                            dim: operand(2),
+                            sampled: operand(6),
+                        },
+                    );
+                }
+                OP_TYPE_SAMPLER => {
+                    types.insert(operand(0), Type::Sampler);
+                }
+                OP_TYPE_SAMPLED_IMAGE => {
+                    types.insert(operand(0), Type::SampledImage);
+                }
+                OP_TYPE_ARRAY => {
+                    types.insert(operand(0), Type::Array(operand(1), Some(operand(2))));
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    types.insert(operand(0), Type::Array(operand(1), None));
+                }
+                OP_TYPE_STRUCT => {
+                    let members = operands.get(1..).unwrap_or_default();
+                    types.insert(operand(0), Type::Struct(members.to_vec()));
+                }
+                OP_TYPE_POINTER => {
+                    types.insert(operand(0), Type::Pointer(operand(2)));
+                }
+                OP_TYPE_ACCELERATION_STRUCTURE => {
+                    types.insert(operand(0), Type::AccelerationStructure);
+                }
+                OP_CONSTANT => {
+                    constants.insert(operand(1), operand(2));
+                }
+                OP_VARIABLE => {
+                    variables.push((operand(1), operand(0), operand(2)));
+                }
+                OP_DECORATE => {
+                    decorations.insert((operand(0), operand(1)), operand(2));
+                }
+                OP_MEMBER_DECORATE => {
+                    member_decorations.insert((operand(0), operand(1), operand(2)), operand(3));
+                }
+                _ => {}
+            }
+            words = &words[count..];
+        }
+
+        let module = Module {
+            types,
+            constants,
+            decorations,
+            member_decorations,
+        };
+        let mut reflection = ShaderReflection {
+            stage: stage.ok_or("SPIR-V module without an entry point")?,
+            bindings: Vec::new(),
+            push_constants: None,
+            inputs: Vec::new(),
+        };
+        for (id, pointer, storage) in variables {
+            let Some(&Type::Pointer(pointee)) = module.types.get(&pointer) else {
+                continue;
+            };
+            match storage {
+                STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                    let (Some(&set), Some(&binding)) = (
+                        module.decorations.get(&(id, DECORATION_DESCRIPTOR_SET)),
+                        module.decorations.get(&(id, DECORATION_BINDING)),
+                    ) else {
+                        continue;
+                    };
+                    let (descriptor_type, count) = module.descriptor(pointee, storage)?;
+                    reflection.bindings.push(ReflectedBinding {
+                        set,
+                        binding,
+                        descriptor_type,
+                        count,
+                    });
+                }
+                STORAGE_PUSH_CONSTANT => {
+                    reflection.push_constants = module.members_range(pointee);
+                }
+                STORAGE_INPUT => {
+                    if module.decorations.contains_key(&(id, DECORATION_BUILT_IN)) {
+                        continue;
+                    }
+                    if let Some(&location) = module.decorations.get(&(id, DECORATION_LOCATION)) {
+                        reflection.inputs.push(location);
+                    }
+                }
+                _ => {}
+            }
+        }
+        reflection.bindings.sort_by_key(|b| (b.set, b.binding));
+        reflection.inputs.sort();
+        Ok(reflection)
+    }
+}
+
+/// Types and decorations of a parsed module, by result id.
+struct Module {
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<(u32, u32), u32>,
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+}
+
+impl Module {
+    /// Descriptor type and count of a resource of type `id` in `storage`.
+    fn descriptor(&self, mut id: u32, storage: u32) -> Result<(vk::DescriptorType, u32), String> {
+        let mut count = 1;
+        while let Some(Type::Array(element, length)) = self.types.get(&id) {
+            // Unsized arrays hold as many descriptors as are bound.
+            count *= length
+                .and_then(|l| self.constants.get(&l))
+                .copied()
+                .unwrap_or(1);
+            id = *element;
+        }
+        let descriptor_type = match self.types.get(&id) {
+            Some(Type::SampledImage) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            Some(Type::Sampler) => vk::DescriptorType::SAMPLER,
+            Some(Type::Image { dim, sampled }) => match (*dim == IMAGE_DIM_BUFFER, *sampled) {
+                (true, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                (true, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                (false, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                (false, _) => vk::DescriptorType::SAMPLED_IMAGE,
+            },
+            Some(Type::AccelerationStructure) => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            Some(Type::Struct(_)) => {
+                if storage == STORAGE_STORAGE_BUFFER
+                    || self
+                        .decorations
+                        .contains_key(&(id, DECORATION_BUFFER_BLOCK))
+                {
+                    vk::DescriptorType::STORAGE_BUFFER
+                } else {
+                    vk::DescriptorType::UNIFORM_BUFFER
+                }
+            }
+            _ => return Err(format!("Unsupported descriptor type for SPIR-V id {}", id)),
+        };
+        Ok((descriptor_type, count))
+    }
+
+    /// Bytes covered by the members of struct `id`, from the first member's
+    /// offset to the end of the last one.
+    fn members_range(&self, id: u32) -> Option<Range<u32>> {
+        let Some(Type::Struct(members)) = self.types.get(&id) else {
+            return None;
+        };
+        members
+            .iter()
+            .enumerate()
+            .map(|(i, &member)| {
+                let key = |decoration| (id, i as u32, decoration);
+                let offset = self
+                    .member_decorations
+                    .get(&key(DECORATION_OFFSET))
+                    .copied()
+                    .unwrap_or(0);
+                let size = match self.types.get(&member) {
+                    Some(&Type::Matrix(column, columns)) => {
+                        match self.member_decorations.get(&key(DECORATION_MATRIX_STRIDE)) {
+                            Some(&stride) => stride * columns,
+                            None => self.size(column) * columns,
+                        }
+                    }
+                    _ => self.size(member),
+                };
+                offset..offset + size
+            })
+            .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end))
+    }
+
+    /// Bytes a value of type `id` takes in a block.
+    fn size(&self, id: u32) -> u32 {
+        match self.types.get(&id) {
+            Some(&Type::Scalar(size)) => size,
+            Some(&Type::Vector(component, count)) => self.size(component) * count,
+            Some(&Type::Matrix(column, columns)) => self.size(column) * columns,
+            Some(&Type::Array(element, Some(length))) => {
+                let length = self.constants.get(&length).copied().unwrap_or(1);
+                match self.decorations.get(&(id, DECORATION_ARRAY_STRIDE)) {
+                    Some(&stride) => stride * length,
+                    None => self.size(element) * length,
+                }
+            }
+            Some(Type::Struct(_)) => self.members_range(id).map_or(0, |range| range.end),
+            _ => 0,
+        }
+    }
+}
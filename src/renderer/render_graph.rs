@@ -0,0 +1,463 @@
+use ash::vk;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::{FrameStats, TransientImage, VkDevice};
+
+/// Version of an image in a `RenderGraph`: each pass writing the image
+/// returns a newer one for the passes after it to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphImage {
+    index: usize,
+    version: u32,
+}
+
+/// Last use of an image, before the graph or by one of its passes.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageState {
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+    pub layout: vk::ImageLayout,
+}
+
+impl ImageState {
+    /// Last read at `stage`, in `layout`.
+    pub fn read(stage: vk::PipelineStageFlags, layout: vk::ImageLayout) -> Self {
+        Self {
+            stage,
+            access: vk::AccessFlags::empty(),
+            layout,
+        }
+    }
+
+    /// Last written at `stage` with `access`, in `layout`.
+    pub fn written(
+        stage: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+        layout: vk::ImageLayout,
+    ) -> Self {
+        Self {
+            stage,
+            access,
+            layout,
+        }
+    }
+}
+
+/// How a pass uses an image.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageUse {
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+    /// Layout the pass needs, `UNDEFINED` when its render pass takes the
+    /// image from any layout.
+    pub layout: vk::ImageLayout,
+    /// Layout the pass leaves the image in.
+    pub final_layout: vk::ImageLayout,
+    /// Whether the pass overwrites the whole image, so its contents needn't
+    /// survive the transition.
+    pub discard: bool,
+}
+
+impl ImageUse {
+    /// Color attachment of a render pass starting from `layout` and ending in
+    /// `final_layout`.
+    pub fn color_attachment(layout: vk::ImageLayout, final_layout: vk::ImageLayout) -> Self {
+        Self {
+            stage: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            access: vk::AccessFlags::COLOR_ATTACHMENT_READ
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            layout,
+            final_layout,
+            discard: false,
+        }
+    }
+
+    /// Depth attachment of a render pass starting from `layout`, tested and
+    /// written.
+    pub fn depth_attachment(layout: vk::ImageLayout) -> Self {
+        Self {
+            stage: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            access: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            layout,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            discard: false,
+        }
+    }
+
+    /// Depth attachment only tested against.
+    pub fn depth_test() -> Self {
+        Self {
+            access: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ..Self::depth_attachment(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+        }
+    }
+
+    /// Sampled by shaders at `stage`, in `layout`.
+    pub fn sampled(stage: vk::PipelineStageFlags, layout: vk::ImageLayout) -> Self {
+        Self {
+            stage,
+            access: vk::AccessFlags::SHADER_READ,
+            layout,
+            final_layout: layout,
+            discard: false,
+        }
+    }
+
+    /// Storage image accessed by shaders at `stage`.
+    pub fn storage(stage: vk::PipelineStageFlags, access: vk::AccessFlags) -> Self {
+        Self {
+            stage,
+            access,
+            layout: vk::ImageLayout::GENERAL,
+            final_layout: vk::ImageLayout::GENERAL,
+            discard: false,
+        }
+    }
+
+    /// Cleared by a transfer command.
+    pub fn cleared() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags::TRANSFER,
+            access: vk::AccessFlags::TRANSFER_WRITE,
+            layout: vk::ImageLayout::GENERAL,
+            final_layout: vk::ImageLayout::GENERAL,
+            discard: true,
+        }
+    }
+
+    /// Copied from by a transfer command, in `TRANSFER_SRC_OPTIMAL`.
+    pub fn copied() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags::TRANSFER,
+            access: vk::AccessFlags::TRANSFER_READ,
+            layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            final_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            discard: false,
+        }
+    }
+
+    /// Written by a transfer command, in `TRANSFER_DST_OPTIMAL`.
+    pub fn copied_into() -> Self {
+        Self {
+            stage: vk::PipelineStageFlags::TRANSFER,
+            access: vk::AccessFlags::TRANSFER_WRITE,
+            layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            final_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            discard: false,
+        }
+    }
+
+    /// Same use, overwriting the whole image.
+    pub fn discarding(self) -> Self {
+        Self {
+            discard: true,
+            ..self
+        }
+    }
+}
+
+type RecordPass<'a> = Box<dyn FnOnce(vk::CommandBuffer, &mut FrameStats) + 'a>;
+
+struct Pass<'a> {
+    name: &'static str,
+    reads: Vec<(GraphImage, ImageUse)>,
+    writes: Vec<(GraphImage, ImageUse)>,
+    record: RecordPass<'a>,
+}
+
+struct Image {
+    handle: vk::Image,
+    aspect_mask: vk::ImageAspectFlags,
+    state: ImageState,
+    /// Latest version, the only one passes may write.
+    version: u32,
+    /// Memory shared with other transient images, and the bytes it takes.
+    memory: Option<(vk::DeviceMemory, Range<u64>)>,
+}
+
+/// Passes of a frame declaring the images they read and write. Executing the
+/// graph records them in an order satisfying those dependencies, with the
+/// layout transitions and barriers between them.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    images: Vec<Image>,
+    passes: Vec<Pass<'a>>,
+}
+
+const WRITE_ACCESS: vk::AccessFlags = vk::AccessFlags::from_raw(
+    vk::AccessFlags::SHADER_WRITE.as_raw()
+        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags::TRANSFER_WRITE.as_raw()
+        | vk::AccessFlags::HOST_WRITE.as_raw()
+        | vk::AccessFlags::MEMORY_WRITE.as_raw(),
+);
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an image the graph's passes use, left in `state` by whatever
+    /// used it before, like the previous frame.
+    pub fn import(
+        &mut self,
+        handle: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+        state: ImageState,
+    ) -> GraphImage {
+        self.images.push(Image {
+            handle,
+            aspect_mask,
+            state,
+            version: 0,
+            memory: None,
+        });
+        GraphImage {
+            index: self.images.len() - 1,
+            version: 0,
+        }
+    }
+
+    /// Adds a transient image, whose first use waits for the last uses of the
+    /// images sharing its memory.
+    pub fn import_transient(&mut self, image: &TransientImage) -> GraphImage {
+        let imported = self.import(image.handle, image.aspect_mask, image.previous_use);
+        self.images[imported.index].memory = Some((image.memory(), image.range.clone()));
+        imported
+    }
+
+    /// Adds a pass using `reads` as inputs and `writes` as outputs, returning
+    /// the versions it writes for the passes consuming them. `record` runs
+    /// after the passes writing the versions it uses, and before any pass
+    /// replacing the versions it reads.
+    pub fn add_pass<const W: usize>(
+        &mut self,
+        name: &'static str,
+        reads: &[(GraphImage, ImageUse)],
+        writes: [(GraphImage, ImageUse); W],
+        record: impl FnOnce(vk::CommandBuffer, &mut FrameStats) + 'a,
+    ) -> [GraphImage; W] {
+        let written = writes.map(|(image, _)| {
+            let latest = &mut self.images[image.index].version;
+            *latest = (*latest).max(image.version) + 1;
+            GraphImage {
+                index: image.index,
+                version: *latest,
+            }
+        });
+        self.passes.push(Pass {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            record: Box::new(record),
+        });
+        written
+    }
+
+    /// Records the passes in dependency order, declaration order otherwise,
+    /// each after the barriers its image uses need.
+    pub fn execute(
+        mut self,
+        device: &VkDevice,
+        cmd: vk::CommandBuffer,
+        stats: &mut FrameStats,
+    ) -> Result<(), String> {
+        let order = self.order()?;
+        let mut last_use = vec![0; self.images.len()];
+        for (position, &index) in order.iter().enumerate() {
+            let pass = &self.passes[index];
+            for &(image, _) in pass.reads.iter().chain(&pass.writes) {
+                last_use[image.index] = position;
+            }
+        }
+        let mut used = vec![false; self.images.len()];
+
+        let mut passes: Vec<Option<Pass>> = self.passes.into_iter().map(Some).collect();
+        for (position, index) in order.into_iter().enumerate() {
+            let Some(pass) = passes[index].take() else {
+                continue;
+            };
+            let mut src_stage = vk::PipelineStageFlags::empty();
+            let mut dst_stage = vk::PipelineStageFlags::empty();
+            let mut memory_barrier = vk::MemoryBarrier {
+                s_type: vk::StructureType::MEMORY_BARRIER,
+                ..Default::default()
+            };
+            let mut image_barriers: Vec<vk::ImageMemoryBarrier> = Vec::new();
+
+            for &(image, image_use) in pass.reads.iter().chain(&pass.writes) {
+                if !used[image.index] {
+                    used[image.index] = true;
+                    for alias in Self::aliases(&self.images, image.index) {
+                        if !used[alias] {
+                            continue;
+                        }
+                        if last_use[alias] > position {
+                            return Err(format!(
+                                "Render pass '{}' uses an image whose memory is still in use",
+                                pass.name
+                            ));
+                        }
+                        let state = self.images[alias].state;
+                        src_stage |= state.stage;
+                        dst_stage |= image_use.stage;
+                        memory_barrier.src_access_mask |= state.access & WRITE_ACCESS;
+                        memory_barrier.dst_access_mask |= image_use.access;
+                    }
+                }
+
+                let image = &mut self.images[image.index];
+                let state = image.state;
+                let transition = image_use.layout != vk::ImageLayout::UNDEFINED
+                    && image_use.layout != state.layout;
+                let hazard = (state.access | image_use.access).intersects(WRITE_ACCESS);
+
+                if transition {
+                    image_barriers.push(vk::ImageMemoryBarrier {
+                        s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                        old_layout: if image_use.discard {
+                            vk::ImageLayout::UNDEFINED
+                        } else {
+                            state.layout
+                        },
+                        new_layout: image_use.layout,
+                        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                        image: image.handle,
+                        subresource_range: vk::ImageSubresourceRange {
+                            aspect_mask: image.aspect_mask,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        },
+                        src_access_mask: state.access & WRITE_ACCESS,
+                        dst_access_mask: image_use.access,
+                        ..Default::default()
+                    });
+                } else if hazard {
+                    memory_barrier.src_access_mask |= state.access & WRITE_ACCESS;
+                    memory_barrier.dst_access_mask |= image_use.access;
+                }
+
+                if transition || hazard {
+                    src_stage |= state.stage;
+                    dst_stage |= image_use.stage;
+                    image.state = ImageState {
+                        stage: image_use.stage,
+                        access: image_use.access,
+                        layout: image_use.final_layout,
+                    };
+                } else {
+                    // Reads after reads only add to what the next writer
+                    // waits for.
+                    image.state.stage |= image_use.stage;
+                    image.state.layout = image_use.final_layout;
+                }
+            }
+
+            if !dst_stage.is_empty() {
+                let memory_barriers: &[vk::MemoryBarrier] =
+                    if memory_barrier.dst_access_mask.is_empty() {
+                        &[]
+                    } else {
+                        std::slice::from_ref(&memory_barrier)
+                    };
+                unsafe {
+                    device.handle.cmd_pipeline_barrier(
+                        cmd,
+                        src_stage,
+                        dst_stage,
+                        vk::DependencyFlags::empty(),
+                        memory_barriers,
+                        &[],
+                        &image_barriers,
+                    );
+                }
+            }
+            (pass.record)(cmd, stats);
+        }
+        Ok(())
+    }
+
+    /// Other images whose memory overlaps that of image `index`.
+    fn aliases(images: &[Image], index: usize) -> Vec<usize> {
+        let Some((memory, range)) = &images[index].memory else {
+            return Vec::new();
+        };
+        images
+            .iter()
+            .enumerate()
+            .filter(|&(other, image)| {
+                other != index
+                    && image
+                        .memory
+                        .as_ref()
+                        .is_some_and(|(other_memory, other_range)| {
+                            other_memory == memory
+                                && other_range.start < range.end
+                                && range.start < other_range.end
+                        })
+            })
+            .map(|(other, _)| other)
+            .collect()
+    }
+
+    /// Pass indices with each pass after the producers of the versions it
+    /// uses and, for the images it writes, after the readers of the version
+    /// it replaces.
+    fn order(&self) -> Result<Vec<usize>, String> {
+        let count = self.passes.len();
+        let mut producer = HashMap::new();
+        for (index, pass) in self.passes.iter().enumerate() {
+            for &(image, _) in &pass.writes {
+                let next = (image.index, image.version + 1);
+                if producer.insert(next, index).is_some() {
+                    return Err(format!(
+                        "Render pass '{}' writes an image version already written",
+                        pass.name
+                    ));
+                }
+            }
+        }
+
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); count];
+        for (index, pass) in self.passes.iter().enumerate() {
+            for &(image, _) in pass.reads.iter().chain(&pass.writes) {
+                if let Some(&before) = producer.get(&(image.index, image.version)) {
+                    predecessors[index].push(before);
+                }
+            }
+            for &(image, _) in &pass.reads {
+                if let Some(&writer) = producer.get(&(image.index, image.version + 1)) {
+                    if writer != index {
+                        predecessors[writer].push(index);
+                    }
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            let ready = (0..count).find(|&index| {
+                !done[index] && predecessors[index].iter().all(|&before| done[before])
+            });
+            let Some(index) = ready else {
+                let stuck = (0..count).find(|&index| !done[index]).unwrap_or(0);
+                return Err(format!(
+                    "Render pass '{}' depends on itself",
+                    self.passes[stuck].name
+                ));
+            };
+            done[index] = true;
+            order.push(index);
+        }
+        Ok(order)
+    }
+}
@@ -5,6 +5,20 @@ use crate::renderer::{find_depth_format, VkContext};
 
 use super::VkDevice;
 
+/// What a pass finds in its attachments when it begins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PassStart {
+    /// Nothing worth keeping: everything is cleared.
+    Cleared,
+    /// The color drawn so far, and the depth read by the traced occlusion.
+    AfterOcclusion,
+    /// The color, depth and stencil left by an earlier pass on the target.
+    Continued,
+    /// A cleared color, over the depth left by an earlier pass on another
+    /// color attachment.
+    OverDepth,
+}
+
 pub struct VkRenderPass {
     device: Arc<VkDevice>,
     pub handle: vk::RenderPass,
@@ -12,15 +26,185 @@ pub struct VkRenderPass {
 
 impl VkRenderPass {
     pub fn new(context: &VkContext, swapchain_format: vk::Format) -> Result<VkRenderPass, String> {
-        let color_attachment = vk::AttachmentDescription {
-            format: swapchain_format,
+        Self::create(
+            context,
+            swapchain_format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            PassStart::Cleared,
+        )
+    }
+
+    /// Continues the swapchain pass after the traced occlusion pass: keeps the
+    /// color drawn so far and takes the depth back from shader reads.
+    pub fn resume(
+        context: &VkContext,
+        swapchain_format: vk::Format,
+    ) -> Result<VkRenderPass, String> {
+        Self::create(
+            context,
+            swapchain_format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            PassStart::AfterOcclusion,
+        )
+    }
+
+    /// Continues the swapchain pass in another viewport, keeping everything
+    /// drawn so far.
+    pub fn continued(
+        context: &VkContext,
+        swapchain_format: vk::Format,
+    ) -> Result<VkRenderPass, String> {
+        Self::create(
+            context,
+            swapchain_format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            PassStart::Continued,
+        )
+    }
+
+    /// Same attachments as the swapchain pass, but leaves the color image ready
+    /// to be copied back to the host.
+    pub fn offscreen(context: &VkContext, format: vk::Format) -> Result<VkRenderPass, String> {
+        Self::create(
+            context,
+            format,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            PassStart::Cleared,
+        )
+    }
+
+    /// Same attachments as the swapchain pass, but leaves the color image ready
+    /// to be sampled by shaders.
+    pub fn sampled(context: &VkContext, format: vk::Format) -> Result<VkRenderPass, String> {
+        Self::create(
+            context,
+            format,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            PassStart::Cleared,
+        )
+    }
+
+    /// Draws motion vectors in `format` over the depth of the sampled pass,
+    /// leaving them ready to be sampled by shaders.
+    pub fn velocity(context: &VkContext, format: vk::Format) -> Result<VkRenderPass, String> {
+        Self::create(
+            context,
+            format,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            PassStart::OverDepth,
+        )
+    }
+
+    /// Depth alone in `format`, cleared, for the shadow map cascades, leaving
+    /// it ready to be sampled by the scene's fragment shaders.
+    pub fn shadow(context: &VkContext, format: vk::Format) -> Result<VkRenderPass, String> {
+        let depth_attachment = vk::AttachmentDescription {
+            format,
             samples: vk::SampleCountFlags::TYPE_1,
             load_op: vk::AttachmentLoadOp::CLEAR,
             store_op: vk::AttachmentStoreOp::STORE,
             stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
             stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
             initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ..Default::default()
+        };
+
+        let depth_attachment_ref = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+
+        let subpass = vk::SubpassDescription {
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            p_depth_stencil_attachment: &depth_attachment_ref,
+            ..Default::default()
+        };
+
+        // Waits for the previous frame's lookups before clearing, and makes
+        // the depth visible to this frame's.
+        let dependencies = [
+            vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                src_access_mask: vk::AccessFlags::empty(),
+                dst_stage_mask: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                dst_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ..Default::default()
+            },
+            vk::SubpassDependency {
+                src_subpass: 0,
+                dst_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                ..Default::default()
+            },
+        ];
+
+        let render_pass_create_info = vk::RenderPassCreateInfo {
+            s_type: vk::StructureType::RENDER_PASS_CREATE_INFO,
+            attachment_count: 1,
+            p_attachments: &depth_attachment,
+            subpass_count: 1,
+            p_subpasses: &subpass,
+            dependency_count: dependencies.len() as u32,
+            p_dependencies: dependencies.as_ptr(),
+            ..Default::default()
+        };
+
+        let device = context.device();
+        let handle = unsafe {
+            device
+                .handle
+                .create_render_pass(&render_pass_create_info, None)
+                .map_err(|e| format!("Failed to create shadow render pass: {}", e))?
+        };
+
+        return Ok(VkRenderPass { device, handle });
+    }
+
+    fn create(
+        context: &VkContext,
+        format: vk::Format,
+        final_layout: vk::ImageLayout,
+        start: PassStart,
+    ) -> Result<VkRenderPass, String> {
+        let (load_op, color_layout, depth_layout) = match start {
+            PassStart::Cleared => (
+                vk::AttachmentLoadOp::CLEAR,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::UNDEFINED,
+            ),
+            PassStart::AfterOcclusion => (
+                vk::AttachmentLoadOp::LOAD,
+                final_layout,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ),
+            PassStart::Continued | PassStart::OverDepth => (
+                vk::AttachmentLoadOp::LOAD,
+                final_layout,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ),
+        };
+        let (color_load_op, color_layout) = if start == PassStart::OverDepth {
+            (vk::AttachmentLoadOp::CLEAR, vk::ImageLayout::UNDEFINED)
+        } else {
+            (load_op, color_layout)
+        };
+
+        let color_attachment = vk::AttachmentDescription {
+            format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: color_load_op,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: color_layout,
+            final_layout,
             ..Default::default()
         };
 
@@ -32,11 +216,22 @@ impl VkRenderPass {
         let depth_attachment = vk::AttachmentDescription {
             format: find_depth_format(&context.instance, &context.physical_device)?,
             samples: vk::SampleCountFlags::TYPE_1,
-            load_op: vk::AttachmentLoadOp::CLEAR,
+            load_op,
             store_op: vk::AttachmentStoreOp::STORE,
-            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-            initial_layout: vk::ImageLayout::UNDEFINED,
+            // The ground reflection's mask, kept for the next viewports but
+            // unused once the pass resumes after the traced occlusion.
+            stencil_load_op: match start {
+                PassStart::Cleared => vk::AttachmentLoadOp::CLEAR,
+                PassStart::AfterOcclusion | PassStart::OverDepth => vk::AttachmentLoadOp::DONT_CARE,
+                PassStart::Continued => vk::AttachmentLoadOp::LOAD,
+            },
+            stencil_store_op: match start {
+                PassStart::AfterOcclusion | PassStart::OverDepth => {
+                    vk::AttachmentStoreOp::DONT_CARE
+                }
+                PassStart::Cleared | PassStart::Continued => vk::AttachmentStoreOp::STORE,
+            },
+            initial_layout: depth_layout,
             final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
             ..Default::default()
         };
@@ -54,17 +249,65 @@ impl VkRenderPass {
             ..Default::default()
         };
 
-        let dependency = vk::SubpassDependency {
-            src_subpass: vk::SUBPASS_EXTERNAL,
-            dst_subpass: 0,
-            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            src_access_mask: vk::AccessFlags::empty(),
-            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
-                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-            ..Default::default()
+        let dependency = match start {
+            // Waits for the first half's color writes and the compute pass's
+            // depth reads.
+            PassStart::AfterOcclusion => vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::COMPUTE_SHADER,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+                    | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ..Default::default()
+            },
+            // Waits for the earlier viewports' color and depth writes.
+            PassStart::Continued => vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+                    | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ..Default::default()
+            },
+            // Waits for the depth writes, and for the previous frame's
+            // resolve to be done reading the color.
+            PassStart::OverDepth => vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::COMPUTE_SHADER,
+                src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+                ..Default::default()
+            },
+            PassStart::Cleared => vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                src_access_mask: vk::AccessFlags::empty(),
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ..Default::default()
+            },
         };
 
         let attachments = [color_attachment, depth_attachment];
@@ -1,84 +1,349 @@
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Instant;
 
 use ash::vk;
 
-use super::query_swapchain_support;
-use super::MAX_FRAMES_IN_FLIGHT;
+use super::{depth_aspects, query_swapchain_support};
 use super::{
-    FrameData, GpuGroup, GpuMesh, MaterialPushConstants, MeshPushConstants, ResourcesManager,
-    VkCommandPool, VkContext, VkDescriptorPool, VkDescriptorSetLayout, VkPipeline, VkQueue,
-    VkRenderPass, VkSwapchain,
+    Cascades, DescriptorLayouts, DisplayAdjustments, DynamicBuffer, EngineEvent, EngineEvents,
+    FaceSort, FrameData, FramePass, GpuGroup, GpuMaterial, GpuMesh, GraphImage, GroupKey,
+    ImageState, ImageUse, Matcap, MaterialPushConstants, MaterialSet, MeshPipelineKey,
+    MeshPushConstants, MeshShading, MeshVariant, OcclusionCulling, PipelineManager, PluginFrame,
+    PluginSetup, RenderGraph, RenderPlugin, ResourcesManager, ShadingMode, ShadowCascades,
+    ShadowQuality, SurfaceAnalysis, SurfaceWindow, SyncTimings, TemporalResolve, TextureHandle,
+    TracedOcclusion, TransientDesc, TransientImage, Viewport, VkCommandPool, VkContext,
+    VkDescriptorPool, VkOffscreenTarget, VkPipeline, VkQueue, VkRenderPass, VkSurface, VkSwapchain,
+    VELOCITY_FORMAT,
 };
+use super::{FRAME_SET, MAX_CASCADES, MAX_FRAMES_IN_FLIGHT, MAX_JOINTS};
 use crate::camera::Camera;
-use crate::math::Mat4;
-use crate::scene::Scene;
-
-use sdl3::video::Window;
+use crate::math::{Aabb, Mat4, Vec4};
+use crate::scene::{GroundReflection, LayerDepth, Light, Object, RenderLayer, Scene};
+use crate::{breadcrumb, crash};
 
+#[repr(C)]
 pub struct Uniforms {
     pub view: Mat4,
     pub proj: Mat4,
+    pub light_position: Vec4,
+    pub light_color: Vec4,
+    pub shading_mode: u32,
+    /// Texture read by matcap shading.
+    pub matcap: u32,
+    /// Height map scale of displaced materials, in world units.
+    pub displacement: f32,
+    /// `DisplayAdjustments` of the shaded colors.
+    pub exposure: f32,
+    pub gamma: f32,
+    pub brightness: f32,
+    pub contrast: f32,
+    /// `SurfaceAnalysis` of the analysis shading modes, the deviation angle
+    /// in radians.
+    pub curvature_radius: f32,
+    pub analysis_axis: Vec4,
+    pub deviation_angle: f32,
+    /// Angle the light's disk spans, in radians, and shadow map taps per
+    /// fragment, see `ShadowQuality`.
+    pub light_size: f32,
+    pub shadow_samples: u32,
+    /// `Cascades` of the shadow map, none when 0.
+    pub cascade_count: u32,
+    pub cascade_view_proj: [Mat4; MAX_CASCADES],
+    pub cascade_splits: Vec4,
+}
+
+impl Uniforms {
+    pub fn new(
+        camera: &Camera,
+        light: &Light,
+        shading_mode: ShadingMode,
+        matcap: TextureHandle,
+        displacement: f32,
+        display: DisplayAdjustments,
+        analysis: SurfaceAnalysis,
+        shadows: ShadowQuality,
+        light_size: f32,
+        cascades: &Cascades,
+    ) -> Self {
+        let p = light.position;
+        let c = light.color * light.intensity;
+        Self {
+            view: camera.get_view_matrix(),
+            proj: camera.get_projection_matrix(),
+            light_position: Vec4::new(p.x, p.y, p.z, 1.),
+            light_color: Vec4::new(c.x, c.y, c.z, 1.),
+            shading_mode: shading_mode as u32,
+            matcap: matcap as u32,
+            displacement,
+            exposure: display.exposure,
+            gamma: display.gamma,
+            brightness: display.brightness,
+            contrast: display.contrast,
+            curvature_radius: analysis.curvature_radius,
+            analysis_axis: Vec4::new(analysis.axis.x, analysis.axis.y, analysis.axis.z, 0.),
+            deviation_angle: analysis.deviation_angle.to_radians(),
+            light_size: light_size.to_radians(),
+            shadow_samples: shadows.samples(),
+            cascade_count: cascades.count,
+            cascade_view_proj: cascades.view_proj,
+            cascade_splits: cascades.splits,
+        }
+    }
+}
+
+/// Ink color of the toon shading outlines.
+const OUTLINE_COLOR: Vec4 = Vec4 {
+    x: 0.02,
+    y: 0.02,
+    z: 0.02,
+    w: 1.,
+};
+/// Outline color of the selected object, also blended into its materials.
+const SELECTION_COLOR: Vec4 = Vec4 {
+    x: 1.,
+    y: 0.55,
+    z: 0.1,
+    w: 1.,
+};
+const SELECTION_TINT: f32 = 0.3;
+/// Color of the meshes' line segments.
+const MESH_LINE_COLOR: Vec4 = Vec4 {
+    x: 0.85,
+    y: 0.85,
+    z: 0.85,
+    w: 1.,
+};
+
+/// An object queued for drawing, with its skin's first joint matrix.
+#[derive(Clone, Copy)]
+struct MeshDraw<'a> {
+    object: &'a Object,
+    index: usize,
+    /// Groups skipped this frame, see `OcclusionCulling`.
+    culled: &'a HashSet<GroupKey>,
+    joint_offset: Option<u32>,
+    /// Pipeline variant reading the mesh's vertices.
+    variant: MeshVariant,
+    selected: bool,
+    /// Mirror the object is drawn through, instead of in place.
+    reflection: Option<&'a GroundReflection>,
+}
+
+/// What `draw_meshes` draws, under the cull mode already set.
+#[derive(Clone, Copy)]
+enum MeshPass {
+    /// One-sided groups, with front faces culled.
+    BackFaces,
+    /// One-sided groups with back faces culled, then two-sided ones unculled.
+    FrontFaces,
+    /// Every group in a solid color.
+    Outline(Vec4),
+}
+
+/// Where `scene_passes` draws.
+#[derive(Clone, Copy)]
+struct SceneTarget {
+    render_pass: vk::RenderPass,
+    /// Pass continuing the target after the traced occlusion, `None` to draw
+    /// without it.
+    resume_pass: Option<vk::RenderPass>,
+    /// Pass continuing the target in the next viewport, `None` to draw the
+    /// whole target from the main camera.
+    viewport_pass: Option<vk::RenderPass>,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    /// Whether the HUD text is drawn over the scene, `false` when it is added
+    /// after the temporal resolve.
+    hud: bool,
+    color: GraphImage,
+    /// Layout the render passes leave the color in.
+    color_layout: vk::ImageLayout,
+    depth: GraphImage,
 }
 
+/// Images of `Renderer::create_transients`.
+struct FrameTransients {
+    overdraw: TransientImage,
+    occlusion: TransientImage,
+    /// Present with the temporal resolve.
+    velocity: Option<TransientImage>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    /// Groups skipped as hidden by occlusion culling.
+    pub occluded_groups: u32,
+}
+
+// Fields drop in declaration order: the swapchain goes before its surface.
 pub struct Renderer {
     frames: Vec<FrameData>,
     frame: usize,
+    uniforms: DynamicBuffer<Uniforms>,
+    joints: DynamicBuffer<Mat4>,
+    shading_mode: ShadingMode,
+    matcap: Matcap,
+    stats: FrameStats,
+    sync: SyncTimings,
+    /// When the previous frame started, for `SyncTimings::interval`.
+    last_frame: Option<Instant>,
 
     command_pool: VkCommandPool,
     swapchain: VkSwapchain,
-    pipeline: VkPipeline,
+    /// Mesh pipelines of every shading and vertex layout drawn so far.
+    /// Without fragment shader stores the overdraw view falls back to lit
+    /// shading, and without tessellation shaders height maps are ignored.
+    pipelines: PipelineManager,
+    displacement: f32,
+    display: DisplayAdjustments,
+    analysis: SurfaceAnalysis,
+    shadows: ShadowQuality,
+    /// In degrees, see `ShadowQuality`.
+    light_size: f32,
+    shadow_cascades: ShadowCascades,
+    reflection_mask: VkPipeline,
+    /// Whether triangles are drawn sorted back to front on the CPU instead
+    /// of depth tested.
+    face_sorted: bool,
+    /// Fragment counters of the overdraw view, sized like the swapchain.
+    overdraw: TransientImage,
+    traced_occlusion: TracedOcclusion,
+    /// Whether the regular shading modes get the traced occlusion.
+    occlusion_traced: bool,
+    occlusion_culling: OcclusionCulling,
+    /// Temporal anti-aliasing and upscaling, `None` when off.
+    temporal: Option<TemporalResolve>,
+    line_pipeline: VkPipeline,
+    voxel_pipeline: VkPipeline,
+    text_pipeline: VkPipeline,
     render_pass: VkRenderPass,
+    viewport_pass: VkRenderPass,
+    /// Cameras drawn side by side, the whole window from the main camera
+    /// when empty.
+    viewports: Vec<Viewport>,
+    /// Drawn over every frame of the window, in the order added.
+    plugins: Vec<Box<dyn RenderPlugin>>,
+    events: EngineEvents,
     descriptor_pool: VkDescriptorPool,
-    descriptor_set_layout: VkDescriptorSetLayout,
+    /// Textures of every material, bound next to the frame's set.
+    material_set: MaterialSet,
+    descriptor_layouts: DescriptorLayouts,
     present_queue: VkQueue,
     graphics_queue: VkQueue,
+    /// Window surface presented to, `None` while suspended.
+    surface: Option<VkSurface>,
     context: Arc<VkContext>,
 }
 
 impl Renderer {
-    pub fn new(window: &Window, context: Arc<VkContext>) -> Result<Renderer, String> {
+    /// Initial height map scale, in world units.
+    pub const DEFAULT_DISPLACEMENT: f32 = 1.;
+
+    pub fn new(
+        window: &dyn SurfaceWindow,
+        context: Arc<VkContext>,
+        events: EngineEvents,
+    ) -> Result<Renderer, String> {
         let graphics_queue = VkQueue::new(context.device(), context.graphics_family());
         let present_queue = VkQueue::new(context.device(), context.present_family());
 
+        let surface = VkSurface::new(window, &context.entry, &context.instance)?;
         let support_details = query_swapchain_support(
             &context.physical_device.handle,
-            &context.surface.loader,
-            &context.surface.handle,
+            &surface.loader,
+            &surface.handle,
         )?;
 
         let capabilities = support_details.capabilities;
         let surface_format = Renderer::choose_surface_format(&support_details.formats);
         let present_mode = Renderer::choose_present_mode(&support_details.present_modes);
-        let (width, height) = window.size().into();
+        let (width, height) = window.size();
         let extent = Renderer::choose_extent(&support_details.capabilities, width, height);
 
         let render_pass = VkRenderPass::new(&context, surface_format.format)?;
+        let viewport_pass = VkRenderPass::continued(&context, surface_format.format)?;
         let swapchain = VkSwapchain::new(
             &context,
+            surface.handle,
             &render_pass,
             capabilities,
             surface_format,
             present_mode,
             extent,
         )?;
+        crash::set_swapchain(Self::swapchain_state(&swapchain));
 
-        let descriptor_set_layout = VkDescriptorSetLayout::new(context.device())?;
-        let pipeline = VkPipeline::new(context.device(), &render_pass, &descriptor_set_layout)?;
+        let descriptor_layouts = DescriptorLayouts::new(context.device())?;
+        // The material pipelines draw every frame, so shader errors still
+        // fail here rather than on the first frame.
+        let pipelines = PipelineManager::new(context.device());
+        for variant in MeshVariant::ALL {
+            let key = MeshPipelineKey::new(MeshShading::Material, variant);
+            pipelines.preload(&render_pass, &descriptor_layouts, key)?;
+        }
+        if !context.device.fragment_stores {
+            eprintln!("Overdraw view unavailable: no fragment shader stores on this device");
+        }
+        if !context.device.tessellation {
+            eprintln!("Displacement maps unavailable: no tessellation shaders on this device");
+        }
+        let reflection_mask =
+            VkPipeline::reflection_mask(context.device(), &render_pass, &descriptor_layouts)?;
+        let transients = Renderer::create_transients(&context, extent, None)?;
+        let overdraw = transients.overdraw;
+        let traced_occlusion = TracedOcclusion::new(
+            &context,
+            &render_pass,
+            &descriptor_layouts,
+            surface_format.format,
+            transients.occlusion,
+        )?;
+        let occlusion_culling =
+            OcclusionCulling::new(context.device(), &render_pass, &descriptor_layouts)?;
+        let line_pipeline = VkPipeline::lines(context.device(), &render_pass, &descriptor_layouts)?;
+        let voxel_pipeline =
+            VkPipeline::voxels(context.device(), &render_pass, &descriptor_layouts)?;
+        let text_pipeline = VkPipeline::text(context.device(), &render_pass, &descriptor_layouts)?;
         let command_pool = VkCommandPool::new(
             context.device(),
             context.graphics_family(),
             vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
         )?;
+        let shadow_cascades = ShadowCascades::new(
+            &context,
+            &graphics_queue,
+            &command_pool,
+            &descriptor_layouts,
+            0,
+        )?;
+
+        let descriptor_pool = VkDescriptorPool::new(
+            context.device(),
+            &descriptor_layouts.frame,
+            MAX_FRAMES_IN_FLIGHT,
+        )?;
+        let material_set = MaterialSet::new(context.device(), &descriptor_layouts)?;
 
-        let descriptor_pool = VkDescriptorPool::new(context.device(), MAX_FRAMES_IN_FLIGHT)?;
+        let uniforms = DynamicBuffer::new(
+            &context,
+            1,
+            vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+        )?;
+        let joints =
+            DynamicBuffer::new(&context, MAX_JOINTS, vk::BufferUsageFlags::STORAGE_BUFFER)?;
 
         let frames = (0..MAX_FRAMES_IN_FLIGHT as usize)
-            .map(|_| {
+            .map(|index| {
                 FrameData::new(
                     &context,
+                    index,
                     &command_pool,
                     &descriptor_pool,
-                    &descriptor_set_layout,
+                    &descriptor_layouts.frame,
+                    (&uniforms, &joints),
                 )
             })
             .collect::<Result<Vec<_>, _>>()?;
@@ -87,52 +352,419 @@ impl Renderer {
             context,
             graphics_queue,
             present_queue,
+            surface: Some(surface),
             swapchain,
             render_pass,
-            pipeline,
+            viewport_pass,
+            viewports: Vec::new(),
+            plugins: Vec::new(),
+            events,
+            pipelines,
+            displacement: Self::DEFAULT_DISPLACEMENT,
+            display: DisplayAdjustments::default(),
+            analysis: SurfaceAnalysis::default(),
+            shadows: ShadowQuality::default(),
+            light_size: ShadowQuality::DEFAULT_LIGHT_SIZE,
+            shadow_cascades,
+            reflection_mask,
+            face_sorted: false,
+            overdraw,
+            traced_occlusion,
+            occlusion_traced: false,
+            occlusion_culling,
+            temporal: None,
+            line_pipeline,
+            voxel_pipeline,
+            text_pipeline,
             command_pool,
             descriptor_pool,
-            descriptor_set_layout,
+            material_set,
+            descriptor_layouts,
             frames,
             frame: 0,
+            uniforms,
+            joints,
+            shading_mode: ShadingMode::default(),
+            matcap: Matcap::default(),
+            stats: FrameStats::default(),
+            sync: SyncTimings::default(),
+            last_frame: None,
         })
     }
 
-    fn sync_textures(&self, resources: &ResourcesManager) {
-        let set = self.frames[self.frame].descriptor_set;
-        for (handle, texture) in resources.textures.iter().enumerate() {
-            self.descriptor_pool.update_texture(set, handle, texture);
+    /// Draw submission counters of the last recorded frame.
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    /// Waits of the last drawn frame.
+    pub fn sync_timings(&self) -> SyncTimings {
+        self.sync
+    }
+
+    pub fn shading_mode(&self) -> ShadingMode {
+        self.shading_mode
+    }
+
+    pub fn set_shading_mode(&mut self, mode: ShadingMode) {
+        self.shading_mode = mode;
+    }
+
+    pub fn matcap(&self) -> Matcap {
+        self.matcap
+    }
+
+    pub fn set_matcap(&mut self, matcap: Matcap) {
+        self.matcap = matcap;
+    }
+
+    /// Height map scale of displaced materials, `None` when the device can't
+    /// tessellate them.
+    pub fn displacement(&self) -> Option<f32> {
+        self.context
+            .device
+            .tessellation
+            .then_some(self.displacement)
+    }
+
+    pub fn set_displacement(&mut self, displacement: f32) {
+        self.displacement = displacement;
+    }
+
+    pub fn display_adjustments(&self) -> DisplayAdjustments {
+        self.display
+    }
+
+    pub fn set_display_adjustments(&mut self, display: DisplayAdjustments) {
+        self.display = display;
+    }
+
+    pub fn surface_analysis(&self) -> SurfaceAnalysis {
+        self.analysis
+    }
+
+    pub fn set_surface_analysis(&mut self, analysis: SurfaceAnalysis) {
+        self.analysis = analysis;
+    }
+
+    pub fn shadow_quality(&self) -> ShadowQuality {
+        self.shadows
+    }
+
+    pub fn set_shadow_quality(&mut self, shadows: ShadowQuality) {
+        self.shadows = shadows;
+    }
+
+    pub fn light_size(&self) -> f32 {
+        self.light_size
+    }
+
+    pub fn set_light_size(&mut self, degrees: f32) {
+        self.light_size = degrees;
+    }
+
+    /// Shadow map cascades drawn each frame, 0 when off.
+    pub fn shadow_cascades(&self) -> usize {
+        self.shadow_cascades.count()
+    }
+
+    /// Draws the light's depth into `count` shadow map cascades, up to
+    /// `MAX_CASCADES`, which replace the traced shadows; 0 turns them off.
+    /// Skipped with viewports.
+    pub fn set_shadow_cascades(&mut self, count: usize) -> Result<(), String> {
+        self.wait_idle();
+        self.shadow_cascades.set_count(
+            &self.context,
+            &self.graphics_queue,
+            &self.command_pool,
+            count,
+        )
+    }
+
+    /// Whether the shadow map cascades are drawn this frame, not with
+    /// viewports or in the overdraw view.
+    fn draws_cascades(&self) -> bool {
+        self.shadow_cascades.count() > 0
+            && self.viewports.is_empty()
+            && self.shading_mode != ShadingMode::Overdraw
+    }
+
+    /// Whether groups with a height map go through the displaced pipelines
+    /// this frame.
+    fn displaces(&self) -> bool {
+        self.context.device.tessellation && self.shading_mode != ShadingMode::Overdraw
+    }
+
+    /// Whether the depth buffer has the stencil masking the ground reflection.
+    pub fn reflection_supported(&self) -> bool {
+        depth_aspects(self.swapchain.depth_image.format).contains(vk::ImageAspectFlags::STENCIL)
+    }
+
+    /// Whether this frame is drawn in the overdraw view, which needs fragment
+    /// shader stores.
+    fn draws_overdraw(&self) -> bool {
+        self.context.device.fragment_stores && self.shading_mode == ShadingMode::Overdraw
+    }
+
+    /// Mesh pipeline of `shading` over `variant`, `None` when it failed to
+    /// build.
+    fn mesh_pipeline(&self, shading: MeshShading, variant: MeshVariant) -> Option<Arc<VkPipeline>> {
+        let key = MeshPipelineKey::new(shading, variant);
+        self.pipelines
+            .get(&self.render_pass, &self.descriptor_layouts, key)
+    }
+
+    /// Whether the ground reflection is drawn this frame, not in the overdraw
+    /// or face-sorted views.
+    fn reflects(&self) -> bool {
+        self.reflection_supported() && self.shading_mode != ShadingMode::Overdraw && !self.sorts()
+    }
+
+    /// Draws each of `viewports` in its part of the window instead of the
+    /// main camera, without occlusion culling or traced occlusion.
+    pub fn set_viewports(&mut self, viewports: Vec<Viewport>) {
+        self.viewports = viewports;
+    }
+
+    /// Sets `plugin` up and draws it over the following frames.
+    pub fn add_plugin(&mut self, mut plugin: Box<dyn RenderPlugin>) -> Result<(), String> {
+        plugin.setup(&PluginSetup {
+            context: &self.context,
+            render_pass: self.viewport_pass.handle,
+            color_format: self.swapchain.image_format,
+            frames_in_flight: MAX_FRAMES_IN_FLIGHT as usize,
+        })?;
+        self.plugins.push(plugin);
+        Ok(())
+    }
+
+    /// Removes the plugins, to add them to another renderer.
+    pub fn take_plugins(&mut self) -> Vec<Box<dyn RenderPlugin>> {
+        self.wait_idle();
+        std::mem::take(&mut self.plugins)
+    }
+
+    pub fn face_sorted(&self) -> bool {
+        self.face_sorted
+    }
+
+    /// Draws every triangle sorted back to front from the main camera each
+    /// frame, without depth test, instead of the regular passes. Skips the
+    /// outlines, reflection and displacement.
+    pub fn set_face_sorted(&mut self, sorted: bool) {
+        self.face_sorted = sorted;
+    }
+
+    /// Whether this frame is face-sorted, not in the overdraw view.
+    fn sorts(&self) -> bool {
+        self.face_sorted && self.shading_mode != ShadingMode::Overdraw
+    }
+
+    pub fn occlusion_traced(&self) -> bool {
+        self.occlusion_traced
+    }
+
+    pub fn set_occlusion_traced(&mut self, traced: bool) {
+        self.occlusion_traced = traced;
+    }
+
+    /// Scale of the temporal upscaling's target, `None` when temporal
+    /// anti-aliasing is off.
+    pub fn temporal_scale(&self) -> Option<f32> {
+        self.temporal.as_ref().map(TemporalResolve::scale)
+    }
+
+    /// Draws the scene at `scale` times the window's resolution, jittered,
+    /// and accumulates the frames at full resolution; `None` draws it
+    /// directly. Skipped with viewports, and replaces the traced occlusion.
+    pub fn set_temporal(&mut self, scale: Option<f32>) -> Result<(), String> {
+        self.wait_idle();
+        self.temporal = None;
+        let scale = scale.map(|scale| scale.clamp(0.25, 1.));
+        let velocity = self.reallocate_transients(scale)?;
+        if let (Some(scale), Some(velocity)) = (scale, velocity) {
+            self.temporal = Some(TemporalResolve::new(
+                &self.context,
+                &self.render_pass,
+                &self.descriptor_layouts,
+                self.swapchain.image_format,
+                self.swapchain.extent,
+                scale,
+                velocity,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Brings the current frame's descriptors, uniforms, debug lines and HUD
+    /// text up to date before recording. With `jittered`, the frame goes
+    /// through the temporal resolve when it is on.
+    fn prepare_frame(
+        &mut self,
+        camera: &Camera,
+        scene: &Scene,
+        resources: &ResourcesManager,
+        jittered: bool,
+    ) -> Result<(), String> {
+        let jittered = jittered && self.temporal.is_some() && self.viewports.is_empty();
+        let traces_occlusion = self.occlusion_traced
+            && ShadingMode::STYLES.contains(&self.shading_mode)
+            && self.viewports.is_empty()
+            && !jittered;
+        if traces_occlusion {
+            self.traced_occlusion.update_geometry(
+                &self.context,
+                &self.graphics_queue,
+                &self.command_pool,
+                resources,
+            )?;
+        }
+
+        self.occlusion_culling
+            .update(&mut self.frames[self.frame], camera, scene, resources);
+
+        let mut uniforms = self.uniforms_for(camera, scene, resources);
+        let frame = &mut self.frames[self.frame];
+        frame.temporal = None;
+        if let Some(temporal) = self.temporal.as_mut().filter(|_| jittered) {
+            let models = scene.objects.iter().map(Object::transform).collect();
+            let (jitter, resolve, previous) =
+                temporal.next_frame(uniforms.proj * uniforms.view, models);
+            uniforms.proj = jitter * uniforms.proj;
+            frame.temporal = Some(resolve);
+            frame.previous_transforms = previous;
+            self.descriptor_pool
+                .update_temporal(frame.descriptor_set, temporal);
+        }
+
+        let face_sort = self
+            .sorts()
+            .then(|| FaceSort::build(camera, scene, resources));
+        let frame = &mut self.frames[self.frame];
+        if !self.viewports.is_empty() || face_sort.is_some() {
+            // Probes and culling only hold for the main camera, against
+            // the depth face sorting doesn't write.
+            frame.occlusion_probes.clear();
+            frame.culled_groups.clear();
         }
+        if let Some(structure) = &mut frame.scene_structure {
+            structure.update(scene, resources);
+        }
+        frame.occlusion = None;
+        if traces_occlusion {
+            frame.occlusion = self
+                .traced_occlusion
+                .upload_instances(frame.index, scene, resources);
+            self.descriptor_pool.update_traced_occlusion(
+                frame.descriptor_set,
+                self.swapchain.depth_view,
+                &self.traced_occlusion,
+                frame.index,
+            );
+        }
+        self.material_set.update(&resources.textures);
+        self.descriptor_pool
+            .update_environment(frame.descriptor_set, &resources.environment);
+        self.descriptor_pool
+            .update_overdraw(frame.descriptor_set, &self.overdraw);
+        self.descriptor_pool
+            .update_shadow_cascades(frame.descriptor_set, &self.shadow_cascades);
+        frame.update_uniforms(&self.uniforms, uniforms);
+        frame.upload_joints(&self.joints, &scene.skins)?;
+        frame.upload_lines(&self.context, &scene.debug_lines.vertices)?;
+        frame.upload_voxels(&self.context, &scene.voxels.instances)?;
+        frame.upload_face_sort(&self.context, face_sort)?;
+        frame.upload_text(&self.context, &scene.hud)
+    }
+
+    /// Uniforms of `camera`, with the shadow map cascades fitted to its view
+    /// of the scene when they are drawn.
+    fn uniforms_for(
+        &self,
+        camera: &Camera,
+        scene: &Scene,
+        resources: &ResourcesManager,
+    ) -> Uniforms {
+        let cascades = if self.draws_cascades() {
+            let bounds = scene.objects.iter().fold(Aabb::EMPTY, |bounds, object| {
+                bounds.union(
+                    resources
+                        .get_mesh(object.id())
+                        .bounds
+                        .transform(&object.transform()),
+                )
+            });
+            let count = self.shadow_cascades.count();
+            Cascades::fit(camera, &scene.light, &bounds, count)
+        } else {
+            Cascades::default()
+        };
+        Uniforms::new(
+            camera,
+            &scene.light,
+            self.shading_mode,
+            ResourcesManager::matcap_texture(self.matcap),
+            self.displacement,
+            self.display,
+            self.analysis,
+            self.shadows,
+            self.light_size,
+            &cascades,
+        )
     }
 
     pub fn draw(
         &mut self,
-        window: &Window,
+        window: &dyn SurfaceWindow,
         camera: &Camera,
         scene: &Scene,
         resources: &ResourcesManager,
     ) -> Result<(), String> {
+        if self.surface.is_none() {
+            self.resume(window)?;
+        }
+        let start = Instant::now();
+        let interval = self
+            .last_frame
+            .replace(start)
+            .map_or(0., |last| start.duration_since(last).as_secs_f32());
+        crash::next_frame();
         self.wait_for_frame()?;
+        let fence_wait = start.elapsed().as_secs_f32();
 
-        let image_index = match self.acquire_image()? {
-            Some(index) => index,
+        let acquiring = Instant::now();
+        let acquired = self.acquire_image()?;
+        let acquire = acquiring.elapsed().as_secs_f32();
+        let (image_index, suboptimal) = match acquired {
+            Some(acquired) => acquired,
             None => {
-                let (w, h) = window.size().into();
-                self.resize(w, h)?;
+                self.recreate_swapchain(window)?;
                 return Ok(());
             }
         };
 
-        self.sync_textures(resources);
-
-        self.frames[self.frame].update_uniforms(camera);
+        self.prepare_frame(camera, scene, resources, true)?;
         self.reset_frame()?;
-        self.record(image_index, scene, resources)?;
+        // Out of the renderer while recording, which borrows it whole.
+        let mut plugins = std::mem::take(&mut self.plugins);
+        let recorded = self.record(image_index, camera, scene, resources, &mut plugins);
+        self.plugins = plugins;
+        self.stats = recorded?;
         self.submit()?;
 
-        if self.present(image_index)? {
-            let (w, h) = window.size().into();
-            self.resize(w, h)?;
+        // A suboptimal image is still presentable, so the swapchain is only
+        // recreated once the frame is out instead of dropping it.
+        let presenting = Instant::now();
+        let out_of_date = self.present(image_index)?;
+        self.events.emit(|| EngineEvent::FrameRendered(self.stats));
+        self.sync = SyncTimings {
+            fence_wait,
+            acquire,
+            present: presenting.elapsed().as_secs_f32(),
+            interval,
+        };
+        if out_of_date || suboptimal {
+            self.recreate_swapchain(window)?;
         }
 
         self.frame = (self.frame + 1) % MAX_FRAMES_IN_FLIGHT as usize;
@@ -146,11 +778,16 @@ impl Renderer {
                 .device()
                 .handle
                 .wait_for_fences(&[fence], true, u64::MAX)
-                .map_err(|e| format!("Failed to wait for fence: {}", e))
+                .map_err(|e| format!("Failed to wait for fence: {}", e))?;
         }
+        breadcrumb!("vkWaitForFences");
+        Ok(())
     }
 
-    fn acquire_image(&self) -> Result<Option<u32>, String> {
+    /// Next swapchain image and whether it is suboptimal, or `None` when the
+    /// swapchain is out of date or its surface lost and must be recreated
+    /// first.
+    fn acquire_image(&self) -> Result<Option<(u32, bool)>, String> {
         let semaphore = self.frames[self.frame].image_available.handle;
         match unsafe {
             self.swapchain.loader.acquire_next_image(
@@ -160,8 +797,11 @@ impl Renderer {
                 vk::Fence::null(),
             )
         } {
-            Ok((index, false)) => Ok(Some(index)),
-            Ok((_, true)) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(None),
+            Ok(acquired) => {
+                breadcrumb!("vkAcquireNextImageKHR");
+                Ok(Some(acquired))
+            }
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::ERROR_SURFACE_LOST_KHR) => Ok(None),
             Err(e) => Err(format!("Failed to acquire next image: {:?}", e)),
         }
     }
@@ -206,9 +846,11 @@ impl Renderer {
     fn record(
         &self,
         image_index: u32,
+        camera: &Camera,
         scene: &Scene,
         resources: &ResourcesManager,
-    ) -> Result<(), String> {
+        plugins: &mut [Box<dyn RenderPlugin>],
+    ) -> Result<FrameStats, String> {
         let frame = &self.frames[self.frame];
         let cmd = frame.command_buffer;
         let device = &self.context.device;
@@ -226,125 +868,1485 @@ impl Renderer {
                 .map_err(|e| format!("Failed to begin command buffer: {}", e))?;
         }
 
-        self.begin_render_pass(cmd, image_index);
-        self.bind_pipeline_and_viewport(cmd, frame);
+        // The swapchain image is only written once acquired, when the
+        // submission's wait on the color output stage is over.
+        let mut graph = RenderGraph::new();
+        let color = graph.import(
+            self.swapchain.images[image_index as usize],
+            vk::ImageAspectFlags::COLOR,
+            ImageState::read(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::ImageLayout::UNDEFINED,
+            ),
+        );
+        let depth_image = &self.swapchain.depth_image;
+        let depth = graph.import(
+            depth_image.handle,
+            depth_aspects(depth_image.format),
+            ImageState::read(
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::ImageLayout::UNDEFINED,
+            ),
+        );
+        let framebuffer = self.swapchain.framebuffers[image_index as usize];
+        let extent = self.swapchain.extent;
+        let target = SceneTarget {
+            render_pass: self.render_pass.handle,
+            resume_pass: frame
+                .occlusion
+                .map(|_| self.traced_occlusion.resume_pass.handle),
+            viewport_pass: Some(self.viewport_pass.handle),
+            framebuffer,
+            extent,
+            hud: true,
+            color,
+            color_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            depth,
+        };
+        let [color, depth] = match self.temporal.as_ref().filter(|_| frame.temporal.is_some()) {
+            Some(temporal) => self.temporal_passes(&mut graph, temporal, target, scene, resources),
+            None => self.scene_passes(&mut graph, target, scene, resources),
+        };
+
+        if !plugins.is_empty() {
+            graph.add_pass(
+                "plugins",
+                &[],
+                [
+                    (
+                        color,
+                        ImageUse::color_attachment(
+                            vk::ImageLayout::PRESENT_SRC_KHR,
+                            vk::ImageLayout::PRESENT_SRC_KHR,
+                        ),
+                    ),
+                    (
+                        depth,
+                        ImageUse::depth_attachment(
+                            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                        ),
+                    ),
+                ],
+                move |cmd, stats| {
+                    self.begin_render_pass(cmd, self.viewport_pass.handle, framebuffer, extent);
+                    let mut plugin_frame = PluginFrame {
+                        index: self.frame,
+                        extent,
+                        view: camera.get_view_matrix(),
+                        proj: camera.get_projection_matrix(),
+                        scene,
+                        stats,
+                    };
+                    for plugin in plugins {
+                        self.set_viewport(
+                            cmd,
+                            vk::Rect2D {
+                                offset: vk::Offset2D { x: 0, y: 0 },
+                                extent,
+                            },
+                        );
+                        plugin.record(cmd, &mut plugin_frame);
+                    }
+                    unsafe {
+                        device.handle.cmd_end_render_pass(cmd);
+                    }
+                },
+            );
+        }
+
+        let mut stats = FrameStats::default();
+        graph.execute(device, cmd, &mut stats)?;
 
         unsafe {
-            device
-                .handle
-                .cmd_set_cull_mode(cmd, vk::CullModeFlags::FRONT);
-            self.draw_meshes(&cmd, frame, scene, resources);
-            device
-                .handle
-                .cmd_set_cull_mode(cmd, vk::CullModeFlags::BACK);
-            self.draw_meshes(&cmd, frame, scene, resources);
-            device.handle.cmd_end_render_pass(cmd);
-
             device
                 .handle
                 .end_command_buffer(cmd)
                 .map_err(|e| format!("Failed to end command buffer: {}", e))?;
         }
 
-        Ok(())
+        Ok(stats)
     }
 
-    fn begin_render_pass(&self, cmd: vk::CommandBuffer, image_index: u32) {
-        let clear_values = [
-            vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0., 0., 0., 1.],
+    /// Adds the passes drawing the scene into the temporal target with its
+    /// motion vectors, resolving it into the history and copying the result
+    /// to `target` under the HUD text, and returns the versions of the
+    /// target's color and depth they leave.
+    fn temporal_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        temporal: &'a TemporalResolve,
+        target: SceneTarget,
+        scene: &'a Scene,
+        resources: &'a ResourcesManager,
+    ) -> [GraphImage; 2] {
+        let frame = &self.frames[self.frame];
+        let device = &self.context.device;
+        let scene_target = &temporal.target;
+
+        // Last read by the previous frame's resolve.
+        let resolved = ImageState::read(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let color = graph.import(
+            scene_target.color.handle,
+            vk::ImageAspectFlags::COLOR,
+            resolved,
+        );
+        let depth = graph.import(
+            scene_target.depth.handle,
+            depth_aspects(scene_target.depth.format),
+            resolved,
+        );
+        let velocity = graph.import_transient(&scene_target.velocity);
+        // The history read holds nothing to keep after a reset, and the one
+        // written is overwritten whole once the previous frame's composite is
+        // done with it.
+        let reset = frame.temporal.is_some_and(|resolve| resolve.reset != 0);
+        let previous = graph.import(
+            temporal.history[1 - temporal.current].handle,
+            vk::ImageAspectFlags::COLOR,
+            ImageState::written(
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+                if reset {
+                    vk::ImageLayout::UNDEFINED
+                } else {
+                    vk::ImageLayout::GENERAL
                 },
+            ),
+        );
+        let history = graph.import(
+            temporal.history[temporal.current].handle,
+            vk::ImageAspectFlags::COLOR,
+            ImageState::read(
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::ImageLayout::UNDEFINED,
+            ),
+        );
+
+        let [color, depth] = self.scene_passes(
+            graph,
+            SceneTarget {
+                render_pass: temporal.scene_pass.handle,
+                resume_pass: None,
+                viewport_pass: None,
+                framebuffer: scene_target.framebuffer,
+                extent: scene_target.extent,
+                hud: false,
+                color,
+                color_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                depth,
             },
-            vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.,
-                    stencil: 0,
-                },
+            scene,
+            resources,
+        );
+
+        let [velocity] = graph.add_pass(
+            "velocity",
+            &[(depth, ImageUse::depth_test())],
+            [(
+                velocity,
+                ImageUse::color_attachment(
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ),
+            )],
+            move |cmd, stats| {
+                let extent = scene_target.extent;
+                self.begin_render_pass(
+                    cmd,
+                    temporal.velocity_pass.handle,
+                    scene_target.velocity_framebuffer,
+                    extent,
+                );
+                self.set_viewport(
+                    cmd,
+                    vk::Rect2D {
+                        offset: vk::Offset2D { x: 0, y: 0 },
+                        extent,
+                    },
+                );
+                // Voxels and face-sorted triangles are reprojected by depth
+                // alone.
+                if scene.voxels.is_empty() && frame.sorted_runs.is_empty() {
+                    self.draw_velocity(&cmd, temporal, scene, resources, stats);
+                }
+                unsafe {
+                    device.handle.cmd_end_render_pass(cmd);
+                }
+            },
+        );
+
+        let sampled = |layout| ImageUse::sampled(vk::PipelineStageFlags::COMPUTE_SHADER, layout);
+        let [history] = graph.add_pass(
+            "temporal resolve",
+            &[
+                (color, sampled(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)),
+                (
+                    depth,
+                    sampled(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL),
+                ),
+                (velocity, sampled(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)),
+                (previous, sampled(vk::ImageLayout::GENERAL)),
+            ],
+            [(
+                history,
+                ImageUse::storage(
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::AccessFlags::SHADER_WRITE,
+                )
+                .discarding(),
+            )],
+            move |cmd, _| temporal.record(device, cmd, frame),
+        );
+
+        graph.add_pass(
+            "temporal composite",
+            &[(
+                history,
+                ImageUse::storage(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::AccessFlags::SHADER_READ,
+                ),
+            )],
+            [
+                (
+                    target.color,
+                    ImageUse::color_attachment(vk::ImageLayout::UNDEFINED, target.color_layout),
+                ),
+                (
+                    target.depth,
+                    ImageUse::depth_attachment(vk::ImageLayout::UNDEFINED),
+                ),
+            ],
+            move |cmd, stats| {
+                self.begin_render_pass(cmd, target.render_pass, target.framebuffer, target.extent);
+                self.set_viewport(
+                    cmd,
+                    vk::Rect2D {
+                        offset: vk::Offset2D { x: 0, y: 0 },
+                        extent: target.extent,
+                    },
+                );
+                temporal.composite(device, cmd, frame);
+                stats.draw_calls += 1;
+                self.draw_text(&cmd, frame, target.extent, stats);
+                unsafe {
+                    device.handle.cmd_end_render_pass(cmd);
+                }
             },
+        )
+    }
+
+    /// Draws the rigid objects' motion since the previous frame over the
+    /// depth of the temporal target. Skinned and displaced meshes don't match
+    /// that depth and are left to the depth reprojection.
+    fn draw_velocity(
+        &self,
+        cmd: &vk::CommandBuffer,
+        temporal: &TemporalResolve,
+        scene: &Scene,
+        resources: &ResourcesManager,
+        stats: &mut FrameStats,
+    ) {
+        let frame = &self.frames[self.frame];
+        let device = &self.context.device;
+        let variants = [
+            (MeshVariant::Rigid, &temporal.velocity),
+            (MeshVariant::Compact, &temporal.compact_velocity),
         ];
+        for (variant, pipeline) in variants {
+            self.bind_pipeline(cmd, frame, pipeline);
+            self.set_cull_mode(cmd, vk::CullModeFlags::NONE);
+            for draw in self.mesh_draws(scene, resources, variant, None) {
+                let Some(previous) = frame.previous_transforms.get(draw.index) else {
+                    continue;
+                };
+                let mesh = resources.get_mesh(draw.object.id());
+                self.bind_mesh(cmd, pipeline, mesh, draw.object.transform());
+                let previous = *previous * mesh.vertices.decode();
+                unsafe {
+                    device.handle.cmd_push_constants(
+                        *cmd,
+                        pipeline.layout,
+                        pipeline.material_stages(),
+                        64,
+                        std::slice::from_raw_parts(
+                            &previous as *const _ as *const u8,
+                            std::mem::size_of::<Mat4>(),
+                        ),
+                    );
+                }
+                for (group_index, group) in mesh.groups.iter().enumerate() {
+                    let displaced = self.displaces()
+                        && resources.get_material(group.material).map_disp.is_some();
+                    if displaced || draw.culled.contains(&(draw.index, group_index)) {
+                        continue;
+                    }
+                    unsafe {
+                        device.handle.cmd_draw_indexed(
+                            *cmd,
+                            group.index_count,
+                            1,
+                            group.index_offset,
+                            group.vertex_offset,
+                            0,
+                        );
+                    }
+                    stats.draw_calls += 1;
+                    stats.triangles += (group.index_count / 3) as u64;
+                }
+            }
+        }
+    }
 
-        let render_pass_info = vk::RenderPassBeginInfo {
+    /// Draws the rigid objects' depth into each shadow map cascade, in its
+    /// slice of the atlas, with both faces and every group, hidden from the
+    /// camera or not. Displaced groups cast the shadow of their undisplaced
+    /// mesh, cutouts of their whole triangles.
+    fn draw_cascades(
+        &self,
+        cmd: vk::CommandBuffer,
+        scene: &Scene,
+        resources: &ResourcesManager,
+        stats: &mut FrameStats,
+    ) {
+        let frame = &self.frames[self.frame];
+        let device = &self.context.device;
+        let cascades = &self.shadow_cascades;
+        let extent = cascades.extent();
+        let clear = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.,
+                stencil: 0,
+            },
+        };
+        let begin_info = vk::RenderPassBeginInfo {
             s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
-            render_pass: self.render_pass.handle,
-            framebuffer: self.swapchain.framebuffers[image_index as usize],
+            render_pass: cascades.render_pass.handle,
+            framebuffer: cascades.framebuffer(),
             render_area: vk::Rect2D {
                 offset: vk::Offset2D { x: 0, y: 0 },
-                extent: self.swapchain.extent,
+                extent,
             },
-            clear_value_count: clear_values.len() as u32,
-            p_clear_values: clear_values.as_ptr(),
+            clear_value_count: 1,
+            p_clear_values: &clear,
             ..Default::default()
         };
+        unsafe {
+            device
+                .handle
+                .cmd_begin_render_pass(cmd, &begin_info, vk::SubpassContents::INLINE);
+        }
+
+        let variants = [
+            (MeshVariant::Rigid, &cascades.pipeline),
+            (MeshVariant::Compact, &cascades.compact_pipeline),
+        ];
+        for (variant, pipeline) in variants {
+            self.bind_pipeline(&cmd, frame, pipeline);
+            self.set_cull_mode(&cmd, vk::CullModeFlags::NONE);
+            for draw in self.mesh_draws(scene, resources, variant, None) {
+                let mesh = resources.get_mesh(draw.object.id());
+                self.bind_mesh(&cmd, pipeline, mesh, draw.object.transform());
+                for cascade in 0..cascades.count() {
+                    self.set_viewport(cmd, cascades.slice(cascade));
+                    for group in &mesh.groups {
+                        // The first instance picks the cascade's transform.
+                        unsafe {
+                            device.handle.cmd_draw_indexed(
+                                cmd,
+                                group.index_count,
+                                1,
+                                group.index_offset,
+                                group.vertex_offset,
+                                cascade as u32,
+                            );
+                        }
+                        stats.draw_calls += 1;
+                        stats.triangles += (group.index_count / 3) as u64;
+                    }
+                }
+            }
+        }
 
         unsafe {
-            self.context.device().handle.cmd_begin_render_pass(
-                cmd,
-                &render_pass_info,
-                vk::SubpassContents::INLINE,
-            );
+            device.handle.cmd_end_render_pass(cmd);
         }
     }
 
-    fn bind_pipeline_and_viewport(&self, cmd: vk::CommandBuffer, frame: &FrameData) {
-        let viewport = vk::Viewport {
-            x: 0.,
-            y: 0.,
-            width: self.swapchain.extent.width as f32,
-            height: self.swapchain.extent.height as f32,
-            min_depth: 0.,
-            max_depth: 1.,
-        };
-        let scissor = vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: self.swapchain.extent,
-        };
-
+    /// Adds the passes drawing the ground reflection, the objects layer by
+    /// layer, then the debug lines and HUD text, the lines tested against the
+    /// depth left by the last layer, and returns the versions of the target's
+    /// color and depth they leave. With a resume pass, the traced occlusion
+    /// runs in between, from the target's depth, and is composited under the
+    /// overlays. With viewports and a viewport pass, all but the text is
+    /// drawn once per viewport, each in its own render pass after writing its
+    /// camera's uniforms.
+    fn scene_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        target: SceneTarget,
+        scene: &'a Scene,
+        resources: &'a ResourcesManager,
+    ) -> [GraphImage; 2] {
+        let frame = &self.frames[self.frame];
         let device = &self.context.device;
-        unsafe {
-            device.handle.cmd_bind_pipeline(
-                cmd,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline.handle,
+        let extent = target.extent;
+
+        let mut inputs = Vec::new();
+        if self.draws_overdraw() {
+            let overdraw = graph.import_transient(&self.overdraw);
+            let [overdraw] = graph.add_pass(
+                "overdraw clear",
+                &[],
+                [(overdraw, ImageUse::cleared())],
+                move |cmd, _| self.clear_overdraw(cmd),
             );
-            device.handle.cmd_set_viewport(cmd, 0, &[viewport]);
-            device.handle.cmd_set_scissor(cmd, 0, &[scissor]);
-            device.handle.cmd_bind_descriptor_sets(
-                cmd,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline.layout,
-                0,
-                &[frame.descriptor_set],
+            inputs.push((
+                overdraw,
+                ImageUse::storage(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                ),
+            ));
+        }
+
+        if self.draws_cascades() {
+            let cascades = &self.shadow_cascades;
+            // Last read by the previous frame's scene.
+            let atlas = graph.import(
+                cascades.atlas.handle,
+                vk::ImageAspectFlags::DEPTH,
+                ImageState::read(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                ),
+            );
+            let [atlas] = graph.add_pass(
+                "shadow cascades",
                 &[],
+                [(
+                    atlas,
+                    ImageUse {
+                        final_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                        discard: true,
+                        ..ImageUse::depth_attachment(vk::ImageLayout::UNDEFINED)
+                    },
+                )],
+                move |cmd, stats| self.draw_cascades(cmd, scene, resources, stats),
             );
+            inputs.push((
+                atlas,
+                ImageUse::sampled(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                ),
+            ));
         }
-    }
 
-    fn draw_meshes(
-        &self,
-        cmd: &vk::CommandBuffer,
-        frame: &FrameData,
+        let full = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let mut panes: Vec<(vk::Rect2D, Option<Uniforms>)> = match target.viewport_pass {
+            Some(_) => self
+                .viewports
+                .iter()
+                .map(|v| {
+                    let uniforms = self.uniforms_for(&v.camera, scene, resources);
+                    (v.rect(extent), Some(uniforms))
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        if panes.is_empty() {
+            panes.push((full, None));
+        }
+        let last_pane = panes[panes.len() - 1].0;
+
+        let [color, depth] = graph.add_pass(
+            "scene",
+            &inputs,
+            [
+                (
+                    target.color,
+                    ImageUse::color_attachment(vk::ImageLayout::UNDEFINED, target.color_layout),
+                ),
+                (
+                    target.depth,
+                    ImageUse::depth_attachment(vk::ImageLayout::UNDEFINED),
+                ),
+            ],
+            move |cmd, stats| self.draw_panes(cmd, &target, &panes, scene, resources, stats),
+        );
+
+        let Some(resume_pass) = target.resume_pass else {
+            return [color, depth];
+        };
+
+        let occlusion = graph.import_transient(&self.traced_occlusion.image);
+        let [occlusion] = graph.add_pass(
+            "traced occlusion",
+            &[(
+                depth,
+                ImageUse::sampled(
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                ),
+            )],
+            [(
+                occlusion,
+                ImageUse::storage(
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::AccessFlags::SHADER_WRITE,
+                )
+                .discarding(),
+            )],
+            move |cmd, _| self.traced_occlusion.record(device, cmd, frame, extent),
+        );
+
+        graph.add_pass(
+            "overlays",
+            &[(
+                occlusion,
+                ImageUse::storage(
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::AccessFlags::SHADER_READ,
+                ),
+            )],
+            [
+                (
+                    color,
+                    ImageUse::color_attachment(target.color_layout, target.color_layout),
+                ),
+                (
+                    depth,
+                    ImageUse::depth_attachment(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL),
+                ),
+            ],
+            move |cmd, stats| {
+                self.begin_render_pass(cmd, resume_pass, target.framebuffer, extent);
+                self.set_viewport(cmd, full);
+                self.traced_occlusion.composite(device, cmd, frame);
+                stats.draw_calls += 1;
+
+                self.set_viewport(cmd, last_pane);
+                self.draw_lines(&cmd, frame, scene, last_pane.extent, stats);
+                self.draw_overlay_text(cmd, &target, stats);
+            },
+        )
+    }
+
+    /// Records `panes` of the scene pass, all but the overlays of the last
+    /// one when the traced occlusion resumes the pass to add them.
+    fn draw_panes(
+        &self,
+        cmd: vk::CommandBuffer,
+        target: &SceneTarget,
+        panes: &[(vk::Rect2D, Option<Uniforms>)],
+        scene: &Scene,
+        resources: &ResourcesManager,
+        stats: &mut FrameStats,
+    ) {
+        let frame = &self.frames[self.frame];
+        let device = &self.context.device;
+
+        if let Some(structure) = &frame.scene_structure {
+            structure.record_build(&self.context.device, cmd);
+        }
+        if !frame.occlusion_probes.is_empty() {
+            frame.occlusion_queries.record_reset(cmd);
+        }
+
+        let mut layers: Vec<RenderLayer> = scene.objects.iter().map(Object::layer).collect();
+        layers.sort();
+        layers.dedup();
+
+        for (pane, (rect, uniforms)) in panes.iter().enumerate() {
+            if pane > 0 {
+                unsafe {
+                    device.handle.cmd_end_render_pass(cmd);
+                }
+            }
+            if let Some(uniforms) = uniforms {
+                frame.record_uniforms(device, cmd, &self.uniforms, uniforms);
+            }
+            let render_pass = match target.viewport_pass {
+                Some(viewport_pass) if pane > 0 => viewport_pass,
+                _ => target.render_pass,
+            };
+            self.begin_render_pass(cmd, render_pass, target.framebuffer, target.extent);
+            self.set_viewport(cmd, *rect);
+
+            let voxels = !scene.voxels.is_empty();
+            if let Some(reflection) = scene.reflection.filter(|_| self.reflects() && !voxels) {
+                self.draw_reflection(&cmd, scene, resources, &reflection, stats);
+                self.clear_depth(cmd, *rect);
+            }
+
+            if voxels {
+                self.draw_voxels(&cmd, frame, scene, stats);
+            } else if frame.sorted_runs.is_empty() {
+                for (index, &layer) in layers.iter().enumerate() {
+                    if index > 0 && layer.depth == LayerDepth::Cleared {
+                        self.clear_depth(cmd, *rect);
+                    }
+                    for variant in MeshVariant::ALL {
+                        self.draw_objects(&cmd, scene, resources, layer, variant, stats);
+                    }
+                    self.draw_mesh_lines(&cmd, scene, resources, layer, stats);
+                }
+            } else {
+                self.draw_sorted(&cmd, scene, resources, stats);
+            }
+
+            if pane + 1 == panes.len() {
+                self.occlusion_culling
+                    .record(&self.context.device, cmd, frame, scene, resources);
+                stats.occluded_groups = frame.culled_groups.len() as u32;
+
+                if target.resume_pass.is_some() {
+                    unsafe {
+                        device.handle.cmd_end_render_pass(cmd);
+                    }
+                    return;
+                }
+            }
+
+            self.draw_lines(&cmd, frame, scene, rect.extent, stats);
+        }
+
+        self.draw_overlay_text(cmd, target, stats);
+    }
+
+    /// Draws the HUD text over the whole target, if it has one, and ends
+    /// the target's render pass.
+    fn draw_overlay_text(
+        &self,
+        cmd: vk::CommandBuffer,
+        target: &SceneTarget,
+        stats: &mut FrameStats,
+    ) {
+        let frame = &self.frames[self.frame];
+        self.set_viewport(
+            cmd,
+            vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: target.extent,
+            },
+        );
+        if target.hud {
+            self.draw_text(&cmd, frame, target.extent, stats);
+        }
+
+        unsafe {
+            self.context.device.handle.cmd_end_render_pass(cmd);
+        }
+    }
+
+    /// Renders the scene once into an offscreen image of the given size and
+    /// returns its pixels as RGBA8, without the traced occlusion. Blocks until
+    /// the GPU is done.
+    pub fn capture(
+        &mut self,
+        camera: &Camera,
+        scene: &Scene,
+        resources: &ResourcesManager,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, String> {
+        self.wait_idle();
+
+        let target =
+            VkOffscreenTarget::new(&self.context, self.swapchain.image_format, width, height)?;
+        self.render_offscreen(camera, scene, resources, &target, true, |graph, color| {
+            graph.add_pass("readback", &[(color, ImageUse::copied())], [], |cmd, _| {
+                target.record_readback(cmd)
+            });
+        })?;
+
+        target.read_rgba8()
+    }
+
+    /// Renders the scene without the HUD into `target`, then blits its color
+    /// into `image`, left in `COLOR_ATTACHMENT_OPTIMAL` for the headset
+    /// compositor.
+    pub fn render_to_image(
+        &mut self,
+        camera: &Camera,
+        scene: &Scene,
+        resources: &ResourcesManager,
+        target: &VkOffscreenTarget,
+        image: vk::Image,
+    ) -> Result<(), String> {
+        self.wait_idle();
+
+        let device = self.context.device();
+        self.render_offscreen(camera, scene, resources, target, false, |graph, color| {
+            let unused = ImageState::read(
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::ImageLayout::UNDEFINED,
+            );
+            let output = graph.import(image, vk::ImageAspectFlags::COLOR, unused);
+            let [output] = graph.add_pass(
+                "blit to headset",
+                &[(color, ImageUse::copied())],
+                [(output, ImageUse::copied_into().discarding())],
+                move |cmd, _| {
+                    let subresource = vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: 0,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    };
+                    let corner = vk::Offset3D {
+                        x: target.extent.width as i32,
+                        y: target.extent.height as i32,
+                        z: 1,
+                    };
+                    let region = vk::ImageBlit {
+                        src_subresource: subresource,
+                        src_offsets: [vk::Offset3D::default(), corner],
+                        dst_subresource: subresource,
+                        dst_offsets: [vk::Offset3D::default(), corner],
+                    };
+                    unsafe {
+                        device.handle.cmd_blit_image(
+                            cmd,
+                            target.color_image.handle,
+                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            image,
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            &[region],
+                            vk::Filter::NEAREST,
+                        );
+                    }
+                },
+            );
+            let attachment = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+            graph.add_pass(
+                "headset handoff",
+                &[],
+                [(output, ImageUse::color_attachment(attachment, attachment))],
+                |_, _| {},
+            );
+        })
+    }
+
+    /// Draws every group of the scene into `target`, probing none, with the
+    /// HUD when `hud`, then runs the passes `finish` adds after it, reading
+    /// the color left in `TRANSFER_SRC_OPTIMAL`.
+    fn render_offscreen<'a>(
+        &'a mut self,
+        camera: &Camera,
+        scene: &'a Scene,
+        resources: &'a ResourcesManager,
+        target: &'a VkOffscreenTarget,
+        hud: bool,
+        finish: impl FnOnce(&mut RenderGraph<'a>, GraphImage),
+    ) -> Result<(), String> {
+        self.prepare_frame(camera, scene, resources, false)?;
+        let frame = &mut self.frames[self.frame];
+        frame.occlusion_probes.clear();
+        frame.culled_groups.clear();
+
+        let renderer: &'a Renderer = self;
+        let mut graph = RenderGraph::new();
+        let unused = ImageState::read(
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::ImageLayout::UNDEFINED,
+        );
+        let color = graph.import(
+            target.color_image.handle,
+            vk::ImageAspectFlags::COLOR,
+            unused,
+        );
+        let depth = graph.import(
+            target.depth_image.handle,
+            depth_aspects(target.depth_image.format),
+            unused,
+        );
+        let [color, _] = renderer.scene_passes(
+            &mut graph,
+            SceneTarget {
+                render_pass: target.render_pass.handle,
+                resume_pass: None,
+                viewport_pass: None,
+                framebuffer: target.framebuffer,
+                extent: target.extent,
+                hud,
+                color,
+                color_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                depth,
+            },
+            scene,
+            resources,
+        );
+        finish(&mut graph, color);
+
+        let cmd = renderer.command_pool.begin_single_cmd()?;
+        graph.execute(&renderer.context.device, cmd, &mut FrameStats::default())?;
+        renderer
+            .command_pool
+            .end_single_cmd(&renderer.graphics_queue, cmd)
+    }
+
+    /// Format of the window's swapchain, which offscreen targets share.
+    pub fn color_format(&self) -> vk::Format {
+        self.swapchain.image_format
+    }
+
+    fn begin_render_pass(
+        &self,
+        cmd: vk::CommandBuffer,
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+    ) {
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0., 0., 0., 1.],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.,
+                    stencil: 0,
+                },
+            },
+        ];
+
+        let render_pass_info = vk::RenderPassBeginInfo {
+            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+            render_pass,
+            framebuffer,
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+            clear_value_count: clear_values.len() as u32,
+            p_clear_values: clear_values.as_ptr(),
+            ..Default::default()
+        };
+
+        unsafe {
+            self.context.device().handle.cmd_begin_render_pass(
+                cmd,
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            );
+        }
+    }
+
+    /// Draws into `rect` only, mapping the whole clip space onto it.
+    fn set_viewport(&self, cmd: vk::CommandBuffer, rect: vk::Rect2D) {
+        let viewport = vk::Viewport {
+            x: rect.offset.x as f32,
+            y: rect.offset.y as f32,
+            width: rect.extent.width as f32,
+            height: rect.extent.height as f32,
+            min_depth: 0.,
+            max_depth: 1.,
+        };
+
+        let device = &self.context.device;
+        unsafe {
+            device.handle.cmd_set_viewport(cmd, 0, &[viewport]);
+            device.handle.cmd_set_scissor(cmd, 0, &[rect]);
+        }
+    }
+
+    fn bind_pipeline(&self, cmd: &vk::CommandBuffer, frame: &FrameData, pipeline: &VkPipeline) {
+        let device = &self.context.device;
+        unsafe {
+            device
+                .handle
+                .cmd_bind_pipeline(*cmd, vk::PipelineBindPoint::GRAPHICS, pipeline.handle);
+            device.handle.cmd_bind_descriptor_sets(
+                *cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.layout,
+                FRAME_SET,
+                &[frame.descriptor_set, self.material_set.handle],
+                &[],
+            );
+        }
+    }
+
+    /// The objects of `scene` drawn by the `variant` pipelines, in place or
+    /// mirrored by `reflection`.
+    fn mesh_draws<'a>(
+        &'a self,
+        scene: &'a Scene,
+        resources: &ResourcesManager,
+        variant: MeshVariant,
+        reflection: Option<&'a GroundReflection>,
+    ) -> Vec<MeshDraw<'a>> {
+        let frame = &self.frames[self.frame];
+        scene
+            .objects
+            .iter()
+            .enumerate()
+            .map(|(handle, object)| {
+                let mesh = resources.get_mesh(object.id());
+                let joint_offset = object
+                    .skin()
+                    .filter(|_| mesh.skin_buffer.is_some())
+                    .and_then(|skin| frame.joint_offsets.get(skin).copied());
+                let variant = if joint_offset.is_some() {
+                    MeshVariant::Skinned
+                } else if mesh.vertices.is_compact() {
+                    MeshVariant::Compact
+                } else {
+                    MeshVariant::Rigid
+                };
+                MeshDraw {
+                    object,
+                    index: handle,
+                    culled: &frame.culled_groups,
+                    joint_offset,
+                    variant,
+                    selected: reflection.is_none() && scene.selected == Some(handle),
+                    reflection,
+                }
+            })
+            .filter(|draw| draw.variant == variant)
+            .collect()
+    }
+
+    /// Marks the square of `reflection` in the stencil, then draws the
+    /// objects of layers sharing the scene's depth mirrored inside it. Their
+    /// depth is left for the caller to clear.
+    fn draw_reflection(
+        &self,
+        cmd: &vk::CommandBuffer,
+        scene: &Scene,
+        resources: &ResourcesManager,
+        reflection: &GroundReflection,
+        stats: &mut FrameStats,
+    ) {
+        let frame = &self.frames[self.frame];
+        let mask = &self.reflection_mask;
+        let vpc = MeshPushConstants {
+            transform: reflection.plane(),
+        };
+
+        self.bind_pipeline(cmd, frame, mask);
+        self.set_cull_mode(cmd, vk::CullModeFlags::NONE);
+        let device = &self.context.device;
+        unsafe {
+            device.handle.cmd_push_constants(
+                *cmd,
+                mask.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                std::slice::from_raw_parts(
+                    &vpc as *const _ as *const u8,
+                    std::mem::size_of::<MeshPushConstants>(),
+                ),
+            );
+            device.handle.cmd_draw(*cmd, 36, 1, 0, 0);
+        }
+        stats.draw_calls += 1;
+
+        for variant in MeshVariant::ALL {
+            let draws: Vec<MeshDraw> = self
+                .mesh_draws(scene, resources, variant, Some(reflection))
+                .into_iter()
+                .filter(|draw| draw.object.layer().depth == LayerDepth::Shared)
+                .collect();
+            if draws.is_empty() {
+                continue;
+            }
+
+            let Some(pipeline) = self.mesh_pipeline(MeshShading::Reflected, variant) else {
+                continue;
+            };
+            self.bind_pipeline(cmd, frame, &pipeline);
+            for (cull_mode, pass) in [
+                (vk::CullModeFlags::FRONT, MeshPass::BackFaces),
+                (vk::CullModeFlags::BACK, MeshPass::FrontFaces),
+            ] {
+                self.set_cull_mode(cmd, cull_mode);
+                self.draw_meshes(cmd, &pipeline, &draws, resources, pass, stats);
+            }
+        }
+    }
+
+    /// Draws the frame's `sorted_runs` in order with the depth test off,
+    /// culling nothing.
+    fn draw_sorted(
+        &self,
+        cmd: &vk::CommandBuffer,
+        scene: &Scene,
+        resources: &ResourcesManager,
+        stats: &mut FrameStats,
+    ) {
+        let frame = &self.frames[self.frame];
+        let draws: Vec<MeshDraw> = MeshVariant::ALL
+            .into_iter()
+            .flat_map(|variant| self.mesh_draws(scene, resources, variant, None))
+            .collect();
+        let mut bound: Option<(MeshVariant, Arc<VkPipeline>)> = None;
+
+        for run in &frame.sorted_runs {
+            let Some(draw) = draws.iter().find(|draw| draw.index == run.object) else {
+                continue;
+            };
+            let pipeline = match &bound {
+                Some((variant, pipeline)) if *variant == draw.variant => pipeline.clone(),
+                _ => {
+                    let Some(pipeline) = self.mesh_pipeline(MeshShading::Sorted, draw.variant)
+                    else {
+                        continue;
+                    };
+                    self.bind_pipeline(cmd, frame, &pipeline);
+                    self.set_cull_mode(cmd, vk::CullModeFlags::NONE);
+                    bound = Some((draw.variant, pipeline.clone()));
+                    pipeline
+                }
+            };
+
+            let mesh = resources.get_mesh(draw.object.id());
+            let group = &mesh.groups[run.group];
+            let material = resources.get_material(group.material);
+            self.bind_mesh(cmd, &pipeline, mesh, draw.object.transform());
+            let device = &self.context.device;
+            unsafe {
+                device.handle.cmd_bind_index_buffer(
+                    *cmd,
+                    frame.sorted_indices.handle,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+            }
+            self.draw_submesh(
+                cmd,
+                &pipeline,
+                &GpuGroup {
+                    index_offset: run.first_index,
+                    index_count: run.index_count,
+                    ..group.clone()
+                },
+                &Self::lit_material(draw, mesh, material),
+                draw.joint_offset.unwrap_or(0),
+            );
+            stats.draw_calls += 1;
+            stats.triangles += (run.index_count / 3) as u64;
+        }
+    }
+
+    /// Clears `rect` of the depth attachment of the current render pass,
+    /// keeping the colors drawn so far.
+    fn clear_depth(&self, cmd: vk::CommandBuffer, rect: vk::Rect2D) {
+        let attachment = vk::ClearAttachment {
+            aspect_mask: vk::ImageAspectFlags::DEPTH,
+            color_attachment: 0,
+            clear_value: vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.,
+                    stencil: 0,
+                },
+            },
+        };
+        let rect = vk::ClearRect {
+            rect,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        unsafe {
+            self.context
+                .device
+                .handle
+                .cmd_clear_attachments(cmd, &[attachment], &[rect]);
+        }
+    }
+
+    /// Draws the objects of `layer` of one pipeline `variant`, back faces
+    /// first, then their outlines: all of them in toon shading, the selected
+    /// one always.
+    fn draw_objects(
+        &self,
+        cmd: &vk::CommandBuffer,
+        scene: &Scene,
+        resources: &ResourcesManager,
+        layer: RenderLayer,
+        variant: MeshVariant,
+        stats: &mut FrameStats,
+    ) {
+        let frame = &self.frames[self.frame];
+        let draws: Vec<MeshDraw> = self
+            .mesh_draws(scene, resources, variant, None)
+            .into_iter()
+            .filter(|draw| draw.object.layer() == layer)
+            .collect();
+
+        if draws.is_empty() {
+            return;
+        }
+
+        let shading = if self.draws_overdraw() {
+            MeshShading::Overdraw
+        } else {
+            MeshShading::Material
+        };
+        let displaced = self.displaces().then_some(MeshShading::Displaced);
+
+        for shading in std::iter::once(shading).chain(displaced) {
+            let Some(pipeline) = self.mesh_pipeline(shading, variant) else {
+                continue;
+            };
+            self.bind_pipeline(cmd, frame, &pipeline);
+            for (cull_mode, pass) in [
+                (vk::CullModeFlags::FRONT, MeshPass::BackFaces),
+                (vk::CullModeFlags::BACK, MeshPass::FrontFaces),
+            ] {
+                self.set_cull_mode(cmd, cull_mode);
+                self.draw_meshes(cmd, &pipeline, &draws, resources, pass, stats);
+            }
+        }
+
+        let selected: Vec<MeshDraw> = draws.iter().filter(|d| d.selected).copied().collect();
+        let outlines = [
+            (
+                self.shading_mode == ShadingMode::Toon,
+                &draws,
+                OUTLINE_COLOR,
+            ),
+            (!selected.is_empty(), &selected, SELECTION_COLOR),
+        ];
+        for (enabled, draws, color) in outlines {
+            if !enabled {
+                continue;
+            }
+
+            // Only the hull's back faces show, around the silhouette.
+            let Some(outline) = self.mesh_pipeline(MeshShading::Outline, variant) else {
+                continue;
+            };
+            self.bind_pipeline(cmd, frame, &outline);
+            self.set_cull_mode(cmd, vk::CullModeFlags::FRONT);
+            let pass = MeshPass::Outline(color);
+            self.draw_meshes(cmd, &outline, draws, resources, pass, stats);
+        }
+    }
+
+    fn set_cull_mode(&self, cmd: &vk::CommandBuffer, cull_mode: vk::CullModeFlags) {
+        unsafe {
+            self.context
+                .device
+                .handle
+                .cmd_set_cull_mode(*cmd, cull_mode);
+        }
+    }
+
+    /// Draws the groups of `draws` that `pass` covers with their material,
+    /// tinted when selected and faded when reflected, or in the solid outline
+    /// color. Two-sided groups
+    /// are drawn with culling disabled, restored to back faces afterwards.
+    /// Material passes only draw the groups with a height map through a
+    /// tessellated `pipeline`, while displacement is on.
+    fn draw_meshes(
+        &self,
+        cmd: &vk::CommandBuffer,
+        pipeline: &VkPipeline,
+        draws: &[MeshDraw],
+        resources: &ResourcesManager,
+        pass: MeshPass,
+        stats: &mut FrameStats,
+    ) {
+        for draw in draws {
+            let mesh = resources.get_mesh(draw.object.id());
+            let transform = match draw.reflection {
+                Some(reflection) => reflection.mirror() * draw.object.transform(),
+                None => draw.object.transform(),
+            };
+            self.bind_mesh(cmd, pipeline, mesh, transform);
+            for (group_index, group) in mesh.groups.iter().enumerate() {
+                // Culling follows the camera's view, not the mirror's.
+                if draw.reflection.is_none() && draw.culled.contains(&(draw.index, group_index)) {
+                    continue;
+                }
+                let material = resources.get_material(group.material);
+                let two_sided = material.two_sided;
+                let displaced = self.displaces() && material.map_disp.is_some();
+                let material = match pass {
+                    MeshPass::BackFaces | MeshPass::FrontFaces
+                        if displaced != pipeline.tessellated =>
+                    {
+                        continue
+                    }
+                    MeshPass::Outline(color) => MaterialPushConstants::solid(color),
+                    MeshPass::BackFaces if two_sided => continue,
+                    MeshPass::BackFaces | MeshPass::FrontFaces => {
+                        Self::lit_material(draw, mesh, material)
+                    }
+                };
+                let unculled = two_sided && matches!(pass, MeshPass::FrontFaces);
+                if unculled {
+                    self.set_cull_mode(cmd, vk::CullModeFlags::NONE);
+                }
+                self.draw_submesh(
+                    cmd,
+                    pipeline,
+                    group,
+                    &material,
+                    draw.joint_offset.unwrap_or(0),
+                );
+                if unculled {
+                    self.set_cull_mode(cmd, vk::CullModeFlags::BACK);
+                }
+                stats.draw_calls += 1;
+                stats.triangles += (group.index_count / 3) as u64;
+            }
+        }
+    }
+
+    /// Push constants of `material` on `draw`'s mesh, tinted when selected
+    /// and faded when reflected.
+    fn lit_material(
+        draw: &MeshDraw,
+        mesh: &GpuMesh,
+        material: &GpuMaterial,
+    ) -> MaterialPushConstants {
+        let mut fpc = MaterialPushConstants::from(material);
+        if let Some(lightmap) = mesh.lightmap {
+            fpc.tex_lightmap = lightmap as u32;
+        }
+        if draw.selected {
+            fpc.tint(SELECTION_COLOR.xyz(), SELECTION_TINT);
+        }
+        if let Some(reflection) = draw.reflection {
+            fpc.dissolve *= reflection.strength;
+        }
+        fpc
+    }
+
+    /// Draws the line segments of the meshes of `layer`, one pixel wide, in
+    /// the selection color for the selected object. Skinned meshes' lines
+    /// stay in the bind pose.
+    fn draw_mesh_lines(
+        &self,
+        cmd: &vk::CommandBuffer,
         scene: &Scene,
         resources: &ResourcesManager,
+        layer: RenderLayer,
+        stats: &mut FrameStats,
+    ) {
+        let frame = &self.frames[self.frame];
+        let draws: Vec<MeshDraw> = MeshVariant::ALL
+            .into_iter()
+            .flat_map(|variant| self.mesh_draws(scene, resources, variant, None))
+            .filter(|draw| draw.object.layer() == layer)
+            .filter(|draw| resources.get_mesh(draw.object.id()).line_buffer.is_some())
+            .collect();
+        if draws.is_empty() {
+            return;
+        }
+        let Some(pipeline) = self.mesh_pipeline(MeshShading::Lines, MeshVariant::Rigid) else {
+            return;
+        };
+
+        self.bind_pipeline(cmd, frame, &pipeline);
+        self.set_cull_mode(cmd, vk::CullModeFlags::NONE);
+        let device = &self.context.device;
+        for draw in draws {
+            let mesh = resources.get_mesh(draw.object.id());
+            let Some(line_buffer) = &mesh.line_buffer else {
+                continue;
+            };
+            let vpc = MeshPushConstants {
+                transform: draw.object.transform(),
+            };
+            let color = if draw.selected {
+                SELECTION_COLOR
+            } else {
+                MESH_LINE_COLOR
+            };
+            let material = MaterialPushConstants::solid(color);
+            unsafe {
+                device.handle.cmd_push_constants(
+                    *cmd,
+                    pipeline.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    std::slice::from_raw_parts(
+                        &vpc as *const _ as *const u8,
+                        std::mem::size_of::<MeshPushConstants>(),
+                    ),
+                );
+                device.handle.cmd_push_constants(
+                    *cmd,
+                    pipeline.layout,
+                    pipeline.material_stages(),
+                    64,
+                    std::slice::from_raw_parts(
+                        &material as *const _ as *const u8,
+                        std::mem::size_of::<MaterialPushConstants>(),
+                    ),
+                );
+                device
+                    .handle
+                    .cmd_bind_vertex_buffers(*cmd, 0, &[line_buffer.handle], &[0]);
+                let vertex_count = mesh.geometry.lines.len() as u32 * 2;
+                device.handle.cmd_draw(*cmd, vertex_count, 1, 0, 0);
+            }
+            stats.draw_calls += 1;
+        }
+    }
+
+    /// Draws the debug lines `scene.debug_lines.width` pixels wide.
+    fn draw_lines(
+        &self,
+        cmd: &vk::CommandBuffer,
+        frame: &FrameData,
+        scene: &Scene,
+        extent: vk::Extent2D,
+        stats: &mut FrameStats,
+    ) {
+        let lines = &scene.debug_lines;
+        if lines.is_empty() {
+            return;
+        }
+
+        let constants = [extent.width as f32, extent.height as f32, lines.width];
+
+        self.bind_pipeline(cmd, frame, &self.line_pipeline);
+
+        let device = &self.context.device;
+        unsafe {
+            device
+                .handle
+                .cmd_set_cull_mode(*cmd, vk::CullModeFlags::NONE);
+            device.handle.cmd_push_constants(
+                *cmd,
+                self.line_pipeline.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                std::slice::from_raw_parts(
+                    constants.as_ptr() as *const u8,
+                    std::mem::size_of_val(&constants),
+                ),
+            );
+            device
+                .handle
+                .cmd_bind_vertex_buffers(*cmd, 0, &[frame.line_buffer.handle], &[0]);
+            device
+                .handle
+                .cmd_draw(*cmd, 6, lines.vertices.len() as u32 / 2, 0, 0);
+        }
+        stats.draw_calls += 1;
+    }
+
+    /// Draws the voxel preview's cubes, one instanced call per batch.
+    fn draw_voxels(
+        &self,
+        cmd: &vk::CommandBuffer,
+        frame: &FrameData,
+        scene: &Scene,
+        stats: &mut FrameStats,
     ) {
-        for object in &scene.objects {
-            let transform = object.transform();
-            let mesh = resources.get_mesh(object.id());
-            self.bind_mesh(cmd, mesh, transform);
-            for group in &mesh.groups {
-                self.draw_submesh(cmd, group, resources);
+        self.bind_pipeline(cmd, frame, &self.voxel_pipeline);
+        self.set_cull_mode(cmd, vk::CullModeFlags::BACK);
+
+        let device = &self.context.device;
+        for batch in &scene.voxels.batches {
+            let constants = MeshPushConstants {
+                transform: batch.transform,
+            };
+            unsafe {
+                device.handle.cmd_push_constants(
+                    *cmd,
+                    self.voxel_pipeline.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    std::slice::from_raw_parts(
+                        &constants as *const _ as *const u8,
+                        std::mem::size_of::<MeshPushConstants>(),
+                    ),
+                );
+                device
+                    .handle
+                    .cmd_bind_vertex_buffers(*cmd, 0, &[frame.voxel_buffer.handle], &[0]);
+                device
+                    .handle
+                    .cmd_draw(*cmd, 36, batch.count, 0, batch.first);
             }
+            stats.draw_calls += 1;
+            stats.triangles += 12 * batch.count as u64;
+        }
+    }
+
+    /// Draws the HUD text on top of the scene, in window pixels.
+    fn draw_text(
+        &self,
+        cmd: &vk::CommandBuffer,
+        frame: &FrameData,
+        extent: vk::Extent2D,
+        stats: &mut FrameStats,
+    ) {
+        if frame.text_vertex_count == 0 {
+            return;
+        }
+
+        let screen = [extent.width as f32, extent.height as f32];
+        let atlas = ResourcesManager::font_texture() as u32;
+
+        self.bind_pipeline(cmd, frame, &self.text_pipeline);
+
+        let device = &self.context.device;
+        unsafe {
+            device
+                .handle
+                .cmd_set_cull_mode(*cmd, vk::CullModeFlags::NONE);
+            device.handle.cmd_push_constants(
+                *cmd,
+                self.text_pipeline.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                std::slice::from_raw_parts(
+                    screen.as_ptr() as *const u8,
+                    std::mem::size_of_val(&screen),
+                ),
+            );
+            device.handle.cmd_push_constants(
+                *cmd,
+                self.text_pipeline.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                64,
+                &atlas.to_ne_bytes(),
+            );
+            device
+                .handle
+                .cmd_bind_vertex_buffers(*cmd, 0, &[frame.text_buffer.handle], &[0]);
+            device
+                .handle
+                .cmd_draw(*cmd, frame.text_vertex_count, 1, 0, 0);
         }
+        stats.draw_calls += 1;
     }
 
-    fn bind_mesh(&self, cmd: &vk::CommandBuffer, mesh: &GpuMesh, transform: Mat4) {
-        let vpc = MeshPushConstants { transform };
+    fn bind_mesh(
+        &self,
+        cmd: &vk::CommandBuffer,
+        pipeline: &VkPipeline,
+        mesh: &GpuMesh,
+        transform: Mat4,
+    ) {
+        let vpc = MeshPushConstants {
+            transform: transform * mesh.vertices.decode(),
+        };
 
         let device = &self.context.device;
         unsafe {
             device.handle.cmd_push_constants(
                 *cmd,
-                self.pipeline.layout,
+                pipeline.layout,
                 vk::ShaderStageFlags::VERTEX,
                 0,
                 std::slice::from_raw_parts(
@@ -354,7 +2356,12 @@ impl Renderer {
             );
             device
                 .handle
-                .cmd_bind_vertex_buffers(*cmd, 0, &[mesh.vertex_buffer.handle], &[0]);
+                .cmd_bind_vertex_buffers(*cmd, 0, &[mesh.vertices.handle()], &[0]);
+            if let Some(skin_buffer) = &mesh.skin_buffer {
+                device
+                    .handle
+                    .cmd_bind_vertex_buffers(*cmd, 1, &[skin_buffer.handle], &[0]);
+            }
             device.handle.cmd_bind_index_buffer(
                 *cmd,
                 mesh.index_buffer.handle,
@@ -364,24 +2371,25 @@ impl Renderer {
         }
     }
 
+    /// `first_instance` carries the skin's joint offset to the skinned
+    /// vertex shader.
     fn draw_submesh(
         &self,
         cmd: &vk::CommandBuffer,
+        pipeline: &VkPipeline,
         group: &GpuGroup,
-        resources: &ResourcesManager,
+        fpc: &MaterialPushConstants,
+        first_instance: u32,
     ) {
-        let mat = resources.get_material(group.material);
-        let fpc = MaterialPushConstants::from(mat);
-
         let device = &self.context.device;
         unsafe {
             device.handle.cmd_push_constants(
                 *cmd,
-                self.pipeline.layout,
-                vk::ShaderStageFlags::FRAGMENT,
+                pipeline.layout,
+                pipeline.material_stages(),
                 64,
                 std::slice::from_raw_parts(
-                    &fpc as *const _ as *const u8,
+                    fpc as *const _ as *const u8,
                     std::mem::size_of::<MaterialPushConstants>(),
                 ),
             );
@@ -391,18 +2399,23 @@ impl Renderer {
                 1,
                 group.index_offset,
                 group.vertex_offset,
-                0,
+                first_instance,
             );
         }
     }
 
+    /// Recreates the swapchain for a window of `width` by `height`. Does
+    /// nothing while suspended, `resume` sizes it from the window instead.
     pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        let Some(surface) = &self.surface else {
+            return Ok(());
+        };
         self.wait_idle();
 
         let support_details = query_swapchain_support(
             &self.context.physical_device.handle,
-            &self.context.surface.loader,
-            &self.context.surface.handle,
+            &surface.loader,
+            &surface.handle,
         )?;
 
         self.swapchain.resize(
@@ -412,10 +2425,193 @@ impl Renderer {
             Renderer::choose_surface_format(&support_details.formats),
             Renderer::choose_present_mode(&support_details.present_modes),
             Renderer::choose_extent(&support_details.capabilities, width, height),
+        )?;
+        self.swapchain_recreated();
+        self.resize_targets()
+    }
+
+    /// Recreates the swapchain for the window's current size, along with
+    /// the surface when it was lost instead of assuming it lives as long as
+    /// the window.
+    fn recreate_swapchain(&mut self, window: &dyn SurfaceWindow) -> Result<(), String> {
+        let lost = self
+            .surface
+            .as_ref()
+            .is_some_and(|surface| surface.is_lost(self.context.physical_device.handle));
+        if lost {
+            self.suspend();
+            return self.resume(window);
+        }
+        let (width, height) = window.size();
+        self.resize(width, height)
+    }
+
+    /// Destroys the swapchain and the window surface, for windows whose
+    /// native surface goes away: minimized, or of an Android app in the
+    /// background. The next frame drawn creates them again, see `resume`.
+    pub fn suspend(&mut self) {
+        self.wait_idle();
+        self.swapchain.destroy();
+        self.surface = None;
+        crash::set_swapchain("none, suspended".to_string());
+    }
+
+    /// Creates a surface for `window` again after `suspend`, and a swapchain
+    /// of its current size.
+    pub fn resume(&mut self, window: &dyn SurfaceWindow) -> Result<(), String> {
+        if self.surface.is_some() {
+            return Ok(());
+        }
+
+        let surface = VkSurface::new(window, &self.context.entry, &self.context.instance)?;
+        if !surface.supports_present(
+            self.context.physical_device.handle,
+            self.context.present_family(),
+        ) {
+            return Err("The GPU can't present to the resumed window".to_string());
+        }
+        let support_details = query_swapchain_support(
+            &self.context.physical_device.handle,
+            &surface.loader,
+            &surface.handle,
+        )?;
+        // The render passes were created for the previous surface's format.
+        let surface_format = Renderer::choose_surface_format(&support_details.formats);
+        if surface_format.format != self.swapchain.image_format {
+            return Err(format!(
+                "The resumed window surface uses {:?} instead of {:?}",
+                surface_format.format, self.swapchain.image_format
+            ));
+        }
+
+        let (width, height) = window.size();
+        self.swapchain = VkSwapchain::new(
+            &self.context,
+            surface.handle,
+            &self.render_pass,
+            support_details.capabilities,
+            surface_format,
+            Renderer::choose_present_mode(&support_details.present_modes),
+            Renderer::choose_extent(&support_details.capabilities, width, height),
+        )?;
+        self.surface = Some(surface);
+        self.swapchain_recreated();
+        self.resize_targets()
+    }
+
+    /// Description of `swapchain` for crash reports.
+    fn swapchain_state(swapchain: &VkSwapchain) -> String {
+        format!(
+            "{}x{} {:?}, {} images",
+            swapchain.extent.width,
+            swapchain.extent.height,
+            swapchain.image_format,
+            swapchain.images.len()
         )
     }
 
-    fn choose_surface_format(formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
+    fn swapchain_recreated(&self) {
+        let extent = self.swapchain.extent;
+        crash::set_swapchain(Self::swapchain_state(&self.swapchain));
+        self.events.emit(|| EngineEvent::SwapchainRecreated {
+            width: extent.width,
+            height: extent.height,
+        });
+    }
+
+    /// Resizes the images sized like the swapchain after it changed.
+    fn resize_targets(&mut self) -> Result<(), String> {
+        let velocity =
+            self.reallocate_transients(self.temporal.as_ref().map(TemporalResolve::scale))?;
+        if let (Some(temporal), Some(velocity)) = (&mut self.temporal, velocity) {
+            temporal.resize(&self.context, self.swapchain.extent, velocity)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the frame's transient images with ones sized for the
+    /// swapchain, returning the motion vectors for a temporal resolve at
+    /// `temporal_scale`.
+    fn reallocate_transients(
+        &mut self,
+        temporal_scale: Option<f32>,
+    ) -> Result<Option<TransientImage>, String> {
+        let transients =
+            Renderer::create_transients(&self.context, self.swapchain.extent, temporal_scale)?;
+        self.overdraw = transients.overdraw;
+        self.traced_occlusion.image = transients.occlusion;
+        Ok(transients.velocity)
+    }
+
+    /// Allocates the intermediate images of frames drawn at `extent`. Each is
+    /// only alive during some of the frame's passes, so they share memory:
+    /// the overdraw counters are done with before the traced occlusion, and
+    /// the temporal resolve's motion vectors replace both.
+    fn create_transients(
+        context: &VkContext,
+        extent: vk::Extent2D,
+        temporal_scale: Option<f32>,
+    ) -> Result<FrameTransients, String> {
+        let mut descs = vec![
+            TransientDesc {
+                extent,
+                format: vk::Format::R32_UINT,
+                usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_DST,
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                first: FramePass::OverdrawClear,
+                last: FramePass::Scene,
+            },
+            TransientDesc {
+                extent,
+                format: vk::Format::R32_SFLOAT,
+                usage: vk::ImageUsageFlags::STORAGE,
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                first: FramePass::TracedOcclusion,
+                last: FramePass::Overlays,
+            },
+        ];
+        if let Some(scale) = temporal_scale {
+            descs.push(TransientDesc {
+                extent: TemporalResolve::scaled(extent, scale),
+                format: VELOCITY_FORMAT,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                first: FramePass::Velocity,
+                last: FramePass::TemporalResolve,
+            });
+        }
+
+        let mut images = TransientImage::allocate(context, &descs)?.into_iter();
+        let missing = || "Missing transient image".to_string();
+        Ok(FrameTransients {
+            overdraw: images.next().ok_or_else(missing)?,
+            occlusion: images.next().ok_or_else(missing)?,
+            velocity: images.next(),
+        })
+    }
+
+    /// Zeroes the overdraw counters, left in the general layout.
+    fn clear_overdraw(&self, cmd: vk::CommandBuffer) {
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        unsafe {
+            self.context.device.handle.cmd_clear_color_image(
+                cmd,
+                self.overdraw.handle,
+                vk::ImageLayout::GENERAL,
+                &vk::ClearColorValue { uint32: [0; 4] },
+                &[range],
+            );
+        }
+    }
+
+    pub fn choose_surface_format(formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
         formats
             .iter()
             .find(|f| {
@@ -426,7 +2622,7 @@ impl Renderer {
             .unwrap_or(formats[0])
     }
 
-    fn choose_present_mode(modes: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+    pub fn choose_present_mode(modes: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
         modes
             .iter()
             .find(|&&m| m == vk::PresentModeKHR::MAILBOX)
@@ -434,7 +2630,7 @@ impl Renderer {
             .unwrap_or(vk::PresentModeKHR::FIFO)
     }
 
-    fn choose_extent(
+    pub fn choose_extent(
         capabilities: &vk::SurfaceCapabilitiesKHR,
         width: u32,
         height: u32,
@@ -457,6 +2653,18 @@ impl Renderer {
     pub fn wait_idle(&self) {
         self.context.device.wait_idle();
     }
+
+    /// Blocks until every frame in flight has been executed.
+    pub fn wait_for_frames(&self) {
+        let fences: Vec<vk::Fence> = self.frames.iter().map(|f| f.in_flight.handle).collect();
+        unsafe {
+            let _ = self
+                .context
+                .device
+                .handle
+                .wait_for_fences(&fences, true, u64::MAX);
+        }
+    }
 }
 
 impl Drop for Renderer {
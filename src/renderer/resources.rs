@@ -1,14 +1,19 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 
 use ash::vk;
 
+use super::load_mask_rgba8;
 use super::{
-    GpuGroup, GpuMaterial, GpuMesh, Vertex, VkBuffer, VkCommandPool, VkContext, VkDevice, VkQueue,
-    VkTexture,
+    BitmapFont, CompactVertex, GpuGroup, GpuMaterial, GpuMesh, Matcap, MeshVertices, SamplerKey,
+    Vertex, VertexSkin, VkAccelerationStructure, VkBuffer, VkCommandPool, VkContext, VkCubemap,
+    VkDevice, VkQueue, VkTexture, MATCAP_SIZE,
 };
+use crate::math::{Aabb, Vec2, Vec3};
 use crate::parser::ObjFileParser;
-use crate::scene::{Material, Mesh};
+use crate::profile::LoadProfile;
+use crate::scene::{Bvh, Group, Material, Mesh};
 pub type TextureHandle = usize;
 pub type MaterialHandle = usize;
 pub type MeshHandle = usize;
@@ -17,10 +22,24 @@ pub struct ResourcesManager {
     pub textures: Vec<VkTexture>,
     pub texture_cache: HashMap<String, TextureHandle>,
 
+    /// Sky reflected by shiny materials.
+    pub environment: VkCubemap,
+
     pub materials: Vec<GpuMaterial>,
 
     pub meshes: Vec<GpuMesh>,
     pub mesh_cache: HashMap<String, MeshHandle>,
+    /// Bumped whenever a mesh is uploaded or replaced, so data derived from
+    /// all the meshes knows to rebuild.
+    pub generation: u64,
+    /// Whether rigid meshes uploaded from now on are stored as
+    /// `CompactVertex`es, skinned ones keeping full vertices.
+    pub compact_vertices: bool,
+
+    /// Replaced meshes and lightmaps, which frames in flight may still read,
+    /// kept until `destroy_retired` once the device is idle.
+    retired_meshes: Vec<GpuMesh>,
+    retired_textures: Vec<VkTexture>,
 
     upload_queue: VkQueue,
     upload_pool: VkCommandPool,
@@ -37,14 +56,40 @@ impl ResourcesManager {
         )?;
 
         let white = VkTexture::white(&context, &upload_queue, &upload_pool)?;
+        let font = VkTexture::from_rgba8(
+            &context,
+            &upload_queue,
+            &upload_pool,
+            &BitmapFont::atlas_rgba8(),
+            BitmapFont::atlas_size(),
+            SamplerKey::nearest(),
+        )?;
+        let mut textures = vec![white, font];
+        for matcap in Matcap::ALL {
+            textures.push(VkTexture::from_rgba8(
+                &context,
+                &upload_queue,
+                &upload_pool,
+                &matcap.rgba8(MATCAP_SIZE),
+                (MATCAP_SIZE, MATCAP_SIZE),
+                SamplerKey::default(),
+            )?);
+        }
+        let environment = VkCubemap::sky(&context, &upload_queue, &upload_pool)?;
         let default_material = GpuMaterial::default();
 
         Ok(Self {
-            textures: vec![white],
+            textures,
             texture_cache: HashMap::new(),
+            environment,
             materials: vec![default_material],
             meshes: Vec::new(),
             mesh_cache: HashMap::new(),
+            generation: 0,
+            compact_vertices: false,
+
+            retired_meshes: Vec::new(),
+            retired_textures: Vec::new(),
 
             upload_pool,
             upload_queue,
@@ -56,6 +101,14 @@ impl ResourcesManager {
         0
     }
 
+    pub fn font_texture() -> TextureHandle {
+        1
+    }
+
+    pub fn matcap_texture(matcap: Matcap) -> TextureHandle {
+        2 + matcap as usize
+    }
+
     pub fn default_material() -> MaterialHandle {
         0
     }
@@ -86,12 +139,46 @@ impl ResourcesManager {
         }
     }
 
+    /// Cutout mask for `map_d`, see `load_mask_rgba8`; white, which keeps every
+    /// fragment, without one.
+    pub fn load_mask_texture(&mut self, context: &VkContext, path: &str) -> TextureHandle {
+        if path.is_empty() {
+            return Self::white_texture();
+        }
+
+        // Cached apart from the color texture of the same file.
+        let key = format!("{}#mask", path);
+        if let Some(&handle) = self.texture_cache.get(&key) {
+            return handle;
+        }
+
+        let texture = load_mask_rgba8(path).and_then(|mask| {
+            VkTexture::from_image(context, &self.upload_queue, &self.upload_pool, &mask)
+        });
+        match texture {
+            Ok(tex) => {
+                let handle = self.textures.len();
+                self.textures.push(tex);
+                self.texture_cache.insert(key, handle);
+                handle
+            }
+            Err(e) => {
+                eprintln!("Failed to load mask '{}': {} — rendering opaque", path, e);
+                Self::white_texture()
+            }
+        }
+    }
+
     /// Converts a Material (string paths) into a GpuMaterial (TextureHandles),
     /// uploading any textures that haven't been loaded yet.
     fn resolve_material(&mut self, context: &VkContext, raw: &Material) -> GpuMaterial {
         let map_kd = self.load_texture(context, &raw.map_kd);
         let map_ks = self.load_texture(context, &raw.map_ks);
         let map_ka = self.load_texture(context, &raw.map_ka);
+        let map_d = self.load_mask_texture(context, &raw.map_d);
+        // A height map that failed to load displaces nothing.
+        let map_disp = Some(self.load_texture(context, &raw.disp))
+            .filter(|&handle| handle != Self::white_texture());
 
         GpuMaterial {
             ka: raw.ka,
@@ -104,74 +191,252 @@ impl ResourcesManager {
             map_kd,
             map_ks,
             map_ka,
+            map_d,
+            map_disp,
+            two_sided: raw.two_sided,
         }
     }
 
     pub fn save_mesh(&mut self, context: &VkContext, mesh: &Mesh) -> Result<MeshHandle, String> {
-        let mut all_vertices: Vec<Vertex> = Vec::new();
-        let mut all_indices: Vec<u32> = Vec::new();
-        let mut groups: Vec<GpuGroup> = Vec::new();
+        self.save_mesh_profiled(context, mesh, &mut LoadProfile::default())
+    }
 
-        let mut materials: Vec<MaterialHandle> = Vec::new();
-        for raw in &mesh.materials {
-            let handle = self.materials.len();
-            let mat = self.resolve_material(context, raw);
-            self.materials.push(mat);
-            materials.push(handle);
+    /// Same as `save_mesh`, timing each step into `profile`.
+    pub fn save_mesh_profiled(
+        &mut self,
+        context: &VkContext,
+        mesh: &Mesh,
+        profile: &mut LoadProfile,
+    ) -> Result<MeshHandle, String> {
+        let gpu_mesh = self.upload_mesh(context, mesh, profile)?;
+        self.meshes.push(gpu_mesh);
+        self.generation += 1;
+
+        Ok(self.meshes.len() - 1)
+    }
+
+    /// Uploads `mesh` again in place of `handle`, which must no longer be in
+    /// use by the GPU. A new lightmap takes over the slot of the old one.
+    pub fn replace_mesh(
+        &mut self,
+        context: &VkContext,
+        handle: MeshHandle,
+        mesh: &Mesh,
+    ) -> Result<(), String> {
+        let previous = self.meshes[handle].lightmap;
+        let mut gpu_mesh = self.upload_mesh(context, mesh, &mut LoadProfile::default())?;
+        if let (Some(old), Some(_)) = (previous, gpu_mesh.lightmap) {
+            let texture = self.textures.swap_remove(old);
+            self.retired_textures.push(texture);
+            gpu_mesh.lightmap = Some(old);
         }
+        let replaced = std::mem::replace(&mut self.meshes[handle], gpu_mesh);
+        self.retired_meshes.push(replaced);
+        self.generation += 1;
 
-        for group in &mesh.groups {
-            if group.indices.is_empty() {
-                continue;
-            }
+        Ok(())
+    }
 
-            let index_offset = all_indices.len() as u32;
-            let vertex_offset = all_vertices.len() as i32;
+    /// Destroys the meshes and lightmaps `replace_mesh` retired. The device
+    /// must be idle.
+    pub fn destroy_retired(&mut self) {
+        self.retired_meshes.clear();
+        self.retired_textures.clear();
+    }
+
+    fn upload_mesh(
+        &mut self,
+        context: &VkContext,
+        mesh: &Mesh,
+        profile: &mut LoadProfile,
+    ) -> Result<GpuMesh, String> {
+        let skinned = mesh.groups.iter().any(|g| !g.skinning.is_empty());
+        let uploaded: Vec<&Group> = mesh
+            .groups
+            .iter()
+            .filter(|g| !g.indices.is_empty())
+            .collect();
+
+        let materials: Vec<MaterialHandle> = profile.stage("textures", |_| {
+            mesh.materials
+                .iter()
+                .map(|raw| {
+                    let mat = self.resolve_material(context, raw);
+                    self.materials.push(mat);
+                    self.materials.len() - 1
+                })
+                .collect()
+        });
 
+        // Groups are laid out one after the other, written straight into
+        // the staging buffers rather than gathered in copies first.
+        let (mut vertex_count, mut index_count) = (0, 0);
+        let mut groups: Vec<GpuGroup> = Vec::new();
+        for group in &uploaded {
             let material = group
                 .material
                 .map(|i| materials[i])
                 .unwrap_or(Self::default_material());
 
-            all_vertices.extend_from_slice(&group.vertices);
-            all_indices.extend_from_slice(&group.indices);
-
             groups.push(GpuGroup {
-                index_offset,
+                index_offset: index_count as u32,
                 index_count: group.indices.len() as u32,
-                vertex_offset,
+                vertex_offset: vertex_count as i32,
                 material,
+                bounds: Aabb::from_points(group.vertices.iter().map(|v| v.position)),
             });
+            vertex_count += group.vertices.len();
+            index_count += group.indices.len();
         }
 
-        if all_vertices.is_empty() || all_indices.is_empty() {
+        if vertex_count == 0 || index_count == 0 {
             return Err("Mesh has no geometry".to_string());
         }
 
-        let vertex_buffer = VkBuffer::device_local(
-            context,
-            &self.upload_queue,
-            &self.upload_pool,
-            &all_vertices,
-            vk::BufferUsageFlags::VERTEX_BUFFER,
-        )?;
-
-        let index_buffer = VkBuffer::device_local(
+        let start = Instant::now();
+        let vertices = if self.compact_vertices && !skinned {
+            let bounds = mesh.bounds();
+            let buffer = VkBuffer::<CompactVertex>::device_local_with(
+                context,
+                &self.upload_queue,
+                &self.upload_pool,
+                vertex_count,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                |writer| {
+                    for group in &uploaded {
+                        writer.extend(
+                            group
+                                .vertices
+                                .iter()
+                                .map(|vertex| CompactVertex::encode(vertex, &bounds)),
+                        );
+                    }
+                },
+            )?;
+            MeshVertices::Compact {
+                buffer,
+                decode: CompactVertex::decode(&bounds),
+            }
+        } else {
+            MeshVertices::Full(VkBuffer::<Vertex>::device_local_with(
+                context,
+                &self.upload_queue,
+                &self.upload_pool,
+                vertex_count,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                |writer| {
+                    for group in &uploaded {
+                        writer.extend_from_slice(&group.vertices);
+                    }
+                },
+            )?)
+        };
+
+        let index_buffer = VkBuffer::<u32>::device_local_with(
             context,
             &self.upload_queue,
             &self.upload_pool,
-            &all_indices,
+            index_count,
             vk::BufferUsageFlags::INDEX_BUFFER,
+            |writer| {
+                for group in &uploaded {
+                    writer.extend_from_slice(&group.indices);
+                }
+            },
         )?;
 
-        let handle = self.meshes.len();
-        self.meshes.push(GpuMesh {
-            vertex_buffer,
+        let skin_buffer = if skinned {
+            Some(VkBuffer::<VertexSkin>::device_local_with(
+                context,
+                &self.upload_queue,
+                &self.upload_pool,
+                vertex_count,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                |writer| {
+                    for group in &uploaded {
+                        writer.extend(
+                            (0..group.vertices.len())
+                                .map(|i| group.skinning.get(i).copied().unwrap_or_default()),
+                        );
+                    }
+                },
+            )?)
+        } else {
+            None
+        };
+
+        // Full vertices whatever the mesh's layout, as the line pipeline
+        // reads them.
+        let line_buffer = if mesh.lines.is_empty() {
+            None
+        } else {
+            Some(VkBuffer::<Vertex>::device_local_with(
+                context,
+                &self.upload_queue,
+                &self.upload_pool,
+                mesh.lines.len() * 2,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                |writer| {
+                    writer.extend(mesh.lines.iter().flatten().map(|&position| Vertex {
+                        position,
+                        normal: Vec3::Y,
+                        uv: Vec2::ZERO,
+                        occlusion: 1.,
+                        lightmap_uv: Vec2::ZERO,
+                    }));
+                },
+            )?)
+        };
+
+        let lightmap = match &mesh.lightmap {
+            Some(lightmap) => {
+                let texture = VkTexture::from_rgba8(
+                    context,
+                    &self.upload_queue,
+                    &self.upload_pool,
+                    &lightmap.pixels,
+                    (lightmap.size, lightmap.size),
+                    SamplerKey {
+                        address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        anisotropy: 1,
+                        ..Default::default()
+                    },
+                )?;
+                self.textures.push(texture);
+                Some(self.textures.len() - 1)
+            }
+            None => None,
+        };
+        profile.record("buffers", start.elapsed());
+
+        let blas = if context.device().ray_query() {
+            Some(profile.stage("acceleration structure", |_| {
+                VkAccelerationStructure::bottom_level(
+                    context,
+                    &self.upload_queue,
+                    &self.upload_pool,
+                    mesh,
+                )
+            })?)
+        } else {
+            None
+        };
+
+        let hull = profile.stage("hull", |_| mesh.convex_hull());
+        Ok(GpuMesh {
+            vertices,
             index_buffer,
+            skin_buffer,
+            line_buffer,
             groups,
-        });
-
-        Ok(handle)
+            bounds: mesh.bounds(),
+            obb: hull.oriented_box(),
+            hull,
+            geometry: mesh.clone(),
+            bvh: profile.stage("bvh", |_| Bvh::build(mesh)),
+            lightmap,
+            blas,
+        })
     }
 
     pub fn load_mesh(&mut self, context: &VkContext, path: &str) -> Result<MeshHandle, String> {
@@ -182,7 +447,18 @@ impl ResourcesManager {
         let mesh = ObjFileParser::parse(path)
             .map_err(|e| format!("Failed to parse mesh '{}': {}", path, e))?;
 
-        let handle = self.save_mesh(context, &mesh)?;
+        self.insert_mesh(context, path, &mesh, &mut LoadProfile::default())
+    }
+
+    /// Uploads a mesh already parsed from `path` and caches it under that path.
+    pub fn insert_mesh(
+        &mut self,
+        context: &VkContext,
+        path: &str,
+        mesh: &Mesh,
+        profile: &mut LoadProfile,
+    ) -> Result<MeshHandle, String> {
+        let handle = self.save_mesh_profiled(context, mesh, profile)?;
         self.mesh_cache.insert(path.to_string(), handle);
 
         Ok(handle)
@@ -0,0 +1,52 @@
+use ash::vk;
+
+/// Sampler settings, used as the key of the device's sampler cache so that
+/// textures with identical settings share one `vk::Sampler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerKey {
+    pub filter: vk::Filter,
+    pub address_mode: vk::SamplerAddressMode,
+    /// Maximum anisotropy, 1 to disable. Clamped to what the device supports.
+    pub anisotropy: u8,
+}
+
+impl Default for SamplerKey {
+    fn default() -> Self {
+        Self {
+            filter: vk::Filter::LINEAR,
+            address_mode: vk::SamplerAddressMode::REPEAT,
+            anisotropy: 16,
+        }
+    }
+}
+
+impl SamplerKey {
+    /// Unfiltered and clamped, for pixel-exact lookups like font atlases.
+    pub fn nearest() -> Self {
+        Self {
+            filter: vk::Filter::NEAREST,
+            address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            anisotropy: 1,
+        }
+    }
+
+    pub fn create_info(&self, max_anisotropy: f32) -> vk::SamplerCreateInfo<'static> {
+        let anisotropy = (self.anisotropy as f32).min(max_anisotropy);
+        vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            mag_filter: self.filter,
+            min_filter: self.filter,
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            anisotropy_enable: (anisotropy > 1.).into(),
+            max_anisotropy: anisotropy.max(1.),
+            border_color: vk::BorderColor::INT_OPAQUE_BLACK,
+            unnormalized_coordinates: vk::FALSE,
+            compare_enable: vk::FALSE,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            max_lod: vk::LOD_CLAMP_NONE,
+            ..Default::default()
+        }
+    }
+}
@@ -1,16 +1,21 @@
-use super::VkDevice;
+use super::{ShaderReflection, VkDevice};
+use crate::assets;
 use ash::vk;
-use std::fs::File;
+use std::io::Cursor;
 use std::sync::Arc;
 
 pub struct VkShaderModule {
     device: Arc<VkDevice>,
     pub handle: vk::ShaderModule,
+    /// What the shader reads from the pipeline.
+    pub reflection: ShaderReflection,
 }
 
 impl VkShaderModule {
     pub fn new(device: Arc<VkDevice>, path: &str) -> Result<VkShaderModule, String> {
         let code = VkShaderModule::read_spv_file(path)?;
+        let reflection = ShaderReflection::parse(&code)
+            .map_err(|e| format!("Failed to reflect {}: {}", path, e))?;
 
         let create_info = vk::ShaderModuleCreateInfo {
             s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
@@ -26,14 +31,18 @@ impl VkShaderModule {
                 .map_err(|e| format!("Failed to create shader module: {}", e))?
         };
 
-        return Ok(VkShaderModule { device, handle });
+        return Ok(VkShaderModule {
+            device,
+            handle,
+            reflection,
+        });
     }
 
     fn read_spv_file(path: &str) -> Result<Vec<u32>, String> {
-        let mut file =
-            File::open(path).map_err(|e| format!("Failed to open file {}: {}", path, e))?;
+        let bytes =
+            assets::read(path).map_err(|e| format!("Failed to open file {}: {}", path, e))?;
 
-        let content = ash::util::read_spv(&mut file)
+        let content = ash::util::read_spv(&mut Cursor::new(bytes))
             .map_err(|e| format!("Failed to decode SPIR-V file {}: {}", path, e))?;
 
         return Ok(content);
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadingMode {
+    #[default]
+    Lit,
+    Flat,
+    Unlit,
+    /// Banded diffuse with hard highlights and an ink outline.
+    Toon,
+    /// Color read from the selected matcap by view-space normal.
+    Matcap,
+    /// Checker pattern over the texture coordinates.
+    UvChecker,
+    /// World-space normals as RGB.
+    Normals,
+    /// View depth as grayscale, white near.
+    Depth,
+    /// Front faces blue, back faces red.
+    FaceOrientation,
+    /// Heatmap of how many fragments each pixel received.
+    Overdraw,
+    /// Diffuse color lit by the mesh's baked lightmap.
+    Baked,
+    /// Red on bulges, blue on hollows, white where flat.
+    Curvature,
+    /// Heatmap of the angle between the normals and a reference axis.
+    NormalDeviation,
+    /// Each shadow map cascade tinted its own color, darkened where shadowed.
+    Cascades,
+}
+
+impl ShadingMode {
+    pub const ALL: [ShadingMode; 14] = [
+        ShadingMode::Lit,
+        ShadingMode::Flat,
+        ShadingMode::Unlit,
+        ShadingMode::Toon,
+        ShadingMode::Matcap,
+        ShadingMode::UvChecker,
+        ShadingMode::Normals,
+        ShadingMode::Depth,
+        ShadingMode::FaceOrientation,
+        ShadingMode::Overdraw,
+        ShadingMode::Baked,
+        ShadingMode::Curvature,
+        ShadingMode::NormalDeviation,
+        ShadingMode::Cascades,
+    ];
+
+    /// Regular modes, cycled by `next`.
+    pub const STYLES: [ShadingMode; 6] = [
+        ShadingMode::Lit,
+        ShadingMode::Flat,
+        ShadingMode::Unlit,
+        ShadingMode::Toon,
+        ShadingMode::Matcap,
+        ShadingMode::Baked,
+    ];
+
+    /// Diagnostic visualizations, cycled by `next_debug`.
+    pub const DEBUG: [ShadingMode; 8] = [
+        ShadingMode::UvChecker,
+        ShadingMode::Normals,
+        ShadingMode::Depth,
+        ShadingMode::FaceOrientation,
+        ShadingMode::Overdraw,
+        ShadingMode::Curvature,
+        ShadingMode::NormalDeviation,
+        ShadingMode::Cascades,
+    ];
+
+    /// Next regular mode; from a debug mode, back to the first one.
+    pub fn next(self) -> Self {
+        let index = Self::STYLES
+            .iter()
+            .position(|&m| m == self)
+            .map_or(0, |i| i + 1);
+        Self::STYLES[index % Self::STYLES.len()]
+    }
+
+    /// Next debug mode, or back to lit after the last one.
+    pub fn next_debug(self) -> Self {
+        match Self::DEBUG.iter().position(|&m| m == self) {
+            Some(i) if i + 1 < Self::DEBUG.len() => Self::DEBUG[i + 1],
+            Some(_) => ShadingMode::Lit,
+            None => Self::DEBUG[0],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShadingMode::Lit => "lit",
+            ShadingMode::Flat => "flat",
+            ShadingMode::Unlit => "unlit",
+            ShadingMode::Toon => "toon",
+            ShadingMode::Matcap => "matcap",
+            ShadingMode::UvChecker => "uv-checker",
+            ShadingMode::Normals => "normals",
+            ShadingMode::Depth => "depth",
+            ShadingMode::FaceOrientation => "face-orientation",
+            ShadingMode::Overdraw => "overdraw",
+            ShadingMode::Baked => "baked",
+            ShadingMode::Curvature => "curvature",
+            ShadingMode::NormalDeviation => "normal-deviation",
+            ShadingMode::Cascades => "cascades",
+        }
+    }
+}
+
+impl FromStr for ShadingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|m| m.name() == s)
+            .ok_or_else(|| format!("Unknown shading mode '{}'", s))
+    }
+}
+
+impl fmt::Display for ShadingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
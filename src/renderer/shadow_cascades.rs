@@ -0,0 +1,381 @@
+use ash::vk;
+use std::sync::Arc;
+
+use super::{
+    DescriptorLayouts, VkCommandPool, VkContext, VkDevice, VkImage, VkPipeline, VkQueue,
+    VkRenderPass,
+};
+use crate::camera::Camera;
+use crate::math::{Aabb, Mat4, Vec3, Vec4};
+use crate::scene::Light;
+
+/// Most cascades the uniforms hold, as in `material.glsl`.
+pub const MAX_CASCADES: usize = 4;
+
+/// Texels along each edge of a cascade's slice of the atlas.
+const CASCADE_RESOLUTION: u32 = 2048;
+
+/// Weight of the logarithmic split distances against the uniform ones:
+/// higher gives the cascades near the camera a shorter range, and so more
+/// detail.
+const SPLIT_LAMBDA: f32 = 0.75;
+
+/// Depth formats the atlas may use, most precise first. Both are sampled
+/// with comparison; the second is supported everywhere.
+const CASCADE_FORMATS: [vk::Format; 2] = [vk::Format::D32_SFLOAT, vk::Format::D16_UNORM];
+
+/// Where the cascades covering a camera's view project from, and how far
+/// from the camera each one reaches, for the uniforms.
+#[derive(Debug, Clone, Copy)]
+pub struct Cascades {
+    pub count: u32,
+    /// World to clip space of each cascade, identity past `count`.
+    pub view_proj: [Mat4; MAX_CASCADES],
+    /// View depth each cascade ends at.
+    pub splits: Vec4,
+}
+
+impl Default for Cascades {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            view_proj: [Mat4::identity(); MAX_CASCADES],
+            splits: Vec4::ZERO,
+        }
+    }
+}
+
+impl Cascades {
+    /// Splits `camera`'s view, clipped to `bounds`, into `count` ranges of
+    /// depth, shorter near the camera, and fits each cascade around the
+    /// bounding sphere of its range. Each projects orthographically along
+    /// the light's direction to the center of that sphere, back far enough
+    /// to include anything in `bounds` casting a shadow into it. None without
+    /// bounds.
+    pub fn fit(camera: &Camera, light: &Light, bounds: &Aabb, count: usize) -> Self {
+        let mut cascades = Self::default();
+        if count == 0 || bounds.is_empty() {
+            return cascades;
+        }
+
+        let count = count.min(MAX_CASCADES);
+        let scene_center = bounds.center();
+        let scene_radius = bounds.radius().max(f32::EPSILON);
+        let near = camera.near();
+        let far = camera
+            .far()
+            .min((scene_center - camera.position).length() + scene_radius)
+            .max(near * 2.);
+
+        let mut splits = [0.; MAX_CASCADES];
+        let mut start = near;
+        for (index, split) in splits.iter_mut().enumerate().take(count) {
+            let t = (index + 1) as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(t);
+            let uniform = near + (far - near) * t;
+            let end = SPLIT_LAMBDA * logarithmic + (1. - SPLIT_LAMBDA) * uniform;
+
+            let mut range = camera.clone();
+            range.set_clip_planes(start, end);
+            let corners = range.frustum_corners();
+            let center = corners.iter().fold(Vec3::ZERO, |sum, &corner| sum + corner) / 8.;
+            let radius = corners
+                .iter()
+                .map(|&corner| (corner - center).length())
+                .fold(f32::EPSILON, f32::max);
+
+            let direction = (center - light.position)
+                .try_normalize()
+                .unwrap_or(Vec3::new(0., -1., 0.));
+            let up = if direction.y.abs() > 0.99 {
+                Vec3::Z
+            } else {
+                Vec3::Y
+            };
+            let back = (center - scene_center).length() + scene_radius;
+            let view = Mat4::look_at(center - direction * back, center, up);
+            let proj = Mat4::orthographic(radius, radius, 0., back + radius);
+
+            cascades.view_proj[index] = proj * view;
+            *split = end;
+            start = end;
+        }
+
+        cascades.count = count as u32;
+        cascades.splits = Vec4::from(splits);
+        cascades
+    }
+}
+
+/// Shadow map cascades: the light's depth rendered into one slice of a
+/// shared atlas per cascade, each covering a range of the camera's depth
+/// with the same resolution, so large scenes keep sharp shadows up close
+/// without one map having to span them whole. Drawn by a pass before the
+/// scene, whose fragments look their cascade up by view depth. Skinned
+/// meshes cast none.
+pub struct ShadowCascades {
+    device: Arc<VkDevice>,
+    pub render_pass: VkRenderPass,
+    pub pipeline: VkPipeline,
+    /// `pipeline` for meshes of `CompactVertex`es.
+    pub compact_pipeline: VkPipeline,
+    /// The cascades side by side, a single texel while off. Left ready to
+    /// sample between frames.
+    pub atlas: VkImage,
+    framebuffer: vk::Framebuffer,
+    /// Compares with the depth looked up, filtering the results.
+    pub sampler: vk::Sampler,
+    /// Reads the depth itself, for the occluder search of soft shadows.
+    pub depth_sampler: vk::Sampler,
+    count: usize,
+}
+
+impl ShadowCascades {
+    pub fn new(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        descriptor_layouts: &DescriptorLayouts,
+        count: usize,
+    ) -> Result<Self, String> {
+        let format = Self::find_format(context)?;
+        let render_pass = VkRenderPass::shadow(context, format)?;
+        let count = count.min(MAX_CASCADES);
+        let (atlas, framebuffer) =
+            Self::create_atlas(context, queue, command_pool, &render_pass, format, count)?;
+
+        let sampler_info = vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            compare_enable: vk::TRUE,
+            compare_op: vk::CompareOp::LESS_OR_EQUAL,
+            border_color: vk::BorderColor::FLOAT_OPAQUE_WHITE,
+            ..Default::default()
+        };
+        let sampler = unsafe {
+            context
+                .device()
+                .handle
+                .create_sampler(&sampler_info, None)
+                .map_err(|e| format!("Failed to create shadow sampler: {}", e))?
+        };
+        let depth_sampler_info = vk::SamplerCreateInfo {
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::NEAREST,
+            compare_enable: vk::FALSE,
+            ..sampler_info
+        };
+        let depth_sampler = unsafe {
+            context
+                .device()
+                .handle
+                .create_sampler(&depth_sampler_info, None)
+                .map_err(|e| format!("Failed to create shadow depth sampler: {}", e))?
+        };
+
+        Ok(Self {
+            device: context.device(),
+            pipeline: VkPipeline::shadow_cascades(
+                context.device(),
+                &render_pass,
+                descriptor_layouts,
+                false,
+            )?,
+            compact_pipeline: VkPipeline::shadow_cascades(
+                context.device(),
+                &render_pass,
+                descriptor_layouts,
+                true,
+            )?,
+            render_pass,
+            atlas,
+            framebuffer,
+            sampler,
+            depth_sampler,
+            count,
+        })
+    }
+
+    /// Cascades drawn, 0 when off.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Reallocates the atlas for `count` cascades, up to `MAX_CASCADES`.
+    /// The device must be idle.
+    pub fn set_count(
+        &mut self,
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        count: usize,
+    ) -> Result<(), String> {
+        let count = count.min(MAX_CASCADES);
+        let (atlas, framebuffer) = Self::create_atlas(
+            context,
+            queue,
+            command_pool,
+            &self.render_pass,
+            self.atlas.format,
+            count,
+        )?;
+        unsafe {
+            self.device
+                .handle
+                .destroy_framebuffer(self.framebuffer, None);
+        }
+        self.atlas = atlas;
+        self.framebuffer = framebuffer;
+        self.count = count;
+        Ok(())
+    }
+
+    pub fn framebuffer(&self) -> vk::Framebuffer {
+        self.framebuffer
+    }
+
+    /// Size of the whole atlas.
+    pub fn extent(&self) -> vk::Extent2D {
+        Self::atlas_extent(self.count)
+    }
+
+    /// Slice of the atlas cascade `index` is drawn into.
+    pub fn slice(&self, index: usize) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: (index as u32 * CASCADE_RESOLUTION) as i32,
+                y: 0,
+            },
+            extent: vk::Extent2D {
+                width: CASCADE_RESOLUTION,
+                height: CASCADE_RESOLUTION,
+            },
+        }
+    }
+
+    fn atlas_extent(count: usize) -> vk::Extent2D {
+        if count == 0 {
+            return vk::Extent2D {
+                width: 1,
+                height: 1,
+            };
+        }
+        vk::Extent2D {
+            width: CASCADE_RESOLUTION * count as u32,
+            height: CASCADE_RESOLUTION,
+        }
+    }
+
+    fn find_format(context: &VkContext) -> Result<vk::Format, String> {
+        let required = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+            | vk::FormatFeatureFlags::SAMPLED_IMAGE
+            | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+        CASCADE_FORMATS
+            .into_iter()
+            .find(|&format| {
+                let props = unsafe {
+                    context
+                        .instance
+                        .handle
+                        .get_physical_device_format_properties(
+                            context.physical_device.handle,
+                            format,
+                        )
+                };
+                props.optimal_tiling_features.contains(required)
+            })
+            .ok_or_else(|| "Failed to find a filterable depth format for shadows".to_string())
+    }
+
+    /// Creates the atlas of `count` cascades and its framebuffer, the atlas
+    /// moved to the layout it is sampled in, since the scene's shaders may
+    /// read it before any cascade is drawn.
+    fn create_atlas(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        render_pass: &VkRenderPass,
+        format: vk::Format,
+        count: usize,
+    ) -> Result<(VkImage, vk::Framebuffer), String> {
+        let extent = Self::atlas_extent(count);
+        let atlas = VkImage::new(
+            context,
+            extent.width,
+            extent.height,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::ImageAspectFlags::DEPTH,
+        )?;
+
+        let device = context.device();
+        let cmd = command_pool.begin_single_cmd()?;
+        unsafe {
+            device.handle.cmd_pipeline_barrier(
+                cmd,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[vk::ImageMemoryBarrier {
+                    s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                    old_layout: vk::ImageLayout::UNDEFINED,
+                    new_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                    src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                    dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                    image: atlas.handle,
+                    subresource_range: vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::DEPTH,
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    src_access_mask: vk::AccessFlags::empty(),
+                    dst_access_mask: vk::AccessFlags::SHADER_READ,
+                    ..Default::default()
+                }],
+            );
+        }
+        command_pool.end_single_cmd(queue, cmd)?;
+
+        let create_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+            render_pass: render_pass.handle,
+            attachment_count: 1,
+            p_attachments: &atlas.view,
+            width: extent.width,
+            height: extent.height,
+            layers: 1,
+            ..Default::default()
+        };
+        let framebuffer = unsafe {
+            device
+                .handle
+                .create_framebuffer(&create_info, None)
+                .map_err(|e| format!("Failed to create shadow framebuffer: {}", e))?
+        };
+
+        Ok((atlas, framebuffer))
+    }
+}
+
+impl Drop for ShadowCascades {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .handle
+                .destroy_framebuffer(self.framebuffer, None);
+            self.device.handle.destroy_sampler(self.sampler, None);
+            self.device.handle.destroy_sampler(self.depth_sampler, None);
+        }
+    }
+}
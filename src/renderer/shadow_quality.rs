@@ -0,0 +1,71 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How the fragment shader filters the shadow map cascades. Soft shadows
+/// treat the light as a disk: percentage-closer soft shadows (PCSS) average
+/// the depth of the occluders around each fragment and filter over the
+/// penumbra the disk casts from there, so shadows widen away from the
+/// occluder, as under a large lamp or an overcast sky, instead of keeping the
+/// hard edges of a point light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadowQuality {
+    /// A fixed 3x3 filter.
+    #[default]
+    Hard,
+    Soft,
+    /// Soft, with smoother penumbras at twice the cost.
+    High,
+}
+
+impl ShadowQuality {
+    pub const ALL: [ShadowQuality; 3] = [
+        ShadowQuality::Hard,
+        ShadowQuality::Soft,
+        ShadowQuality::High,
+    ];
+
+    /// Angle the light's disk spans seen from the lit surface, in degrees,
+    /// by default.
+    pub const DEFAULT_LIGHT_SIZE: f32 = 4.;
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&q| q == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Shadow map taps of the occluder search and of the filter each, per
+    /// fragment; 1 for the 3x3 filter of hard shadows.
+    pub fn samples(self) -> u32 {
+        match self {
+            ShadowQuality::Hard => 1,
+            ShadowQuality::Soft => 16,
+            ShadowQuality::High => 32,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShadowQuality::Hard => "hard",
+            ShadowQuality::Soft => "soft",
+            ShadowQuality::High => "high",
+        }
+    }
+}
+
+impl FromStr for ShadowQuality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|q| q.name() == s)
+            .ok_or_else(|| format!("Unknown shadow quality '{}'", s))
+    }
+}
+
+impl fmt::Display for ShadowQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,341 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::query_swapchain_support;
+use super::{
+    BitmapFont, DescriptorLayouts, MaterialSet, Renderer, ResourcesManager, SurfaceWindow,
+    TextVertex, VkBuffer, VkCommandPool, VkContext, VkFence, VkPipeline, VkQueue, VkRenderPass,
+    VkSemaphore, VkSurface, VkSwapchain, MATERIAL_SET, MAX_FRAMES_IN_FLIGHT,
+};
+use crate::scene::Hud;
+
+/// Background of the stats window.
+const CLEAR_COLOR: [f32; 4] = [0.05, 0.05, 0.06, 1.];
+
+struct StatsFrame {
+    image_available: VkSemaphore,
+    render_finished: VkSemaphore,
+    in_flight: VkFence,
+    command_buffer: vk::CommandBuffer,
+    text_buffer: VkBuffer<TextVertex>,
+    text_vertex_count: u32,
+}
+
+/// Second window showing a HUD of its own, drawn with the text pipeline into
+/// a swapchain of its own on the renderer's device.
+// Fields drop in declaration order: the swapchain goes before its surface.
+pub struct StatsWindow {
+    frames: Vec<StatsFrame>,
+    frame: usize,
+    pipeline: VkPipeline,
+    swapchain: VkSwapchain,
+    render_pass: VkRenderPass,
+    /// Holds the font atlas, the only texture drawn here.
+    material_set: MaterialSet,
+    _descriptor_layouts: DescriptorLayouts,
+    _command_pool: VkCommandPool,
+    queue: VkQueue,
+    surface: VkSurface,
+    context: Arc<VkContext>,
+}
+
+impl StatsWindow {
+    const INITIAL_TEXT_CAPACITY: usize = 24 * 1024;
+
+    pub fn new(
+        window: &dyn SurfaceWindow,
+        context: Arc<VkContext>,
+        resources: &ResourcesManager,
+    ) -> Result<Self, String> {
+        let surface = VkSurface::new(window, &context.entry, &context.instance)?;
+        if !surface.supports_present(context.physical_device.handle, context.present_family()) {
+            return Err("The GPU can't present to the stats window".to_string());
+        }
+
+        let support = query_swapchain_support(
+            &context.physical_device.handle,
+            &surface.loader,
+            &surface.handle,
+        )?;
+        let surface_format = Renderer::choose_surface_format(&support.formats);
+        let (width, height) = window.size();
+        let render_pass = VkRenderPass::new(&context, surface_format.format)?;
+        let swapchain = VkSwapchain::new(
+            &context,
+            surface.handle,
+            &render_pass,
+            support.capabilities,
+            surface_format,
+            Renderer::choose_present_mode(&support.present_modes),
+            Renderer::choose_extent(&support.capabilities, width, height),
+        )?;
+
+        let descriptor_layouts = DescriptorLayouts::new(context.device())?;
+        let mut material_set = MaterialSet::new(context.device(), &descriptor_layouts)?;
+        material_set.update(&resources.textures);
+        let pipeline = VkPipeline::text(context.device(), &render_pass, &descriptor_layouts)?;
+        let queue = VkQueue::new(context.device(), context.graphics_family());
+        let command_pool = VkCommandPool::new(
+            context.device(),
+            context.graphics_family(),
+            vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+        )?;
+
+        let frames = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                Ok(StatsFrame {
+                    image_available: VkSemaphore::new(context.device())?,
+                    render_finished: VkSemaphore::new(context.device())?,
+                    in_flight: VkFence::new(context.device())?,
+                    command_buffer: command_pool
+                        .allocate_buffers(vk::CommandBufferLevel::PRIMARY, 1)?
+                        .remove(0),
+                    text_buffer: VkBuffer::host_visible(
+                        &context,
+                        Self::INITIAL_TEXT_CAPACITY,
+                        vk::BufferUsageFlags::VERTEX_BUFFER,
+                    )?,
+                    text_vertex_count: 0,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            frames,
+            frame: 0,
+            pipeline,
+            swapchain,
+            render_pass,
+            material_set,
+            _descriptor_layouts: descriptor_layouts,
+            _command_pool: command_pool,
+            queue,
+            surface,
+            context,
+        })
+    }
+
+    /// Draws `hud` alone into the window. Skips the frame when the swapchain
+    /// is out of date, until `resize` is called.
+    pub fn draw(&mut self, hud: &Hud) -> Result<(), String> {
+        let device = self.context.device();
+        let frame = &self.frames[self.frame];
+        unsafe {
+            device
+                .handle
+                .wait_for_fences(&[frame.in_flight.handle], true, u64::MAX)
+                .map_err(|e| format!("Failed to wait for fence: {}", e))?;
+        }
+
+        let image_index = match unsafe {
+            self.swapchain.loader.acquire_next_image(
+                self.swapchain.handle,
+                u64::MAX,
+                frame.image_available.handle,
+                vk::Fence::null(),
+            )
+        } {
+            Ok((index, _)) => index,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return Ok(()),
+            Err(e) => return Err(format!("Failed to acquire next image: {:?}", e)),
+        };
+
+        self.upload_text(hud)?;
+
+        let frame = &self.frames[self.frame];
+        unsafe {
+            device
+                .handle
+                .reset_fences(&[frame.in_flight.handle])
+                .map_err(|e| format!("Failed to reset fence: {}", e))?;
+            device
+                .handle
+                .reset_command_buffer(frame.command_buffer, vk::CommandBufferResetFlags::empty())
+                .map_err(|e| format!("Failed to reset command buffer: {}", e))?;
+        }
+        self.record(frame, image_index)?;
+
+        self.queue.submit(
+            &frame.command_buffer,
+            &[frame.image_available.handle],
+            &[frame.render_finished.handle],
+            &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT],
+            &frame.in_flight.handle,
+        )?;
+        self.swapchain.queue_present(
+            &self.queue.handle,
+            &[frame.render_finished.handle],
+            image_index,
+        )?;
+
+        self.frame = (self.frame + 1) % self.frames.len();
+        Ok(())
+    }
+
+    /// Lays out `hud` into the current frame's vertex buffer, growing it when
+    /// needed. The frame must not be in flight.
+    fn upload_text(&mut self, hud: &Hud) -> Result<(), String> {
+        let mut vertices = Vec::new();
+        BitmapFont::layout(hud, &mut vertices);
+
+        let frame = &mut self.frames[self.frame];
+        if vertices.len() > frame.text_buffer.capacity() {
+            frame.text_buffer = VkBuffer::host_visible(
+                &self.context,
+                vertices.len().next_power_of_two(),
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+            )?;
+        }
+        frame.text_buffer.write(&vertices);
+        frame.text_vertex_count = vertices.len() as u32;
+        Ok(())
+    }
+
+    fn record(&self, frame: &StatsFrame, image_index: u32) -> Result<(), String> {
+        let device = &self.context.device;
+        let cmd = frame.command_buffer;
+        let extent = self.swapchain.extent;
+
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: CLEAR_COLOR,
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.,
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let render_pass_info = vk::RenderPassBeginInfo {
+            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+            render_pass: self.render_pass.handle,
+            framebuffer: self.swapchain.framebuffers[image_index as usize],
+            render_area,
+            clear_value_count: clear_values.len() as u32,
+            p_clear_values: clear_values.as_ptr(),
+            ..Default::default()
+        };
+        let viewport = vk::Viewport {
+            x: 0.,
+            y: 0.,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.,
+            max_depth: 1.,
+        };
+        let screen = [extent.width as f32, extent.height as f32];
+        let atlas = ResourcesManager::font_texture() as u32;
+
+        unsafe {
+            device
+                .handle
+                .begin_command_buffer(
+                    cmd,
+                    &vk::CommandBufferBeginInfo {
+                        s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+                        ..Default::default()
+                    },
+                )
+                .map_err(|e| format!("Failed to begin command buffer: {}", e))?;
+            device.handle.cmd_begin_render_pass(
+                cmd,
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            );
+
+            if frame.text_vertex_count > 0 {
+                device.handle.cmd_bind_pipeline(
+                    cmd,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline.handle,
+                );
+                device.handle.cmd_bind_descriptor_sets(
+                    cmd,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline.layout,
+                    MATERIAL_SET,
+                    &[self.material_set.handle],
+                    &[],
+                );
+                device.handle.cmd_set_viewport(cmd, 0, &[viewport]);
+                device.handle.cmd_set_scissor(cmd, 0, &[render_area]);
+                device
+                    .handle
+                    .cmd_set_cull_mode(cmd, vk::CullModeFlags::NONE);
+                device.handle.cmd_push_constants(
+                    cmd,
+                    self.pipeline.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    std::slice::from_raw_parts(
+                        screen.as_ptr() as *const u8,
+                        std::mem::size_of_val(&screen),
+                    ),
+                );
+                device.handle.cmd_push_constants(
+                    cmd,
+                    self.pipeline.layout,
+                    vk::ShaderStageFlags::FRAGMENT,
+                    64,
+                    &atlas.to_ne_bytes(),
+                );
+                device
+                    .handle
+                    .cmd_bind_vertex_buffers(cmd, 0, &[frame.text_buffer.handle], &[0]);
+                device
+                    .handle
+                    .cmd_draw(cmd, frame.text_vertex_count, 1, 0, 0);
+            }
+
+            device.handle.cmd_end_render_pass(cmd);
+            device
+                .handle
+                .end_command_buffer(cmd)
+                .map_err(|e| format!("Failed to end command buffer: {}", e))
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.wait_for_frames();
+
+        let support = query_swapchain_support(
+            &self.context.physical_device.handle,
+            &self.surface.loader,
+            &self.surface.handle,
+        )?;
+        self.swapchain.resize(
+            &self.context,
+            &self.render_pass,
+            support.capabilities,
+            Renderer::choose_surface_format(&support.formats),
+            Renderer::choose_present_mode(&support.present_modes),
+            Renderer::choose_extent(&support.capabilities, width, height),
+        )
+    }
+
+    /// Blocks until every frame in flight has been executed.
+    pub fn wait_for_frames(&self) {
+        let fences: Vec<vk::Fence> = self.frames.iter().map(|f| f.in_flight.handle).collect();
+        unsafe {
+            let _ = self
+                .context
+                .device
+                .handle
+                .wait_for_fences(&fences, true, u64::MAX);
+        }
+    }
+}
+
+impl Drop for StatsWindow {
+    fn drop(&mut self) {
+        self.wait_for_frames();
+    }
+}
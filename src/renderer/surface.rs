@@ -1,7 +1,6 @@
-use ash::{khr, vk, Entry, Instance};
-use sdl3::video::Window;
+use ash::{khr, vk, Entry};
 
-use crate::renderer::VkInstance;
+use crate::renderer::{SurfaceWindow, VkInstance};
 
 pub struct VkSurface {
     pub loader: khr::surface::Instance,
@@ -9,22 +8,35 @@ pub struct VkSurface {
 }
 
 impl VkSurface {
-    pub fn new(window: &Window, entry: &Entry, instance: &VkInstance) -> Result<VkSurface, String> {
+    pub fn new(
+        window: &dyn SurfaceWindow,
+        entry: &Entry,
+        instance: &VkInstance,
+    ) -> Result<VkSurface, String> {
         let loader = khr::surface::Instance::new(entry, &instance.handle);
-        let handle = VkSurface::create_surface(window, &instance.handle)?;
+        let handle = window.create_surface(entry, &instance.handle)?;
 
         return Ok(VkSurface { loader, handle });
     }
 
-    fn create_surface(window: &Window, instance: &Instance) -> Result<vk::SurfaceKHR, String> {
-        let vk_instance = instance.handle();
-        let surface = unsafe {
-            window
-                .vulkan_create_surface(vk_instance)
-                .expect("Failed to create Vulkan surface")
+    /// Whether the surface is gone with its native window, as after some
+    /// compositors restart, and must be created again.
+    pub fn is_lost(&self, physical_device: vk::PhysicalDevice) -> bool {
+        let capabilities = unsafe {
+            self.loader
+                .get_physical_device_surface_capabilities(physical_device, self.handle)
         };
+        capabilities.err() == Some(vk::Result::ERROR_SURFACE_LOST_KHR)
+    }
 
-        return Ok(surface);
+    /// Whether queues of `family` on `physical_device` can present to the
+    /// surface.
+    pub fn supports_present(&self, physical_device: vk::PhysicalDevice, family: u32) -> bool {
+        unsafe {
+            self.loader
+                .get_physical_device_surface_support(physical_device, family, self.handle)
+                .unwrap_or(false)
+        }
     }
 }
 
@@ -0,0 +1,58 @@
+use crate::math::Vec3;
+
+/// Ranges of the false-color analysis shading modes, which color the surface
+/// by its curvature or by how far its normals lean from an axis, to spot
+/// dents and warps in scanned parts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceAnalysis {
+    /// Radius of curvature, in world units, at which `curvature` shading is
+    /// fully red on bulges and fully blue on hollows.
+    pub curvature_radius: f32,
+    /// World axis `normal-deviation` shading measures the normals against,
+    /// in either direction.
+    pub axis: Vec3,
+    /// Angle to the axis, in degrees, at which `normal-deviation` shading
+    /// turns fully red.
+    pub deviation_angle: f32,
+}
+
+impl Default for SurfaceAnalysis {
+    fn default() -> Self {
+        Self {
+            curvature_radius: 1.,
+            axis: Vec3::Y,
+            deviation_angle: 90.,
+        }
+    }
+}
+
+impl SurfaceAnalysis {
+    /// Scales both ranges so that `factor` above 1 colors smaller defects
+    /// as strongly as larger ones were.
+    pub fn sharpen(&mut self, factor: f32) {
+        self.curvature_radius *= factor;
+        self.deviation_angle = (self.deviation_angle / factor).min(90.);
+    }
+
+    /// Next of the X, Y and Z axes, X after any other axis.
+    pub fn next_axis(&mut self) {
+        self.axis = if self.axis == Vec3::X {
+            Vec3::Y
+        } else if self.axis == Vec3::Y {
+            Vec3::Z
+        } else {
+            Vec3::X
+        };
+    }
+
+    /// Name of the axis, for the HUD.
+    pub fn axis_name(&self) -> &'static str {
+        if self.axis == Vec3::X {
+            "X"
+        } else if self.axis == Vec3::Y {
+            "Y"
+        } else {
+            "Z"
+        }
+    }
+}
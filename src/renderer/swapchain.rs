@@ -1,24 +1,30 @@
 use ash::{khr, vk};
 use std::sync::Arc;
 
-use super::find_depth_format;
+use super::{depth_aspects, find_depth_format};
 use super::{VkContext, VkDevice, VkImage, VkRenderPass};
+use crate::breadcrumb;
 
 pub struct VkSwapchain {
     device: Arc<VkDevice>,
     pub loader: khr::swapchain::Device,
     pub handle: vk::SwapchainKHR,
+    /// Window surface presented to.
+    surface: vk::SurfaceKHR,
     pub images: Vec<vk::Image>,
     pub image_format: vk::Format,
     pub extent: vk::Extent2D,
     pub image_views: Vec<vk::ImageView>,
     pub framebuffers: Vec<vk::Framebuffer>,
     pub depth_image: VkImage,
+    /// Depth aspect alone of `depth_image`, for sampling.
+    pub depth_view: vk::ImageView,
 }
 
 impl VkSwapchain {
     pub fn new(
         context: &VkContext,
+        surface: vk::SurfaceKHR,
         render_pass: &VkRenderPass,
         capabilities: vk::SurfaceCapabilitiesKHR,
         surface_format: vk::SurfaceFormatKHR,
@@ -27,12 +33,12 @@ impl VkSwapchain {
     ) -> Result<VkSwapchain, String> {
         Self::swapchain_create(
             context,
+            (surface, vk::SwapchainKHR::null()),
             render_pass,
             capabilities,
             surface_format,
             present_mode,
             extent,
-            vk::SwapchainKHR::null(),
         )
     }
 
@@ -50,12 +56,12 @@ impl VkSwapchain {
         let old_swapchain = self.handle;
         let new_swapchain = Self::swapchain_create(
             context,
+            (self.surface, old_swapchain),
             render_pass,
             capabilities,
             surface_format,
             present_mode,
             extent,
-            old_swapchain,
         )?;
 
         let _ = std::mem::replace(self, new_swapchain);
@@ -65,12 +71,12 @@ impl VkSwapchain {
 
     fn swapchain_create(
         context: &VkContext,
+        (surface, old_swapchain): (vk::SurfaceKHR, vk::SwapchainKHR),
         render_pass: &VkRenderPass,
         capabilities: vk::SurfaceCapabilitiesKHR,
         surface_format: vk::SurfaceFormatKHR,
         present_mode: vk::PresentModeKHR,
         extent: vk::Extent2D,
-        old_swapchain: vk::SwapchainKHR,
     ) -> Result<VkSwapchain, String> {
         let mut image_count = capabilities.min_image_count + 1;
         if capabilities.max_image_count > 0 && image_count > capabilities.max_image_count {
@@ -80,7 +86,7 @@ impl VkSwapchain {
         let image_format = surface_format.format;
         let mut create_info = vk::SwapchainCreateInfoKHR {
             s_type: vk::StructureType::SWAPCHAIN_CREATE_INFO_KHR,
-            surface: context.surface.handle,
+            surface,
             min_image_count: image_count,
             image_format,
             image_color_space: surface_format.color_space,
@@ -115,6 +121,7 @@ impl VkSwapchain {
                 .create_swapchain(&create_info, None)
                 .map_err(|e| format!("Failed to create swapchain: {}", e))?
         };
+        breadcrumb!("vkCreateSwapchainKHR");
 
         let images = unsafe {
             loader
@@ -131,10 +138,11 @@ impl VkSwapchain {
             extent.height,
             depth_format,
             vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            vk::ImageAspectFlags::DEPTH,
+            depth_aspects(depth_format),
         )?;
+        let depth_view = Self::create_depth_view(context.device(), &depth_image)?;
 
         let framebuffers = image_views
             .iter()
@@ -164,15 +172,44 @@ impl VkSwapchain {
             device: context.device(),
             loader,
             handle,
+            surface,
             images,
             image_format,
             extent,
             image_views,
             framebuffers,
             depth_image,
+            depth_view,
         })
     }
 
+    /// View of the depth aspect alone of `image`, for sampling.
+    pub fn create_depth_view(
+        device: Arc<VkDevice>,
+        image: &VkImage,
+    ) -> Result<vk::ImageView, String> {
+        let create_info = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+            image: image.handle,
+            view_type: vk::ImageViewType::TYPE_2D,
+            format: image.format,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::DEPTH,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            ..Default::default()
+        };
+        unsafe {
+            device
+                .handle
+                .create_image_view(&create_info, None)
+                .map_err(|e| format!("Failed to create depth view: {}", e))
+        }
+    }
+
     fn create_image_views(
         device: Arc<VkDevice>,
         images: &[vk::Image],
@@ -225,15 +262,20 @@ impl VkSwapchain {
         };
 
         match unsafe { self.loader.queue_present(*queue, &present_info) } {
-            Ok(suboptimal) => Ok(suboptimal),
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(true),
+            Ok(suboptimal) => {
+                breadcrumb!("vkQueuePresentKHR");
+                Ok(suboptimal)
+            }
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::ERROR_SURFACE_LOST_KHR) => Ok(true),
             Err(e) => Err(format!("Failed to present queue: {}", e)),
         }
     }
 }
 
-impl Drop for VkSwapchain {
-    fn drop(&mut self) {
+impl VkSwapchain {
+    /// Destroys the swapchain and its views early, releasing the window
+    /// surface for another swapchain.
+    pub fn destroy(&mut self) {
         unsafe {
             for framebuffer in self.framebuffers.drain(..) {
                 self.device.handle.destroy_framebuffer(framebuffer, None);
@@ -241,6 +283,10 @@ impl Drop for VkSwapchain {
             for view in self.image_views.drain(..) {
                 self.device.handle.destroy_image_view(view, None);
             }
+            if self.depth_view != vk::ImageView::null() {
+                self.device.handle.destroy_image_view(self.depth_view, None);
+                self.depth_view = vk::ImageView::null();
+            }
             if self.handle != vk::SwapchainKHR::null() {
                 self.loader.destroy_swapchain(self.handle, None);
                 self.handle = vk::SwapchainKHR::null();
@@ -248,3 +294,9 @@ impl Drop for VkSwapchain {
         }
     }
 }
+
+impl Drop for VkSwapchain {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
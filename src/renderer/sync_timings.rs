@@ -0,0 +1,88 @@
+use std::fmt;
+
+/// What holds the frame rate back, judged from where `Renderer::draw` waits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameBound {
+    /// The CPU waits for the GPU to finish earlier frames.
+    Gpu,
+    /// The CPU waits for the presentation engine, usually on vsync.
+    Present,
+    /// The CPU barely waits: updating and recording take the frame.
+    Cpu,
+}
+
+impl fmt::Display for FrameBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameBound::Gpu => write!(f, "GPU-bound"),
+            FrameBound::Present => write!(f, "present-bound"),
+            FrameBound::Cpu => write!(f, "CPU-bound"),
+        }
+    }
+}
+
+/// Time the CPU spent blocked on the GPU and the swapchain during a frame, in
+/// seconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncTimings {
+    /// In `wait_for_fences`, until the GPU is done with the frame slot.
+    pub fence_wait: f32,
+    /// In `acquire_next_image`.
+    pub acquire: f32,
+    /// In `queue_present`.
+    pub present: f32,
+    /// Since the previous frame started.
+    pub interval: f32,
+}
+
+impl SyncTimings {
+    /// Share of the frame a wait must take for the frame to be bound by it.
+    const BOUND_SHARE: f32 = 0.25;
+
+    /// Moves every timing `factor` of the way to `sample`, or straight to it
+    /// the first time.
+    pub fn smoothed(self, sample: SyncTimings, factor: f32) -> SyncTimings {
+        if self.interval <= 0. {
+            return sample;
+        }
+        let blend = |from: f32, to: f32| from + (to - from) * factor;
+        SyncTimings {
+            fence_wait: blend(self.fence_wait, sample.fence_wait),
+            acquire: blend(self.acquire, sample.acquire),
+            present: blend(self.present, sample.present),
+            interval: blend(self.interval, sample.interval),
+        }
+    }
+
+    /// Time left for the CPU's own work.
+    pub fn busy(&self) -> f32 {
+        (self.interval - self.fence_wait - self.acquire - self.present).max(0.)
+    }
+
+    /// The longest wait, when it takes a good share of the frame.
+    pub fn bound(&self) -> FrameBound {
+        let threshold = self.interval * Self::BOUND_SHARE;
+        let swapchain = self.acquire + self.present;
+        if swapchain >= self.fence_wait && swapchain > threshold {
+            FrameBound::Present
+        } else if self.fence_wait > threshold {
+            FrameBound::Gpu
+        } else {
+            FrameBound::Cpu
+        }
+    }
+}
+
+impl fmt::Display for SyncTimings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "fence {:.2} ms | acquire {:.2} ms | present {:.2} ms | cpu {:.2} ms ({})",
+            self.fence_wait * 1000.,
+            self.acquire * 1000.,
+            self.present * 1000.,
+            self.busy() * 1000.,
+            self.bound()
+        )
+    }
+}
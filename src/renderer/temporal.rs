@@ -0,0 +1,409 @@
+use ash::vk;
+use std::sync::Arc;
+
+use super::{depth_aspects, find_depth_format};
+use super::{
+    DescriptorLayouts, FrameData, SamplerKey, TransientImage, VkContext, VkDevice, VkImage,
+    VkPipeline, VkRenderPass, VkSwapchain,
+};
+use crate::math::{Mat4, Vec3};
+
+/// Weight of each new frame in the history: lower is smoother but slower to
+/// follow changes.
+const TEMPORAL_BLEND: f32 = 0.1;
+
+/// Subpixel offsets cycled through before repeating.
+const JITTER_PHASES: u32 = 8;
+
+/// Pixels along each edge of a compute workgroup, as in the shader.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Motion vectors in red and green, coverage in blue.
+pub const VELOCITY_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TemporalPushConstants {
+    /// Takes this frame's unjittered clip space to the previous frame's.
+    pub reprojection: Mat4,
+    /// Offset of this frame's samples, in texture coordinates of the scene
+    /// target.
+    pub jitter: [f32; 2],
+    pub blend: f32,
+    /// Nonzero when the history holds nothing yet.
+    pub reset: u32,
+}
+
+/// Color, depth and motion vectors the scene is drawn into before the
+/// resolve, `scale` times the swapchain's size.
+pub struct TemporalTarget {
+    device: Arc<VkDevice>,
+    pub color: VkImage,
+    pub depth: VkImage,
+    /// Depth aspect alone of `depth`, for sampling.
+    pub depth_view: vk::ImageView,
+    /// Transient, only read by the resolve.
+    pub velocity: TransientImage,
+    pub framebuffer: vk::Framebuffer,
+    /// `velocity` over `depth`.
+    pub velocity_framebuffer: vk::Framebuffer,
+    pub extent: vk::Extent2D,
+}
+
+impl TemporalTarget {
+    fn new(
+        context: &VkContext,
+        (scene_pass, velocity_pass): (&VkRenderPass, &VkRenderPass),
+        format: vk::Format,
+        velocity: TransientImage,
+    ) -> Result<Self, String> {
+        let extent = velocity.extent;
+        let color = VkImage::new(
+            context,
+            extent.width,
+            extent.height,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+        let depth_format = find_depth_format(&context.instance, &context.physical_device)?;
+        let depth = VkImage::new(
+            context,
+            extent.width,
+            extent.height,
+            depth_format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            depth_aspects(depth_format),
+        )?;
+        let depth_view = VkSwapchain::create_depth_view(context.device(), &depth)?;
+        let framebuffer =
+            Self::create_framebuffer(context, scene_pass, [color.view, depth.view], extent)?;
+        let velocity_framebuffer =
+            Self::create_framebuffer(context, velocity_pass, [velocity.view, depth.view], extent)?;
+
+        Ok(Self {
+            device: context.device(),
+            color,
+            depth,
+            depth_view,
+            velocity,
+            framebuffer,
+            velocity_framebuffer,
+            extent,
+        })
+    }
+
+    fn create_framebuffer(
+        context: &VkContext,
+        render_pass: &VkRenderPass,
+        attachments: [vk::ImageView; 2],
+        extent: vk::Extent2D,
+    ) -> Result<vk::Framebuffer, String> {
+        let create_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+            render_pass: render_pass.handle,
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            width: extent.width,
+            height: extent.height,
+            layers: 1,
+            ..Default::default()
+        };
+        unsafe {
+            context
+                .device()
+                .handle
+                .create_framebuffer(&create_info, None)
+                .map_err(|e| format!("Failed to create temporal framebuffer: {}", e))
+        }
+    }
+}
+
+impl Drop for TemporalTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .handle
+                .destroy_framebuffer(self.framebuffer, None);
+            self.device
+                .handle
+                .destroy_framebuffer(self.velocity_framebuffer, None);
+            self.device.handle.destroy_image_view(self.depth_view, None);
+        }
+    }
+}
+
+/// Temporal anti-aliasing and upscaling. The scene and debug lines are drawn
+/// into `target` with the projection moved by a different subpixel offset
+/// every frame, then the rigid meshes again over its depth, writing how far
+/// each of their pixels moved since the previous frame. A compute pass
+/// reprojects the previous result onto the new frame through those motion
+/// vectors, or through the depth where there are none, clamps it to the
+/// colors around each pixel so surfaces that appear don't leave trails, and
+/// blends the new frame in at the swapchain's resolution. The result is
+/// copied to the swapchain, under the HUD text.
+pub struct TemporalResolve {
+    resolve: VkPipeline,
+    composite: VkPipeline,
+    /// Draws the scene into `target`, leaving its color ready to sample.
+    pub scene_pass: VkRenderPass,
+    /// Draws the motion vectors over the scene's depth.
+    pub velocity_pass: VkRenderPass,
+    pub velocity: VkPipeline,
+    /// `velocity` for meshes of `CompactVertex`es.
+    pub compact_velocity: VkPipeline,
+    pub target: TemporalTarget,
+    /// Blended frames sized like the swapchain, one read and the other
+    /// written in turn.
+    pub history: [VkImage; 2],
+    /// Index of the `history` image written this frame.
+    pub current: usize,
+    /// Size of the swapchain, and of `history`.
+    extent: vk::Extent2D,
+    pub linear_sampler: vk::Sampler,
+    pub nearest_sampler: vk::Sampler,
+    /// Size of `target` relative to the swapchain.
+    scale: f32,
+    frame_count: u32,
+    /// Unjittered view-projection of the previous frame, `None` when the
+    /// history is empty.
+    previous_view_proj: Option<Mat4>,
+    /// Model transform of each object in the previous frame.
+    previous_models: Vec<Mat4>,
+}
+
+impl TemporalResolve {
+    pub fn new(
+        context: &VkContext,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        scale: f32,
+        velocity: TransientImage,
+    ) -> Result<Self, String> {
+        let scene_pass = VkRenderPass::sampled(context, format)?;
+        let velocity_pass = VkRenderPass::velocity(context, VELOCITY_FORMAT)?;
+        let target = TemporalTarget::new(context, (&scene_pass, &velocity_pass), format, velocity)?;
+        Ok(Self {
+            resolve: VkPipeline::compute(
+                context.device(),
+                descriptor_layouts,
+                "shaders/temporal.comp.spv",
+            )?,
+            composite: VkPipeline::temporal_composite(
+                context.device(),
+                render_pass,
+                descriptor_layouts,
+            )?,
+            velocity: VkPipeline::velocity(
+                context.device(),
+                &velocity_pass,
+                descriptor_layouts,
+                false,
+            )?,
+            compact_velocity: VkPipeline::velocity(
+                context.device(),
+                &velocity_pass,
+                descriptor_layouts,
+                true,
+            )?,
+            scene_pass,
+            velocity_pass,
+            target,
+            history: [
+                Self::create_history(context, extent)?,
+                Self::create_history(context, extent)?,
+            ],
+            current: 0,
+            extent,
+            linear_sampler: context.device.sampler(SamplerKey {
+                filter: vk::Filter::LINEAR,
+                address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                anisotropy: 1,
+            })?,
+            nearest_sampler: context.device.sampler(SamplerKey::nearest())?,
+            scale,
+            frame_count: 0,
+            previous_view_proj: None,
+            previous_models: Vec::new(),
+        })
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Recreates the images for a new swapchain extent, dropping the history.
+    /// `velocity` is sized for the target, `scaled` from `extent`.
+    pub fn resize(
+        &mut self,
+        context: &VkContext,
+        extent: vk::Extent2D,
+        velocity: TransientImage,
+    ) -> Result<(), String> {
+        self.target = TemporalTarget::new(
+            context,
+            (&self.scene_pass, &self.velocity_pass),
+            self.target.color.format,
+            velocity,
+        )?;
+        self.history = [
+            Self::create_history(context, extent)?,
+            Self::create_history(context, extent)?,
+        ];
+        self.extent = extent;
+        self.previous_view_proj = None;
+        Ok(())
+    }
+
+    /// Size of the target drawn at `scale` times `extent`.
+    pub fn scaled(extent: vk::Extent2D, scale: f32) -> vk::Extent2D {
+        let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+        vk::Extent2D {
+            width: scaled(extent.width),
+            height: scaled(extent.height),
+        }
+    }
+
+    fn create_history(context: &VkContext, extent: vk::Extent2D) -> Result<VkImage, String> {
+        VkImage::new(
+            context,
+            extent.width,
+            extent.height,
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::ImageAspectFlags::COLOR,
+        )
+    }
+
+    /// Moves on to the next frame, seen through `view_proj` with objects
+    /// placed by `models`: swaps the history images and returns the jitter to
+    /// apply to the projection, the resolve's parameters, and each object's
+    /// model-view-projection in the previous frame.
+    pub fn next_frame(
+        &mut self,
+        view_proj: Mat4,
+        models: Vec<Mat4>,
+    ) -> (Mat4, TemporalPushConstants, Vec<Mat4>) {
+        self.current = 1 - self.current;
+        self.frame_count = self.frame_count.wrapping_add(1);
+
+        // Halton (2, 3) points, spread evenly over the pixel.
+        let phase = self.frame_count % JITTER_PHASES + 1;
+        let offset = [halton(phase, 2) - 0.5, halton(phase, 3) - 0.5];
+        let extent = self.target.extent;
+        let ndc = [
+            offset[0] * 2. / extent.width as f32,
+            offset[1] * 2. / extent.height as f32,
+        ];
+        let jitter = Mat4::identity().translate(Vec3::new(ndc[0], ndc[1], 0.));
+
+        let reprojection = match (self.previous_view_proj, view_proj.inverse()) {
+            (Some(previous), Some(inverse)) => Some(previous * inverse),
+            _ => None,
+        };
+        // Objects new since the previous frame count as still.
+        let previous_view_proj = self.previous_view_proj.unwrap_or(view_proj);
+        let previous_transforms = models
+            .iter()
+            .enumerate()
+            .map(|(i, model)| previous_view_proj * *self.previous_models.get(i).unwrap_or(model))
+            .collect();
+        self.previous_view_proj = Some(view_proj);
+        self.previous_models = models;
+        (
+            jitter,
+            TemporalPushConstants {
+                reprojection: reprojection.unwrap_or_else(Mat4::identity),
+                jitter: [ndc[0] * 0.5, ndc[1] * 0.5],
+                blend: TEMPORAL_BLEND,
+                reset: reprojection.is_none() as u32,
+            },
+            previous_transforms,
+        )
+    }
+
+    /// Blends `frame`'s scene, left in `target` by the scene and velocity
+    /// passes, into the history image written this frame. The render graph
+    /// makes the images ready for it.
+    pub fn record(&self, device: &VkDevice, cmd: vk::CommandBuffer, frame: &FrameData) {
+        let Some(resolve) = &frame.temporal else {
+            return;
+        };
+
+        unsafe {
+            device.handle.cmd_bind_pipeline(
+                cmd,
+                vk::PipelineBindPoint::COMPUTE,
+                self.resolve.handle,
+            );
+            device.handle.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::COMPUTE,
+                self.resolve.layout,
+                0,
+                &[frame.descriptor_set],
+                &[],
+            );
+            device.handle.cmd_push_constants(
+                cmd,
+                self.resolve.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                std::slice::from_raw_parts(
+                    resolve as *const _ as *const u8,
+                    std::mem::size_of::<TemporalPushConstants>(),
+                ),
+            );
+            device.handle.cmd_dispatch(
+                cmd,
+                self.extent.width.div_ceil(WORKGROUP_SIZE),
+                self.extent.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+    }
+
+    /// Copies the history written this frame over the whole swapchain image,
+    /// inside its pass.
+    pub fn composite(&self, device: &VkDevice, cmd: vk::CommandBuffer, frame: &FrameData) {
+        unsafe {
+            device.handle.cmd_bind_pipeline(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.composite.handle,
+            );
+            device.handle.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.composite.layout,
+                0,
+                &[frame.descriptor_set],
+                &[],
+            );
+            device
+                .handle
+                .cmd_set_cull_mode(cmd, vk::CullModeFlags::NONE);
+            device.handle.cmd_draw(cmd, 3, 1, 0, 0);
+        }
+    }
+}
+
+/// `index`th point of the Halton sequence in `base`, in [0, 1).
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.;
+    let mut result = 0.;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
@@ -0,0 +1,279 @@
+use ash::vk;
+
+use crate::math::{Vec2, Vec4};
+use crate::scene::Hud;
+
+/// Vertex of the HUD text pipeline, positioned in window pixels.
+#[derive(Clone, Copy)]
+pub struct TextVertex {
+    pub position: Vec2,
+    pub uv: Vec2,
+    pub color: Vec4,
+}
+
+impl TextVertex {
+    pub fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<TextVertex>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }
+    }
+
+    pub fn get_attribute_description() -> [vk::VertexInputAttributeDescription; 3] {
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: std::mem::offset_of!(TextVertex, position) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: std::mem::offset_of!(TextVertex, uv) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: std::mem::offset_of!(TextVertex, color) as u32,
+            },
+        ]
+    }
+}
+
+/// Built-in 5x7 bitmap font covering printable ASCII, baked into a texture
+/// atlas of 8x8 cells.
+pub struct BitmapFont;
+
+impl BitmapFont {
+    pub const GLYPH_WIDTH: u32 = 5;
+    pub const GLYPH_HEIGHT: u32 = 7;
+    /// Horizontal distance between glyph origins, in font pixels.
+    pub const ADVANCE: f32 = 6.;
+    pub const LINE_HEIGHT: f32 = 9.;
+
+    const CELL: u32 = 8;
+    const COLUMNS: u32 = 16;
+    const FIRST: u8 = b' ';
+    const SOLID: usize = GLYPHS.len() - 1;
+
+    pub fn atlas_size() -> (u32, u32) {
+        let rows = (GLYPHS.len() as u32).div_ceil(Self::COLUMNS);
+        (Self::COLUMNS * Self::CELL, rows * Self::CELL)
+    }
+
+    /// White RGBA8 atlas with the glyph coverage in the alpha channel.
+    pub fn atlas_rgba8() -> Vec<u8> {
+        let (width, height) = Self::atlas_size();
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+
+        for (index, rows) in GLYPHS.iter().enumerate() {
+            let (cell_x, cell_y) = Self::cell(index);
+            for (y, row) in rows.iter().enumerate() {
+                for x in 0..Self::GLYPH_WIDTH {
+                    if row & (1 << (Self::GLYPH_WIDTH - 1 - x)) == 0 {
+                        continue;
+                    }
+                    let px = cell_x + x;
+                    let py = cell_y + y as u32;
+                    let offset = ((py * width + px) * 4) as usize;
+                    pixels[offset..offset + 4].copy_from_slice(&[255; 4]);
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// Appends two triangles per HUD rectangle, then per visible character of
+    /// every HUD text so the text lands on top.
+    pub fn layout(hud: &Hud, vertices: &mut Vec<TextVertex>) {
+        let (atlas_width, atlas_height) = Self::atlas_size();
+        let texel = Vec2::new(1. / atlas_width as f32, 1. / atlas_height as f32);
+        let glyph = Vec2::new(Self::GLYPH_WIDTH as f32, Self::GLYPH_HEIGHT as f32);
+
+        // Rectangles stretch a single texel from the middle of the solid glyph.
+        let (solid_x, solid_y) = Self::cell(Self::SOLID);
+        let solid = Vec2::new(
+            (solid_x as f32 + 2.5) * texel.x,
+            (solid_y as f32 + 3.5) * texel.y,
+        );
+        for rect in &hud.rects {
+            let corner = |x: f32, y: f32| TextVertex {
+                position: Vec2::new(x, y),
+                uv: solid,
+                color: rect.color,
+            };
+            let (p0, p1) = (rect.position, rect.position + rect.size);
+            let a = corner(p0.x, p0.y);
+            let b = corner(p1.x, p0.y);
+            let c = corner(p1.x, p1.y);
+            let d = corner(p0.x, p1.y);
+            vertices.extend_from_slice(&[a, b, c, a, c, d]);
+        }
+
+        for entry in &hud.texts {
+            let mut pen = entry.position;
+            for c in entry.text.chars() {
+                if c == '\n' {
+                    pen = Vec2::new(entry.position.x, pen.y + Self::LINE_HEIGHT * entry.scale);
+                    continue;
+                }
+                if c != ' ' {
+                    let (cell_x, cell_y) = Self::cell(Self::glyph_index(c));
+                    let uv0 = Vec2::new(cell_x as f32 * texel.x, cell_y as f32 * texel.y);
+                    let uv1 = Vec2::new(uv0.x + glyph.x * texel.x, uv0.y + glyph.y * texel.y);
+                    let p0 = pen;
+                    let p1 =
+                        Vec2::new(pen.x + glyph.x * entry.scale, pen.y + glyph.y * entry.scale);
+
+                    let corner = |p: Vec2, uv: Vec2| TextVertex {
+                        position: p,
+                        uv,
+                        color: entry.color,
+                    };
+                    let a = corner(p0, uv0);
+                    let b = corner(Vec2::new(p1.x, p0.y), Vec2::new(uv1.x, uv0.y));
+                    let c = corner(p1, uv1);
+                    let d = corner(Vec2::new(p0.x, p1.y), Vec2::new(uv0.x, uv1.y));
+                    vertices.extend_from_slice(&[a, b, c, a, c, d]);
+                }
+                pen.x += Self::ADVANCE * entry.scale;
+            }
+        }
+    }
+
+    /// Size in window pixels of `text` laid out at `scale`.
+    pub fn measure(text: &str, scale: f32) -> Vec2 {
+        let lines = text.split('\n');
+        let columns = lines.clone().map(|l| l.chars().count()).max().unwrap_or(0);
+        let rows = lines.count();
+        Vec2::new(
+            columns as f32 * Self::ADVANCE * scale,
+            rows as f32 * Self::LINE_HEIGHT * scale,
+        )
+    }
+
+    fn glyph_index(c: char) -> usize {
+        let index = (c as u32).wrapping_sub(Self::FIRST as u32) as usize;
+        if index < Self::SOLID {
+            index
+        } else {
+            (b'?' - Self::FIRST) as usize
+        }
+    }
+
+    /// Top-left texel of the glyph's cell, past the 1 texel padding keeping
+    /// neighbours from bleeding in.
+    fn cell(index: usize) -> (u32, u32) {
+        let index = index as u32;
+        (
+            (index % Self::COLUMNS) * Self::CELL + 1,
+            (index / Self::COLUMNS) * Self::CELL + 1,
+        )
+    }
+}
+
+/// Rows of each glyph from ' ' to '~' then a solid block, top to bottom, the
+/// leftmost pixel in bit 4.
+const GLYPHS: [[u8; 7]; 96] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // &
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // @
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // \
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // _
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F], // a
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E], // b
+    [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E], // c
+    [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F], // d
+    [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E], // e
+    [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08], // f
+    [0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E], // g
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // h
+    [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E], // i
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0C], // j
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // k
+    [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // l
+    [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11], // m
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // n
+    [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E], // o
+    [0x00, 0x00, 0x1E, 0x11, 0x1E, 0x10, 0x10], // p
+    [0x00, 0x00, 0x0D, 0x13, 0x0F, 0x01, 0x01], // q
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // r
+    [0x00, 0x00, 0x0E, 0x10, 0x0E, 0x01, 0x1E], // s
+    [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06], // t
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D], // u
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04], // v
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A], // w
+    [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11], // x
+    [0x00, 0x00, 0x11, 0x11, 0x0F, 0x01, 0x0E], // y
+    [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F], // z
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // {
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // |
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // }
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // ~
+    [0x1F, 0x1F, 0x1F, 0x1F, 0x1F, 0x1F, 0x1F], // solid block, used by rectangles
+];
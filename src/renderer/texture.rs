@@ -1,7 +1,46 @@
 use ash::vk;
+use image::{Rgba, RgbaImage};
 use std::sync::Arc;
 
-use super::{VkBuffer, VkCommandPool, VkContext, VkDevice, VkQueue};
+use crate::assets;
+
+use super::{
+    image_size, load_compressed, load_ktx2, CompressedFormats, CompressedImage, SamplerKey,
+    VkBuffer, VkCommandPool, VkContext, VkDevice, VkQueue,
+};
+
+/// Decodes an image file held in memory (PNG, JPEG, ...) into RGBA8 pixels.
+pub fn decode_rgba8(bytes: &[u8]) -> Result<RgbaImage, String> {
+    image::load_from_memory(bytes)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| format!("Failed to decode image: {}", e))
+}
+
+/// Reads and decodes the image at `path`, see `decode_rgba8`. KTX2 files
+/// are decoded from their compressed blocks.
+pub fn load_rgba8(path: &str) -> Result<RgbaImage, String> {
+    if path.ends_with(".ktx2") {
+        return load_ktx2(path, &CompressedFormats::default())?.decode_rgba8();
+    }
+    let bytes = assets::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    decode_rgba8(&bytes)
+}
+
+/// Reads the image at `path` as a cutout mask for `map_d`: its alpha channel
+/// when it has one, its luminance otherwise, stored as alpha over white.
+pub fn load_mask_rgba8(path: &str) -> Result<RgbaImage, String> {
+    let bytes = assets::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let image =
+        image::load_from_memory(&bytes).map_err(|e| format!("Failed to decode image: {}", e))?;
+    if image.color().has_alpha() {
+        return Ok(image.to_rgba8());
+    }
+
+    let luma = image.to_luma8();
+    Ok(RgbaImage::from_fn(luma.width(), luma.height(), |x, y| {
+        Rgba([255, 255, 255, luma.get_pixel(x, y)[0]])
+    }))
+}
 
 pub struct VkTexture {
     device: Arc<VkDevice>,
@@ -9,6 +48,7 @@ pub struct VkTexture {
     pub memory: vk::DeviceMemory,
     pub view: vk::ImageView,
     pub format: vk::Format,
+    /// Shared through the device's sampler cache, not owned.
     pub sampler: vk::Sampler,
 }
 
@@ -19,11 +59,38 @@ impl VkTexture {
         command_pool: &VkCommandPool,
         path: &str,
     ) -> Result<Self, String> {
-        let img = image::open(path)
-            .map_err(|e| format!("Failed to open texture '{}': {}", path, e))?
-            .to_rgba8();
-        let (width, height) = img.dimensions();
-        Self::from_rgba8(context, queue, command_pool, img.as_raw(), width, height)
+        // Compressed variants the device samples are uploaded as they are,
+        // anything else is decoded.
+        let compressed = load_compressed(path, &context.device.compressed_formats)
+            .map_err(|e| format!("Failed to open texture '{}': {}", path, e))?;
+        if let Some(compressed) = compressed {
+            return Self::from_compressed(
+                context,
+                queue,
+                command_pool,
+                &compressed,
+                SamplerKey::default(),
+            );
+        }
+        let img =
+            load_rgba8(path).map_err(|e| format!("Failed to open texture '{}': {}", path, e))?;
+        Self::from_image(context, queue, command_pool, &img)
+    }
+
+    pub fn from_image(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        image: &RgbaImage,
+    ) -> Result<Self, String> {
+        Self::from_rgba8(
+            context,
+            queue,
+            command_pool,
+            image.as_raw(),
+            image.dimensions(),
+            SamplerKey::default(),
+        )
     }
 
     pub fn white(
@@ -32,28 +99,100 @@ impl VkTexture {
         command_pool: &VkCommandPool,
     ) -> Result<Self, String> {
         let pixels: [u8; 4] = [255, 255, 255, 255];
-        Self::from_rgba8(context, queue, command_pool, &pixels, 1, 1)
+        Self::from_rgba8(
+            context,
+            queue,
+            command_pool,
+            &pixels,
+            (1, 1),
+            SamplerKey::default(),
+        )
     }
 
-    fn from_rgba8(
+    /// Uploads tightly packed sRGB RGBA8 pixels, sampled with `sampler`.
+    pub fn from_rgba8(
         context: &VkContext,
         queue: &VkQueue,
         command_pool: &VkCommandPool,
         pixels: &[u8],
-        width: u32,
-        height: u32,
+        (width, height): (u32, u32),
+        sampler: SamplerKey,
     ) -> Result<Self, String> {
-        let device = context.device();
+        Self::upload(
+            context,
+            queue,
+            command_pool,
+            vk::Format::R8G8B8A8_SRGB,
+            (width, height),
+            &[pixels],
+            sampler,
+        )
+    }
 
-        let staging = VkBuffer::<u8>::host_visible(
+    /// Uploads `image`'s levels as they are, which the device must sample.
+    pub fn from_compressed(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        image: &CompressedImage,
+        sampler: SamplerKey,
+    ) -> Result<Self, String> {
+        let levels: Vec<&[u8]> = image.levels.iter().map(Vec::as_slice).collect();
+        Self::upload(
             context,
-            pixels.len(),
-            vk::BufferUsageFlags::TRANSFER_SRC,
-        )?;
+            queue,
+            command_pool,
+            image.format,
+            (image.width, image.height),
+            &levels,
+            sampler,
+        )
+    }
+
+    /// Creates an image in `format` with one mip level per entry of `levels`,
+    /// the largest first, and copies them in.
+    fn upload(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        format: vk::Format,
+        (width, height): (u32, u32),
+        levels: &[&[u8]],
+        sampler: SamplerKey,
+    ) -> Result<Self, String> {
+        let device = context.device();
+
+        let mut regions = Vec::with_capacity(levels.len());
+        let mut offset = 0;
+        for (level, data) in levels.iter().enumerate() {
+            let extent = vk::Extent3D {
+                width: (width >> level).max(1),
+                height: (height >> level).max(1),
+                depth: 1,
+            };
+            if image_size(format, extent.width, extent.height) != Some(data.len()) {
+                return Err(format!("Texture level {} has the wrong size", level));
+            }
+            regions.push(vk::BufferImageCopy {
+                buffer_offset: offset as vk::DeviceSize,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: level as u32,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                image_extent: extent,
+            });
+            offset += data.len();
+        }
 
-        staging.write(pixels);
+        let staging =
+            VkBuffer::<u8>::host_visible(context, offset, vk::BufferUsageFlags::TRANSFER_SRC)?;
+        staging.write(&levels.concat());
 
-        let format = vk::Format::R8G8B8A8_SRGB;
         let tiling = vk::ImageTiling::OPTIMAL;
         let usage = vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED;
         let properties = vk::MemoryPropertyFlags::DEVICE_LOCAL;
@@ -67,7 +206,7 @@ impl VkTexture {
                 height,
                 depth: 1,
             },
-            mip_levels: 1,
+            mip_levels: levels.len() as u32,
             array_layers: 1,
             format,
             tiling,
@@ -119,7 +258,7 @@ impl VkTexture {
             subresource_range: vk::ImageSubresourceRange {
                 aspect_mask,
                 base_mip_level: 0,
-                level_count: 1,
+                level_count: levels.len() as u32,
                 base_array_layer: 0,
                 layer_count: 1,
             },
@@ -133,43 +272,26 @@ impl VkTexture {
                 .map_err(|e| format!("Failed to create image view: {}", e))?
         };
 
-        command_pool.transition_image_layout(
+        let layers = (levels.len() as u32, 1);
+        command_pool.transition_image_layers(
             queue,
             handle,
+            layers,
             vk::ImageLayout::UNDEFINED,
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
         )?;
 
-        command_pool.copy_buffer_to_image(queue, staging.handle, handle, width, height)?;
+        command_pool.copy_buffer_to_image_regions(queue, staging.handle, handle, &regions)?;
 
-        command_pool.transition_image_layout(
+        command_pool.transition_image_layers(
             queue,
             handle,
+            layers,
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         )?;
 
-        let sampler_info = vk::SamplerCreateInfo {
-            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
-            mag_filter: vk::Filter::LINEAR,
-            min_filter: vk::Filter::LINEAR,
-            address_mode_u: vk::SamplerAddressMode::REPEAT,
-            address_mode_v: vk::SamplerAddressMode::REPEAT,
-            address_mode_w: vk::SamplerAddressMode::REPEAT,
-            anisotropy_enable: vk::FALSE,
-            border_color: vk::BorderColor::INT_OPAQUE_BLACK,
-            unnormalized_coordinates: vk::FALSE,
-            compare_enable: vk::FALSE,
-            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
-            ..Default::default()
-        };
-
-        let sampler = unsafe {
-            device
-                .handle
-                .create_sampler(&sampler_info, None)
-                .map_err(|e| format!("Failed to create sampler: {}", e))?
-        };
+        let sampler = device.sampler(sampler)?;
 
         Ok(Self {
             device,
@@ -185,7 +307,6 @@ impl VkTexture {
 impl Drop for VkTexture {
     fn drop(&mut self) {
         unsafe {
-            self.device.handle.destroy_sampler(self.sampler, None);
             self.device.handle.destroy_image_view(self.view, None);
             self.device.handle.free_memory(self.memory, None);
             self.device.handle.destroy_image(self.handle, None);
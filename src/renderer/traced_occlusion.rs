@@ -0,0 +1,305 @@
+use ash::vk;
+
+use super::{
+    DescriptorLayouts, DynamicBuffer, FrameData, ResourcesManager, SamplerKey, TransientImage,
+    VkBuffer, VkCommandPool, VkContext, VkDevice, VkPipeline, VkQueue, VkRenderPass,
+};
+use crate::math::{Aabb, Mat4, Vec4};
+use crate::scene::Scene;
+
+/// Objects the occlusion rays are traced against; the rest cast none.
+pub const MAX_TRACED_INSTANCES: usize = 256;
+
+/// Rays per pixel.
+const TRACED_OCCLUSION_SAMPLES: u32 = 8;
+
+/// Rays longer than this fraction of the scene's bounding radius don't count
+/// as occluded.
+const TRACED_OCCLUSION_RANGE: f32 = 0.1;
+
+/// Offset along the normal for rays leaving a surface, per unit of distance
+/// to the camera, since depth precision drops with distance.
+const TRACED_OCCLUSION_BIAS: f32 = 2e-3;
+
+/// Pixels along each edge of a compute workgroup, as in the shader.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// `BvhNode` as laid out in the shader's storage buffer, with `start`
+/// relative to the whole buffer.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct GpuBvhNode {
+    pub min: [f32; 3],
+    pub start: u32,
+    pub max: [f32; 3],
+    pub count: u32,
+}
+
+/// An object traced against: its mesh's root node and the transform taking
+/// rays into mesh space.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TracedInstance {
+    pub to_local: Mat4,
+    pub root: u32,
+    pub _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TracePushConstants {
+    pub instance_count: u32,
+    pub samples: u32,
+    pub range: f32,
+    pub bias: f32,
+}
+
+/// The BVHs and triangles of every uploaded mesh, concatenated into storage
+/// buffers.
+pub struct TracedGeometry {
+    pub nodes: VkBuffer<GpuBvhNode>,
+    /// Three positions per triangle, in BVH leaf order.
+    pub triangles: VkBuffer<Vec4>,
+    /// Root node of each mesh, by handle.
+    roots: Vec<u32>,
+}
+
+impl TracedGeometry {
+    /// Flattens the meshes of `resources`, or `None` when they have no
+    /// triangles.
+    fn new(
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        resources: &ResourcesManager,
+    ) -> Result<Option<Self>, String> {
+        let mut nodes: Vec<GpuBvhNode> = Vec::new();
+        let mut triangles: Vec<Vec4> = Vec::new();
+        let mut roots = Vec::new();
+        for mesh in &resources.meshes {
+            let node_offset = nodes.len() as u32;
+            let triangle_offset = (triangles.len() / 3) as u32;
+            roots.push(node_offset);
+
+            nodes.extend(mesh.bvh.nodes().iter().map(|node| GpuBvhNode {
+                min: [node.bounds.min.x, node.bounds.min.y, node.bounds.min.z],
+                start: if node.count == 0 {
+                    node.start + node_offset
+                } else {
+                    node.start + triangle_offset
+                },
+                max: [node.bounds.max.x, node.bounds.max.y, node.bounds.max.z],
+                count: node.count,
+            }));
+
+            for triangle in mesh.bvh.triangles() {
+                let group = &mesh.geometry.groups[triangle.group as usize];
+                let first = triangle.triangle as usize * 3;
+                triangles.extend(
+                    group.indices[first..first + 3]
+                        .iter()
+                        .map(|&index| Vec4::from_vec3(group.vertices[index as usize].position, 1.)),
+                );
+            }
+        }
+
+        if triangles.is_empty() {
+            return Ok(None);
+        }
+
+        let usage = vk::BufferUsageFlags::STORAGE_BUFFER;
+        Ok(Some(Self {
+            nodes: VkBuffer::device_local(context, queue, command_pool, &nodes, usage)?,
+            triangles: VkBuffer::device_local(context, queue, command_pool, &triangles, usage)?,
+            roots,
+        }))
+    }
+}
+
+/// Screen-space ambient occlusion traced by a compute pass against the scene
+/// triangles, for devices without ray tracing extensions. The pass runs
+/// between the objects and the overlays: it rebuilds positions and normals
+/// from the depth buffer, casts short rays through each mesh's BVH, and the
+/// result is multiplied over the image. Skinned meshes are traced in their
+/// bind pose.
+pub struct TracedOcclusion {
+    pipeline: VkPipeline,
+    composite: VkPipeline,
+    /// Continues the swapchain pass after the compute pass.
+    pub resume_pass: VkRenderPass,
+    /// Visibility per pixel, sized like the swapchain and replaced with it.
+    pub image: TransientImage,
+    pub depth_sampler: vk::Sampler,
+    pub instances: DynamicBuffer<TracedInstance>,
+    /// Absent until first traced, or when no mesh has triangles.
+    pub geometry: Option<TracedGeometry>,
+    /// `ResourcesManager::generation` `geometry` was built from.
+    generation: Option<u64>,
+}
+
+impl TracedOcclusion {
+    pub fn new(
+        context: &VkContext,
+        render_pass: &VkRenderPass,
+        descriptor_layouts: &DescriptorLayouts,
+        format: vk::Format,
+        image: TransientImage,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            pipeline: VkPipeline::compute(
+                context.device(),
+                descriptor_layouts,
+                "shaders/traced_occlusion.comp.spv",
+            )?,
+            composite: VkPipeline::occlusion_composite(
+                context.device(),
+                render_pass,
+                descriptor_layouts,
+            )?,
+            resume_pass: VkRenderPass::resume(context, format)?,
+            image,
+            depth_sampler: context.device.sampler(SamplerKey::nearest())?,
+            instances: DynamicBuffer::new(
+                context,
+                MAX_TRACED_INSTANCES,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            )?,
+            geometry: None,
+            generation: None,
+        })
+    }
+
+    /// Rebuilds the geometry buffers when meshes were uploaded since the last
+    /// build. Waits for the device first, as frames in flight may read them.
+    pub fn update_geometry(
+        &mut self,
+        context: &VkContext,
+        queue: &VkQueue,
+        command_pool: &VkCommandPool,
+        resources: &ResourcesManager,
+    ) -> Result<(), String> {
+        if self.generation == Some(resources.generation) {
+            return Ok(());
+        }
+
+        context.device.wait_idle();
+        self.geometry = TracedGeometry::new(context, queue, command_pool, resources)?;
+        self.generation = Some(resources.generation);
+        Ok(())
+    }
+
+    /// Writes the scene's objects into frame `frame`'s instances and returns
+    /// the dispatch parameters, or `None` when there is nothing to trace.
+    pub fn upload_instances(
+        &self,
+        frame: usize,
+        scene: &Scene,
+        resources: &ResourcesManager,
+    ) -> Option<TracePushConstants> {
+        let geometry = self.geometry.as_ref()?;
+
+        let mut instances = Vec::new();
+        let mut bounds = Aabb::EMPTY;
+        for object in scene.objects.iter() {
+            let Some(&root) = geometry.roots.get(object.id()) else {
+                continue;
+            };
+            let to_world = object.transform();
+            let Some(to_local) = to_world.inverse() else {
+                continue;
+            };
+
+            bounds = bounds.union(resources.get_mesh(object.id()).bounds.transform(&to_world));
+            instances.push(TracedInstance {
+                to_local,
+                root,
+                _padding: [0; 3],
+            });
+            if instances.len() == MAX_TRACED_INSTANCES {
+                break;
+            }
+        }
+
+        if instances.is_empty() {
+            return None;
+        }
+
+        self.instances.write(frame, &instances);
+        Some(TracePushConstants {
+            instance_count: instances.len() as u32,
+            samples: TRACED_OCCLUSION_SAMPLES,
+            range: bounds.radius() * TRACED_OCCLUSION_RANGE,
+            bias: TRACED_OCCLUSION_BIAS,
+        })
+    }
+
+    /// Traces `frame`'s occlusion from the depth left by the first half of
+    /// the frame. The render graph makes the images ready for it.
+    pub fn record(
+        &self,
+        device: &VkDevice,
+        cmd: vk::CommandBuffer,
+        frame: &FrameData,
+        extent: vk::Extent2D,
+    ) {
+        let Some(trace) = &frame.occlusion else {
+            return;
+        };
+
+        unsafe {
+            device.handle.cmd_bind_pipeline(
+                cmd,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.handle,
+            );
+            device.handle.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.layout,
+                0,
+                &[frame.descriptor_set],
+                &[],
+            );
+            device.handle.cmd_push_constants(
+                cmd,
+                self.pipeline.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                std::slice::from_raw_parts(
+                    trace as *const _ as *const u8,
+                    std::mem::size_of::<TracePushConstants>(),
+                ),
+            );
+            device.handle.cmd_dispatch(
+                cmd,
+                extent.width.div_ceil(WORKGROUP_SIZE),
+                extent.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+    }
+
+    /// Multiplies the traced visibility over the image, inside the resumed
+    /// pass.
+    pub fn composite(&self, device: &VkDevice, cmd: vk::CommandBuffer, frame: &FrameData) {
+        unsafe {
+            device.handle.cmd_bind_pipeline(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.composite.handle,
+            );
+            device.handle.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.composite.layout,
+                0,
+                &[frame.descriptor_set],
+                &[],
+            );
+            device
+                .handle
+                .cmd_set_cull_mode(cmd, vk::CullModeFlags::NONE);
+            device.handle.cmd_draw(cmd, 3, 1, 0, 0);
+        }
+    }
+}
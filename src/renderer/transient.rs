@@ -0,0 +1,349 @@
+use ash::vk;
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::{ImageState, VkContext, VkDevice};
+
+/// Passes of a frame, in the order the render graph runs them. Transient
+/// images live from one to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FramePass {
+    OverdrawClear,
+    Scene,
+    TracedOcclusion,
+    Overlays,
+    Velocity,
+    TemporalResolve,
+    TemporalComposite,
+}
+
+/// Usage of images the tile memory of tilers can hold without backing them.
+const ATTACHMENT_USAGE: vk::ImageUsageFlags = vk::ImageUsageFlags::from_raw(
+    vk::ImageUsageFlags::COLOR_ATTACHMENT.as_raw()
+        | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT.as_raw()
+        | vk::ImageUsageFlags::INPUT_ATTACHMENT.as_raw(),
+);
+
+/// An intermediate image of the frame, written and read by the passes from
+/// `first` to `last` and dead outside of them.
+#[derive(Debug, Clone, Copy)]
+pub struct TransientDesc {
+    pub extent: vk::Extent2D,
+    pub format: vk::Format,
+    pub usage: vk::ImageUsageFlags,
+    pub aspect_mask: vk::ImageAspectFlags,
+    pub first: FramePass,
+    pub last: FramePass,
+}
+
+impl TransientDesc {
+    fn overlaps(&self, other: &TransientDesc) -> bool {
+        self.first <= other.last && other.first <= self.last
+    }
+}
+
+struct TransientMemory {
+    device: Arc<VkDevice>,
+    handle: vk::DeviceMemory,
+}
+
+impl Drop for TransientMemory {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle.free_memory(self.handle, None);
+        }
+    }
+}
+
+/// Memory of a placed image, its range and the uses it shares.
+type Placement = (Arc<TransientMemory>, Range<u64>, ImageState);
+
+/// Memory block shared by the transient images placed in it.
+struct Block {
+    type_bits: u32,
+    memory_type: u32,
+    /// Lazily allocated, holding a single attachment.
+    lazy: bool,
+    /// Range each image placed in the block occupies, by index.
+    placed: Vec<(usize, Range<u64>)>,
+}
+
+/// Image whose memory is shared with the other transient images used by
+/// passes outside its own. The render graph makes each image's first use
+/// wait for those of the images it replaces.
+pub struct TransientImage {
+    device: Arc<VkDevice>,
+    memory: Arc<TransientMemory>,
+    pub handle: vk::Image,
+    pub view: vk::ImageView,
+    pub extent: vk::Extent2D,
+    pub format: vk::Format,
+    pub aspect_mask: vk::ImageAspectFlags,
+    /// Bytes of the shared memory the image occupies.
+    pub range: Range<u64>,
+    /// Every use of the images sharing that range, any of which the previous
+    /// frame may have left pending.
+    pub previous_use: ImageState,
+}
+
+impl TransientImage {
+    /// Creates the images of `descs`, in order, packing those whose passes
+    /// don't overlap into the same memory. Images only used as attachments
+    /// get lazily allocated memory of their own when the device has some, so
+    /// tilers need not back them at all.
+    pub fn allocate(
+        context: &VkContext,
+        descs: &[TransientDesc],
+    ) -> Result<Vec<TransientImage>, String> {
+        let device = context.device();
+
+        let mut images = Vec::with_capacity(descs.len());
+        for desc in descs {
+            let mut usage = desc.usage;
+            if (usage & !ATTACHMENT_USAGE).is_empty() {
+                usage |= vk::ImageUsageFlags::TRANSIENT_ATTACHMENT;
+            }
+            let create_info = vk::ImageCreateInfo {
+                s_type: vk::StructureType::IMAGE_CREATE_INFO,
+                image_type: vk::ImageType::TYPE_2D,
+                extent: vk::Extent3D {
+                    width: desc.extent.width,
+                    height: desc.extent.height,
+                    depth: 1,
+                },
+                mip_levels: 1,
+                array_layers: 1,
+                format: desc.format,
+                tiling: vk::ImageTiling::OPTIMAL,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                usage,
+                samples: vk::SampleCountFlags::TYPE_1,
+                sharing_mode: vk::SharingMode::EXCLUSIVE,
+                ..Default::default()
+            };
+            let handle = unsafe {
+                device
+                    .handle
+                    .create_image(&create_info, None)
+                    .map_err(|e| format!("Failed to create transient image: {}", e))?
+            };
+            let requirements = unsafe { device.handle.get_image_memory_requirements(handle) };
+            images.push((handle, usage, requirements));
+        }
+
+        // Largest first, so the smaller images fill the gaps around them.
+        let mut by_size: Vec<usize> = (0..descs.len()).collect();
+        by_size.sort_by_key(|&index| std::cmp::Reverse(images[index].2.size));
+
+        let mut blocks: Vec<Block> = Vec::new();
+        for index in by_size {
+            let (_, usage, requirements) = &images[index];
+            let range_at = |offset: u64| offset..offset + requirements.size;
+
+            if usage.contains(vk::ImageUsageFlags::TRANSIENT_ATTACHMENT) {
+                if let Ok(memory_type) = context.physical_device.find_memory_type(
+                    requirements.memory_type_bits,
+                    vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+                ) {
+                    blocks.push(Block {
+                        type_bits: requirements.memory_type_bits,
+                        memory_type,
+                        lazy: true,
+                        placed: vec![(index, range_at(0))],
+                    });
+                    continue;
+                }
+            }
+
+            let fits = blocks
+                .iter_mut()
+                .filter(|block| !block.lazy)
+                .find_map(|block| {
+                    let type_bits = block.type_bits & requirements.memory_type_bits;
+                    let memory_type = context
+                        .physical_device
+                        .find_memory_type(type_bits, vk::MemoryPropertyFlags::DEVICE_LOCAL)
+                        .ok()?;
+                    let offset = free_offset(
+                        &block.placed,
+                        descs,
+                        index,
+                        requirements.size,
+                        requirements.alignment,
+                    );
+                    Some((block, type_bits, memory_type, offset))
+                });
+            match fits {
+                Some((block, type_bits, memory_type, offset)) => {
+                    block.type_bits = type_bits;
+                    block.memory_type = memory_type;
+                    block.placed.push((index, range_at(offset)));
+                }
+                None => blocks.push(Block {
+                    type_bits: requirements.memory_type_bits,
+                    memory_type: context.physical_device.find_memory_type(
+                        requirements.memory_type_bits,
+                        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    )?,
+                    lazy: false,
+                    placed: vec![(index, range_at(0))],
+                }),
+            }
+        }
+
+        let mut placed: Vec<Option<Placement>> = (0..descs.len()).map(|_| None).collect();
+        for block in &blocks {
+            let size = block.placed.iter().map(|(_, range)| range.end).max();
+            let allocate_info = vk::MemoryAllocateInfo {
+                s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+                allocation_size: size.unwrap_or(0),
+                memory_type_index: block.memory_type,
+                ..Default::default()
+            };
+            let memory = Arc::new(TransientMemory {
+                device: context.device(),
+                handle: unsafe {
+                    device
+                        .handle
+                        .allocate_memory(&allocate_info, None)
+                        .map_err(|e| format!("Failed to allocate transient memory: {}", e))?
+                },
+            });
+
+            for (index, range) in &block.placed {
+                unsafe {
+                    device
+                        .handle
+                        .bind_image_memory(images[*index].0, memory.handle, range.start)
+                        .map_err(|e| format!("Failed to bind transient image memory: {}", e))?
+                };
+                let previous_use = block
+                    .placed
+                    .iter()
+                    .filter(|(_, other)| other.start < range.end && range.start < other.end)
+                    .fold(
+                        usage_state(vk::ImageUsageFlags::empty()),
+                        |state, (other, _)| {
+                            let other = usage_state(images[*other].1);
+                            ImageState::written(
+                                state.stage | other.stage,
+                                state.access | other.access,
+                                vk::ImageLayout::UNDEFINED,
+                            )
+                        },
+                    );
+                placed[*index] = Some((memory.clone(), range.clone(), previous_use));
+            }
+        }
+
+        let mut transients = Vec::with_capacity(descs.len());
+        for ((desc, (handle, _, _)), placement) in descs.iter().zip(images).zip(placed) {
+            let Some((memory, range, previous_use)) = placement else {
+                return Err("Transient image left without memory".to_string());
+            };
+            let view_create_info = vk::ImageViewCreateInfo {
+                s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+                image: handle,
+                view_type: vk::ImageViewType::TYPE_2D,
+                format: desc.format,
+                subresource_range: vk::ImageSubresourceRange {
+                    aspect_mask: desc.aspect_mask,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                ..Default::default()
+            };
+            let view = unsafe {
+                device
+                    .handle
+                    .create_image_view(&view_create_info, None)
+                    .map_err(|e| format!("Failed to create transient image view: {}", e))?
+            };
+            transients.push(TransientImage {
+                device: device.clone(),
+                memory,
+                handle,
+                view,
+                extent: desc.extent,
+                format: desc.format,
+                aspect_mask: desc.aspect_mask,
+                range,
+                previous_use,
+            });
+        }
+        Ok(transients)
+    }
+
+    /// Memory the image shares with its aliases.
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory.handle
+    }
+}
+
+impl Drop for TransientImage {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle.destroy_image_view(self.view, None);
+            self.device.handle.destroy_image(self.handle, None);
+        }
+    }
+}
+
+/// Lowest offset aligned to `alignment` where `size` bytes overlap none of
+/// the `placed` images used by the passes of `descs[index]`.
+fn free_offset(
+    placed: &[(usize, Range<u64>)],
+    descs: &[TransientDesc],
+    index: usize,
+    size: u64,
+    alignment: u64,
+) -> u64 {
+    let live: Vec<&Range<u64>> = placed
+        .iter()
+        .filter(|(other, _)| descs[*other].overlaps(&descs[index]))
+        .map(|(_, range)| range)
+        .collect();
+    let align = |offset: u64| offset.div_ceil(alignment.max(1)) * alignment.max(1);
+
+    let mut candidates: Vec<u64> = std::iter::once(0)
+        .chain(live.iter().map(|range| align(range.end)))
+        .collect();
+    candidates.sort_unstable();
+    candidates
+        .into_iter()
+        .find(|&offset| {
+            live.iter()
+                .all(|range| offset + size <= range.start || range.end <= offset)
+        })
+        .unwrap_or(0)
+}
+
+/// Every stage and write access an image with `usage` may be used with.
+fn usage_state(usage: vk::ImageUsageFlags) -> ImageState {
+    let mut stage = vk::PipelineStageFlags::empty();
+    let mut access = vk::AccessFlags::empty();
+    if usage.intersects(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::STORAGE) {
+        stage |= vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER;
+    }
+    if usage.contains(vk::ImageUsageFlags::STORAGE) {
+        access |= vk::AccessFlags::SHADER_WRITE;
+    }
+    if usage.contains(vk::ImageUsageFlags::COLOR_ATTACHMENT) {
+        stage |= vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+        access |= vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
+    }
+    if usage.contains(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT) {
+        stage |= vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+        access |= vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+    }
+    if usage.intersects(vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST) {
+        stage |= vk::PipelineStageFlags::TRANSFER;
+    }
+    if usage.contains(vk::ImageUsageFlags::TRANSFER_DST) {
+        access |= vk::AccessFlags::TRANSFER_WRITE;
+    }
+    ImageState::written(stage, access, vk::ImageLayout::UNDEFINED)
+}
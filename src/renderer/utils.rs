@@ -39,14 +39,17 @@ pub fn query_swapchain_support(
     });
 }
 
+/// First depth format usable both as an attachment and as a sampled image,
+/// since the traced occlusion pass reads the depth buffer back. Formats with
+/// a stencil, which masks the ground reflection, come first.
 pub fn find_depth_format(
     instance: &VkInstance,
     physical_device: &VkPhysicalDevice,
 ) -> Result<vk::Format, String> {
     let candidates = [
-        vk::Format::D32_SFLOAT,
         vk::Format::D32_SFLOAT_S8_UINT,
         vk::Format::D24_UNORM_S8_UINT,
+        vk::Format::D32_SFLOAT,
     ];
 
     for format in candidates {
@@ -56,11 +59,42 @@ pub fn find_depth_format(
                 .get_physical_device_format_properties(physical_device.handle, format)
         };
 
-        if (props.optimal_tiling_features & vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
-            == vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
-        {
+        let required = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+            | vk::FormatFeatureFlags::SAMPLED_IMAGE;
+        if props.optimal_tiling_features.contains(required) {
             return Ok(format);
         }
     }
     Err("Failed to find supported depth format".to_string())
 }
+
+/// Aspects of a depth attachment of `format`, for layout transitions.
+pub fn depth_aspects(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::DEPTH,
+    }
+}
+
+/// Encodes a linear color channel for an 8-bit sRGB texture.
+pub fn linear_to_srgb8(linear: f32) -> u8 {
+    let c = linear.clamp(0., 1.);
+    let srgb = if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    };
+    (srgb * 255. + 0.5) as u8
+}
+
+/// Decodes an 8-bit sRGB channel to linear.
+pub fn srgb8_to_linear(srgb: u8) -> f32 {
+    let c = srgb as f32 / 255.;
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
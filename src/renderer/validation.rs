@@ -0,0 +1,117 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ash::vk;
+
+use super::VALIDATION_LAYERS_ENABLED;
+
+/// Validation requested at startup, on top of `VALIDATION_LAYERS_ENABLED`.
+/// Any of the extra checks, enabled through `VK_EXT_validation_features`,
+/// also enables the validation layers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValidationFeatures {
+    /// The layers' default checks only.
+    pub core: bool,
+    /// Instruments shaders to catch out-of-bounds descriptor and buffer
+    /// accesses on the GPU. Slow.
+    pub gpu_assisted: bool,
+    /// Warns about valid but inefficient API usage.
+    pub best_practices: bool,
+    /// Reports missing barriers and other hazards between commands.
+    pub synchronization: bool,
+}
+
+impl ValidationFeatures {
+    /// Environment variable read like the `--validation` option.
+    pub const ENV: &'static str = "SCOP_VALIDATION";
+
+    /// Features listed in `SCOP_VALIDATION`, none when it is unset.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var(Self::ENV) {
+            Ok(value) => value
+                .parse()
+                .map_err(|e| format!("Invalid {}: {}", Self::ENV, e)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Features enabled in either `self` or `other`.
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            core: self.core || other.core,
+            gpu_assisted: self.gpu_assisted || other.gpu_assisted,
+            best_practices: self.best_practices || other.best_practices,
+            synchronization: self.synchronization || other.synchronization,
+        }
+    }
+
+    pub fn layers_enabled(&self) -> bool {
+        VALIDATION_LAYERS_ENABLED || self.core || !self.enables().is_empty()
+    }
+
+    /// Checks to chain into the instance, through `VkValidationFeaturesEXT`.
+    pub fn enables(&self) -> Vec<vk::ValidationFeatureEnableEXT> {
+        let mut enables = Vec::new();
+        if self.gpu_assisted {
+            enables.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+            enables.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+        }
+        if self.best_practices {
+            enables.push(vk::ValidationFeatureEnableEXT::BEST_PRACTICES);
+        }
+        if self.synchronization {
+            enables.push(vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION);
+        }
+        enables
+    }
+}
+
+impl FromStr for ValidationFeatures {
+    type Err = String;
+
+    /// Parses a comma-separated list of `core`, `gpu`, `best-practices`,
+    /// `sync` or `all`.
+    fn from_str(value: &str) -> Result<Self, String> {
+        let mut features = Self::default();
+        for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match name {
+                "core" => features.core = true,
+                "gpu" => features.gpu_assisted = true,
+                "best-practices" => features.best_practices = true,
+                "sync" => features.synchronization = true,
+                "all" => {
+                    features.gpu_assisted = true;
+                    features.best_practices = true;
+                    features.synchronization = true;
+                }
+                _ => {
+                    return Err(format!(
+                        "Unknown validation feature '{}', expected core, gpu, best-practices, sync or all",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(features)
+    }
+}
+
+impl fmt::Display for ValidationFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = [
+            (self.gpu_assisted, "gpu"),
+            (self.best_practices, "best-practices"),
+            (self.synchronization, "sync"),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| *name)
+        .collect();
+
+        if names.is_empty() {
+            write!(f, "core")
+        } else {
+            write!(f, "core, {}", names.join(", "))
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::math::{Vec2, Vec3};
+use crate::math::{Aabb, Mat4, Vec2, Vec3};
 use ash::vk;
 
 #[derive(Clone, Copy)]
@@ -6,6 +6,11 @@ pub struct Vertex {
     pub position: Vec3,
     pub normal: Vec3,
     pub uv: Vec2,
+    /// Baked ambient visibility, 1 when nothing occludes the vertex.
+    pub occlusion: f32,
+    /// Second texture coordinate set, unique per triangle, addressing the
+    /// mesh's baked lightmap.
+    pub lightmap_uv: Vec2,
 }
 
 impl Vertex {
@@ -17,7 +22,7 @@ impl Vertex {
         };
     }
 
-    pub fn get_attribute_description() -> [vk::VertexInputAttributeDescription; 3] {
+    pub fn get_attribute_description() -> [vk::VertexInputAttributeDescription; 5] {
         let position_attribute = vk::VertexInputAttributeDescription {
             binding: 0,
             location: 0,
@@ -39,6 +44,302 @@ impl Vertex {
             offset: std::mem::offset_of!(Vertex, uv) as u32,
         };
 
-        return [position_attribute, normal_attribute, uv_attribute];
+        // Locations 3 and 4 belong to the skin stream.
+        let occlusion_attribute = vk::VertexInputAttributeDescription {
+            binding: 0,
+            location: 5,
+            format: vk::Format::R32_SFLOAT,
+            offset: std::mem::offset_of!(Vertex, occlusion) as u32,
+        };
+
+        let lightmap_uv_attribute = vk::VertexInputAttributeDescription {
+            binding: 0,
+            location: 6,
+            format: vk::Format::R32G32_SFLOAT,
+            offset: std::mem::offset_of!(Vertex, lightmap_uv) as u32,
+        };
+
+        return [
+            position_attribute,
+            normal_attribute,
+            uv_attribute,
+            occlusion_attribute,
+            lightmap_uv_attribute,
+        ];
+    }
+}
+
+/// `Vertex` in 20 bytes instead of 44: half-float positions scaled into the
+/// mesh's bounds with the occlusion in their fourth component, octahedral
+/// normals and half-float texture coordinates. The model transform is
+/// multiplied by `CompactVertex::decode` to draw them.
+#[derive(Clone, Copy)]
+pub struct CompactVertex {
+    pub position: [u16; 4],
+    pub normal: [i16; 2],
+    pub uv: [u16; 2],
+    pub lightmap_uv: [u16; 2],
+}
+
+impl CompactVertex {
+    /// `vertex` of a mesh within `bounds`.
+    pub fn encode(vertex: &Vertex, bounds: &Aabb) -> Self {
+        let (center, extent) = Self::range(bounds);
+        let position = vertex.position - center;
+        Self {
+            position: [
+                half(position.x / extent.x),
+                half(position.y / extent.y),
+                half(position.z / extent.z),
+                half(vertex.occlusion),
+            ],
+            // Scaled like the positions, so the decoded normals stay
+            // perpendicular to the surface under `decode`.
+            normal: octahedral(vertex.normal * extent),
+            uv: [half(vertex.uv.x), half(vertex.uv.y)],
+            lightmap_uv: [half(vertex.lightmap_uv.x), half(vertex.lightmap_uv.y)],
+        }
+    }
+
+    /// Mesh space transform of the positions encoded for `bounds`.
+    pub fn decode(bounds: &Aabb) -> Mat4 {
+        let (center, extent) = Self::range(bounds);
+        Mat4::identity().translate(center).scale(extent)
+    }
+
+    /// Center and half size of `bounds`, never flat so that planar meshes
+    /// keep their normals.
+    fn range(bounds: &Aabb) -> (Vec3, Vec3) {
+        if bounds.is_empty() {
+            return (Vec3::ZERO, Vec3::splat(1.));
+        }
+        let extent = bounds.size() * 0.5;
+        let floor = extent.x.max(extent.y).max(extent.z).max(1.) * 1e-6;
+        (bounds.center(), extent.max(Vec3::splat(floor)))
+    }
+
+    pub fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<CompactVertex>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }
+    }
+
+    /// The locations of `Vertex::get_attribute_description`, the occlusion
+    /// read from the positions' fourth component.
+    pub fn get_attribute_description() -> [vk::VertexInputAttributeDescription; 5] {
+        let position = std::mem::offset_of!(CompactVertex, position) as u32;
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R16G16B16A16_SFLOAT,
+                offset: position,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R16G16_SNORM,
+                offset: std::mem::offset_of!(CompactVertex, normal) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R16G16_SFLOAT,
+                offset: std::mem::offset_of!(CompactVertex, uv) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 5,
+                format: vk::Format::R16_SFLOAT,
+                offset: position + 3 * std::mem::size_of::<u16>() as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 6,
+                format: vk::Format::R16G16_SFLOAT,
+                offset: std::mem::offset_of!(CompactVertex, lightmap_uv) as u32,
+            },
+        ]
+    }
+}
+
+/// Bits of the half float nearest to `value`, infinite beyond its range.
+fn half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal, the implicit leading bit made explicit.
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let rounding = (mantissa >> (shift - 1)) & 1;
+        return sign | ((mantissa >> shift) + rounding) as u16;
+    }
+    // A rounding carry into the exponent gives the next power of two.
+    let rounding = (mantissa >> 12) & 1;
+    sign | ((((exponent as u32) << 10) | (mantissa >> 13)) + rounding) as u16
+}
+
+/// Direction of `normal` folded onto an octahedron and unwrapped into the
+/// unit square, as signed normalized 16-bit coordinates.
+fn octahedral(normal: Vec3) -> [i16; 2] {
+    let length = normal.x.abs() + normal.y.abs() + normal.z.abs();
+    if length == 0. {
+        return [0, 0];
+    }
+    let n = normal / length;
+    let (x, y) = if n.z >= 0. {
+        (n.x, n.y)
+    } else {
+        (
+            (1. - n.y.abs()) * n.x.signum(),
+            (1. - n.x.abs()) * n.y.signum(),
+        )
+    };
+    let snorm = |v: f32| (v.clamp(-1., 1.) * i16::MAX as f32).round() as i16;
+    [snorm(x), snorm(y)]
+}
+
+/// Joint influences of a skinned vertex, bound as a second vertex stream next
+/// to `Vertex`.
+#[derive(Clone, Copy)]
+pub struct VertexSkin {
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+impl Default for VertexSkin {
+    /// Fully bound to the first joint.
+    fn default() -> Self {
+        Self {
+            joints: [0; 4],
+            weights: [1., 0., 0., 0.],
+        }
+    }
+}
+
+impl VertexSkin {
+    pub fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding: 1,
+            stride: std::mem::size_of::<VertexSkin>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }
+    }
+
+    pub fn get_attribute_description() -> [vk::VertexInputAttributeDescription; 2] {
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 1,
+                location: 3,
+                format: vk::Format::R32G32B32A32_UINT,
+                offset: std::mem::offset_of!(VertexSkin, joints) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 1,
+                location: 4,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: std::mem::offset_of!(VertexSkin, weights) as u32,
+            },
+        ]
+    }
+}
+
+/// Vertex of the debug line pipeline. Consecutive pairs are the ends of a
+/// segment, read together as one instance.
+#[derive(Clone, Copy)]
+pub struct LineVertex {
+    pub position: Vec3,
+    pub color: Vec3,
+}
+
+impl LineVertex {
+    pub fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: 2 * std::mem::size_of::<LineVertex>() as u32,
+            input_rate: vk::VertexInputRate::INSTANCE,
+        }
+    }
+
+    /// Position and color of the start of the segment, then of its end.
+    pub fn get_attribute_description() -> [vk::VertexInputAttributeDescription; 4] {
+        let end = std::mem::size_of::<LineVertex>() as u32;
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: std::mem::offset_of!(LineVertex, position) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: std::mem::offset_of!(LineVertex, color) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: end + std::mem::offset_of!(LineVertex, position) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: end + std::mem::offset_of!(LineVertex, color) as u32,
+            },
+        ]
+    }
+}
+
+/// One cube of the voxel preview, at integer cell coordinates.
+#[derive(Debug, Clone, Copy)]
+pub struct VoxelInstance {
+    /// Corner of the cube with the lowest coordinates.
+    pub cell: Vec3,
+    pub color: Vec3,
+}
+
+impl VoxelInstance {
+    pub fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<VoxelInstance>() as u32,
+            input_rate: vk::VertexInputRate::INSTANCE,
+        }
+    }
+
+    pub fn get_attribute_description() -> [vk::VertexInputAttributeDescription; 2] {
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: std::mem::offset_of!(VoxelInstance, cell) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: std::mem::offset_of!(VoxelInstance, color) as u32,
+            },
+        ]
     }
 }
@@ -0,0 +1,131 @@
+use ash::vk;
+
+use crate::camera::Camera;
+use crate::math::{Aabb, Vec2, Vec3};
+
+/// Side-by-side stereo settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stereo {
+    /// Distance between the two eyes, in world units.
+    pub ipd: f32,
+    /// Right eye on the left half, for cross-eyed viewing, instead of the
+    /// left eye as 3D TVs expect.
+    pub cross_eye: bool,
+}
+
+impl Stereo {
+    pub const DEFAULT_IPD: f32 = 6.5;
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            ipd: Self::DEFAULT_IPD,
+            cross_eye: false,
+        }
+    }
+}
+
+/// A camera drawn into part of the window.
+#[derive(Debug, Clone)]
+pub struct Viewport {
+    pub name: &'static str,
+    pub camera: Camera,
+    /// Top-left corner, as a fraction of the window size.
+    pub origin: Vec2,
+    /// Size, as a fraction of the window size.
+    pub size: Vec2,
+}
+
+impl Viewport {
+    /// Margin left around the bounds framed by the orthographic panes.
+    const FRAMING: f32 = 1.1;
+
+    /// CAD-style quad view: `camera` in the top-left quarter, then top, front
+    /// and side orthographic views framing `bounds`. The quarters keep the
+    /// window's aspect ratio, so `camera` needs no change.
+    pub fn quad(camera: &Camera, bounds: &Aabb) -> Vec<Viewport> {
+        let center = bounds.center();
+        let radius = bounds.radius().max(1.);
+        let ratio = camera.ratio();
+        let half_height = radius * Self::FRAMING * (1. / ratio).max(1.);
+        let ortho = |direction: Vec3| {
+            Camera::orthographic(
+                center + direction * (radius * 2.),
+                center,
+                half_height,
+                ratio,
+                radius * 0.5,
+                radius * 3.5,
+            )
+        };
+
+        vec![
+            Viewport {
+                name: "perspective",
+                camera: camera.clone(),
+                origin: Vec2::new(0., 0.),
+                size: Vec2::splat(0.5),
+            },
+            Viewport {
+                name: "top",
+                camera: ortho(Vec3::Y),
+                origin: Vec2::new(0.5, 0.),
+                size: Vec2::splat(0.5),
+            },
+            Viewport {
+                name: "front",
+                camera: ortho(Vec3::Z),
+                origin: Vec2::new(0., 0.5),
+                size: Vec2::splat(0.5),
+            },
+            Viewport {
+                name: "side",
+                camera: ortho(Vec3::X),
+                origin: Vec2::new(0.5, 0.5),
+                size: Vec2::splat(0.5),
+            },
+        ]
+    }
+
+    /// `camera` twice, moved half the eye distance to each side with parallel
+    /// axes, in the left and right halves of the window.
+    pub fn stereo(camera: &Camera, stereo: Stereo) -> Vec<Viewport> {
+        let eye = |side: f32| {
+            let mut eye = camera.clone();
+            eye.position += camera.right() * (side * stereo.ipd * 0.5);
+            eye.set_ratio(camera.ratio() * 0.5);
+            eye
+        };
+        let (left, right) = if stereo.cross_eye {
+            (("right eye", eye(1.)), ("left eye", eye(-1.)))
+        } else {
+            (("left eye", eye(-1.)), ("right eye", eye(1.)))
+        };
+
+        [(left, 0.), (right, 0.5)]
+            .into_iter()
+            .map(|((name, camera), x)| Viewport {
+                name,
+                camera,
+                origin: Vec2::new(x, 0.),
+                size: Vec2::new(0.5, 1.),
+            })
+            .collect()
+    }
+
+    /// Pixels covered in a target of `extent`.
+    pub fn rect(&self, extent: vk::Extent2D) -> vk::Rect2D {
+        let (width, height) = (extent.width as f32, extent.height as f32);
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: (self.origin.x * width) as i32,
+                y: (self.origin.y * height) as i32,
+            },
+            extent: vk::Extent2D {
+                width: ((self.size.x * width) as u32).max(1),
+                height: ((self.size.y * height) as u32).max(1),
+            },
+        }
+    }
+}
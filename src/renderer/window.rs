@@ -0,0 +1,83 @@
+use std::ffi::CStr;
+
+use ash::{vk, Entry, Instance};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+
+/// A window the renderer presents to, whichever library created it.
+pub trait SurfaceWindow {
+    /// Instance extensions the window's surface needs.
+    fn instance_extensions(&self) -> Result<Vec<String>, String>;
+
+    /// Creates a surface for the window on `instance`.
+    fn create_surface(&self, entry: &Entry, instance: &Instance) -> Result<vk::SurfaceKHR, String>;
+
+    /// Size of the window, which the swapchain matches when the surface
+    /// leaves it free.
+    fn size(&self) -> (u32, u32);
+}
+
+impl SurfaceWindow for sdl3::video::Window {
+    fn instance_extensions(&self) -> Result<Vec<String>, String> {
+        self.vulkan_instance_extensions()
+            .map_err(|e| format!("Failed to get the window's Vulkan extensions: {}", e))
+    }
+
+    fn create_surface(
+        &self,
+        _entry: &Entry,
+        instance: &Instance,
+    ) -> Result<vk::SurfaceKHR, String> {
+        unsafe { self.vulkan_create_surface(instance.handle()) }
+            .map_err(|e| format!("Failed to create Vulkan surface: {}", e))
+    }
+
+    fn size(&self) -> (u32, u32) {
+        sdl3::video::Window::size(self)
+    }
+}
+
+/// A window of another library, like winit, presented to through its raw
+/// handles. `size` is read again whenever the swapchain is recreated, so it
+/// must follow the window's.
+pub struct RawWindow<'a, W> {
+    pub window: &'a W,
+    pub size: (u32, u32),
+}
+
+impl<W: HasWindowHandle + HasDisplayHandle> SurfaceWindow for RawWindow<'_, W> {
+    fn instance_extensions(&self) -> Result<Vec<String>, String> {
+        let display = self
+            .window
+            .display_handle()
+            .map_err(|e| format!("Failed to get the display handle: {}", e))?;
+        let names = ash_window::enumerate_required_extensions(display.as_raw())
+            .map_err(|e| format!("Unsupported display: {}", e))?;
+        Ok(names
+            .iter()
+            .map(|&name| {
+                unsafe { CStr::from_ptr(name) }
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect())
+    }
+
+    fn create_surface(&self, entry: &Entry, instance: &Instance) -> Result<vk::SurfaceKHR, String> {
+        let display = self
+            .window
+            .display_handle()
+            .map_err(|e| format!("Failed to get the display handle: {}", e))?;
+        let window = self
+            .window
+            .window_handle()
+            .map_err(|e| format!("Failed to get the window handle: {}", e))?;
+        unsafe {
+            ash_window::create_surface(entry, instance, display.as_raw(), window.as_raw(), None)
+        }
+        .map_err(|e| format!("Failed to create Vulkan surface: {}", e))
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+}
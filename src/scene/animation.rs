@@ -0,0 +1,237 @@
+use std::ops::{Add, Mul};
+
+use crate::math::{Quat, Vec3};
+use crate::scene::{ObjectHandle, Scene, SkinHandle};
+
+/// Keyframe interpolation, matching the glTF sampler modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    Step,
+    #[default]
+    Linear,
+    /// Hermite spline; every keyframe stores `[in_tangent, value, out_tangent]`.
+    CubicSpline,
+}
+
+/// Keyframe values of a channel, one per key (three per key for cubic
+/// splines).
+#[derive(Debug, Clone)]
+pub enum Track {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+/// Node whose transform a channel drives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelTarget {
+    Object(ObjectHandle),
+    Joint { skin: SkinHandle, joint: usize },
+}
+
+/// Animates one property of one node.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub target: ChannelTarget,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub track: Track,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<Channel>,
+}
+
+impl Animation {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            channels: Vec::new(),
+        }
+    }
+
+    /// Time of the last keyframe over all channels.
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .filter_map(|c| c.times.last().copied())
+            .fold(0., f32::max)
+    }
+
+    /// Poses the targeted nodes at `time`, in seconds.
+    pub fn apply(&self, scene: &mut Scene, time: f32) {
+        for channel in &self.channels {
+            let (times, mode) = (&channel.times, channel.interpolation);
+            match channel.target {
+                ChannelTarget::Object(handle) => {
+                    let Some(object) = scene.objects.get_mut(handle) else {
+                        continue;
+                    };
+                    match &channel.track {
+                        Track::Translation(values) => {
+                            if let Some(v) = sample(times, values, mode, time) {
+                                object.set_position(v);
+                            }
+                        }
+                        Track::Rotation(values) => {
+                            if let Some(q) = sample(times, values, mode, time) {
+                                let euler = q.to_euler();
+                                object.set_rotation(euler.x, euler.y, euler.z);
+                            }
+                        }
+                        Track::Scale(values) => {
+                            if let Some(v) = sample(times, values, mode, time) {
+                                object.set_scale(v);
+                            }
+                        }
+                    }
+                }
+                ChannelTarget::Joint { skin, joint } => {
+                    let Some(joint) = scene
+                        .skins
+                        .get_mut(skin)
+                        .and_then(|s| s.joints.get_mut(joint))
+                    else {
+                        continue;
+                    };
+                    match &channel.track {
+                        Track::Translation(values) => {
+                            if let Some(v) = sample(times, values, mode, time) {
+                                joint.translation = v;
+                            }
+                        }
+                        Track::Rotation(values) => {
+                            if let Some(q) = sample(times, values, mode, time) {
+                                joint.rotation = q;
+                            }
+                        }
+                        Track::Scale(values) => {
+                            if let Some(v) = sample(times, values, mode, time) {
+                                joint.scale = v;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Playback state shared by the scene animations.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    pub time: f32,
+    pub speed: f32,
+    pub playing: bool,
+    pub looping: bool,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self {
+            time: 0.,
+            speed: 1.,
+            playing: true,
+            looping: true,
+        }
+    }
+}
+
+impl AnimationPlayer {
+    /// Step used by frame-by-frame stepping.
+    pub const FRAME: f32 = 1. / 30.;
+
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+    }
+
+    /// Advances playback by `dt` seconds when playing.
+    pub fn update(&mut self, dt: f32, duration: f32) {
+        if self.playing {
+            self.seek(self.time + dt * self.speed, duration);
+        }
+    }
+
+    /// Pauses and moves by `frames` frames, backwards when negative.
+    pub fn step(&mut self, frames: i32, duration: f32) {
+        self.playing = false;
+        self.seek(self.time + frames as f32 * Self::FRAME, duration);
+    }
+
+    fn seek(&mut self, time: f32, duration: f32) {
+        self.time = if duration <= 0. {
+            0.
+        } else if self.looping {
+            time.rem_euclid(duration)
+        } else {
+            time.clamp(0., duration)
+        };
+    }
+}
+
+trait Keyframe: Copy + Add<Output = Self> + Mul<f32, Output = Self> {
+    fn interpolate(self, rhs: Self, t: f32) -> Self;
+
+    fn finish(self) -> Self {
+        self
+    }
+}
+
+impl Keyframe for Vec3 {
+    fn interpolate(self, rhs: Self, t: f32) -> Self {
+        self + (rhs - self) * t
+    }
+}
+
+impl Keyframe for Quat {
+    fn interpolate(self, rhs: Self, t: f32) -> Self {
+        self.slerp(rhs, t)
+    }
+
+    fn finish(self) -> Self {
+        self.normalize()
+    }
+}
+
+fn sample<T: Keyframe>(times: &[f32], values: &[T], mode: Interpolation, time: f32) -> Option<T> {
+    let stride = if mode == Interpolation::CubicSpline {
+        3
+    } else {
+        1
+    };
+    let value = |key: usize| values.get(key * stride + stride / 2).copied();
+
+    let last = times.len().checked_sub(1)?;
+    if time <= times[0] {
+        return value(0);
+    }
+    if time >= times[last] {
+        return value(last);
+    }
+
+    let next = times.partition_point(|&t| t <= time);
+    let key = next - 1;
+    let dt = times[next] - times[key];
+    let t = if dt > 0. {
+        (time - times[key]) / dt
+    } else {
+        0.
+    };
+
+    match mode {
+        Interpolation::Step => value(key),
+        Interpolation::Linear => Some(value(key)?.interpolate(value(next)?, t)),
+        Interpolation::CubicSpline => {
+            let out_tangent = *values.get(key * 3 + 2)?;
+            let in_tangent = *values.get(next * 3)?;
+            let (t2, t3) = (t * t, t * t * t);
+            let v = value(key)? * (2. * t3 - 3. * t2 + 1.)
+                + out_tangent * ((t3 - 2. * t2 + t) * dt)
+                + value(next)? * (-2. * t3 + 3. * t2)
+                + in_tangent * ((t3 - t2) * dt);
+            Some(v.finish())
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use crate::math::{Quat, Vec3};
+
+/// Demo spin of the loaded models around a fixed world axis.
+#[derive(Debug, Clone, Copy)]
+pub struct AutoRotation {
+    /// Unit axis of the spin, through each object's origin.
+    pub axis: Vec3,
+    /// Angular speed, in radians per second.
+    pub speed: f32,
+    pub playing: bool,
+    /// Angle turned so far, in radians.
+    pub angle: f32,
+}
+
+impl Default for AutoRotation {
+    fn default() -> Self {
+        Self {
+            axis: Vec3::Y,
+            speed: Self::DEFAULT_SPEED,
+            playing: true,
+            angle: 0.,
+        }
+    }
+}
+
+impl AutoRotation {
+    /// Angular speed at startup, in radians per second.
+    pub const DEFAULT_SPEED: f32 = 2.;
+
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+    }
+
+    /// Turns by `dt` seconds at `speed` when playing.
+    pub fn update(&mut self, dt: f32) {
+        if self.playing {
+            self.angle += self.speed * dt;
+        }
+    }
+
+    /// Next of the X, Y and Z axes, X after any other axis.
+    pub fn next_axis(&mut self) {
+        self.axis = if self.axis == Vec3::X {
+            Vec3::Y
+        } else if self.axis == Vec3::Y {
+            Vec3::Z
+        } else {
+            Vec3::X
+        };
+    }
+
+    /// Euler angles of an object starting from `rotation` turned by the
+    /// current angle times `factor`, in the `Object` convention.
+    pub fn orient(&self, rotation: Vec3, factor: f32) -> Vec3 {
+        let spin = Quat::from_axis_angle(self.axis, self.angle * factor);
+        (spin * Quat::from_euler(rotation)).to_euler()
+    }
+}
+
+impl fmt::Display for AutoRotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.0} deg/s around ", self.speed.to_degrees())?;
+        match [(Vec3::X, "X"), (Vec3::Y, "Y"), (Vec3::Z, "Z")]
+            .iter()
+            .find(|(axis, _)| *axis == self.axis)
+        {
+            Some((_, name)) => write!(f, "{}", name)?,
+            None => {
+                let Vec3 { x, y, z } = self.axis;
+                write!(f, "({:.2}, {:.2}, {:.2})", x, y, z)?
+            }
+        }
+        if !self.playing {
+            write!(f, " (paused)")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+use crate::math::{Aabb, Ray, Vec3};
+
+use super::Mesh;
+
+/// Triangles per leaf before a node is split.
+const LEAF_SIZE: usize = 4;
+
+/// A triangle of a mesh, by group and position in the group's index list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriangleRef {
+    pub group: u32,
+    pub triangle: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BvhNode {
+    pub bounds: Aabb,
+    /// First triangle of a leaf, or left child of an inner node (the right
+    /// child follows it).
+    pub start: u32,
+    /// Triangles of a leaf, 0 for inner nodes.
+    pub count: u32,
+}
+
+impl BvhNode {
+    const EMPTY: BvhNode = BvhNode {
+        bounds: Aabb::EMPTY,
+        start: 0,
+        count: 0,
+    };
+}
+
+/// Bounding volume hierarchy over a mesh's triangles, in mesh space, so ray
+/// queries only test the triangles whose boxes the ray crosses.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    triangles: Vec<TriangleRef>,
+}
+
+impl Bvh {
+    /// Splits the triangles at the median of their centroids along the
+    /// longest axis until leaves are small enough.
+    pub fn build(mesh: &Mesh) -> Self {
+        let mut items: Vec<(TriangleRef, Aabb, Vec3)> = Vec::new();
+        for (group_index, group) in mesh.groups.iter().enumerate() {
+            for (triangle, indices) in group.indices.chunks_exact(3).enumerate() {
+                let bounds =
+                    Aabb::from_points(indices.iter().map(|&i| group.vertices[i as usize].position));
+                let triangle = TriangleRef {
+                    group: group_index as u32,
+                    triangle: triangle as u32,
+                };
+                items.push((triangle, bounds, bounds.center()));
+            }
+        }
+
+        let mut bvh = Self::default();
+        if !items.is_empty() {
+            bvh.nodes.push(BvhNode::EMPTY);
+            bvh.subdivide(0, &mut items, 0);
+            bvh.triangles = items.into_iter().map(|(triangle, _, _)| triangle).collect();
+        }
+        bvh
+    }
+
+    /// Fills node `index` with `items`, which start at triangle `start`,
+    /// splitting it further when needed. Reorders `items` so every leaf
+    /// covers a contiguous range.
+    fn subdivide(&mut self, index: usize, items: &mut [(TriangleRef, Aabb, Vec3)], start: usize) {
+        let bounds = items
+            .iter()
+            .fold(Aabb::EMPTY, |acc, (_, bounds, _)| acc.union(*bounds));
+        self.nodes[index] = BvhNode {
+            bounds,
+            start: start as u32,
+            count: items.len() as u32,
+        };
+
+        if items.len() <= LEAF_SIZE {
+            return;
+        }
+
+        let centroids = Aabb::from_points(items.iter().map(|(_, _, centroid)| *centroid));
+        let size = centroids.size();
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| a.2[axis].total_cmp(&b.2[axis]));
+        let (left, right) = items.split_at_mut(mid);
+
+        let child = self.nodes.len();
+        self.nodes.push(BvhNode::EMPTY);
+        self.nodes.push(BvhNode::EMPTY);
+        self.subdivide(child, left, start);
+        self.subdivide(child + 1, right, start + mid);
+
+        self.nodes[index].start = child as u32;
+        self.nodes[index].count = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// Nodes in depth-first order, the root first.
+    pub fn nodes(&self) -> &[BvhNode] {
+        &self.nodes
+    }
+
+    /// Triangles in leaf order, indexed by the leaves' `start`.
+    pub fn triangles(&self) -> &[TriangleRef] {
+        &self.triangles
+    }
+
+    /// Closest triangle of `mesh` hit by `ray`, with its distance along the
+    /// ray. `mesh` must be the one the hierarchy was built from.
+    pub fn intersect(&self, mesh: &Mesh, ray: &Ray) -> Option<(TriangleRef, f32)> {
+        let mut closest: Option<(TriangleRef, f32)> = None;
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let mut stack = vec![0usize];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let Some(entry) = ray.intersect_aabb(&node.bounds) else {
+                continue;
+            };
+            if closest.is_some_and(|(_, distance)| distance < entry) {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.start as usize);
+                stack.push(node.start as usize + 1);
+                continue;
+            }
+
+            let start = node.start as usize;
+            for &triangle in &self.triangles[start..start + node.count as usize] {
+                let group = &mesh.groups[triangle.group as usize];
+                let first = triangle.triangle as usize * 3;
+                let [a, b, c] =
+                    [0, 1, 2].map(|i| group.vertices[group.indices[first + i] as usize].position);
+                if let Some(distance) = ray.intersect_triangle(a, b, c) {
+                    if closest.is_none_or(|(_, best)| distance < best) {
+                        closest = Some((triangle, distance));
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Triangle of `mesh` closest to `point`, with the distance to it. `mesh`
+    /// must be the one the hierarchy was built from.
+    pub fn nearest(&self, mesh: &Mesh, point: Vec3) -> Option<(TriangleRef, f32)> {
+        let mut closest: Option<(TriangleRef, f32)> = None;
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        // Squared distances, compared to the boxes' without roots.
+        let mut stack = vec![0usize];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let entry = node.bounds.distance_squared(point);
+            if closest.is_some_and(|(_, best)| best <= entry) {
+                continue;
+            }
+
+            if node.count == 0 {
+                // The nearer child goes on top, to shrink `closest` early.
+                let (left, right) = (node.start as usize, node.start as usize + 1);
+                let left_entry = self.nodes[left].bounds.distance_squared(point);
+                let right_entry = self.nodes[right].bounds.distance_squared(point);
+                if left_entry < right_entry {
+                    stack.extend([right, left]);
+                } else {
+                    stack.extend([left, right]);
+                }
+                continue;
+            }
+
+            let start = node.start as usize;
+            for &triangle in &self.triangles[start..start + node.count as usize] {
+                let group = &mesh.groups[triangle.group as usize];
+                let first = triangle.triangle as usize * 3;
+                let [a, b, c] =
+                    [0, 1, 2].map(|i| group.vertices[group.indices[first + i] as usize].position);
+                let distance = (closest_point_on_triangle(point, a, b, c) - point).length_squared();
+                if closest.is_none_or(|(_, best)| distance < best) {
+                    closest = Some((triangle, distance));
+                }
+            }
+        }
+
+        closest.map(|(triangle, distance)| (triangle, distance.sqrt()))
+    }
+
+    /// Whether any triangle of `mesh` is hit by `ray` closer than
+    /// `max_distance`, stopping at the first one found.
+    pub fn occluded(&self, mesh: &Mesh, ray: &Ray, max_distance: f32) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut stack = vec![0usize];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            match ray.intersect_aabb(&node.bounds) {
+                Some(entry) if entry < max_distance => {}
+                _ => continue,
+            }
+
+            if node.count == 0 {
+                stack.push(node.start as usize);
+                stack.push(node.start as usize + 1);
+                continue;
+            }
+
+            let start = node.start as usize;
+            for &triangle in &self.triangles[start..start + node.count as usize] {
+                let group = &mesh.groups[triangle.group as usize];
+                let first = triangle.triangle as usize * 3;
+                let [a, b, c] =
+                    [0, 1, 2].map(|i| group.vertices[group.indices[first + i] as usize].position);
+                if ray
+                    .intersect_triangle(a, b, c)
+                    .is_some_and(|distance| distance < max_distance)
+                {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+/// Point of triangle `abc` closest to `p`, from the Voronoi region of `p`
+/// (Ericson, Real-Time Collision Detection, 5.1.5).
+fn closest_point_on_triangle(p: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0. && d2 <= 0. {
+        return a;
+    }
+
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0. && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0. && d1 >= 0. && d3 <= 0. {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0. && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0. && d2 >= 0. && d6 <= 0. {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0. && d4 - d3 >= 0. && d5 - d6 >= 0. {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    // Inside the face; degenerate triangles fall back to a corner.
+    let sum = va + vb + vc;
+    if sum.abs() <= f32::EPSILON {
+        return a;
+    }
+    a + ab * (vb / sum) + ac * (vc / sum)
+}
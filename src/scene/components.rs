@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use crate::math::Vec3;
+
+use super::{Group, Mesh, Topology};
+
+impl Mesh {
+    /// One mesh per connected component, in the order of their first
+    /// triangle, keeping the groups, materials and lightmap of the original.
+    /// Degenerate triangles and lines are dropped.
+    pub fn split_components(&self) -> Vec<Mesh> {
+        let topology = Topology::build(self);
+        let components = topology.components();
+        let count = components.iter().max().map_or(0, |last| last + 1);
+
+        // Vertices of each part, remapped from the original group's indices.
+        let mut remaps: Vec<Vec<HashMap<u32, u32>>> =
+            vec![vec![HashMap::new(); self.groups.len()]; count];
+        let mut parts: Vec<Mesh> = (0..count)
+            .map(|_| Mesh {
+                groups: self
+                    .groups
+                    .iter()
+                    .map(|group| Group {
+                        vertices: Vec::new(),
+                        indices: Vec::new(),
+                        material: group.material,
+                        object: group.object,
+                        skinning: Vec::new(),
+                    })
+                    .collect(),
+                materials: self.materials.clone(),
+                lightmap: self.lightmap.clone(),
+                objects: self.objects.clone(),
+                lines: Vec::new(),
+            })
+            .collect();
+
+        for (triangle, &component) in topology.triangles.iter().zip(&components) {
+            let source = &self.groups[triangle.group as usize];
+            let target = &mut parts[component].groups[triangle.group as usize];
+            let remap = &mut remaps[component][triangle.group as usize];
+            let first = triangle.triangle as usize * 3;
+            for &index in &source.indices[first..first + 3] {
+                let mapped = *remap.entry(index).or_insert_with(|| {
+                    target.vertices.push(source.vertices[index as usize]);
+                    if let Some(skin) = source.skinning.get(index as usize) {
+                        target.skinning.push(*skin);
+                    }
+                    target.vertices.len() as u32 - 1
+                });
+                target.indices.push(mapped);
+            }
+        }
+
+        for part in &mut parts {
+            part.groups.retain(|group| !group.indices.is_empty());
+        }
+        parts
+    }
+
+    /// One mesh per named object, in the order of `objects`, then one for
+    /// the groups outside any, keeping the materials and lightmap of the
+    /// original. Objects without groups, and lines, are left out.
+    pub fn split_objects(&self) -> Vec<Mesh> {
+        let mut parts: Vec<Mesh> = (0..=self.objects.len())
+            .map(|object| Mesh {
+                groups: Vec::new(),
+                materials: self.materials.clone(),
+                lightmap: self.lightmap.clone(),
+                objects: self.objects.get(object).cloned().into_iter().collect(),
+                lines: Vec::new(),
+            })
+            .collect();
+        for group in &self.groups {
+            let object = group.object.filter(|&object| object < self.objects.len());
+            parts[object.unwrap_or(self.objects.len())]
+                .groups
+                .push(Group {
+                    object: object.map(|_| 0),
+                    ..group.clone()
+                });
+        }
+        parts.retain(|part| !part.groups.is_empty());
+        parts
+    }
+
+    /// Moves the vertices so the bounds are centered on the origin, returning
+    /// the previous center.
+    pub fn recenter(&mut self) -> Vec3 {
+        let bounds = self.bounds();
+        if bounds.is_empty() {
+            return Vec3::ZERO;
+        }
+        let center = bounds.center();
+        for group in &mut self.groups {
+            for vertex in &mut group.vertices {
+                vertex.position -= center;
+            }
+        }
+        for point in self.lines.iter_mut().flatten() {
+            *point -= center;
+        }
+        center
+    }
+}
@@ -0,0 +1,69 @@
+use std::f32::consts::TAU;
+
+use crate::math::{Aabb, Vec3};
+use crate::renderer::LineVertex;
+
+/// Immediate-mode list of colored line segments, rebuilt every frame and drawn
+/// on top of the scene geometry.
+pub struct DebugLines {
+    pub vertices: Vec<LineVertex>,
+    /// Width of every line, in pixels; kept by `clear`.
+    pub width: f32,
+}
+
+impl Default for DebugLines {
+    fn default() -> Self {
+        Self {
+            vertices: Vec::new(),
+            width: Self::DEFAULT_WIDTH,
+        }
+    }
+}
+
+impl DebugLines {
+    pub const DEFAULT_WIDTH: f32 = 2.;
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: Vec3) {
+        self.vertices.push(LineVertex { position: a, color });
+        self.vertices.push(LineVertex { position: b, color });
+    }
+
+    /// Three axis-aligned circles, reading as a sphere from any direction.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec3) {
+        const SEGMENTS: usize = 24;
+
+        let axes = [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)];
+        for (u, v) in axes {
+            let point = |i: usize| {
+                let angle = TAU * i as f32 / SEGMENTS as f32;
+                center + (u * angle.cos() + v * angle.sin()) * radius
+            };
+            for i in 0..SEGMENTS {
+                self.line(point(i), point(i + 1), color);
+            }
+        }
+    }
+
+    /// Edges of a box given its 8 corners, near face first, each face going
+    /// around in order.
+    pub fn hexahedron(&mut self, corners: &[Vec3; 8], color: Vec3) {
+        for i in 0..4 {
+            let j = (i + 1) % 4;
+            self.line(corners[i], corners[j], color);
+            self.line(corners[i + 4], corners[j + 4], color);
+            self.line(corners[i], corners[i + 4], color);
+        }
+    }
+
+    pub fn aabb(&mut self, bounds: &Aabb, color: Vec3) {
+        self.hexahedron(&bounds.corners(), color);
+    }
+}
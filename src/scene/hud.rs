@@ -0,0 +1,54 @@
+use crate::math::{Vec2, Vec4};
+
+pub struct HudText {
+    /// Top-left corner, in window pixels.
+    pub position: Vec2,
+    /// Size of a font pixel, in window pixels.
+    pub scale: f32,
+    pub color: Vec4,
+    pub text: String,
+}
+
+/// Filled rectangle drawn under the HUD text.
+pub struct HudRect {
+    /// Top-left corner, in window pixels.
+    pub position: Vec2,
+    pub size: Vec2,
+    pub color: Vec4,
+}
+
+/// Screen-space text drawn over the scene, rebuilt every frame like
+/// `DebugLines`.
+#[derive(Default)]
+pub struct Hud {
+    pub texts: Vec<HudText>,
+    pub rects: Vec<HudRect>,
+}
+
+impl Hud {
+    pub fn clear(&mut self) {
+        self.texts.clear();
+        self.rects.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty() && self.texts.iter().all(|t| t.text.trim().is_empty())
+    }
+
+    pub fn text(&mut self, position: Vec2, scale: f32, color: Vec4, text: &str) {
+        self.texts.push(HudText {
+            position,
+            scale,
+            color,
+            text: text.to_string(),
+        });
+    }
+
+    pub fn rect(&mut self, position: Vec2, size: Vec2, color: Vec4) {
+        self.rects.push(HudRect {
+            position,
+            size,
+            color,
+        });
+    }
+}
@@ -0,0 +1,31 @@
+/// How the objects of a layer are depth tested against earlier layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LayerDepth {
+    /// Hidden behind whatever earlier layers drew in front of them.
+    #[default]
+    Shared,
+    /// The depth buffer is cleared before the layer, so its objects show over
+    /// earlier layers while still hiding each other.
+    Cleared,
+}
+
+/// Draw order of an object: layers are drawn by ascending `order`, and
+/// objects sharing one are drawn together in the order they were added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct RenderLayer {
+    pub order: i32,
+    pub depth: LayerDepth,
+}
+
+impl RenderLayer {
+    /// Layer of every object unless set otherwise.
+    pub const SCENE: RenderLayer = RenderLayer {
+        order: 0,
+        depth: LayerDepth::Shared,
+    };
+    /// Drawn after and over the scene, for gizmos or measurement markers.
+    pub const OVERLAY: RenderLayer = RenderLayer {
+        order: 100,
+        depth: LayerDepth::Cleared,
+    };
+}
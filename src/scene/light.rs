@@ -0,0 +1,37 @@
+use crate::camera::Camera;
+use crate::math::{Aabb, Vec3};
+
+#[derive(Debug, Clone)]
+pub struct Light {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(0., 500., -700.),
+            color: Vec3::ONE,
+            intensity: 1.,
+        }
+    }
+}
+
+impl Light {
+    /// Camera of the light frustum gizmo: looking from the light at the
+    /// center of `bounds` and tightly enclosing its bounding sphere, to show
+    /// what the light faces. Shadows aren't rendered from it; the shadow map
+    /// cascades are fitted to the view, see `Cascades::fit`.
+    pub fn frustum_camera(&self, bounds: &Aabb) -> Camera {
+        let center = bounds.center();
+        let radius = bounds.radius().max(f32::EPSILON);
+        let distance = (center - self.position).length().max(radius * 1.01);
+
+        let fov = 2. * (radius / distance).asin();
+        let near = (distance - radius).max(0.1);
+        let far = distance + radius;
+
+        Camera::new(self.position, center, fov, 1., near, far)
+    }
+}
@@ -0,0 +1,250 @@
+use std::thread;
+
+use crate::math::{Mat4, Ray, Vec2, Vec3, Vec4};
+use crate::renderer::linear_to_srgb8;
+use crate::scene::{Bvh, Light, Mesh, Occlusion};
+
+/// Uniform sky light added to every texel, scaled by its ambient occlusion.
+const BAKED_AMBIENT: f32 = 0.2;
+
+/// Ambient occlusion rays per lightmap texel.
+const BAKED_OCCLUSION_SAMPLES: u32 = 16;
+
+/// Baked irradiance of a mesh, multiplied with the diffuse color in baked
+/// shading.
+#[derive(Debug, Clone)]
+pub struct Lightmap {
+    /// Texels along each edge.
+    pub size: u32,
+    /// sRGB RGBA8, row-major.
+    pub pixels: Vec<u8>,
+}
+
+impl Lightmap {
+    /// Full white, so baked shading matches unlit until a bake.
+    pub fn unlit(size: u32) -> Self {
+        Self {
+            size,
+            pixels: vec![255; (size * size * 4) as usize],
+        }
+    }
+}
+
+impl Mesh {
+    /// Gives every triangle its own chart in a `size` x `size` lightmap, two
+    /// right triangles per square cell, and resets the lightmap to unlit.
+    /// Vertices are split so no two triangles share one. Charts ignore the
+    /// triangles' shapes and areas: every triangle gets the same texel count.
+    pub fn generate_lightmap_uvs(&mut self, size: u32) {
+        let layout = ChartLayout::new(self.triangle_count(), size);
+
+        let mut chart = 0;
+        for group in &mut self.groups {
+            let mut vertices = Vec::with_capacity(group.indices.len());
+            let mut skinning = Vec::new();
+            for triangle in group.indices.chunks_exact(3) {
+                let corners = layout.corners(chart);
+                chart += 1;
+
+                for (&index, corner) in triangle.iter().zip(corners) {
+                    let mut vertex = group.vertices[index as usize];
+                    vertex.lightmap_uv = corner;
+                    vertices.push(vertex);
+                    if let Some(&skin) = group.skinning.get(index as usize) {
+                        skinning.push(skin);
+                    }
+                }
+            }
+
+            group.indices = (0..vertices.len() as u32).collect();
+            group.vertices = vertices;
+            group.skinning = skinning;
+        }
+
+        self.lightmap = Some(Lightmap::unlit(size));
+    }
+
+    /// Bakes `light` plus an occluded ambient term into the lightmap, for the
+    /// mesh placed with the inverse of `to_local`. Only the mesh itself casts
+    /// shadows.
+    pub fn bake_lightmap(&mut self, light: &Light, to_local: &Mat4) -> Result<(), String> {
+        let size = match &self.lightmap {
+            Some(lightmap) => lightmap.size,
+            None => return Err("Mesh has no lightmap UVs".to_string()),
+        };
+
+        let layout = ChartLayout::new(self.triangle_count(), size);
+        let charts: Vec<(usize, usize)> = self
+            .groups
+            .iter()
+            .enumerate()
+            .flat_map(|(g, group)| (0..group.indices.len() / 3).map(move |t| (g, t)))
+            .collect();
+
+        let bvh = Bvh::build(self);
+        let baker = Baker {
+            mesh: self,
+            bvh: &bvh,
+            occlusion: Occlusion::new(self, &bvh, BAKED_OCCLUSION_SAMPLES),
+            light_position: (*to_local * Vec4::from_vec3(light.position, 1.)).xyz(),
+            light_color: light.color * light.intensity,
+        };
+
+        let threads = thread::available_parallelism().map_or(1, |n| n.get()) as u32;
+        let rows = size.div_ceil(threads).max(1);
+        let pixels = thread::scope(|scope| {
+            let workers: Vec<_> = (0..size)
+                .step_by(rows as usize)
+                .map(|first| {
+                    let (baker, layout, charts) = (&baker, &layout, &charts);
+                    scope.spawn(move || {
+                        let mut pixels = Vec::new();
+                        for y in first..(first + rows).min(size) {
+                            for x in 0..size {
+                                let uv = Vec2::new(
+                                    (x as f32 + 0.5) / size as f32,
+                                    (y as f32 + 0.5) / size as f32,
+                                );
+                                let irradiance = layout.locate(uv, charts.len()).map_or(
+                                    Vec3::ZERO,
+                                    |(chart, barycentric)| {
+                                        let (group, triangle) = charts[chart];
+                                        let seed = y * size + x;
+                                        baker.irradiance(group, triangle, barycentric, seed)
+                                    },
+                                );
+                                pixels.extend_from_slice(&[
+                                    linear_to_srgb8(irradiance.x),
+                                    linear_to_srgb8(irradiance.y),
+                                    linear_to_srgb8(irradiance.z),
+                                    255,
+                                ]);
+                            }
+                        }
+                        pixels
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        self.lightmap = Some(Lightmap { size, pixels });
+        Ok(())
+    }
+}
+
+/// Square cells of two charts each, filling the lightmap row by row.
+struct ChartLayout {
+    /// Cells along each edge.
+    cells: u32,
+    /// Gap around each chart, in cell units, so bilinear filtering doesn't
+    /// blend neighbouring charts.
+    padding: f32,
+    /// Edge length of a chart's legs, in cell units.
+    leg: f32,
+}
+
+impl ChartLayout {
+    fn new(charts: usize, size: u32) -> Self {
+        let cells = (charts.div_ceil(2) as f32).sqrt().ceil().max(1.) as u32;
+        let texel = (cells as f32 / size as f32).min(0.1);
+        Self {
+            cells,
+            padding: texel,
+            // One texel of padding on each side plus two between the charts
+            // along the diagonal.
+            leg: 1. - 4. * texel,
+        }
+    }
+
+    /// Lightmap UVs of the corners of `chart`.
+    fn corners(&self, chart: usize) -> [Vec2; 3] {
+        let cell = (chart / 2) as u32;
+        let origin = Vec2::new((cell % self.cells) as f32, (cell / self.cells) as f32);
+        let (p, l) = (self.padding, self.leg);
+        let local = if chart.is_multiple_of(2) {
+            [Vec2::new(p, p), Vec2::new(p + l, p), Vec2::new(p, p + l)]
+        } else {
+            let q = 1. - p;
+            [Vec2::new(q, q), Vec2::new(q - l, q), Vec2::new(q, q - l)]
+        };
+        local.map(|corner| (origin + corner) * (1. / self.cells as f32))
+    }
+
+    /// Chart covering `uv` and the barycentric coordinates of the closest
+    /// point of it, so padding texels repeat the chart's edge.
+    fn locate(&self, uv: Vec2, charts: usize) -> Option<(usize, Vec3)> {
+        let x = uv.x * self.cells as f32;
+        let y = uv.y * self.cells as f32;
+        let (cx, cy) = (x.floor(), y.floor());
+        let (x, y) = (x - cx, y - cy);
+
+        let upper = x + y >= 1.;
+        let chart = ((cy as u32 * self.cells + cx as u32) * 2 + upper as u32) as usize;
+        if chart >= charts {
+            return None;
+        }
+
+        let (p, l) = (self.padding, self.leg);
+        let (mut s, mut t) = if upper {
+            ((1. - p - x) / l, (1. - p - y) / l)
+        } else {
+            ((x - p) / l, (y - p) / l)
+        };
+        s = s.max(0.);
+        t = t.max(0.);
+        if s + t > 1. {
+            let sum = s + t;
+            s /= sum;
+            t /= sum;
+        }
+
+        Some((chart, Vec3::new(1. - s - t, s, t)))
+    }
+}
+
+struct Baker<'a> {
+    mesh: &'a Mesh,
+    bvh: &'a Bvh,
+    occlusion: Occlusion<'a>,
+    light_position: Vec3,
+    light_color: Vec3,
+}
+
+impl Baker<'_> {
+    /// Light reaching the point at `barycentric` on a triangle.
+    fn irradiance(&self, group: usize, triangle: usize, barycentric: Vec3, seed: u32) -> Vec3 {
+        let group = &self.mesh.groups[group];
+        let [a, b, c] = [0, 1, 2].map(|i| group.vertices[group.indices[triangle * 3 + i] as usize]);
+
+        let position =
+            a.position * barycentric.x + b.position * barycentric.y + c.position * barycentric.z;
+        let face_normal = (b.position - a.position).cross(c.position - a.position);
+        let normal =
+            (a.normal * barycentric.x + b.normal * barycentric.y + c.normal * barycentric.z)
+                .try_normalize()
+                .or(face_normal.try_normalize())
+                .unwrap_or(Vec3::Y);
+
+        let to_light = self.light_position - position;
+        let distance = to_light.length();
+        let direction = to_light / distance.max(f32::EPSILON);
+        let mut diffuse = normal.dot(direction).max(0.);
+        if diffuse > 0. {
+            let origin = position + normal * self.occlusion.bias();
+            if self
+                .bvh
+                .occluded(self.mesh, &Ray::new(origin, direction), distance)
+            {
+                diffuse = 0.;
+            }
+        }
+
+        let ambient = BAKED_AMBIENT * self.occlusion.visibility(position, normal, seed);
+        self.light_color * diffuse + Vec3::splat(ambient)
+    }
+}
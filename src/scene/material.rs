@@ -1,7 +1,10 @@
+use super::Mesh;
 use crate::math::Vec3;
 
 #[derive(Debug, Clone)]
 pub struct Material {
+    /// Name given by `newmtl`, empty for the default material.
+    pub name: String,
     pub ka: Vec3,
     pub kd: Vec3,
     pub ks: Vec3,
@@ -12,11 +15,19 @@ pub struct Material {
     pub map_ka: String,
     pub map_kd: String,
     pub map_ks: String,
+    /// Cutout mask: fragments where it is below half are discarded.
+    pub map_d: String,
+    /// Height map from `disp`, pushing the surface out along its normals.
+    pub disp: String,
+    /// Lit on both sides, from the `two_sided` key: back faces are shaded with
+    /// their normal flipped.
+    pub two_sided: bool,
 }
 
 impl Default for Material {
     fn default() -> Self {
         Self {
+            name: String::new(),
             ka: Vec3::new(1., 0., 0.),
             kd: Vec3::new(1., 0., 0.),
             ks: Vec3::new(1., 0., 0.),
@@ -27,6 +38,58 @@ impl Default for Material {
             map_ka: String::new(),
             map_kd: String::new(),
             map_ks: String::new(),
+            map_d: String::new(),
+            disp: String::new(),
+            two_sided: false,
         }
     }
 }
+
+/// Diffuse colors forced over the materials of loaded meshes, for a quick
+/// look at a model whose MTL file is missing or wrong.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialOverrides {
+    /// Color of every material, groups without one included.
+    pub color: Option<Vec3>,
+    /// Colors of materials by name, taking precedence over `color`.
+    pub named: Vec<(String, Vec3)>,
+}
+
+impl MaterialOverrides {
+    /// Share of a forced color reflected as ambient light.
+    const AMBIENT: f32 = 0.1;
+
+    pub fn is_empty(&self) -> bool {
+        self.color.is_none() && self.named.is_empty()
+    }
+
+    /// Replaces the diffuse color of the overridden materials of `mesh`, and
+    /// drops their diffuse and ambient textures so the color shows.
+    pub fn apply(&self, mesh: &mut Mesh) {
+        if self.color.is_some() && mesh.groups.iter().any(|g| g.material.is_none()) {
+            let index = mesh.materials.len();
+            mesh.materials.push(Material::default());
+            for group in mesh.groups.iter_mut().filter(|g| g.material.is_none()) {
+                group.material = Some(index);
+            }
+        }
+        for material in &mut mesh.materials {
+            let named = self.named.iter().rev().find(|(n, _)| *n == material.name);
+            let Some(color) = named.map(|(_, c)| *c).or(self.color) else {
+                continue;
+            };
+            material.kd = color;
+            material.ka = color * Self::AMBIENT;
+            material.map_kd.clear();
+            material.map_ka.clear();
+        }
+    }
+
+    /// Overridden names that no material of `mesh` has.
+    pub fn unmatched<'a>(&'a self, mesh: &'a Mesh) -> impl Iterator<Item = &'a str> {
+        self.named
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| mesh.materials.iter().all(|m| m.name != *name))
+    }
+}
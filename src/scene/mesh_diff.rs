@@ -0,0 +1,124 @@
+use crate::math::Vec3;
+
+use super::{Bvh, Group, Material, Mesh};
+
+/// Colors of the first and second model in an overlay comparison.
+pub const DIFF_COLORS: [Vec3; 2] = [
+    Vec3 {
+        x: 1.,
+        y: 0.45,
+        z: 0.1,
+    },
+    Vec3 {
+        x: 0.1,
+        y: 0.55,
+        z: 1.,
+    },
+];
+/// Opacity of both models in an overlay comparison.
+pub const DIFF_DISSOLVE: f32 = 0.5;
+
+/// Colors of the heatmap bands, from matching surfaces to the farthest ones.
+const HEATMAP_BANDS: usize = 16;
+
+/// How far two versions of a model are from each other.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeshDistance {
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl Mesh {
+    /// Copy with every group in one flat `color`, blended by `dissolve`.
+    pub fn tinted(&self, color: Vec3, dissolve: f32) -> Mesh {
+        let mut mesh = self.clone();
+        for group in &mut mesh.groups {
+            group.material = Some(0);
+        }
+        mesh.materials = vec![Self::flat_material("diff", color, dissolve)];
+        mesh
+    }
+
+    /// Copy with each triangle colored by how far its vertices lie from the
+    /// surface of `reference`, from blue where they match to red at the
+    /// largest distance, with the distances found.
+    pub fn distance_heatmap(&self, reference: &Mesh) -> (Mesh, MeshDistance) {
+        let bvh = Bvh::build(reference);
+        let distances: Vec<Vec<f32>> = self
+            .groups
+            .iter()
+            .map(|group| {
+                group
+                    .vertices
+                    .iter()
+                    .map(|v| bvh.nearest(reference, v.position).map_or(0., |(_, d)| d))
+                    .collect()
+            })
+            .collect();
+
+        let count: usize = distances.iter().map(Vec::len).sum();
+        let max = distances.iter().flatten().copied().fold(0., f32::max);
+        let mean = distances.iter().flatten().sum::<f32>() / count.max(1) as f32;
+
+        let mut bands: Vec<Group> = (0..HEATMAP_BANDS)
+            .map(|band| Group {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+                material: Some(band),
+                object: None,
+                skinning: Vec::new(),
+            })
+            .collect();
+        for (group, distances) in self.groups.iter().zip(&distances) {
+            for triangle in group.indices.chunks_exact(3) {
+                let distance = triangle.iter().map(|&i| distances[i as usize]).sum::<f32>() / 3.;
+                let t = if max > 0. { distance / max } else { 0. };
+                let band = &mut bands[((t * HEATMAP_BANDS as f32) as usize).min(HEATMAP_BANDS - 1)];
+                for &i in triangle {
+                    band.indices.push(band.vertices.len() as u32);
+                    band.vertices.push(group.vertices[i as usize]);
+                }
+            }
+        }
+
+        let materials = (0..HEATMAP_BANDS)
+            .map(|band| {
+                let t = band as f32 / (HEATMAP_BANDS - 1) as f32;
+                Self::flat_material(&format!("heatmap {}", band), Self::heatmap_color(t), 1.)
+            })
+            .collect();
+
+        let mesh = Mesh {
+            groups: bands
+                .into_iter()
+                .filter(|g| !g.indices.is_empty())
+                .collect(),
+            materials,
+            lightmap: None,
+            objects: Vec::new(),
+            lines: Vec::new(),
+        };
+        (mesh, MeshDistance { max, mean })
+    }
+
+    /// Blue through green to red as `t` goes from 0 to 1.
+    fn heatmap_color(t: f32) -> Vec3 {
+        let t = t.clamp(0., 1.);
+        if t < 0.5 {
+            Vec3::new(0., t * 2., 1. - t * 2.)
+        } else {
+            Vec3::new(t * 2. - 1., 2. - t * 2., 0.)
+        }
+    }
+
+    fn flat_material(name: &str, color: Vec3, dissolve: f32) -> Material {
+        Material {
+            name: name.to_string(),
+            ka: color * 0.3,
+            kd: color,
+            ks: Vec3::ZERO,
+            dissolve,
+            ..Material::default()
+        }
+    }
+}
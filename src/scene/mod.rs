@@ -1,7 +1,46 @@
+mod animation;
+mod auto_rotation;
+mod bvh;
+mod components;
+mod debug;
+mod hud;
+mod layer;
+mod light;
+mod lightmap;
 mod material;
+mod mesh_diff;
 mod object;
+mod occlusion;
+mod orientation;
+mod primitives;
+mod reflection;
 mod scene;
+mod simplify;
+mod skin;
+mod stats_history;
+mod subdivide;
+mod topology;
+mod unwrap;
+mod vertex_cache;
+mod voxel;
 
+pub use animation::*;
+pub use auto_rotation::*;
+pub use bvh::*;
+pub use debug::*;
+pub use hud::*;
+pub use layer::*;
+pub use light::*;
+pub use lightmap::*;
 pub use material::*;
+pub use mesh_diff::*;
 pub use object::*;
+pub use occlusion::*;
+pub use orientation::*;
+pub use reflection::*;
 pub use scene::*;
+pub use skin::*;
+pub use stats_history::*;
+pub use topology::*;
+pub use unwrap::*;
+pub use voxel::*;
@@ -1,17 +1,125 @@
 use crate::math::Mat4;
-use crate::math::Vec3;
-use crate::renderer::{MeshHandle, Vertex};
-use crate::scene::Material;
+use crate::math::{Aabb, ConvexHull, Vec2, Vec3};
+use crate::renderer::{MeshHandle, Vertex, VertexSkin};
+use crate::scene::{Lightmap, Material, RenderLayer, SkinHandle};
 
+#[derive(Clone)]
 pub struct Group {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
     pub material: Option<usize>,
+    /// Index in the mesh's `objects` of the named object it is part of.
+    pub object: Option<usize>,
+    /// Parallel to `vertices` for skinned meshes, empty otherwise.
+    pub skinning: Vec<VertexSkin>,
 }
 
+#[derive(Clone)]
 pub struct Mesh {
     pub groups: Vec<Group>,
     pub materials: Vec<Material>,
+    /// Baked lighting addressed by the vertices' `lightmap_uv`, see
+    /// `generate_lightmap_uvs`.
+    pub lightmap: Option<Lightmap>,
+    /// Names of the objects of an OBJ file's `o` statements, each made of
+    /// the groups naming it and so drawn apart from the others.
+    pub objects: Vec<String>,
+    /// Segments of an OBJ file's `l` statements, drawn in a solid color
+    /// over the faces.
+    pub lines: Vec<[Vec3; 2]>,
+}
+
+impl Mesh {
+    /// Builds a single-group mesh from parallel vertex arrays and a triangle
+    /// list, for geometry generated in memory. `normals` and `uvs` may be
+    /// empty: normals are then averaged from the faces around each vertex,
+    /// and texture coordinates default to zero. Upload the result with
+    /// `App::add_mesh`.
+    pub fn from_buffers(
+        positions: &[Vec3],
+        normals: &[Vec3],
+        uvs: &[Vec2],
+        indices: &[u32],
+    ) -> Result<Mesh, String> {
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(format!(
+                "Expected {} normals, one per position, got {}",
+                positions.len(),
+                normals.len()
+            ));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(format!(
+                "Expected {} texture coordinates, one per position, got {}",
+                positions.len(),
+                uvs.len()
+            ));
+        }
+        if !indices.len().is_multiple_of(3) {
+            return Err(format!(
+                "Expected a triangle list, got {} indices",
+                indices.len()
+            ));
+        }
+        if let Some(&index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+            return Err(format!(
+                "Index {} out of bounds for {} positions",
+                index,
+                positions.len()
+            ));
+        }
+
+        let mut vertices: Vec<Vertex> = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| Vertex {
+                position,
+                normal: normals.get(i).copied().unwrap_or(Vec3::ZERO),
+                uv: uvs.get(i).copied().unwrap_or(Vec2::ZERO),
+                occlusion: 1.,
+                lightmap_uv: Vec2::ZERO,
+            })
+            .collect();
+
+        if normals.is_empty() {
+            // Unnormalized cross products weigh each face by its area.
+            for triangle in indices.chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+                let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+                vertices[a].normal += normal;
+                vertices[b].normal += normal;
+                vertices[c].normal += normal;
+            }
+            // Vertices outside any triangle, or on degenerate ones only, face up.
+            for vertex in &mut vertices {
+                vertex.normal = vertex.normal.try_normalize().unwrap_or(Vec3::Y);
+            }
+        }
+
+        Ok(Self::from_geometry(vertices, indices.to_vec()))
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(
+            self.groups
+                .iter()
+                .flat_map(|g| g.vertices.iter().map(|v| v.position))
+                .chain(self.lines.iter().flatten().copied()),
+        )
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.groups.iter().map(|g| g.indices.len() / 3).sum()
+    }
+
+    pub fn convex_hull(&self) -> ConvexHull {
+        let positions: Vec<Vec3> = self
+            .groups
+            .iter()
+            .flat_map(|g| g.vertices.iter().map(|v| v.position))
+            .collect();
+        ConvexHull::build(&positions)
+    }
 }
 
 pub type ObjectHandle = usize;
@@ -21,6 +129,8 @@ pub struct Object {
     position: Vec3,
     rotation: Vec3,
     scale: Vec3,
+    skin: Option<SkinHandle>,
+    layer: RenderLayer,
 }
 
 impl Object {
@@ -30,6 +140,8 @@ impl Object {
             position: Vec3::ZERO,
             rotation: Vec3::ZERO,
             scale: Vec3::ONE,
+            skin: None,
+            layer: RenderLayer::SCENE,
         }
     }
 
@@ -37,6 +149,45 @@ impl Object {
         self.mesh
     }
 
+    /// Draws `mesh` in place of the object's mesh, keeping its transform.
+    pub fn set_mesh(&mut self, mesh: MeshHandle) -> &mut Self {
+        self.mesh = mesh;
+        self
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn rotation(&self) -> Vec3 {
+        self.rotation
+    }
+
+    pub fn scale(&self) -> Vec3 {
+        self.scale
+    }
+
+    pub fn skin(&self) -> Option<SkinHandle> {
+        self.skin
+    }
+
+    pub fn layer(&self) -> RenderLayer {
+        self.layer
+    }
+
+    /// Draws the object with the other objects of `layer`, see `RenderLayer`.
+    pub fn set_layer(&mut self, layer: RenderLayer) -> &mut Self {
+        self.layer = layer;
+        self
+    }
+
+    /// Deforms the object's mesh with a skeleton of the scene. The mesh must
+    /// have been loaded with per-vertex skinning data.
+    pub fn set_skin(&mut self, skin: SkinHandle) -> &mut Self {
+        self.skin = Some(skin);
+        self
+    }
+
     pub fn set_position(&mut self, position: Vec3) -> &mut Self {
         self.position = position;
         self
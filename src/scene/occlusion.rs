@@ -0,0 +1,137 @@
+use std::f32::consts::TAU;
+use std::thread;
+
+use crate::math::{Ray, Vec3};
+use crate::scene::{Bvh, Mesh};
+
+/// Rays longer than this fraction of the mesh's bounding radius don't count
+/// as occluded, so only nearby geometry darkens a vertex.
+const OCCLUSION_RANGE: f32 = 0.25;
+
+/// Fraction of the bounding radius rays start off the surface, so they don't
+/// hit the triangles around their own vertex.
+const OCCLUSION_BIAS: f32 = 1e-4;
+
+impl Mesh {
+    /// Bakes ambient occlusion into `Vertex::occlusion` by casting `samples`
+    /// cosine-weighted rays over each vertex's hemisphere. Vertices are split
+    /// across the available cores.
+    pub fn bake_occlusion(&mut self, samples: u32) {
+        let bvh = Bvh::build(self);
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+        let occlusion = Occlusion::new(self, &bvh, samples);
+        let baked: Vec<Vec<f32>> = self
+            .groups
+            .iter()
+            .map(|group| {
+                let chunk = group.vertices.len().div_ceil(threads).max(1);
+                thread::scope(|scope| {
+                    let workers: Vec<_> = group
+                        .vertices
+                        .chunks(chunk)
+                        .enumerate()
+                        .map(|(index, vertices)| {
+                            let occlusion = &occlusion;
+                            scope.spawn(move || {
+                                vertices
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, vertex)| {
+                                        let seed = (index * chunk + i) as u32;
+                                        occlusion.visibility(vertex.position, vertex.normal, seed)
+                                    })
+                                    .collect::<Vec<f32>>()
+                            })
+                        })
+                        .collect();
+
+                    workers
+                        .into_iter()
+                        .flat_map(|worker| worker.join().unwrap())
+                        .collect()
+                })
+            })
+            .collect();
+
+        for (group, values) in self.groups.iter_mut().zip(baked) {
+            for (vertex, occlusion) in group.vertices.iter_mut().zip(values) {
+                vertex.occlusion = occlusion;
+            }
+        }
+    }
+}
+
+/// Ambient occlusion queries against a mesh, in mesh space.
+pub struct Occlusion<'a> {
+    mesh: &'a Mesh,
+    bvh: &'a Bvh,
+    samples: u32,
+    range: f32,
+    bias: f32,
+}
+
+impl<'a> Occlusion<'a> {
+    /// Casts `samples` rays per query against `bvh`, built from `mesh`.
+    pub fn new(mesh: &'a Mesh, bvh: &'a Bvh, samples: u32) -> Self {
+        let radius = mesh.bounds().radius();
+        Self {
+            mesh,
+            bvh,
+            samples: samples.max(1),
+            range: radius * OCCLUSION_RANGE,
+            bias: radius * OCCLUSION_BIAS,
+        }
+    }
+
+    /// Distance rays start off the surface.
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    /// Fraction of the rays leaving `position` around `normal` that escape,
+    /// 1 for points without a usable normal. `seed` rotates the sample
+    /// pattern.
+    pub fn visibility(&self, position: Vec3, normal: Vec3, seed: u32) -> f32 {
+        let Some(normal) = normal.try_normalize() else {
+            return 1.;
+        };
+        let helper = if normal.x.abs() < 0.9 {
+            Vec3::X
+        } else {
+            Vec3::Y
+        };
+        let tangent = helper.cross(normal).normalize();
+        let bitangent = normal.cross(tangent);
+        let origin = position + normal * self.bias;
+
+        // Stratified along the elevation, with a per-vertex rotation so
+        // neighbouring vertices don't share the same banding.
+        let rotation = hash(seed) as f32 / u32::MAX as f32;
+        let mut escaped = 0;
+        for i in 0..self.samples {
+            let u = (i as f32 + 0.5) / self.samples as f32;
+            let phi = TAU * (i as f32 * 0.618_034 + rotation).fract();
+            let r = u.sqrt();
+            let direction =
+                tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1. - u).sqrt();
+
+            let ray = Ray::new(origin, direction);
+            if !self.bvh.occluded(self.mesh, &ray, self.range) {
+                escaped += 1;
+            }
+        }
+
+        escaped as f32 / self.samples as f32
+    }
+}
+
+/// Integer hash (lowbias32) spreading consecutive seeds over the full range.
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
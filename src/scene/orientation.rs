@@ -0,0 +1,171 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::math::{Ray, Vec3};
+
+use super::{Mesh, Topology};
+
+/// Rays cast from the largest triangles of a component to vote whether it
+/// faces inward.
+const PARITY_RAYS: usize = 5;
+
+/// What `Mesh::fix_orientation` changed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrientationFix {
+    pub flipped_triangles: usize,
+    /// Components turned inside out as a whole.
+    pub flipped_components: usize,
+    /// Vertex normals pointing against their faces, negated.
+    pub flipped_normals: usize,
+}
+
+impl Mesh {
+    /// Makes the winding of triangles consistent across each connected
+    /// component, turns components whose faces point inward inside out, and
+    /// negates vertex normals pointing against their faces, fixing models
+    /// that render inside-out with back faces culled.
+    ///
+    /// Windings are propagated across manifold edges. A component faces
+    /// inward when rays cast along the normals of its largest triangles cross
+    /// the component an odd number of times; open components rarely do.
+    pub fn fix_orientation(&mut self) -> OrientationFix {
+        let topology = Topology::build(self);
+        let count = topology.corners.len();
+        let mut flipped = vec![false; count];
+        let mut visited = vec![false; count];
+        let mut fix = OrientationFix::default();
+
+        for seed in 0..count {
+            if visited[seed] {
+                continue;
+            }
+            visited[seed] = true;
+            let mut component = vec![seed];
+            let mut queue = VecDeque::from([seed]);
+            while let Some(triangle) = queue.pop_front() {
+                for (other, consistent) in topology.neighbors(triangle) {
+                    if !visited[other] {
+                        visited[other] = true;
+                        flipped[other] = flipped[triangle] == consistent;
+                        component.push(other);
+                        queue.push_back(other);
+                    }
+                }
+            }
+
+            if self.faces_inward(&topology, &component, &flipped) {
+                for &triangle in &component {
+                    flipped[triangle] = !flipped[triangle];
+                }
+                fix.flipped_components += 1;
+            }
+        }
+
+        for (triangle, _) in flipped.iter().enumerate().filter(|(_, &f)| f) {
+            let t = topology.triangles[triangle];
+            let indices = &mut self.groups[t.group as usize].indices;
+            indices.swap(t.triangle as usize * 3 + 1, t.triangle as usize * 3 + 2);
+            fix.flipped_triangles += 1;
+        }
+        fix.flipped_normals = self.align_normals();
+        fix
+    }
+
+    /// Normal of triangle `index` of `topology`, scaled by twice its area,
+    /// reversed when `flipped`.
+    fn face_normal(&self, topology: &Topology, index: usize, flipped: bool) -> Vec3 {
+        let [a, b, c] = self.triangle_positions(topology.triangles[index]);
+        let normal = (b - a).cross(c - a);
+        if flipped {
+            -normal
+        } else {
+            normal
+        }
+    }
+
+    /// Whether most rays cast along the normals of the largest triangles of
+    /// `component` start inside it.
+    fn faces_inward(&self, topology: &Topology, component: &[usize], flipped: &[bool]) -> bool {
+        let mut largest: Vec<(usize, Vec3)> = component
+            .iter()
+            .map(|&t| (t, self.face_normal(topology, t, flipped[t])))
+            .collect();
+        largest.sort_by(|a, b| b.1.length().total_cmp(&a.1.length()));
+
+        let mut inward = 0;
+        let mut cast = 0;
+        for &(start, normal) in largest.iter().take(PARITY_RAYS) {
+            let Some(direction) = normal.try_normalize() else {
+                continue;
+            };
+            let [a, b, c] = self.triangle_positions(topology.triangles[start]);
+            let ray = Ray::new((a + b + c) / 3., direction);
+
+            let crossings = component
+                .iter()
+                .filter(|&&other| other != start)
+                .filter(|&&other| {
+                    let [a, b, c] = self.triangle_positions(topology.triangles[other]);
+                    ray.intersect_triangle(a, b, c)
+                        .is_some_and(|distance| distance > f32::EPSILON)
+                })
+                .count();
+            cast += 1;
+            if crossings % 2 == 1 {
+                inward += 1;
+            }
+        }
+        inward * 2 > cast
+    }
+
+    /// Negates the vertex normals pointing away from the area-weighted normal
+    /// of their triangles, returning how many.
+    fn align_normals(&mut self) -> usize {
+        let mut flipped = 0;
+        for group in &mut self.groups {
+            let mut face_normals: HashMap<u32, Vec3> = HashMap::new();
+            for triangle in group.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| group.vertices[triangle[i] as usize].position);
+                let normal = (b - a).cross(c - a);
+                for &index in triangle {
+                    *face_normals.entry(index).or_insert(Vec3::ZERO) += normal;
+                }
+            }
+            for (index, normal) in face_normals {
+                let vertex = &mut group.vertices[index as usize];
+                if vertex.normal.dot(normal) < 0. {
+                    vertex.normal = -vertex.normal;
+                    flipped += 1;
+                }
+            }
+        }
+        flipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_consistent_windings() {
+        for mut mesh in [Mesh::cube(1.), Mesh::icosphere(1., 2)] {
+            let before = mesh.groups[0].indices.clone();
+            let fix = mesh.fix_orientation();
+            assert_eq!(fix.flipped_triangles, 0);
+            assert_eq!(fix.flipped_components, 0);
+            assert_eq!(mesh.groups[0].indices, before);
+        }
+    }
+
+    #[test]
+    fn flips_inverted_face() {
+        for mut mesh in [Mesh::cube(1.), Mesh::icosphere(1., 2)] {
+            let before = mesh.groups[0].indices.clone();
+            let last = before.len() - 1;
+            mesh.groups[0].indices.swap(last - 1, last);
+            let fix = mesh.fix_orientation();
+            assert_eq!(fix.flipped_triangles, 1);
+            assert_eq!(mesh.groups[0].indices, before);
+        }
+    }
+}
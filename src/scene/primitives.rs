@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use crate::math::{Vec2, Vec3};
+use crate::renderer::Vertex;
+use crate::scene::{Group, Mesh};
+
+/// Procedural meshes, centered on the origin, with counter-clockwise
+/// outward-facing triangles like OBJ files.
+impl Mesh {
+    pub const PRIMITIVES: [&'static str; 6] =
+        ["cube", "sphere", "icosphere", "plane", "torus", "cone"];
+
+    /// Builds a primitive by name with default tessellation, sized to fit in
+    /// a box of edge `size`.
+    pub fn primitive(name: &str, size: f32) -> Result<Mesh, String> {
+        let half = size * 0.5;
+        match name {
+            "cube" => Ok(Self::cube(size)),
+            "sphere" => Ok(Self::uv_sphere(half, 32, 16)),
+            "icosphere" => Ok(Self::icosphere(half, 3)),
+            "plane" => Ok(Self::plane(size, 1)),
+            "torus" => Ok(Self::torus(half * 0.7, half * 0.3, 48, 24)),
+            "cone" => Ok(Self::cone(half, size, 32)),
+            _ => Err(format!(
+                "Unknown primitive '{}', expected one of: {}",
+                name,
+                Self::PRIMITIVES.join(", ")
+            )),
+        }
+    }
+
+    /// Wraps raw geometry into a single group using the default material.
+    pub fn from_geometry(vertices: Vec<Vertex>, indices: Vec<u32>) -> Mesh {
+        Mesh {
+            groups: vec![Group {
+                vertices,
+                indices,
+                material: None,
+                object: None,
+                skinning: Vec::new(),
+            }],
+            materials: Vec::new(),
+            lightmap: None,
+            objects: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn cube(size: f32) -> Mesh {
+        let h = size * 0.5;
+        // (normal, u, v) with u x v = normal so corners wind counter-clockwise.
+        let faces = [
+            (Vec3::X, -Vec3::Z, Vec3::Y),
+            (-Vec3::X, Vec3::Z, Vec3::Y),
+            (Vec3::Y, Vec3::X, -Vec3::Z),
+            (-Vec3::Y, Vec3::X, Vec3::Z),
+            (Vec3::Z, Vec3::X, Vec3::Y),
+            (-Vec3::Z, -Vec3::X, Vec3::Y),
+        ];
+        let corners = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)];
+
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for (normal, u, v) in faces {
+            let base = vertices.len() as u32;
+            for (cu, cv) in corners {
+                vertices.push(Vertex {
+                    position: (normal + u * cu + v * cv) * h,
+                    normal,
+                    uv: Vec2::new((cu + 1.) * 0.5, (1. - cv) * 0.5),
+                    occlusion: 1.,
+                    lightmap_uv: Vec2::ZERO,
+                });
+            }
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        Self::from_geometry(vertices, indices)
+    }
+
+    /// Square in the XZ plane facing +Y, split into `subdivisions²` quads.
+    pub fn plane(size: f32, subdivisions: u32) -> Mesh {
+        let n = subdivisions.max(1);
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for j in 0..=n {
+            for i in 0..=n {
+                let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+                vertices.push(Vertex {
+                    position: Vec3::new((u - 0.5) * size, 0., (v - 0.5) * size),
+                    normal: Vec3::Y,
+                    uv: Vec2::new(u, v),
+                    occlusion: 1.,
+                    lightmap_uv: Vec2::ZERO,
+                });
+            }
+        }
+
+        let row = n + 1;
+        for j in 0..n {
+            for i in 0..n {
+                let a = j * row + i;
+                let (b, c, d) = (a + 1, a + row + 1, a + row);
+                indices.extend_from_slice(&[a, c, b, a, d, c]);
+            }
+        }
+
+        Self::from_geometry(vertices, indices)
+    }
+
+    /// Latitude/longitude sphere with a duplicated seam for clean UVs. The
+    /// seam's vertices copy the positions of the first column, and each pole
+    /// is a single point, so welding by position closes the surface.
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+        let (segments, rings) = (segments.max(3), rings.max(2));
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for r in 0..=rings {
+            let theta = PI * r as f32 / rings as f32;
+            for s in 0..=segments {
+                let phi = TAU * (s % segments) as f32 / segments as f32;
+                let normal = match r {
+                    0 => Vec3::Y,
+                    _ if r == rings => -Vec3::Y,
+                    _ => Vec3::new(
+                        theta.sin() * phi.cos(),
+                        theta.cos(),
+                        theta.sin() * phi.sin(),
+                    ),
+                };
+                vertices.push(Vertex {
+                    position: normal * radius,
+                    normal,
+                    uv: Vec2::new(s as f32 / segments as f32, r as f32 / rings as f32),
+                    occlusion: 1.,
+                    lightmap_uv: Vec2::ZERO,
+                });
+            }
+        }
+
+        let row = segments + 1;
+        for r in 0..rings {
+            for s in 0..segments {
+                let a = r * row + s;
+                let (b, c, d) = (a + 1, a + row + 1, a + row);
+                if r != 0 {
+                    indices.extend_from_slice(&[a, b, d]);
+                }
+                if r != rings - 1 {
+                    indices.extend_from_slice(&[b, c, d]);
+                }
+            }
+        }
+
+        Self::from_geometry(vertices, indices)
+    }
+
+    /// Subdivided icosahedron, giving evenly sized triangles.
+    pub fn icosphere(radius: f32, subdivisions: u32) -> Mesh {
+        let t = (1. + 5f32.sqrt()) * 0.5;
+        let mut positions: Vec<Vec3> = [
+            (-1., t, 0.),
+            (1., t, 0.),
+            (-1., -t, 0.),
+            (1., -t, 0.),
+            (0., -1., t),
+            (0., 1., t),
+            (0., -1., -t),
+            (0., 1., -t),
+            (t, 0., -1.),
+            (t, 0., 1.),
+            (-t, 0., -1.),
+            (-t, 0., 1.),
+        ]
+        .into_iter()
+        .map(|p| Vec3::from(p).normalize())
+        .collect();
+
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| -> u32 {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let p = (positions[a as usize] + positions[b as usize]).normalize();
+                    positions.push(p);
+                    (positions.len() - 1) as u32
+                })
+            };
+
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let vertices = positions
+            .iter()
+            .map(|&normal| Vertex {
+                position: normal * radius,
+                normal,
+                uv: Vec2::new(
+                    0.5 + normal.z.atan2(normal.x) / TAU,
+                    normal.y.clamp(-1., 1.).acos() / PI,
+                ),
+                occlusion: 1.,
+                lightmap_uv: Vec2::ZERO,
+            })
+            .collect();
+
+        Self::from_geometry(vertices, triangles.concat())
+    }
+
+    /// Torus around the Y axis. The last ring and column copy the positions
+    /// of the first ones, only their UVs differing, so welding by position
+    /// closes the surface.
+    pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Mesh {
+        let (segments, sides) = (segments.max(3), sides.max(3));
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for i in 0..=segments {
+            let phi = TAU * (i % segments) as f32 / segments as f32;
+            for j in 0..=sides {
+                let theta = TAU * (j % sides) as f32 / sides as f32;
+                let normal = Vec3::new(
+                    theta.cos() * phi.cos(),
+                    theta.sin(),
+                    theta.cos() * phi.sin(),
+                );
+                let center = Vec3::new(phi.cos(), 0., phi.sin()) * major_radius;
+                vertices.push(Vertex {
+                    position: center + normal * minor_radius,
+                    normal,
+                    uv: Vec2::new(i as f32 / segments as f32, j as f32 / sides as f32),
+                    occlusion: 1.,
+                    lightmap_uv: Vec2::ZERO,
+                });
+            }
+        }
+
+        let row = sides + 1;
+        for i in 0..segments {
+            for j in 0..sides {
+                let a = i * row + j;
+                let (b, c, d) = (a + row, a + row + 1, a + 1);
+                indices.extend_from_slice(&[a, d, b, b, d, c]);
+            }
+        }
+
+        Self::from_geometry(vertices, indices)
+    }
+
+    /// Cone along Y with its apex at `height / 2` and a capped base.
+    pub fn cone(radius: f32, height: f32, segments: u32) -> Mesh {
+        let segments = segments.max(3);
+        let h = height * 0.5;
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        let slant =
+            |phi: f32| Vec3::new(phi.cos() * height, radius, phi.sin() * height).normalize();
+
+        for s in 0..segments {
+            let phi0 = TAU * s as f32 / segments as f32;
+            let phi1 = TAU * (s + 1) as f32 / segments as f32;
+            // The last side ends at the first one's angle, so their shared
+            // edge has the exact same positions.
+            let end = TAU * ((s + 1) % segments) as f32 / segments as f32;
+            let u0 = s as f32 / segments as f32;
+            let u1 = (s + 1) as f32 / segments as f32;
+
+            let base = vertices.len() as u32;
+            vertices.push(Vertex {
+                position: Vec3::new(0., h, 0.),
+                normal: slant((phi0 + phi1) * 0.5),
+                uv: Vec2::new((u0 + u1) * 0.5, 0.),
+                occlusion: 1.,
+                lightmap_uv: Vec2::ZERO,
+            });
+            vertices.push(Vertex {
+                position: Vec3::new(end.cos() * radius, -h, end.sin() * radius),
+                normal: slant(end),
+                uv: Vec2::new(u1, 1.),
+                occlusion: 1.,
+                lightmap_uv: Vec2::ZERO,
+            });
+            vertices.push(Vertex {
+                position: Vec3::new(phi0.cos() * radius, -h, phi0.sin() * radius),
+                normal: slant(phi0),
+                uv: Vec2::new(u0, 1.),
+                occlusion: 1.,
+                lightmap_uv: Vec2::ZERO,
+            });
+            indices.extend_from_slice(&[base, base + 1, base + 2]);
+        }
+
+        let center = vertices.len() as u32;
+        vertices.push(Vertex {
+            position: Vec3::new(0., -h, 0.),
+            normal: -Vec3::Y,
+            uv: Vec2::new(0.5, 0.5),
+            occlusion: 1.,
+            lightmap_uv: Vec2::ZERO,
+        });
+        for s in 0..segments {
+            let phi = TAU * s as f32 / segments as f32;
+            vertices.push(Vertex {
+                position: Vec3::new(phi.cos() * radius, -h, phi.sin() * radius),
+                normal: -Vec3::Y,
+                uv: Vec2::new(0.5 + phi.cos() * 0.5, 0.5 + phi.sin() * 0.5),
+                occlusion: 1.,
+                lightmap_uv: Vec2::ZERO,
+            });
+        }
+        for s in 0..segments {
+            let a = center + 1 + s;
+            let b = center + 1 + (s + 1) % segments;
+            indices.extend_from_slice(&[center, a, b]);
+        }
+
+        Self::from_geometry(vertices, indices)
+    }
+}
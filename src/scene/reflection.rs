@@ -0,0 +1,45 @@
+use crate::math::{Aabb, Mat4, Vec3};
+
+/// Mirror image of the objects on a horizontal ground plane, drawn within a
+/// square under them.
+#[derive(Debug, Clone, Copy)]
+pub struct GroundReflection {
+    /// Center of the mirror, on the ground plane.
+    pub center: Vec3,
+    /// Half the side of the mirror.
+    pub extent: f32,
+    /// Opacity of the reflected objects over the background.
+    pub strength: f32,
+}
+
+impl GroundReflection {
+    pub const DEFAULT_STRENGTH: f32 = 0.35;
+
+    /// Mirror touching the bottom of `bounds`, twice as wide as their widest
+    /// horizontal side.
+    pub fn under(bounds: &Aabb) -> Self {
+        let center = bounds.center();
+        let size = bounds.size();
+        Self {
+            center: Vec3::new(center.x, bounds.min.y, center.z),
+            extent: size.x.max(size.z),
+            strength: Self::DEFAULT_STRENGTH,
+        }
+    }
+
+    /// Reflects world positions across the ground plane.
+    pub fn mirror(&self) -> Mat4 {
+        let ground = Vec3::new(0., self.center.y, 0.);
+        Mat4::identity()
+            .translate(ground)
+            .scale(Vec3::new(1., -1., 1.))
+            .translate(-ground)
+    }
+
+    /// Transform flattening the unit cube onto the mirror's square.
+    pub fn plane(&self) -> Mat4 {
+        Mat4::identity()
+            .translate(self.center - Vec3::new(self.extent, 0., self.extent))
+            .scale(Vec3::new(self.extent * 2., 0., self.extent * 2.))
+    }
+}
@@ -1,16 +1,44 @@
-use super::{Object, ObjectHandle};
+use super::{
+    Animation, DebugLines, GroundReflection, Hud, Light, Object, ObjectHandle, Skin, SkinHandle,
+    VoxelPreview,
+};
 
 pub struct Scene {
     pub objects: Vec<Object>,
+    pub light: Light,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+    pub debug_lines: DebugLines,
+    pub hud: Hud,
+    /// Object picked by the user, drawn highlighted.
+    pub selected: Option<ObjectHandle>,
+    /// Mirror on the ground, drawn when the depth buffer has a stencil.
+    pub reflection: Option<GroundReflection>,
+    /// Cubes drawn in place of the objects when not empty.
+    pub voxels: VoxelPreview,
 }
 
 impl Scene {
     pub fn new() -> Self {
         Self {
             objects: Vec::new(),
+            light: Light::default(),
+            skins: Vec::new(),
+            animations: Vec::new(),
+            debug_lines: DebugLines::default(),
+            hud: Hud::default(),
+            selected: None,
+            reflection: None,
+            voxels: VoxelPreview::default(),
         }
     }
 
+    pub fn add_skin(&mut self, skin: Skin) -> SkinHandle {
+        self.skins.push(skin);
+
+        (self.skins.len() - 1) as SkinHandle
+    }
+
     pub fn add_object(&mut self, object: Object) -> ObjectHandle {
         self.objects.push(object);
 
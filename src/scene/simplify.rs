@@ -0,0 +1,308 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::math::Vec3;
+
+use super::{Group, Mesh};
+
+impl Mesh {
+    /// Copy with about `ratio` of the triangles of each group left, by
+    /// collapsing the edges whose removal changes the surface least, as
+    /// measured by quadric error metrics.
+    ///
+    /// Vertices sharing their position with another one, on texture or
+    /// normal seams and between groups, and vertices on open borders never
+    /// move, so the mesh doesn't tear apart. Groups made mostly of those keep
+    /// more triangles than asked.
+    pub fn simplify(&self, ratio: f32) -> Mesh {
+        let ratio = ratio.clamp(0., 1.);
+        let mut positions: HashMap<[u32; 3], usize> = HashMap::new();
+        for group in &self.groups {
+            for vertex in &group.vertices {
+                let p = vertex.position;
+                *positions
+                    .entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+                    .or_default() += 1;
+            }
+        }
+
+        let groups = self
+            .groups
+            .iter()
+            .map(|group| {
+                let shared: Vec<bool> = group
+                    .vertices
+                    .iter()
+                    .map(|v| {
+                        let p = v.position;
+                        positions[&[p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]] > 1
+                    })
+                    .collect();
+                let target = (group.indices.len() / 3) as f32 * ratio;
+                simplify_group(group, shared, target.round() as usize)
+            })
+            .collect();
+
+        Mesh {
+            groups,
+            materials: self.materials.clone(),
+            lightmap: self.lightmap.clone(),
+            objects: self.objects.clone(),
+            lines: self.lines.clone(),
+        }
+    }
+}
+
+/// Symmetric 4x4 matrix summing the squared distances to planes, stored as
+/// its upper triangle.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Squared distance to the plane through `point` with unit `normal`,
+    /// weighted by `weight`.
+    fn plane(normal: Vec3, point: Vec3, weight: f64) -> Self {
+        let [a, b, c] = [normal.x, normal.y, normal.z].map(f64::from);
+        let d = -(a * point.x as f64 + b * point.y as f64 + c * point.z as f64);
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|x| x * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, p: Vec3) -> f64 {
+        let [x, y, z] = [p.x, p.y, p.z].map(f64::from);
+        let q = &self.0;
+        q[0] * x * x
+            + 2. * q[1] * x * y
+            + 2. * q[2] * x * z
+            + 2. * q[3] * x
+            + q[4] * y * y
+            + 2. * q[5] * y * z
+            + 2. * q[6] * y
+            + q[7] * z * z
+            + 2. * q[8] * z
+            + q[9]
+    }
+}
+
+/// Collapse of the vertex `from` onto `to`, ordered cheapest first.
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    /// `versions[from]` when the collapse was queued, stale once it changed.
+    version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Collapses edges of `group` onto their endpoints, keeping the attributes of
+/// the vertex collapsed onto, until `target` triangles are left. `locked`
+/// vertices stay, as do those on the group's open borders.
+fn simplify_group(group: &Group, mut locked: Vec<bool>, target: usize) -> Group {
+    let position = |v: u32| group.vertices[v as usize].position;
+    let mut triangles: Vec<[u32; 3]> = group
+        .indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .collect();
+    let mut alive = vec![true; triangles.len()];
+    let mut remaining = triangles.len();
+    let mut incident: Vec<Vec<usize>> = vec![Vec::new(); group.vertices.len()];
+    let mut quadrics = vec![Quadric::default(); group.vertices.len()];
+    let mut edge_uses: HashMap<(u32, u32), u32> = HashMap::new();
+
+    for (index, &[a, b, c]) in triangles.iter().enumerate() {
+        let normal = (position(b) - position(a)).cross(position(c) - position(a));
+        let area = normal.length() as f64;
+        if let Some(unit) = normal.try_normalize() {
+            let quadric = Quadric::plane(unit, position(a), area);
+            for v in [a, b, c] {
+                quadrics[v as usize].add(&quadric);
+            }
+        }
+        for v in [a, b, c] {
+            incident[v as usize].push(index);
+        }
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            *edge_uses.entry((from.min(to), from.max(to))).or_default() += 1;
+        }
+    }
+    for (&(a, b), &uses) in &edge_uses {
+        if uses == 1 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+
+    let mut versions = vec![0u32; group.vertices.len()];
+    let mut queue: BinaryHeap<Collapse> = BinaryHeap::new();
+    let queue_collapses = |queue: &mut BinaryHeap<Collapse>,
+                           quadrics: &[Quadric],
+                           versions: &[u32],
+                           from: u32,
+                           neighbors: &HashSet<u32>| {
+        for &to in neighbors {
+            let mut quadric = quadrics[from as usize];
+            quadric.add(&quadrics[to as usize]);
+            queue.push(Collapse {
+                cost: quadric.error(position(to)),
+                from,
+                to,
+                version: versions[from as usize],
+            });
+        }
+    };
+    let neighbors = |incident: &[Vec<usize>], triangles: &[[u32; 3]], v: u32| -> HashSet<u32> {
+        incident[v as usize]
+            .iter()
+            .flat_map(|&t| triangles[t])
+            .filter(|&w| w != v)
+            .collect()
+    };
+    for v in 0..group.vertices.len() as u32 {
+        if !locked[v as usize] {
+            let around = neighbors(&incident, &triangles, v);
+            queue_collapses(&mut queue, &quadrics, &versions, v, &around);
+        }
+    }
+
+    while remaining > target {
+        let Some(collapse) = queue.pop() else {
+            break;
+        };
+        let (from, to) = (collapse.from, collapse.to);
+        if collapse.version != versions[from as usize] || locked[from as usize] {
+            continue;
+        }
+        let around = neighbors(&incident, &triangles, from);
+        if !around.contains(&to) {
+            continue;
+        }
+
+        // Rejected when a remaining triangle would turn over, or when the
+        // two vertices share more neighbors than the triangles on their edge,
+        // which would pinch the surface.
+        let shared = neighbors(&incident, &triangles, to)
+            .intersection(&around)
+            .count();
+        let edge_triangles = incident[from as usize]
+            .iter()
+            .filter(|&&t| triangles[t].contains(&to))
+            .count();
+        let flips = incident[from as usize].iter().any(|&t| {
+            let corners = triangles[t];
+            if corners.contains(&to) {
+                return false;
+            }
+            let [a, b, c] = corners.map(position);
+            let before = (b - a).cross(c - a);
+            let [a, b, c] = corners.map(|v| position(if v == from { to } else { v }));
+            let after = (b - a).cross(c - a);
+            before.dot(after) <= 0.
+        });
+        if shared > edge_triangles || flips {
+            continue;
+        }
+
+        for t in std::mem::take(&mut incident[from as usize]) {
+            if triangles[t].contains(&to) {
+                alive[t] = false;
+                remaining -= 1;
+                for v in triangles[t] {
+                    incident[v as usize].retain(|&other| other != t);
+                }
+            } else {
+                for v in triangles[t].iter_mut().filter(|v| **v == from) {
+                    *v = to;
+                }
+                incident[to as usize].push(t);
+            }
+        }
+        let from_quadric = quadrics[from as usize];
+        quadrics[to as usize].add(&from_quadric);
+        versions[from as usize] += 1;
+        versions[to as usize] += 1;
+        locked[from as usize] = true;
+
+        // Collapses of `to` and of its neighbors now cost differently.
+        let around = neighbors(&incident, &triangles, to);
+        if !locked[to as usize] {
+            queue_collapses(&mut queue, &quadrics, &versions, to, &around);
+        }
+        for &v in &around {
+            if !locked[v as usize] {
+                versions[v as usize] += 1;
+                let others = neighbors(&incident, &triangles, v);
+                queue_collapses(&mut queue, &quadrics, &versions, v, &others);
+            }
+        }
+    }
+
+    // Keeps the vertices still in use, in their original order.
+    let mut remap = vec![u32::MAX; group.vertices.len()];
+    let mut simplified = Group {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        material: group.material,
+        object: group.object,
+        skinning: Vec::new(),
+    };
+    let used: HashSet<u32> = triangles
+        .iter()
+        .zip(&alive)
+        .filter(|(_, &alive)| alive)
+        .flat_map(|(t, _)| *t)
+        .collect();
+    for (v, vertex) in group.vertices.iter().enumerate() {
+        if used.contains(&(v as u32)) {
+            remap[v] = simplified.vertices.len() as u32;
+            simplified.vertices.push(*vertex);
+            if let Some(&skin) = group.skinning.get(v) {
+                simplified.skinning.push(skin);
+            }
+        }
+    }
+    for (triangle, _) in triangles.iter().zip(&alive).filter(|(_, &alive)| alive) {
+        simplified
+            .indices
+            .extend(triangle.iter().map(|&v| remap[v as usize]));
+    }
+    simplified
+}
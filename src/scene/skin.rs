@@ -0,0 +1,52 @@
+use crate::math::{Mat4, Quat, Vec3};
+
+pub type SkinHandle = usize;
+
+/// Bone of a skeleton, posed relative to its parent.
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    /// Takes mesh-space positions into the joint's bind-pose space.
+    pub inverse_bind: Mat4,
+}
+
+impl Joint {
+    pub fn local_transform(&self) -> Mat4 {
+        Mat4::identity().translate(self.translation)
+            * self.rotation.to_mat4()
+            * Mat4::identity().scale(self.scale)
+    }
+}
+
+/// Skeleton deforming a skinned mesh, with joints sorted so that parents come
+/// before their children.
+#[derive(Debug, Clone, Default)]
+pub struct Skin {
+    pub joints: Vec<Joint>,
+}
+
+impl Skin {
+    /// Skinning matrices for the current pose, taking mesh-space positions to
+    /// their deformed mesh-space positions.
+    pub fn joint_matrices(&self) -> Vec<Mat4> {
+        let mut globals: Vec<Mat4> = Vec::with_capacity(self.joints.len());
+        for joint in &self.joints {
+            let local = joint.local_transform();
+            let global = match joint.parent {
+                Some(parent) if parent < globals.len() => globals[parent] * local,
+                _ => local,
+            };
+            globals.push(global);
+        }
+
+        globals
+            .iter()
+            .zip(&self.joints)
+            .map(|(global, joint)| *global * joint.inverse_bind)
+            .collect()
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+
+use super::Hud;
+use crate::math::{Vec2, Vec4};
+
+/// Statistics of one frame, as plotted by `StatsHistory`.
+#[derive(Clone, Copy, Default)]
+pub struct StatsSample {
+    /// Frame duration, in milliseconds.
+    pub frame_ms: f32,
+    pub draw_calls: u32,
+    pub triangles: u64,
+    /// Device-local memory in use, in bytes, when the GPU reports it.
+    pub gpu_memory: Option<u64>,
+}
+
+/// One statistic over the history, with how to print its values.
+struct Plot {
+    name: &'static str,
+    values: Vec<f32>,
+    format: fn(f32) -> String,
+    color: Vec4,
+}
+
+/// The last few seconds of frame statistics, laid out as line plots for the
+/// stats window.
+pub struct StatsHistory {
+    samples: VecDeque<StatsSample>,
+}
+
+impl Default for StatsHistory {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(Self::CAPACITY),
+        }
+    }
+}
+
+impl StatsHistory {
+    /// Samples kept, one per frame.
+    const CAPACITY: usize = 240;
+    const MARGIN: f32 = 10.;
+    const TEXT_SCALE: f32 = 2.;
+    /// Height of the label above each plot, in window pixels.
+    const LABEL_HEIGHT: f32 = 22.;
+    const LINE_WIDTH: f32 = 2.;
+
+    pub fn push(&mut self, sample: StatsSample) {
+        if self.samples.len() == Self::CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Fills `hud` with one plot per statistic, stacked in a window of `size`
+    /// pixels. Each plot is scaled to its largest sample.
+    pub fn layout(&self, hud: &mut Hud, size: Vec2) {
+        hud.clear();
+
+        let mut plots = vec![
+            Plot {
+                name: "frame time",
+                values: self.samples.iter().map(|s| s.frame_ms).collect(),
+                format: |v| format!("{:.2} ms", v),
+                color: Vec4::new(1., 0.8, 0.3, 1.),
+            },
+            Plot {
+                name: "draw calls",
+                values: self.samples.iter().map(|s| s.draw_calls as f32).collect(),
+                format: |v| format!("{}", v as u32),
+                color: Vec4::new(0.4, 0.8, 1., 1.),
+            },
+            Plot {
+                name: "triangles",
+                values: self.samples.iter().map(|s| s.triangles as f32).collect(),
+                format: |v| format!("{}", v as u64),
+                color: Vec4::new(0.5, 1., 0.5, 1.),
+            },
+        ];
+        if self.samples.iter().any(|s| s.gpu_memory.is_some()) {
+            plots.push(Plot {
+                name: "gpu memory",
+                values: self
+                    .samples
+                    .iter()
+                    .map(|s| s.gpu_memory.unwrap_or(0) as f32 / (1024. * 1024.))
+                    .collect(),
+                format: |v| format!("{:.1} MiB", v),
+                color: Vec4::new(1., 0.5, 0.8, 1.),
+            });
+        }
+
+        let height = (size.y - Self::MARGIN) / plots.len() as f32;
+        for (i, plot) in plots.iter().enumerate() {
+            let top = Self::MARGIN + i as f32 * height;
+            let area = (
+                Vec2::new(Self::MARGIN, top + Self::LABEL_HEIGHT),
+                Vec2::new(
+                    size.x - 2. * Self::MARGIN,
+                    height - Self::LABEL_HEIGHT - Self::MARGIN,
+                ),
+            );
+            if area.1.x <= 0. || area.1.y <= 0. {
+                continue;
+            }
+
+            let current = plot.values.last().copied().unwrap_or(0.);
+            let max = plot.values.iter().copied().fold(0., f32::max);
+            let format = plot.format;
+            hud.text(
+                Vec2::new(Self::MARGIN, top),
+                Self::TEXT_SCALE,
+                plot.color,
+                &format!("{}: {} (max {})", plot.name, format(current), format(max)),
+            );
+            hud.rect(area.0, area.1, Vec4::new(1., 1., 1., 0.08));
+            Self::plot(hud, area, &plot.values, max, plot.color);
+        }
+    }
+
+    /// Draws `values` as a line over `area` (corner, size), `max` at the top,
+    /// with one rectangle per step from a sample to the next.
+    fn plot(hud: &mut Hud, area: (Vec2, Vec2), values: &[f32], max: f32, color: Vec4) {
+        if max <= 0. {
+            return;
+        }
+        let (corner, size) = area;
+        let step = size.x / (Self::CAPACITY - 1) as f32;
+        let y = |v: f32| corner.y + size.y * (1. - v / max).clamp(0., 1.);
+
+        let start = Self::CAPACITY - values.len();
+        for (i, pair) in values.windows(2).enumerate() {
+            let (from, to) = (y(pair[0]), y(pair[1]));
+            let x = corner.x + (start + i) as f32 * step;
+            hud.rect(
+                Vec2::new(x, from.min(to) - Self::LINE_WIDTH / 2.),
+                Vec2::new(step.max(1.), (from - to).abs() + Self::LINE_WIDTH),
+                color,
+            );
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use crate::math::Vec3;
+use crate::renderer::{Vertex, VertexSkin};
+
+use super::{Group, Mesh, Topology};
+
+impl Mesh {
+    /// Copy smoothed by `levels` steps of Loop subdivision, each splitting
+    /// every triangle in four, with normals averaged from the new faces.
+    ///
+    /// Vertices at the same position move together, so seams stay closed,
+    /// while their other attributes are interpolated within their group.
+    /// Open borders and non-manifold edges are kept as creases.
+    pub fn subdivide(&self, levels: u32) -> Mesh {
+        let mut mesh = self.clone();
+        for _ in 0..levels {
+            mesh = mesh.subdivide_once();
+        }
+        if levels > 0 {
+            mesh.smooth_normals();
+        }
+        mesh
+    }
+
+    fn subdivide_once(&self) -> Mesh {
+        let topology = Topology::build(self);
+        let welded: HashMap<[u32; 3], u32> = topology
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| (position_key(p), i as u32))
+            .collect();
+
+        // Neighbors of each welded vertex, and those along a crease.
+        let count = topology.positions.len();
+        let mut neighbors: Vec<Vec<u32>> = vec![Vec::new(); count];
+        let mut creases: Vec<Vec<u32>> = vec![Vec::new(); count];
+        for (&(a, b), triangles) in &topology.edges {
+            neighbors[a as usize].push(b);
+            neighbors[b as usize].push(a);
+            if triangles.len() != 2 {
+                creases[a as usize].push(b);
+                creases[b as usize].push(a);
+            }
+        }
+
+        let position = |v: u32| topology.positions[v as usize];
+        let vertex_points: Vec<Vec3> = (0..count)
+            .map(|v| {
+                let p = topology.positions[v];
+                match (&neighbors[v][..], &creases[v][..]) {
+                    ([], _) => p,
+                    (around, []) => {
+                        let n = around.len() as f32;
+                        let beta = if around.len() == 3 {
+                            3. / 16.
+                        } else {
+                            3. / (8. * n)
+                        };
+                        let sum = around.iter().fold(Vec3::ZERO, |sum, &w| sum + position(w));
+                        p * (1. - n * beta) + sum * beta
+                    }
+                    (_, &[a, b]) => p * 0.75 + (position(a) + position(b)) * 0.125,
+                    // Corners where creases meet stay put.
+                    _ => p,
+                }
+            })
+            .collect();
+        let edge_points: HashMap<(u32, u32), Vec3> = topology
+            .edges
+            .iter()
+            .map(|(&(a, b), triangles)| {
+                let mid = (position(a) + position(b)) * 0.5;
+                let point = match triangles[..] {
+                    [first, second] => {
+                        let opposite = |t: usize| {
+                            let corner = topology.corners[t]
+                                .into_iter()
+                                .find(|&c| c != a && c != b)
+                                .unwrap_or(a);
+                            position(corner)
+                        };
+                        mid * 0.75 + (opposite(first) + opposite(second)) * 0.125
+                    }
+                    _ => mid,
+                };
+                ((a, b), point)
+            })
+            .collect();
+
+        let groups = self
+            .groups
+            .iter()
+            .map(|group| {
+                let weld = |v: u32| welded[&position_key(group.vertices[v as usize].position)];
+                let mut subdivided = Group {
+                    vertices: group
+                        .vertices
+                        .iter()
+                        .map(|v| Vertex {
+                            // Vertices outside any triangle aren't welded.
+                            position: welded
+                                .get(&position_key(v.position))
+                                .map_or(v.position, |&w| vertex_points[w as usize]),
+                            ..*v
+                        })
+                        .collect(),
+                    indices: Vec::with_capacity(group.indices.len() * 4),
+                    material: group.material,
+                    object: group.object,
+                    skinning: group.skinning.clone(),
+                };
+
+                // One new vertex per edge of the group, shared by both sides.
+                let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+                let mut midpoint = |subdivided: &mut Group, a: u32, b: u32| {
+                    *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                        let (wa, wb) = (weld(a), weld(b));
+                        let (va, vb) = (&group.vertices[a as usize], &group.vertices[b as usize]);
+                        let position = edge_points
+                            .get(&(wa.min(wb), wa.max(wb)))
+                            .copied()
+                            .unwrap_or((va.position + vb.position) * 0.5);
+                        subdivided.vertices.push(Vertex {
+                            position,
+                            normal: (va.normal + vb.normal) * 0.5,
+                            uv: (va.uv + vb.uv) * 0.5,
+                            occlusion: (va.occlusion + vb.occlusion) * 0.5,
+                            lightmap_uv: (va.lightmap_uv + vb.lightmap_uv) * 0.5,
+                        });
+                        if let (Some(sa), Some(sb)) = (
+                            group.skinning.get(a as usize),
+                            group.skinning.get(b as usize),
+                        ) {
+                            subdivided.skinning.push(blend_skins(sa, sb));
+                        }
+                        subdivided.vertices.len() as u32 - 1
+                    })
+                };
+                for triangle in group.indices.chunks_exact(3) {
+                    let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                    let ab = midpoint(&mut subdivided, a, b);
+                    let bc = midpoint(&mut subdivided, b, c);
+                    let ca = midpoint(&mut subdivided, c, a);
+                    subdivided
+                        .indices
+                        .extend([a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+                }
+                subdivided
+            })
+            .collect();
+
+        Mesh {
+            groups,
+            materials: self.materials.clone(),
+            lightmap: self.lightmap.clone(),
+            objects: self.objects.clone(),
+            lines: self.lines.clone(),
+        }
+    }
+
+    /// Sets every vertex normal to the area-weighted normal of the triangles
+    /// around its position, in any group.
+    fn smooth_normals(&mut self) {
+        let mut normals: HashMap<[u32; 3], Vec3> = HashMap::new();
+        for group in &self.groups {
+            for triangle in group.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| group.vertices[triangle[i] as usize].position);
+                let normal = (b - a).cross(c - a);
+                for p in [a, b, c] {
+                    *normals.entry(position_key(p)).or_insert(Vec3::ZERO) += normal;
+                }
+            }
+        }
+        for group in &mut self.groups {
+            for vertex in &mut group.vertices {
+                if let Some(normal) = normals
+                    .get(&position_key(vertex.position))
+                    .and_then(|n| n.try_normalize())
+                {
+                    vertex.normal = normal;
+                }
+            }
+        }
+    }
+}
+
+fn position_key(p: Vec3) -> [u32; 3] {
+    [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+}
+
+/// Halfway between two skins: the four heaviest joints of both, with their
+/// weights averaged.
+fn blend_skins(a: &VertexSkin, b: &VertexSkin) -> VertexSkin {
+    let mut weights: Vec<(u32, f32)> = Vec::with_capacity(8);
+    for skin in [a, b] {
+        for (&joint, &weight) in skin.joints.iter().zip(&skin.weights) {
+            match weights.iter_mut().find(|(j, _)| *j == joint) {
+                Some((_, total)) => *total += weight * 0.5,
+                None => weights.push((joint, weight * 0.5)),
+            }
+        }
+    }
+    weights.sort_by(|x, y| y.1.total_cmp(&x.1));
+    weights.truncate(4);
+
+    let total: f32 = weights.iter().map(|(_, w)| w).sum();
+    let mut skin = VertexSkin::default();
+    if total > 0. {
+        for (i, (joint, weight)) in weights.into_iter().enumerate() {
+            skin.joints[i] = joint;
+            skin.weights[i] = weight / total;
+        }
+    }
+    skin
+}
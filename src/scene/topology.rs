@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::math::Vec3;
+
+use super::{Mesh, TriangleRef};
+
+/// How a mesh's triangles connect through their edges, across groups, with
+/// vertices at the same position welded so seams between UV islands or
+/// normal splits don't break the surface apart.
+pub struct Topology {
+    /// Every non-degenerate triangle of the mesh.
+    pub triangles: Vec<TriangleRef>,
+    /// Welded corners of each triangle, in winding order.
+    pub corners: Vec<[u32; 3]>,
+    /// Position of each welded corner.
+    pub positions: Vec<Vec3>,
+    /// Triangles along each undirected edge, keyed by its welded corners,
+    /// lowest first.
+    pub edges: HashMap<(u32, u32), Vec<usize>>,
+}
+
+impl Topology {
+    pub fn build(mesh: &Mesh) -> Self {
+        let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+        let mut topology = Self {
+            triangles: Vec::new(),
+            corners: Vec::new(),
+            positions: Vec::new(),
+            edges: HashMap::new(),
+        };
+
+        for (group_index, group) in mesh.groups.iter().enumerate() {
+            for (triangle, indices) in group.indices.chunks_exact(3).enumerate() {
+                let corners = [0, 1, 2].map(|i| {
+                    let p = group.vertices[indices[i] as usize].position;
+                    *welded
+                        .entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+                        .or_insert_with(|| {
+                            topology.positions.push(p);
+                            topology.positions.len() as u32 - 1
+                        })
+                });
+                let [a, b, c] = corners;
+                if a == b || b == c || c == a {
+                    continue;
+                }
+
+                let index = topology.corners.len();
+                topology.triangles.push(TriangleRef {
+                    group: group_index as u32,
+                    triangle: triangle as u32,
+                });
+                topology.corners.push(corners);
+                for (from, to) in [(a, b), (b, c), (c, a)] {
+                    topology
+                        .edges
+                        .entry((from.min(to), from.max(to)))
+                        .or_default()
+                        .push(index);
+                }
+            }
+        }
+        topology
+    }
+
+    /// Connected component of each triangle, numbered from 0 in the order of
+    /// their first triangle. Triangles sharing any edge, even a non-manifold
+    /// one, are connected.
+    pub fn components(&self) -> Vec<usize> {
+        // Union-find over the triangles, joined along every shared edge.
+        let mut parents: Vec<usize> = (0..self.corners.len()).collect();
+        for triangles in self.edges.values() {
+            let first = root(&mut parents, triangles[0]);
+            for &other in &triangles[1..] {
+                let other = root(&mut parents, other);
+                parents[other] = first;
+            }
+        }
+
+        let mut labels: HashMap<usize, usize> = HashMap::new();
+        (0..parents.len())
+            .map(|i| {
+                let next = labels.len();
+                *labels.entry(root(&mut parents, i)).or_insert(next)
+            })
+            .collect()
+    }
+
+    /// Whether `triangle` goes along the edge from `from` to `to`, rather
+    /// than the other way.
+    pub fn has_directed_edge(&self, triangle: usize, from: u32, to: u32) -> bool {
+        let [a, b, c] = self.corners[triangle];
+        [(a, b), (b, c), (c, a)].contains(&(from, to))
+    }
+
+    /// Triangles sharing a manifold edge with `triangle`, one other triangle
+    /// along it, and whether their windings agree: they go along the shared
+    /// edge in opposite directions.
+    pub fn neighbors(&self, triangle: usize) -> Vec<(usize, bool)> {
+        let [a, b, c] = self.corners[triangle];
+        [(a, b), (b, c), (c, a)]
+            .into_iter()
+            .filter_map(
+                |(from, to)| match self.edges[&(from.min(to), from.max(to))][..] {
+                    [first, second] => {
+                        let other = if first == triangle { second } else { first };
+                        Some((other, !self.has_directed_edge(other, from, to)))
+                    }
+                    _ => None,
+                },
+            )
+            .collect()
+    }
+}
+
+/// Where a mesh fails to be a closed surface that could be printed.
+#[derive(Debug, Clone, Default)]
+pub struct TopologyReport {
+    /// Edges of a single triangle, along holes and open borders.
+    pub boundary_edges: Vec<[Vec3; 2]>,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: Vec<[Vec3; 2]>,
+    /// Groups of triangles connected through their edges.
+    pub components: usize,
+}
+
+impl TopologyReport {
+    /// Whether every edge joins exactly two triangles.
+    pub fn is_watertight(&self) -> bool {
+        self.boundary_edges.is_empty() && self.non_manifold_edges.is_empty()
+    }
+}
+
+impl fmt::Display for TopologyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} boundary edges, {} non-manifold edges, {} components{}",
+            self.boundary_edges.len(),
+            self.non_manifold_edges.len(),
+            self.components,
+            if self.is_watertight() {
+                " (watertight)"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+impl Mesh {
+    /// Finds the open and non-manifold edges of the mesh, in its local space,
+    /// and counts its connected components.
+    pub fn analyze_topology(&self) -> TopologyReport {
+        let topology = Topology::build(self);
+        let mut report = TopologyReport {
+            // Components are numbered in order.
+            components: topology
+                .components()
+                .into_iter()
+                .max()
+                .map_or(0, |last| last + 1),
+            ..TopologyReport::default()
+        };
+
+        for (&(a, b), triangles) in &topology.edges {
+            let edge = [a, b].map(|corner| topology.positions[corner as usize]);
+            match triangles.len() {
+                1 => report.boundary_edges.push(edge),
+                2 => {}
+                _ => report.non_manifold_edges.push(edge),
+            }
+        }
+        report
+    }
+
+    /// Corner positions of `triangle`, in winding order.
+    pub fn triangle_positions(&self, triangle: TriangleRef) -> [Vec3; 3] {
+        let group = &self.groups[triangle.group as usize];
+        let first = triangle.triangle as usize * 3;
+        [0, 1, 2].map(|i| group.vertices[group.indices[first + i] as usize].position)
+    }
+}
+
+/// Representative of the union-find set containing `i`, halving the path.
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
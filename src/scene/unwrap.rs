@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use crate::math::{Vec2, Vec3};
+
+use super::{Mesh, Topology};
+
+/// Length of a component's summed normal, relative to its area, above which
+/// it is about flat and projected whole on the plane of that normal.
+const PLANAR_THRESHOLD: f32 = 0.9;
+
+/// Gap left around each chart, as a share of the atlas' edge, so texture
+/// filtering and painting brushes don't bleed between charts.
+const CHART_PADDING: f32 = 0.005;
+
+/// What `Mesh::unwrap_uvs` made.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UvUnwrap {
+    pub components: usize,
+    /// Components projected whole on one plane, the others on a box.
+    pub planar_components: usize,
+    pub charts: usize,
+}
+
+/// Triangles of one component projected together on a plane.
+struct Chart {
+    /// Axes of the plane, in the winding of the triangles facing its normal.
+    u: Vec3,
+    v: Vec3,
+    min: Vec2,
+    max: Vec2,
+    /// Bottom left corner in the atlas, before scaling it to the unit square.
+    offset: Vec2,
+}
+
+impl Chart {
+    fn new(normal: Vec3) -> Self {
+        let helper = if normal.y.abs() > 0.99 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let u = helper.cross(normal).normalize();
+        Self {
+            u,
+            v: normal.cross(u),
+            min: Vec2::new(f32::MAX, f32::MAX),
+            max: Vec2::new(f32::MIN, f32::MIN),
+            offset: Vec2::ZERO,
+        }
+    }
+
+    fn project(&self, position: Vec3) -> Vec2 {
+        Vec2::new(position.dot(self.u), position.dot(self.v))
+    }
+
+    fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+}
+
+impl Mesh {
+    /// Replaces the texture coordinates with an automatic unwrap into a
+    /// single atlas, for painting tools to texture scans that come without
+    /// any. Each connected component about flat is projected on the plane
+    /// of its average normal; the others are box projected, each triangle on
+    /// the side of the box its normal faces most. The triangles a component
+    /// projects on the same plane make a chart, and the charts, keeping
+    /// their sizes relative to each other, are packed in rows into the unit
+    /// square.
+    ///
+    /// Vertices are split where charts meet. Charts overlap themselves where
+    /// the surface folds back over its plane, as projections do; degenerate
+    /// triangles get the atlas' corner.
+    pub fn unwrap_uvs(&mut self) -> UvUnwrap {
+        let topology = Topology::build(self);
+        let components = topology.components();
+        let count = components.iter().max().map_or(0, |last| last + 1);
+
+        // Unnormalized cross products weigh each normal by its triangle's
+        // area.
+        let normals: Vec<Vec3> = topology
+            .triangles
+            .iter()
+            .map(|&triangle| {
+                let [a, b, c] = self.triangle_positions(triangle);
+                (b - a).cross(c - a)
+            })
+            .collect();
+        let mut sums = vec![(Vec3::ZERO, 0.); count];
+        for (normal, &component) in normals.iter().zip(&components) {
+            sums[component].0 += *normal;
+            sums[component].1 += normal.length();
+        }
+        let planes: Vec<Option<Vec3>> = sums
+            .iter()
+            .map(|&(sum, area)| {
+                (sum.length() > PLANAR_THRESHOLD * area)
+                    .then(|| sum.try_normalize())
+                    .flatten()
+            })
+            .collect();
+
+        // Charts keyed by component and plane: 0 to 5 for the box's sides,
+        // 6 for a component's own plane.
+        let mut charts: Vec<Chart> = Vec::new();
+        let mut keys: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut placed: Vec<Vec<Option<usize>>> = self
+            .groups
+            .iter()
+            .map(|group| vec![None; group.indices.len() / 3])
+            .collect();
+        for (index, (&triangle, &component)) in
+            topology.triangles.iter().zip(&components).enumerate()
+        {
+            let (side, normal) = match planes[component] {
+                Some(plane) => (6, plane),
+                None => box_side(normals[index]),
+            };
+            let chart = *keys.entry((component, side)).or_insert_with(|| {
+                charts.push(Chart::new(normal));
+                charts.len() - 1
+            });
+            for position in self.triangle_positions(triangle) {
+                let chart = &mut charts[chart];
+                let uv = chart.project(position);
+                chart.min = Vec2::new(chart.min.x.min(uv.x), chart.min.y.min(uv.y));
+                chart.max = Vec2::new(chart.max.x.max(uv.x), chart.max.y.max(uv.y));
+            }
+            placed[triangle.group as usize][triangle.triangle as usize] = Some(chart);
+        }
+
+        let scale = pack(&mut charts);
+
+        for (group, placed) in self.groups.iter_mut().zip(&placed) {
+            let mut vertices = Vec::with_capacity(group.vertices.len());
+            let mut skinning = Vec::new();
+            let mut split: HashMap<(u32, Option<usize>), u32> = HashMap::new();
+            for (triangle, &chart) in group.indices.chunks_exact_mut(3).zip(placed) {
+                for index in triangle {
+                    let original = *index;
+                    *index = *split.entry((original, chart)).or_insert_with(|| {
+                        let mut vertex = group.vertices[original as usize];
+                        vertex.uv = match chart {
+                            Some(chart) => {
+                                let chart = &charts[chart];
+                                (chart.project(vertex.position) - chart.min + chart.offset) * scale
+                            }
+                            None => Vec2::ZERO,
+                        };
+                        vertices.push(vertex);
+                        if let Some(&skin) = group.skinning.get(original as usize) {
+                            skinning.push(skin);
+                        }
+                        vertices.len() as u32 - 1
+                    });
+                }
+            }
+            group.vertices = vertices;
+            group.skinning = skinning;
+        }
+
+        UvUnwrap {
+            components: count,
+            planar_components: planes.iter().filter(|plane| plane.is_some()).count(),
+            charts: charts.len(),
+        }
+    }
+}
+
+/// Side of a box `normal` faces most, from 0 to 5 for +X, -X, +Y, -Y, +Z and
+/// -Z, with the side's outward axis.
+fn box_side(normal: Vec3) -> (usize, Vec3) {
+    let axis = (0..3)
+        .max_by(|&a, &b| normal[a].abs().total_cmp(&normal[b].abs()))
+        .unwrap_or(0);
+    let direction = [Vec3::X, Vec3::Y, Vec3::Z][axis];
+    if normal[axis] >= 0. {
+        (axis * 2, direction)
+    } else {
+        (axis * 2 + 1, -direction)
+    }
+}
+
+/// Places the charts in rows, tallest first, each row about as wide as the
+/// square root of their summed areas, and returns the scale fitting the whole
+/// into the unit square.
+fn pack(charts: &mut [Chart]) -> f32 {
+    let area: f32 = charts
+        .iter()
+        .map(|chart| chart.size().x * chart.size().y)
+        .sum();
+    let widest = charts.iter().map(|chart| chart.size().x).fold(0., f32::max);
+    let row_width = area.sqrt().max(widest);
+    let padding = row_width * CHART_PADDING;
+
+    let mut order: Vec<usize> = (0..charts.len()).collect();
+    order.sort_by(|&a, &b| charts[b].size().y.total_cmp(&charts[a].size().y));
+
+    let (mut x, mut y, mut row_height) = (0., 0., 0.);
+    let mut extent: f32 = 0.;
+    for index in order {
+        let size = charts[index].size() + Vec2::new(padding, padding) * 2.;
+        if x > 0. && x + size.x > row_width {
+            x = 0.;
+            y += row_height;
+            row_height = 0.;
+        }
+        charts[index].offset = Vec2::new(x + padding, y + padding);
+        x += size.x;
+        row_height = f32::max(row_height, size.y);
+        extent = extent.max(x).max(y + row_height);
+    }
+
+    if extent > 0. {
+        1. / extent
+    } else {
+        0.
+    }
+}
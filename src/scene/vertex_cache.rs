@@ -0,0 +1,229 @@
+use super::Mesh;
+
+/// Entries of the simulated post-transform cache, about what GPUs keep.
+const CACHE_SIZE: usize = 16;
+/// Score of the vertices of the triangle just drawn, a bit under the next
+/// ones so that strips don't turn back on themselves.
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const VALENCE_BOOST_SCALE: f32 = 2.;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+impl Mesh {
+    /// Reorders the triangles of each group so consecutive ones share
+    /// vertices, following Tom Forsyth's linear-speed vertex cache
+    /// optimisation, then the vertices in the order they are first used.
+    /// The GPU then transforms each vertex about once, and reads the vertex
+    /// buffer front to back.
+    pub fn optimize_vertex_cache(&mut self) {
+        for group in &mut self.groups {
+            let count = group.vertices.len();
+            optimize_triangles(&mut group.indices, count);
+
+            // New index of each vertex, by first use.
+            let mut remap = vec![u32::MAX; count];
+            let mut order = Vec::with_capacity(count);
+            for index in &mut group.indices {
+                let vertex = *index as usize;
+                if remap[vertex] == u32::MAX {
+                    remap[vertex] = order.len() as u32;
+                    order.push(vertex);
+                }
+                *index = remap[vertex];
+            }
+            // Vertices no triangle uses stay, at the end.
+            order.extend((0..count).filter(|&vertex| remap[vertex] == u32::MAX));
+
+            group.vertices = order.iter().map(|&v| group.vertices[v]).collect();
+            if group.skinning.len() == count {
+                group.skinning = order.iter().map(|&v| group.skinning[v]).collect();
+            }
+        }
+    }
+}
+
+/// Vertex scores by position in the cache and by triangles left, computed
+/// once per group rather than for every vertex at every step.
+struct Scores {
+    cache: [f32; CACHE_SIZE],
+    valence: [f32; Scores::VALENCES],
+}
+
+impl Scores {
+    /// Triangles left up to which the valence boost is tabulated.
+    const VALENCES: usize = 64;
+
+    fn new() -> Self {
+        let mut cache = [0.; CACHE_SIZE];
+        for (position, score) in cache.iter_mut().enumerate() {
+            *score = if position < 3 {
+                LAST_TRIANGLE_SCORE
+            } else {
+                let scale = 1. / (CACHE_SIZE - 3) as f32;
+                (1. - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+            };
+        }
+        let mut valence = [0.; Self::VALENCES];
+        for (remaining, score) in valence.iter_mut().enumerate() {
+            *score = Self::valence_boost(remaining as u32);
+        }
+        Self { cache, valence }
+    }
+
+    /// Favors vertices with few triangles left, so none is left alone to be
+    /// fetched again later.
+    fn valence_boost(remaining: u32) -> f32 {
+        VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+    }
+
+    /// Score of a vertex at `position` in the cache, if any, used by
+    /// `remaining` triangles not yet drawn.
+    fn vertex(&self, position: Option<usize>, remaining: u32) -> f32 {
+        if remaining == 0 {
+            return -1.;
+        }
+        let cache = position.map_or(0., |position| self.cache[position]);
+        let valence = match self.valence.get(remaining as usize) {
+            Some(&valence) => valence,
+            None => Self::valence_boost(remaining),
+        };
+        cache + valence
+    }
+}
+
+/// Reorders the triangles of `indices`, into `vertex_count` vertices, for
+/// the vertex cache.
+fn optimize_triangles(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count < 2 {
+        return;
+    }
+
+    // Triangles of each vertex, the ones left to draw first in its range.
+    let mut offsets = vec![0u32; vertex_count + 1];
+    for &index in indices.iter() {
+        offsets[index as usize + 1] += 1;
+    }
+    for vertex in 0..vertex_count {
+        offsets[vertex + 1] += offsets[vertex];
+    }
+    let mut remaining: Vec<u32> = (0..vertex_count)
+        .map(|vertex| offsets[vertex + 1] - offsets[vertex])
+        .collect();
+    let mut adjacency = vec![0u32; indices.len()];
+    let mut filled = vec![0u32; vertex_count];
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &vertex in corners {
+            let vertex = vertex as usize;
+            adjacency[(offsets[vertex] + filled[vertex]) as usize] = triangle as u32;
+            filled[vertex] += 1;
+        }
+    }
+
+    let table = Scores::new();
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut scores: Vec<f32> = remaining.iter().map(|&r| table.vertex(None, r)).collect();
+    let mut triangle_scores: Vec<f32> = indices
+        .chunks_exact(3)
+        .map(|t| t.iter().map(|&v| scores[v as usize]).sum())
+        .collect();
+    let mut drawn = vec![false; triangle_count];
+
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut next_cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut output: Vec<u32> = Vec::with_capacity(indices.len());
+    let mut best = None;
+    // Where to look for an undrawn triangle when none in the cache is left.
+    let mut cursor = 0;
+
+    while output.len() < indices.len() {
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                while drawn[cursor] {
+                    cursor += 1;
+                }
+                cursor
+            }
+        };
+        drawn[triangle] = true;
+        let corners = [
+            indices[3 * triangle],
+            indices[3 * triangle + 1],
+            indices[3 * triangle + 2],
+        ];
+        output.extend_from_slice(&corners);
+
+        for &vertex in &corners {
+            let vertex = vertex as usize;
+            let start = offsets[vertex] as usize;
+            let live = &mut adjacency[start..start + remaining[vertex] as usize];
+            if let Some(slot) = live.iter().position(|&t| t as usize == triangle) {
+                let last = live.len() - 1;
+                live.swap(slot, last);
+                remaining[vertex] -= 1;
+            }
+        }
+
+        // The triangle's vertices move to the front, pushing the others
+        // back and out past the cache's end.
+        next_cache.clear();
+        for vertex in corners {
+            if !next_cache.contains(&vertex) {
+                next_cache.push(vertex);
+            }
+        }
+        next_cache.extend(cache.iter().filter(|v| !corners.contains(v)));
+        for (position, &vertex) in next_cache.iter().enumerate() {
+            cache_position[vertex as usize] = (position < CACHE_SIZE).then_some(position);
+        }
+        std::mem::swap(&mut cache, &mut next_cache);
+
+        // Rescores the vertices whose position or triangles changed, then
+        // picks the best triangle among those still cached.
+        for &vertex in &cache {
+            let vertex = vertex as usize;
+            let score = table.vertex(cache_position[vertex], remaining[vertex]);
+            let delta = score - scores[vertex];
+            scores[vertex] = score;
+            let start = offsets[vertex] as usize;
+            for &t in &adjacency[start..start + remaining[vertex] as usize] {
+                triangle_scores[t as usize] += delta;
+            }
+        }
+        cache.truncate(CACHE_SIZE);
+        best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for &vertex in &cache {
+            let start = offsets[vertex as usize] as usize;
+            for &t in &adjacency[start..start + remaining[vertex as usize] as usize] {
+                if triangle_scores[t as usize] > best_score {
+                    best_score = triangle_scores[t as usize];
+                    best = Some(t as usize);
+                }
+            }
+        }
+    }
+
+    // Files exported by tools that already ordered them can lose.
+    if cache_misses(&output, vertex_count) < cache_misses(indices, vertex_count) {
+        indices.copy_from_slice(&output);
+    }
+}
+
+/// Vertices transformed to draw `indices` through a first-in first-out
+/// cache of `CACHE_SIZE` entries.
+fn cache_misses(indices: &[u32], vertex_count: usize) -> usize {
+    // Misses counted when each vertex last entered the cache, which it left
+    // `CACHE_SIZE` misses later.
+    let mut entered = vec![None; vertex_count];
+    let mut misses = 0;
+    for &index in indices {
+        let cached = entered[index as usize].is_some_and(|at| misses - at < CACHE_SIZE);
+        if !cached {
+            entered[index as usize] = Some(misses);
+            misses += 1;
+        }
+    }
+    misses
+}
@@ -0,0 +1,322 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::math::{Aabb, Mat4, Vec3};
+use crate::renderer::VoxelInstance;
+
+use super::Mesh;
+
+/// Color of the voxels of groups without a material, like their default
+/// material.
+const DEFAULT_VOXEL_COLOR: Vec3 = Vec3 {
+    x: 0.7,
+    y: 0.8,
+    z: 0.6,
+};
+/// Color of the voxels inside a solid grid, seen through cuts only.
+const INTERIOR_VOXEL_COLOR: Vec3 = Vec3 {
+    x: 0.5,
+    y: 0.5,
+    z: 0.5,
+};
+
+/// Which voxels of a grid a mesh fills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoxelFill {
+    /// Voxels crossed by a triangle.
+    #[default]
+    Surface,
+    /// Voxels crossed by a triangle or enclosed by the surface.
+    Solid,
+}
+
+impl fmt::Display for VoxelFill {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxelFill::Surface => write!(f, "surface"),
+            VoxelFill::Solid => write!(f, "solid"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Voxel {
+    Empty,
+    /// Crossed by a triangle using the given entry of `VoxelGrid::colors`.
+    Surface(u16),
+    Interior,
+}
+
+/// Cubic cells of a mesh's bounds, in the mesh's local space.
+#[derive(Debug, Clone)]
+pub struct VoxelGrid {
+    /// Corner of the first cell.
+    pub origin: Vec3,
+    /// Edge of a cell.
+    pub voxel_size: f32,
+    /// Cells along x, y and z.
+    pub dims: [usize; 3],
+    /// Cells, x varying fastest, then y.
+    cells: Vec<Voxel>,
+    /// Diffuse color of each material, then of groups without one.
+    colors: Vec<Vec3>,
+}
+
+impl Mesh {
+    /// Splits the bounds into cubes, `resolution` along their longest side,
+    /// and fills those crossed by a triangle, and with `VoxelFill::Solid`
+    /// those enclosed by them. Holes in the surface let the outside in.
+    pub fn voxelize(&self, resolution: u32, fill: VoxelFill) -> VoxelGrid {
+        let bounds = self.bounds();
+        let longest = if bounds.is_empty() {
+            0.
+        } else {
+            let size = bounds.size();
+            size.x.max(size.y).max(size.z)
+        };
+        let mut colors: Vec<Vec3> = self.materials.iter().map(|m| m.kd).collect();
+        colors.push(DEFAULT_VOXEL_COLOR);
+        if longest <= 0. || resolution == 0 {
+            return VoxelGrid {
+                origin: Vec3::ZERO,
+                voxel_size: 1.,
+                dims: [0; 3],
+                cells: Vec::new(),
+                colors,
+            };
+        }
+
+        let voxel_size = longest / resolution as f32;
+        let size = bounds.size();
+        let dims = [size.x, size.y, size.z].map(|s| ((s / voxel_size).ceil() as usize).max(1));
+        let mut grid = VoxelGrid {
+            origin: bounds.min,
+            voxel_size,
+            dims,
+            cells: vec![Voxel::Empty; dims[0] * dims[1] * dims[2]],
+            colors,
+        };
+
+        for group in &self.groups {
+            let color = group
+                .material
+                .filter(|&m| m < self.materials.len())
+                .unwrap_or(self.materials.len()) as u16;
+            for triangle in group.indices.chunks_exact(3) {
+                let corners = [0, 1, 2].map(|i| {
+                    (group.vertices[triangle[i] as usize].position - grid.origin) / voxel_size
+                });
+                grid.rasterize(corners, color);
+            }
+        }
+        if fill == VoxelFill::Solid {
+            grid.fill_interior();
+        }
+        grid
+    }
+}
+
+impl VoxelGrid {
+    /// Filled cells.
+    pub fn count(&self) -> usize {
+        self.cells.iter().filter(|&&v| v != Voxel::Empty).count()
+    }
+
+    /// Maps cell coordinates to the mesh's local space.
+    pub fn transform(&self) -> Mat4 {
+        Mat4::identity()
+            .translate(self.origin)
+            .scale(Vec3::splat(self.voxel_size))
+    }
+
+    /// Filled cells next to an empty one or to the side of the grid, the only
+    /// ones that can be seen, in cell coordinates.
+    pub fn visible_instances(&self) -> Vec<VoxelInstance> {
+        let [nx, ny, nz] = self.dims;
+        let mut instances = Vec::new();
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let color = match self.cells[self.index(x, y, z)] {
+                        Voxel::Empty => continue,
+                        Voxel::Surface(color) => self.colors[color as usize],
+                        Voxel::Interior => INTERIOR_VOXEL_COLOR,
+                    };
+                    let neighbors = self.neighbors(x, y, z);
+                    if neighbors.len() < 6
+                        || neighbors.iter().any(|&i| self.cells[i] == Voxel::Empty)
+                    {
+                        instances.push(VoxelInstance {
+                            cell: Vec3::new(x as f32, y as f32, z as f32),
+                            color,
+                        });
+                    }
+                }
+            }
+        }
+        instances
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + self.dims[0] * (y + self.dims[1] * z)
+    }
+
+    /// Cells sharing a face with the given one.
+    fn neighbors(&self, x: usize, y: usize, z: usize) -> Vec<usize> {
+        let [nx, ny, nz] = self.dims;
+        let mut neighbors = Vec::with_capacity(6);
+        if x > 0 {
+            neighbors.push(self.index(x - 1, y, z));
+        }
+        if x + 1 < nx {
+            neighbors.push(self.index(x + 1, y, z));
+        }
+        if y > 0 {
+            neighbors.push(self.index(x, y - 1, z));
+        }
+        if y + 1 < ny {
+            neighbors.push(self.index(x, y + 1, z));
+        }
+        if z > 0 {
+            neighbors.push(self.index(x, y, z - 1));
+        }
+        if z + 1 < nz {
+            neighbors.push(self.index(x, y, z + 1));
+        }
+        neighbors
+    }
+
+    /// Marks the cells crossed by a triangle given in cell coordinates.
+    fn rasterize(&mut self, corners: [Vec3; 3], color: u16) {
+        let bounds = Aabb::from_points(corners);
+        let range = |min: f32, max: f32, count: usize| {
+            let clamp = |v: f32| (v.floor().max(0.) as usize).min(count - 1);
+            clamp(min)..=clamp(max)
+        };
+        for z in range(bounds.min.z, bounds.max.z, self.dims[2]) {
+            for y in range(bounds.min.y, bounds.max.y, self.dims[1]) {
+                for x in range(bounds.min.x, bounds.max.x, self.dims[0]) {
+                    let center = Vec3::new(x as f32, y as f32, z as f32) + Vec3::splat(0.5);
+                    let index = self.index(x, y, z);
+                    if self.cells[index] == Voxel::Empty && triangle_overlaps_cell(center, corners)
+                    {
+                        self.cells[index] = Voxel::Surface(color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fills the empty cells the outside can't reach, flooding it in from
+    /// the empty cells on the sides of the grid.
+    fn fill_interior(&mut self) {
+        let [nx, ny, nz] = self.dims;
+        let mut outside = vec![false; self.cells.len()];
+        let mut queue: VecDeque<(usize, usize, usize)> = VecDeque::new();
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let side =
+                        x == 0 || y == 0 || z == 0 || x + 1 == nx || y + 1 == ny || z + 1 == nz;
+                    let index = self.index(x, y, z);
+                    if side && self.cells[index] == Voxel::Empty {
+                        outside[index] = true;
+                        queue.push_back((x, y, z));
+                    }
+                }
+            }
+        }
+        while let Some((x, y, z)) = queue.pop_front() {
+            let steps = [
+                (x.wrapping_sub(1), y, z),
+                (x + 1, y, z),
+                (x, y.wrapping_sub(1), z),
+                (x, y + 1, z),
+                (x, y, z.wrapping_sub(1)),
+                (x, y, z + 1),
+            ];
+            for (x, y, z) in steps {
+                if x >= nx || y >= ny || z >= nz {
+                    continue;
+                }
+                let index = self.index(x, y, z);
+                if !outside[index] && self.cells[index] == Voxel::Empty {
+                    outside[index] = true;
+                    queue.push_back((x, y, z));
+                }
+            }
+        }
+        for (cell, outside) in self.cells.iter_mut().zip(outside) {
+            if *cell == Voxel::Empty && !outside {
+                *cell = Voxel::Interior;
+            }
+        }
+    }
+}
+
+/// Whether a triangle crosses the unit cube around `center`, by the
+/// separating axis test over the cube's faces, the triangle's plane and the
+/// cross products of their edges.
+fn triangle_overlaps_cell(center: Vec3, corners: [Vec3; 3]) -> bool {
+    const HALF: f32 = 0.5;
+    let [a, b, c] = corners.map(|p| p - center);
+    let separates = |axis: Vec3| {
+        let (pa, pb, pc) = (a.dot(axis), b.dot(axis), c.dot(axis));
+        let radius = HALF * (axis.x.abs() + axis.y.abs() + axis.z.abs());
+        pa.min(pb).min(pc) > radius || pa.max(pb).max(pc) < -radius
+    };
+
+    let edges = [b - a, c - b, a - c];
+    let cube_axes = [Vec3::X, Vec3::Y, Vec3::Z];
+    if cube_axes.into_iter().any(separates) {
+        return false;
+    }
+    let normal = edges[0].cross(edges[1]);
+    if normal.dot(a).abs() > HALF * (normal.x.abs() + normal.y.abs() + normal.z.abs()) {
+        return false;
+    }
+    !edges
+        .iter()
+        .flat_map(|&edge| cube_axes.map(|axis| axis.cross(edge)))
+        .any(separates)
+}
+
+/// Voxel cubes drawn in place of the objects, rebuilt every frame.
+#[derive(Debug, Default)]
+pub struct VoxelPreview {
+    pub instances: Vec<VoxelInstance>,
+    pub batches: Vec<VoxelBatch>,
+}
+
+/// Consecutive `VoxelPreview::instances` sharing a transform.
+#[derive(Debug, Clone, Copy)]
+pub struct VoxelBatch {
+    /// Maps cell coordinates to world space.
+    pub transform: Mat4,
+    pub first: u32,
+    pub count: u32,
+}
+
+impl VoxelPreview {
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.batches.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// Adds cubes in cell coordinates, placed by `transform`.
+    pub fn add(&mut self, instances: &[VoxelInstance], transform: Mat4) {
+        if instances.is_empty() {
+            return;
+        }
+        self.batches.push(VoxelBatch {
+            transform,
+            first: self.instances.len() as u32,
+            count: instances.len() as u32,
+        });
+        self.instances.extend_from_slice(instances);
+    }
+}
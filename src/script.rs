@@ -0,0 +1,270 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use rhai::{Array, Dynamic, Engine as Rhai, EvalAltResult, INT};
+
+use crate::app::App;
+use crate::gallery::Prepare;
+use crate::math::Vec3;
+use crate::parser::ObjFileParser;
+use crate::renderer::{Matcap, ShadingMode};
+use crate::scene::{AutoRotation, Mesh, Object, ObjectHandle};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// What the script's functions act on.
+struct ScriptState {
+    app: App,
+    prepare: Prepare,
+    /// Objects added by the script, with the rotation `render` spins them
+    /// from.
+    objects: Vec<(ObjectHandle, Vec3)>,
+}
+
+impl ScriptState {
+    fn add(&mut self, mesh: &Mesh) -> ScriptResult<INT> {
+        let mesh = self.app.add_mesh(mesh)?;
+        let object = self.app.add_object(Object::new(mesh));
+        self.objects.push((object, Vec3::ZERO));
+        Ok(object as INT)
+    }
+
+    fn load(&self, path: &str) -> ScriptResult<Mesh> {
+        let mut mesh = ObjFileParser::parse(path)
+            .map_err(|e| format!("Failed to parse mesh '{}': {}", path, e))?;
+        (self.prepare)(&mut mesh);
+        Ok(mesh)
+    }
+
+    fn object(&mut self, object: INT) -> ScriptResult<&mut (ObjectHandle, Vec3)> {
+        self.objects
+            .iter_mut()
+            .find(|(handle, _)| *handle as INT == object)
+            .ok_or_else(|| format!("No object {}", object).into())
+    }
+
+    /// Draws frames for `seconds`, spinning the objects. Fails once the
+    /// window is closed, ending the script.
+    fn render(&mut self, seconds: f32) -> ScriptResult<()> {
+        let end = Instant::now() + Duration::from_secs_f32(seconds.max(0.));
+        loop {
+            if !self.app.handle_events()? {
+                return Err("Window closed".into());
+            }
+            let rotation = self.app.auto_rotation();
+            for &(object, base) in &self.objects {
+                let r = rotation.orient(base, 1.);
+                self.app.get_object(object).set_rotation(r.x, r.y, r.z);
+            }
+            self.app.update();
+            self.app.draw();
+            if Instant::now() >= end {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A script number, integer or not.
+fn number(value: &Dynamic) -> ScriptResult<f32> {
+    value
+        .as_float()
+        .or_else(|_| value.as_int().map(|value| value as f64))
+        .map(|value| value as f32)
+        .map_err(|_| format!("Expected a number, got {}", value.type_name()).into())
+}
+
+/// A script array of 3 numbers.
+fn vec3(array: &Array) -> ScriptResult<Vec3> {
+    match array.as_slice() {
+        [x, y, z] => Ok(Vec3::new(number(x)?, number(y)?, number(z)?)),
+        _ => Err(format!("Expected [x, y, z], got {} items", array.len()).into()),
+    }
+}
+
+/// Runs the Rhai script at `path` on `app`. Its objects keep still until
+/// it sets a spin with `set_spin`. `prepare` runs on every model it loads.
+pub fn run_script(mut app: App, path: &str, prepare: Prepare) -> Result<(), String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read script '{}': {}", path, e))?;
+    let rotation = app.auto_rotation();
+    app.set_auto_rotation(AutoRotation {
+        playing: false,
+        ..rotation
+    });
+    let state = Rc::new(RefCell::new(ScriptState {
+        app,
+        prepare,
+        objects: Vec::new(),
+    }));
+
+    let mut rhai = Rhai::new();
+    let s = Rc::clone(&state);
+    rhai.register_fn("load_model", move |path: &str| -> ScriptResult<INT> {
+        let mut state = s.borrow_mut();
+        let mesh = state.load(path)?;
+        match state.objects.first().map(|&(object, _)| object) {
+            Some(object) => {
+                let mesh = state.app.add_mesh(&mesh)?;
+                state.app.get_object(object).set_mesh(mesh);
+                Ok(object as INT)
+            }
+            None => state.add(&mesh),
+        }
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn("add_model", move |path: &str| -> ScriptResult<INT> {
+        let mut state = s.borrow_mut();
+        let mesh = state.load(path)?;
+        state.add(&mesh)
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn("add_primitive", move |name: &str| -> ScriptResult<INT> {
+        let mesh = Mesh::primitive(name, 100.)?;
+        s.borrow_mut().add(&mesh)
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn(
+        "set_position",
+        move |object: INT, position: Array| -> ScriptResult<()> {
+            let mut state = s.borrow_mut();
+            let (object, _) = *state.object(object)?;
+            state.app.get_object(object).set_position(vec3(&position)?);
+            Ok(())
+        },
+    );
+    let s = Rc::clone(&state);
+    rhai.register_fn(
+        "set_rotation",
+        move |object: INT, degrees: Array| -> ScriptResult<()> {
+            let mut state = s.borrow_mut();
+            let rotation = vec3(&degrees)? * std::f32::consts::PI / 180.;
+            let (object, base) = state.object(object)?;
+            *base = rotation;
+            let object = *object;
+            let r = state.app.auto_rotation().orient(rotation, 1.);
+            state.app.get_object(object).set_rotation(r.x, r.y, r.z);
+            Ok(())
+        },
+    );
+    let s = Rc::clone(&state);
+    rhai.register_fn(
+        "set_scale",
+        move |object: INT, scale: Dynamic| -> ScriptResult<()> {
+            let mut state = s.borrow_mut();
+            let (object, _) = *state.object(object)?;
+            state
+                .app
+                .get_object(object)
+                .set_scale(Vec3::splat(number(&scale)?));
+            Ok(())
+        },
+    );
+    let s = Rc::clone(&state);
+    rhai.register_fn(
+        "set_camera",
+        move |position: Array, target: Array| -> ScriptResult<()> {
+            let (position, target) = (vec3(&position)?, vec3(&target)?);
+            let direction = (target - position)
+                .try_normalize()
+                .ok_or("The camera target must differ from its position")?;
+            let mut state = s.borrow_mut();
+            let camera = state.app.camera();
+            camera.position = position;
+            camera.look_towards(direction);
+            Ok(())
+        },
+    );
+    let s = Rc::clone(&state);
+    rhai.register_fn("set_fov", move |degrees: Dynamic| -> ScriptResult<()> {
+        let degrees = number(&degrees)?;
+        if !(1. ..=179.).contains(&degrees) {
+            return Err(format!(
+                "Expected a field of view in 1..=179 degrees, got {}",
+                degrees
+            )
+            .into());
+        }
+        s.borrow_mut().app.camera().set_fov(degrees.to_radians());
+        Ok(())
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn("frame", move || {
+        let mut state = s.borrow_mut();
+        let camera = state.app.camera();
+        let (direction, ratio) = (camera.forward(), camera.ratio());
+        state.app.frame_scene(direction, ratio);
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn("set_shading", move |name: &str| -> ScriptResult<()> {
+        let mode: ShadingMode = name.parse()?;
+        s.borrow_mut().app.set_shading_mode(mode);
+        Ok(())
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn("set_matcap", move |name: &str| -> ScriptResult<()> {
+        let matcap: Matcap = name.parse()?;
+        s.borrow_mut().app.set_matcap(matcap);
+        Ok(())
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn(
+        "set_material",
+        move |name: &str, color: Array| -> ScriptResult<INT> {
+            let kd = vec3(&color)?;
+            let changed = s.borrow_mut().app.set_material_color(name, kd)?;
+            Ok(changed as INT)
+        },
+    );
+    let s = Rc::clone(&state);
+    rhai.register_fn("set_light", move |position: Array| -> ScriptResult<()> {
+        s.borrow_mut().app.light().position = vec3(&position)?;
+        Ok(())
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn("set_spin", move |degrees: Dynamic| -> ScriptResult<()> {
+        let speed = number(&degrees)?.to_radians();
+        let mut state = s.borrow_mut();
+        let rotation = state.app.auto_rotation();
+        state.app.set_auto_rotation(AutoRotation {
+            speed,
+            playing: speed != 0.,
+            ..rotation
+        });
+        Ok(())
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn("screenshot", move |path: &str| -> ScriptResult<()> {
+        screenshot(&mut s.borrow_mut().app, path, 800, 600)
+    });
+    let s = Rc::clone(&state);
+    rhai.register_fn(
+        "screenshot",
+        move |path: &str, width: INT, height: INT| -> ScriptResult<()> {
+            let size = |value: INT| {
+                u32::try_from(value)
+                    .ok()
+                    .filter(|&value| value > 0)
+                    .ok_or_else(|| format!("Invalid screenshot size {}", value))
+            };
+            screenshot(&mut s.borrow_mut().app, path, size(width)?, size(height)?)
+        },
+    );
+    let s = Rc::clone(&state);
+    rhai.register_fn("render", move |seconds: Dynamic| -> ScriptResult<()> {
+        s.borrow_mut().render(number(&seconds)?)
+    });
+
+    rhai.run(&source)
+        .map_err(|e| format!("Script '{}' failed: {}", path, e))
+}
+
+fn screenshot(app: &mut App, path: &str, width: u32, height: u32) -> ScriptResult<()> {
+    let image = app.capture(width, height)?;
+    image
+        .save(path)
+        .map_err(|e| format!("Failed to save '{}': {}", path, e))?;
+    println!("Wrote {}", path);
+    Ok(())
+}
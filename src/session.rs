@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+
+use crate::math::Vec3;
+use crate::pose::{json_string, Json, ViewPose};
+use crate::renderer::{Matcap, ShadingMode};
+use crate::scene::{AutoRotation, Light};
+
+/// Overlays and views toggled from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Toggles {
+    pub hud: bool,
+    pub light_gizmo: bool,
+    pub light_frustum: bool,
+    pub inspect_faces: bool,
+    pub reflection: bool,
+    pub quad_view: bool,
+    pub stereo: bool,
+    pub voxels: bool,
+    pub hulls: bool,
+    pub topology: bool,
+    pub face_sorting: bool,
+}
+
+impl Toggles {
+    /// The toggles with their names in the session file.
+    fn entries(&self) -> [(&'static str, bool); 11] {
+        [
+            ("hud", self.hud),
+            ("light_gizmo", self.light_gizmo),
+            ("light_frustum", self.light_frustum),
+            ("inspect_faces", self.inspect_faces),
+            ("reflection", self.reflection),
+            ("quad_view", self.quad_view),
+            ("stereo", self.stereo),
+            ("voxels", self.voxels),
+            ("hulls", self.hulls),
+            ("topology", self.topology),
+            ("face_sorting", self.face_sorting),
+        ]
+    }
+
+    fn from_value(show: &Json) -> Result<Self, String> {
+        let flag = |name| show.get(name)?.boolean();
+        Ok(Self {
+            hud: flag("hud")?,
+            light_gizmo: flag("light_gizmo")?,
+            light_frustum: flag("light_frustum")?,
+            inspect_faces: flag("inspect_faces")?,
+            reflection: flag("reflection")?,
+            quad_view: flag("quad_view")?,
+            stereo: flag("stereo")?,
+            voxels: flag("voxels")?,
+            hulls: flag("hulls")?,
+            topology: flag("topology")?,
+            face_sorting: flag("face_sorting")?,
+        })
+    }
+}
+
+/// `scop` in the user's state directory: `XDG_STATE_HOME`, else
+/// `~/.local/state`, or `APPDATA` on Windows. None when the platform has no
+/// such directory.
+pub fn state_dir() -> Option<PathBuf> {
+    let state = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(state.join("scop"))
+}
+
+/// Viewer state written on exit and restored at the next launch, so an
+/// inspection resumes where it stopped.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// Model shown, none for a primitive or a diff.
+    pub model: Option<String>,
+    /// Camera and placement of the first model.
+    pub pose: ViewPose,
+    pub shading: ShadingMode,
+    pub matcap: Matcap,
+    pub light: Light,
+    pub auto_rotation: AutoRotation,
+    pub toggles: Toggles,
+}
+
+impl Session {
+    /// Environment variable naming the session file when `--session` is not
+    /// given.
+    pub const ENV: &'static str = "SCOP_SESSION";
+
+    /// Session file used without `--session`: `SCOP_SESSION`, else
+    /// `scop/session.json` in the user's state directory. None when the
+    /// platform has no such directory.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(Self::ENV) {
+            return Some(PathBuf::from(path));
+        }
+        Some(state_dir()?.join("session.json"))
+    }
+
+    /// Reads the session at `path`, none when there is no such file yet.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_json(&text)
+                .map(Some)
+                .map_err(|e| format!("Invalid session '{}': {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!(
+                "Failed to read session '{}': {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    /// Writes the session to `path`, creating its directory.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create '{}': {}", parent.display(), e))?;
+        }
+        std::fs::write(path, self.to_json())
+            .map_err(|e| format!("Failed to write session '{}': {}", path.display(), e))
+    }
+
+    pub fn to_json(&self) -> String {
+        let vec = |v: Vec3| format!("[{}, {}, {}]", v.x, v.y, v.z);
+        let toggles = self
+            .toggles
+            .entries()
+            .iter()
+            .map(|(name, value)| format!("    \"{}\": {}", name, value))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        let (light, rotation) = (&self.light, &self.auto_rotation);
+        format!(
+            "{{\n  \"model\": {},\n  \"view\": {},\n  \"shading\": {},\n  \"matcap\": {},\n  \"light\": {{\n    \"position\": {},\n    \"color\": {},\n    \"intensity\": {}\n  }},\n  \"rotation\": {{\n    \"axis\": {},\n    \"speed\": {},\n    \"playing\": {},\n    \"angle\": {}\n  }},\n  \"show\": {{\n{}\n  }}\n}}\n",
+            self.model.as_deref().map_or("null".to_string(), json_string),
+            self.pose.to_json().replace('\n', "\n  "),
+            json_string(self.shading.name()),
+            json_string(self.matcap.name()),
+            vec(light.position),
+            vec(light.color),
+            light.intensity,
+            vec(rotation.axis),
+            rotation.speed,
+            rotation.playing,
+            rotation.angle,
+            toggles
+        )
+    }
+
+    /// Reads what `to_json` writes. Unknown keys are ignored, and every known
+    /// one is required.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let root = Json::parse_document(text)?;
+        let model = root
+            .find("model")
+            .map(|model| model.string().map(str::to_string))
+            .transpose()?;
+
+        let light = root.get("light")?;
+        let rotation = root.get("rotation")?;
+
+        Ok(Self {
+            model,
+            pose: ViewPose::from_value(root.get("view")?)?,
+            shading: root.get("shading")?.string()?.parse()?,
+            matcap: root.get("matcap")?.string()?.parse()?,
+            light: Light {
+                position: light.get("position")?.vec3()?,
+                color: light.get("color")?.vec3()?,
+                intensity: light.get("intensity")?.number()?,
+            },
+            auto_rotation: AutoRotation {
+                axis: rotation
+                    .get("axis")?
+                    .vec3()?
+                    .try_normalize()
+                    .ok_or("Expected a non-zero rotation axis")?,
+                speed: rotation.get("speed")?.number()?,
+                playing: rotation.get("playing")?.boolean()?,
+                angle: rotation.get("angle")?.number()?,
+            },
+            toggles: Toggles::from_value(root.get("show")?)?,
+        })
+    }
+}